@@ -81,8 +81,9 @@ fetchDurationFromHeaders();
    AUTOPLAY
 =========================== */
 
-// Attempt autoplay when page loads
+// Attempt autoplay when page loads (unless the embed URL had autoplay=0)
 window.addEventListener('load', function() {
+  if (video.getAttribute("data-autoplay") === "0") return;
   // Check if autoplay is allowed by browser policies
   var playPromise = video.play();
   
@@ -598,9 +599,17 @@ if (settingsPanel) {
   }
   var qualitySelect = settingsPanel.querySelector(".settings-quality");
   var codecSelect = settingsPanel.querySelector(".settings-codec");
+  if (qualitySelect) {
+    var initialQuality = /[?&]quality=(\d+)/.exec(video.getAttribute("src") || "");
+    if (initialQuality) {
+      for (var qi = 0; qi < qualitySelect.options.length; qi++) {
+        if (qualitySelect.options[qi].value === initialQuality[1]) qualitySelect.selectedIndex = qi;
+      }
+    }
+  }
   function buildSrcWithParams(baseSrc) {
     if (!baseSrc) return "";
-    var src = baseSrc
+    var src = baseSrc.split("#")[0]
       .replace(/\bquality=[^&]*&?/g, "")
       .replace(/\bcodec=[^&]*&?/g, "")
       .replace(/[&?]$/, "");
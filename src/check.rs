@@ -1,16 +1,412 @@
+use serde::Serialize;
+use sha2::{Digest, Sha256};
 use std::fs;
-use std::io::{self, Write};
 use std::path::Path;
 use std::process::Command;
+use std::sync::OnceLock;
 use tokio::io::AsyncWriteExt;
+use utoipa::ToSchema;
+
+use crate::routes::now_secs;
 
 pub async fn perform_startup_checks() {
     log::info!("Performing startup checks...");
     check_and_generate_config();
     check_and_download_yt_dlp().await;
+    check_ffmpeg_capabilities();
     log::info!("Startup checks completed.");
 }
 
+/// `--check` mode: runs every startup check without side effects (no config generation,
+/// no yt-dlp download prompt), prints a report and returns `true` when everything passed.
+pub async fn run_self_check() -> bool {
+    let mut results: Vec<(&str, bool, String)> = Vec::new();
+
+    let config = match crate::config::Config::from_file("config.yml") {
+        Ok(c) => {
+            results.push(("config.yml", true, "parsed".to_string()));
+            Some(c)
+        }
+        Err(e) => {
+            results.push(("config.yml", false, e.to_string()));
+            None
+        }
+    };
+
+    if let Some(config) = &config {
+        crate::endpoints::init(&config.api.endpoints);
+        crate::http_client::init(&config.api.http, &config.proxy.outbound_url);
+        if let Some(url) = crate::http_client::outbound_proxy() {
+            match reqwest::Proxy::all(url) {
+                Ok(_) => results.push(("outbound proxy", true, url.to_string())),
+                Err(e) => results.push(("outbound proxy", false, format!("{}: {}", url, e))),
+            }
+        }
+        let client = crate::http_client::builder()
+            .timeout(std::time::Duration::from_secs(config.api.request_timeout))
+            .build()
+            .unwrap_or_default();
+
+        let keys = &config.api.keys.active;
+        if keys.is_empty() {
+            results.push(("api keys", false, "no active keys".to_string()));
+        } else {
+            let mut valid = 0;
+            for key in keys {
+                let status = data_api_key_status(&client, key).await;
+                if status.is_usable() {
+                    valid += 1;
+                } else {
+                    results.push((
+                        "api key",
+                        false,
+                        format!("{}: {}", crate::routes::additional::mask_key(key), status.describe()),
+                    ));
+                }
+            }
+            results.push((
+                "api keys",
+                valid == keys.len(),
+                format!("{}/{} active keys valid", valid, keys.len()),
+            ));
+        }
+
+        results.push((
+            "innertube key",
+            config.get_innertube_key().is_some(),
+            if config.get_innertube_key().is_some() { "set" } else { "missing" }.to_string(),
+        ));
+
+        results.push(check_oauth_credentials(&client, config).await);
+
+        let port = config.server.port;
+        match std::net::TcpListener::bind(("0.0.0.0", port)) {
+            Ok(_) => results.push(("port", true, format!("{} is free", port))),
+            Err(e) if config.server.port_fallback => results.push((
+                "port",
+                true,
+                format!("{}: {} (port_fallback is on)", port, e),
+            )),
+            Err(e) => results.push(("port", false, format!("{}: {}", port, e))),
+        }
+    }
+
+    let mut yt_dlp = check_binary("yt-dlp", &crate::routes::video::yt_dlp_binary(), "--version");
+    if let Some(extractor) = config.as_ref().map(|c| &c.video.extractor).filter(|e| e.native) {
+        let known = crate::routes::extractor::client_names();
+        let unknown: Vec<&String> = extractor
+            .clients
+            .iter()
+            .filter(|c| !known.iter().any(|k| k.eq_ignore_ascii_case(c)))
+            .collect();
+        results.push(if unknown.is_empty() {
+            ("extractor", true, format!("native ({})", extractor.clients.join(", ")))
+        } else {
+            ("extractor", false, format!("unknown clients {:?} (known: {})", unknown, known.join(", ")))
+        });
+        // Only needed for what the native extractor can't resolve
+        if !yt_dlp.1 {
+            yt_dlp = ("yt-dlp", true, format!("{} (optional with the native extractor)", yt_dlp.2));
+        }
+    }
+    results.push(yt_dlp);
+    results.push(check_binary("ffmpeg", &crate::routes::video::ffmpeg_binary(), "-version"));
+    let caps = ffmpeg_capabilities();
+    if caps.ffmpeg.is_some() {
+        results.push((
+            "ffprobe",
+            caps.ffprobe.is_some(),
+            caps.ffprobe.clone().unwrap_or_else(|| "not found".to_string()),
+        ));
+        results.push((
+            "ffmpeg encoders",
+            caps.require(&["libx264", "aac"]).is_ok(),
+            caps.summary(),
+        ));
+    }
+
+    println!("Self-check report:");
+    let mut all_ok = true;
+    for (name, ok, detail) in &results {
+        all_ok &= *ok;
+        println!("  [{}] {:<14} {}", if *ok { " OK " } else { "FAIL" }, name, detail);
+    }
+    println!("{}", if all_ok { "All checks passed." } else { "Some checks failed." });
+    all_ok
+}
+
+fn check_binary(name: &'static str, binary: &str, version_arg: &str) -> (&'static str, bool, String) {
+    match Command::new(binary).arg(version_arg).output() {
+        Ok(out) if out.status.success() => {
+            let version = String::from_utf8_lossy(&out.stdout)
+                .lines()
+                .next()
+                .unwrap_or("")
+                .trim()
+                .to_string();
+            (name, true, format!("{} ({})", binary, version))
+        }
+        Ok(out) => (name, false, format!("{} exited with {}", binary, out.status)),
+        Err(e) => (name, false, format!("{}: {}", binary, e)),
+    }
+}
+
+/// Encoders worth reporting at startup; the endpoints ask for exactly what they run.
+const NOTABLE_ENCODERS: [&str; 8] = ["libx264", "aac", "libopus", "mpeg4", "h263", "libopencore_amrnb", "libmp3lame", "mov_text"];
+
+/// What the local ffmpeg build can do, detected once (`ffmpeg -encoders`).
+#[derive(Clone, Default, Serialize)]
+pub struct FfmpegCapabilities {
+    /// Path that answered `-version`; `None` when ffmpeg is missing.
+    pub ffmpeg: Option<String>,
+    pub ffprobe: Option<String>,
+    pub version: String,
+    pub encoders: Vec<String>,
+}
+
+static FFMPEG_CAPABILITIES: OnceLock<FfmpegCapabilities> = OnceLock::new();
+
+impl FfmpegCapabilities {
+    pub fn has_encoder(&self, name: &str) -> bool {
+        self.encoders.iter().any(|e| e == name)
+    }
+
+    /// `Err` naming what is missing when ffmpeg or any of `encoders` is unavailable.
+    pub fn require(&self, encoders: &[&str]) -> Result<(), String> {
+        if self.ffmpeg.is_none() {
+            return Err("ffmpeg is not installed on this instance".to_string());
+        }
+        let missing: Vec<&str> = encoders
+            .iter()
+            .copied()
+            .filter(|e| !self.has_encoder(e))
+            .collect();
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(format!(
+                "ffmpeg on this instance lacks the {} encoder{}",
+                missing.join(", "),
+                if missing.len() > 1 { "s" } else { "" }
+            ))
+        }
+    }
+
+    fn summary(&self) -> String {
+        NOTABLE_ENCODERS
+            .iter()
+            .map(|e| format!("{} {}", e, if self.has_encoder(e) { "yes" } else { "no" }))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// ffprobe from the same directory as ffmpeg, otherwise from PATH.
+fn ffprobe_binary(ffmpeg: &str) -> String {
+    let exe_name = if cfg!(target_os = "windows") { "ffprobe.exe" } else { "ffprobe" };
+    let sibling = Path::new(ffmpeg).with_file_name(exe_name);
+    if Path::new(ffmpeg).parent().is_some_and(|p| !p.as_os_str().is_empty()) && sibling.exists() {
+        return sibling.to_string_lossy().to_string();
+    }
+    exe_name.to_string()
+}
+
+fn first_line(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).lines().next().unwrap_or("").trim().to_string()
+}
+
+/// Encoder names from `ffmpeg -encoders`: rows after the `------` line look like ` V....D libx264  ...`.
+fn parse_encoders(listing: &str) -> Vec<String> {
+    listing
+        .lines()
+        .skip_while(|l| !l.trim_start().starts_with("---"))
+        .skip(1)
+        .filter_map(|l| l.split_whitespace().nth(1))
+        .map(|name| name.to_string())
+        .collect()
+}
+
+fn detect_ffmpeg() -> FfmpegCapabilities {
+    let ffmpeg = crate::routes::video::ffmpeg_binary();
+    let version = match Command::new(&ffmpeg).arg("-version").output() {
+        Ok(out) if out.status.success() => first_line(&out.stdout),
+        _ => return FfmpegCapabilities::default(),
+    };
+    let encoders = Command::new(&ffmpeg)
+        .args(["-hide_banner", "-encoders"])
+        .output()
+        .map(|out| parse_encoders(&String::from_utf8_lossy(&out.stdout)))
+        .unwrap_or_default();
+    let ffprobe = ffprobe_binary(&ffmpeg);
+    let ffprobe = Command::new(&ffprobe)
+        .arg("-version")
+        .output()
+        .ok()
+        .filter(|out| out.status.success())
+        .map(|_| ffprobe);
+    FfmpegCapabilities {
+        ffmpeg: Some(ffmpeg),
+        ffprobe,
+        version,
+        encoders,
+    }
+}
+
+/// The detected capabilities; probes ffmpeg on first use if startup did not.
+pub fn ffmpeg_capabilities() -> &'static FfmpegCapabilities {
+    FFMPEG_CAPABILITIES.get_or_init(detect_ffmpeg)
+}
+
+fn check_ffmpeg_capabilities() {
+    let caps = ffmpeg_capabilities();
+    match &caps.ffmpeg {
+        Some(path) => {
+            log::info!("CHECK: ffmpeg found at {} ({}).", path, caps.version);
+            log::info!("CHECK: ffmpeg encoders: {}.", caps.summary());
+            if caps.ffprobe.is_none() {
+                log::warn!("CHECK: ffprobe not found next to ffmpeg or in PATH.");
+            }
+        }
+        None => log::warn!("CHECK: ffmpeg not found; conversion, clips, subtitles and >360p downloads are disabled."),
+    }
+}
+
+/// What a Data API key is good for, from the error Google returns for it.
+pub(crate) enum KeyStatus {
+    Valid,
+    /// Valid, but today's quota is used up.
+    QuotaExceeded,
+    /// Unknown or deleted key.
+    Invalid,
+    /// Valid key that may not call the Data API from here (IP/referrer/API restrictions,
+    /// API not enabled in the project); carries Google's reason.
+    Restricted(String),
+    Unreachable(String),
+}
+
+impl KeyStatus {
+    pub(crate) fn is_usable(&self) -> bool {
+        matches!(self, KeyStatus::Valid | KeyStatus::QuotaExceeded)
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            KeyStatus::Valid => "valid".to_string(),
+            KeyStatus::QuotaExceeded => "valid, daily quota exceeded".to_string(),
+            KeyStatus::Invalid => "invalid key".to_string(),
+            KeyStatus::Restricted(reason) => format!("restricted ({})", reason),
+            KeyStatus::Unreachable(e) => format!("not checked, Data API unreachable: {}", e),
+        }
+    }
+}
+
+/// The cheapest Data API call there is (videos.list, part=id, 1 unit): every call costs quota,
+/// but this one is enough to tell a working key from a rejected one.
+pub(crate) async fn data_api_key_status(client: &reqwest::Client, key: &str) -> KeyStatus {
+    let key = key.trim();
+    if key.is_empty() {
+        return KeyStatus::Invalid;
+    }
+    let url = crate::endpoints::data_api(&format!("videos?part=id&id=dQw4w9WgXcQ&key={}", key));
+    let resp = match client.get(&url).send().await {
+        Ok(resp) => resp,
+        // The request URL carries the key; keep it out of the message
+        Err(e) => return KeyStatus::Unreachable(e.without_url().to_string()),
+    };
+    let status = resp.status();
+    if status.is_success() {
+        return KeyStatus::Valid;
+    }
+    let body: serde_json::Value = resp.json().await.unwrap_or_default();
+    let error = &body["error"];
+    // `errors[].reason` for the classic ones, `details[].reason` for API key restrictions
+    let reason = error
+        .pointer("/errors/0/reason")
+        .or_else(|| error.pointer("/details/0/reason"))
+        .and_then(|r| r.as_str())
+        .unwrap_or_default()
+        .to_string();
+    let message = error["message"].as_str().unwrap_or_default();
+    match reason.as_str() {
+        "quotaExceeded" | "dailyLimitExceeded" | "rateLimitExceeded" => KeyStatus::QuotaExceeded,
+        "keyInvalid" | "API_KEY_INVALID" => KeyStatus::Invalid,
+        _ if message.contains("API key not valid") => KeyStatus::Invalid,
+        "" => KeyStatus::Restricted(format!("HTTP {}", status.as_u16())),
+        _ => KeyStatus::Restricted(reason),
+    }
+}
+
+/// Startup counterpart of the `--check` credential checks (`api.validate_keys_on_startup`):
+/// logs every active Data API key and the OAuth client that Google rejects, so a bad key shows
+/// up at boot rather than as 403s later. Never stops the server.
+pub async fn validate_credentials(config: &crate::config::Config) {
+    let client = crate::http_client::builder()
+        .timeout(std::time::Duration::from_secs(config.api.request_timeout))
+        .build()
+        .unwrap_or_default();
+
+    let keys = &config.api.keys.active;
+    let mut usable = 0;
+    for key in keys {
+        let status = data_api_key_status(&client, key).await;
+        let masked = crate::routes::additional::mask_key(key);
+        match status {
+            KeyStatus::Valid => usable += 1,
+            KeyStatus::QuotaExceeded => {
+                usable += 1;
+                log::warn!("API key {}: {}", masked, status.describe());
+            }
+            _ => log::warn!("API key {}: {}", masked, status.describe()),
+        }
+    }
+    if !keys.is_empty() {
+        log::info!("API keys: {}/{} active keys usable", usable, keys.len());
+    }
+
+    let oauth = &config.api.oauth;
+    if !oauth.client_id.trim().is_empty() || !oauth.client_secret.trim().is_empty() {
+        let (_, ok, detail) = check_oauth_credentials(&client, config).await;
+        if ok {
+            log::info!("OAuth client: {}", detail);
+        } else {
+            log::warn!("OAuth client {}: {}", oauth.client_id, detail);
+        }
+    }
+}
+
+/// A refresh with a bogus token answers `invalid_grant` for a valid client and `invalid_client` otherwise.
+async fn check_oauth_credentials(
+    client: &reqwest::Client,
+    config: &crate::config::Config,
+) -> (&'static str, bool, String) {
+    let oauth = &config.api.oauth;
+    if oauth.client_id.trim().is_empty() || oauth.client_secret.trim().is_empty() {
+        return ("oauth", false, "client_id/client_secret not set".to_string());
+    }
+    let params = [
+        ("client_id", oauth.client_id.as_str()),
+        ("client_secret", oauth.client_secret.as_str()),
+        ("refresh_token", "self-check"),
+        ("grant_type", "refresh_token"),
+    ];
+    match client
+        .post(crate::endpoints::oauth_token())
+        .form(&params)
+        .send()
+        .await
+    {
+        Ok(resp) => {
+            let body = resp.text().await.unwrap_or_default();
+            if body.contains("invalid_client") || body.contains("unauthorized_client") {
+                ("oauth", false, "credentials rejected by Google".to_string())
+            } else {
+                ("oauth", true, "credentials accepted".to_string())
+            }
+        }
+        Err(e) => ("oauth", false, format!("token endpoint unreachable: {}", e.without_url())),
+    }
+}
+
 fn check_and_generate_config() {
     if !Path::new("config.yml").exists() {
         log::warn!("config.yml not found. Generating default config...");
@@ -19,18 +415,73 @@ fn check_and_generate_config() {
   port: 2823
   main_url: ""
   secret_key: ""
+  admin_key: ""
+  port_fallback: false
+  fallback_ports: []
+  error_language: "en"
+  force_http_urls: false
+  persistent_login:
+    enabled: false
+    max_age_days: 180
+  legacy_tls:
+    enabled: false
+    port: 2824
+    cert_path: ""
+    key_path: ""
+    min_version: "tls1.0"
+    ciphers: "DEFAULT:@SECLEVEL=0"
+  trusted_proxies: ["127.0.0.1", "::1"]
+  limits:
+    client_request_timeout_ms: 5000
+    client_disconnect_timeout_ms: 1000
+    keep_alive_secs: 5
+    max_connections: 25000
+    max_header_bytes: 16384
+    max_body_bytes: 262144
+    auth_body_bytes: 8192
 
 api:
   request_timeout: 30
+  hl: "en"
+  gl: "US"
+  validate_keys_on_startup: true
+  quota:
+    enabled: false
+    daily_units: 1000
+    exempt_ips:
+      - "127.0.0.1"
+      - "::1"
   keys:
     active: []
     disabled: []
+    removed: []
   innertube:
     key: ""
+    extra_keys: []
+    disabled_keys: []
   oauth:
     client_id: ""
     client_secret: ""
     redirect_uri: null
+    token_validation:
+      enabled: false
+      interval_minutes: 360
+  http:
+    http2: true
+    connect_timeout_secs: 10
+    pool_idle_timeout_secs: 90
+    pool_max_idle_per_host: 16
+    googlevideo_pool_max_idle_per_host: 64
+  endpoints:
+    data_api: "https://www.googleapis.com/youtube/v3"
+    data_api_upload: "https://www.googleapis.com/upload/youtube/v3"
+    innertube: "https://www.youtube.com/youtubei/v1"
+    suggest: "https://clients1.google.com/complete/search"
+    oauth_token: "https://oauth2.googleapis.com/token"
+    ryd: "https://returnyoutubedislikeapi.com"
+  ryd:
+    enabled: false
+    cache_minutes: 60
 
 video:
   source: "direct"
@@ -46,17 +497,164 @@ video:
     - "1440"
     - "2160"
   default_count: 50
+  comments_count: 25
+  include_comments: true
+  include_statistics: true
+  ytdlp_resolve_timeout_secs: 45
+  ytdlp_download_timeout_secs: 900
+  ytdlp_max_concurrent: 4
+  ytdlp_max_queue: 16
+  hwaccel: "none"
+  hwaccel_device: "/dev/dri/renderD128"
+  max_conversions: 4
+  mp3_bitrate_kbps: 128
+  extractor:
+    native: true
+    clients: ["ANDROID_VR", "ANDROID", "IOS", "TVHTML5"]
+    prefer_working: true
+  cookies:
+    dir: "cookies"
+    check_interval_minutes: 360
+    test_video_id: "jNQXAC9IVRw"
+  po_token:
+    po_token: ""
+    visitor_data: ""
+    generator_url: ""
+    cache_minutes: 360
+  ytdlp_update:
+    enabled: true
+    interval_hours: 24
+    auto_install: true
+  loudnorm:
+    enabled: false
+    target_lufs: -16.0
+    true_peak: -1.5
+    lra: 11.0
+  device_profiles:
+    enabled: true
+    custom: []
 
 proxy:
   thumbnails:
     video: true
     channel: false
     fetch_channel_thumbnails: false
+    webp_to_jpeg: true
   video_proxy: true
+  thumbnail_hosts:
+    - "i.ytimg.com"
+    - "img.youtube.com"
+    - "i1.ytimg.com"
+    - "i9.ytimg.com"
+  host_failure_threshold: 3
+  host_cooldown_secs: 300
+  use_channel_thumbnail_proxy: false
+  image_proxy_hosts:
+    - "yt3.googleusercontent.com"
+    - "yt3.ggpht.com"
+    - "yt4.ggpht.com"
+    - "lh3.googleusercontent.com"
+    - "i.ytimg.com"
+    - "i1.ytimg.com"
+    - "i9.ytimg.com"
+    - "img.youtube.com"
+  signed_urls:
+    enabled: false
+    ttl_secs: 21600
+    trusted_networks:
+      - "127.0.0.0/8"
+      - "::1"
+  hotlink:
+    enabled: false
+    allowed_referers: []
+    allow_empty_referer: true
+  outbound_url: ""
 
 cache:
   temp_folder_max_size_mb: 5120
   cleanup_threshold_mb: 100
+  trending_max_age: 300
+  search_max_age: 120
+  playlist_max_age: 600
+  channel_max_age: 600
+  categories_max_age: 86400
+  segments:
+    enabled: false
+    dir: ""
+    segment_size_kb: 1024
+    max_size_mb: 2048
+    max_age_hours: 24
+  transcodes:
+    enabled: true
+    dir: ""
+    max_size_mb: 1024
+    max_age_hours: 72
+
+systemd:
+  notify: false
+  watchdog: false
+
+library:
+  enabled: false
+  dir: "library"
+  allow_signed_in: false
+
+parental:
+  enabled: false
+  blocked_channels: []
+  blocked_keywords: []
+  blocked_categories: []
+  state_file: "blocklist.json"
+
+profiles:
+  enabled: false
+  db_path: "profiles.db"
+  history_limit: 200
+
+favorites:
+  enabled: false
+  max_items: 500
+
+playback_errors:
+  enabled: false
+  retention_days: 30
+
+audit:
+  enabled: false
+  retention_days: 90
+
+home:
+  shelves:
+    - type: recommendations
+    - type: favorites
+    - type: history
+    - type: pinned_channels
+      audience: signed_out
+    - type: trending
+      count: 24
+      audience: signed_in
+  pinned_channels: []
+  pinned_per_channel: 6
+  pinned_refresh_minutes: 30
+
+crawlers:
+  allow: true
+  sitemap: false
+  sitemap_trending_count: 50
+
+upload:
+  enabled: false
+  max_chunk_bytes: 8388608
+  max_file_bytes: 0
+  session_ttl_hours: 24
+
+usage:
+  enabled: false
+  daily_cap_mb: 0
+  client_caps_mb: {}
+  history_days: 7
+  public: false
+  exempt_ips: ["127.0.0.1", "::1"]
 
 instances:
   - "https://yt.legacyprojects.ru"
@@ -85,73 +683,262 @@ async fn check_and_download_yt_dlp() {
         return;
     }
 
-    log::error!("CHECK: yt-dlp not found!");
-    log::info!("Would you like to download the latest version of yt-dlp from GitHub? (y/n): ");
-    io::stdout().flush().unwrap();
-
-    let mut input = String::new();
-    io::stdin()
-        .read_line(&mut input)
-        .expect("Failed to read input");
+    log::warn!("CHECK: yt-dlp not found, downloading the latest release...");
+    match startup_client() {
+        Ok(client) => match download_yt_dlp(&client).await {
+            Ok(version) => log::info!("yt-dlp {} downloaded to assets/.", version),
+            Err(e) => log::error!("Failed to download yt-dlp: {}", e),
+        },
+        Err(e) => log::error!("Failed to download yt-dlp: {}", e),
+    }
+}
 
-    if input.trim().to_lowercase() == "y"
-        || input.trim().to_lowercase() == "yes"
-        || input.trim().eq_ignore_ascii_case("д")
-        || input.trim().eq_ignore_ascii_case("да")
-    {
-        log::info!("Downloading latest yt-dlp...");
+/// Runs before main() has read the config and set up the shared clients.
+fn startup_client() -> Result<reqwest::Client, reqwest::Error> {
+    let outbound = crate::config::Config::from_file("config.yml")
+        .map(|c| c.proxy.outbound_url)
+        .unwrap_or_default();
+    let mut builder = reqwest::Client::builder();
+    if !outbound.trim().is_empty() {
+        builder = builder.proxy(reqwest::Proxy::all(outbound.trim())?);
+    }
+    builder.build()
+}
 
-        match download_yt_dlp().await {
-            Ok(_) => {
-                log::info!("yt-dlp downloaded successfully!");
-                log::info!("Please, reopen the server for changes to take effect.");
-                std::process::exit(0);
-            }
-            Err(e) => {
-                log::error!("Failed to download yt-dlp: {}", e);
-                std::process::exit(1);
-            }
+/// Release asset for this platform and the name it is saved under in assets/. The standalone
+/// builds bundle Python; the plain `yt-dlp` zipapp is the fallback and needs python3.
+fn yt_dlp_asset() -> (&'static str, &'static str) {
+    if cfg!(target_os = "windows") {
+        if cfg!(target_arch = "x86") {
+            ("yt-dlp_x86.exe", "yt-dlp.exe")
+        } else {
+            ("yt-dlp.exe", "yt-dlp.exe")
         }
+    } else if cfg!(target_os = "macos") {
+        ("yt-dlp_macos", "yt-dlp")
+    } else if cfg!(target_arch = "x86_64") {
+        ("yt-dlp_linux", "yt-dlp")
+    } else if cfg!(target_arch = "aarch64") {
+        ("yt-dlp_linux_aarch64", "yt-dlp")
+    } else if cfg!(target_arch = "arm") {
+        ("yt-dlp_linux_armv7l", "yt-dlp")
     } else {
-        log::error!("yt-dlp is required to run this server.");
-        std::process::exit(1);
+        ("yt-dlp", "yt-dlp")
     }
 }
 
-async fn download_yt_dlp() -> Result<(), Box<dyn std::error::Error>> {
+/// SHA-256 of `asset` as listed in the release's `SHA2-256SUMS`, lowercase hex.
+async fn yt_dlp_checksum(client: &reqwest::Client, asset: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let url = "https://github.com/yt-dlp/yt-dlp/releases/latest/download/SHA2-256SUMS";
+    let sums = client.get(url).send().await?.error_for_status()?.text().await?;
+    sums.lines()
+        .filter_map(|line| line.split_once(char::is_whitespace))
+        .find(|(_, name)| name.trim().trim_start_matches('*') == asset)
+        .map(|(hash, _)| hash.trim().to_ascii_lowercase())
+        .ok_or_else(|| format!("{} is not listed in SHA2-256SUMS", asset).into())
+}
+
+/// Downloads the latest release into assets/ and returns its version. The download has to
+/// match the release's SHA2-256SUMS before it is written, and is only moved over the old
+/// binary once it runs, so neither a tampered nor a broken download replaces a working yt-dlp.
+async fn download_yt_dlp(client: &reqwest::Client) -> Result<String, Box<dyn std::error::Error>> {
     if !Path::new("assets").exists() {
         fs::create_dir("assets")?;
     }
+    let (asset, binary_name) = yt_dlp_asset();
+    let url = format!("https://github.com/yt-dlp/yt-dlp/releases/latest/download/{}", asset);
 
-    let client = reqwest::Client::new();
-
-    let (url, binary_name) = if cfg!(target_os = "windows") {
-        (
-            "https://github.com/yt-dlp/yt-dlp/releases/latest/download/yt-dlp.exe",
-            "yt-dlp.exe",
-        )
-    } else {
-        (
-            "https://github.com/yt-dlp/yt-dlp/releases/latest/download/yt-dlp_linux",
-            "yt-dlp",
-        )
-    };
-
-    let response = client.get(url).send().await?;
+    let expected = yt_dlp_checksum(client, asset).await?;
+    let response = client.get(&url).send().await?.error_for_status()?;
     let content = response.bytes().await?;
+    let actual: String = Sha256::digest(&content).iter().map(|b| format!("{:02x}", b)).collect();
+    if actual != expected {
+        return Err(format!("{} does not match SHA2-256SUMS (got {}, expected {})", asset, actual, expected).into());
+    }
 
     let file_path = format!("assets/{}", binary_name);
+    let staged_path = format!("{}.download", file_path);
 
-    let mut file = tokio::fs::File::create(&file_path).await?;
+    let mut file = tokio::fs::File::create(&staged_path).await?;
     file.write_all(&content).await?;
+    file.flush().await?;
+    drop(file);
 
     #[cfg(unix)]
     {
         use std::os::unix::fs::PermissionsExt;
-        let mut perms = fs::metadata(&file_path)?.permissions();
+        let mut perms = fs::metadata(&staged_path)?.permissions();
         perms.set_mode(0o755);
-        fs::set_permissions(&file_path, perms)?;
+        fs::set_permissions(&staged_path, perms)?;
     }
 
-    Ok(())
+    let staged = staged_path.clone();
+    let version = tokio::task::spawn_blocking(move || Command::new(&staged).arg("--version").output()).await??;
+    if !version.status.success() {
+        let _ = fs::remove_file(&staged_path);
+        return Err(format!("downloaded {} does not run: {}", asset, first_line(&version.stderr)).into());
+    }
+    fs::rename(&staged_path, &file_path)?;
+    Ok(first_line(&version.stdout))
+}
+
+/// Last look at GitHub's latest yt-dlp release.
+struct ReleaseCheck {
+    latest: Option<String>,
+    checked_at: Option<u64>,
+    error: Option<String>,
+}
+
+static RELEASE_CHECK: std::sync::Mutex<ReleaseCheck> = std::sync::Mutex::new(ReleaseCheck {
+    latest: None,
+    checked_at: None,
+    error: None,
+});
+/// Held while a download is running, so the timer and /admin/ytdlp/update don't race.
+static UPDATING: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+#[derive(Serialize, ToSchema)]
+pub struct YtDlpStatus {
+    /// What resolves run: assets/yt-dlp(.exe) when present, else yt-dlp from PATH.
+    pub binary: String,
+    /// `yt-dlp --version`; `None` when it doesn't run.
+    pub installed: Option<String>,
+    /// Tag of the latest GitHub release, as of `checked_at`.
+    pub latest: Option<String>,
+    pub update_available: bool,
+    /// Unix time of the last release check.
+    pub checked_at: Option<u64>,
+    pub last_error: Option<String>,
+}
+
+/// Release tags and `--version` are both `YYYY.MM.DD[.HHMMSS]`, so they compare as strings.
+fn is_newer(latest: &str, installed: &str) -> bool {
+    latest.trim_start_matches('v') > installed.trim()
+}
+
+fn installed_yt_dlp_version() -> Option<String> {
+    Command::new(crate::routes::video::yt_dlp_binary())
+        .arg("--version")
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| first_line(&o.stdout))
+}
+
+/// Installed and latest known yt-dlp versions; runs `yt-dlp --version`, so call it off the
+/// async workers.
+pub fn yt_dlp_status() -> YtDlpStatus {
+    let installed = installed_yt_dlp_version();
+    let (latest, checked_at, last_error) = RELEASE_CHECK
+        .lock()
+        .map(|c| (c.latest.clone(), c.checked_at, c.error.clone()))
+        .unwrap_or_default();
+    YtDlpStatus {
+        binary: crate::routes::video::yt_dlp_binary(),
+        update_available: matches!((&latest, &installed), (Some(l), Some(i)) if is_newer(l, i))
+            || (latest.is_some() && installed.is_none()),
+        installed,
+        latest,
+        checked_at,
+        last_error,
+    }
+}
+
+/// Whether a release check has been made since start.
+pub fn yt_dlp_release_checked() -> bool {
+    RELEASE_CHECK.lock().map(|c| c.checked_at.is_some()).unwrap_or(false)
+}
+
+/// Asks GitHub for the latest release tag and remembers it for `yt_dlp_status`.
+pub async fn check_yt_dlp_release() -> Result<String, String> {
+    let result = async {
+        let resp = crate::http_client::shared()
+            .get("https://api.github.com/repos/yt-dlp/yt-dlp/releases/latest")
+            .header("User-Agent", "yt-api-legacy")
+            .header("Accept", "application/vnd.github+json")
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if !resp.status().is_success() {
+            return Err(format!("GitHub returned {}", resp.status()));
+        }
+        let release: serde_json::Value = resp.json().await.map_err(|e| e.to_string())?;
+        release
+            .get("tag_name")
+            .and_then(|t| t.as_str())
+            .map(str::to_string)
+            .ok_or_else(|| "release without tag_name".to_string())
+    }
+    .await;
+    if let Ok(mut check) = RELEASE_CHECK.lock() {
+        check.checked_at = Some(now_secs());
+        match &result {
+            Ok(tag) => {
+                check.latest = Some(tag.clone());
+                check.error = None;
+            }
+            Err(e) => check.error = Some(e.clone()),
+        }
+    }
+    result
+}
+
+/// Installs the latest release into assets/ (which `yt_dlp_binary` prefers over PATH) and
+/// returns the version now installed.
+pub async fn update_yt_dlp() -> Result<String, String> {
+    let _updating = UPDATING.lock().await;
+    let result = download_yt_dlp(&crate::http_client::shared()).await.map_err(|e| e.to_string());
+    match &result {
+        Ok(version) => {
+            log::info!("yt-dlp updated to {}", version);
+            if let Ok(mut check) = RELEASE_CHECK.lock() {
+                check.error = None;
+            }
+        }
+        Err(e) => {
+            log::error!("yt-dlp update failed: {}", e);
+            if let Ok(mut check) = RELEASE_CHECK.lock() {
+                check.error = Some(e.clone());
+            }
+        }
+    }
+    result
+}
+
+/// Checks for a new yt-dlp release shortly after start and then every
+/// `video.ytdlp_update.interval_hours`, installing it with `auto_install`. YouTube changes
+/// break old releases within weeks, and nothing else tells the admin resolves are failing.
+pub fn spawn_yt_dlp_updater(config: &crate::config::YtDlpUpdateConfig) {
+    if !config.enabled || config.interval_hours == 0 {
+        return;
+    }
+    let config = config.clone();
+    let period = std::time::Duration::from_secs(u64::from(config.interval_hours) * 3600);
+    tokio::spawn(async move {
+        // Let start-up traffic settle first
+        tokio::time::sleep(std::time::Duration::from_secs(60)).await;
+        loop {
+            match check_yt_dlp_release().await {
+                Ok(latest) => {
+                    let installed = tokio::task::spawn_blocking(installed_yt_dlp_version)
+                        .await
+                        .ok()
+                        .flatten();
+                    let outdated = installed.as_deref().is_none_or(|i| is_newer(&latest, i));
+                    if outdated {
+                        log::info!(
+                            "yt-dlp {} is available (installed: {})",
+                            latest,
+                            installed.as_deref().unwrap_or("none")
+                        );
+                        if config.auto_install {
+                            let _ = update_yt_dlp().await;
+                        }
+                    }
+                }
+                Err(e) => log::warn!("yt-dlp release check failed: {}", e),
+            }
+            tokio::time::sleep(period).await;
+        }
+    });
 }
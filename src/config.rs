@@ -1,5 +1,5 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use utoipa::ToSchema;
@@ -12,6 +12,168 @@ pub struct ServerConfig {
     pub main_url: String,
     #[serde(rename = "secret_key")]
     pub secretkey: String,
+    /// Key for admin endpoints (`X-Admin-Key` header or `admin_key` query); empty disables them.
+    #[serde(default)]
+    pub admin_key: String,
+    /// Try other ports when `port` is already taken instead of exiting.
+    #[serde(default)]
+    pub port_fallback: bool,
+    /// Ports to try (in order) when `port_fallback` is on; empty means port+1, port+2, ...
+    #[serde(default)]
+    pub fallback_ports: Vec<u16>,
+    #[serde(default)]
+    pub limits: ServerLimitsConfig,
+    /// Language of the `error` strings in responses: `en` or `ru`.
+    #[serde(default = "default_error_language")]
+    pub error_language: String,
+    #[serde(default)]
+    pub persistent_login: PersistentLoginConfig,
+    /// Every URL the API hands out (thumbnails, avatars, streams) uses `http://` on this host, and
+    /// Google-hosted ones go through the proxies, for devices that cannot do modern TLS.
+    #[serde(default)]
+    pub force_http_urls: bool,
+    #[serde(default)]
+    pub legacy_tls: LegacyTlsConfig,
+    /// Peers whose `X-Forwarded-For` / `Forwarded` is believed (addresses or CIDR networks);
+    /// everyone else is known by the socket address.
+    #[serde(default = "default_trusted_proxies")]
+    pub trusted_proxies: Vec<String>,
+}
+
+fn default_error_language() -> String {
+    "en".to_string()
+}
+
+fn default_trusted_proxies() -> Vec<String> {
+    vec!["127.0.0.1".to_string(), "::1".to_string()]
+}
+
+/// Frontend logins kept in an encrypted cookie (keyed by `secret_key`) so they survive restarts.
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
+pub struct PersistentLoginConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_persistent_login_max_age_days")]
+    pub max_age_days: u32,
+}
+
+fn default_persistent_login_max_age_days() -> u32 {
+    180
+}
+
+impl Default for PersistentLoginConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_age_days: default_persistent_login_max_age_days(),
+        }
+    }
+}
+
+/// Extra HTTPS port with a relaxed OpenSSL profile for clients that cannot do TLS 1.2.
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
+pub struct LegacyTlsConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_legacy_tls_port")]
+    pub port: u16,
+    /// PEM certificate chain.
+    #[serde(default)]
+    pub cert_path: String,
+    /// PEM private key.
+    #[serde(default)]
+    pub key_path: String,
+    /// Lowest protocol accepted: `tls1.0`, `tls1.1` or `tls1.2`.
+    #[serde(default = "default_legacy_tls_min_version")]
+    pub min_version: String,
+    /// OpenSSL cipher string; `@SECLEVEL=0` is what lets OpenSSL 3 negotiate TLS 1.0/1.1 at all.
+    #[serde(default = "default_legacy_tls_ciphers")]
+    pub ciphers: String,
+}
+
+fn default_legacy_tls_port() -> u16 {
+    2824
+}
+
+fn default_legacy_tls_min_version() -> String {
+    "tls1.0".to_string()
+}
+
+fn default_legacy_tls_ciphers() -> String {
+    "DEFAULT:@SECLEVEL=0".to_string()
+}
+
+impl Default for LegacyTlsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: default_legacy_tls_port(),
+            cert_path: String::new(),
+            key_path: String::new(),
+            min_version: default_legacy_tls_min_version(),
+            ciphers: default_legacy_tls_ciphers(),
+        }
+    }
+}
+
+/// Connection and payload limits, so stalled legacy clients can't hold sockets forever.
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
+pub struct ServerLimitsConfig {
+    /// Time allowed to receive the request head, in milliseconds (0 disables).
+    #[serde(default = "default_client_request_timeout_ms")]
+    pub client_request_timeout_ms: u64,
+    /// Time allowed for a graceful connection shutdown, in milliseconds.
+    #[serde(default = "default_client_disconnect_timeout_ms")]
+    pub client_disconnect_timeout_ms: u64,
+    /// Keep-alive for idle connections, in seconds (0 disables keep-alive).
+    #[serde(default = "default_keep_alive_secs")]
+    pub keep_alive_secs: u64,
+    #[serde(default = "default_max_connections")]
+    pub max_connections: usize,
+    /// Total size of request headers; larger requests get 431.
+    #[serde(default = "default_max_header_bytes")]
+    pub max_header_bytes: usize,
+    #[serde(default = "default_max_body_bytes")]
+    pub max_body_bytes: usize,
+    /// Body limit for the login/token form endpoints.
+    #[serde(default = "default_auth_body_bytes")]
+    pub auth_body_bytes: usize,
+}
+
+fn default_client_request_timeout_ms() -> u64 {
+    5000
+}
+fn default_client_disconnect_timeout_ms() -> u64 {
+    1000
+}
+fn default_keep_alive_secs() -> u64 {
+    5
+}
+fn default_max_connections() -> usize {
+    25000
+}
+fn default_max_header_bytes() -> usize {
+    16 * 1024
+}
+fn default_max_body_bytes() -> usize {
+    256 * 1024
+}
+fn default_auth_body_bytes() -> usize {
+    8 * 1024
+}
+
+impl Default for ServerLimitsConfig {
+    fn default() -> Self {
+        Self {
+            client_request_timeout_ms: default_client_request_timeout_ms(),
+            client_disconnect_timeout_ms: default_client_disconnect_timeout_ms(),
+            keep_alive_secs: default_keep_alive_secs(),
+            max_connections: default_max_connections(),
+            max_header_bytes: default_max_header_bytes(),
+            max_body_bytes: default_max_body_bytes(),
+            auth_body_bytes: default_auth_body_bytes(),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
@@ -20,6 +182,9 @@ pub struct ApiKeysConfig {
     pub active: Vec<String>,
     #[serde(default)]
     pub disabled: Vec<String>,
+    /// Soft-deleted through /admin/keys; never used, but can be restored.
+    #[serde(default)]
+    pub removed: Vec<String>,
 }
 
 impl Default for ApiKeysConfig {
@@ -27,7 +192,34 @@ impl Default for ApiKeysConfig {
         Self {
             active: Vec::new(),
             disabled: Vec::new(),
+            removed: Vec::new(),
+        }
+    }
+}
+
+impl ApiKeysConfig {
+    /// Active keys that are not disabled.
+    fn usable(&self) -> Vec<&str> {
+        let bad: HashSet<&str> = self.disabled.iter().map(|s| s.as_str()).collect();
+        self.active
+            .iter()
+            .map(|s| s.as_str())
+            .filter(|k| !k.is_empty() && !bad.contains(k))
+            .collect()
+    }
+
+    pub fn has_usable(&self) -> bool {
+        !self.usable().is_empty()
+    }
+
+    /// Round-robin over active keys that are not disabled; empty when there are none.
+    pub fn rotated(&self) -> String {
+        let good_keys = self.usable();
+        if good_keys.is_empty() {
+            return String::new();
         }
+        let index = API_KEY_COUNTER.fetch_add(1, Ordering::Relaxed) % good_keys.len();
+        good_keys[index].to_string()
     }
 }
 
@@ -108,6 +300,11 @@ pub struct InnertubeConfig {
     pub user_agent: Option<String>,
     #[serde(default)]
     pub client: Option<InnertubeClientConfig>,
+    /// Fallbacks tried in order when `key` is empty or disabled.
+    #[serde(default)]
+    pub extra_keys: Vec<String>,
+    #[serde(default)]
+    pub disabled_keys: Vec<String>,
 }
 
 impl Default for InnertubeConfig {
@@ -116,10 +313,24 @@ impl Default for InnertubeConfig {
             key: None,
             user_agent: None,
             client: None,
+            extra_keys: Vec::new(),
+            disabled_keys: Vec::new(),
         }
     }
 }
 
+impl InnertubeConfig {
+    /// First configured key that is not disabled.
+    pub fn usable_key(&self) -> Option<String> {
+        self.key
+            .iter()
+            .chain(self.extra_keys.iter())
+            .map(|k| k.trim())
+            .find(|k| !k.is_empty() && !self.disabled_keys.iter().any(|d| d.trim() == *k))
+            .map(|k| k.to_string())
+    }
+}
+
 pub const DEFAULT_INNERTUBE_USER_AGENT: &str =
     "com.google.ios.youtube/19.16.3 (iPhone16,2; U; CPU iOS 18_0 like Mac OS X)";
 
@@ -132,6 +343,8 @@ pub struct OAuthConfig {
     #[serde(rename = "redirect_uri")]
     #[serde(default)]
     pub redirect_uri: Option<String>,
+    #[serde(default)]
+    pub token_validation: TokenValidationConfig,
 }
 
 impl Default for OAuthConfig {
@@ -140,6 +353,29 @@ impl Default for OAuthConfig {
             client_id: String::new(),
             client_secret: String::new(),
             redirect_uri: None,
+            token_validation: TokenValidationConfig::default(),
+        }
+    }
+}
+
+/// Periodic check of stored refresh tokens (browser sessions and tokens.json devices).
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
+pub struct TokenValidationConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_token_validation_interval_minutes")]
+    pub interval_minutes: u32,
+}
+
+fn default_token_validation_interval_minutes() -> u32 {
+    360
+}
+
+impl Default for TokenValidationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_minutes: default_token_validation_interval_minutes(),
         }
     }
 }
@@ -154,6 +390,167 @@ pub struct ApiConfig {
     pub innertube: InnertubeConfig,
     #[serde(default)]
     pub oauth: OAuthConfig,
+    #[serde(default)]
+    pub http: HttpClientConfig,
+    /// Default metadata language (`hl=` overrides per request).
+    #[serde(default = "default_hl")]
+    pub hl: String,
+    /// Default region for trending and categories (`gl=` overrides per request).
+    #[serde(default = "default_gl")]
+    pub gl: String,
+    #[serde(default)]
+    pub quota: QuotaConfig,
+    /// Check every active key and the OAuth client against Google at startup (1 unit per key).
+    #[serde(default = "default_true")]
+    pub validate_keys_on_startup: bool,
+    #[serde(default)]
+    pub endpoints: EndpointsConfig,
+    #[serde(default)]
+    pub ryd: RydConfig,
+}
+
+/// Return YouTube Dislike (`api.endpoints.ryd`): the dislike counts YouTube stopped publishing.
+/// Off by default, since every lookup tells a third party which video is being watched.
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
+pub struct RydConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// How long fetched votes are reused.
+    #[serde(default = "default_ryd_cache_minutes")]
+    pub cache_minutes: u32,
+}
+
+fn default_ryd_cache_minutes() -> u32 {
+    60
+}
+
+impl Default for RydConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            cache_minutes: default_ryd_cache_minutes(),
+        }
+    }
+}
+
+/// Upstream base URLs; point them at a mock or a relay. No trailing slash needed.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, ToSchema)]
+pub struct EndpointsConfig {
+    #[serde(default = "default_data_api_endpoint")]
+    pub data_api: String,
+    #[serde(default = "default_data_api_upload_endpoint")]
+    pub data_api_upload: String,
+    #[serde(default = "default_innertube_endpoint")]
+    pub innertube: String,
+    #[serde(default = "default_suggest_endpoint")]
+    pub suggest: String,
+    #[serde(default = "default_oauth_token_endpoint")]
+    pub oauth_token: String,
+    #[serde(default = "default_ryd_endpoint")]
+    pub ryd: String,
+}
+
+fn default_data_api_endpoint() -> String {
+    "https://www.googleapis.com/youtube/v3".to_string()
+}
+fn default_data_api_upload_endpoint() -> String {
+    "https://www.googleapis.com/upload/youtube/v3".to_string()
+}
+fn default_innertube_endpoint() -> String {
+    "https://www.youtube.com/youtubei/v1".to_string()
+}
+fn default_suggest_endpoint() -> String {
+    "https://clients1.google.com/complete/search".to_string()
+}
+fn default_oauth_token_endpoint() -> String {
+    "https://oauth2.googleapis.com/token".to_string()
+}
+fn default_ryd_endpoint() -> String {
+    "https://returnyoutubedislikeapi.com".to_string()
+}
+
+impl Default for EndpointsConfig {
+    fn default() -> Self {
+        Self {
+            data_api: default_data_api_endpoint(),
+            data_api_upload: default_data_api_upload_endpoint(),
+            innertube: default_innertube_endpoint(),
+            suggest: default_suggest_endpoint(),
+            oauth_token: default_oauth_token_endpoint(),
+            ryd: default_ryd_endpoint(),
+        }
+    }
+}
+
+/// Daily Data API budget per client (OAuth token, else IP), in YouTube quota units.
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
+pub struct QuotaConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_quota_daily_units")]
+    pub daily_units: u32,
+    /// Never limited (the frontend's own requests come from here).
+    #[serde(default = "default_quota_exempt_ips")]
+    pub exempt_ips: Vec<String>,
+}
+
+impl Default for QuotaConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            daily_units: default_quota_daily_units(),
+            exempt_ips: default_quota_exempt_ips(),
+        }
+    }
+}
+
+fn default_quota_daily_units() -> u32 {
+    1000
+}
+
+fn default_quota_exempt_ips() -> Vec<String> {
+    vec!["127.0.0.1".to_string(), "::1".to_string()]
+}
+
+/// Upstream HTTP client tuning (connection pools, HTTP/2).
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
+pub struct HttpClientConfig {
+    #[serde(default = "default_true")]
+    pub http2: bool,
+    #[serde(default = "default_connect_timeout_secs")]
+    pub connect_timeout_secs: u64,
+    #[serde(default = "default_pool_idle_timeout_secs")]
+    pub pool_idle_timeout_secs: u64,
+    #[serde(default = "default_pool_max_idle_per_host")]
+    pub pool_max_idle_per_host: usize,
+    /// Separate pool for googlevideo.com streaming.
+    #[serde(default = "default_googlevideo_pool_max_idle_per_host")]
+    pub googlevideo_pool_max_idle_per_host: usize,
+}
+
+fn default_connect_timeout_secs() -> u64 {
+    10
+}
+fn default_pool_idle_timeout_secs() -> u64 {
+    90
+}
+fn default_pool_max_idle_per_host() -> usize {
+    16
+}
+fn default_googlevideo_pool_max_idle_per_host() -> usize {
+    64
+}
+
+impl Default for HttpClientConfig {
+    fn default() -> Self {
+        Self {
+            http2: true,
+            connect_timeout_secs: default_connect_timeout_secs(),
+            pool_idle_timeout_secs: default_pool_idle_timeout_secs(),
+            pool_max_idle_per_host: default_pool_max_idle_per_host(),
+            googlevideo_pool_max_idle_per_host: default_googlevideo_pool_max_idle_per_host(),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
@@ -168,119 +565,1044 @@ pub struct VideoConfig {
     pub available_qualities: Vec<String>,
     #[serde(default = "default_count")]
     pub default_count: u32,
+    #[serde(default = "default_comments_count")]
+    pub comments_count: u32,
+    #[serde(default = "default_true")]
+    pub include_comments: bool,
+    #[serde(default = "default_true")]
+    pub include_statistics: bool,
+    /// yt-dlp is killed if resolving a stream URL takes longer than this.
+    #[serde(default = "default_ytdlp_resolve_timeout_secs")]
+    pub ytdlp_resolve_timeout_secs: u64,
+    /// Same for full downloads (>360p muxing, library saves).
+    #[serde(default = "default_ytdlp_download_timeout_secs")]
+    pub ytdlp_download_timeout_secs: u64,
+    /// Hardware acceleration for codec= conversions: none, vaapi, nvenc, qsv or v4l2m2m.
+    #[serde(default = "default_hwaccel")]
+    pub hwaccel: String,
+    /// Render node used by vaapi (and qsv on Linux).
+    #[serde(default = "default_hwaccel_device")]
+    pub hwaccel_device: String,
+    /// yt-dlp processes allowed to run at once; further resolves wait for a slot.
+    #[serde(default = "default_ytdlp_max_concurrent")]
+    pub ytdlp_max_concurrent: u32,
+    /// Resolves allowed to wait for a slot; beyond that they get a 429 (`resolver_busy`).
+    #[serde(default = "default_ytdlp_max_queue")]
+    pub ytdlp_max_queue: u32,
+    /// Conversions allowed to run at once; the rest wait for a slot.
+    #[serde(default = "default_max_conversions")]
+    pub max_conversions: u32,
+    /// Bitrate of `/direct_audio_url?format=mp3`, in kbit/s.
+    #[serde(default = "default_mp3_bitrate_kbps")]
+    pub mp3_bitrate_kbps: u32,
+    #[serde(default)]
+    pub extractor: ExtractorConfig,
+    #[serde(default)]
+    pub cookies: CookiesConfig,
+    #[serde(default)]
+    pub po_token: PoTokenConfig,
+    #[serde(default)]
+    pub ytdlp_update: YtDlpUpdateConfig,
+    #[serde(default)]
+    pub loudnorm: LoudnormConfig,
+    #[serde(default)]
+    pub device_profiles: DeviceProfilesConfig,
 }
 
+/// ffmpeg `loudnorm` on live transcodes (`format=`); see routes::transcode.
 #[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
-pub struct ProxyThumbnailsConfig {
-    pub video: bool,
-    pub channel: bool,
-    #[serde(rename = "fetch_channel_thumbnails")]
-    pub fetch_channel_thumbnails: bool,
+pub struct LoudnormConfig {
+    /// Level `/direct_audio_url?format=` by default; `normalize=` overrides it per request.
+    #[serde(default)]
+    pub enabled: bool,
+    /// Integrated loudness target in LUFS.
+    #[serde(default = "default_loudnorm_target_lufs")]
+    pub target_lufs: f64,
+    /// Maximum true peak in dBTP.
+    #[serde(default = "default_loudnorm_true_peak")]
+    pub true_peak: f64,
+    /// Loudness range target in LU.
+    #[serde(default = "default_loudnorm_lra")]
+    pub lra: f64,
 }
 
-impl Default for ProxyThumbnailsConfig {
+fn default_loudnorm_target_lufs() -> f64 {
+    -16.0
+}
+
+fn default_loudnorm_true_peak() -> f64 {
+    -1.5
+}
+
+fn default_loudnorm_lra() -> f64 {
+    11.0
+}
+
+impl Default for LoudnormConfig {
     fn default() -> Self {
         Self {
-            video: false,
-            channel: false,
-            fetch_channel_thumbnails: false,
+            enabled: false,
+            target_lufs: default_loudnorm_target_lufs(),
+            true_peak: default_loudnorm_true_peak(),
+            lra: default_loudnorm_lra(),
         }
     }
 }
 
+/// Quality limits by User-Agent for requests that don't ask for a quality; see
+/// routes::device_profile.
 #[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
-pub struct ProxyConfig {
-    pub thumbnails: ProxyThumbnailsConfig,
-    #[serde(rename = "video_proxy")]
-    pub video_proxy: bool,
+pub struct DeviceProfilesConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Extra profiles, checked before the built-in ones.
+    #[serde(default)]
+    pub custom: Vec<DeviceProfileConfig>,
 }
 
-impl Default for ProxyConfig {
+impl Default for DeviceProfilesConfig {
     fn default() -> Self {
         Self {
-            thumbnails: ProxyThumbnailsConfig::default(),
-            video_proxy: false,
+            enabled: true,
+            custom: Vec::new(),
         }
     }
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
-pub struct CacheConfig {
-    #[serde(rename = "temp_folder_max_size_mb")]
-    #[serde(default = "temp_folder_max_size_mb")]
-    pub temp_folder_max_size_mb: u32,
-    #[serde(rename = "cleanup_threshold_mb")]
-    #[serde(default = "cleanup_threshold_mb")]
-    pub cleanup_threshold_mb: u32,
+pub struct DeviceProfileConfig {
+    pub name: String,
+    /// User-Agent substrings (case-insensitive); any one of them selects the profile.
+    #[serde(rename = "match")]
+    pub user_agent: Vec<String>,
+    /// Tallest video the device plays.
+    pub max_height: u32,
+    /// Baseline H.264 and AAC only.
+    #[serde(default)]
+    pub baseline: bool,
 }
 
+/// Release checks for the yt-dlp binary; see `check::spawn_yt_dlp_updater`.
 #[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
-#[serde(transparent)]
-pub struct InstantInstance(pub String);
+pub struct YtDlpUpdateConfig {
+    /// Look for new releases on GitHub.
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Hours between checks; 0 turns them off.
+    #[serde(default = "default_ytdlp_update_interval_hours")]
+    pub interval_hours: u32,
+    /// Install a newer release into assets/ on its own; otherwise only log and report it.
+    #[serde(default = "default_true")]
+    pub auto_install: bool,
+}
+
+fn default_ytdlp_update_interval_hours() -> u32 {
+    24
+}
+
+impl Default for YtDlpUpdateConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_hours: default_ytdlp_update_interval_hours(),
+            auto_install: true,
+        }
+    }
+}
 
+/// Proof-of-origin token for yt-dlp and the native extractor; see routes::po_token.
 #[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
-pub struct Config {
-    pub server: ServerConfig,
-    pub api: ApiConfig,
-    pub video: VideoConfig,
-    pub proxy: ProxyConfig,
-    pub cache: CacheConfig,
+pub struct PoTokenConfig {
+    /// A fixed token; takes precedence over `generator_url`.
     #[serde(default)]
-    #[serde(rename = "instances")]
-    pub instants: Vec<InstantInstance>,
+    pub po_token: String,
+    /// Visitor data the token was minted for (sent as `X-Goog-Visitor-Id`).
+    #[serde(default)]
+    pub visitor_data: String,
+    /// Service that mints tokens on POST, e.g. `http://127.0.0.1:4416/get_pot`.
+    #[serde(default)]
+    pub generator_url: String,
+    /// How long a generated token is reused.
+    #[serde(default = "default_po_token_cache_minutes")]
+    pub cache_minutes: u32,
 }
 
-static API_KEY_COUNTER: AtomicUsize = AtomicUsize::new(0);
+fn default_po_token_cache_minutes() -> u32 {
+    360
+}
 
-fn default_port() -> u16 {
-    2823
+impl Default for PoTokenConfig {
+    fn default() -> Self {
+        Self {
+            po_token: String::new(),
+            visitor_data: String::new(),
+            generator_url: String::new(),
+            cache_minutes: default_po_token_cache_minutes(),
+        }
+    }
 }
 
-fn default_main_url() -> String {
-    String::new()
+/// yt-dlp cookie files, used while `use_cookies` is on; see routes::cookies.
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
+pub struct CookiesConfig {
+    /// Every `*.txt` here is a cookie file; /admin/cookies uploads land here too.
+    #[serde(default = "default_cookies_dir")]
+    pub dir: String,
+    /// Minutes between test extractions of every file; 0 disables them.
+    #[serde(default = "default_cookies_check_interval_minutes")]
+    pub check_interval_minutes: u32,
+    /// Video the tests extract; should stay public and unrestricted.
+    #[serde(default = "default_cookies_test_video_id")]
+    pub test_video_id: String,
 }
 
-fn default_request_timeout() -> u64 {
-    30
+fn default_cookies_dir() -> String {
+    "cookies".to_string()
 }
 
-fn default_count() -> u32 {
-    50
+fn default_cookies_check_interval_minutes() -> u32 {
+    360
 }
 
-fn temp_folder_max_size_mb() -> u32 {
-    5120
+fn default_cookies_test_video_id() -> String {
+    "jNQXAC9IVRw".to_string()
 }
 
-fn cleanup_threshold_mb() -> u32 {
-    100
+impl Default for CookiesConfig {
+    fn default() -> Self {
+        Self {
+            dir: default_cookies_dir(),
+            check_interval_minutes: default_cookies_check_interval_minutes(),
+            test_video_id: default_cookies_test_video_id(),
+        }
+    }
 }
 
-fn normalize_url(input: &str) -> String {
-    input.trim().trim_end_matches('/').to_lowercase()
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
+pub struct ExtractorConfig {
+    /// Resolve streams through InnerTube's player endpoint, with yt-dlp only as the fallback.
+    #[serde(default = "default_true")]
+    pub native: bool,
+    /// InnerTube clients tried in order: ANDROID_VR, ANDROID, IOS, TVHTML5.
+    #[serde(default = "default_extractor_clients")]
+    pub clients: Vec<String>,
+    /// Reorder `clients` by their recent success rate per video type.
+    #[serde(default = "default_true")]
+    pub prefer_working: bool,
 }
 
-fn parse_quality_value(value: &str) -> Option<u32> {
-    let digits = value
-        .chars()
-        .filter(|c| c.is_ascii_digit())
-        .collect::<String>();
-    digits.parse::<u32>().ok()
+fn default_extractor_clients() -> Vec<String> {
+    vec![
+        "ANDROID_VR".to_string(),
+        "ANDROID".to_string(),
+        "IOS".to_string(),
+        "TVHTML5".to_string(),
+    ]
 }
 
-fn compare_quality(a: &str, b: &str) -> std::cmp::Ordering {
-    match (parse_quality_value(a), parse_quality_value(b)) {
-        (Some(a_val), Some(b_val)) => a_val.cmp(&b_val),
-        (Some(_), None) => std::cmp::Ordering::Less,
-        (None, Some(_)) => std::cmp::Ordering::Greater,
-        (None, None) => a.cmp(b),
+impl Default for ExtractorConfig {
+    fn default() -> Self {
+        Self {
+            native: true,
+            clients: default_extractor_clients(),
+            prefer_working: true,
+        }
     }
 }
 
-impl Config {
-    pub fn from_file(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
-        let contents = fs::read_to_string(path)?;
-        let config: Config = serde_yaml::from_str(&contents)?;
+fn default_ytdlp_resolve_timeout_secs() -> u64 {
+    45
+}
+
+fn default_ytdlp_download_timeout_secs() -> u64 {
+    900
+}
+
+fn default_ytdlp_max_concurrent() -> u32 {
+    4
+}
+
+fn default_ytdlp_max_queue() -> u32 {
+    16
+}
+
+fn default_hwaccel() -> String {
+    "none".to_string()
+}
+
+fn default_hwaccel_device() -> String {
+    "/dev/dri/renderD128".to_string()
+}
+
+fn default_max_conversions() -> u32 {
+    4
+}
+
+fn default_mp3_bitrate_kbps() -> u32 {
+    128
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
+pub struct ProxyThumbnailsConfig {
+    pub video: bool,
+    pub channel: bool,
+    #[serde(rename = "fetch_channel_thumbnails")]
+    pub fetch_channel_thumbnails: bool,
+    /// /thumbnail re-encodes WebP answers as JPEG for browsers that can't show WebP.
+    #[serde(default = "default_true")]
+    pub webp_to_jpeg: bool,
+}
+
+impl Default for ProxyThumbnailsConfig {
+    fn default() -> Self {
+        Self {
+            video: false,
+            channel: false,
+            fetch_channel_thumbnails: false,
+            webp_to_jpeg: true,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
+pub struct ProxyConfig {
+    pub thumbnails: ProxyThumbnailsConfig,
+    #[serde(rename = "video_proxy")]
+    pub video_proxy: bool,
+    /// Thumbnail hosts tried in order by /thumbnail (some ISPs block i.ytimg.com).
+    #[serde(default = "default_thumbnail_hosts")]
+    pub thumbnail_hosts: Vec<String>,
+    /// Consecutive failures before a host is skipped, and for how long.
+    #[serde(default = "default_host_failure_threshold")]
+    pub host_failure_threshold: u32,
+    #[serde(default = "default_host_cooldown_secs")]
+    pub host_cooldown_secs: u64,
+    /// Serve channel avatars and other direct Google image URLs through /image.proxy.
+    #[serde(default)]
+    pub use_channel_thumbnail_proxy: bool,
+    /// Hosts /image.proxy and /channel_icon are allowed to fetch from.
+    #[serde(default = "default_image_proxy_hosts")]
+    pub image_proxy_hosts: Vec<String>,
+    #[serde(default)]
+    pub signed_urls: SignedUrlsConfig,
+    #[serde(default)]
+    pub hotlink: HotlinkConfig,
+    /// `http://`, `https://` or `socks5://` proxy for every upstream request, yt-dlp included;
+    /// empty connects directly.
+    #[serde(default)]
+    pub outbound_url: String,
+}
+
+/// Referer/session checks on /thumbnail, /channel_icon and /direct_url.
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
+pub struct HotlinkConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Other sites allowed to embed media (a domain covers its subdomains).
+    #[serde(default)]
+    pub allowed_referers: Vec<String>,
+    /// Let requests without a Referer through; most legacy apps send none.
+    #[serde(default = "default_true")]
+    pub allow_empty_referer: bool,
+}
+
+impl Default for HotlinkConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            allowed_referers: Vec::new(),
+            allow_empty_referer: true,
+        }
+    }
+}
+
+/// HMAC-signed, expiring /direct_url and /video.proxy URLs (needs `server.secret_key`).
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
+pub struct SignedUrlsConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// How long a handed-out URL stays valid.
+    #[serde(default = "default_signed_urls_ttl_secs")]
+    pub ttl_secs: u64,
+    /// Addresses or CIDR ranges (by socket address) that may use unsigned URLs.
+    #[serde(default = "default_signed_urls_trusted_networks")]
+    pub trusted_networks: Vec<String>,
+}
+
+fn default_signed_urls_ttl_secs() -> u64 {
+    6 * 3600
+}
+
+fn default_signed_urls_trusted_networks() -> Vec<String> {
+    vec!["127.0.0.0/8".to_string(), "::1".to_string()]
+}
+
+impl Default for SignedUrlsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            ttl_secs: default_signed_urls_ttl_secs(),
+            trusted_networks: default_signed_urls_trusted_networks(),
+        }
+    }
+}
+
+fn default_thumbnail_hosts() -> Vec<String> {
+    vec![
+        "i.ytimg.com".to_string(),
+        "img.youtube.com".to_string(),
+        "i1.ytimg.com".to_string(),
+        "i9.ytimg.com".to_string(),
+    ]
+}
+
+fn default_image_proxy_hosts() -> Vec<String> {
+    [
+        "yt3.googleusercontent.com",
+        "yt3.ggpht.com",
+        "yt4.ggpht.com",
+        "lh3.googleusercontent.com",
+        "i.ytimg.com",
+        "i1.ytimg.com",
+        "i9.ytimg.com",
+        "img.youtube.com",
+    ]
+    .iter()
+    .map(|h| h.to_string())
+    .collect()
+}
+
+fn default_host_failure_threshold() -> u32 {
+    3
+}
+
+fn default_host_cooldown_secs() -> u64 {
+    300
+}
+
+impl Default for ProxyConfig {
+    fn default() -> Self {
+        Self {
+            thumbnails: ProxyThumbnailsConfig::default(),
+            video_proxy: false,
+            thumbnail_hosts: default_thumbnail_hosts(),
+            host_failure_threshold: default_host_failure_threshold(),
+            host_cooldown_secs: default_host_cooldown_secs(),
+            use_channel_thumbnail_proxy: false,
+            image_proxy_hosts: default_image_proxy_hosts(),
+            signed_urls: SignedUrlsConfig::default(),
+            hotlink: HotlinkConfig::default(),
+            outbound_url: String::new(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
+pub struct CacheConfig {
+    #[serde(rename = "temp_folder_max_size_mb")]
+    #[serde(default = "temp_folder_max_size_mb")]
+    pub temp_folder_max_size_mb: u32,
+    #[serde(rename = "cleanup_threshold_mb")]
+    #[serde(default = "cleanup_threshold_mb")]
+    pub cleanup_threshold_mb: u32,
+    /// Cache-Control max-age (seconds) for JSON responses; 0 sends `no-cache`.
+    #[serde(default = "default_trending_max_age")]
+    pub trending_max_age: u32,
+    #[serde(default = "default_search_max_age")]
+    pub search_max_age: u32,
+    #[serde(default = "default_playlist_max_age")]
+    pub playlist_max_age: u32,
+    #[serde(default = "default_channel_max_age")]
+    pub channel_max_age: u32,
+    /// Category lists are also kept in memory this long, per region and language.
+    #[serde(default = "default_categories_max_age")]
+    pub categories_max_age: u32,
+    #[serde(default)]
+    pub segments: SegmentCacheConfig,
+    #[serde(default)]
+    pub transcodes: TranscodeCacheConfig,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
+pub struct SegmentCacheConfig {
+    /// Keep fixed-size byte ranges of proxied streams on disk and serve repeat/seek requests from them.
+    #[serde(default)]
+    pub enabled: bool,
+    /// Empty = `<temp>/yt_api_segment_cache`.
+    #[serde(default)]
+    pub dir: String,
+    #[serde(default = "default_segment_size_kb")]
+    pub segment_size_kb: u32,
+    #[serde(default = "default_segment_max_size_mb")]
+    pub max_size_mb: u32,
+    /// Streams not touched for this long are dropped.
+    #[serde(default = "default_segment_max_age_hours")]
+    pub max_age_hours: u32,
+}
+
+impl Default for SegmentCacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dir: String::new(),
+            segment_size_kb: default_segment_size_kb(),
+            max_size_mb: default_segment_max_size_mb(),
+            max_age_hours: default_segment_max_age_hours(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
+pub struct TranscodeCacheConfig {
+    /// Keep finished codec= conversions on disk and serve repeat requests from them.
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Empty = `<temp>/yt_api_transcode_cache`.
+    #[serde(default)]
+    pub dir: String,
+    #[serde(default = "default_transcode_max_size_mb")]
+    pub max_size_mb: u32,
+    /// Files not served for this long are dropped.
+    #[serde(default = "default_transcode_max_age_hours")]
+    pub max_age_hours: u32,
+}
+
+impl Default for TranscodeCacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            dir: String::new(),
+            max_size_mb: default_transcode_max_size_mb(),
+            max_age_hours: default_transcode_max_age_hours(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
+pub struct SystemdConfig {
+    /// Send READY=1 / STOPPING=1 to $NOTIFY_SOCKET (Type=notify units).
+    #[serde(default)]
+    pub notify: bool,
+    /// Ping the watchdog (WatchdogSec=) while /health responds.
+    #[serde(default)]
+    pub watchdog: bool,
+}
+
+impl Default for SystemdConfig {
+    fn default() -> Self {
+        Self {
+            notify: false,
+            watchdog: false,
+        }
+    }
+}
+
+/// Blocklists for kid-focused instances. Channel ids and category ids/names match exactly,
+/// keywords match case-insensitively anywhere in title, author, description or tags.
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
+pub struct ParentalConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub blocked_channels: Vec<String>,
+    #[serde(default)]
+    pub blocked_keywords: Vec<String>,
+    #[serde(default)]
+    pub blocked_categories: Vec<String>,
+    /// Where changes made through /admin/blocklist are saved; when present it replaces the lists above.
+    #[serde(default = "default_blocklist_state_file")]
+    pub state_file: String,
+}
+
+impl Default for ParentalConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            blocked_channels: Vec::new(),
+            blocked_keywords: Vec::new(),
+            blocked_categories: Vec::new(),
+            state_file: default_blocklist_state_file(),
+        }
+    }
+}
+
+/// Local profiles (name + PIN) with their own history, resume positions and blocklist additions.
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
+pub struct ProfilesConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// SQLite database file.
+    #[serde(default = "default_profiles_db_path")]
+    pub db_path: String,
+    /// Watch history entries kept per profile.
+    #[serde(default = "default_profile_history_limit")]
+    pub history_limit: u32,
+}
+
+impl Default for ProfilesConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            db_path: default_profiles_db_path(),
+            history_limit: default_profile_history_limit(),
+        }
+    }
+}
+
+/// One shelf of the root page, rendered in config order.
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
+pub struct ShelfConfig {
+    /// trending, category, pinned_channels, playlist, recommendations, history or favorites.
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// Heading; empty means the shelf's usual one.
+    #[serde(default)]
+    pub title: String,
+    #[serde(default = "default_shelf_count")]
+    pub count: u32,
+    /// For `category`.
+    #[serde(default)]
+    pub category_id: String,
+    /// For `playlist`.
+    #[serde(default)]
+    pub playlist_id: String,
+    /// all, signed_in (Google session or local profile) or signed_out.
+    #[serde(default = "default_shelf_audience")]
+    pub audience: String,
+    /// For `trending` and `category`: leave out what the viewer watched lately.
+    #[serde(default)]
+    pub hide_watched: bool,
+}
+
+impl ShelfConfig {
+    fn new(kind: &str, audience: &str) -> Self {
+        Self {
+            kind: kind.to_string(),
+            title: String::new(),
+            count: default_shelf_count(),
+            category_id: String::new(),
+            playlist_id: String::new(),
+            audience: audience.to_string(),
+            hide_watched: false,
+        }
+    }
+}
+
+/// Root page layout.
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
+pub struct HomeConfig {
+    #[serde(default = "default_home_shelves")]
+    pub shelves: Vec<ShelfConfig>,
+    /// Channel ids (UC...) for the `pinned_channels` shelf.
+    #[serde(default)]
+    pub pinned_channels: Vec<String>,
+    #[serde(default = "default_pinned_per_channel")]
+    pub pinned_per_channel: u32,
+    #[serde(default = "default_pinned_refresh_minutes")]
+    pub pinned_refresh_minutes: u32,
+}
+
+impl Default for HomeConfig {
+    fn default() -> Self {
+        Self {
+            shelves: default_home_shelves(),
+            pinned_channels: Vec::new(),
+            pinned_per_channel: default_pinned_per_channel(),
+            pinned_refresh_minutes: default_pinned_refresh_minutes(),
+        }
+    }
+}
+
+/// Local "Liked" and "Favorites" lists for devices without a Google login. Kept per profile,
+/// or per browser (`device_id` cookie) when no profile is selected; stored in `profiles.db_path`.
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
+pub struct FavoritesConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Entries kept per list.
+    #[serde(default = "default_favorites_max_items")]
+    pub max_items: u32,
+}
+
+impl Default for FavoritesConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_items: default_favorites_max_items(),
+        }
+    }
+}
+
+/// Client reports from /report_playback_error, summarized in /admin/stats; stored in `profiles.db_path`.
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
+pub struct PlaybackErrorsConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Reports older than this are deleted.
+    #[serde(default = "default_playback_errors_retention_days")]
+    pub retention_days: u32,
+}
+
+impl Default for PlaybackErrorsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            retention_days: default_playback_errors_retention_days(),
+        }
+    }
+}
+
+/// Auth audit log (logins, device links, failed refreshes, /actions/* calls), read through
+/// /admin/audit; stored in `profiles.db_path`.
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
+pub struct AuditConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Entries older than this are deleted.
+    #[serde(default = "default_audit_retention_days")]
+    pub retention_days: u32,
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            retention_days: default_audit_retention_days(),
+        }
+    }
+}
+
+/// Offline library: videos saved to disk and served by /direct_url without going upstream.
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
+pub struct LibraryConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_library_dir")]
+    pub dir: String,
+    /// Let signed-in users (not only the admin key) add videos.
+    #[serde(default)]
+    pub allow_signed_in: bool,
+}
+
+impl Default for LibraryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dir: default_library_dir(),
+            allow_signed_in: false,
+        }
+    }
+}
+
+/// Bytes sent per client and day, with optional caps; see routes::usage.
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
+pub struct UsageConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Cap for every client in MB per day; 0 = none.
+    #[serde(default)]
+    pub daily_cap_mb: u64,
+    /// Caps for single clients, keyed by IP or by the client key /usage shows.
+    #[serde(default)]
+    pub client_caps_mb: HashMap<String, u64>,
+    /// Days kept in memory for /usage.
+    #[serde(default = "default_usage_history_days")]
+    pub history_days: u32,
+    /// /usage lists every client to anyone, not only to the admin.
+    #[serde(default)]
+    pub public: bool,
+    /// Neither metered nor capped (the frontend's own requests come from here).
+    #[serde(default = "default_quota_exempt_ips")]
+    pub exempt_ips: Vec<String>,
+}
+
+fn default_usage_history_days() -> u32 {
+    7
+}
+
+impl Default for UsageConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            daily_cap_mb: 0,
+            client_caps_mb: HashMap::new(),
+            history_days: default_usage_history_days(),
+            public: false,
+            exempt_ips: default_quota_exempt_ips(),
+        }
+    }
+}
+
+/// /upload: resumable uploads to the signed-in user's channel.
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
+pub struct UploadConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Largest body accepted per chunk request. Google wants every chunk but the last to be a
+    /// multiple of 256 KiB.
+    #[serde(default = "default_upload_max_chunk_bytes")]
+    pub max_chunk_bytes: u64,
+    /// Largest file that may be announced (0 = no limit beyond YouTube's).
+    #[serde(default)]
+    pub max_file_bytes: u64,
+    /// Unfinished uploads are forgotten after this long.
+    #[serde(default = "default_upload_session_ttl_hours")]
+    pub session_ttl_hours: u32,
+}
+
+fn default_upload_max_chunk_bytes() -> u64 {
+    8 * 1024 * 1024
+}
+
+fn default_upload_session_ttl_hours() -> u32 {
+    24
+}
+
+impl Default for UploadConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_chunk_bytes: default_upload_max_chunk_bytes(),
+            max_file_bytes: 0,
+            session_ttl_hours: default_upload_session_ttl_hours(),
+        }
+    }
+}
+
+impl UploadConfig {
+    pub fn session_ttl(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(u64::from(self.session_ttl_hours.max(1)) * 3600)
+    }
+}
+
+/// /robots.txt and /sitemap.xml.
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
+pub struct CrawlersConfig {
+    /// false disallows all crawling, for private instances.
+    #[serde(default = "default_true")]
+    pub allow: bool,
+    /// Serve /sitemap.xml with the home page, trending and pinned channel uploads.
+    #[serde(default)]
+    pub sitemap: bool,
+    /// Trending videos listed in the sitemap.
+    #[serde(default = "default_sitemap_trending_count")]
+    pub sitemap_trending_count: u32,
+}
+
+impl Default for CrawlersConfig {
+    fn default() -> Self {
+        Self {
+            allow: true,
+            sitemap: false,
+            sitemap_trending_count: default_sitemap_trending_count(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
+#[serde(transparent)]
+pub struct InstantInstance(pub String);
+
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub api: ApiConfig,
+    pub video: VideoConfig,
+    pub proxy: ProxyConfig,
+    pub cache: CacheConfig,
+    #[serde(default)]
+    pub systemd: SystemdConfig,
+    #[serde(default)]
+    pub library: LibraryConfig,
+    #[serde(default)]
+    pub parental: ParentalConfig,
+    #[serde(default)]
+    pub profiles: ProfilesConfig,
+    #[serde(default)]
+    pub favorites: FavoritesConfig,
+    #[serde(default)]
+    pub playback_errors: PlaybackErrorsConfig,
+    #[serde(default)]
+    pub audit: AuditConfig,
+    #[serde(default)]
+    pub home: HomeConfig,
+    #[serde(default)]
+    pub crawlers: CrawlersConfig,
+    #[serde(default)]
+    pub upload: UploadConfig,
+    #[serde(default)]
+    pub usage: UsageConfig,
+    #[serde(default)]
+    #[serde(rename = "instances")]
+    pub instants: Vec<InstantInstance>,
+}
+
+static API_KEY_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+fn default_port() -> u16 {
+    2823
+}
+
+fn default_main_url() -> String {
+    String::new()
+}
+
+fn default_request_timeout() -> u64 {
+    30
+}
+
+fn default_hl() -> String {
+    "en".to_string()
+}
+
+fn default_gl() -> String {
+    "US".to_string()
+}
+
+fn default_count() -> u32 {
+    50
+}
+
+fn default_comments_count() -> u32 {
+    25
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn temp_folder_max_size_mb() -> u32 {
+    5120
+}
+
+fn cleanup_threshold_mb() -> u32 {
+    100
+}
+
+fn default_trending_max_age() -> u32 {
+    300
+}
+
+fn default_search_max_age() -> u32 {
+    120
+}
+
+fn default_playlist_max_age() -> u32 {
+    600
+}
+
+fn default_channel_max_age() -> u32 {
+    600
+}
+
+fn default_categories_max_age() -> u32 {
+    86400
+}
+
+fn default_blocklist_state_file() -> String {
+    "blocklist.json".to_string()
+}
+
+fn default_profiles_db_path() -> String {
+    "profiles.db".to_string()
+}
+
+fn default_profile_history_limit() -> u32 {
+    200
+}
+
+fn default_favorites_max_items() -> u32 {
+    500
+}
+
+fn default_playback_errors_retention_days() -> u32 {
+    30
+}
+
+fn default_audit_retention_days() -> u32 {
+    90
+}
+
+fn default_shelf_count() -> u32 {
+    24
+}
+
+fn default_shelf_audience() -> String {
+    "all".to_string()
+}
+
+/// The layout before shelves were configurable.
+fn default_home_shelves() -> Vec<ShelfConfig> {
+    vec![
+        ShelfConfig::new("recommendations", "all"),
+        ShelfConfig::new("favorites", "all"),
+        ShelfConfig::new("history", "all"),
+        ShelfConfig::new("pinned_channels", "signed_out"),
+        ShelfConfig::new("trending", "signed_in"),
+    ]
+}
+
+fn default_pinned_per_channel() -> u32 {
+    6
+}
+
+fn default_pinned_refresh_minutes() -> u32 {
+    30
+}
+
+fn default_sitemap_trending_count() -> u32 {
+    50
+}
+
+fn default_library_dir() -> String {
+    "library".to_string()
+}
+
+fn default_segment_size_kb() -> u32 {
+    1024
+}
+
+fn default_segment_max_size_mb() -> u32 {
+    2048
+}
+
+fn default_segment_max_age_hours() -> u32 {
+    24
+}
+
+fn default_transcode_max_size_mb() -> u32 {
+    1024
+}
+
+fn default_transcode_max_age_hours() -> u32 {
+    72
+}
+
+fn normalize_url(input: &str) -> String {
+    input.trim().trim_end_matches('/').to_lowercase()
+}
+
+fn parse_quality_value(value: &str) -> Option<u32> {
+    let digits = value
+        .chars()
+        .filter(|c| c.is_ascii_digit())
+        .collect::<String>();
+    digits.parse::<u32>().ok()
+}
+
+fn compare_quality(a: &str, b: &str) -> std::cmp::Ordering {
+    match (parse_quality_value(a), parse_quality_value(b)) {
+        (Some(a_val), Some(b_val)) => a_val.cmp(&b_val),
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => a.cmp(b),
+    }
+}
+
+impl Config {
+    pub fn from_file(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let contents = fs::read_to_string(path)?;
+        let config: Config = serde_yaml::from_str(&contents)?;
         Ok(config)
     }
 
@@ -321,6 +1643,17 @@ impl Config {
             .retain(|inst| seen.insert(normalize_url(&inst.0)));
     }
 
+    /// `base` (this instance's URL) with `https://` downgraded when `server.force_http_urls` is on.
+    pub fn public_base(&self, base: String) -> String {
+        if !self.server.force_http_urls {
+            return base;
+        }
+        match base.strip_prefix("https://") {
+            Some(rest) => format!("http://{}", rest),
+            None => base,
+        }
+    }
+
     pub fn persist(&mut self, path: &str) -> Result<(), String> {
         self.tidy();
         serde_yaml::to_string(&self)
@@ -330,37 +1663,21 @@ impl Config {
             })
     }
 
-    pub fn get_api_key_rotated(&self) -> &str {
-        let bad: HashSet<&str> = self.api.keys.disabled.iter().map(|s| s.as_str()).collect();
-        let good_keys: Vec<&str> = self
-            .api
-            .keys
-            .active
-            .iter()
-            .map(|s| s.as_str())
-            .filter(|k| !k.is_empty() && !bad.contains(k))
-            .collect();
+    /// Next Data API key from the live set (/admin/keys), or from this config before startup.
+    pub fn get_api_key_rotated(&self) -> String {
+        crate::routes::api_keys::with_live(|live| live.keys.rotated())
+            .unwrap_or_else(|| self.api.keys.rotated())
+    }
 
-        if good_keys.is_empty() {
-            return self
-                .api
-                .keys
-                .active
-                .first()
-                .map(|s| s.as_str())
-                .unwrap_or("");
-        }
-        let index = API_KEY_COUNTER.fetch_add(1, Ordering::Relaxed) % good_keys.len();
-        good_keys[index]
+    /// Whether the live key set (see `get_api_key_rotated`) has a key to call the Data API with.
+    pub fn has_api_key(&self) -> bool {
+        crate::routes::api_keys::with_live(|live| live.keys.has_usable())
+            .unwrap_or_else(|| self.api.keys.has_usable())
     }
 
-    pub fn get_innertube_key(&self) -> Option<&str> {
-        self.api
-            .innertube
-            .key
-            .as_deref()
-            .map(|k| k.trim())
-            .filter(|k| !k.is_empty())
+    pub fn get_innertube_key(&self) -> Option<String> {
+        crate::routes::api_keys::with_live(|live| live.innertube.usable_key())
+            .unwrap_or_else(|| self.api.innertube.usable_key())
     }
 
     pub fn get_innertube_user_agent(&self) -> String {
@@ -0,0 +1,103 @@
+//! Local SQLite store for per-instance user data (profiles, their history, local favorites),
+//! client playback error reports, the auth audit log and token permission scopes.
+//! A single connection behind a mutex: queries are tiny, so they run inline on the worker.
+
+use rusqlite::Connection;
+use std::sync::{Mutex, OnceLock};
+
+static CONN: OnceLock<Mutex<Connection>> = OnceLock::new();
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS profiles (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL UNIQUE,
+    pin_hash TEXT NOT NULL,
+    created_at INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS profile_sessions (
+    token TEXT PRIMARY KEY,
+    profile_id INTEGER NOT NULL REFERENCES profiles(id) ON DELETE CASCADE,
+    created_at INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS profile_history (
+    profile_id INTEGER NOT NULL REFERENCES profiles(id) ON DELETE CASCADE,
+    video_id TEXT NOT NULL,
+    title TEXT NOT NULL DEFAULT '',
+    author TEXT NOT NULL DEFAULT '',
+    watched_at INTEGER NOT NULL,
+    PRIMARY KEY (profile_id, video_id)
+);
+CREATE TABLE IF NOT EXISTS profile_positions (
+    profile_id INTEGER NOT NULL REFERENCES profiles(id) ON DELETE CASCADE,
+    video_id TEXT NOT NULL,
+    position_secs INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL,
+    PRIMARY KEY (profile_id, video_id)
+);
+CREATE TABLE IF NOT EXISTS profile_blocklist (
+    profile_id INTEGER NOT NULL REFERENCES profiles(id) ON DELETE CASCADE,
+    kind TEXT NOT NULL,
+    value TEXT NOT NULL,
+    PRIMARY KEY (profile_id, kind, value)
+);
+CREATE TABLE IF NOT EXISTS local_lists (
+    owner TEXT NOT NULL,
+    list TEXT NOT NULL,
+    video_id TEXT NOT NULL,
+    title TEXT NOT NULL DEFAULT '',
+    author TEXT NOT NULL DEFAULT '',
+    added_at INTEGER NOT NULL,
+    PRIMARY KEY (owner, list, video_id)
+);
+CREATE TABLE IF NOT EXISTS playback_errors (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    video_id TEXT NOT NULL,
+    code TEXT NOT NULL,
+    stage TEXT NOT NULL,
+    position_secs INTEGER,
+    user_agent TEXT NOT NULL DEFAULT '',
+    reported_at INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS playback_errors_reported_at ON playback_errors (reported_at);
+CREATE TABLE IF NOT EXISTS auth_audit (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    at INTEGER NOT NULL,
+    event TEXT NOT NULL,
+    session TEXT NOT NULL DEFAULT '',
+    ip TEXT NOT NULL DEFAULT '',
+    ok INTEGER NOT NULL,
+    detail TEXT NOT NULL DEFAULT ''
+);
+CREATE INDEX IF NOT EXISTS auth_audit_at ON auth_audit (at);
+CREATE TABLE IF NOT EXISTS token_scopes (
+    token_hash TEXT PRIMARY KEY,
+    allow_actions INTEGER NOT NULL,
+    allow_history_write INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL
+);
+";
+
+/// Opens (or creates) the database and applies the schema. Called once at startup.
+pub fn init(path: &str) -> Result<(), String> {
+    let conn = Connection::open(path).map_err(|e| format!("Failed to open {}: {}", path, e))?;
+    conn.execute_batch("PRAGMA journal_mode = WAL; PRAGMA foreign_keys = ON;")
+        .and_then(|_| conn.execute_batch(SCHEMA))
+        .map_err(|e| format!("Failed to prepare {}: {}", path, e))?;
+    CONN.set(Mutex::new(conn))
+        .map_err(|_| "Database already initialized".to_string())
+}
+
+pub fn is_ready() -> bool {
+    CONN.get().is_some()
+}
+
+/// Runs `f` on the shared connection.
+pub fn with_conn<T>(f: impl FnOnce(&Connection) -> rusqlite::Result<T>) -> Result<T, String> {
+    let conn = CONN.get().ok_or("Database is not initialized")?;
+    let guard = conn.lock().map_err(|_| "Database lock poisoned".to_string())?;
+    f(&guard).map_err(|e| e.to_string())
+}
+
+pub fn now_secs() -> i64 {
+    crate::routes::now_secs() as i64
+}
@@ -0,0 +1,60 @@
+//! Upstream base URLs (`api.endpoints`). Every Data API, InnerTube, suggest and OAuth token call
+//! builds its URL here, so an instance can be pointed at a mock server or a regional relay
+//! without touching the handlers. Thumbnails have their own host list (`proxy.thumbnail_hosts`).
+
+use std::sync::OnceLock;
+
+use crate::config::EndpointsConfig;
+
+static ENDPOINTS: OnceLock<EndpointsConfig> = OnceLock::new();
+
+/// Called once the config is loaded; before that the defaults are used.
+pub fn init(config: &EndpointsConfig) {
+    if ENDPOINTS.set(config.clone()).is_ok() && *config != EndpointsConfig::default() {
+        log::info!(
+            "Upstream endpoints: data_api={} innertube={} suggest={} oauth_token={}",
+            config.data_api,
+            config.innertube,
+            config.suggest,
+            config.oauth_token
+        );
+    }
+}
+
+fn current() -> &'static EndpointsConfig {
+    ENDPOINTS.get_or_init(EndpointsConfig::default)
+}
+
+fn join(base: &str, path: &str) -> String {
+    format!("{}/{}", base.trim_end_matches('/'), path.trim_start_matches('/'))
+}
+
+/// `<api.endpoints.data_api>/<path>`, e.g. `data_api("videos?part=id")`.
+pub fn data_api(path: &str) -> String {
+    join(&current().data_api, path)
+}
+
+/// `<api.endpoints.data_api_upload>/<path>`: media uploads, e.g. `data_api_upload("videos")`.
+pub fn data_api_upload(path: &str) -> String {
+    join(&current().data_api_upload, path)
+}
+
+/// `<api.endpoints.ryd>/<path>`, e.g. `ryd("votes?videoId=...")`.
+pub fn ryd(path: &str) -> String {
+    join(&current().ryd, path)
+}
+
+/// `<api.endpoints.innertube>/<path>`, e.g. `innertube("player?key=...")`.
+pub fn innertube(path: &str) -> String {
+    join(&current().innertube, path)
+}
+
+/// Search suggestions (`complete/search`); the query string is appended by the caller.
+pub fn suggest() -> &'static str {
+    &current().suggest
+}
+
+/// OAuth token endpoint (code exchange, refresh, device flow).
+pub fn oauth_token() -> &'static str {
+    &current().oauth_token
+}
@@ -0,0 +1,169 @@
+//! Shared upstream HTTP clients. A `reqwest::Client` owns its connection pool, so building one
+//! per request meant a fresh TLS handshake for every call — and for every Range request a
+//! seeking player sends to googlevideo. Clients are cheap to clone (Arc inside).
+
+use lazy_static::lazy_static;
+use reqwest::Client;
+use serde_json::Value;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use crate::config::HttpClientConfig;
+use crate::singleflight::SingleFlight;
+
+static SHARED: OnceLock<Client> = OnceLock::new();
+static GOOGLEVIDEO: OnceLock<Client> = OnceLock::new();
+/// `proxy.outbound_url`; `None` when unset.
+static OUTBOUND_PROXY: OnceLock<Option<String>> = OnceLock::new();
+
+lazy_static! {
+    static ref JSON_FLIGHTS: SingleFlight<Result<Value, FetchError>> = SingleFlight::new();
+}
+
+/// Why a coalesced `get_json` failed; kept apart so callers can log and answer as before.
+#[derive(Clone, Debug)]
+pub enum FetchError {
+    /// The request itself failed (connect, TLS, timeout).
+    Request(String),
+    /// The body was not JSON.
+    Parse(String),
+}
+
+impl std::fmt::Display for FetchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FetchError::Request(e) | FetchError::Parse(e) => f.write_str(e),
+        }
+    }
+}
+
+/// The upstream proxy every outbound request goes through, for yt-dlp's `--proxy` and the like.
+pub fn outbound_proxy() -> Option<&'static str> {
+    OUTBOUND_PROXY.get().and_then(|p| p.as_deref())
+}
+
+fn proxy() -> Option<reqwest::Proxy> {
+    let url = outbound_proxy()?;
+    match reqwest::Proxy::all(url) {
+        Ok(proxy) => Some(proxy),
+        Err(e) => {
+            log::error!("proxy.outbound_url '{}' is unusable ({}), connecting directly", url, e);
+            None
+        }
+    }
+}
+
+/// `Client::builder()` with `proxy.outbound_url` applied, for clients that need their own
+/// settings (timeouts, user agent) rather than `shared()`.
+pub fn builder() -> reqwest::ClientBuilder {
+    match proxy() {
+        Some(proxy) => Client::builder().proxy(proxy),
+        None => Client::builder(),
+    }
+}
+
+/// `builder()` for the blocking client ffmpeg feeders use.
+pub fn blocking_builder() -> reqwest::blocking::ClientBuilder {
+    let builder = reqwest::blocking::Client::builder();
+    match proxy() {
+        Some(proxy) => builder.proxy(proxy),
+        None => builder,
+    }
+}
+
+/// ffmpeg options that send its own HTTP inputs through the proxy. ffmpeg only speaks HTTP
+/// proxies; with a SOCKS one its reads go direct.
+pub fn ffmpeg_proxy_args() -> Vec<String> {
+    match outbound_proxy() {
+        Some(url) if url.starts_with("http://") || url.starts_with("https://") => {
+            vec!["-http_proxy".to_string(), url.to_string()]
+        }
+        _ => Vec::new(),
+    }
+}
+
+fn build(config: &HttpClientConfig, max_idle_per_host: usize) -> Client {
+    let mut builder = builder()
+        .connect_timeout(Duration::from_secs(config.connect_timeout_secs))
+        .pool_max_idle_per_host(max_idle_per_host)
+        .pool_idle_timeout(Duration::from_secs(config.pool_idle_timeout_secs))
+        .tcp_keepalive(Duration::from_secs(60));
+    builder = if config.http2 {
+        // h2 is negotiated via ALPN, HTTP/1.1 hosts keep working.
+        builder
+            .http2_adaptive_window(true)
+            .http2_keep_alive_interval(Duration::from_secs(30))
+            .http2_keep_alive_while_idle(true)
+    } else {
+        builder.http1_only()
+    };
+    builder.build().unwrap_or_else(|e| {
+        log::error!("Failed to build HTTP client ({}), using defaults", e);
+        Client::new()
+    })
+}
+
+/// Builds both clients from `api.http`, routed through `proxy.outbound_url` when it is set;
+/// called once at startup.
+pub fn init(config: &HttpClientConfig, outbound_url: &str) {
+    let outbound_url = outbound_url.trim();
+    let _ = OUTBOUND_PROXY.set((!outbound_url.is_empty()).then(|| outbound_url.to_string()));
+    let _ = SHARED.set(build(config, config.pool_max_idle_per_host));
+    let _ = GOOGLEVIDEO.set(build(config, config.googlevideo_pool_max_idle_per_host));
+}
+
+/// Client for YouTube / Google API calls.
+pub fn shared() -> Client {
+    SHARED
+        .get_or_init(|| {
+            let config = HttpClientConfig::default();
+            build(&config, config.pool_max_idle_per_host)
+        })
+        .clone()
+}
+
+/// Dedicated pool for googlevideo.com media streaming (many parallel Range requests per host).
+pub fn googlevideo() -> Client {
+    GOOGLEVIDEO
+        .get_or_init(|| {
+            let config = HttpClientConfig::default();
+            build(&config, config.googlevideo_pool_max_idle_per_host)
+        })
+        .clone()
+}
+
+/// Flight key for a URL: the `key=` parameter is dropped, since rotation hands each caller a
+/// different API key for what is the same request.
+fn flight_key(url: &str) -> String {
+    let Some((base, query)) = url.split_once('?') else {
+        return url.to_string();
+    };
+    let query: Vec<&str> = query.split('&').filter(|p| !p.starts_with("key=")).collect();
+    format!("{}?{}", base, query.join("&"))
+}
+
+/// GET `url` on the shared client and parse the body as JSON. Identical calls already in flight
+/// (e.g. trending for the same region from many clients) are joined rather than repeated.
+/// Only for unauthenticated requests: the response must not depend on who asked.
+pub async fn get_json(url: &str) -> Result<Value, FetchError> {
+    let client = shared();
+    let owned = url.to_string();
+    JSON_FLIGHTS
+        .run(flight_key(url), async move {
+            let response = client
+                .get(&owned)
+                .send()
+                .await
+                .map_err(|e| FetchError::Request(e.to_string()))?;
+            response
+                .json::<Value>()
+                .await
+                .map_err(|e| FetchError::Parse(e.to_string()))
+        })
+        .await
+}
+
+/// Requests served by joining another caller's `get_json`, since start.
+pub fn coalesced() -> u64 {
+    JSON_FLIGHTS.joined()
+}
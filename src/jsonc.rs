@@ -0,0 +1,208 @@
+//! `alt=jsonc` on the native `.php` endpoints: wraps the usual JSON in the old GData
+//! envelope (`{"apiVersion": "2.1", "data": {"items": [...], "startIndex", "itemsPerPage"}}`)
+//! so clients written against GData v2 can read it without a separate code path.
+
+use actix_web::{
+    body::{to_bytes, EitherBody, MessageBody},
+    dev::{Service, ServiceRequest, ServiceResponse, Transform},
+    http::header::{CONTENT_LENGTH, CONTENT_TYPE, ETAG, IF_NONE_MATCH},
+    web, Error, HttpResponse,
+};
+use futures_util::future::LocalBoxFuture;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::future::{ready, Ready};
+use std::task::{Context, Poll};
+
+const API_VERSION: &str = "2.1";
+
+pub struct AltJsonc;
+
+impl<S, B> Transform<S, ServiceRequest> for AltJsonc
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = AltJsoncMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(AltJsoncMiddleware { service }))
+    }
+}
+
+pub struct AltJsoncMiddleware<S> {
+    service: S,
+}
+
+/// Paging hints from the request: GData's `start-index` / `max-results`, or the native `count`.
+struct Paging {
+    start_index: u64,
+    items_per_page: Option<u64>,
+}
+
+impl Paging {
+    fn from_query(query: &HashMap<String, String>) -> Self {
+        let number = |keys: &[&str]| {
+            keys.iter()
+                .find_map(|k| query.get(*k))
+                .and_then(|v| v.parse::<u64>().ok())
+        };
+        Self {
+            start_index: number(&["start-index", "start_index"]).unwrap_or(1).max(1),
+            items_per_page: number(&["max-results", "count"]),
+        }
+    }
+}
+
+/// Picks the list a response is "about": the whole body if it is an array, otherwise the
+/// first array-of-objects field (`videos`, `comments`, ...). The remaining fields stay in `data`.
+fn envelope(body: Value, paging: &Paging) -> Value {
+    let (items, mut data) = match body {
+        Value::Array(items) => (Some(items), Map::new()),
+        Value::Object(mut map) => {
+            let key = map
+                .iter()
+                .find(|(_, v)| {
+                    v.as_array()
+                        .map(|a| a.iter().all(|i| i.is_object()))
+                        .unwrap_or(false)
+                })
+                .map(|(k, _)| k.clone());
+            let items = key
+                .and_then(|k| map.remove(&k))
+                .and_then(|v| match v {
+                    Value::Array(items) => Some(items),
+                    _ => None,
+                });
+            (items, map)
+        }
+        other => return json!({ "apiVersion": API_VERSION, "data": other }),
+    };
+    if let Some(items) = items {
+        let count = items.len() as u64;
+        data.insert("totalItems".into(), json!(count));
+        data.insert("startIndex".into(), json!(paging.start_index));
+        data.insert(
+            "itemsPerPage".into(),
+            json!(paging.items_per_page.unwrap_or(count)),
+        );
+        data.insert("items".into(), Value::Array(items));
+    }
+    json!({ "apiVersion": API_VERSION, "data": data })
+}
+
+/// GData-style error object from our `{"error": "..."}` bodies.
+fn error_envelope(body: Value, code: u16) -> Value {
+    let message = body
+        .get("error")
+        .and_then(|e| e.as_str())
+        .map(|s| s.to_string())
+        .unwrap_or_else(|| body.to_string());
+    json!({
+        "apiVersion": API_VERSION,
+        "error": { "code": code, "message": message }
+    })
+}
+
+impl<S, B> Service<ServiceRequest> for AltJsoncMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let query = web::Query::<HashMap<String, String>>::from_query(req.query_string())
+            .map(|q| q.into_inner())
+            .unwrap_or_default();
+        let wanted = req.path().ends_with(".php")
+            && query.get("alt").map(|a| a == "jsonc").unwrap_or(false);
+        let paging = Paging::from_query(&query);
+        // the tags a client holds for the JSONC variant; the handler only knows the JSON ones
+        let if_none_match = req
+            .headers()
+            .get(IF_NONE_MATCH)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string())
+            .filter(|_| wanted);
+
+        let fut = self.service.call(req);
+        Box::pin(async move {
+            let res = fut.await?;
+            let is_json = res
+                .headers()
+                .get(CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .map(|v| v.starts_with("application/json"))
+                .unwrap_or(false);
+            if !wanted || !is_json {
+                return Ok(res.map_into_left_body());
+            }
+
+            let (req, res) = res.into_parts();
+            let status = res.status();
+            let headers = res.headers().clone();
+            let bytes = to_bytes(res.into_body())
+                .await
+                .map_err(|e| actix_web::error::ErrorInternalServerError(e.into()))?;
+            let body: Value = match serde_json::from_slice(&bytes) {
+                Ok(v) => v,
+                Err(_) => {
+                    let mut passthrough = HttpResponse::build(status);
+                    for (name, value) in headers.iter().filter(|(n, _)| **n != CONTENT_LENGTH) {
+                        passthrough.append_header((name.clone(), value.clone()));
+                    }
+                    let res = passthrough.body(bytes);
+                    return Ok(ServiceResponse::new(req, res).map_into_right_body());
+                }
+            };
+            let wrapped = if status.is_success() {
+                envelope(body, &paging)
+            } else {
+                error_envelope(body, status.as_u16())
+            };
+            let wrapped = serde_json::to_vec(&wrapped)
+                .map_err(|e| actix_web::error::ErrorInternalServerError(e.to_string()))?;
+
+            // the upstream ETag names the plain JSON; the envelope is another entity
+            let etag = headers
+                .contains_key(ETAG)
+                .then(|| crate::routes::http_cache::etag_for(&wrapped));
+            let not_modified = etag
+                .as_deref()
+                .zip(if_none_match.as_deref())
+                .is_some_and(|(etag, inm)| crate::routes::http_cache::etag_listed(inm, etag));
+            let mut builder = HttpResponse::build(if not_modified {
+                actix_web::http::StatusCode::NOT_MODIFIED
+            } else {
+                status
+            });
+            for (name, value) in headers.iter() {
+                if name != CONTENT_LENGTH && name != CONTENT_TYPE && name != ETAG {
+                    builder.append_header((name.clone(), value.clone()));
+                }
+            }
+            if let Some(etag) = etag {
+                builder.insert_header((ETAG, etag));
+            }
+            let res = if not_modified {
+                builder.finish()
+            } else {
+                builder.content_type("application/json").body(wrapped)
+            };
+            Ok(ServiceResponse::new(req, res).map_into_right_body())
+        })
+    }
+}
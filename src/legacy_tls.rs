@@ -0,0 +1,51 @@
+//! Optional second listener (`server.legacy_tls`) speaking HTTPS with a relaxed OpenSSL profile,
+//! so consoles and old phones that stop at TLS 1.0/1.1 and SHA-1/CBC ciphers can still connect.
+//! It serves the same app as the main port; only the handshake settings differ.
+
+use openssl::ssl::{SslAcceptor, SslAcceptorBuilder, SslFiletype, SslMethod, SslVersion};
+
+use crate::config::LegacyTlsConfig;
+
+fn min_version(name: &str) -> Result<SslVersion, String> {
+    match name.to_ascii_lowercase().as_str() {
+        "tls1.0" | "tls1" | "1.0" => Ok(SslVersion::TLS1),
+        "tls1.1" | "1.1" => Ok(SslVersion::TLS1_1),
+        "tls1.2" | "1.2" => Ok(SslVersion::TLS1_2),
+        other => Err(format!(
+            "unknown min_version '{}' (expected tls1.0, tls1.1 or tls1.2)",
+            other
+        )),
+    }
+}
+
+fn acceptor(config: &LegacyTlsConfig) -> Result<SslAcceptorBuilder, String> {
+    if config.cert_path.is_empty() || config.key_path.is_empty() {
+        return Err("cert_path and key_path are required".to_string());
+    }
+    let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::tls_server())
+        .map_err(|e| format!("OpenSSL init failed: {}", e))?;
+    builder
+        .set_min_proto_version(Some(min_version(&config.min_version)?))
+        .map_err(|e| format!("min_version rejected by OpenSSL: {}", e))?;
+    builder
+        .set_cipher_list(&config.ciphers)
+        .map_err(|e| format!("ciphers '{}' rejected by OpenSSL: {}", config.ciphers, e))?;
+    builder
+        .set_certificate_chain_file(&config.cert_path)
+        .map_err(|e| format!("{}: {}", config.cert_path, e))?;
+    builder
+        .set_private_key_file(&config.key_path, SslFiletype::PEM)
+        .map_err(|e| format!("{}: {}", config.key_path, e))?;
+    builder
+        .check_private_key()
+        .map_err(|e| format!("key does not match the certificate: {}", e))?;
+    Ok(builder)
+}
+
+/// The bound port and handshake settings for `HttpServer::listen_openssl`.
+pub fn bind(config: &LegacyTlsConfig) -> Result<(std::net::TcpListener, SslAcceptorBuilder), String> {
+    let builder = acceptor(config)?;
+    let listener = std::net::TcpListener::bind(("0.0.0.0", config.port))
+        .map_err(|e| format!("port {}: {}", config.port, e))?;
+    Ok((listener, builder))
+}
@@ -40,7 +40,41 @@ fn format_log(buf: &mut env_logger::fmt::Formatter, record: &log::Record) -> std
     )
 }
 
-pub use log::info;
+pub use log::{debug, error, info, warn};
+
+/// Query parameters whose values never reach the log: OAuth tokens, API and admin keys,
+/// OAuth codes and URL signatures.
+fn is_secret_param(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    name.contains("token")
+        || name.contains("key")
+        || name.contains("secret")
+        || name.contains("password")
+        || matches!(name.as_str(), "code" | "sig" | "auth")
+}
+
+/// `query` with the values of secret parameters replaced by `***`.
+pub fn redact_query(query: &str) -> String {
+    query
+        .split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some((name, _)) if is_secret_param(&urlencoding::decode(name).unwrap_or_default()) => {
+                format!("{}=***", name)
+            }
+            _ => pair.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// Path and query of a request URL, redacted for logging.
+pub fn redact_target(path: &str, query: &str) -> String {
+    if query.is_empty() {
+        path.to_string()
+    } else {
+        format!("{}?{}", path, redact_query(query))
+    }
+}
 
 #[derive(Default)]
 pub struct SelectiveLogger;
@@ -92,7 +126,7 @@ where
                     "{} {} - {}",
                     status.as_u16(),
                     status.canonical_reason().unwrap_or("Unknown"),
-                    res.request().path()
+                    redact_target(res.request().path(), res.request().query_string())
                 );
             }
 
@@ -100,3 +134,45 @@ where
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacts_secret_params() {
+        assert_eq!(
+            redact_query("video_id=abc&token=1//0gXYZ&key=AIzaSy123&sig=q8Zx&code=4/0Ad&count=5"),
+            "video_id=abc&token=***&key=***&sig=***&code=***&count=5"
+        );
+        assert_eq!(
+            redact_query("refresh_token=x&admin_key=y&client_secret=z&password=p&auth=a"),
+            "refresh_token=***&admin_key=***&client_secret=***&password=***&auth=***"
+        );
+    }
+
+    #[test]
+    fn redacts_percent_encoded_names() {
+        // The name is kept as sent, only the value goes
+        assert_eq!(redact_query("%74oken=secret"), "%74oken=***");
+        assert_eq!(redact_query("%6B%65%79=secret"), "%6B%65%79=***");
+        assert_eq!(redact_query("%73ig=secret&%63ode=secret"), "%73ig=***&%63ode=***");
+        assert_eq!(redact_query("access%5Ftoken=secret"), "access%5Ftoken=***");
+        assert_eq!(redact_query("TOKEN=secret&Sig=secret"), "TOKEN=***&Sig=***");
+    }
+
+    #[test]
+    fn keeps_other_params() {
+        assert_eq!(redact_query("v=dQw4w9WgXcQ&quality=360"), "v=dQw4w9WgXcQ&quality=360");
+        // exact names only for the short ones: `signal` and `codec` are not secrets
+        assert_eq!(redact_query("signal=1&codec=h264"), "signal=1&codec=h264");
+        assert_eq!(redact_query("token"), "token");
+        assert_eq!(redact_query(""), "");
+    }
+
+    #[test]
+    fn redact_target_adds_query_only_when_present() {
+        assert_eq!(redact_target("/get-ytvideo-info.php", ""), "/get-ytvideo-info.php");
+        assert_eq!(redact_target("/direct_url", "video_id=abc&sig=x"), "/direct_url?video_id=abc&sig=***");
+    }
+}
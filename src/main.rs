@@ -1,4 +1,5 @@
 use actix_files as fs;
+use actix_web::dev::Service;
 use actix_web::middleware::{NormalizePath, TrailingSlash};
 use actix_web::{web, App, HttpResponse, HttpServer, Responder};
 use serde::{Deserialize, Serialize};
@@ -6,10 +7,19 @@ use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 
 mod config;
+mod db;
+mod endpoints;
 use config::Config;
 mod check;
+mod http_client;
+mod jsonc;
+#[cfg(feature = "legacy-tls")]
+mod legacy_tls;
+mod limits;
 mod log;
 mod routes;
+mod singleflight;
+mod systemd;
 
 use routes::auth::{AuthConfig, TokenStore};
 
@@ -31,19 +41,28 @@ use routes::auth::{AuthConfig, TokenStore};
         routes::search::get_top_videos,
         routes::search::get_search_videos,
         routes::search::get_search_suggestions,
+        routes::search::complete_search,
         routes::search::get_categories,
         routes::search::get_categories_videos,
         routes::search::get_playlist_videos,
         routes::channel::get_author_videos,
         routes::channel::get_author_videos_by_id,
+        routes::podcast::podcast_feed,
         routes::channel::get_channel_thumbnail_api,
         routes::video::get_ytvideo_info,
         routes::video::get_related_videos,
+        routes::video::get_up_next,
         routes::video::direct_url,
         routes::video::direct_audio_url,
         routes::video::get_direct_video_url,
+        routes::video::get_video_formats,
+        routes::video::get_captions,
         routes::video::hls_manifest_url,
+        routes::video::hls_master,
+        routes::video::hls_media,
+        routes::video::dash_manifest,
         routes::video::video_proxy,
+        routes::video::image_proxy,
         routes::video::download_video,
         routes::additional::get_recommendations,
         routes::additional::get_subscriptions,
@@ -52,11 +71,61 @@ use routes::auth::{AuthConfig, TokenStore};
         routes::additional::get_instants,
         routes::additional::check_api_keys,
         routes::actions::subscribe,
+        routes::actions::subscribe_form,
         routes::actions::unsubscribe,
         routes::actions::rate,
+        routes::actions::rate_comment,
         routes::actions::check_rating,
         routes::actions::check_subscription,
+        routes::actions::get_my_playlists,
+        routes::actions::get_my_videos,
+        routes::actions::update_video_query,
+        routes::actions::update_video_form,
+        routes::actions::set_thumbnail,
+        routes::actions::get_notifications,
+        routes::upload::start_upload,
+        routes::upload::upload_chunk,
+        routes::upload::upload_status,
+        routes::upload::finalize_upload,
+        routes::actions::playlist_add,
+        routes::actions::playlist_add_form,
         routes::additional::check_failed_api_keys,
+        routes::metrics::get_metrics,
+        routes::library::download_to_library,
+        routes::library::list_library,
+        routes::library::library_thumbnail,
+        routes::blocklist::get_blocklist,
+        routes::blocklist::update_blocklist,
+        routes::admin::cache_stats,
+        routes::admin::admin_stats,
+        routes::admin::ytdlp_version,
+        routes::admin::ytdlp_update,
+        routes::usage::get_usage,
+        routes::cookies::get_cookies,
+        routes::cookies::upload_cookies,
+        routes::cookies::delete_cookies,
+        routes::api_keys::get_keys,
+        routes::api_keys::update_keys,
+        routes::playback_errors::report_playback_error,
+        routes::playback_errors::report_playback_error_form,
+        routes::audit::get_audit,
+        routes::token_scopes::get_scope,
+        routes::token_scopes::update_scope,
+        routes::bandwidth::probe,
+        routes::profiles::list_profiles,
+        routes::profiles::create_profile,
+        routes::profiles::select_profile,
+        routes::profiles::leave_profile,
+        routes::profiles::delete_profile,
+        routes::profiles::get_history,
+        routes::profiles::add_history,
+        routes::profiles::get_position,
+        routes::profiles::save_position,
+        routes::profiles::get_profile_blocklist,
+        routes::profiles::update_profile_blocklist,
+        routes::favorites::get_list,
+        routes::favorites::add_to_list,
+        routes::favorites::remove_from_list,
     ),
     components(
         schemas(
@@ -68,6 +137,7 @@ use routes::auth::{AuthConfig, TokenStore};
             routes::auth_routes::OAuth2TokenResponse,
             routes::auth_routes::OAuth2UserInfoResponse,
             routes::search::TopVideo,
+            routes::search::ItemStatistics,
             routes::search::SearchResult,
             routes::search::CategoryItem,
             routes::search::PlaylistInfo,
@@ -76,10 +146,22 @@ use routes::auth::{AuthConfig, TokenStore};
             routes::channel::ChannelInfo,
             routes::channel::ChannelVideo,
             routes::channel::ChannelVideosResponse,
+            routes::envelope::TopVideoList,
+            routes::envelope::SearchResultList,
+            routes::envelope::PlaylistVideoList,
+            routes::envelope::ChannelVideoList,
+            routes::envelope::HistoryItemList,
+            routes::envelope::RecommendationItemList,
             routes::video::VideoInfoResponse,
             routes::video::Comment,
+            routes::video::Chapter,
             routes::video::RelatedVideo,
+            routes::video::UpNextResponse,
             routes::video::DirectUrlResponse,
+            routes::video::VideoFormat,
+            routes::video::VideoFormatsResponse,
+            routes::video::CaptionTrack,
+            routes::video::CaptionTracksResponse,
             routes::video::HlsManifestUrlResponse,
             routes::additional::RecommendationItem,
             routes::additional::HistoryItem,
@@ -87,12 +169,67 @@ use routes::auth::{AuthConfig, TokenStore};
             routes::additional::InstantsResponse,
             routes::actions::YoutubeSubscriptionRequest,
             routes::actions::YoutubeRateRequest,
+            routes::actions::RateCommentRequest,
             routes::actions::YoutubeActionResponse,
             routes::actions::RatingCheckRequest,
             routes::actions::RatingCheckResponse,
             routes::actions::SubscriptionCheckRequest,
             routes::actions::SubscriptionCheckResponse,
+            routes::actions::MyPlaylistsRequest,
+            routes::actions::MyPlaylist,
+            routes::actions::MyPlaylistsResponse,
+            routes::actions::MyVideo,
+            routes::actions::MyVideosResponse,
+            routes::actions::UpdateVideoRequest,
+            routes::actions::SetThumbnailRequest,
+            routes::actions::Notification,
+            routes::actions::NotificationsResponse,
+            routes::upload::UploadStartForm,
+            routes::upload::UploadFinalizeForm,
+            routes::upload::UploadStatusResponse,
+            routes::upload::UploadFinalizeResponse,
+            routes::actions::PlaylistAddRequest,
+            routes::actions::PlaylistAddForm,
+            routes::actions::SubscribeForm,
             routes::additional::InstantItem,
+            routes::metrics::MetricsResponse,
+            routes::metrics::UpstreamErrorCount,
+            routes::metrics::PayloadSizes,
+            routes::circuit::HostHealth,
+            routes::library::LibraryItem,
+            routes::library::LibraryListResponse,
+            routes::blocklist::BlocklistLists,
+            routes::blocklist::BlocklistChange,
+            routes::blocklist::BlocklistResponse,
+            routes::admin::CacheStatsResponse,
+            routes::admin::AdminStatsResponse,
+            check::YtDlpStatus,
+            routes::usage::UsageResponse,
+            routes::usage::UsageDay,
+            routes::usage::ClientUsageEntry,
+            routes::extractor::ClientStats,
+            routes::cookies::CookieFileStatus,
+            routes::api_keys::KeyChange,
+            routes::api_keys::KeysResponse,
+            routes::playback_errors::PlaybackErrorReport,
+            routes::playback_errors::PlaybackErrorSummary,
+            routes::playback_errors::CountEntry,
+            routes::audit::AuditEntry,
+            routes::audit::AuditResponse,
+            routes::token_scopes::ScopeResponse,
+            routes::token_scopes::ScopeChange,
+            routes::transcode_cache::TranscodeCacheStats,
+            routes::segment_cache::SegmentCacheStats,
+            routes::profiles::ProfileInfo,
+            routes::profiles::ProfilesResponse,
+            routes::profiles::ProfileHistoryItem,
+            routes::profiles::ResumePosition,
+            routes::profiles::ProfileForm,
+            routes::profiles::HistoryForm,
+            routes::profiles::PositionForm,
+            routes::favorites::LocalListItem,
+            routes::favorites::LocalListResponse,
+            routes::favorites::LocalListForm,
         )
     ),
     tags(
@@ -117,17 +254,99 @@ struct AppState {
     )
 )]
 async fn health_check() -> impl Responder {
-    log::info!("Health check endpoint called");
+    // debug: the systemd watchdog polls this every few seconds
+    log::debug!("Health check endpoint called");
     HttpResponse::Ok().json("YouTube API Legacy is running!")
 }
 
+/// Binds the configured port, or the first free fallback port when `server.port_fallback` is on.
+fn bind_listener(server: &config::ServerConfig) -> std::io::Result<std::net::TcpListener> {
+    let first_err = match std::net::TcpListener::bind(("0.0.0.0", server.port)) {
+        Ok(listener) => return Ok(listener),
+        Err(e) => e,
+    };
+    if !server.port_fallback {
+        log::error!("Port {} is unavailable: {}", server.port, first_err);
+        return Err(first_err);
+    }
+
+    let candidates: Vec<u16> = if server.fallback_ports.is_empty() {
+        (1..=20).filter_map(|i| server.port.checked_add(i)).collect()
+    } else {
+        server.fallback_ports.clone()
+    };
+    for port in candidates {
+        match std::net::TcpListener::bind(("0.0.0.0", port)) {
+            Ok(listener) => return Ok(listener),
+            Err(e) => log::warn!("Fallback port {} is unavailable: {}", port, e),
+        }
+    }
+    log::error!("No free port found (configured {})", server.port);
+    Err(first_err)
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     log::init_logger();
 
+    if std::env::args().any(|arg| arg == "--check") {
+        let ok = check::run_self_check().await;
+        std::process::exit(if ok { 0 } else { 1 });
+    }
+
     check::perform_startup_checks().await;
+    routes::metrics::init();
+
+    let mut config = Config::from_file("config.yml").expect("Failed to load config.yml");
+    http_client::init(&config.api.http, &config.proxy.outbound_url);
+    endpoints::init(&config.api.endpoints);
+    routes::messages::init(&config.server.error_language);
+    routes::login_cookie::init(&config);
+    routes::signed_urls::init(&config);
+    routes::bandwidth::init(&config);
+    if config.api.validate_keys_on_startup {
+        let config = config.clone();
+        actix_web::rt::spawn(async move { check::validate_credentials(&config).await });
+    }
+    routes::api_keys::init(&config.api);
+    routes::blocklist::init(&config.parental);
+    routes::hwaccel::init(&config.video);
+    routes::audit::init(&config.audit);
+    routes::pinned::spawn_refresh(&config.home);
+    routes::cookies::spawn(&config.video);
+    check::spawn_yt_dlp_updater(&config.video.ytdlp_update);
+    // always opened: an admin can scope a token at any time, and scopes must survive a restart
+    if let Err(e) = db::init(&config.profiles.db_path) {
+        log::error!("Profiles, favorites and token scopes disabled: {}", e);
+    }
+
+    let listener = bind_listener(&config.server)?;
+    let bound_port = listener.local_addr()?.port();
+    if bound_port != config.server.port {
+        log::warn!(
+            "==== Port {} is busy, serving on port {} instead ====",
+            config.server.port,
+            bound_port
+        );
+        config.server.port = bound_port;
+    }
 
-    let config = Config::from_file("config.yml").expect("Failed to load config.yml");
+    #[cfg(feature = "legacy-tls")]
+    let legacy_tls_listener = if config.server.legacy_tls.enabled {
+        match legacy_tls::bind(&config.server.legacy_tls) {
+            Ok(bound) => Some(bound),
+            Err(e) => {
+                log::error!("Legacy TLS listener disabled: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+    #[cfg(not(feature = "legacy-tls"))]
+    if config.server.legacy_tls.enabled {
+        log::warn!("server.legacy_tls is on, but this build has no OpenSSL; rebuild with --features legacy-tls");
+    }
 
     let redirect_base = if let Some(custom) = config.api.oauth.redirect_uri.clone() {
         custom.trim_end_matches('/').to_string()
@@ -139,12 +358,6 @@ async fn main() -> std::io::Result<()> {
         format!("http://localhost:{}", config.server.port)
     };
 
-    let youtube_api_key = if !config.api.keys.active.is_empty() {
-        config.api.keys.active[0].clone()
-    } else {
-        String::new()
-    };
-
     let auth_config = AuthConfig {
         client_id: config.api.oauth.client_id.clone(),
         client_secret: config.api.oauth.client_secret.clone(),
@@ -159,16 +372,24 @@ async fn main() -> std::io::Result<()> {
             "https://www.googleapis.com/auth/userinfo.profile".to_string(),
             "https://www.googleapis.com/auth/userinfo.email".to_string(),
         ],
-        youtube_api_key,
     };
 
     let auth_config_data = web::Data::new(auth_config);
     let token_store_data = web::Data::new(TokenStore::new());
+    routes::token_health::spawn(
+        &config.api.oauth.token_validation,
+        token_store_data.clone(),
+        auth_config_data.clone(),
+    );
 
     let port = config.server.port;
+    let systemd_config = config.systemd.clone();
+    let server_limits = config.server.limits.clone();
     log::info!("Starting YouTube API Legacy server on port {}...", port);
 
-    let codec_semaphore = std::sync::Arc::new(tokio::sync::Semaphore::new(4));
+    let codec_semaphore = std::sync::Arc::new(tokio::sync::Semaphore::new(
+        config.video.max_conversions.max(1) as usize,
+    ));
     let app_state = web::Data::new(AppState {
         config,
         codec_semaphore,
@@ -181,18 +402,90 @@ async fn main() -> std::io::Result<()> {
             .app_data(app_state.clone())
             .app_data(auth_config_data.clone())
             .app_data(token_store_data.clone())
+            .app_data(web::PayloadConfig::new(server_limits.max_body_bytes))
+            .app_data(web::JsonConfig::default().limit(server_limits.max_body_bytes))
+            .wrap(jsonc::AltJsonc)
+            .wrap(routes::usage::UsageMeter)
             .wrap(NormalizePath::new(TrailingSlash::MergeOnly))
+            .wrap(limits::HeaderLimit::new(server_limits.max_header_bytes))
             .wrap(log::SelectiveLogger::default())
+            .wrap_fn(|req, srv| {
+                let pending = routes::audit::begin_action(req.request());
+                let fut = srv.call(req);
+                async move {
+                    let res = fut.await?;
+                    if let Some(pending) = pending {
+                        pending.finish(res.status().as_u16());
+                    }
+                    Ok(res)
+                }
+            })
+            .wrap_fn(|req, srv| {
+                let fut = srv.call(req);
+                async move {
+                    let res = fut.await?;
+                    routes::metrics::record_payload(&res);
+                    Ok(res)
+                }
+            })
             .service(fs::Files::new("/assets", "assets/").show_files_listing())
             .service(SwaggerUi::new("/docs/{_:.*}").url("/openapi.json", openapi.clone()))
             .route("/", web::get().to(routes::frontend::page_root))
+            .route("/robots.txt", web::get().to(routes::crawlers::robots_txt))
+            .route("/sitemap.xml", web::get().to(routes::crawlers::sitemap_xml))
+            .route("/feeds/podcast/{channel}.xml", web::get().to(routes::podcast::podcast_feed))
             .route("/home", web::get().to(routes::frontend::page_index))
             .route("/results", web::get().to(routes::frontend::page_results))
             .route("/watch", web::get().to(routes::frontend::page_watch))
             .route("/channel", web::get().to(routes::frontend::page_channel))
             .route("/logout", web::get().to(routes::frontend::page_logout))
+            .route("/profiles", web::get().to(routes::frontend::page_profiles))
+            .route("/favorites", web::get().to(routes::frontend::page_favorites))
+            .route("/notifications", web::get().to(routes::frontend::page_notifications))
             .route("/embed/{video_id}", web::get().to(routes::frontend::page_embed))
+            .route("/watch_popup", web::get().to(routes::frontend::page_watch_popup))
+            .route("/v/{video_id}", web::get().to(routes::frontend::page_legacy_embed))
+            .route("/e/{video_id}", web::get().to(routes::frontend::page_legacy_embed))
+            .route("/shorts/{video_id}", web::get().to(routes::frontend::page_shorts))
+            .route(
+                "/embed/{video_id}/captions.vtt",
+                web::get().to(routes::frontend::page_embed_captions),
+            )
             .route("/health", web::get().to(health_check))
+            .route("/metrics", web::get().to(routes::metrics::get_metrics))
+            .route("/probe", web::get().to(routes::bandwidth::probe))
+            .route("/usage", web::get().to(routes::usage::get_usage))
+            .service(
+                web::resource("/admin/blocklist")
+                    .route(web::get().to(routes::blocklist::get_blocklist))
+                    .route(web::post().to(routes::blocklist::update_blocklist)),
+            )
+            .route("/admin/cache/stats", web::get().to(routes::admin::cache_stats))
+            .route("/admin/stats", web::get().to(routes::admin::admin_stats))
+            .route("/admin/ytdlp/version", web::get().to(routes::admin::ytdlp_version))
+            .route("/admin/ytdlp/update", web::post().to(routes::admin::ytdlp_update))
+            .service(
+                web::resource("/admin/cookies")
+                    .route(web::get().to(routes::cookies::get_cookies))
+                    .route(web::post().to(routes::cookies::upload_cookies))
+                    .route(web::delete().to(routes::cookies::delete_cookies)),
+            )
+            .route("/admin/audit", web::get().to(routes::audit::get_audit))
+            .service(
+                web::resource("/admin/token_scopes")
+                    .route(web::get().to(routes::token_scopes::get_scope))
+                    .route(web::post().to(routes::token_scopes::update_scope)),
+            )
+            .service(
+                web::resource("/admin/keys")
+                    .route(web::get().to(routes::api_keys::get_keys))
+                    .route(web::post().to(routes::api_keys::update_keys)),
+            )
+            .service(
+                web::resource("/report_playback_error")
+                    .route(web::get().to(routes::playback_errors::report_playback_error))
+                    .route(web::post().to(routes::playback_errors::report_playback_error_form)),
+            )
             .route("/auth", web::get().to(routes::auth::auth_handler))
             .route("/auth/login", web::get().to(routes::frontend::page_login))
             .route("/auth/start", web::get().to(routes::auth::auth_start))
@@ -206,25 +499,30 @@ async fn main() -> std::io::Result<()> {
                 "/check_if_username_is_taken",
                 web::get().to(routes::auth_routes::check_if_username_is_taken),
             )
-            .route(
-                "/link_device_token",
-                web::post().to(routes::auth_routes::link_device_token),
+            .service(
+                web::resource("/link_device_token")
+                    .app_data(web::PayloadConfig::new(server_limits.auth_body_bytes))
+                    .route(web::post().to(routes::auth_routes::link_device_token)),
             )
-            .route(
-                "/get_session",
-                web::post().to(routes::auth_routes::get_session),
+            .service(
+                web::resource("/get_session")
+                    .app_data(web::PayloadConfig::new(server_limits.auth_body_bytes))
+                    .route(web::post().to(routes::auth_routes::get_session)),
             )
-            .route(
-                "/accounts/ClientLogin",
-                web::post().to(routes::auth_routes::client_login),
+            .service(
+                web::resource("/accounts/ClientLogin")
+                    .app_data(web::PayloadConfig::new(server_limits.auth_body_bytes))
+                    .route(web::post().to(routes::auth_routes::client_login)),
             )
-            .route(
-                "/youtube/accounts/ClientLogin",
-                web::post().to(routes::auth_routes::youtube_client_login),
+            .service(
+                web::resource("/youtube/accounts/ClientLogin")
+                    .app_data(web::PayloadConfig::new(server_limits.auth_body_bytes))
+                    .route(web::post().to(routes::auth_routes::youtube_client_login)),
             )
-            .route(
-                "/o/oauth2/token",
-                web::post().to(routes::auth_routes::oauth2_token),
+            .service(
+                web::resource("/o/oauth2/token")
+                    .app_data(web::PayloadConfig::new(server_limits.auth_body_bytes))
+                    .route(web::post().to(routes::auth_routes::oauth2_token)),
             )
             .route(
                 "/oauth2/v1/userinfo",
@@ -242,6 +540,7 @@ async fn main() -> std::io::Result<()> {
                 "/get_search_suggestions.php",
                 web::get().to(routes::search::get_search_suggestions),
             )
+            .route("/complete/search", web::get().to(routes::search::complete_search))
             .route(
                 "/get-categories.php",
                 web::get().to(routes::search::get_categories),
@@ -275,6 +574,7 @@ async fn main() -> std::io::Result<()> {
                 "/get_related_videos.php",
                 web::get().to(routes::video::get_related_videos),
             )
+            .route("/get_up_next.php", web::get().to(routes::video::get_up_next))
             .service(
                 web::resource("/direct_url")
                     .route(web::get().to(routes::video::direct_url))
@@ -289,16 +589,69 @@ async fn main() -> std::io::Result<()> {
                 web::resource("/hls_manifest_url")
                     .route(web::get().to(routes::video::hls_manifest_url)),
             )
+            .route(
+                "/hls/{video_id}/master.m3u8",
+                web::get().to(routes::video::hls_master),
+            )
+            .route(
+                "/hls/{video_id}/{format_id}.m3u8",
+                web::get().to(routes::video::hls_media),
+            )
+            .route(
+                "/dash/{video_id}.mpd",
+                web::get().to(routes::video::dash_manifest),
+            )
             .route(
                 "/get-direct-video-url.php",
                 web::get().to(routes::video::get_direct_video_url),
             )
+            .route(
+                "/get_video_formats.php",
+                web::get().to(routes::video::get_video_formats),
+            )
+            .route(
+                "/get_captions.php",
+                web::get().to(routes::video::get_captions),
+            )
             .service(
                 web::resource("/video.proxy")
                     .route(web::get().to(routes::video::video_proxy))
                     .route(web::head().to(routes::video::video_proxy)),
             )
             .route("/download", web::get().to(routes::video::download_video))
+            .service(
+                web::resource("/library/download")
+                    .route(web::post().to(routes::library::download_to_library))
+                    .route(web::get().to(routes::library::download_to_library)),
+            )
+            .route("/library/list", web::get().to(routes::library::list_library))
+            .route(
+                "/library/thumbnail/{video_id}",
+                web::get().to(routes::library::library_thumbnail),
+            )
+            .route("/profiles/list", web::get().to(routes::profiles::list_profiles))
+            .route("/profiles/create", web::post().to(routes::profiles::create_profile))
+            .route("/profiles/select", web::post().to(routes::profiles::select_profile))
+            .route("/profiles/leave", web::post().to(routes::profiles::leave_profile))
+            .route("/profiles/delete", web::post().to(routes::profiles::delete_profile))
+            .service(
+                web::resource("/profiles/history")
+                    .route(web::get().to(routes::profiles::get_history))
+                    .route(web::post().to(routes::profiles::add_history)),
+            )
+            .service(
+                web::resource("/profiles/position")
+                    .route(web::get().to(routes::profiles::get_position))
+                    .route(web::post().to(routes::profiles::save_position)),
+            )
+            .service(
+                web::resource("/profiles/blocklist")
+                    .route(web::get().to(routes::profiles::get_profile_blocklist))
+                    .route(web::post().to(routes::profiles::update_profile_blocklist)),
+            )
+            .route("/favorites/list", web::get().to(routes::favorites::get_list))
+            .route("/favorites/add", web::post().to(routes::favorites::add_to_list))
+            .route("/favorites/remove", web::post().to(routes::favorites::remove_from_list))
             .route(
                 "/thumbnail/{video_id}",
                 web::get().to(routes::video::thumbnail_proxy),
@@ -307,6 +660,7 @@ async fn main() -> std::io::Result<()> {
                 "/channel_icon/{path_video_id}",
                 web::get().to(routes::video::channel_icon),
             )
+            .route("/image.proxy", web::get().to(routes::video::image_proxy))
             .route(
                 "/get_recommendations.php",
                 web::get().to(routes::additional::get_recommendations),
@@ -347,6 +701,10 @@ async fn main() -> std::io::Result<()> {
                 "/actions/subscribe",
                 web::get().to(routes::actions::subscribe),
             )
+            .route(
+                "/actions/subscribe_form",
+                web::post().to(routes::actions::subscribe_form),
+            )
             .route(
                 "/actions/unsubscribe",
                 web::post().to(routes::actions::unsubscribe),
@@ -357,6 +715,8 @@ async fn main() -> std::io::Result<()> {
             )
             .route("/actions/rate", web::post().to(routes::actions::rate))
             .route("/actions/rate", web::get().to(routes::actions::rate))
+            .route("/actions/rate_comment", web::post().to(routes::actions::rate_comment))
+            .route("/actions/rate_comment", web::get().to(routes::actions::rate_comment))
             .route(
                 "/actions/check_rating",
                 web::get().to(routes::actions::check_rating),
@@ -365,11 +725,90 @@ async fn main() -> std::io::Result<()> {
                 "/actions/check_subscription",
                 web::get().to(routes::actions::check_subscription),
             )
+            .route(
+                "/actions/playlist_add",
+                web::post().to(routes::actions::playlist_add_form),
+            )
+            .route(
+                "/actions/playlist_add",
+                web::get().to(routes::actions::playlist_add),
+            )
+            .route(
+                "/get_my_playlists.php",
+                web::get().to(routes::actions::get_my_playlists),
+            )
+            .route(
+                "/get_my_videos.php",
+                web::get().to(routes::actions::get_my_videos),
+            )
+            .route(
+                "/actions/update_video",
+                web::post().to(routes::actions::update_video_form),
+            )
+            .route(
+                "/actions/update_video",
+                web::get().to(routes::actions::update_video_query),
+            )
+            .route(
+                "/actions/set_thumbnail",
+                web::post().to(routes::actions::set_thumbnail),
+            )
+            .route(
+                "/get_notifications.php",
+                web::get().to(routes::actions::get_notifications),
+            )
+            .route("/upload", web::post().to(routes::upload::start_upload))
+            .service(
+                web::resource("/upload/{upload_id}")
+                    .route(web::get().to(routes::upload::upload_status))
+                    .route(web::put().to(routes::upload::upload_chunk))
+                    .route(web::post().to(routes::upload::upload_chunk)),
+            )
+            .route(
+                "/upload/{upload_id}/finalize",
+                web::post().to(routes::upload::finalize_upload),
+            )
+    })
+    .client_request_timeout(std::time::Duration::from_millis(
+        server_limits.client_request_timeout_ms,
+    ))
+    .client_disconnect_timeout(std::time::Duration::from_millis(
+        server_limits.client_disconnect_timeout_ms,
+    ))
+    .keep_alive(if server_limits.keep_alive_secs == 0 {
+        actix_web::http::KeepAlive::Disabled
+    } else {
+        actix_web::http::KeepAlive::Timeout(std::time::Duration::from_secs(server_limits.keep_alive_secs))
     })
-    .bind(("0.0.0.0", port))?
-    .run();
+    .max_connections(server_limits.max_connections)
+    .listen(listener)?;
+    #[cfg(feature = "legacy-tls")]
+    let server = match legacy_tls_listener {
+        Some((tls_listener, acceptor)) => {
+            let tls_port = tls_listener.local_addr()?.port();
+            let server = server.listen_openssl(tls_listener, acceptor)?;
+            log::info!("Legacy TLS listener at https://127.0.0.1:{}/", tls_port);
+            server
+        }
+        None => server,
+    };
+    let server = server.run();
 
     log::info!("Server running at http://127.0.0.1:{}/", port);
 
-    server.await
+    if systemd_config.notify && systemd::notify("READY=1") {
+        log::info!("Notified systemd: READY=1");
+    }
+    if systemd_config.watchdog {
+        match systemd::watchdog_interval() {
+            Some(interval) => systemd::spawn_watchdog(port, interval),
+            None => log::warn!("systemd.watchdog is on but WATCHDOG_USEC is not set"),
+        }
+    }
+
+    let result = server.await;
+    if systemd_config.notify {
+        systemd::notify("STOPPING=1");
+    }
+    result
 }
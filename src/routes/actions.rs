@@ -1,4 +1,5 @@
-use actix_web::{http::StatusCode as ActixStatusCode, web, HttpResponse, Responder};
+use actix_web::{http::StatusCode as ActixStatusCode, web, HttpRequest, HttpResponse, Responder};
+use futures_util::StreamExt;
 use lazy_static::lazy_static;
 use regex::Regex;
 use reqwest::Client;
@@ -6,8 +7,12 @@ use serde::{Deserialize, Serialize};
 use serde_json::json;
 use utoipa::ToSchema;
 
-use crate::routes::auth::AuthConfig;
+use crate::routes::auth::{AuthConfig, TokenStore};
+use crate::routes::data_api::{self, ApiList, Channel, Playlist, PlaylistItem, Subscription, Video, VideoRating};
+use crate::routes::messages::Msg;
 use crate::routes::oauth::refresh_access_token;
+use crate::routes::quota::{self, READ_COST, WRITE_COST};
+use crate::routes::token_scopes::{self, Permission};
 
 const USER_AGENT: &str =
     "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) \
@@ -28,6 +33,15 @@ pub struct YoutubeSubscriptionRequest {
     pub token: String,
 }
 
+/// Form flavour of `YoutubeSubscriptionRequest` (subscribe buttons in search results); same
+/// token and redirect handling as `PlaylistAddForm`.
+#[derive(Deserialize, ToSchema)]
+pub struct SubscribeForm {
+    pub channel: String,
+    pub token: Option<String>,
+    pub redirect: Option<String>,
+}
+
 #[derive(Deserialize, ToSchema)]
 pub struct YoutubeRateRequest {
     pub video_id: String,
@@ -35,6 +49,15 @@ pub struct YoutubeRateRequest {
     pub token: String,
 }
 
+#[derive(Deserialize, ToSchema)]
+pub struct RateCommentRequest {
+    pub video_id: String,
+    pub comment_id: String,
+    /// `like` or `none`; comments have no public dislike.
+    pub rating: String,
+    pub token: String,
+}
+
 #[derive(Serialize, ToSchema)]
 pub struct YoutubeActionResponse {
     pub status: String,
@@ -70,6 +93,116 @@ pub struct SubscriptionCheckResponse {
     pub subscribed: bool,
 }
 
+#[derive(Deserialize, ToSchema)]
+pub struct MyPlaylistsRequest {
+    pub token: String,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct MyPlaylist {
+    pub playlist_id: String,
+    pub title: String,
+    pub video_count: u64,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct MyPlaylistsResponse {
+    pub playlists: Vec<MyPlaylist>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct MyVideosRequest {
+    pub token: String,
+    /// 1-50, default 20.
+    pub count: Option<u32>,
+    pub page_token: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct MyVideo {
+    pub video_id: String,
+    pub title: String,
+    pub description: String,
+    /// `public`, `unlisted` or `private`.
+    pub privacy: String,
+    /// `uploaded` while YouTube is still processing, then `processed`.
+    pub upload_status: String,
+    pub tags: Vec<String>,
+    pub published_at: String,
+    pub thumbnail: String,
+    pub views: u64,
+    /// ISO 8601, e.g. `PT4M13S`.
+    pub duration: String,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct MyVideosResponse {
+    pub videos: Vec<MyVideo>,
+    pub next_page_token: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct SetThumbnailRequest {
+    pub video_id: String,
+    /// OAuth refresh token; the frontend session is used when omitted.
+    pub token: Option<String>,
+}
+
+/// Fields left out keep their current value; an empty `tags` clears the tags.
+#[derive(Deserialize, ToSchema)]
+pub struct UpdateVideoRequest {
+    pub video_id: String,
+    /// OAuth refresh token; the frontend session is used when omitted.
+    pub token: Option<String>,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    /// public, unlisted or private.
+    pub privacy: Option<String>,
+    /// Comma-separated.
+    pub tags: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct PlaylistAddRequest {
+    pub video_id: String,
+    pub playlist_id: String,
+    pub token: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct NotificationsRequest {
+    pub token: String,
+    /// Notifications to list; 0 only returns `unread_count` (default: 20).
+    pub count: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct Notification {
+    pub message: String,
+    /// Relative, as YouTube words it ("2 hours ago").
+    pub sent: String,
+    pub thumbnail: Option<String>,
+    /// The video it points at; `None` for channel/community notifications.
+    pub video_id: Option<String>,
+    pub read: bool,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct NotificationsResponse {
+    pub unread_count: u32,
+    pub notifications: Vec<Notification>,
+}
+
+/// Form flavour of `PlaylistAddRequest` (watch page): the token may come from the session cookie
+/// and the result is a redirect to `redirect`.
+#[derive(Deserialize, ToSchema)]
+pub struct PlaylistAddForm {
+    pub video_id: String,
+    pub playlist_id: String,
+    pub token: Option<String>,
+    pub redirect: Option<String>,
+}
+
 fn error_json(status: ActixStatusCode, message: impl ToString) -> HttpResponse {
     HttpResponse::build(status).json(json!({ "error": message.to_string() }))
 }
@@ -166,7 +299,7 @@ async fn subscribe_channel_api(
         }
     });
     let resp = client
-        .post("https://www.googleapis.com/youtube/v3/subscriptions?part=snippet")
+        .post(crate::endpoints::data_api("subscriptions?part=snippet"))
         .header("Authorization", format!("Bearer {}", access_token))
         .header("Content-Type", "application/json")
         .header("Accept", "application/json")
@@ -191,7 +324,7 @@ async fn find_subscription_id(
     access_token: &str,
 ) -> Result<Option<String>, String> {
     let resp = client
-        .get("https://www.googleapis.com/youtube/v3/subscriptions")
+        .get(crate::endpoints::data_api("subscriptions"))
         .header("Authorization", format!("Bearer {}", access_token))
         .query(&[
             ("part", "id"),
@@ -209,14 +342,8 @@ async fn find_subscription_id(
         ));
     }
     let json: serde_json::Value = resp.json().await.map_err(|e| e.to_string())?;
-    if let Some(items) = json.get("items").and_then(|i| i.as_array()) {
-        if let Some(item) = items.first() {
-            if let Some(id) = item.get("id").and_then(|v| v.as_str()) {
-                return Ok(Some(id.to_string()));
-            }
-        }
-    }
-    Ok(None)
+    let list: ApiList<Subscription> = data_api::parse("subscriptions.list", json)?;
+    Ok(list.items.into_iter().next().map(|s| s.id))
 }
 
 /// YouTube Data API v3: subscriptions.delete — как в new_endpoints/subscribe_innertube.py.
@@ -226,7 +353,7 @@ async fn delete_subscription(
     access_token: &str,
 ) -> Result<(), String> {
     let resp = client
-        .delete("https://www.googleapis.com/youtube/v3/subscriptions")
+        .delete(crate::endpoints::data_api("subscriptions"))
         .header("Authorization", format!("Bearer {}", access_token))
         .query(&[("id", subscription_id)])
         .send()
@@ -249,7 +376,7 @@ async fn rate_video_api(
     access_token: &str,
 ) -> Result<(), String> {
     let resp = client
-        .post("https://www.googleapis.com/youtube/v3/videos/rate")
+        .post(crate::endpoints::data_api("videos/rate"))
         .header("Authorization", format!("Bearer {}", access_token))
         .header(reqwest::header::CONTENT_LENGTH, "0")
         .query(&[("id", video_id), ("rating", rating)])
@@ -276,7 +403,7 @@ async fn get_rating_api(
     access_token: &str,
 ) -> Result<String, String> {
     let resp = client
-        .get("https://www.googleapis.com/youtube/v3/videos/getRating")
+        .get(crate::endpoints::data_api("videos/getRating"))
         .header("Authorization", format!("Bearer {}", access_token))
         .query(&[("id", video_id)])
         .send()
@@ -292,14 +419,441 @@ async fn get_rating_api(
         ));
     }
     let json: serde_json::Value = resp.json().await.map_err(|e| e.to_string())?;
-    if let Some(items) = json.get("items").and_then(|v| v.as_array()) {
-        if let Some(first) = items.first() {
-            if let Some(r) = first.get("rating").and_then(|v| v.as_str()) {
-                return Ok(r.to_string());
+    let list: ApiList<VideoRating> = data_api::parse("videos.getRating", json)?;
+    list.items
+        .into_iter()
+        .next()
+        .map(|r| r.rating)
+        .ok_or_else(|| "No rating info returned for the given video id".to_string())
+}
+
+/// YouTube Data API v3: playlists.list (mine=true), first 50 playlists.
+async fn list_my_playlists_api(client: &Client, access_token: &str) -> Result<Vec<Playlist>, String> {
+    let resp = client
+        .get(crate::endpoints::data_api("playlists"))
+        .header("Authorization", format!("Bearer {}", access_token))
+        .query(&[
+            ("part", "snippet,contentDetails"),
+            ("mine", "true"),
+            ("maxResults", "50"),
+        ])
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let text = resp.text().await.unwrap_or_default();
+        return Err(format!(
+            "YouTube playlists.list returned {}: {}",
+            status.as_u16(),
+            text
+        ));
+    }
+    let json: serde_json::Value = resp.json().await.map_err(|e| e.to_string())?;
+    let list: ApiList<Playlist> = data_api::parse("playlists.list", json)?;
+    Ok(list.items)
+}
+
+/// YouTube Data API v3: the signed-in channel's uploads playlist (`UU...`).
+async fn my_uploads_playlist_api(client: &Client, access_token: &str) -> Result<String, String> {
+    let resp = client
+        .get(crate::endpoints::data_api("channels"))
+        .header("Authorization", format!("Bearer {}", access_token))
+        .query(&[("part", "contentDetails"), ("mine", "true")])
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let text = resp.text().await.unwrap_or_default();
+        return Err(format!("YouTube channels.list returned {}: {}", status.as_u16(), text));
+    }
+    let json: serde_json::Value = resp.json().await.map_err(|e| e.to_string())?;
+    let list: ApiList<Channel> = data_api::parse("channels.list", json)?;
+    list.items
+        .into_iter()
+        .find_map(|c| c.content_details.and_then(|d| d.related_playlists.uploads))
+        .ok_or_else(|| "The account has no YouTube channel".to_string())
+}
+
+/// YouTube Data API v3: one page of the uploads playlist, then videos.list for privacy, tags and
+/// views (playlistItems only carry the title and thumbnails).
+async fn list_my_videos_api(
+    client: &Client,
+    access_token: &str,
+    count: u32,
+    page_token: Option<&str>,
+) -> Result<(Vec<Video>, Option<String>), String> {
+    let uploads = my_uploads_playlist_api(client, access_token).await?;
+    let count = count.to_string();
+    let mut query = vec![
+        ("part", "contentDetails"),
+        ("playlistId", uploads.as_str()),
+        ("maxResults", count.as_str()),
+    ];
+    if let Some(token) = page_token {
+        query.push(("pageToken", token));
+    }
+    let resp = client
+        .get(crate::endpoints::data_api("playlistItems"))
+        .header("Authorization", format!("Bearer {}", access_token))
+        .query(&query)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let text = resp.text().await.unwrap_or_default();
+        return Err(format!("YouTube playlistItems.list returned {}: {}", status.as_u16(), text));
+    }
+    let json: serde_json::Value = resp.json().await.map_err(|e| e.to_string())?;
+    let items: ApiList<PlaylistItem> = data_api::parse("playlistItems.list", json)?;
+    let ids: Vec<String> = items
+        .items
+        .into_iter()
+        .filter_map(|i| i.content_details.map(|d| d.video_id))
+        .collect();
+    if ids.is_empty() {
+        return Ok((Vec::new(), items.next_page_token));
+    }
+
+    let resp = client
+        .get(crate::endpoints::data_api("videos"))
+        .header("Authorization", format!("Bearer {}", access_token))
+        .query(&[
+            ("part", "snippet,status,statistics,contentDetails"),
+            ("id", ids.join(",").as_str()),
+        ])
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let text = resp.text().await.unwrap_or_default();
+        return Err(format!("YouTube videos.list returned {}: {}", status.as_u16(), text));
+    }
+    let json: serde_json::Value = resp.json().await.map_err(|e| e.to_string())?;
+    let mut videos: ApiList<Video> = data_api::parse("videos.list", json)?;
+    // videos.list doesn't keep the order of `id`; the playlist's is newest first
+    videos
+        .items
+        .sort_by_key(|v| ids.iter().position(|id| *id == v.id).unwrap_or(usize::MAX));
+    Ok((videos.items, items.next_page_token))
+}
+
+/// `status` fields videos.update accepts; read-only ones (uploadStatus, ...) are dropped.
+const WRITABLE_STATUS_FIELDS: [&str; 7] = [
+    "privacyStatus",
+    "embeddable",
+    "license",
+    "publicStatsViewable",
+    "publishAt",
+    "selfDeclaredMadeForKids",
+    "containsSyntheticMedia",
+];
+
+/// YouTube Data API v3: videos.update. The snippet (and status, when privacy changes) is sent
+/// whole, since the API clears whatever is missing, so the current one is fetched and edited.
+async fn update_video_api(
+    client: &Client,
+    request: &UpdateVideoRequest,
+    access_token: &str,
+) -> Result<(), (ActixStatusCode, String)> {
+    let upstream = |e: String| (ActixStatusCode::BAD_GATEWAY, e);
+    let resp = client
+        .get(crate::endpoints::data_api("videos"))
+        .header("Authorization", format!("Bearer {}", access_token))
+        .query(&[("part", "snippet,status"), ("id", request.video_id.trim())])
+        .send()
+        .await
+        .map_err(|e| upstream(e.to_string()))?;
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let text = resp.text().await.unwrap_or_default();
+        return Err(upstream(format!("YouTube videos.list returned {}: {}", status.as_u16(), text)));
+    }
+    let json: serde_json::Value = resp.json().await.map_err(|e| upstream(e.to_string()))?;
+    let Some(current) = json.pointer("/items/0") else {
+        return Err((ActixStatusCode::NOT_FOUND, Msg::VideoNotAvailable.text().to_string()));
+    };
+
+    let mut snippet = current.get("snippet").cloned().unwrap_or_else(|| json!({}));
+    if let Some(title) = request.title.as_deref().map(str::trim).filter(|t| !t.is_empty()) {
+        snippet["title"] = json!(title);
+    }
+    if let Some(description) = &request.description {
+        snippet["description"] = json!(description);
+    }
+    if let Some(tags) = &request.tags {
+        let tags: Vec<&str> = tags.split(',').map(str::trim).filter(|t| !t.is_empty()).collect();
+        snippet["tags"] = json!(tags);
+    }
+    if let Some(fields) = snippet.as_object_mut() {
+        // Output-only; videos.update rejects a snippet that carries them
+        for key in ["publishedAt", "channelId", "channelTitle", "thumbnails", "localized", "liveBroadcastContent"] {
+            fields.remove(key);
+        }
+    }
+    let mut body = json!({ "id": request.video_id.trim(), "snippet": snippet });
+    let mut parts = "snippet";
+    if let Some(privacy) = &request.privacy {
+        let mut status = serde_json::Map::new();
+        if let Some(current_status) = current.get("status").and_then(|s| s.as_object()) {
+            for (key, value) in current_status {
+                if WRITABLE_STATUS_FIELDS.contains(&key.as_str()) {
+                    status.insert(key.clone(), value.clone());
+                }
+            }
+        }
+        status.insert("privacyStatus".to_string(), json!(privacy));
+        body["status"] = serde_json::Value::Object(status);
+        parts = "snippet,status";
+    }
+
+    let resp = client
+        .put(crate::endpoints::data_api("videos"))
+        .header("Authorization", format!("Bearer {}", access_token))
+        .query(&[("part", parts)])
+        .json(&body)
+        .send()
+        .await
+        .map_err(|e| upstream(e.to_string()))?;
+    match resp.status() {
+        s if s.is_success() => Ok(()),
+        reqwest::StatusCode::FORBIDDEN => Err((
+            ActixStatusCode::FORBIDDEN,
+            "Only the video's owner can edit it".to_string(),
+        )),
+        s => {
+            let text = resp.text().await.unwrap_or_default();
+            Err(upstream(format!("YouTube videos.update returned {}: {}", s.as_u16(), text)))
+        }
+    }
+}
+
+/// Largest image accepted by /actions/set_thumbnail, before it is shrunk to fit YouTube's limits.
+const THUMBNAIL_SOURCE_MAX_BYTES: usize = 16 * 1024 * 1024;
+/// thumbnails.set rejects anything larger.
+const THUMBNAIL_MAX_BYTES: usize = 2 * 1024 * 1024;
+
+/// Re-encodes an uploaded image the way thumbnails.set wants it: JPEG, at most 1280x720, at
+/// least 640 pixels wide, under 2 MB. An image too tall for one scale factor to meet both
+/// bounds is centered on a black 16:9 canvas. Quality is lowered step by step until it fits.
+fn prepare_thumbnail(source: &[u8]) -> Result<Vec<u8>, String> {
+    use image::imageops::FilterType;
+
+    let img = image::load_from_memory(source).map_err(|e| format!("Unreadable image: {}", e))?;
+    let (width, height) = (f64::from(img.width().max(1)), f64::from(img.height().max(1)));
+    let min_scale = 640.0 / width;
+    let max_scale = (1280.0 / width).min(720.0 / height);
+    let rgb = if min_scale <= max_scale {
+        // As close to the original size as the bounds allow
+        let scale = 1.0f64.clamp(min_scale, max_scale);
+        if scale == 1.0 {
+            img.to_rgb8()
+        } else {
+            let w = ((width * scale).round() as u32).clamp(640, 1280);
+            let h = ((height * scale).round() as u32).clamp(1, 720);
+            img.resize_exact(w, h, FilterType::Lanczos3).to_rgb8()
+        }
+    } else {
+        // Portrait past 640:720: fit the height into a 640x360..1280x720 canvas
+        let canvas_height = (height as u32).clamp(360, 720);
+        let canvas_width = canvas_height * 16 / 9;
+        let scaled_width = ((width * f64::from(canvas_height) / height).round() as u32).max(1);
+        let scaled = img
+            .resize_exact(scaled_width, canvas_height, FilterType::Lanczos3)
+            .to_rgb8();
+        let mut canvas = image::RgbImage::new(canvas_width, canvas_height);
+        let x = i64::from((canvas_width - scaled_width) / 2);
+        image::imageops::overlay(&mut canvas, &scaled, x, 0);
+        canvas
+    };
+    for quality in [90u8, 80, 70, 60, 50] {
+        let mut out = std::io::Cursor::new(Vec::new());
+        rgb.write_to(&mut out, image::ImageOutputFormat::Jpeg(quality))
+            .map_err(|e| e.to_string())?;
+        let out = out.into_inner();
+        if out.len() <= THUMBNAIL_MAX_BYTES {
+            return Ok(out);
+        }
+    }
+    Err("Image does not fit in 2 MB even at low quality".to_string())
+}
+
+/// YouTube Data API v3: thumbnails.set. Custom thumbnails need a verified channel, which is the
+/// usual reason for a 403 besides not owning the video.
+async fn set_thumbnail_api(
+    client: &Client,
+    video_id: &str,
+    jpeg: Vec<u8>,
+    access_token: &str,
+) -> Result<(), (ActixStatusCode, String)> {
+    let resp = client
+        .post(crate::endpoints::data_api_upload("thumbnails/set"))
+        .header("Authorization", format!("Bearer {}", access_token))
+        .header("Content-Type", "image/jpeg")
+        .query(&[("videoId", video_id), ("uploadType", "media")])
+        .body(jpeg)
+        .send()
+        .await
+        .map_err(|e| (ActixStatusCode::BAD_GATEWAY, e.to_string()))?;
+    match resp.status() {
+        s if s.is_success() => Ok(()),
+        reqwest::StatusCode::FORBIDDEN => Err((
+            ActixStatusCode::FORBIDDEN,
+            "Only the video's owner can change its thumbnail, and the channel must be verified"
+                .to_string(),
+        )),
+        reqwest::StatusCode::NOT_FOUND => Err((
+            ActixStatusCode::NOT_FOUND,
+            Msg::VideoNotAvailable.text().to_string(),
+        )),
+        s => {
+            let text = resp.text().await.unwrap_or_default();
+            Err((
+                ActixStatusCode::BAD_GATEWAY,
+                format!("YouTube thumbnails.set returned {}: {}", s.as_u16(), text),
+            ))
+        }
+    }
+}
+
+/// YouTube Data API v3: playlistItems.insert (appends to the end of the playlist).
+async fn playlist_insert_api(
+    client: &Client,
+    playlist_id: &str,
+    video_id: &str,
+    access_token: &str,
+) -> Result<(), String> {
+    let payload = json!({
+        "snippet": {
+            "playlistId": playlist_id,
+            "resourceId": {
+                "kind": "youtube#video",
+                "videoId": video_id
             }
         }
+    });
+    let resp = client
+        .post(crate::endpoints::data_api("playlistItems?part=snippet"))
+        .header("Authorization", format!("Bearer {}", access_token))
+        .header("Content-Type", "application/json")
+        .header("Accept", "application/json")
+        .json(&payload)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if resp.status().is_success() {
+        Ok(())
+    } else {
+        let status = resp.status();
+        let text = resp.text().await.unwrap_or_default();
+        Err(format!("YouTube API error {}: {}", status.as_u16(), text))
+    }
+}
+
+/// Shared by the JSON and form handlers; quota is charged by the caller.
+async fn add_to_playlist(
+    refresh_token: &str,
+    video_id: &str,
+    playlist_id: &str,
+    auth_config: &AuthConfig,
+) -> Result<(), (ActixStatusCode, String)> {
+    if video_id.trim().is_empty() || playlist_id.trim().is_empty() {
+        return Err((
+            ActixStatusCode::BAD_REQUEST,
+            "video_id and playlist_id are required".to_string(),
+        ));
+    }
+    let trimmed = refresh_token.trim();
+    if trimmed.is_empty() {
+        return Err((ActixStatusCode::BAD_REQUEST, "Missing refresh_token".to_string()));
+    }
+    let access_token = refresh_access_token(trimmed, auth_config)
+        .await
+        .map_err(|e| (ActixStatusCode::UNAUTHORIZED, e))?;
+    let client = crate::http_client::shared();
+    playlist_insert_api(&client, playlist_id.trim(), video_id.trim(), &access_token)
+        .await
+        .map_err(|e| (ActixStatusCode::BAD_GATEWAY, e))
+}
+
+/// The `PerformCommentActionParams` protobuf InnerTube's comment buttons send: action type 5
+/// likes, 6 takes the like back.
+fn comment_action_params(action: u32, comment_id: &str, video_id: &str) -> String {
+    use base64::{engine::general_purpose, Engine as _};
+
+    fn varint(mut n: usize, out: &mut Vec<u8>) {
+        while n >= 0x80 {
+            out.push((n as u8 & 0x7f) | 0x80);
+            n >>= 7;
+        }
+        out.push(n as u8);
+    }
+
+    let mut out = vec![0x08];
+    varint(action as usize, &mut out);
+    out.extend_from_slice(&[0x10, 0x02]);
+    for (tag, value) in [(0x1a, comment_id), (0x2a, video_id)] {
+        out.push(tag);
+        varint(value.len(), &mut out);
+        out.extend_from_slice(value.as_bytes());
+    }
+    general_purpose::STANDARD.encode(out)
+}
+
+/// InnerTube `comment/perform_comment_action` with the account's bearer token; the Data API
+/// can read comment likes but not set them.
+async fn rate_comment_api(
+    client: &Client,
+    access_token: &str,
+    comment_id: &str,
+    video_id: &str,
+    like: bool,
+) -> Result<(), String> {
+    let payload = json!({
+        "context": {
+            "client": {
+                "clientName": "WEB",
+                "clientVersion": "2.20250101",
+                "hl": "en"
+            }
+        },
+        "actions": [comment_action_params(if like { 5 } else { 6 }, comment_id, video_id)]
+    });
+    let resp = client
+        .post(crate::endpoints::innertube("comment/perform_comment_action?prettyPrint=false"))
+        .header("Authorization", format!("Bearer {}", access_token))
+        .header("User-Agent", USER_AGENT)
+        .header("Content-Type", "application/json")
+        .json(&payload)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    let status = resp.status();
+    if !status.is_success() {
+        let text = resp.text().await.unwrap_or_default();
+        return Err(format!("InnerTube error {}: {}", status.as_u16(), text));
+    }
+    let body: serde_json::Value = resp.json().await.map_err(|e| e.to_string())?;
+    let failed = body
+        .get("actionResults")
+        .and_then(|r| r.as_array())
+        .into_iter()
+        .flatten()
+        .find(|r| r.get("status").and_then(|s| s.as_str()) != Some("STATUS_SUCCEEDED"));
+    match failed {
+        Some(result) => Err(format!(
+            "Comment action refused: {}",
+            result
+                .get("feedback")
+                .and_then(|f| f.as_str())
+                .or_else(|| result.get("status").and_then(|s| s.as_str()))
+                .unwrap_or("unknown status")
+        )),
+        None => Ok(()),
     }
-    Err("No rating info returned for the given video id".to_string())
 }
 
 fn validate_rating(value: &str) -> bool {
@@ -316,20 +870,30 @@ fn validate_rating(value: &str) -> bool {
     responses(
         (status = 200, description = "Subscribed to channel", body = YoutubeActionResponse),
         (status = 400, description = "Invalid request"),
-        (status = 401, description = "Authentication error")
+        (status = 401, description = "Authentication error"),
+        (status = 403, description = "Token is scoped without actions"),
+        (status = 429, description = "Daily API quota exceeded")
     )
 )]
 pub async fn subscribe(
+    req: HttpRequest,
     payload: web::Query<YoutubeSubscriptionRequest>,
     auth_config: web::Data<AuthConfig>,
+    data: web::Data<crate::AppState>,
 ) -> impl Responder {
     let request = payload.into_inner();
+    if let Some(resp) = token_scopes::deny(&request.token, Permission::Actions) {
+        return resp;
+    }
+    if let Some(resp) = quota::charge(&req, &data.config, Some(&request.token), WRITE_COST) {
+        return resp;
+    }
     let access_token = match obtain_access_token(&request.token, &auth_config).await {
         Ok(token) => token,
         Err(err) => return err,
     };
 
-    let client = Client::new();
+    let client = crate::http_client::shared();
     let channel_id = match resolve_channel_id(&request.channel, &client).await {
         Ok(id) => id,
         Err(err) => return error_json(ActixStatusCode::BAD_REQUEST, err),
@@ -348,6 +912,87 @@ pub async fn subscribe(
     })
 }
 
+#[utoipa::path(
+    post,
+    path = "/actions/subscribe_form",
+    request_body(content = SubscribeForm, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "Subscribed to channel", body = YoutubeActionResponse),
+        (status = 303, description = "Redirect to `redirect` with subscribed=1 or error=..."),
+        (status = 400, description = "Invalid request or not signed in"),
+        (status = 403, description = "Token is scoped without actions"),
+        (status = 429, description = "Daily API quota exceeded")
+    )
+)]
+pub async fn subscribe_form(
+    req: HttpRequest,
+    form: web::Form<SubscribeForm>,
+    auth_config: web::Data<AuthConfig>,
+    token_store: web::Data<TokenStore>,
+    data: web::Data<crate::AppState>,
+) -> impl Responder {
+    let form = form.into_inner();
+    let redirect = form.redirect.as_deref();
+    let fail = |resp: HttpResponse, message: &str| {
+        if redirect.is_none() {
+            resp
+        } else {
+            crate::routes::profiles::respond(redirect, false, json!({ "error": message }))
+        }
+    };
+    let token = form
+        .token
+        .clone()
+        .filter(|t| !t.trim().is_empty())
+        .or_else(|| token_store.session_token(&req));
+    let Some(token) = token else {
+        return crate::routes::profiles::respond(
+            redirect,
+            false,
+            json!({ "error": "Sign in to subscribe to channels" }),
+        );
+    };
+    if let Some(resp) = token_scopes::deny(&token, Permission::Actions) {
+        return fail(resp, Msg::NotPermitted.text());
+    }
+    if let Some(resp) = quota::charge(&req, &data.config, Some(&token), WRITE_COST) {
+        return fail(resp, Msg::QuotaExceeded.text());
+    }
+    let access_token = match obtain_access_token(&token, &auth_config).await {
+        Ok(token) => token,
+        Err(resp) => return fail(resp, "Your session has expired; sign in again"),
+    };
+
+    let client = crate::http_client::shared();
+    let result = match resolve_channel_id(&form.channel, &client).await {
+        Ok(channel_id) => subscribe_channel_api(&client, &channel_id, &access_token)
+            .await
+            .map(|_| channel_id)
+            .map_err(|e| (ActixStatusCode::BAD_GATEWAY, e)),
+        Err(e) => Err((ActixStatusCode::BAD_REQUEST, e)),
+    };
+    match result {
+        Ok(channel_id) => {
+            let subscribed = redirect.map(|path| {
+                let sep = if path.contains('?') { '&' } else { '?' };
+                format!("{}{}subscribed=1", path, sep)
+            });
+            crate::routes::profiles::respond(
+                subscribed.as_deref(),
+                true,
+                json!(YoutubeActionResponse {
+                    status: "success".to_string(),
+                    action: "subscribe".to_string(),
+                    channel_id: Some(channel_id),
+                    video_id: None,
+                    message: "Subscribed to channel".to_string(),
+                }),
+            )
+        }
+        Err((status, err)) => fail(error_json(status, &err), &err),
+    }
+}
+
 #[utoipa::path(
     get,
     path = "/actions/unsubscribe",
@@ -359,20 +1004,30 @@ pub async fn subscribe(
         (status = 200, description = "Unsubscribed from channel", body = YoutubeActionResponse),
         (status = 400, description = "Invalid request"),
         (status = 401, description = "Authentication error"),
-        (status = 404, description = "Subscription not found")
+        (status = 403, description = "Token is scoped without actions"),
+        (status = 404, description = "Subscription not found"),
+        (status = 429, description = "Daily API quota exceeded")
     )
 )]
 pub async fn unsubscribe(
+    req: HttpRequest,
     payload: web::Query<YoutubeSubscriptionRequest>,
     auth_config: web::Data<AuthConfig>,
+    data: web::Data<crate::AppState>,
 ) -> impl Responder {
     let request = payload.into_inner();
+    if let Some(resp) = token_scopes::deny(&request.token, Permission::Actions) {
+        return resp;
+    }
+    if let Some(resp) = quota::charge(&req, &data.config, Some(&request.token), READ_COST + WRITE_COST) {
+        return resp;
+    }
     let access_token = match obtain_access_token(&request.token, &auth_config).await {
         Ok(token) => token,
         Err(err) => return err,
     };
 
-    let client = Client::new();
+    let client = crate::http_client::shared();
     let channel_id = match resolve_channel_id(&request.channel, &client).await {
         Ok(id) => id,
         Err(err) => return error_json(ActixStatusCode::BAD_REQUEST, err),
@@ -413,14 +1068,24 @@ pub async fn unsubscribe(
     responses(
         (status = 200, description = "Video rated", body = YoutubeActionResponse),
         (status = 400, description = "Invalid request"),
-        (status = 401, description = "Authentication error")
+        (status = 401, description = "Authentication error"),
+        (status = 403, description = "Token is scoped without actions"),
+        (status = 429, description = "Daily API quota exceeded")
     )
 )]
 pub async fn rate(
+    req: HttpRequest,
     payload: web::Query<YoutubeRateRequest>,
     auth_config: web::Data<AuthConfig>,
+    data: web::Data<crate::AppState>,
 ) -> impl Responder {
     let request = payload.into_inner();
+    if let Some(resp) = token_scopes::deny(&request.token, Permission::Actions) {
+        return resp;
+    }
+    if let Some(resp) = quota::charge(&req, &data.config, Some(&request.token), WRITE_COST) {
+        return resp;
+    }
     if !validate_rating(&request.rating) {
         return error_json(
             ActixStatusCode::BAD_REQUEST,
@@ -433,7 +1098,7 @@ pub async fn rate(
         Err(err) => return err,
     };
 
-    let client = Client::new();
+    let client = crate::http_client::shared();
     if let Err(err) =
         rate_video_api(&client, &request.video_id, &request.rating, &access_token).await
     {
@@ -449,6 +1114,60 @@ pub async fn rate(
     })
 }
 
+#[utoipa::path(
+    get,
+    path = "/actions/rate_comment",
+    params(
+        ("video_id" = String, Query, description = "Video the comment is on"),
+        ("comment_id" = String, Query, description = "`comment_id` from the video's comments"),
+        ("rating" = String, Query, description = "like | none"),
+        ("token" = String, Query, description = "OAuth refresh token")
+    ),
+    responses(
+        (status = 200, description = "Comment rated", body = YoutubeActionResponse),
+        (status = 400, description = "Invalid request"),
+        (status = 401, description = "Authentication error"),
+        (status = 403, description = "Token is scoped without actions"),
+        (status = 502, description = "YouTube refused the action")
+    )
+)]
+pub async fn rate_comment(
+    payload: web::Query<RateCommentRequest>,
+    auth_config: web::Data<AuthConfig>,
+) -> impl Responder {
+    let request = payload.into_inner();
+    if let Some(resp) = token_scopes::deny(&request.token, Permission::Actions) {
+        return resp;
+    }
+    let like = match request.rating.to_lowercase().as_str() {
+        "like" => true,
+        "none" => false,
+        _ => return error_json(ActixStatusCode::BAD_REQUEST, "Rating must be one of: like, none"),
+    };
+    let (video_id, comment_id) = (request.video_id.trim(), request.comment_id.trim());
+    if video_id.is_empty() || comment_id.is_empty() {
+        return error_json(ActixStatusCode::BAD_REQUEST, "video_id and comment_id are required");
+    }
+
+    let access_token = match obtain_access_token(&request.token, &auth_config).await {
+        Ok(token) => token,
+        Err(err) => return err,
+    };
+
+    let client = crate::http_client::shared();
+    if let Err(err) = rate_comment_api(&client, &access_token, comment_id, video_id, like).await {
+        return error_json(ActixStatusCode::BAD_GATEWAY, err);
+    }
+
+    HttpResponse::Ok().json(YoutubeActionResponse {
+        status: "success".to_string(),
+        action: "rate_comment".to_string(),
+        channel_id: None,
+        video_id: Some(video_id.to_string()),
+        message: format!("Comment {} rated {}", comment_id, request.rating.to_lowercase()),
+    })
+}
+
 #[utoipa::path(
     get,
     path = "/actions/check_rating",
@@ -459,14 +1178,20 @@ pub async fn rate(
     responses(
         (status = 200, description = "Current rating for the video", body = RatingCheckResponse),
         (status = 400, description = "Invalid request"),
-        (status = 401, description = "Authentication error")
+        (status = 401, description = "Authentication error"),
+        (status = 429, description = "Daily API quota exceeded")
     )
 )]
 pub async fn check_rating(
+    req: HttpRequest,
     payload: web::Query<RatingCheckRequest>,
     auth_config: web::Data<AuthConfig>,
+    data: web::Data<crate::AppState>,
 ) -> impl Responder {
     let request = payload.into_inner();
+    if let Some(resp) = quota::charge(&req, &data.config, Some(&request.token), READ_COST) {
+        return resp;
+    }
     if request.video_id.trim().is_empty() || request.token.trim().is_empty() {
         return error_json(
             ActixStatusCode::BAD_REQUEST,
@@ -478,7 +1203,7 @@ pub async fn check_rating(
         Ok(token) => token,
         Err(err) => return err,
     };
-    let client = Client::new();
+    let client = crate::http_client::shared();
     match get_rating_api(&client, &request.video_id, &access_token).await {
         Ok(rating) => HttpResponse::Ok().json(RatingCheckResponse {
             status: "success".to_string(),
@@ -500,14 +1225,20 @@ pub async fn check_rating(
         (status = 200, description = "Subscription status", body = SubscriptionCheckResponse),
         (status = 400, description = "Invalid request"),
         (status = 401, description = "Authentication error"),
-        (status = 404, description = "Channel not found")
+        (status = 404, description = "Channel not found"),
+        (status = 429, description = "Daily API quota exceeded")
     )
 )]
 pub async fn check_subscription(
+    req: HttpRequest,
     payload: web::Query<SubscriptionCheckRequest>,
     auth_config: web::Data<AuthConfig>,
+    data: web::Data<crate::AppState>,
 ) -> impl Responder {
     let request = payload.into_inner();
+    if let Some(resp) = quota::charge(&req, &data.config, Some(&request.token), READ_COST) {
+        return resp;
+    }
     if request.channel.trim().is_empty() || request.token.trim().is_empty() {
         return error_json(
             ActixStatusCode::BAD_REQUEST,
@@ -519,7 +1250,7 @@ pub async fn check_subscription(
         Ok(token) => token,
         Err(err) => return err,
     };
-    let client = Client::new();
+    let client = crate::http_client::shared();
     let channel_id = match resolve_channel_id(&request.channel, &client).await {
         Ok(id) => id,
         Err(err) => return error_json(ActixStatusCode::NOT_FOUND, err),
@@ -539,3 +1270,568 @@ pub async fn check_subscription(
         Err(err) => error_json(ActixStatusCode::BAD_GATEWAY, err),
     }
 }
+
+#[utoipa::path(
+    get,
+    path = "/get_my_playlists.php",
+    params(
+        ("token" = String, Query, description = "OAuth refresh token")
+    ),
+    responses(
+        (status = 200, description = "Playlists of the signed-in account (first 50)", body = MyPlaylistsResponse),
+        (status = 400, description = "Invalid request"),
+        (status = 401, description = "Authentication error"),
+        (status = 429, description = "Daily API quota exceeded")
+    )
+)]
+pub async fn get_my_playlists(
+    req: HttpRequest,
+    payload: web::Query<MyPlaylistsRequest>,
+    auth_config: web::Data<AuthConfig>,
+    data: web::Data<crate::AppState>,
+) -> impl Responder {
+    let request = payload.into_inner();
+    if let Some(resp) = quota::charge(&req, &data.config, Some(&request.token), READ_COST) {
+        return resp;
+    }
+    let access_token = match obtain_access_token(&request.token, &auth_config).await {
+        Ok(token) => token,
+        Err(err) => return err,
+    };
+    let client = crate::http_client::shared();
+    match list_my_playlists_api(&client, &access_token).await {
+        Ok(items) => HttpResponse::Ok().json(MyPlaylistsResponse {
+            playlists: items
+                .into_iter()
+                .map(|p| MyPlaylist {
+                    title: p.snippet.map(|s| s.title).unwrap_or_default(),
+                    video_count: p.content_details.and_then(|c| c.item_count).unwrap_or(0),
+                    playlist_id: p.id,
+                })
+                .collect(),
+        }),
+        Err(err) => error_json(ActixStatusCode::BAD_GATEWAY, err),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/get_my_videos.php",
+    params(
+        ("token" = String, Query, description = "OAuth refresh token"),
+        ("count" = Option<u32>, Query, description = "Videos per page (1-50, default 20)"),
+        ("page_token" = Option<String>, Query, description = "next_page_token of the previous page")
+    ),
+    responses(
+        (status = 200, description = "Uploads of the signed-in account, newest first, including private ones", body = MyVideosResponse),
+        (status = 400, description = "Invalid request"),
+        (status = 401, description = "Authentication error"),
+        (status = 429, description = "Daily API quota exceeded")
+    )
+)]
+pub async fn get_my_videos(
+    req: HttpRequest,
+    payload: web::Query<MyVideosRequest>,
+    auth_config: web::Data<AuthConfig>,
+    data: web::Data<crate::AppState>,
+) -> impl Responder {
+    let request = payload.into_inner();
+    // channels.list + playlistItems.list + videos.list
+    if let Some(resp) = quota::charge(&req, &data.config, Some(&request.token), 3 * READ_COST) {
+        return resp;
+    }
+    let access_token = match obtain_access_token(&request.token, &auth_config).await {
+        Ok(token) => token,
+        Err(err) => return err,
+    };
+    let count = request.count.unwrap_or(20).clamp(1, 50);
+    let page_token = request.page_token.as_deref().filter(|t| !t.is_empty());
+    let client = crate::http_client::shared();
+    match list_my_videos_api(&client, &access_token, count, page_token).await {
+        Ok((videos, next_page_token)) => HttpResponse::Ok().json(MyVideosResponse {
+            videos: videos
+                .into_iter()
+                .map(|v| {
+                    let snippet = v.snippet;
+                    let status = v.status.unwrap_or_default();
+                    MyVideo {
+                        title: snippet.as_ref().map(|s| s.title.clone()).unwrap_or_default(),
+                        description: snippet.as_ref().map(|s| s.description.clone()).unwrap_or_default(),
+                        tags: snippet.as_ref().map(|s| s.tags.clone()).unwrap_or_default(),
+                        published_at: snippet
+                            .as_ref()
+                            .and_then(|s| s.published_at.clone())
+                            .unwrap_or_default(),
+                        thumbnail: snippet
+                            .as_ref()
+                            .and_then(|s| s.thumbnails.high_url())
+                            .unwrap_or_default()
+                            .to_string(),
+                        privacy: status.privacy_status.unwrap_or_default(),
+                        upload_status: status.upload_status.unwrap_or_default(),
+                        views: v.statistics.and_then(|s| s.view_count).unwrap_or(0),
+                        duration: v.content_details.and_then(|c| c.duration).unwrap_or_default(),
+                        video_id: v.id,
+                    }
+                })
+                .collect(),
+            next_page_token,
+        }),
+        Err(err) => error_json(ActixStatusCode::BAD_GATEWAY, err),
+    }
+}
+
+/// Shared by GET and POST /actions/update_video.
+async fn update_video(
+    req: HttpRequest,
+    request: UpdateVideoRequest,
+    auth_config: &AuthConfig,
+    token_store: &TokenStore,
+    data: &crate::AppState,
+) -> HttpResponse {
+    if request.video_id.trim().is_empty() {
+        return error_json(ActixStatusCode::BAD_REQUEST, Msg::VideoIdRequired.text());
+    }
+    if let Some(privacy) = &request.privacy {
+        if !matches!(privacy.as_str(), "public" | "unlisted" | "private") {
+            return error_json(
+                ActixStatusCode::BAD_REQUEST,
+                "privacy must be public, unlisted or private",
+            );
+        }
+    }
+    let token = request
+        .token
+        .clone()
+        .filter(|t| !t.trim().is_empty())
+        .or_else(|| token_store.session_token(&req));
+    let Some(token) = token else {
+        return error_json(ActixStatusCode::UNAUTHORIZED, "Missing refresh_token");
+    };
+    if let Some(resp) = token_scopes::deny(&token, Permission::Actions) {
+        return resp;
+    }
+    if let Some(resp) = quota::charge(&req, &data.config, Some(&token), WRITE_COST + READ_COST) {
+        return resp;
+    }
+    let access_token = match obtain_access_token(&token, auth_config).await {
+        Ok(token) => token,
+        Err(err) => return err,
+    };
+    let client = crate::http_client::shared();
+    if let Err((status, err)) = update_video_api(&client, &request, &access_token).await {
+        return error_json(status, err);
+    }
+    HttpResponse::Ok().json(YoutubeActionResponse {
+        status: "success".to_string(),
+        action: "update_video".to_string(),
+        channel_id: None,
+        video_id: Some(request.video_id.trim().to_string()),
+        message: "Video updated".to_string(),
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/actions/update_video",
+    params(
+        ("video_id" = String, Query, description = "One of the signed-in account's uploads"),
+        ("token" = Option<String>, Query, description = "OAuth refresh token (else the frontend session)"),
+        ("title" = Option<String>, Query, description = "New title"),
+        ("description" = Option<String>, Query, description = "New description"),
+        ("privacy" = Option<String>, Query, description = "public, unlisted or private"),
+        ("tags" = Option<String>, Query, description = "Comma-separated tags; empty clears them")
+    ),
+    responses(
+        (status = 200, description = "Video updated", body = YoutubeActionResponse),
+        (status = 400, description = "Invalid request"),
+        (status = 401, description = "Authentication error"),
+        (status = 403, description = "Not the owner, or token is scoped without actions"),
+        (status = 404, description = "No such video"),
+        (status = 429, description = "Daily API quota exceeded")
+    )
+)]
+pub async fn update_video_query(
+    req: HttpRequest,
+    payload: web::Query<UpdateVideoRequest>,
+    auth_config: web::Data<AuthConfig>,
+    token_store: web::Data<TokenStore>,
+    data: web::Data<crate::AppState>,
+) -> impl Responder {
+    update_video(req, payload.into_inner(), &auth_config, &token_store, &data).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/actions/update_video",
+    request_body(content = UpdateVideoRequest, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "Video updated", body = YoutubeActionResponse),
+        (status = 400, description = "Invalid request"),
+        (status = 401, description = "Authentication error"),
+        (status = 403, description = "Not the owner, or token is scoped without actions"),
+        (status = 404, description = "No such video"),
+        (status = 429, description = "Daily API quota exceeded")
+    )
+)]
+pub async fn update_video_form(
+    req: HttpRequest,
+    form: web::Form<UpdateVideoRequest>,
+    auth_config: web::Data<AuthConfig>,
+    token_store: web::Data<TokenStore>,
+    data: web::Data<crate::AppState>,
+) -> impl Responder {
+    update_video(req, form.into_inner(), &auth_config, &token_store, &data).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/actions/set_thumbnail",
+    params(
+        ("video_id" = String, Query, description = "One of the signed-in account's uploads"),
+        ("token" = Option<String>, Query, description = "OAuth refresh token (else the frontend session)")
+    ),
+    request_body(content = Vec<u8>, description = "The image (JPEG, PNG, GIF, BMP or WebP, up to 16 MB); resized and re-encoded to fit YouTube's limits", content_type = "application/octet-stream"),
+    responses(
+        (status = 200, description = "Thumbnail set", body = YoutubeActionResponse),
+        (status = 400, description = "Invalid request or unreadable image"),
+        (status = 401, description = "Authentication error"),
+        (status = 403, description = "Not the owner, channel not verified, or token is scoped without actions"),
+        (status = 404, description = "No such video"),
+        (status = 413, description = "Image larger than 16 MB"),
+        (status = 429, description = "Daily API quota exceeded")
+    )
+)]
+pub async fn set_thumbnail(
+    req: HttpRequest,
+    query: web::Query<SetThumbnailRequest>,
+    mut payload: web::Payload,
+    auth_config: web::Data<AuthConfig>,
+    token_store: web::Data<TokenStore>,
+    data: web::Data<crate::AppState>,
+) -> impl Responder {
+    let request = query.into_inner();
+    let video_id = request.video_id.trim().to_string();
+    if video_id.is_empty() {
+        return error_json(ActixStatusCode::BAD_REQUEST, Msg::VideoIdRequired.text());
+    }
+    let token = request
+        .token
+        .filter(|t| !t.trim().is_empty())
+        .or_else(|| token_store.session_token(&req));
+    let Some(token) = token else {
+        return error_json(ActixStatusCode::UNAUTHORIZED, "Missing refresh_token");
+    };
+    if let Some(resp) = token_scopes::deny(&token, Permission::Actions) {
+        return resp;
+    }
+
+    // Phone photos easily exceed the global body limit, so the image is read with its own
+    let mut source = web::BytesMut::new();
+    while let Some(chunk) = payload.next().await {
+        let chunk = match chunk {
+            Ok(c) => c,
+            Err(e) => return error_json(ActixStatusCode::BAD_REQUEST, e.to_string()),
+        };
+        if source.len() + chunk.len() > THUMBNAIL_SOURCE_MAX_BYTES {
+            return error_json(ActixStatusCode::PAYLOAD_TOO_LARGE, "Image larger than 16 MB");
+        }
+        source.extend_from_slice(&chunk);
+    }
+    if source.is_empty() {
+        return error_json(ActixStatusCode::BAD_REQUEST, "Missing image in the request body");
+    }
+    let jpeg = match web::block(move || prepare_thumbnail(&source)).await {
+        Ok(Ok(jpeg)) => jpeg,
+        Ok(Err(err)) => return error_json(ActixStatusCode::BAD_REQUEST, err),
+        Err(err) => return error_json(ActixStatusCode::INTERNAL_SERVER_ERROR, err.to_string()),
+    };
+
+    if let Some(resp) = quota::charge(&req, &data.config, Some(&token), WRITE_COST) {
+        return resp;
+    }
+    let access_token = match obtain_access_token(&token, &auth_config).await {
+        Ok(token) => token,
+        Err(err) => return err,
+    };
+    let client = crate::http_client::shared();
+    if let Err((status, err)) = set_thumbnail_api(&client, &video_id, jpeg, &access_token).await {
+        return error_json(status, err);
+    }
+    HttpResponse::Ok().json(YoutubeActionResponse {
+        status: "success".to_string(),
+        action: "set_thumbnail".to_string(),
+        channel_id: None,
+        video_id: Some(video_id),
+        message: "Thumbnail updated".to_string(),
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/actions/playlist_add",
+    params(
+        ("video_id" = String, Query, description = "YouTube video id"),
+        ("playlist_id" = String, Query, description = "Playlist of the signed-in account"),
+        ("token" = String, Query, description = "OAuth refresh token")
+    ),
+    responses(
+        (status = 200, description = "Video added to the playlist", body = YoutubeActionResponse),
+        (status = 400, description = "Invalid request"),
+        (status = 401, description = "Authentication error"),
+        (status = 403, description = "Token is scoped without actions"),
+        (status = 429, description = "Daily API quota exceeded")
+    )
+)]
+pub async fn playlist_add(
+    req: HttpRequest,
+    payload: web::Query<PlaylistAddRequest>,
+    auth_config: web::Data<AuthConfig>,
+    data: web::Data<crate::AppState>,
+) -> impl Responder {
+    let request = payload.into_inner();
+    if let Some(resp) = token_scopes::deny(&request.token, Permission::Actions) {
+        return resp;
+    }
+    if let Some(resp) = quota::charge(&req, &data.config, Some(&request.token), WRITE_COST) {
+        return resp;
+    }
+    if let Err((status, err)) = add_to_playlist(
+        &request.token,
+        &request.video_id,
+        &request.playlist_id,
+        &auth_config,
+    )
+    .await
+    {
+        return error_json(status, err);
+    }
+
+    HttpResponse::Ok().json(YoutubeActionResponse {
+        status: "success".to_string(),
+        action: "playlist_add".to_string(),
+        channel_id: None,
+        video_id: Some(request.video_id),
+        message: format!("Video added to playlist {}", request.playlist_id),
+    })
+}
+
+#[utoipa::path(
+    post,
+    path = "/actions/playlist_add",
+    request_body(content = PlaylistAddForm, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "Video added to the playlist", body = YoutubeActionResponse),
+        (status = 303, description = "Redirect to `redirect` with playlist_added=1 or error=..."),
+        (status = 400, description = "Invalid request or not signed in"),
+        (status = 403, description = "Token is scoped without actions"),
+        (status = 429, description = "Daily API quota exceeded")
+    )
+)]
+pub async fn playlist_add_form(
+    req: HttpRequest,
+    form: web::Form<PlaylistAddForm>,
+    auth_config: web::Data<AuthConfig>,
+    token_store: web::Data<TokenStore>,
+    data: web::Data<crate::AppState>,
+) -> impl Responder {
+    let form = form.into_inner();
+    let redirect = form.redirect.as_deref();
+    let token = form
+        .token
+        .clone()
+        .filter(|t| !t.trim().is_empty())
+        .or_else(|| token_store.session_token(&req));
+    let Some(token) = token else {
+        return crate::routes::profiles::respond(
+            redirect,
+            false,
+            json!({ "error": "Sign in to add videos to playlists" }),
+        );
+    };
+    if let Some(resp) = token_scopes::deny(&token, Permission::Actions) {
+        if redirect.is_none() {
+            return resp;
+        }
+        return crate::routes::profiles::respond(
+            redirect,
+            false,
+            json!({ "error": Msg::NotPermitted.text() }),
+        );
+    }
+    if let Some(resp) = quota::charge(&req, &data.config, Some(&token), WRITE_COST) {
+        if redirect.is_none() {
+            return resp;
+        }
+        return crate::routes::profiles::respond(
+            redirect,
+            false,
+            json!({ "error": Msg::QuotaExceeded.text() }),
+        );
+    }
+
+    match add_to_playlist(&token, &form.video_id, &form.playlist_id, &auth_config).await {
+        Ok(()) => {
+            let added = redirect.map(|path| {
+                let sep = if path.contains('?') { '&' } else { '?' };
+                format!("{}{}playlist_added=1", path, sep)
+            });
+            crate::routes::profiles::respond(
+                added.as_deref(),
+                true,
+                json!(YoutubeActionResponse {
+                    status: "success".to_string(),
+                    action: "playlist_add".to_string(),
+                    channel_id: None,
+                    video_id: Some(form.video_id.clone()),
+                    message: format!("Video added to playlist {}", form.playlist_id),
+                }),
+            )
+        }
+        Err((status, err)) if redirect.is_none() => error_json(status, err),
+        Err((_, err)) => crate::routes::profiles::respond(redirect, false, json!({ "error": err })),
+    }
+}
+
+/// InnerTube's notification bell (there is no Data API for it), with the account's bearer token.
+async fn innertube_notifications(
+    client: &Client,
+    access_token: &str,
+    endpoint: &str,
+    extra: serde_json::Value,
+) -> Result<serde_json::Value, String> {
+    let mut payload = json!({
+        "context": {
+            "client": {
+                "clientName": "WEB",
+                "clientVersion": "2.20250101",
+                "hl": "en"
+            }
+        }
+    });
+    if let (Some(body), serde_json::Value::Object(extra)) = (payload.as_object_mut(), extra) {
+        body.extend(extra);
+    }
+    let resp = client
+        .post(crate::endpoints::innertube(&format!("notification/{}?prettyPrint=false", endpoint)))
+        .header("Authorization", format!("Bearer {}", access_token))
+        .header("User-Agent", USER_AGENT)
+        .header("Content-Type", "application/json")
+        .json(&payload)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    let status = resp.status();
+    if !status.is_success() {
+        let text = resp.text().await.unwrap_or_default();
+        return Err(format!("InnerTube error {}: {}", status.as_u16(), text));
+    }
+    resp.json().await.map_err(|e| e.to_string())
+}
+
+fn unseen_count(body: &serde_json::Value) -> u32 {
+    body.get("unseenCount")
+        .or_else(|| body.pointer("/actions/0/updateNotificationsUnseenCountAction/unseenCount"))
+        .and_then(|c| c.as_u64())
+        .unwrap_or(0) as u32
+}
+
+fn collect_notifications(node: &serde_json::Value, out: &mut Vec<Notification>, limit: usize) {
+    if out.len() >= limit {
+        return;
+    }
+    match node {
+        serde_json::Value::Object(map) => {
+            if let Some(n) = map.get("notificationRenderer") {
+                let text = |key: &str| {
+                    n.get(key)
+                        .and_then(|t| {
+                            t.get("simpleText").and_then(|s| s.as_str()).map(str::to_string).or_else(|| {
+                                t.get("runs").and_then(|r| r.as_array()).map(|runs| {
+                                    runs.iter()
+                                        .filter_map(|r| r.get("text").and_then(|s| s.as_str()))
+                                        .collect::<String>()
+                                })
+                            })
+                        })
+                        .unwrap_or_default()
+                };
+                out.push(Notification {
+                    message: text("shortMessage"),
+                    sent: text("sentTimeText"),
+                    thumbnail: n
+                        .pointer("/videoThumbnail/thumbnails")
+                        .and_then(|t| t.as_array())
+                        .and_then(|t| t.last())
+                        .and_then(|t| t.get("url"))
+                        .and_then(|u| u.as_str())
+                        .map(str::to_string),
+                    video_id: n
+                        .pointer("/navigationEndpoint/watchEndpoint/videoId")
+                        .and_then(|v| v.as_str())
+                        .map(str::to_string),
+                    read: n.get("read").and_then(|r| r.as_bool()).unwrap_or(false),
+                });
+                return;
+            }
+            for value in map.values() {
+                collect_notifications(value, out, limit);
+            }
+        }
+        serde_json::Value::Array(items) => {
+            for item in items {
+                collect_notifications(item, out, limit);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/get_notifications.php",
+    params(
+        ("token" = String, Query, description = "OAuth refresh token"),
+        ("count" = Option<usize>, Query, description = "Notifications to list; 0 only returns unread_count (default: 20)")
+    ),
+    responses(
+        (status = 200, description = "Unread count and the latest notifications of the signed-in account", body = NotificationsResponse),
+        (status = 400, description = "Invalid request"),
+        (status = 401, description = "Authentication error"),
+        (status = 502, description = "InnerTube error")
+    )
+)]
+pub async fn get_notifications(
+    payload: web::Query<NotificationsRequest>,
+    auth_config: web::Data<AuthConfig>,
+) -> impl Responder {
+    let request = payload.into_inner();
+    let access_token = match obtain_access_token(&request.token, &auth_config).await {
+        Ok(token) => token,
+        Err(err) => return err,
+    };
+    let client = crate::http_client::shared();
+    let unread_count = match innertube_notifications(&client, &access_token, "get_unseen_count", json!({})).await {
+        Ok(body) => unseen_count(&body),
+        Err(err) => return error_json(ActixStatusCode::BAD_GATEWAY, err),
+    };
+    let limit = request.count.unwrap_or(20).min(100);
+    let mut notifications = Vec::new();
+    if limit > 0 {
+        let menu = innertube_notifications(
+            &client,
+            &access_token,
+            "get_notification_menu",
+            json!({ "notificationsMenuRequestType": "NOTIFICATIONS_MENU_REQUEST_TYPE_INBOX" }),
+        )
+        .await;
+        match menu {
+            Ok(body) => collect_notifications(&body, &mut notifications, limit),
+            Err(err) => return error_json(ActixStatusCode::BAD_GATEWAY, err),
+        }
+    }
+    HttpResponse::Ok().json(NotificationsResponse {
+        unread_count,
+        notifications,
+    })
+}
@@ -1,7 +1,6 @@
 use actix_web::{web, HttpRequest, HttpResponse, Responder};
 use html_escape::decode_html_entities;
 use regex::Regex;
-use reqwest::Client;
 use serde::Serialize;
 use std::collections::{HashMap, HashSet};
 use utoipa::ToSchema;
@@ -9,19 +8,12 @@ use uuid::Uuid;
 
 use crate::config::Config;
 use crate::routes::auth::{AuthConfig, TokenStore};
+use crate::routes::base_url;
+use crate::routes::messages::Msg;
 use crate::routes::oauth::refresh_access_token;
 use std::fs;
-fn base_url(req: &HttpRequest, config: &crate::config::Config) -> String {
-    if !config.server.main_url.is_empty() {
-        return config.server.main_url.clone();
-    }
-    let info = req.connection_info();
-    let scheme = info.scheme();
-    let host = info.host();
-    format!("{}://{}/", scheme, host.trim_end_matches('/'))
-}
 
-fn mask_key(key: &str) -> String {
+pub(crate) fn mask_key(key: &str) -> String {
     let trimmed = key.trim();
     if trimmed.len() <= 6 {
         return "***".to_string();
@@ -52,28 +44,23 @@ fn generate_cpn() -> String {
     out
 }
 
-async fn is_key_valid(client: &Client, key: &str) -> bool {
-    let trimmed = key.trim();
-    if trimmed.is_empty() {
-        return false;
-    }
-
-    let url = format!(
-        "https://www.googleapis.com/youtube/v3/videos?part=id&id=dQw4w9WgXcQ&key={}",
-        trimmed
-    );
-
-    matches!(client.get(&url).send().await, Ok(resp) if resp.status().is_success())
-}
-
 #[utoipa::path(
     get,
     path = "/check_api_keys",
+    params(
+        ("admin_key" = Option<String>, Query, description = "Admin key (or X-Admin-Key header)")
+    ),
     responses(
-        (status = 200, description = "API key health check")
+        (status = 200, description = "API key health check"),
+        (status = 401, description = "Admin key required")
     )
 )]
-pub async fn check_api_keys() -> impl Responder {
+pub async fn check_api_keys(req: HttpRequest, data: web::Data<crate::AppState>) -> impl Responder {
+    if !crate::routes::admin::is_admin(&req, &data.config) {
+        return HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": Msg::AdminKeyRequired.text()
+        }));
+    }
     let path = "config.yml";
     let mut config = match crate::config::Config::from_file(path) {
         Ok(c) => c,
@@ -92,11 +79,13 @@ pub async fn check_api_keys() -> impl Responder {
         }));
     }
 
-    let client = Client::new();
+    let client = crate::http_client::shared();
     let original_keys = config.api.keys.active.clone();
     let mut working_keys: Vec<String> = Vec::with_capacity(original_keys.len());
     let mut failed_keys: Vec<String> = Vec::new();
     let mut failed_set: HashSet<String> = HashSet::new();
+    // Keys we could not check (Data API unreachable) stay active; they are not known to be bad.
+    let mut unknown_keys: Vec<String> = Vec::new();
 
     for key in original_keys.iter() {
         let normalized = key.trim().to_string();
@@ -107,7 +96,11 @@ pub async fn check_api_keys() -> impl Responder {
             continue;
         }
 
-        if is_key_valid(&client, &normalized).await {
+        let status = crate::check::data_api_key_status(&client, &normalized).await;
+        if status.is_usable() {
+            working_keys.push(normalized);
+        } else if matches!(status, crate::check::KeyStatus::Unreachable(_)) {
+            unknown_keys.push(normalized.clone());
             working_keys.push(normalized);
         } else if failed_set.insert(normalized.clone()) {
             failed_keys.push(normalized);
@@ -134,12 +127,14 @@ pub async fn check_api_keys() -> impl Responder {
             "error": e
         }));
     }
+    crate::routes::api_keys::set_api_keys(config.api.keys.clone());
 
     let masked_failed: Vec<String> = failed_keys.iter().map(|k| mask_key(k)).collect();
 
     HttpResponse::Ok().json(serde_json::json!({
         "checked": checked,
         "failed": masked_failed,
+        "unknown": unknown_keys.iter().map(|k| mask_key(k)).collect::<Vec<_>>(),
         "active": config.api.keys.active.len()
     }))
 }
@@ -147,11 +142,20 @@ pub async fn check_api_keys() -> impl Responder {
 #[utoipa::path(
     get,
     path = "/check_failed_api_keys",
+    params(
+        ("admin_key" = Option<String>, Query, description = "Admin key (or X-Admin-Key header)")
+    ),
     responses(
-        (status = 200, description = "Re-check non-working API keys")
+        (status = 200, description = "Re-check non-working API keys"),
+        (status = 401, description = "Admin key required")
     )
 )]
-pub async fn check_failed_api_keys() -> impl Responder {
+pub async fn check_failed_api_keys(req: HttpRequest, data: web::Data<crate::AppState>) -> impl Responder {
+    if !crate::routes::admin::is_admin(&req, &data.config) {
+        return HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": Msg::AdminKeyRequired.text()
+        }));
+    }
     let path = "config.yml";
     let mut config = match crate::config::Config::from_file(path) {
         Ok(c) => c,
@@ -169,9 +173,10 @@ pub async fn check_failed_api_keys() -> impl Responder {
         }));
     }
 
-    let client = Client::new();
+    let client = crate::http_client::shared();
     let mut revived_keys: Vec<String> = Vec::new();
     let mut still_failed_keys: Vec<String> = Vec::new();
+    let mut unknown_keys: Vec<String> = Vec::new();
 
     for key in config.api.keys.disabled.iter() {
         let normalized = key.trim().to_string();
@@ -181,9 +186,13 @@ pub async fn check_failed_api_keys() -> impl Responder {
             continue;
         }
 
-        if is_key_valid(&client, &normalized).await {
+        let status = crate::check::data_api_key_status(&client, &normalized).await;
+        if status.is_usable() {
             revived_keys.push(normalized);
         } else {
+            if matches!(status, crate::check::KeyStatus::Unreachable(_)) {
+                unknown_keys.push(normalized.clone());
+            }
             still_failed_keys.push(normalized);
         }
     }
@@ -203,11 +212,13 @@ pub async fn check_failed_api_keys() -> impl Responder {
             "error": e
         }));
     }
+    crate::routes::api_keys::set_api_keys(config.api.keys.clone());
 
     HttpResponse::Ok().json(serde_json::json!({
         "checked": revived_keys.len() + still_failed_keys.len(),
         "revived": revived_keys.iter().map(|k| mask_key(k)).collect::<Vec<_>>(),
         "still_failed": still_failed_keys.iter().map(|k| mask_key(k)).collect::<Vec<_>>(),
+        "unknown": unknown_keys.iter().map(|k| mask_key(k)).collect::<Vec<_>>(),
         "active": config.api.keys.active.len()
     }))
 }
@@ -372,7 +383,7 @@ async fn fetch_history_page(
     continuation: Option<String>,
     config: &crate::config::Config,
 ) -> Option<serde_json::Value> {
-    let client = Client::new();
+    let client = crate::http_client::shared();
     let mut payload = serde_json::json!({
         "context": {
             "client": {
@@ -388,10 +399,10 @@ async fn fetch_history_page(
     if let Some(cont) = continuation {
         payload["continuation"] = serde_json::Value::String(cont);
     }
-    let url = format!(
-        "https://www.youtube.com/youtubei/v1/browse?key={}",
+    let url = crate::endpoints::innertube(&format!(
+        "browse?key={}",
         config.get_api_key_rotated()
-    );
+    ));
     let res = client
         .post(&url)
         .header("Authorization", format!("Bearer {}", access_token))
@@ -603,6 +614,43 @@ pub async fn fetch_history_for_token(
     videos
 }
 
+/// History entries looked at by `recently_watched`.
+const RECENTLY_WATCHED_COUNT: usize = 100;
+/// How long `recently_watched` reuses an account's history; a walk through it takes several
+/// InnerTube calls, too many for every home page load.
+const RECENTLY_WATCHED_TTL: std::time::Duration = std::time::Duration::from_secs(600);
+
+type WatchedCache = std::sync::Mutex<lru::LruCache<String, (std::time::Instant, std::sync::Arc<HashSet<String>>)>>;
+
+fn watched_cache() -> &'static WatchedCache {
+    static CACHE: std::sync::OnceLock<WatchedCache> = std::sync::OnceLock::new();
+    CACHE.get_or_init(|| std::sync::Mutex::new(lru::LruCache::new(std::num::NonZeroUsize::new(256).unwrap())))
+}
+
+/// Ids of the account's most recently watched videos, cached per token. Empty on any error.
+pub async fn recently_watched(
+    refresh_token: &str,
+    auth_config: &AuthConfig,
+    config: &crate::config::Config,
+) -> std::sync::Arc<HashSet<String>> {
+    let key = crate::routes::audit::token_session(refresh_token);
+    let cached = watched_cache().lock().ok().and_then(|mut cache| {
+        cache
+            .get(&key)
+            .filter(|(at, _)| at.elapsed() < RECENTLY_WATCHED_TTL)
+            .map(|(_, ids)| ids.clone())
+    });
+    if let Some(ids) = cached {
+        return ids;
+    }
+    let history = fetch_history_for_token(refresh_token, auth_config, config, "", RECENTLY_WATCHED_COUNT).await;
+    let ids = std::sync::Arc::new(history.into_iter().map(|item| item.video_id).collect::<HashSet<_>>());
+    if let Ok(mut cache) = watched_cache().lock() {
+        cache.put(key, (std::time::Instant::now(), ids.clone()));
+    }
+    ids
+}
+
 /// Fetches personalized recommendations for a refresh token. Returns None on any error.
 pub async fn fetch_recommendations_for_token(
     refresh_token: &str,
@@ -615,7 +663,7 @@ pub async fn fetch_recommendations_for_token(
         .await
         .ok()?;
     let api_key = config.get_innertube_key()?;
-    let client = Client::new();
+    let client = crate::http_client::shared();
     let payload = serde_json::json!({
         "context": {
             "client": {
@@ -635,7 +683,7 @@ pub async fn fetch_recommendations_for_token(
         },
         "browseId": "FEwhat_to_watch"
     });
-    let url = format!("https://www.youtube.com/youtubei/v1/browse?key={}", api_key);
+    let url = crate::endpoints::innertube(&format!("browse?key={}", api_key));
     let response = client
         .post(&url)
         .header("Authorization", format!("Bearer {}", access_token))
@@ -656,7 +704,8 @@ pub async fn fetch_recommendations_for_token(
     path = "/get_recommendations.php",
     params(
         ("token" = String, Query, description = "Refresh token"),
-        ("count" = Option<i32>, Query, description = "How many recommendations to return (default: 50)")
+        ("count" = Option<i32>, Query, description = "How many recommendations to return (default: 50)"),
+        ("envelope" = Option<bool>, Query, description = "Return a ListResponse (items, next_page_token, total, warnings) instead of the legacy shape")
     ),
     responses(
         (status = 200, description = "Recommendations list", body = [RecommendationItem]),
@@ -701,6 +750,9 @@ pub async fn get_recommendations(
     )
     .await
     {
+        Some(recommendations) if crate::routes::envelope::requested(&req) => {
+            HttpResponse::Ok().json(crate::routes::envelope::ListResponse::new(recommendations))
+        }
         Some(recommendations) => HttpResponse::Ok().json(recommendations),
         None => HttpResponse::InternalServerError().json(serde_json::json!({
             "error": "Failed to get recommendations"
@@ -708,7 +760,11 @@ pub async fn get_recommendations(
     }
 }
 
-fn parse_subscriptions_from_browse(json_data: &serde_json::Value, base_trimmed: &str) -> Vec<SubscriptionItem> {
+fn parse_subscriptions_from_browse(
+    json_data: &serde_json::Value,
+    base_trimmed: &str,
+    config: &crate::config::Config,
+) -> Vec<SubscriptionItem> {
     let mut subs = Vec::new();
     if let Some(tabs) = json_data
         .pointer("/contents/tvBrowseRenderer/content/tvSecondaryNavRenderer/sections/0/tvSecondaryNavSectionRenderer/tabs")
@@ -744,7 +800,7 @@ fn parse_subscriptions_from_browse(json_data: &serde_json::Value, base_trimmed:
                 subs.push(SubscriptionItem {
                     channel_id: channel_id.to_string(),
                     title: username.to_string(),
-                    thumbnail: thumb_url.to_string(),
+                    thumbnail: crate::routes::video::image_proxy_url(config, base_trimmed, &thumb_url),
                     local_thumbnail: format!("{}/channel_icon/{}", base_trimmed, encoded_thumb),
                     profile_url: format!("{}/get_author_videos.php?author={}", base_trimmed, username),
                 });
@@ -765,7 +821,7 @@ pub async fn fetch_subscriptions_for_token(
         Ok(t) => t,
         Err(_) => return Vec::new(),
     };
-    let client = Client::new();
+    let client = crate::http_client::shared();
     let payload = serde_json::json!({
         "context": {
             "client": {
@@ -778,10 +834,10 @@ pub async fn fetch_subscriptions_for_token(
         },
         "browseId": "FEsubscriptions"
     });
-    let url = format!(
-        "https://www.youtube.com/youtubei/v1/browse?key={}",
+    let url = crate::endpoints::innertube(&format!(
+        "browse?key={}",
         config.get_api_key_rotated()
-    );
+    ));
     let Ok(response) = client
         .post(&url)
         .header("Authorization", format!("Bearer {}", access_token))
@@ -794,7 +850,7 @@ pub async fn fetch_subscriptions_for_token(
     let Ok(json_data) = response.json::<serde_json::Value>().await else {
         return Vec::new();
     };
-    parse_subscriptions_from_browse(&json_data, base_trimmed)
+    parse_subscriptions_from_browse(&json_data, base_trimmed, config)
 }
 
 #[utoipa::path(
@@ -836,13 +892,13 @@ pub async fn get_subscriptions(
         Ok(t) => t,
         Err(e) => {
             return HttpResponse::Unauthorized().json(serde_json::json!({
-                "error": "Invalid refresh token",
+                "error": Msg::InvalidRefreshToken.text(),
                 "details": e
             }));
         }
     };
 
-    let client = Client::new();
+    let client = crate::http_client::shared();
     let payload = serde_json::json!({
         "context": {
             "client": {
@@ -856,10 +912,10 @@ pub async fn get_subscriptions(
         "browseId": "FEsubscriptions"
     });
 
-    let url = format!(
-        "https://www.youtube.com/youtubei/v1/browse?key={}",
+    let url = crate::endpoints::innertube(&format!(
+        "browse?key={}",
         data.config.get_api_key_rotated()
-    );
+    ));
 
     let res = client
         .post(url)
@@ -871,7 +927,10 @@ pub async fn get_subscriptions(
     match res {
         Ok(response) => match response.json::<serde_json::Value>().await {
             Ok(json_data) => {
-                let subs = parse_subscriptions_from_browse(&json_data, base_trimmed);
+                if let Some(err) = crate::routes::upstream::error_response("InnerTube browse", &json_data) {
+                    return err;
+                }
+                let subs = parse_subscriptions_from_browse(&json_data, base_trimmed, &data.config);
                 HttpResponse::Ok().json(SubscriptionsResponse {
                     status: "success".to_string(),
                     count: subs.len(),
@@ -881,7 +940,7 @@ pub async fn get_subscriptions(
             Err(e) => {
                 crate::log::info!("Error parsing subscriptions: {}", e);
                 HttpResponse::InternalServerError().json(serde_json::json!({
-                    "error": "Failed to parse response"
+                    "error": Msg::FailedToParseResponse.text()
                 }))
             }
         },
@@ -903,10 +962,7 @@ pub async fn get_subscriptions_session(
 ) -> impl Responder {
     let base = base_url(&req, &data.config);
     let base_trimmed = base.trim_end_matches('/');
-    let refresh_token = req
-        .cookie("session_id")
-        .and_then(|c| token_store.get_token(c.value()))
-        .filter(|t| !t.is_empty() && !t.starts_with("Error"));
+    let refresh_token = token_store.session_token(&req);
     let subscriptions = match refresh_token {
         Some(ref token) => {
             fetch_subscriptions_for_token(token, &auth_config, &data.config, base_trimmed).await
@@ -924,7 +980,8 @@ pub async fn get_subscriptions_session(
     path = "/get_history.php",
     params(
         ("token" = String, Query, description = "Refresh token"),
-        ("count" = Option<i32>, Query, description = "Number of videos to return (default: 50)")
+        ("count" = Option<i32>, Query, description = "Number of videos to return (default: 50)"),
+        ("envelope" = Option<bool>, Query, description = "Return a ListResponse (items, next_page_token, total, warnings) instead of the legacy shape")
     ),
     responses(
         (status = 200, description = "Watch history", body = [HistoryItem]),
@@ -964,7 +1021,7 @@ pub async fn get_history(
         Ok(t) => t,
         Err(e) => {
             return HttpResponse::Unauthorized().json(serde_json::json!({
-                "error": "Invalid refresh token",
+                "error": Msg::InvalidRefreshToken.text(),
                 "details": e
             }));
         }
@@ -989,6 +1046,9 @@ pub async fn get_history(
         continuation = next;
     }
 
+    if crate::routes::envelope::requested(&req) {
+        return HttpResponse::Ok().json(crate::routes::envelope::ListResponse::new(videos));
+    }
     HttpResponse::Ok().json(videos)
 }
 
@@ -1033,7 +1093,8 @@ fn extract_feedback_token(player_body: &str) -> Option<String> {
     ),
     responses(
         (status = 200, description = "Marked as watched"),
-        (status = 400, description = "Missing parameters")
+        (status = 400, description = "Missing parameters"),
+        (status = 403, description = "Token is scoped without history writes")
     )
 )]
 pub async fn mark_video_watched(
@@ -1066,12 +1127,18 @@ pub async fn mark_video_watched(
             }));
         }
     };
+    if let Some(resp) = crate::routes::token_scopes::deny(
+        &refresh_token,
+        crate::routes::token_scopes::Permission::HistoryWrite,
+    ) {
+        return resp;
+    }
 
     let access_token = match refresh_access_token(&refresh_token, &auth_config).await {
         Ok(t) => t,
         Err(e) => {
             return HttpResponse::Unauthorized().json(serde_json::json!({
-                "error": "Invalid refresh token",
+                "error": Msg::InvalidRefreshToken.text(),
                 "details": e
             }));
         }
@@ -1081,11 +1148,11 @@ pub async fn mark_video_watched(
         Some(k) => k,
         None => {
             return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": "Missing innertube_key in config.yml"
+                "error": Msg::MissingInnertubeKey.text()
             }));
         }
     };
-    let client = Client::new();
+    let client = crate::http_client::shared();
     let cpn = generate_cpn();
     let user_agent = "com.google.android.youtube/19.14.37";
 
@@ -1123,10 +1190,10 @@ pub async fn mark_video_watched(
     for include_params in [false, true] {
         let player_payload = build_payload(include_params);
         let resp = client
-            .post(&format!(
-                "https://www.youtube.com/youtubei/v1/player?key={}",
+            .post(crate::endpoints::innertube(&format!(
+                "player?key={}",
                 api_key
-            ))
+            )))
             .header("Authorization", format!("Bearer {}", access_token))
             .header("Content-Type", "application/json")
             .header("User-Agent", user_agent)
@@ -1184,10 +1251,10 @@ pub async fn mark_video_watched(
     });
 
     let feedback_resp = client
-        .post(&format!(
-            "https://www.youtube.com/youtubei/v1/feedback?key={}",
+        .post(crate::endpoints::innertube(&format!(
+            "feedback?key={}",
             api_key
-        ))
+        )))
         .header("Authorization", format!("Bearer {}", access_token))
         .header("Content-Type", "application/json")
         .header("User-Agent", user_agent)
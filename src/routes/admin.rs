@@ -0,0 +1,191 @@
+//! Admin access via `server.admin_key`, and small admin-only endpoints.
+
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use serde::Serialize;
+use std::collections::HashMap;
+use utoipa::ToSchema;
+
+use crate::config::Config;
+use crate::routes::extractor::ClientStats;
+use crate::routes::messages::Msg;
+use crate::routes::playback_errors::PlaybackErrorSummary;
+use crate::routes::segment_cache::SegmentCacheStats;
+use crate::routes::transcode_cache::TranscodeCacheStats;
+
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Key sent by the client: `X-Admin-Key` header, else `admin_key` query parameter.
+fn presented_key(req: &HttpRequest) -> Option<String> {
+    if let Some(v) = req.headers().get("X-Admin-Key").and_then(|v| v.to_str().ok()) {
+        return Some(v.trim().to_string());
+    }
+    req.query_string()
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(k, _)| *k == "admin_key")
+        .map(|(_, v)| urlencoding::decode(v).map(|s| s.into_owned()).unwrap_or_else(|_| v.to_string()))
+}
+
+/// `true` when admin endpoints are enabled and the request carries the right key.
+pub fn is_admin(req: &HttpRequest, config: &Config) -> bool {
+    let expected = config.server.admin_key.trim();
+    if expected.is_empty() {
+        return false;
+    }
+    presented_key(req)
+        .map(|key| constant_time_eq(key.as_bytes(), expected.as_bytes()))
+        .unwrap_or(false)
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CacheStatsResponse {
+    pub transcodes: TranscodeCacheStats,
+    pub segments: SegmentCacheStats,
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/cache/stats",
+    params(
+        ("admin_key" = Option<String>, Query, description = "Admin key (or X-Admin-Key header)")
+    ),
+    responses(
+        (status = 200, description = "Disk cache usage and hit counters", body = CacheStatsResponse),
+        (status = 401, description = "Admin key required")
+    )
+)]
+pub async fn cache_stats(req: HttpRequest, data: web::Data<crate::AppState>) -> impl Responder {
+    let config = &data.config;
+    if !is_admin(&req, config) {
+        return HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": Msg::AdminKeyRequired.text()
+        }));
+    }
+    let transcodes = config.cache.transcodes.clone();
+    let transcodes = web::block(move || crate::routes::transcode_cache::stats(&transcodes)).await;
+    match transcodes {
+        Ok(transcodes) => HttpResponse::Ok().json(CacheStatsResponse {
+            transcodes,
+            segments: crate::routes::segment_cache::stats(&config.cache.segments),
+        }),
+        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to read cache stats",
+            "details": e.to_string()
+        })),
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct AdminStatsResponse {
+    pub playback_errors: PlaybackErrorSummary,
+    /// Native extractor outcomes per InnerTube client and video type.
+    pub extractor: Vec<ClientStats>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/stats",
+    params(
+        ("days" = Option<u32>, Query, description = "Window in days (default 7)"),
+        ("admin_key" = Option<String>, Query, description = "Admin key (or X-Admin-Key header)")
+    ),
+    responses(
+        (status = 200, description = "Playback error reports by stage, video, device and code, and extractor client success rates", body = AdminStatsResponse),
+        (status = 401, description = "Admin key required")
+    )
+)]
+pub async fn admin_stats(
+    req: HttpRequest,
+    query: web::Query<HashMap<String, String>>,
+    data: web::Data<crate::AppState>,
+) -> impl Responder {
+    let config = &data.config;
+    if !is_admin(&req, config) {
+        return HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": Msg::AdminKeyRequired.text()
+        }));
+    }
+    let days = query
+        .get("days")
+        .and_then(|d| d.parse::<u32>().ok())
+        .unwrap_or(7)
+        .clamp(1, 365);
+    HttpResponse::Ok().json(AdminStatsResponse {
+        playback_errors: crate::routes::playback_errors::summary(config, days),
+        extractor: crate::routes::extractor::stats(),
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/ytdlp/version",
+    params(
+        ("refresh" = Option<bool>, Query, description = "Ask GitHub for the latest release now instead of using the last check"),
+        ("admin_key" = Option<String>, Query, description = "Admin key (or X-Admin-Key header)")
+    ),
+    responses(
+        (status = 200, description = "Installed and latest yt-dlp versions", body = crate::check::YtDlpStatus),
+        (status = 401, description = "Admin key required")
+    )
+)]
+pub async fn ytdlp_version(
+    req: HttpRequest,
+    query: web::Query<HashMap<String, String>>,
+    data: web::Data<crate::AppState>,
+) -> impl Responder {
+    let config = &data.config;
+    if !is_admin(&req, config) {
+        return HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": Msg::AdminKeyRequired.text()
+        }));
+    }
+    let refresh = query.get("refresh").is_some_and(|v| v == "true" || v == "1");
+    if refresh || !crate::check::yt_dlp_release_checked() {
+        // A failed check is reported in last_error
+        let _ = crate::check::check_yt_dlp_release().await;
+    }
+    match web::block(crate::check::yt_dlp_status).await {
+        Ok(status) => HttpResponse::Ok().json(status),
+        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to read the yt-dlp version",
+            "details": e.to_string()
+        })),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/ytdlp/update",
+    params(
+        ("admin_key" = Option<String>, Query, description = "Admin key (or X-Admin-Key header)")
+    ),
+    responses(
+        (status = 200, description = "Latest release installed into assets/", body = crate::check::YtDlpStatus),
+        (status = 401, description = "Admin key required"),
+        (status = 502, description = "Download failed or the new binary didn't run; the old one is kept")
+    )
+)]
+pub async fn ytdlp_update(req: HttpRequest, data: web::Data<crate::AppState>) -> impl Responder {
+    let config = &data.config;
+    if !is_admin(&req, config) {
+        return HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": Msg::AdminKeyRequired.text()
+        }));
+    }
+    let _ = crate::check::check_yt_dlp_release().await;
+    if let Err(e) = crate::check::update_yt_dlp().await {
+        return HttpResponse::BadGateway().json(serde_json::json!({
+            "error": "Failed to update yt-dlp",
+            "details": e
+        }));
+    }
+    match web::block(crate::check::yt_dlp_status).await {
+        Ok(status) => HttpResponse::Ok().json(status),
+        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to read the yt-dlp version",
+            "details": e.to_string()
+        })),
+    }
+}
@@ -0,0 +1,310 @@
+//! Live Data API, InnerTube and OAuth credentials. Seeded from `config.yml` at startup and edited
+//! through /admin/keys without a restart; every change is written back to the file. Key
+//! rotation (`Config::get_api_key_rotated`, `get_innertube_key`) and OAuth calls read this set.
+
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use serde::{Deserialize, Serialize};
+use std::sync::RwLock;
+use utoipa::ToSchema;
+
+use crate::config::{ApiConfig, ApiKeysConfig, InnertubeConfig, OAuthConfig};
+use crate::routes::additional::mask_key as mask;
+use crate::routes::messages::Msg;
+
+const CONFIG_PATH: &str = "config.yml";
+
+#[derive(Clone)]
+pub struct LiveCredentials {
+    pub keys: ApiKeysConfig,
+    pub innertube: InnertubeConfig,
+    pub oauth: OAuthConfig,
+}
+
+static LIVE: RwLock<Option<LiveCredentials>> = RwLock::new(None);
+
+pub fn init(api: &ApiConfig) {
+    if let Ok(mut live) = LIVE.write() {
+        *live = Some(LiveCredentials {
+            keys: api.keys.clone(),
+            innertube: api.innertube.clone(),
+            oauth: api.oauth.clone(),
+        });
+    }
+}
+
+/// Runs `f` on the live set; `None` before `init`.
+pub fn with_live<T>(f: impl FnOnce(&LiveCredentials) -> T) -> Option<T> {
+    LIVE.read().ok()?.as_ref().map(f)
+}
+
+/// Replaces the live Data API key lists (used by /check_api_keys after it re-validates them).
+pub fn set_api_keys(keys: ApiKeysConfig) {
+    if let Ok(mut live) = LIVE.write() {
+        if let Some(live) = live.as_mut() {
+            live.keys = keys;
+        }
+    }
+}
+
+/// OAuth client id and secret currently in effect, if changed at runtime.
+pub fn oauth_credentials() -> Option<(String, String)> {
+    with_live(|live| (live.oauth.client_id.clone(), live.oauth.client_secret.clone()))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct KeyChange {
+    /// `data_api`, `innertube` or `oauth`.
+    pub kind: String,
+    /// data_api / innertube: add, disable, enable, remove (soft), restore (data_api), purge.
+    /// oauth: set or remove.
+    pub action: String,
+    #[serde(default)]
+    pub key: String,
+    #[serde(default)]
+    pub client_id: String,
+    #[serde(default)]
+    pub client_secret: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct KeysResponse {
+    pub data_api_active: Vec<String>,
+    pub data_api_disabled: Vec<String>,
+    pub data_api_removed: Vec<String>,
+    pub innertube_keys: Vec<String>,
+    pub innertube_disabled: Vec<String>,
+    pub oauth_client_id: String,
+    pub oauth_configured: bool,
+}
+
+fn snapshot(live: &LiveCredentials) -> KeysResponse {
+    let masked = |keys: &[String]| keys.iter().map(|k| mask(k)).collect::<Vec<_>>();
+    KeysResponse {
+        data_api_active: masked(&live.keys.active),
+        data_api_disabled: masked(&live.keys.disabled),
+        data_api_removed: masked(&live.keys.removed),
+        innertube_keys: live
+            .innertube
+            .key
+            .iter()
+            .chain(live.innertube.extra_keys.iter())
+            .filter(|k| !k.trim().is_empty())
+            .map(|k| mask(k))
+            .collect(),
+        innertube_disabled: masked(&live.innertube.disabled_keys),
+        oauth_client_id: live.oauth.client_id.clone(),
+        oauth_configured: !live.oauth.client_id.trim().is_empty()
+            && !live.oauth.client_secret.trim().is_empty(),
+    }
+}
+
+/// Finds the full key the admin meant: an exact key, or the masked form returned by GET.
+fn resolve<'a>(lists: &[&'a Vec<String>], given: &str) -> Option<&'a String> {
+    let given = given.trim();
+    let all = || lists.iter().flat_map(|l| l.iter());
+    all()
+        .find(|k| k.trim() == given)
+        .or_else(|| {
+            let mut matches = all().filter(|k| mask(k) == given);
+            match (matches.next(), matches.next()) {
+                (Some(k), None) => Some(k),
+                _ => None,
+            }
+        })
+}
+
+fn push_unique(list: &mut Vec<String>, key: &str) {
+    if !list.iter().any(|k| k == key) {
+        list.push(key.to_string());
+    }
+}
+
+fn apply_data_api(keys: &mut ApiKeysConfig, action: &str, given: &str) -> Result<(), String> {
+    if action == "add" {
+        let key = given.trim();
+        if key.is_empty() {
+            return Err("key is required".to_string());
+        }
+        keys.removed.retain(|k| k != key);
+        keys.disabled.retain(|k| k != key);
+        push_unique(&mut keys.active, key);
+        return Ok(());
+    }
+    let key = resolve(&[&keys.active, &keys.disabled, &keys.removed], given)
+        .cloned()
+        .ok_or_else(|| "Unknown key".to_string())?;
+    match action {
+        "disable" => push_unique(&mut keys.disabled, &key),
+        "enable" => keys.disabled.retain(|k| *k != key),
+        "remove" => {
+            keys.active.retain(|k| *k != key);
+            keys.disabled.retain(|k| *k != key);
+            push_unique(&mut keys.removed, &key);
+        }
+        "restore" => {
+            keys.removed.retain(|k| *k != key);
+            push_unique(&mut keys.active, &key);
+        }
+        "purge" => {
+            keys.active.retain(|k| *k != key);
+            keys.disabled.retain(|k| *k != key);
+            keys.removed.retain(|k| *k != key);
+        }
+        other => return Err(format!("Unknown action '{}'", other)),
+    }
+    Ok(())
+}
+
+fn apply_innertube(innertube: &mut InnertubeConfig, action: &str, given: &str) -> Result<(), String> {
+    if action == "add" {
+        let key = given.trim();
+        if key.is_empty() {
+            return Err("key is required".to_string());
+        }
+        innertube.disabled_keys.retain(|k| k != key);
+        if innertube.key.as_deref().map(str::trim).unwrap_or("").is_empty() {
+            innertube.key = Some(key.to_string());
+        } else if innertube.key.as_deref() != Some(key) {
+            push_unique(&mut innertube.extra_keys, key);
+        }
+        return Ok(());
+    }
+    let primary: Vec<String> = innertube.key.iter().cloned().collect();
+    let key = resolve(&[&primary, &innertube.extra_keys, &innertube.disabled_keys], given)
+        .cloned()
+        .ok_or_else(|| "Unknown key".to_string())?;
+    match action {
+        "disable" | "remove" => push_unique(&mut innertube.disabled_keys, &key),
+        "enable" | "restore" => innertube.disabled_keys.retain(|k| *k != key),
+        "purge" => {
+            innertube.extra_keys.retain(|k| *k != key);
+            innertube.disabled_keys.retain(|k| *k != key);
+            if innertube.key.as_deref() == Some(key.as_str()) {
+                innertube.key = None;
+            }
+        }
+        other => return Err(format!("Unknown action '{}'", other)),
+    }
+    Ok(())
+}
+
+fn apply_oauth(oauth: &mut OAuthConfig, action: &str, change: &KeyChange) -> Result<(), String> {
+    match action {
+        "set" => {
+            if change.client_id.trim().is_empty() || change.client_secret.trim().is_empty() {
+                return Err("client_id and client_secret are required".to_string());
+            }
+            oauth.client_id = change.client_id.trim().to_string();
+            oauth.client_secret = change.client_secret.trim().to_string();
+        }
+        "remove" => {
+            oauth.client_id.clear();
+            oauth.client_secret.clear();
+        }
+        other => return Err(format!("Unknown action '{}'", other)),
+    }
+    Ok(())
+}
+
+fn unauthorized() -> HttpResponse {
+    HttpResponse::Unauthorized().json(serde_json::json!({
+        "error": Msg::AdminKeyRequired.text()
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/keys",
+    params(
+        ("admin_key" = Option<String>, Query, description = "Admin key (or X-Admin-Key header)")
+    ),
+    responses(
+        (status = 200, description = "Credentials in use (keys masked)", body = KeysResponse),
+        (status = 401, description = "Admin key required")
+    )
+)]
+pub async fn get_keys(req: HttpRequest, data: web::Data<crate::AppState>) -> impl Responder {
+    if !crate::routes::admin::is_admin(&req, &data.config) {
+        return unauthorized();
+    }
+    match with_live(snapshot) {
+        Some(keys) => HttpResponse::Ok().json(keys),
+        None => HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "error": "Credentials not loaded"
+        })),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/keys",
+    request_body = KeyChange,
+    params(
+        ("admin_key" = Option<String>, Query, description = "Admin key (or X-Admin-Key header)")
+    ),
+    responses(
+        (status = 200, description = "Updated credentials, saved to config.yml", body = KeysResponse),
+        (status = 400, description = "Unknown kind, action or key"),
+        (status = 401, description = "Admin key required")
+    )
+)]
+pub async fn update_keys(
+    req: HttpRequest,
+    data: web::Data<crate::AppState>,
+    body: web::Json<KeyChange>,
+) -> impl Responder {
+    if !crate::routes::admin::is_admin(&req, &data.config) {
+        return unauthorized();
+    }
+    let change = body.into_inner();
+    let mut guard = match LIVE.write() {
+        Ok(guard) => guard,
+        Err(_) => {
+            return HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Credentials unavailable"
+            }));
+        }
+    };
+    let Some(live) = guard.as_mut() else {
+        return HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "error": "Credentials not loaded"
+        }));
+    };
+
+    let mut updated = live.clone();
+    let action = change.action.trim().to_ascii_lowercase();
+    let result = match change.kind.trim() {
+        "data_api" => apply_data_api(&mut updated.keys, &action, &change.key),
+        "innertube" => apply_innertube(&mut updated.innertube, &action, &change.key),
+        "oauth" => apply_oauth(&mut updated.oauth, &action, &change),
+        other => Err(format!("Unknown kind '{}'", other)),
+    };
+    if let Err(e) = result {
+        return HttpResponse::BadRequest().json(serde_json::json!({ "error": e }));
+    }
+
+    // Written from the file on disk so unrelated edits made there since startup survive
+    let saved = crate::config::Config::from_file(CONFIG_PATH)
+        .map_err(|e| format!("Failed to load config: {}", e))
+        .and_then(|mut config| {
+            config.api.keys = updated.keys.clone();
+            config.api.innertube = updated.innertube.clone();
+            config.api.oauth.client_id = updated.oauth.client_id.clone();
+            config.api.oauth.client_secret = updated.oauth.client_secret.clone();
+            config.persist(CONFIG_PATH)?;
+            Ok(config.api.keys)
+        });
+    match saved {
+        Ok(keys) => {
+            // `persist` tidies (trims, dedups) the lists; keep the live set identical
+            updated.keys = keys;
+            *live = updated;
+            log::info!("Credentials changed via /admin/keys: {} {}", change.kind, action);
+            HttpResponse::Ok().json(snapshot(live))
+        }
+        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to save config",
+            "details": e
+        })),
+    }
+}
@@ -0,0 +1,218 @@
+//! Auth audit log (`audit.*`): OAuth logins, device links, failed token refreshes and every
+//! /actions/* call, with time, session and client IP, so operators of shared instances can see
+//! who liked or subscribed to what. Stored in `profiles.db_path`, read through /admin/audit.
+//! Refresh tokens are never written; a session is the `session_id` cookie or a short token
+//! fingerprint.
+
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use rusqlite::params;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use utoipa::ToSchema;
+
+use crate::config::AuditConfig;
+use crate::db;
+use crate::routes::messages::Msg;
+
+pub const OAUTH_LOGIN: &str = "oauth_login";
+pub const DEVICE_LOGIN: &str = "device_login";
+pub const DEVICE_LINK: &str = "device_link";
+pub const TOKEN_REFRESH_FAILED: &str = "token_refresh_failed";
+/// A stored refresh token the background validation found revoked and removed.
+pub const TOKEN_REVOKED: &str = "token_revoked";
+/// Prefix of /actions/* events: `action:subscribe`, `action:rate`, ...
+pub const ACTION: &str = "action";
+
+static SETTINGS: OnceLock<AuditConfig> = OnceLock::new();
+
+#[derive(Serialize, ToSchema)]
+pub struct AuditEntry {
+    pub id: i64,
+    /// Unix seconds.
+    pub at: i64,
+    pub event: String,
+    pub session: String,
+    /// The connecting address, or the one a `server.trusted_proxies` proxy forwarded.
+    pub ip: String,
+    pub ok: bool,
+    pub detail: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct AuditResponse {
+    pub enabled: bool,
+    pub entries: Vec<AuditEntry>,
+}
+
+pub fn init(config: &AuditConfig) {
+    let _ = SETTINGS.set(config.clone());
+}
+
+fn enabled() -> bool {
+    SETTINGS.get().map(|s| s.enabled).unwrap_or(false) && db::is_ready()
+}
+
+/// `token:` plus the first 12 hex digits of SHA-256, enough to tell accounts apart in the log.
+pub fn token_session(refresh_token: &str) -> String {
+    let digest = format!("{:x}", Sha256::digest(refresh_token.trim().as_bytes()));
+    format!("token:{}", &digest[..12])
+}
+
+/// `session_id` cookie of the request, else the token fingerprint.
+pub fn session_of(req: &HttpRequest, refresh_token: Option<&str>) -> String {
+    req.cookie("session_id")
+        .map(|c| format!("session:{}", c.value()))
+        .or_else(|| refresh_token.map(token_session))
+        .unwrap_or_default()
+}
+
+/// Appends an entry; a no-op when auditing is off. Failures are logged, never returned.
+pub fn record(req: Option<&HttpRequest>, event: &str, session: &str, ok: bool, detail: &str) {
+    if !enabled() {
+        return;
+    }
+    let ip = req.map(crate::routes::bandwidth::client_ip).unwrap_or_default();
+    write(event, session, &ip, ok, detail);
+}
+
+fn write(event: &str, session: &str, ip: &str, ok: bool, detail: &str) {
+    let retention_days = SETTINGS.get().map(|s| s.retention_days).unwrap_or(90);
+    let detail: String = detail.chars().take(512).collect();
+    let now = db::now_secs();
+    let cutoff = now - i64::from(retention_days) * 86400;
+    let result = db::with_conn(|conn| {
+        conn.execute(
+            "INSERT INTO auth_audit (at, event, session, ip, ok, detail) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![now, event, session, ip, ok, detail],
+        )?;
+        conn.execute("DELETE FROM auth_audit WHERE at < ?1", params![cutoff])
+    });
+    if let Err(e) = result {
+        log::warn!("Failed to write audit entry {}: {}", event, e);
+    }
+}
+
+/// An /actions/* call seen on the way in, written once the response status is known (see the
+/// `wrap_fn` in main.rs), so every action is covered whichever way the handler returns.
+pub struct PendingAction {
+    event: String,
+    session: String,
+    ip: String,
+    detail: String,
+}
+
+pub fn begin_action(req: &HttpRequest) -> Option<PendingAction> {
+    let name = req.path().strip_prefix("/actions/")?.trim_end_matches('/');
+    if name.is_empty() || !enabled() {
+        return None;
+    }
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string())
+        .map(|q| q.into_inner())
+        .unwrap_or_default();
+    let token = query.get("token").map(|t| t.as_str()).filter(|t| !t.trim().is_empty());
+    let detail = ["channel", "video_id", "rating", "playlist_id"]
+        .iter()
+        .filter_map(|key| query.get(*key).map(|v| format!("{}={}", key, v)))
+        .collect::<Vec<_>>()
+        .join(" ");
+    Some(PendingAction {
+        event: format!("{}:{}", ACTION, name),
+        session: token
+            .map(token_session)
+            .unwrap_or_else(|| session_of(req, None)),
+        ip: crate::routes::bandwidth::client_ip(req),
+        detail,
+    })
+}
+
+impl PendingAction {
+    pub fn finish(self, status: u16) {
+        let detail = if self.detail.is_empty() {
+            format!("status={}", status)
+        } else {
+            format!("{} status={}", self.detail, status)
+        };
+        write(&self.event, &self.session, &self.ip, (200..400).contains(&status), &detail);
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/audit",
+    params(
+        ("days" = Option<u32>, Query, description = "Window in days (default 7)"),
+        ("event" = Option<String>, Query, description = "Only this event (e.g. oauth_login) or prefix (action)"),
+        ("session" = Option<String>, Query, description = "Only this session"),
+        ("limit" = Option<u32>, Query, description = "Newest entries to return (default 200, max 1000)"),
+        ("admin_key" = Option<String>, Query, description = "Admin key (or X-Admin-Key header)")
+    ),
+    responses(
+        (status = 200, description = "Audit entries, newest first", body = AuditResponse),
+        (status = 401, description = "Admin key required")
+    )
+)]
+pub async fn get_audit(
+    req: HttpRequest,
+    query: web::Query<HashMap<String, String>>,
+    data: web::Data<crate::AppState>,
+) -> impl Responder {
+    let config = &data.config;
+    if !crate::routes::admin::is_admin(&req, config) {
+        return HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": Msg::AdminKeyRequired.text()
+        }));
+    }
+    if !enabled() {
+        return HttpResponse::Ok().json(AuditResponse {
+            enabled: false,
+            entries: Vec::new(),
+        });
+    }
+    let days = query
+        .get("days")
+        .and_then(|d| d.parse::<u32>().ok())
+        .unwrap_or(7)
+        .clamp(1, 3650);
+    let limit = query
+        .get("limit")
+        .and_then(|l| l.parse::<u32>().ok())
+        .unwrap_or(200)
+        .clamp(1, 1000);
+    let since = db::now_secs() - i64::from(days) * 86400;
+    let event = query.get("event").cloned().unwrap_or_default();
+    let session = query.get("session").cloned().unwrap_or_default();
+
+    let result = db::with_conn(|conn| {
+        let mut stmt = conn.prepare(
+            "SELECT id, at, event, session, ip, ok, detail FROM auth_audit
+             WHERE at >= ?1
+               AND (?2 = '' OR event = ?2 OR event LIKE ?2 || ':%')
+               AND (?3 = '' OR session = ?3)
+             ORDER BY id DESC LIMIT ?4",
+        )?;
+        let rows = stmt.query_map(params![since, event, session, limit], |row| {
+            Ok(AuditEntry {
+                id: row.get(0)?,
+                at: row.get(1)?,
+                event: row.get(2)?,
+                session: row.get(3)?,
+                ip: row.get(4)?,
+                ok: row.get(5)?,
+                detail: row.get(6)?,
+            })
+        })?;
+        rows.collect::<rusqlite::Result<Vec<_>>>()
+    });
+    match result {
+        Ok(entries) => HttpResponse::Ok().json(AuditResponse {
+            enabled: true,
+            entries,
+        }),
+        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to read audit log",
+            "details": e
+        })),
+    }
+}
@@ -8,6 +8,22 @@ use uuid::Uuid;
 use base64::{Engine as _, engine::general_purpose};
 use reqwest;
 use actix_web::cookie::{Cookie, SameSite};
+use lru::LruCache;
+use sha2::{Digest, Sha256};
+use std::num::NonZeroUsize;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use crate::routes::messages::Msg;
+
+const ACCOUNT_CACHE_TTL: Duration = Duration::from_secs(5 * 60);
+
+type AccountCache = Mutex<LruCache<String, (Instant, AccountInfoResponse)>>;
+
+fn account_cache() -> &'static AccountCache {
+    static CACHE: OnceLock<AccountCache> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(LruCache::new(NonZeroUsize::new(256).unwrap())))
+}
 
 #[derive(Clone)]
 pub struct DeviceFlowData {
@@ -44,6 +60,31 @@ impl TokenStore {
         tokens.remove(session_id)
     }
 
+    /// (session_id, refresh token) of every signed-in browser session.
+    pub fn sessions(&self) -> Vec<(String, String)> {
+        let tokens = self.tokens.lock().unwrap();
+        tokens.iter().map(|(s, t)| (s.clone(), t.clone())).collect()
+    }
+
+    /// Refresh token of the browser session (`session_id` cookie), falling back to the persistent
+    /// login cookie after a restart; a token recovered that way is put back into the store.
+    pub fn session_token(&self, req: &HttpRequest) -> Option<String> {
+        let session = req.cookie("session_id");
+        if let Some(token) = session
+            .as_ref()
+            .and_then(|c| self.get_token(c.value()))
+            .filter(|t| !t.is_empty() && !t.starts_with("Error"))
+        {
+            return Some(token);
+        }
+        let token = crate::routes::login_cookie::read(req)
+            .filter(|t| !crate::routes::token_health::is_revoked(t))?;
+        if let Some(session) = session {
+            self.store_token(session.value().to_string(), token.clone());
+        }
+        Some(token)
+    }
+
     pub fn store_device_flow(&self, session_id: String, data: DeviceFlowData) {
         let mut flows = self.device_flows.lock().unwrap();
         flows.insert(session_id, data);
@@ -66,10 +107,17 @@ pub struct AuthConfig {
     pub client_secret: String,
     pub redirect_uri: String,
     pub scopes: Vec<String>,
-    pub youtube_api_key: String,
 }
 
-#[derive(Serialize, ToSchema)]
+impl AuthConfig {
+    /// OAuth client id and secret, following changes made through /admin/keys.
+    pub fn credentials(&self) -> (String, String) {
+        crate::routes::api_keys::oauth_credentials()
+            .unwrap_or_else(|| (self.client_id.clone(), self.client_secret.clone()))
+    }
+}
+
+#[derive(Serialize, Clone, ToSchema)]
 pub struct AccountInfoResponse {
     pub google_account: GoogleAccount,
     #[schema(nullable = true)]
@@ -147,7 +195,7 @@ struct AccountStateToken {
 }
 
 // Старые структуры оставляем для обратной совместимости, но они больше не используются
-#[derive(Serialize, ToSchema)]
+#[derive(Serialize, Clone, ToSchema)]
 pub struct GoogleAccount {
     #[schema(nullable = true)]
     pub id: Option<String>,
@@ -167,7 +215,7 @@ pub struct GoogleAccount {
     pub locale: Option<String>,
 }
 
-#[derive(Serialize, ToSchema)]
+#[derive(Serialize, Clone, ToSchema)]
 pub struct YouTubeChannel {
     #[schema(nullable = true)]
     pub id: Option<String>,
@@ -380,7 +428,7 @@ async fn get_tv_qr(
     };
 
     let response = client
-        .post(&format!("https://www.youtube.com/youtubei/v1/mdx/handoff?key={}", api_key))
+        .post(crate::endpoints::innertube(&format!("mdx/handoff?key={}", api_key)))
         .header("Content-Type", "application/json")
         .header("User-Agent", "Mozilla/5.0 (SMART-TV; Linux; Tizen 6.0)")
         .json(&payload)
@@ -440,7 +488,7 @@ pub fn get_auth_url(config: &AuthConfig, session_id: &str) -> String {
         access_type=offline&\
         prompt=consent&\
         state={}",
-        config.client_id,
+        urlencoding::encode(&config.credentials().0),
         redirect_uri,
         encoded_scope,
         session_id
@@ -460,14 +508,19 @@ pub async fn auth_login_page() -> impl Responder {
 }
 
 /// Redirects to Google OAuth; callback goes to /oauth/callback. Sets session_id cookie.
+/// `scope=` limits the token as on /auth.
 pub async fn auth_start(
     req: HttpRequest,
+    query: web::Query<HashMap<String, String>>,
     data: web::Data<AuthConfig>,
 ) -> impl Responder {
     let session_id = req
         .cookie("session_id")
         .map(|c| c.value().to_string())
         .unwrap_or_else(|| Uuid::new_v4().to_string());
+    if let Err(e) = crate::routes::token_scopes::set_pending(&session_id, query.get("scope")) {
+        return HttpResponse::ServiceUnavailable().json(serde_json::json!({ "error": e }));
+    }
     let auth_url = get_auth_url(&data, &session_id);
     let cookie = Cookie::build("session_id", session_id.clone())
         .path("/")
@@ -485,10 +538,12 @@ pub async fn auth_start(
     path = "/auth",
     params(
         ("check" = Option<String>, Query, description = "Check authentication status"),
-        ("type" = Option<String>, Query, description = "Type of authentication: 'pc' for user code, default is QR code")
+        ("type" = Option<String>, Query, description = "Type of authentication: 'pc' for user code, default is QR code"),
+        ("scope" = Option<String>, Query, description = "Limit the resulting token: read_only, or a comma list of actions, history")
     ),
     responses(
-        (status = 200, description = "QR code (base64) or refresh token or user code", body = String)
+        (status = 200, description = "QR code (base64) or refresh token or user code", body = String),
+        (status = 503, description = "scope= given but no database is open to keep it in")
     )
 )]
 pub async fn auth_handler(
@@ -496,6 +551,7 @@ pub async fn auth_handler(
     query: web::Query<HashMap<String, String>>,
     data: web::Data<AuthConfig>,
     token_store: web::Data<TokenStore>,
+    app: web::Data<crate::AppState>,
 ) -> impl Responder {
     let session_id = req.cookie("session_id")
         .map(|c| c.value().to_string())
@@ -503,6 +559,9 @@ pub async fn auth_handler(
     
     // Check if type=pc is specified to return user code instead of QR code
     let is_pc_type = query.get("type").map_or(false, |t| t == "pc");
+    if let Err(e) = crate::routes::token_scopes::set_pending(&session_id, query.get("scope")) {
+        return HttpResponse::ServiceUnavailable().json(serde_json::json!({ "error": e }));
+    }
     
     // Если передан refresh_token в заголовке, отдаем его
     if let Some(refresh_token_header) = req.headers().get("refresh_token") {
@@ -535,11 +594,12 @@ pub async fn auth_handler(
     // Если есть активный device flow, проверяем статус авторизации
     // (как в Python скрипте - при каждом запросе проверяется статус)
     if let Some(device_flow) = token_store.get_device_flow(&session_id) {
-        let client = reqwest::Client::new();
+        let client = crate::http_client::shared();
+        let (client_id, client_secret) = data.credentials();
         match check_device_token(
             &client,
-            &data.client_id,
-            &data.client_secret,
+            &client_id,
+            &client_secret,
             &device_flow.device_code,
         ).await {
             Ok(token_response) => {
@@ -547,14 +607,26 @@ pub async fn auth_handler(
                     // Токен получен - удаляем device flow и сохраняем токен
                     token_store.remove_device_flow(&session_id);
                     token_store.store_token(session_id.clone(), refresh_token.clone());
+                    crate::routes::token_scopes::claim(&session_id, &refresh_token);
+                    crate::routes::audit::record(
+                        Some(&req),
+                        crate::routes::audit::DEVICE_LOGIN,
+                        &format!("session:{}", session_id),
+                        true,
+                        "",
+                    );
                     let token_display = format!("Token: {}", html_escape::encode_text(&refresh_token));
                     let cookie = Cookie::build("session_id", session_id.clone())
                         .path("/")
                         .same_site(SameSite::Lax)
                         .http_only(false)
                         .finish();
-                    return HttpResponse::Ok()
-                        .insert_header(("Set-Cookie", cookie.to_string()))
+                    let mut response = HttpResponse::Ok();
+                    response.append_header(("Set-Cookie", cookie.to_string()));
+                    if let Some(login) = crate::routes::login_cookie::issue(&refresh_token) {
+                        response.append_header(("Set-Cookie", login));
+                    }
+                    return response
                         .content_type("text/html; charset=utf-8")
                         .body(format!("<ytreq>{}</ytreq>", token_display));
                 } else if let Some(error) = token_response.error {
@@ -599,12 +671,14 @@ pub async fn auth_handler(
     
     // Получение device code и QR (только если device flow еще не начат)
     let device_id = Uuid::new_v4().to_string();
-    let client = reqwest::Client::new();
+    let client = crate::http_client::shared();
     
-    match get_device_code(&client, &data.client_id, &device_id).await {
+    match get_device_code(&client, &data.credentials().0, &device_id).await {
         Ok(device_code_response) => {
             // Получаем QR код
-            match get_tv_qr(&client, &device_code_response.user_code, &data.youtube_api_key).await {
+            // the live key set, so keys changed through /admin/keys apply here too
+            let api_key = app.config.get_api_key_rotated();
+            match get_tv_qr(&client, &device_code_response.user_code, &api_key).await {
                 Ok(qr_bytes) => {
                     // Кодируем QR в base64
                     let qr_base64 = general_purpose::STANDARD.encode(&qr_bytes);
@@ -696,6 +770,7 @@ pub async fn auth_events(
     )
 )]
 pub async fn oauth_callback(
+    req: HttpRequest,
     query: web::Query<HashMap<String, String>>,
     data: web::Data<AuthConfig>,
     token_store: web::Data<TokenStore>,
@@ -718,18 +793,24 @@ pub async fn oauth_callback(
     
     let code = code.unwrap();
     let session_id = session_id.unwrap();
+    let audit_session = format!("session:{}", session_id);
+    let audit = |ok: bool, detail: &str| {
+        use crate::routes::audit;
+        audit::record(Some(&req), audit::OAUTH_LOGIN, &audit_session, ok, detail)
+    };
     
-    let client = reqwest::Client::new();
+    let client = crate::http_client::shared();
+    let (client_id, client_secret) = data.credentials();
     let params = [
         ("code", code.as_str()),
-        ("client_id", data.client_id.as_str()),
-        ("client_secret", data.client_secret.as_str()),
+        ("client_id", client_id.as_str()),
+        ("client_secret", client_secret.as_str()),
         ("redirect_uri", data.redirect_uri.as_str()),
         ("grant_type", "authorization_code"),
     ];
     
     let res = client
-        .post("https://oauth2.googleapis.com/token")
+        .post(crate::endpoints::oauth_token())
         .form(&params)
         .send()
         .await;
@@ -742,6 +823,8 @@ pub async fn oauth_callback(
                     Ok(token_data) => {
                         if let Some(refresh_token) = &token_data.refresh_token {
                             token_store.store_token(session_id.clone(), refresh_token.clone());
+                            crate::routes::token_scopes::claim(session_id, refresh_token);
+                            audit(true, "");
                             
                             let cookie = Cookie::build("session_id", session_id.clone())
                                 .path("/")
@@ -749,8 +832,12 @@ pub async fn oauth_callback(
                                 .http_only(false)
                                 .finish();
                             
-                            HttpResponse::Ok()
-                                .insert_header(("Set-Cookie", cookie.to_string()))
+                            let mut response = HttpResponse::Ok();
+                            response.append_header(("Set-Cookie", cookie.to_string()));
+                            if let Some(login) = crate::routes::login_cookie::issue(refresh_token) {
+                                response.append_header(("Set-Cookie", login));
+                            }
+                            response
                                 .content_type("text/html; charset=utf-8")
                                 .body(r#"
                                     <html>
@@ -765,6 +852,8 @@ pub async fn oauth_callback(
                                 "#)
                         } else {
                             token_store.store_token(session_id.clone(), token_data.access_token.clone());
+                            crate::routes::token_scopes::claim(session_id, &token_data.access_token);
+                            audit(true, "no refresh token");
                             
                             let cookie = Cookie::build("session_id", session_id.clone())
                                 .path("/")
@@ -790,6 +879,7 @@ pub async fn oauth_callback(
                     }
                     Err(_) => {
                         token_store.store_token(session_id.clone(), "Error: Failed to parse token response".to_string());
+                        audit(false, "Failed to parse token response");
                         HttpResponse::BadRequest()
                             .content_type("text/html; charset=utf-8")
                             .body(r#"
@@ -803,6 +893,7 @@ pub async fn oauth_callback(
                     }
                 }
             } else {
+                audit(false, &format!("Token endpoint returned {}", response.status()));
                 token_store.store_token(session_id.clone(), "Error: Failed to get token".to_string());
                 HttpResponse::BadRequest()
                     .content_type("text/html; charset=utf-8")
@@ -818,6 +909,7 @@ pub async fn oauth_callback(
         }
         Err(_) => {
             token_store.store_token(session_id.clone(), "Error: Network error".to_string());
+            audit(false, "Network error");
             HttpResponse::BadRequest()
                 .content_type("text/html; charset=utf-8")
                 .body(r#"
@@ -851,12 +943,10 @@ pub async fn account_info(
     token_store: web::Data<TokenStore>,
 ) -> impl Responder {
     // Token: from query ?token=... or from session (cookie session_id)
-    let refresh_token = query.get("token").cloned().or_else(|| {
-        req.cookie("session_id")
-            .map(|c| c.value().to_string())
-            .and_then(|session_id| token_store.get_token(&session_id))
-            .filter(|t| !t.is_empty() && !t.starts_with("Error"))
-    });
+    let refresh_token = query
+        .get("token")
+        .cloned()
+        .or_else(|| token_store.session_token(&req));
 
     if refresh_token.is_none() {
         return HttpResponse::Unauthorized()
@@ -867,17 +957,69 @@ pub async fn account_info(
     }
 
     let refresh_token = refresh_token.unwrap();
-    
-    let client = reqwest::Client::new();
+
+    // Получаем base URL для channel_icon из запроса
+    let base_url = req
+        .headers()
+        .get("host")
+        .and_then(|h| h.to_str().ok())
+        .map(|host| {
+            let scheme = req
+                .uri()
+                .scheme_str()
+                .unwrap_or("http");
+            format!("{}://{}", scheme, host)
+        })
+        .unwrap_or_else(|| {
+            // Fallback на localhost если не можем определить из запроса
+            "http://localhost:2823".to_string()
+        });
+
+    match account_for_token(&refresh_token, &data, &base_url).await {
+        Ok(response) => HttpResponse::Ok()
+            .insert_header(("Cache-Control", "no-store, no-cache, must-revalidate"))
+            .json(response),
+        Err(err) => err,
+    }
+}
+
+/// Account info for a refresh token, cached for `ACCOUNT_CACHE_TTL` (the navbar asks on every page).
+pub async fn account_for_token(
+    refresh_token: &str,
+    data: &AuthConfig,
+    base_url: &str,
+) -> Result<AccountInfoResponse, HttpResponse> {
+    let key = format!("{:x}|{}", Sha256::digest(refresh_token.as_bytes()), base_url);
+    if let Ok(mut cache) = account_cache().lock() {
+        if let Some((at, info)) = cache.get(&key) {
+            if at.elapsed() < ACCOUNT_CACHE_TTL {
+                return Ok(info.clone());
+            }
+        }
+    }
+    let info = fetch_account_info(refresh_token, data, base_url).await?;
+    if let Ok(mut cache) = account_cache().lock() {
+        cache.put(key, (Instant::now(), info.clone()));
+    }
+    Ok(info)
+}
+
+async fn fetch_account_info(
+    refresh_token: &str,
+    data: &AuthConfig,
+    base_url: &str,
+) -> Result<AccountInfoResponse, HttpResponse> {
+    let client = crate::http_client::shared();
+    let (client_id, client_secret) = data.credentials();
     let params = [
-        ("client_id", data.client_id.as_str()),
-        ("client_secret", data.client_secret.as_str()),
-        ("refresh_token", &refresh_token),
+        ("client_id", client_id.as_str()),
+        ("client_secret", client_secret.as_str()),
+        ("refresh_token", refresh_token),
         ("grant_type", "refresh_token"),
     ];
     
     let res = client
-        .post("https://oauth2.googleapis.com/token")
+        .post(crate::endpoints::oauth_token())
         .form(&params)
         .send()
         .await;
@@ -889,27 +1031,27 @@ pub async fn account_info(
                 match token_response {
                     Ok(token_data) => token_data.access_token,
                     Err(_) => {
-                        return HttpResponse::Unauthorized()
+                        return Err(HttpResponse::Unauthorized()
                             .json(serde_json::json!({
-                                "error": "Invalid refresh token",
+                                "error": Msg::InvalidRefreshToken.text(),
                                 "details": "Failed to parse token response"
-                            }));
+                            })));
                     }
                 }
             } else {
-                return HttpResponse::Unauthorized()
+                return Err(HttpResponse::Unauthorized()
                     .json(serde_json::json!({
-                        "error": "Invalid refresh token",
+                        "error": Msg::InvalidRefreshToken.text(),
                         "details": "Failed to refresh token"
-                    }));
+                    })));
             }
         }
         Err(_) => {
-            return HttpResponse::InternalServerError()
+            return Err(HttpResponse::InternalServerError()
                 .json(serde_json::json!({
-                    "error": "Failed to get account information",
+                    "error": Msg::FailedToGetAccountInfo.text(),
                     "details": "Network error occurred while refreshing token"
-                }));
+                })));
         }
     };
     
@@ -937,7 +1079,7 @@ pub async fn account_info(
     });
 
     let accounts_res = client
-        .post("https://www.youtube.com/youtubei/v1/account/accounts_list?prettyPrint=false")
+        .post(crate::endpoints::innertube("account/accounts_list?prettyPrint=false"))
         .header("Authorization", format!("Bearer {}", access_token))
         .header("X-Youtube-Client-Name", "85")
         .header("X-Youtube-Client-Version", "7.20251217.19.00")
@@ -950,29 +1092,29 @@ pub async fn account_info(
     let accounts_data: serde_json::Value = match accounts_res {
         Ok(response) => {
             if !response.status().is_success() {
-                return HttpResponse::InternalServerError()
+                return Err(HttpResponse::InternalServerError()
                     .json(serde_json::json!({
-                        "error": "Failed to get account information",
+                        "error": Msg::FailedToGetAccountInfo.text(),
                         "details": format!("HTTP error: {}", response.status())
-                    }));
+                    })));
             }
             match response.json().await {
                 Ok(data) => data,
                 Err(e) => {
-                    return HttpResponse::InternalServerError()
+                    return Err(HttpResponse::InternalServerError()
                         .json(serde_json::json!({
-                            "error": "Failed to get account information",
+                            "error": Msg::FailedToGetAccountInfo.text(),
                             "details": format!("Failed to parse response: {}", e)
-                        }));
+                        })));
                 }
             }
         }
         Err(e) => {
-            return HttpResponse::InternalServerError()
+            return Err(HttpResponse::InternalServerError()
                 .json(serde_json::json!({
-                    "error": "Failed to get account information",
+                    "error": Msg::FailedToGetAccountInfo.text(),
                     "details": format!("Network error: {}", e)
-                }));
+                })));
         }
     };
 
@@ -1006,11 +1148,11 @@ pub async fn account_info(
     };
 
     if primary_account.is_none() {
-        return HttpResponse::InternalServerError()
+        return Err(HttpResponse::InternalServerError()
             .json(serde_json::json!({
-                "error": "Failed to get account information",
+                "error": Msg::FailedToGetAccountInfo.text(),
                 "details": "Primary account not found"
-            }));
+            })));
     }
 
     let account = primary_account.unwrap();
@@ -1072,23 +1214,6 @@ pub async fn account_info(
                 })
         });
 
-    // Получаем base URL для channel_icon из запроса
-    let base_url = req
-        .headers()
-        .get("host")
-        .and_then(|h| h.to_str().ok())
-        .map(|host| {
-            let scheme = req
-                .uri()
-                .scheme_str()
-                .unwrap_or("http");
-            format!("{}://{}", scheme, host)
-        })
-        .unwrap_or_else(|| {
-            // Fallback на localhost если не можем определить из запроса
-            "http://localhost:2823".to_string()
-        });
-
     // Формируем URL для иконки через /channel_icon/
     let picture_url = photo_url_raw.map(|url| {
         format!("{}/channel_icon/{}", base_url, urlencoding::encode(&url))
@@ -1133,12 +1258,8 @@ pub async fn account_info(
         None
     };
 
-    let response = AccountInfoResponse {
+    Ok(AccountInfoResponse {
         google_account,
         youtube_channel,
-    };
-    
-    HttpResponse::Ok()
-        .insert_header(("Cache-Control", "no-store, no-cache, must-revalidate"))
-        .json(response)
+    })
 }
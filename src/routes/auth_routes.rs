@@ -1,4 +1,4 @@
-use actix_web::{web, HttpResponse, Responder};
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs::{self, File};
@@ -62,6 +62,32 @@ fn save_sessions(sessions: &Vec<YouTubeSession>) -> Result<(), std::io::Error> {
     Ok(())
 }
 
+/// Refresh tokens of the linked devices in tokens.json.
+pub(crate) fn linked_refresh_tokens() -> Vec<String> {
+    load_sessions()
+        .into_iter()
+        .filter(|s| s.is_linked && !s.refresh_token.is_empty())
+        .map(|s| s.refresh_token)
+        .collect()
+}
+
+/// Unlinks the devices whose refresh token is `revoked` (the username and password stay, so the
+/// device can link again) and returns how many there were.
+pub(crate) fn unlink_revoked(revoked: impl Fn(&str) -> bool) -> Result<usize, std::io::Error> {
+    let mut sessions = load_sessions();
+    let mut unlinked = 0;
+    for session in sessions.iter_mut().filter(|s| s.is_linked && revoked(&s.refresh_token)) {
+        session.is_linked = false;
+        session.access_token.clear();
+        session.refresh_token.clear();
+        unlinked += 1;
+    }
+    if unlinked > 0 {
+        save_sessions(&sessions)?;
+    }
+    Ok(unlinked)
+}
+
 fn is_username_taken(username: &str) -> bool {
     let sessions = load_sessions();
     sessions.iter().any(|s| s.username == username)
@@ -118,7 +144,7 @@ pub async fn check_if_username_is_taken(
         (status = 400, description = "Bad request")
     )
 )]
-pub async fn link_device_token(body: web::Bytes) -> impl Responder {
+pub async fn link_device_token(req: HttpRequest, body: web::Bytes) -> impl Responder {
     let json_str = match std::str::from_utf8(&body) {
         Ok(s) => s,
         Err(_) => {
@@ -144,6 +170,11 @@ pub async fn link_device_token(body: web::Bytes) -> impl Responder {
             return HttpResponse::BadRequest().body("Missing device_id");
         }
     };
+    let audit_session = format!("device:{}", device_id);
+    let audit = |ok: bool, detail: &str| {
+        use crate::routes::audit;
+        audit::record(Some(&req), audit::DEVICE_LINK, &audit_session, ok, detail)
+    };
 
     let username = data
         .get("username")
@@ -169,6 +200,7 @@ pub async fn link_device_token(body: web::Bytes) -> impl Responder {
     let mut sessions = load_sessions();
 
     if !username.is_empty() && sessions.iter().any(|s| s.username == username) {
+        audit(false, "Username taken");
         return HttpResponse::BadRequest().body("Username taken");
     }
 
@@ -195,9 +227,11 @@ pub async fn link_device_token(body: web::Bytes) -> impl Responder {
     }
 
     if let Err(_) = save_sessions(&sessions) {
+        audit(false, "Failed to save sessions");
         return HttpResponse::InternalServerError().body("Failed to save sessions");
     }
 
+    audit(true, "");
     HttpResponse::Ok().body("Device linked")
 }
 
@@ -0,0 +1,188 @@
+//! Per-client throughput for `quality=auto`: `/probe` sends a block of filler and times how long
+//! the client takes to drain it, or takes the figure a client measured itself (`kbps=`). The last
+//! value per client IP is kept for an hour.
+
+use actix_web::http::header::{CACHE_CONTROL, CONTENT_TYPE};
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use bytes::Bytes;
+use lazy_static::lazy_static;
+use lru::LruCache;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::num::NonZeroUsize;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::routes::signed_urls::{in_network, parse_network};
+
+const CHUNK: usize = 16 * 1024;
+const DEFAULT_PROBE_KB: usize = 256;
+const MAX_PROBE_KB: usize = 2048;
+const MEASUREMENT_TTL: Duration = Duration::from_secs(3600);
+
+lazy_static! {
+    static ref MEASUREMENTS: Mutex<LruCache<String, (u32, Instant)>> =
+        Mutex::new(LruCache::new(NonZeroUsize::new(4096).unwrap()));
+}
+
+/// `server.trusted_proxies`, parsed once at start-up.
+static TRUSTED_PROXIES: OnceLock<Vec<(IpAddr, u8)>> = OnceLock::new();
+
+pub fn init(config: &crate::config::Config) {
+    let trusted = config
+        .server
+        .trusted_proxies
+        .iter()
+        .filter_map(|net| {
+            let parsed = parse_network(net);
+            if parsed.is_none() {
+                log::warn!("server.trusted_proxies: ignoring '{}'", net);
+            }
+            parsed
+        })
+        .collect();
+    let _ = TRUSTED_PROXIES.set(trusted);
+}
+
+fn trusted(ip: IpAddr) -> bool {
+    TRUSTED_PROXIES
+        .get()
+        .is_some_and(|nets| nets.iter().any(|net| in_network(ip, *net)))
+}
+
+/// `1.2.3.4`, `1.2.3.4:5678`, `[::1]:5678` or `"[::1]"` to an address.
+fn parse_ip(value: &str) -> Option<IpAddr> {
+    let value = value.trim().trim_matches('"');
+    value
+        .parse::<IpAddr>()
+        .ok()
+        .or_else(|| value.parse::<std::net::SocketAddr>().ok().map(|s| s.ip()))
+        .or_else(|| value.trim_start_matches('[').trim_end_matches(']').parse().ok())
+}
+
+/// Client IP, without the port: the socket address, unless that is one of
+/// `server.trusted_proxies`. Then the forwarded chain is walked back from the proxy, skipping
+/// further trusted hops, so an entry the client put there itself is never taken.
+pub(crate) fn client_ip(req: &HttpRequest) -> String {
+    let Some(peer) = req.peer_addr().map(|p| p.ip()) else {
+        return "unknown".to_string();
+    };
+    if !trusted(peer) {
+        return peer.to_string();
+    }
+    let forwarded: Vec<IpAddr> = match req.headers().get("X-Forwarded-For").and_then(|v| v.to_str().ok()) {
+        Some(chain) => chain.split(',').filter_map(parse_ip).collect(),
+        None => req
+            .headers()
+            .get("Forwarded")
+            .and_then(|v| v.to_str().ok())
+            .map(|value| {
+                value
+                    .split([',', ';'])
+                    .filter_map(|pair| pair.trim().split_once('='))
+                    .filter(|(k, _)| k.eq_ignore_ascii_case("for"))
+                    .filter_map(|(_, v)| parse_ip(v))
+                    .collect()
+            })
+            .unwrap_or_default(),
+    };
+    forwarded
+        .iter()
+        .rev()
+        .find(|ip| !trusted(**ip))
+        .or_else(|| forwarded.first())
+        .copied()
+        .unwrap_or(peer)
+        .to_string()
+}
+
+fn record(key: String, kbps: u32) {
+    if let Ok(mut cache) = MEASUREMENTS.lock() {
+        cache.put(key, (kbps, Instant::now()));
+    }
+}
+
+/// Last throughput measured for the client, in kbit/s.
+pub fn last_kbps(req: &HttpRequest) -> Option<u32> {
+    let key = client_ip(req);
+    let mut cache = MEASUREMENTS.lock().ok()?;
+    match cache.get(&key) {
+        Some((kbps, at)) if at.elapsed() < MEASUREMENT_TTL => Some(*kbps),
+        _ => None,
+    }
+}
+
+/// Height for a throughput, leaving headroom for the audio track and the muxing overhead.
+pub fn height_for_kbps(kbps: u32) -> u32 {
+    match kbps {
+        0..=450 => 240,
+        451..=1800 => 360,
+        _ => 720,
+    }
+}
+
+/// `quality=auto` for this client; `None` until it has been probed.
+pub fn auto_height(req: &HttpRequest) -> Option<u32> {
+    last_kbps(req).map(height_for_kbps)
+}
+
+struct ProbeState {
+    key: String,
+    remaining: usize,
+    sent: usize,
+    started: Option<Instant>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/probe",
+    params(
+        ("size" = Option<u32>, Query, description = "KiB of filler to send (default 256, max 2048)"),
+        ("kbps" = Option<u32>, Query, description = "Report a throughput the client measured itself instead of downloading")
+    ),
+    responses(
+        (status = 200, description = "Filler bytes (application/octet-stream), or JSON with the recorded kbps and quality when kbps= is given")
+    )
+)]
+pub async fn probe(req: HttpRequest, query: web::Query<HashMap<String, String>>) -> impl Responder {
+    let key = client_ip(&req);
+    if let Some(kbps) = query.get("kbps").and_then(|v| v.parse::<u32>().ok()) {
+        record(key, kbps);
+        return HttpResponse::Ok().json(serde_json::json!({
+            "kbps": kbps,
+            "quality": format!("{}p", height_for_kbps(kbps))
+        }));
+    }
+
+    let size_kb = query
+        .get("size")
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(DEFAULT_PROBE_KB)
+        .clamp(16, MAX_PROBE_KB);
+    let state = ProbeState {
+        key,
+        remaining: size_kb * 1024,
+        sent: 0,
+        started: None,
+    };
+    // The next poll after the last chunk means the client has taken everything but what is
+    // still in the socket buffers, which is why the probe defaults to a few hundred KiB.
+    let stream = futures_util::stream::unfold(state, |mut st| async move {
+        let started = *st.started.get_or_insert_with(Instant::now);
+        if st.remaining == 0 {
+            let secs = started.elapsed().as_secs_f64().max(0.001);
+            let kbps = (st.sent as f64 * 8.0 / 1000.0 / secs) as u32;
+            record(std::mem::take(&mut st.key), kbps);
+            return None;
+        }
+        let n = st.remaining.min(CHUNK);
+        st.remaining -= n;
+        st.sent += n;
+        Some((Ok::<_, actix_web::Error>(Bytes::from(vec![0u8; n])), st))
+    });
+    HttpResponse::Ok()
+        .insert_header((CONTENT_TYPE, "application/octet-stream"))
+        .insert_header((CACHE_CONTROL, "no-store"))
+        .no_chunking((size_kb * 1024) as u64)
+        .streaming(stream)
+}
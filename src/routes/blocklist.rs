@@ -0,0 +1,252 @@
+//! Parental blocklists (`parental.*`): channels, keywords and categories hidden from search,
+//! trending, related and video info. Seeded from config at startup and editable through
+//! /admin/blocklist; edits are written to `state_file` so they survive restarts.
+
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::sync::RwLock;
+use utoipa::ToSchema;
+
+use crate::config::ParentalConfig;
+use crate::routes::messages::Msg;
+
+#[derive(Serialize, Deserialize, Clone, Default, ToSchema)]
+pub struct BlocklistLists {
+    #[serde(default)]
+    pub channels: Vec<String>,
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    #[serde(default)]
+    pub categories: Vec<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct BlocklistChange {
+    #[serde(default)]
+    pub add: BlocklistLists,
+    #[serde(default)]
+    pub remove: BlocklistLists,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BlocklistResponse {
+    pub enabled: bool,
+    #[serde(flatten)]
+    pub lists: BlocklistLists,
+}
+
+#[derive(Default)]
+struct State {
+    enabled: bool,
+    state_file: String,
+    lists: BlocklistLists,
+}
+
+lazy_static! {
+    static ref STATE: RwLock<State> = RwLock::new(State::default());
+}
+
+pub(crate) fn normalize(lists: &mut BlocklistLists) {
+    fn clean(values: &mut Vec<String>, lowercase: bool) {
+        for v in values.iter_mut() {
+            *v = v.trim().to_string();
+            if lowercase {
+                *v = v.to_lowercase();
+            }
+        }
+        values.retain(|v| !v.is_empty());
+        values.sort();
+        values.dedup();
+    }
+    clean(&mut lists.channels, false);
+    clean(&mut lists.keywords, true);
+    clean(&mut lists.categories, true);
+}
+
+/// Loads the lists from `state_file` if it exists, otherwise from config.
+pub fn init(config: &ParentalConfig) {
+    let saved = if config.state_file.is_empty() {
+        None
+    } else {
+        fs::read_to_string(&config.state_file)
+            .ok()
+            .and_then(|text| serde_json::from_str::<BlocklistLists>(&text).ok())
+    };
+    let mut lists = saved.unwrap_or_else(|| BlocklistLists {
+        channels: config.blocked_channels.clone(),
+        keywords: config.blocked_keywords.clone(),
+        categories: config.blocked_categories.clone(),
+    });
+    normalize(&mut lists);
+    if config.enabled {
+        log::info!(
+            "Parental blocklist: {} channels, {} keywords, {} categories",
+            lists.channels.len(),
+            lists.keywords.len(),
+            lists.categories.len()
+        );
+    }
+    if let Ok(mut state) = STATE.write() {
+        *state = State {
+            enabled: config.enabled,
+            state_file: config.state_file.clone(),
+            lists,
+        };
+    }
+}
+
+fn matches(lists: &BlocklistLists, channel_id: Option<&str>, texts: &[&str], category: Option<&str>) -> bool {
+    if let Some(id) = channel_id.filter(|id| !id.is_empty()) {
+        if lists.channels.iter().any(|c| c == id) {
+            return true;
+        }
+    }
+    if let Some(cat) = category.map(|c| c.trim().to_lowercase()).filter(|c| !c.is_empty()) {
+        if lists.categories.contains(&cat) {
+            return true;
+        }
+    }
+    if !lists.keywords.is_empty() {
+        for text in texts {
+            let text = text.to_lowercase();
+            if lists.keywords.iter().any(|k| text.contains(k.as_str())) {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+/// `true` if any of the given fields hits the instance blocklist or `extra` (a profile's own
+/// additions). `texts` are searched for keywords; `category` may be a category id or name.
+pub fn is_blocked(
+    extra: Option<&BlocklistLists>,
+    channel_id: Option<&str>,
+    texts: &[&str],
+    category: Option<&str>,
+) -> bool {
+    if let Some(lists) = extra {
+        if matches(lists, channel_id, texts, category) {
+            return true;
+        }
+    }
+    match STATE.read() {
+        Ok(state) => state.enabled && matches(&state.lists, channel_id, texts, category),
+        Err(_) => false,
+    }
+}
+
+/// Blocklist check against an InnerTube player response (videoDetails + microformat).
+pub fn blocks_player_response(pr: &Value, extra: Option<&BlocklistLists>) -> bool {
+    let vd = pr.get("videoDetails").unwrap_or(&Value::Null);
+    let str_of = |v: &Value, key: &str| v.get(key).and_then(|s| s.as_str()).unwrap_or("").to_string();
+    let mut texts = vec![
+        str_of(vd, "title"),
+        str_of(vd, "author"),
+        str_of(vd, "shortDescription"),
+    ];
+    if let Some(keywords) = vd.get("keywords").and_then(|k| k.as_array()) {
+        texts.extend(keywords.iter().filter_map(|k| k.as_str()).map(|k| k.to_string()));
+    }
+    let texts: Vec<&str> = texts.iter().map(|s| s.as_str()).collect();
+    let category = pr
+        .pointer("/microformat/playerMicroformatRenderer/category")
+        .and_then(|c| c.as_str());
+    let channel_id = str_of(vd, "channelId");
+    is_blocked(extra, Some(&channel_id), &texts, category)
+}
+
+fn snapshot() -> BlocklistResponse {
+    STATE
+        .read()
+        .map(|s| BlocklistResponse {
+            enabled: s.enabled,
+            lists: s.lists.clone(),
+        })
+        .unwrap_or(BlocklistResponse {
+            enabled: false,
+            lists: BlocklistLists::default(),
+        })
+}
+
+fn unauthorized() -> HttpResponse {
+    HttpResponse::Unauthorized().json(serde_json::json!({
+        "error": Msg::AdminKeyRequired.text()
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/blocklist",
+    params(
+        ("admin_key" = Option<String>, Query, description = "Admin key (or X-Admin-Key header)")
+    ),
+    responses(
+        (status = 200, description = "Current blocklists", body = BlocklistResponse),
+        (status = 401, description = "Admin key required")
+    )
+)]
+pub async fn get_blocklist(req: HttpRequest, data: web::Data<crate::AppState>) -> impl Responder {
+    if !crate::routes::admin::is_admin(&req, &data.config) {
+        return unauthorized();
+    }
+    HttpResponse::Ok().json(snapshot())
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/blocklist",
+    request_body = BlocklistChange,
+    params(
+        ("admin_key" = Option<String>, Query, description = "Admin key (or X-Admin-Key header)")
+    ),
+    responses(
+        (status = 200, description = "Updated blocklists", body = BlocklistResponse),
+        (status = 401, description = "Admin key required")
+    )
+)]
+pub async fn update_blocklist(
+    req: HttpRequest,
+    data: web::Data<crate::AppState>,
+    body: web::Json<BlocklistChange>,
+) -> impl Responder {
+    if !crate::routes::admin::is_admin(&req, &data.config) {
+        return unauthorized();
+    }
+    let change = body.into_inner();
+    let mut remove = change.remove;
+    normalize(&mut remove);
+
+    let (state_file, json) = match STATE.write() {
+        Ok(mut state) => {
+            let lists = &mut state.lists;
+            lists.channels.extend(change.add.channels);
+            lists.keywords.extend(change.add.keywords);
+            lists.categories.extend(change.add.categories);
+            normalize(lists);
+            lists.channels.retain(|c| !remove.channels.contains(c));
+            lists.keywords.retain(|k| !remove.keywords.contains(k));
+            lists.categories.retain(|c| !remove.categories.contains(c));
+            (state.state_file.clone(), serde_json::to_string_pretty(&state.lists))
+        }
+        Err(_) => {
+            return HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Blocklist unavailable"
+            }));
+        }
+    };
+
+    if !state_file.is_empty() {
+        if let Err(e) = json
+            .map_err(|e| e.to_string())
+            .and_then(|json| fs::write(&state_file, json).map_err(|e| e.to_string()))
+        {
+            log::warn!("Failed to save blocklist to {}: {}", state_file, e);
+        }
+    }
+    log::info!("Blocklist updated via admin endpoint");
+    HttpResponse::Ok().json(snapshot())
+}
@@ -5,15 +5,8 @@ use std::collections::HashMap;
 use urlencoding;
 use utoipa::ToSchema;
 
-fn base_url(req: &HttpRequest, config: &crate::config::Config) -> String {
-    if !config.server.main_url.is_empty() {
-        return config.server.main_url.clone();
-    }
-    let info = req.connection_info();
-    let scheme = info.scheme();
-    let host = info.host();
-    format!("{}://{}/", scheme, host.trim_end_matches('/'))
-}
+use crate::routes::messages::Msg;
+use crate::routes::base_url;
 
 fn parse_number(text: &str) -> String {
     let lower_text = text.trim().to_lowercase();
@@ -66,6 +59,23 @@ pub struct ChannelVideo {
 pub struct ChannelVideosResponse {
     pub channel_info: ChannelInfo,
     pub videos: Vec<ChannelVideo>,
+    /// True when part of the response could not be fetched; see `warnings`.
+    #[serde(default)]
+    pub partial: bool,
+    /// One entry per missing section (`channel_info: ...`, `videos: ...`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+fn unknown_channel_info() -> ChannelInfo {
+    ChannelInfo {
+        title: "Unknown".to_string(),
+        description: "".to_string(),
+        thumbnail: "".to_string(),
+        banner: "".to_string(),
+        subscriber_count: "0".to_string(),
+        video_count: "0".to_string(),
+    }
 }
 
 
@@ -74,8 +84,12 @@ pub struct ChannelVideosResponse {
     get,
     path = "/get_author_videos.php",
     params(
-        ("author" = String, Query, description = "Channel username/search query"),
-        ("count" = Option<i32>, Query, description = "Number of videos to return (default: 50)")
+        ("author" = String, Query, description = "Channel handle, username or UC id"),
+        ("count" = Option<i32>, Query, description = "Number of videos to return (default: 50)"),
+        ("fields" = Option<String>, Query, description = "Comma-separated whitelist of response fields (e.g. channel_info.title,videos.title)"),
+        ("hl" = Option<String>, Query, description = "Metadata language (default: api.hl)"),
+        ("gl" = Option<String>, Query, description = "Region (default: api.gl)"),
+        ("envelope" = Option<bool>, Query, description = "Return a ListResponse (items, next_page_token, total, warnings) instead of the legacy shape")
     ),
     responses(
         (status = 200, description = "Videos for the author", body = ChannelVideosResponse),
@@ -115,26 +129,31 @@ pub async fn get_author_videos(
         Some(key) => key,
         None => {
             return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": "Missing innertube_key in config.yml"
+                "error": Msg::MissingInnertubeKey.text()
             }));
         }
     };
 
-    let client = Client::new();
+    let client = crate::http_client::shared();
 
-    // Resolve handle to channel ID using InnerTube API
-    let channel_id = resolve_handle_to_channel_id(&author, &client, &innertube_key, &base).await;
+    // Resolve handle to channel ID using InnerTube API; channel search results link by UC id
+    let channel_id = if author.len() == 24 && author.starts_with("UC") {
+        Some(author.clone())
+    } else {
+        resolve_handle_to_channel_id(&author, &client, &innertube_key, &base).await
+    };
 
     let channel_id = match channel_id {
         Some(id) => id,
         None => {
             return HttpResponse::BadRequest().json(serde_json::json!({
-                "error": "Channel not found"
+                "error": Msg::ChannelNotFound.text()
             }));
         }
     };
 
-    get_author_videos_by_id_internal(&channel_id, count, config, &base).await
+    let fields = query_params.get("fields").map(|s| s.as_str());
+    get_author_videos_by_id_internal(&req, &channel_id, count, config, &base, fields).await
 }
 
 #[utoipa::path(
@@ -142,7 +161,11 @@ pub async fn get_author_videos(
     path = "/get_author_videos_by_id.php",
     params(
         ("channel_id" = String, Query, description = "YouTube channel ID"),
-        ("count" = Option<i32>, Query, description = "Number of videos to return (default: 50)")
+        ("count" = Option<i32>, Query, description = "Number of videos to return (default: 50)"),
+        ("fields" = Option<String>, Query, description = "Comma-separated whitelist of response fields (e.g. channel_info.title,videos.title)"),
+        ("hl" = Option<String>, Query, description = "Metadata language (default: api.hl)"),
+        ("gl" = Option<String>, Query, description = "Region (default: api.gl)"),
+        ("envelope" = Option<bool>, Query, description = "Return a ListResponse (items, next_page_token, total, warnings) instead of the legacy shape")
     ),
     responses(
         (status = 200, description = "Videos for channel", body = ChannelVideosResponse),
@@ -177,38 +200,56 @@ pub async fn get_author_videos_by_id(
         .and_then(|c| c.parse().ok())
         .unwrap_or(config.video.default_count as i32);
 
-    get_author_videos_by_id_internal(&channel_id, count, config, &base).await
+    let fields = query_params.get("fields").map(|s| s.as_str());
+    get_author_videos_by_id_internal(&req, &channel_id, count, config, &base, fields).await
 }
 
 async fn get_author_videos_by_id_internal(
+    req: &HttpRequest,
     channel_id: &str,
     count: i32,
     config: &crate::config::Config,
     base: &str,
+    fields: Option<&str>,
 ) -> HttpResponse {
     // Use InnerTube API key from config
     let innertube_key = match config.get_innertube_key() {
         Some(key) => key,
         None => {
             return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": "Missing innertube_key in config.yml"
+                "error": Msg::MissingInnertubeKey.text()
             }));
         }
     };
 
-    let (videos, channel_info) = fetch_channel_videos_inner_tube(channel_id, count, &innertube_key, base).await;
-
+    let locale = crate::routes::locale::Locale::from_request(req, config);
+    let (videos, channel_info, warnings) =
+        fetch_channel_videos_inner_tube(channel_id, count, &innertube_key, base, &locale).await;
+
+    if crate::routes::envelope::requested(req) {
+        let list = crate::routes::envelope::ListResponse {
+            items: videos,
+            next_page_token: None,
+            total: channel_info.video_count.parse().ok(),
+            warnings,
+        };
+        let body = crate::routes::fields::filter_fields(&list, fields);
+        return crate::routes::http_cache::cached_json(req, &body, config.cache.channel_max_age);
+    }
     let response = ChannelVideosResponse {
         channel_info,
         videos,
+        partial: !warnings.is_empty(),
+        warnings,
     };
 
-    HttpResponse::Ok().json(response)
+    let body = crate::routes::fields::filter_fields(&response, fields);
+    crate::routes::http_cache::cached_json(req, &body, config.cache.channel_max_age)
 }
 
-async fn resolve_handle_to_channel_id(handle: &str, client: &Client, innertube_key: &str, _base: &str) -> Option<String> {
+pub(crate) async fn resolve_handle_to_channel_id(handle: &str, client: &Client, innertube_key: &str, _base: &str) -> Option<String> {
     let clean_handle = handle.trim().trim_start_matches('@');
-    let url = format!("https://www.youtube.com/youtubei/v1/navigation/resolve_url?key={}&prettyPrint=false", innertube_key);
+    let url = crate::endpoints::innertube(&format!("navigation/resolve_url?key={}&prettyPrint=false", innertube_key));
     
     let context = serde_json::json!({
         "client": {
@@ -246,22 +287,23 @@ async fn resolve_handle_to_channel_id(handle: &str, client: &Client, innertube_k
     }
 }
 
-async fn fetch_channel_videos_inner_tube(
+pub(crate) async fn fetch_channel_videos_inner_tube(
     channel_id: &str,
     count: i32,
     innertube_key: &str,
     base: &str,
-) -> (Vec<ChannelVideo>, ChannelInfo) {
-    let client = Client::new();
+    locale: &crate::routes::locale::Locale,
+) -> (Vec<ChannelVideo>, ChannelInfo, Vec<String>) {
+    let client = crate::http_client::shared();
     
-    let url = format!("https://www.youtube.com/youtubei/v1/browse?key={}&prettyPrint=false", innertube_key);
+    let url = crate::endpoints::innertube(&format!("browse?key={}&prettyPrint=false", innertube_key));
     
     let context = serde_json::json!({
         "client": {
             "clientName": "WEB",
             "clientVersion": "2.20260220.00.00",
-            "hl": "en",
-            "gl": "US"
+            "hl": locale.hl,
+            "gl": locale.gl
         }
     });
     
@@ -278,32 +320,30 @@ async fn fetch_channel_videos_inner_tube(
         .await
     {
         Ok(resp) => resp,
-        Err(_) => {
-            return (Vec::new(), ChannelInfo {
-                title: "Unknown".to_string(),
-                description: "".to_string(),
-                thumbnail: "".to_string(),
-                banner: "".to_string(),
-                subscriber_count: "0".to_string(),
-                video_count: "0".to_string(),
-            });
+        Err(e) => {
+            log::info!("Error calling browse for channel {}: {}", channel_id, e);
+            let warning = "channel: InnerTube browse request failed".to_string();
+            return (Vec::new(), unknown_channel_info(), vec![warning]);
         }
     };
     
     let data: serde_json::Value = match response.json().await {
         Ok(json) => json,
-        Err(_) => {
-            return (Vec::new(), ChannelInfo {
-                title: "Unknown".to_string(),
-                description: "".to_string(),
-                thumbnail: "".to_string(),
-                banner: "".to_string(),
-                subscriber_count: "0".to_string(),
-                video_count: "0".to_string(),
-            });
+        Err(e) => {
+            log::info!("Error parsing browse response for channel {}: {}", channel_id, e);
+            let warning = "channel: InnerTube browse returned an unreadable response".to_string();
+            return (Vec::new(), unknown_channel_info(), vec![warning]);
         }
     };
+    if let Some((code, reason)) = crate::routes::upstream::report("InnerTube browse", &data) {
+        let warning = format!("channel: InnerTube browse returned {} {}", code, reason);
+        return (Vec::new(), unknown_channel_info(), vec![warning]);
+    }
     
+    let mut warnings = Vec::new();
+    if data.pointer("/metadata/channelMetadataRenderer").is_none() {
+        warnings.push("channel_info: no channel metadata in the browse response".to_string());
+    }
     let channel_info = extract_channel_info(&data, base, channel_id).await;
     
     // Find the Videos tab
@@ -345,12 +385,14 @@ async fn fetch_channel_videos_inner_tube(
     let mut videos = Vec::new();
     if let Some(content) = videos_content {
         videos = collect_videos_from_content(content, &channel_info, base, &client, innertube_key).await;
+    } else {
+        warnings.push("videos: no videos tab in the browse response".to_string());
     }
     
     // Limit the number of videos
     videos.truncate(count as usize);
     
-    (videos, channel_info)
+    (videos, channel_info, warnings)
 }
 
 async fn extract_channel_info(data: &serde_json::Value, base: &str, channel_id: &str) -> ChannelInfo {
@@ -614,7 +656,7 @@ pub async fn get_channel_thumbnail_api(
         Some(id) => id.clone(),
         None => {
             return HttpResponse::BadRequest().json(serde_json::json!({
-                "error": "ID параметр обязателен"
+                "error": Msg::IdRequired.text()
             }));
         }
     };
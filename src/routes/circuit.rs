@@ -0,0 +1,98 @@
+//! Per-host circuit breaker for upstream hosts (thumbnail CDNs and the like).
+//! After `threshold` consecutive failures a host is skipped for `cooldown`; the first request
+//! after the cooldown is let through as a probe and closes the breaker on success.
+
+use lazy_static::lazy_static;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use utoipa::ToSchema;
+
+#[derive(Default)]
+struct HostState {
+    consecutive_failures: u32,
+    total_failures: u64,
+    total_successes: u64,
+    open_until: Option<Instant>,
+}
+
+lazy_static! {
+    static ref HOSTS: Mutex<HashMap<String, HostState>> = Mutex::new(HashMap::new());
+}
+
+/// `true` while the host is in cooldown and should be skipped.
+pub fn is_open(host: &str) -> bool {
+    HOSTS
+        .lock()
+        .ok()
+        .and_then(|map| map.get(host).and_then(|s| s.open_until))
+        .map(|until| Instant::now() < until)
+        .unwrap_or(false)
+}
+
+pub fn record_success(host: &str) {
+    if let Ok(mut map) = HOSTS.lock() {
+        let state = map.entry(host.to_string()).or_default();
+        state.consecutive_failures = 0;
+        state.total_successes += 1;
+        state.open_until = None;
+    }
+}
+
+pub fn record_failure(host: &str, threshold: u32, cooldown: Duration) {
+    if let Ok(mut map) = HOSTS.lock() {
+        let state = map.entry(host.to_string()).or_default();
+        state.consecutive_failures += 1;
+        state.total_failures += 1;
+        if threshold > 0 && state.consecutive_failures >= threshold {
+            if state.open_until.map(|u| Instant::now() >= u).unwrap_or(true) {
+                log::warn!(
+                    "Circuit open for {} after {} failures (cooldown {}s)",
+                    host,
+                    state.consecutive_failures,
+                    cooldown.as_secs()
+                );
+            }
+            state.open_until = Some(Instant::now() + cooldown);
+        }
+    }
+}
+
+/// Orders `hosts` so healthy ones come first; open ones are kept at the end as a last resort.
+pub fn order_hosts(hosts: &[String]) -> Vec<&str> {
+    let (closed, open): (Vec<&str>, Vec<&str>) = hosts
+        .iter()
+        .map(|h| h.as_str())
+        .partition(|h| !is_open(h));
+    closed.into_iter().chain(open).collect()
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct HostHealth {
+    pub host: String,
+    pub open: bool,
+    pub consecutive_failures: u32,
+    pub total_failures: u64,
+    pub total_successes: u64,
+}
+
+pub fn snapshot() -> Vec<HostHealth> {
+    let now = Instant::now();
+    let mut hosts: Vec<HostHealth> = HOSTS
+        .lock()
+        .map(|map| {
+            map.iter()
+                .map(|(host, s)| HostHealth {
+                    host: host.clone(),
+                    open: s.open_until.map(|u| now < u).unwrap_or(false),
+                    consecutive_failures: s.consecutive_failures,
+                    total_failures: s.total_failures,
+                    total_successes: s.total_successes,
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    hosts.sort_by(|a, b| a.host.cmp(&b.host));
+    hosts
+}
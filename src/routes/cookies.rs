@@ -0,0 +1,389 @@
+//! yt-dlp cookie files (`video.cookies`): every `*.txt` in `cookies.dir`, then the legacy
+//! `assets/cookies.txt` and `cookies.txt`. Resolves take the usable files in turn, so several
+//! accounts share the load; a file whose login cookies have all expired, or that YouTube
+//! rejects, is benched until a test extraction passes with it again. /admin/cookies lists the
+//! files and uploads, replaces or deletes one without a restart.
+
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use lazy_static::lazy_static;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+use utoipa::ToSchema;
+
+use crate::config::VideoConfig;
+use crate::routes::messages::Msg;
+use crate::routes::now_secs;
+
+const LEGACY_PATHS: [&str; 2] = ["assets/cookies.txt", "cookies.txt"];
+
+/// Cookies that only exist while an account is signed in; anonymous files have none of them.
+const LOGIN_COOKIES: [&str; 5] = ["SID", "__Secure-1PSID", "__Secure-3PSID", "SAPISID", "LOGIN_INFO"];
+
+/// What yt-dlp prints when YouTube wants a (working) signed-in session.
+const REJECTION_MARKERS: [&str; 3] = [
+    "cookies are no longer valid",
+    "sign in to confirm you",
+    "use --cookies-from-browser or --cookies",
+];
+
+#[derive(Default)]
+struct Health {
+    rejected: bool,
+    checked_at: Option<u64>,
+    last_error: Option<String>,
+    uses: u64,
+}
+
+lazy_static! {
+    static ref HEALTH: Mutex<HashMap<PathBuf, Health>> = Mutex::new(HashMap::new());
+    /// Login expiry per file with the mtime it was read at, so a file is parsed again only
+    /// after it changes (an upload or replace through /admin/cookies, or an edit on disk).
+    static ref EXPIRY: Mutex<HashMap<PathBuf, (SystemTime, Option<u64>)>> = Mutex::new(HashMap::new());
+}
+
+/// Where the next rotation starts.
+static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+/// Cookie files on disk, `cookies.dir` first (sorted by name), then the legacy paths.
+fn discover(config: &VideoConfig) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(&config.cookies.dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|e| e.path())
+                .filter(|p| p.is_file() && p.extension().is_some_and(|ext| ext == "txt"))
+                .collect()
+        })
+        .unwrap_or_default();
+    paths.sort();
+    paths.extend(LEGACY_PATHS.iter().map(PathBuf::from).filter(|p| p.exists()));
+    paths
+}
+
+/// Netscape-format lines (`domain flag path secure expiry name value`); `#HttpOnly_` marks a
+/// cookie, not a comment.
+fn cookie_lines(text: &str) -> impl Iterator<Item = Vec<&str>> {
+    text.lines()
+        .map(|l| l.strip_prefix("#HttpOnly_").unwrap_or(l))
+        .filter(|l| !l.starts_with('#'))
+        .map(|l| l.split('\t').collect::<Vec<_>>())
+        .filter(|fields| fields.len() == 7)
+}
+
+/// When the last login cookie in `text` runs out. `None` without login cookies or with a
+/// session one (expiry 0), which never counts as expired.
+fn login_expiry(text: &str) -> Option<u64> {
+    let expiries: Vec<u64> = cookie_lines(text)
+        .filter(|f| LOGIN_COOKIES.contains(&f[5]))
+        .map(|f| f[4].parse::<u64>().unwrap_or(0))
+        .collect();
+    if expiries.contains(&0) {
+        return None;
+    }
+    expiries.into_iter().max()
+}
+
+/// True when the file has login cookies and every one of them is past its expiry.
+fn expired(path: &Path) -> bool {
+    let Ok(modified) = fs::metadata(path).and_then(|m| m.modified()) else {
+        return false;
+    };
+    let cached = EXPIRY
+        .lock()
+        .ok()
+        .and_then(|c| c.get(path).filter(|(at, _)| *at == modified).map(|(_, e)| *e));
+    let expiry = match cached {
+        Some(expiry) => expiry,
+        None => {
+            let Ok(text) = fs::read_to_string(path) else {
+                return false;
+            };
+            let expiry = login_expiry(&text);
+            if let Ok(mut cache) = EXPIRY.lock() {
+                cache.insert(path.to_path_buf(), (modified, expiry));
+            }
+            expiry
+        }
+    };
+    expiry.is_some_and(|at| at < now_secs())
+}
+
+fn is_rejected(health: &HashMap<PathBuf, Health>, path: &Path) -> bool {
+    health.get(path).is_some_and(|h| h.rejected)
+}
+
+fn rejects_cookies(stderr: &str) -> bool {
+    let stderr = stderr.to_lowercase();
+    REJECTION_MARKERS.iter().any(|m| stderr.contains(m))
+}
+
+/// Usable cookie files for one yt-dlp run, starting one further along each call. Empty when
+/// `video.use_cookies` is off.
+pub fn rotation(config: &VideoConfig) -> Vec<PathBuf> {
+    if !config.use_cookies {
+        return Vec::new();
+    }
+    let unexpired: Vec<PathBuf> = discover(config).into_iter().filter(|p| !expired(p)).collect();
+    let mut usable: Vec<PathBuf> = match HEALTH.lock() {
+        Ok(health) => unexpired.into_iter().filter(|p| !is_rejected(&health, p)).collect(),
+        Err(_) => unexpired,
+    };
+    if !usable.is_empty() {
+        let start = NEXT.fetch_add(1, Ordering::Relaxed) % usable.len();
+        usable.rotate_left(start);
+    }
+    usable
+}
+
+/// Records how a yt-dlp run with `path` went. Only a failure that blames the session benches
+/// the file; network errors and unavailable videos say nothing about it.
+pub fn report(path: &Path, result: Result<(), &str>) {
+    let Ok(mut health) = HEALTH.lock() else {
+        return;
+    };
+    let entry = health.entry(path.to_path_buf()).or_default();
+    match result {
+        Ok(()) => {
+            entry.uses += 1;
+            entry.rejected = false;
+        }
+        Err(stderr) if rejects_cookies(stderr) => {
+            if !entry.rejected {
+                log::warn!("Cookie file {} was rejected by YouTube; benched", path.display());
+            }
+            entry.rejected = true;
+            entry.last_error = Some(crate::routes::video::stderr_tail(stderr.as_bytes()));
+        }
+        Err(_) => {}
+    }
+}
+
+/// Test extraction of `video.cookies.test_video_id` with `path` only.
+async fn test(path: &Path, config: &VideoConfig) {
+    let slot = match crate::routes::video::yt_dlp_slot(config).await {
+        Ok(slot) => slot,
+        Err(e) => {
+            log::info!("Cookie check for {} skipped: {}", path.display(), e);
+            return;
+        }
+    };
+    let url = format!("https://www.youtube.com/watch?v={}", config.cookies.test_video_id);
+    let timeout = Duration::from_secs(config.ytdlp_resolve_timeout_secs.max(1));
+    let owned = path.to_path_buf();
+    let outcome = tokio::task::spawn_blocking(move || {
+        let _slot = slot;
+        let mut cmd = crate::routes::video::yt_dlp_command(&crate::routes::video::yt_dlp_binary());
+        cmd.args(["--simulate", "--no-playlist", "--no-warnings", "--cookies"])
+            .arg(&owned)
+            .arg(&url);
+        crate::routes::video::run_yt_dlp(&mut cmd, timeout)
+    })
+    .await;
+    let error = match outcome {
+        Ok(Ok(output)) if output.status.success() => None,
+        Ok(Ok(output)) => Some(String::from_utf8_lossy(&output.stderr).into_owned()),
+        Ok(Err(e)) => Some(e),
+        Err(e) => Some(e.to_string()),
+    };
+    report(path, error.as_deref().map_or(Ok(()), Err));
+    if let Ok(mut health) = HEALTH.lock() {
+        let entry = health.entry(path.to_path_buf()).or_default();
+        entry.checked_at = Some(now_secs());
+        entry.last_error = error.map(|e| crate::routes::video::stderr_tail(e.as_bytes()));
+    }
+}
+
+/// Tests every cookie file each `video.cookies.check_interval_minutes`, the first round one
+/// interval after boot; 0 turns the checks off.
+pub fn spawn(config: &VideoConfig) {
+    if !config.use_cookies || config.cookies.check_interval_minutes == 0 {
+        return;
+    }
+    let config = config.clone();
+    let period = Duration::from_secs(u64::from(config.cookies.check_interval_minutes) * 60);
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(period).await;
+            for path in discover(&config) {
+                test(&path, &config).await;
+            }
+        }
+    });
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CookieFileStatus {
+    pub path: String,
+    /// Handed to yt-dlp: not expired and not rejected.
+    pub usable: bool,
+    pub expired: bool,
+    /// YouTube turned the session down, in a resolve or the last test.
+    pub rejected: bool,
+    /// Unix time of the last test extraction.
+    pub checked_at: Option<u64>,
+    pub last_error: Option<String>,
+    /// Successful yt-dlp runs since start.
+    pub uses: u64,
+}
+
+fn statuses(config: &VideoConfig) -> Vec<CookieFileStatus> {
+    let files: Vec<(PathBuf, bool)> = discover(config)
+        .into_iter()
+        .map(|p| {
+            let expired = expired(&p);
+            (p, expired)
+        })
+        .collect();
+    let health = HEALTH.lock().ok();
+    files
+        .into_iter()
+        .map(|(p, expired)| {
+            let h = health.as_ref().and_then(|h| h.get(&p));
+            let rejected = h.map(|h| h.rejected).unwrap_or(false);
+            CookieFileStatus {
+                path: p.display().to_string(),
+                usable: !rejected && !expired,
+                expired,
+                rejected,
+                checked_at: h.and_then(|h| h.checked_at),
+                last_error: h.and_then(|h| h.last_error.clone()),
+                uses: h.map(|h| h.uses).unwrap_or(0),
+            }
+        })
+        .collect()
+}
+
+/// `name` as a file in `cookies.dir`: letters, digits, `-` and `_`, with `.txt` added.
+fn upload_path(config: &VideoConfig, name: &str) -> Result<PathBuf, String> {
+    let name = name.trim().trim_end_matches(".txt");
+    if name.is_empty()
+        || name.len() > 64
+        || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err("name must be 1-64 letters, digits, '-' or '_'".to_string());
+    }
+    Ok(Path::new(&config.cookies.dir).join(format!("{}.txt", name)))
+}
+
+fn unauthorized() -> HttpResponse {
+    HttpResponse::Unauthorized().json(serde_json::json!({
+        "error": Msg::AdminKeyRequired.text()
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/cookies",
+    params(
+        ("admin_key" = Option<String>, Query, description = "Admin key (or X-Admin-Key header)")
+    ),
+    responses(
+        (status = 200, description = "Cookie files with their health", body = [CookieFileStatus]),
+        (status = 401, description = "Admin key required")
+    )
+)]
+pub async fn get_cookies(req: HttpRequest, data: web::Data<crate::AppState>) -> impl Responder {
+    if !crate::routes::admin::is_admin(&req, &data.config) {
+        return unauthorized();
+    }
+    HttpResponse::Ok().json(statuses(&data.config.video))
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/cookies",
+    request_body(content = String, description = "cookies.txt in Netscape format", content_type = "text/plain"),
+    params(
+        ("name" = String, Query, description = "File name in video.cookies.dir, without .txt; an existing file is replaced"),
+        ("admin_key" = Option<String>, Query, description = "Admin key (or X-Admin-Key header)")
+    ),
+    responses(
+        (status = 200, description = "Saved and tested; all cookie files", body = [CookieFileStatus]),
+        (status = 400, description = "Bad name, or no YouTube cookies in the body"),
+        (status = 401, description = "Admin key required")
+    )
+)]
+pub async fn upload_cookies(
+    req: HttpRequest,
+    query: web::Query<HashMap<String, String>>,
+    data: web::Data<crate::AppState>,
+    body: web::Bytes,
+) -> impl Responder {
+    let config = &data.config.video;
+    if !crate::routes::admin::is_admin(&req, &data.config) {
+        return unauthorized();
+    }
+    let path = match upload_path(config, query.get("name").map(|n| n.as_str()).unwrap_or("")) {
+        Ok(path) => path,
+        Err(e) => return HttpResponse::BadRequest().json(serde_json::json!({ "error": e })),
+    };
+    let text = String::from_utf8_lossy(&body);
+    if !cookie_lines(&text).any(|f| f[0].trim_start_matches('.').ends_with("youtube.com")) {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": "No youtube.com cookies found; expected a Netscape-format cookies.txt"
+        }));
+    }
+    let written = fs::create_dir_all(&config.cookies.dir).and_then(|_| fs::write(&path, text.as_bytes()));
+    if let Err(e) = written {
+        return HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to save cookie file",
+            "details": e.to_string()
+        }));
+    }
+    if let Ok(mut health) = HEALTH.lock() {
+        health.remove(&path);
+    }
+    log::info!("Cookie file {} uploaded via /admin/cookies", path.display());
+    test(&path, config).await;
+    HttpResponse::Ok().json(statuses(config))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/admin/cookies",
+    params(
+        ("name" = String, Query, description = "File name in video.cookies.dir, without .txt"),
+        ("admin_key" = Option<String>, Query, description = "Admin key (or X-Admin-Key header)")
+    ),
+    responses(
+        (status = 200, description = "Deleted; the remaining cookie files", body = [CookieFileStatus]),
+        (status = 400, description = "Bad name"),
+        (status = 401, description = "Admin key required"),
+        (status = 404, description = "No such file")
+    )
+)]
+pub async fn delete_cookies(
+    req: HttpRequest,
+    query: web::Query<HashMap<String, String>>,
+    data: web::Data<crate::AppState>,
+) -> impl Responder {
+    let config = &data.config.video;
+    if !crate::routes::admin::is_admin(&req, &data.config) {
+        return unauthorized();
+    }
+    let path = match upload_path(config, query.get("name").map(|n| n.as_str()).unwrap_or("")) {
+        Ok(path) => path,
+        Err(e) => return HttpResponse::BadRequest().json(serde_json::json!({ "error": e })),
+    };
+    match fs::remove_file(&path) {
+        Ok(()) => {
+            if let Ok(mut health) = HEALTH.lock() {
+                health.remove(&path);
+            }
+            log::info!("Cookie file {} deleted via /admin/cookies", path.display());
+            HttpResponse::Ok().json(statuses(config))
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            HttpResponse::NotFound().json(serde_json::json!({ "error": "No such cookie file" }))
+        }
+        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to delete cookie file",
+            "details": e.to_string()
+        })),
+    }
+}
@@ -0,0 +1,88 @@
+//! /robots.txt and /sitemap.xml (`crawlers`). Private instances set `allow: false` to keep
+//! every crawler out; public ones can list the home page, trending and pinned channel
+//! uploads so search engines find the watch pages.
+
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+
+use crate::routes::frontend::{base_url, fetch_json};
+use crate::routes::search::TopVideo;
+
+/// Paths that are per-session, stream bytes or change state; never worth crawling.
+const DISALLOWED: &[&str] = &[
+    "/actions/",
+    "/admin/",
+    "/auth",
+    "/direct_url",
+    "/video.proxy",
+    "/profiles",
+    "/favorites",
+    "/logout",
+];
+
+pub async fn robots_txt(req: HttpRequest, data: web::Data<crate::AppState>) -> impl Responder {
+    let config = &data.config;
+    let mut body = String::from("User-agent: *\n");
+    if config.crawlers.allow {
+        for path in DISALLOWED {
+            body.push_str(&format!("Disallow: {}\n", path));
+        }
+        if config.crawlers.sitemap {
+            body.push_str(&format!("\nSitemap: {}/sitemap.xml\n", base_url(&req, config)));
+        }
+    } else {
+        body.push_str("Disallow: /\n");
+    }
+    HttpResponse::Ok()
+        .content_type("text/plain; charset=utf-8")
+        .insert_header(("Cache-Control", "public, max-age=3600"))
+        .body(body)
+}
+
+fn url_entry(loc: &str, lastmod: Option<&str>) -> String {
+    let lastmod = lastmod
+        .filter(|l| !l.is_empty())
+        .map(|l| format!("<lastmod>{}</lastmod>", html_escape::encode_text(l)))
+        .unwrap_or_default();
+    format!("  <url><loc>{}</loc>{}</url>\n", html_escape::encode_text(loc), lastmod)
+}
+
+pub async fn sitemap_xml(req: HttpRequest, data: web::Data<crate::AppState>) -> impl Responder {
+    let config = &data.config;
+    if !config.crawlers.allow || !config.crawlers.sitemap {
+        return HttpResponse::NotFound().finish();
+    }
+    let base = base_url(&req, config);
+    let watch_url = |id: &str| format!("{}/watch?v={}", base, urlencoding::encode(id));
+
+    let mut seen = std::collections::HashSet::new();
+    let mut body = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+    );
+    body.push_str(&url_entry(&format!("{}/", base), None));
+
+    for video in crate::routes::pinned::latest(usize::MAX) {
+        if seen.insert(video.video_id.clone()) {
+            body.push_str(&url_entry(&watch_url(&video.video_id), Some(&video.published)));
+        }
+    }
+    let count = config.crawlers.sitemap_trending_count;
+    if count > 0 {
+        match fetch_json::<Vec<TopVideo>>(&req, &base, &format!("/get_top_videos.php?count={}", count)).await {
+            Ok(videos) => {
+                for video in videos {
+                    if seen.insert(video.video_id.clone()) {
+                        body.push_str(&url_entry(&watch_url(&video.video_id), None));
+                    }
+                }
+            }
+            // a sitemap without trending is still a valid sitemap
+            Err(e) => crate::log::info!("Sitemap: failed to fetch trending: {}", e),
+        }
+    }
+    body.push_str("</urlset>\n");
+
+    HttpResponse::Ok()
+        .content_type("application/xml; charset=utf-8")
+        .insert_header(("Cache-Control", format!("public, max-age={}", config.cache.trending_max_age)))
+        .body(body)
+}
@@ -0,0 +1,380 @@
+//! Typed YouTube Data API v3 responses. Handlers parse upstream bodies into these with `parse`,
+//! so a renamed or retyped field shows up in the log with the endpoint and the serde error
+//! instead of quietly turning into empty strings. Fields that depend on `part=` or are
+//! legitimately absent are `Option` / defaulted. The models follow the API rather than today's
+//! handlers (nothing calls search.list or commentThreads.list yet), hence the `dead_code` allow.
+
+#![allow(dead_code)]
+
+use actix_web::HttpResponse;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+
+/// `*.list` envelope.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiList<T> {
+    #[serde(default = "Vec::new")]
+    pub items: Vec<T>,
+    pub next_page_token: Option<String>,
+    pub prev_page_token: Option<String>,
+    pub page_info: Option<PageInfo>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct PageInfo {
+    pub total_results: Option<u64>,
+    pub results_per_page: Option<u64>,
+}
+
+/// Counts come as JSON strings (`"viewCount": "123"`); numbers are accepted too.
+fn count<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    Ok(match Option::<Value>::deserialize(deserializer)? {
+        Some(Value::String(s)) => s.parse().ok(),
+        Some(Value::Number(n)) => n.as_u64(),
+        _ => None,
+    })
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Thumbnail {
+    pub url: String,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+pub struct Thumbnails {
+    pub default: Option<Thumbnail>,
+    pub medium: Option<Thumbnail>,
+    pub high: Option<Thumbnail>,
+    pub standard: Option<Thumbnail>,
+    pub maxres: Option<Thumbnail>,
+}
+
+impl Thumbnails {
+    /// `high`, falling back to the next smaller size that is present.
+    pub fn high_url(&self) -> Option<&str> {
+        self.high
+            .as_ref()
+            .or(self.medium.as_ref())
+            .or(self.default.as_ref())
+            .map(|t| t.url.as_str())
+    }
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+pub struct Localized {
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub description: String,
+}
+
+// videos.list
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Video {
+    pub id: String,
+    pub snippet: Option<VideoSnippet>,
+    pub content_details: Option<VideoContentDetails>,
+    pub statistics: Option<VideoStatistics>,
+    /// Only with the owner's token for private and unlisted videos.
+    pub status: Option<VideoStatus>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct VideoSnippet {
+    pub published_at: Option<String>,
+    pub channel_id: Option<String>,
+    pub title: String,
+    #[serde(default)]
+    pub description: String,
+    pub channel_title: Option<String>,
+    #[serde(default)]
+    pub thumbnails: Thumbnails,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub category_id: Option<String>,
+    pub live_broadcast_content: Option<String>,
+    /// Present with `hl=`: title and description in that language.
+    pub localized: Option<Localized>,
+}
+
+impl VideoSnippet {
+    /// Title in the requested language when the API translated it.
+    pub fn display_title(&self) -> &str {
+        self.localized
+            .as_ref()
+            .map(|l| l.title.as_str())
+            .filter(|t| !t.is_empty())
+            .unwrap_or(&self.title)
+    }
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct VideoContentDetails {
+    /// ISO 8601, e.g. `PT4M13S`.
+    pub duration: Option<String>,
+    pub definition: Option<String>,
+    pub caption: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct VideoStatistics {
+    #[serde(default, deserialize_with = "count")]
+    pub view_count: Option<u64>,
+    #[serde(default, deserialize_with = "count")]
+    pub like_count: Option<u64>,
+    #[serde(default, deserialize_with = "count")]
+    pub comment_count: Option<u64>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct VideoStatus {
+    /// `public`, `unlisted` or `private`.
+    pub privacy_status: Option<String>,
+    /// `uploaded` (still processing), `processed`, `failed`, `rejected` or `deleted`.
+    pub upload_status: Option<String>,
+}
+
+// search.list
+
+#[derive(Deserialize, Debug)]
+pub struct SearchResult {
+    pub id: SearchResultId,
+    pub snippet: Option<SearchSnippet>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchResultId {
+    /// `youtube#video`, `youtube#channel` or `youtube#playlist`.
+    pub kind: String,
+    pub video_id: Option<String>,
+    pub channel_id: Option<String>,
+    pub playlist_id: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchSnippet {
+    pub published_at: Option<String>,
+    pub channel_id: Option<String>,
+    pub title: String,
+    #[serde(default)]
+    pub description: String,
+    pub channel_title: Option<String>,
+    #[serde(default)]
+    pub thumbnails: Thumbnails,
+    pub live_broadcast_content: Option<String>,
+}
+
+// channels.list
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Channel {
+    pub id: String,
+    pub snippet: Option<ChannelSnippet>,
+    pub statistics: Option<ChannelStatistics>,
+    pub content_details: Option<ChannelContentDetails>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ChannelContentDetails {
+    #[serde(default)]
+    pub related_playlists: RelatedPlaylists,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct RelatedPlaylists {
+    /// The channel's uploads as a playlist (`UU...`).
+    pub uploads: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ChannelSnippet {
+    pub title: String,
+    #[serde(default)]
+    pub description: String,
+    pub custom_url: Option<String>,
+    pub published_at: Option<String>,
+    #[serde(default)]
+    pub thumbnails: Thumbnails,
+    pub localized: Option<Localized>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ChannelStatistics {
+    #[serde(default, deserialize_with = "count")]
+    pub view_count: Option<u64>,
+    /// Absent when `hidden_subscriber_count` is set.
+    #[serde(default, deserialize_with = "count")]
+    pub subscriber_count: Option<u64>,
+    #[serde(default)]
+    pub hidden_subscriber_count: bool,
+    #[serde(default, deserialize_with = "count")]
+    pub video_count: Option<u64>,
+}
+
+// commentThreads.list
+
+#[derive(Deserialize, Debug)]
+pub struct CommentThread {
+    pub id: String,
+    pub snippet: CommentThreadSnippet,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CommentThreadSnippet {
+    pub video_id: Option<String>,
+    pub top_level_comment: Comment,
+    #[serde(default)]
+    pub total_reply_count: u64,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Comment {
+    pub id: String,
+    pub snippet: CommentSnippet,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CommentSnippet {
+    pub author_display_name: String,
+    pub author_profile_image_url: Option<String>,
+    pub author_channel_id: Option<AuthorChannelId>,
+    /// HTML as shown on the site.
+    #[serde(default)]
+    pub text_display: String,
+    #[serde(default)]
+    pub text_original: String,
+    #[serde(default)]
+    pub like_count: u64,
+    pub published_at: Option<String>,
+    pub updated_at: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct AuthorChannelId {
+    pub value: String,
+}
+
+// playlists.list / playlistItems.list
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Playlist {
+    pub id: String,
+    pub snippet: Option<PlaylistSnippet>,
+    pub content_details: Option<PlaylistContentDetails>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaylistSnippet {
+    pub title: String,
+    #[serde(default)]
+    pub description: String,
+    pub channel_id: Option<String>,
+    pub channel_title: Option<String>,
+    pub published_at: Option<String>,
+    #[serde(default)]
+    pub thumbnails: Thumbnails,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaylistContentDetails {
+    pub item_count: Option<u64>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaylistItem {
+    pub id: String,
+    pub snippet: Option<PlaylistItemSnippet>,
+    pub content_details: Option<PlaylistItemContentDetails>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaylistItemSnippet {
+    pub title: String,
+    #[serde(default)]
+    pub description: String,
+    pub published_at: Option<String>,
+    /// Owner of the playlist, not of the video.
+    pub channel_title: Option<String>,
+    pub video_owner_channel_id: Option<String>,
+    pub video_owner_channel_title: Option<String>,
+    pub position: Option<u64>,
+    #[serde(default)]
+    pub thumbnails: Thumbnails,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaylistItemContentDetails {
+    pub video_id: String,
+    pub video_published_at: Option<String>,
+}
+
+// videoCategories.list, subscriptions.list, videos.getRating
+
+#[derive(Deserialize, Debug)]
+pub struct VideoCategory {
+    pub id: String,
+    pub snippet: VideoCategorySnippet,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct VideoCategorySnippet {
+    pub title: String,
+    #[serde(default)]
+    pub assignable: bool,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Subscription {
+    pub id: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct VideoRating {
+    pub video_id: String,
+    /// `like`, `dislike`, `none` or `unspecified`.
+    pub rating: String,
+}
+
+/// Deserializes a Data API body, logging the endpoint and the serde error (which names the field)
+/// when it doesn't match. Google error bodies should be handled before calling this.
+pub fn parse<T: DeserializeOwned>(endpoint: &str, body: Value) -> Result<T, String> {
+    serde_json::from_value(body).map_err(|e| {
+        log::warn!("Data API {}: unexpected response shape: {}", endpoint, e);
+        crate::routes::metrics::record_upstream_error(endpoint, 0, "schema_mismatch");
+        format!("Unexpected {} response: {}", endpoint, e)
+    })
+}
+
+/// 502 for a body `parse` rejected.
+pub fn mismatch_response(details: String) -> HttpResponse {
+    HttpResponse::BadGateway().json(serde_json::json!({
+        "error": "Unexpected YouTube API response",
+        "details": details
+    }))
+}
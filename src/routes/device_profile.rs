@@ -0,0 +1,148 @@
+//! Device profiles (`video.device_profiles`): what an old handheld or console can decode,
+//! told apart by its User-Agent. Requests that pass no `quality=` get at most the profile's
+//! height, and baseline devices (no Main/High profile H.264, no Opus) get Baseline H.264 and
+//! AAC where YouTube offers them. Players with a quality picker are left alone.
+
+use actix_web::HttpRequest;
+
+use crate::config::{Config, DeviceProfilesConfig};
+
+#[derive(Clone, Debug)]
+pub struct DeviceProfile {
+    pub name: String,
+    pub max_height: u32,
+    /// Baseline H.264 and AAC only.
+    pub baseline: bool,
+}
+
+struct Builtin {
+    name: &'static str,
+    /// Lowercase User-Agent substrings.
+    markers: &'static [&'static str],
+    max_height: u32,
+    baseline: bool,
+}
+
+/// Checked in order, so a marker that contains another comes first ("nintendo wiiu" before
+/// "nintendo wii").
+const BUILTIN: &[Builtin] = &[
+    Builtin {
+        name: "PS Vita",
+        markers: &["playstation vita"],
+        max_height: 544,
+        baseline: false,
+    },
+    Builtin {
+        name: "PSP",
+        markers: &["playstation portable", "(psp"],
+        max_height: 272,
+        baseline: true,
+    },
+    Builtin {
+        name: "Wii U",
+        markers: &["nintendo wiiu"],
+        max_height: 720,
+        baseline: false,
+    },
+    Builtin {
+        name: "Wii",
+        markers: &["nintendo wii"],
+        max_height: 360,
+        baseline: true,
+    },
+    Builtin {
+        name: "Nintendo 3DS",
+        markers: &["nintendo 3ds"],
+        max_height: 240,
+        baseline: true,
+    },
+    Builtin {
+        name: "Nintendo DSi",
+        markers: &["nintendo dsi"],
+        max_height: 240,
+        baseline: true,
+    },
+    Builtin {
+        name: "Symbian",
+        markers: &["symbian", "series60", "series 60", "s60;"],
+        max_height: 360,
+        baseline: true,
+    },
+    Builtin {
+        name: "BlackBerry",
+        markers: &["blackberry"],
+        max_height: 360,
+        baseline: true,
+    },
+    Builtin {
+        name: "Windows Mobile",
+        markers: &["windows ce", "iemobile/6", "iemobile 6"],
+        max_height: 240,
+        baseline: true,
+    },
+    Builtin {
+        name: "Windows Phone 7",
+        markers: &["windows phone os 7"],
+        max_height: 480,
+        baseline: true,
+    },
+    Builtin {
+        name: "J2ME",
+        markers: &["midp-", "j2me"],
+        max_height: 240,
+        baseline: true,
+    },
+    Builtin {
+        name: "Android 1.x/2.x",
+        markers: &["android 1.", "android 2."],
+        max_height: 360,
+        baseline: true,
+    },
+    Builtin {
+        name: "Android 3.x/4.0",
+        markers: &["android 3.", "android 4.0"],
+        max_height: 480,
+        baseline: false,
+    },
+    Builtin {
+        name: "iPhone OS 3/4",
+        markers: &["iphone os 3_", "iphone os 4_", "cpu os 3_", "cpu os 4_"],
+        max_height: 360,
+        baseline: true,
+    },
+];
+
+/// The profile for `user_agent`: the first custom one that matches, else a built-in one.
+pub fn for_user_agent(user_agent: &str, config: &DeviceProfilesConfig) -> Option<DeviceProfile> {
+    if !config.enabled || user_agent.is_empty() {
+        return None;
+    }
+    let ua = user_agent.to_ascii_lowercase();
+    let custom = config.custom.iter().find(|p| {
+        p.user_agent
+            .iter()
+            .map(|m| m.trim().to_ascii_lowercase())
+            .any(|m| !m.is_empty() && ua.contains(&m))
+    });
+    if let Some(p) = custom {
+        return Some(DeviceProfile {
+            name: p.name.clone(),
+            max_height: p.max_height,
+            baseline: p.baseline,
+        });
+    }
+    BUILTIN
+        .iter()
+        .find(|b| b.markers.iter().any(|m| ua.contains(m)))
+        .map(|b| DeviceProfile {
+            name: b.name.to_string(),
+            max_height: b.max_height,
+            baseline: b.baseline,
+        })
+}
+
+/// The profile for the request's User-Agent, if any.
+pub fn detect(req: &HttpRequest, config: &Config) -> Option<DeviceProfile> {
+    let user_agent = req.headers().get("User-Agent").and_then(|v| v.to_str().ok())?;
+    for_user_agent(user_agent, &config.video.device_profiles)
+}
@@ -0,0 +1,50 @@
+//! `envelope=true`: one response shape for list endpoints. Without it each endpoint keeps its
+//! historical shape (bare arrays, `{videos}`, ...), which existing clients depend on.
+
+use actix_web::{web, HttpRequest};
+use serde::Serialize;
+use std::collections::HashMap;
+use utoipa::ToSchema;
+
+use crate::routes::additional::{HistoryItem, RecommendationItem};
+use crate::routes::channel::ChannelVideo;
+use crate::routes::search::{PlaylistVideo, SearchResult, TopVideo};
+
+#[derive(Serialize, ToSchema)]
+#[aliases(
+    TopVideoList = ListResponse<TopVideo>,
+    SearchResultList = ListResponse<SearchResult>,
+    PlaylistVideoList = ListResponse<PlaylistVideo>,
+    ChannelVideoList = ListResponse<ChannelVideo>,
+    HistoryItemList = ListResponse<HistoryItem>,
+    RecommendationItemList = ListResponse<RecommendationItem>
+)]
+pub struct ListResponse<T> {
+    pub items: Vec<T>,
+    /// Value for the endpoint's paging parameter to get the next page; null on the last page
+    /// or where the endpoint does not page.
+    pub next_page_token: Option<String>,
+    /// Size of the whole list, when known.
+    pub total: Option<u64>,
+    /// Parts of the list that could not be fetched or were left out.
+    pub warnings: Vec<String>,
+}
+
+impl<T> ListResponse<T> {
+    pub fn new(items: Vec<T>) -> Self {
+        Self {
+            items,
+            next_page_token: None,
+            total: None,
+            warnings: Vec::new(),
+        }
+    }
+}
+
+/// Whether the client asked for the envelope (`envelope=true` or `envelope=1`).
+pub fn requested(req: &HttpRequest) -> bool {
+    web::Query::<HashMap<String, String>>::from_query(req.query_string())
+        .ok()
+        .and_then(|q| q.get("envelope").map(|v| v == "true" || v == "1"))
+        .unwrap_or(false)
+}
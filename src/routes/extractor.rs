@@ -0,0 +1,398 @@
+//! Native stream resolution through InnerTube's `player` endpoint (`video.extractor`). The
+//! ANDROID-family and TV clients answer with plain googlevideo URLs for most videos, which spares
+//! a yt-dlp process (Python start-up alone is a second or more on small boards) per resolve.
+//! Anything this can't handle — ciphered URLs, live streams, age or region gates — is left to
+//! yt-dlp, which `video::yt_dlp_info` runs when every client here has failed.
+//!
+//! YouTube breaks one client at a time, so each keeps a window of recent outcomes per video type
+//! and, with `video.extractor.prefer_working`, the best-scoring client is tried first.
+
+use lazy_static::lazy_static;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use utoipa::ToSchema;
+
+use crate::routes::video::{YtDlpFormat, YtDlpInfo};
+
+/// One InnerTube client identity; each gets a differently filtered set of formats.
+struct PlayerClient {
+    name: &'static str,
+    /// `X-YouTube-Client-Name`.
+    id: u32,
+    version: &'static str,
+    user_agent: &'static str,
+    /// `context.client` besides name and version.
+    extra: fn() -> Value,
+}
+
+const ANDROID_VR: PlayerClient = PlayerClient {
+    name: "ANDROID_VR",
+    id: 28,
+    version: "1.60.19",
+    user_agent: "com.google.android.apps.youtube.vr.oculus/1.60.19 (Linux; U; Android 12L; eureka-user Build/SQ3A.220605.009.A1) gzip",
+    extra: || {
+        json!({
+            "deviceMake": "Oculus",
+            "deviceModel": "Quest 3",
+            "androidSdkVersion": 32,
+            "osName": "Android",
+            "osVersion": "12L",
+        })
+    },
+};
+
+const ANDROID: PlayerClient = PlayerClient {
+    name: "ANDROID",
+    id: 3,
+    version: "19.44.38",
+    user_agent: "com.google.android.youtube/19.44.38 (Linux; U; Android 11) gzip",
+    extra: || json!({ "androidSdkVersion": 30, "osName": "Android", "osVersion": "11" }),
+};
+
+const IOS: PlayerClient = PlayerClient {
+    name: "IOS",
+    id: 5,
+    version: "19.45.4",
+    user_agent: "com.google.ios.youtube/19.45.4 (iPhone16,2; U; CPU iOS 18_1_0 like Mac OS X;)",
+    extra: || {
+        json!({
+            "deviceMake": "Apple",
+            "deviceModel": "iPhone16,2",
+            "osName": "iPhone",
+            "osVersion": "18.1.0.22B83",
+        })
+    },
+};
+
+/// Serves embeds the app clients refuse, but signs many URLs; those formats are skipped.
+const TVHTML5: PlayerClient = PlayerClient {
+    name: "TVHTML5",
+    id: 7,
+    version: "7.20250312.16.00",
+    user_agent: "Mozilla/5.0 (ChromiumStylePlatform) Cobalt/Version",
+    extra: || json!({}),
+};
+
+const CLIENTS: &[&PlayerClient] = &[&ANDROID_VR, &ANDROID, &IOS, &TVHTML5];
+
+pub fn client_names() -> Vec<&'static str> {
+    CLIENTS.iter().map(|c| c.name).collect()
+}
+
+/// Outcomes kept per client and video type for the success rate.
+const WINDOW: usize = 50;
+
+/// Every video until a client's answer says otherwise.
+const REGULAR: &str = "regular";
+const AGE_RESTRICTED: &str = "age_restricted";
+
+#[derive(Default)]
+struct ClientRecord {
+    recent: VecDeque<bool>,
+    total_successes: u64,
+    total_failures: u64,
+    last_error: Option<String>,
+}
+
+impl ClientRecord {
+    /// Share of successes in the window; an untried client counts as working.
+    fn success_rate(&self) -> f64 {
+        if self.recent.is_empty() {
+            return 1.0;
+        }
+        self.recent.iter().filter(|ok| **ok).count() as f64 / self.recent.len() as f64
+    }
+}
+
+lazy_static! {
+    static ref RECORDS: Mutex<HashMap<(&'static str, &'static str), ClientRecord>> = Mutex::new(HashMap::new());
+}
+
+fn record(client: &'static str, video_type: &'static str, outcome: Result<(), &str>) {
+    let Ok(mut records) = RECORDS.lock() else {
+        return;
+    };
+    let entry = records.entry((client, video_type)).or_default();
+    if entry.recent.len() == WINDOW {
+        entry.recent.pop_front();
+    }
+    entry.recent.push_back(outcome.is_ok());
+    match outcome {
+        Ok(()) => entry.total_successes += 1,
+        Err(e) => {
+            entry.total_failures += 1;
+            entry.last_error = Some(e.to_string());
+        }
+    }
+}
+
+/// Best recent success rate first; ties keep the configured order.
+fn rank(clients: &mut [&'static PlayerClient], video_type: &'static str) {
+    let Ok(records) = RECORDS.lock() else {
+        return;
+    };
+    let rate = |c: &PlayerClient| records.get(&(c.name, video_type)).map_or(1.0, |r| r.success_rate());
+    clients.sort_by(|a, b| rate(b).total_cmp(&rate(a)));
+}
+
+/// The type a refusal reveals, so the remaining clients are ranked by how they do on those.
+fn revealed_type(response: &Value) -> Option<&'static str> {
+    let reason = response
+        .pointer("/playabilityStatus/reason")
+        .and_then(|r| r.as_str())?
+        .to_lowercase();
+    (reason.contains("confirm your age") || reason.contains("inappropriate for some users"))
+        .then_some(AGE_RESTRICTED)
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ClientStats {
+    pub client: String,
+    pub video_type: String,
+    /// Over the last `attempts` resolutions (at most 50).
+    pub success_rate: f64,
+    pub attempts: usize,
+    pub total_successes: u64,
+    pub total_failures: u64,
+    pub last_error: Option<String>,
+}
+
+/// Per client and video type, in the order they'd currently be tried.
+pub fn stats() -> Vec<ClientStats> {
+    let Ok(records) = RECORDS.lock() else {
+        return Vec::new();
+    };
+    let mut stats: Vec<ClientStats> = records
+        .iter()
+        .map(|((client, video_type), r)| ClientStats {
+            client: client.to_string(),
+            video_type: video_type.to_string(),
+            success_rate: (r.success_rate() * 1000.0).round() / 1000.0,
+            attempts: r.recent.len(),
+            total_successes: r.total_successes,
+            total_failures: r.total_failures,
+            last_error: r.last_error.clone(),
+        })
+        .collect();
+    stats.sort_by(|a, b| {
+        a.video_type
+            .cmp(&b.video_type)
+            .then(b.success_rate.total_cmp(&a.success_rate))
+            .then(a.client.cmp(&b.client))
+    });
+    stats
+}
+
+async fn player_response(
+    client: &PlayerClient,
+    video_id: &str,
+    config: &crate::config::Config,
+) -> Result<Value, String> {
+    let mut context = (client.extra)();
+    context["clientName"] = json!(client.name);
+    context["clientVersion"] = json!(client.version);
+    context["hl"] = json!("en");
+    context["gl"] = json!("US");
+    let po_token = crate::routes::po_token::ensure(config).await;
+    if let Some(token) = po_token.as_ref().filter(|t| !t.visitor_data.is_empty()) {
+        context["visitorData"] = json!(token.visitor_data);
+    }
+    let mut body = json!({
+        "context": { "client": context },
+        "videoId": video_id,
+        "contentCheckOk": true,
+        "racyCheckOk": true,
+    });
+    if let Some(token) = &po_token {
+        body["serviceIntegrityDimensions"] = json!({ "poToken": token.po_token });
+    }
+    let path = match config.get_innertube_key() {
+        Some(key) => format!("player?key={}&prettyPrint=false", key),
+        None => "player?prettyPrint=false".to_string(),
+    };
+    let mut request = crate::http_client::shared()
+        .post(crate::endpoints::innertube(&path))
+        .header("User-Agent", client.user_agent)
+        .header("X-YouTube-Client-Name", client.id.to_string())
+        .header("X-YouTube-Client-Version", client.version)
+        .header("Content-Type", "application/json");
+    if let Some(token) = po_token.as_ref().filter(|t| !t.visitor_data.is_empty()) {
+        request = request.header("X-Goog-Visitor-Id", token.visitor_data.as_str());
+    }
+    let resp = request
+        .json(&body)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !resp.status().is_success() {
+        return Err(format!("player API HTTP {}", resp.status()));
+    }
+    resp.json::<Value>().await.map_err(|e| e.to_string())
+}
+
+/// `video/mp4; codecs="avc1.42001E, mp4a.40.2"` -> (`video`, `mp4`, [`avc1.42001E`, `mp4a.40.2`]).
+fn parse_mime(mime: &str) -> Option<(&str, &str, Vec<&str>)> {
+    let (kind, params) = mime.split_once(';').unwrap_or((mime, ""));
+    let (media, container) = kind.trim().split_once('/')?;
+    let codecs = params
+        .trim()
+        .strip_prefix("codecs=")
+        .map(|c| c.trim_matches('"').split(',').map(str::trim).collect())
+        .unwrap_or_default();
+    Some((media, container, codecs))
+}
+
+/// The yt-dlp view of one `formats`/`adaptiveFormats` entry; `None` for ciphered ones.
+fn parse_format(format: &Value) -> Option<YtDlpFormat> {
+    let url = format.get("url")?.as_str()?;
+    let itag = format.get("itag")?.as_u64()?;
+    let (media, container, codecs) = parse_mime(format.get("mimeType")?.as_str()?)?;
+    let is_video = media == "video";
+    let codec = |i: usize| codecs.get(i).map(|c| c.to_string()).unwrap_or_else(|| "none".to_string());
+    let (vcodec, acodec) = if is_video {
+        (codec(0), codec(1))
+    } else {
+        ("none".to_string(), codec(0))
+    };
+    let ext = match (is_video, container) {
+        (false, "mp4") => "m4a",
+        (_, "3gpp") => "3gp",
+        (_, other) => other,
+    };
+    let number = |key: &str| format.get(key).and_then(|v| v.as_u64());
+    // Int64 fields come as strings
+    let string_number = |key: &str| format.get(key).and_then(|v| v.as_str()).and_then(|s| s.parse::<u64>().ok());
+    let is_drc = format.get("isDrc").and_then(|d| d.as_bool()).unwrap_or(false);
+    Some(YtDlpFormat {
+        format_id: Some(if is_drc { format!("{}-drc", itag) } else { itag.to_string() }),
+        url: Some(url.to_string()),
+        ext: Some(ext.to_string()),
+        width: number("width").map(|w| w as u32),
+        height: number("height").map(|h| h as u32),
+        fps: format.get("fps").and_then(|f| f.as_f64()),
+        asr: string_number("audioSampleRate").map(|r| r as u32),
+        filesize: string_number("contentLength"),
+        filesize_approx: None,
+        vcodec: Some(vcodec),
+        acodec: Some(acodec),
+        tbr: number("bitrate").map(|b| b as f64 / 1000.0),
+        abr: (!is_video)
+            .then(|| number("averageBitrate").or(number("bitrate")))
+            .flatten()
+            .map(|b| b as f64 / 1000.0),
+        protocol: Some("https".to_string()),
+    })
+}
+
+/// Caption tracks keyed by language, split into uploaded and automatic (`kind: asr`) ones.
+fn parse_captions(response: &Value) -> (HashMap<String, Value>, HashMap<String, Value>) {
+    let mut manual = HashMap::new();
+    let mut automatic = HashMap::new();
+    let tracks = response
+        .pointer("/captions/playerCaptionsTracklistRenderer/captionTracks")
+        .and_then(|t| t.as_array());
+    for track in tracks.into_iter().flatten() {
+        let (Some(lang), Some(url)) = (
+            track.get("languageCode").and_then(|l| l.as_str()),
+            track.get("baseUrl").and_then(|u| u.as_str()),
+        ) else {
+            continue;
+        };
+        let target = if track.get("kind").and_then(|k| k.as_str()) == Some("asr") {
+            &mut automatic
+        } else {
+            &mut manual
+        };
+        target.insert(lang.to_string(), json!([{ "url": url }]));
+    }
+    (manual, automatic)
+}
+
+fn parse_player(response: &Value) -> Result<YtDlpInfo, String> {
+    let status = response
+        .pointer("/playabilityStatus/status")
+        .and_then(|s| s.as_str())
+        .unwrap_or("missing");
+    if status != "OK" {
+        let reason = response
+            .pointer("/playabilityStatus/reason")
+            .and_then(|r| r.as_str())
+            .unwrap_or("no reason given");
+        return Err(format!("{} ({})", status, reason));
+    }
+    let streaming = response.get("streamingData").ok_or("no streamingData")?;
+    let formats: Vec<YtDlpFormat> = ["formats", "adaptiveFormats"]
+        .iter()
+        .filter_map(|key| streaming.get(*key).and_then(|f| f.as_array()))
+        .flatten()
+        .filter_map(parse_format)
+        .collect();
+    if !formats.iter().any(|f| f.vcodec.as_deref() != Some("none")) {
+        return Err("no unciphered video formats".to_string());
+    }
+    let (subtitles, automatic_captions) = parse_captions(response);
+    Ok(YtDlpInfo {
+        duration: response
+            .pointer("/videoDetails/lengthSeconds")
+            .and_then(|l| l.as_str())
+            .and_then(|l| l.parse::<f64>().ok()),
+        is_live: Some(false),
+        formats,
+        // Only in the watch page's engagement panels, which this doesn't load
+        chapters: None,
+        subtitles: Some(subtitles),
+        automatic_captions: Some(automatic_captions),
+    })
+}
+
+/// Stream info from the first of `video.extractor.clients` that returns usable formats; with
+/// `prefer_working`, tried in order of recent success for the video's type.
+pub(crate) async fn player_info(
+    video_id: &str,
+    config: &crate::config::Config,
+) -> Result<YtDlpInfo, String> {
+    let mut errors = Vec::new();
+    let mut remaining: Vec<&'static PlayerClient> = Vec::new();
+    for name in &config.video.extractor.clients {
+        match CLIENTS.iter().find(|c| c.name.eq_ignore_ascii_case(name)) {
+            Some(client) => remaining.push(client),
+            None => errors.push(format!("{}: unknown client", name)),
+        }
+    }
+    let mut video_type = REGULAR;
+    while !remaining.is_empty() {
+        if config.video.extractor.prefer_working {
+            rank(&mut remaining, video_type);
+        }
+        let client = remaining.remove(0);
+        let response = match player_response(client, video_id, config).await {
+            Ok(r) => r,
+            Err(e) => {
+                record(client.name, video_type, Err(&e));
+                errors.push(format!("{}: {}", client.name, e));
+                continue;
+            }
+        };
+        // Live streams only come as HLS, which every client serves alike
+        if response.pointer("/videoDetails/isLive").and_then(|l| l.as_bool()) == Some(true) {
+            return Err("live stream".to_string());
+        }
+        if let Some(t) = revealed_type(&response) {
+            video_type = t;
+        }
+        match parse_player(&response) {
+            Ok(info) => {
+                record(client.name, video_type, Ok(()));
+                log::debug!("Resolved {} natively with the {} client", video_id, client.name);
+                return Ok(info);
+            }
+            Err(e) => {
+                record(client.name, video_type, Err(&e));
+                errors.push(format!("{}: {}", client.name, e));
+            }
+        }
+    }
+    Err(errors.join("; "))
+}
@@ -0,0 +1,271 @@
+//! Local "Liked" and "Favorites" lists (`favorites.*`) for devices that never sign in to Google.
+//! Lists belong to the selected profile, otherwise to the browser via a `device_id` cookie
+//! handed out on the first add.
+
+use actix_web::http::header::SET_COOKIE;
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use utoipa::ToSchema;
+
+use crate::config::Config;
+use crate::db;
+
+const DEVICE_COOKIE: &str = "device_id";
+const LISTS: [&str; 2] = ["favorites", "liked"];
+
+#[derive(Serialize, Clone, ToSchema)]
+pub struct LocalListItem {
+    pub video_id: String,
+    pub title: String,
+    pub author: String,
+    /// Unix seconds.
+    pub added_at: i64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct LocalListResponse {
+    pub list: String,
+    pub items: Vec<LocalListItem>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct LocalListForm {
+    /// `favorites` (default) or `liked`.
+    pub list: Option<String>,
+    pub video_id: String,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub author: String,
+    /// Local path to redirect to (frontend forms).
+    pub redirect: Option<String>,
+}
+
+fn enabled(config: &Config) -> bool {
+    config.favorites.enabled && db::is_ready()
+}
+
+fn list_name(list: Option<&str>) -> Option<&'static str> {
+    let list = list.unwrap_or("favorites");
+    LISTS.iter().copied().find(|l| *l == list)
+}
+
+fn valid_video_id(id: &str) -> bool {
+    id.len() == 11
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Owner key of the request's lists: `profile:<id>` or `device:<cookie>`.
+pub(crate) fn owner(req: &HttpRequest, config: &Config) -> Option<String> {
+    if config.profiles.enabled {
+        if let Some(profile) = crate::routes::profiles::current_profile(req) {
+            return Some(format!("profile:{}", profile.id));
+        }
+    }
+    req.cookie(DEVICE_COOKIE)
+        .map(|c| c.value().to_string())
+        .filter(|v| !v.is_empty() && v.len() <= 64)
+        .map(|v| format!("device:{}", v))
+}
+
+pub(crate) fn items(owner: &str, list: &str, limit: u32) -> Vec<LocalListItem> {
+    db::with_conn(|conn| {
+        let mut stmt = conn.prepare(
+            "SELECT video_id, title, author, added_at FROM local_lists
+             WHERE owner = ?1 AND list = ?2 ORDER BY added_at DESC LIMIT ?3",
+        )?;
+        let items = stmt
+            .query_map(params![owner, list, limit], |row| {
+                Ok(LocalListItem {
+                    video_id: row.get(0)?,
+                    title: row.get(1)?,
+                    author: row.get(2)?,
+                    added_at: row.get(3)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(items)
+    })
+    .unwrap_or_default()
+}
+
+pub(crate) fn contains(owner: &str, list: &str, video_id: &str) -> bool {
+    db::with_conn(|conn| {
+        conn.query_row(
+            "SELECT COUNT(*) FROM local_lists WHERE owner = ?1 AND list = ?2 AND video_id = ?3",
+            params![owner, list, video_id],
+            |row| row.get::<_, i64>(0),
+        )
+    })
+    .map(|n| n > 0)
+    .unwrap_or(false)
+}
+
+/// Drops every list of `owner` (used when a profile is deleted).
+pub(crate) fn remove_owner(owner: &str) {
+    if let Err(e) = db::with_conn(|conn| {
+        conn.execute("DELETE FROM local_lists WHERE owner = ?1", params![owner])
+    }) {
+        log::warn!("Failed to remove local lists of {}: {}", owner, e);
+    }
+}
+
+fn disabled() -> HttpResponse {
+    HttpResponse::NotFound().json(serde_json::json!({
+        "error": "Local favorites are disabled"
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/favorites/list",
+    params(
+        ("list" = Option<String>, Query, description = "favorites (default) or liked")
+    ),
+    responses(
+        (status = 200, description = "Items of the list, newest first", body = LocalListResponse),
+        (status = 400, description = "Unknown list"),
+        (status = 404, description = "Local favorites disabled")
+    )
+)]
+pub async fn get_list(
+    req: HttpRequest,
+    query: web::Query<HashMap<String, String>>,
+    data: web::Data<crate::AppState>,
+) -> impl Responder {
+    let config = &data.config;
+    if !enabled(config) {
+        return disabled();
+    }
+    let list = match list_name(query.get("list").map(|s| s.as_str())) {
+        Some(l) => l,
+        None => {
+            return HttpResponse::BadRequest().json(serde_json::json!({
+                "error": "list must be favorites or liked"
+            }));
+        }
+    };
+    let items = owner(&req, config)
+        .map(|o| items(&o, list, config.favorites.max_items))
+        .unwrap_or_default();
+    HttpResponse::Ok().json(LocalListResponse {
+        list: list.to_string(),
+        items,
+    })
+}
+
+#[utoipa::path(
+    post,
+    path = "/favorites/add",
+    request_body(content = LocalListForm, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "Added (sets the device_id cookie if needed)"),
+        (status = 400, description = "Invalid list or video_id"),
+        (status = 404, description = "Local favorites disabled")
+    )
+)]
+pub async fn add_to_list(
+    req: HttpRequest,
+    form: web::Form<LocalListForm>,
+    data: web::Data<crate::AppState>,
+) -> impl Responder {
+    let config = &data.config;
+    if !enabled(config) {
+        return disabled();
+    }
+    let redirect = form.redirect.as_deref();
+    let list = match list_name(form.list.as_deref()) {
+        Some(l) if valid_video_id(&form.video_id) => l,
+        _ => {
+            return crate::routes::profiles::respond(
+                redirect,
+                false,
+                serde_json::json!({ "error": "Invalid list or video_id" }),
+            );
+        }
+    };
+
+    let (owner, new_device) = match owner(&req, config) {
+        Some(o) => (o, None),
+        None => {
+            let device = uuid::Uuid::new_v4().simple().to_string();
+            (format!("device:{}", device), Some(device))
+        }
+    };
+    let limit = config.favorites.max_items;
+    let result = db::with_conn(|conn| {
+        conn.execute(
+            "INSERT INTO local_lists (owner, list, video_id, title, author, added_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+             ON CONFLICT(owner, list, video_id) DO UPDATE SET added_at = excluded.added_at",
+            params![owner, list, form.video_id, form.title, form.author, db::now_secs()],
+        )?;
+        conn.execute(
+            "DELETE FROM local_lists WHERE owner = ?1 AND list = ?2 AND video_id NOT IN
+             (SELECT video_id FROM local_lists WHERE owner = ?1 AND list = ?2 ORDER BY added_at DESC LIMIT ?3)",
+            params![owner, list, limit],
+        )?;
+        Ok(())
+    });
+    let mut resp = match result {
+        Ok(()) => crate::routes::profiles::respond(redirect, true, serde_json::json!({ "ok": true })),
+        Err(e) => crate::routes::profiles::respond(redirect, false, serde_json::json!({ "error": e })),
+    };
+    if let Some(device) = new_device {
+        let cookie = format!(
+            "{}={}; Path=/; Max-Age=315360000; HttpOnly; SameSite=Lax",
+            DEVICE_COOKIE, device
+        );
+        if let Ok(value) = cookie.parse() {
+            resp.headers_mut().append(SET_COOKIE, value);
+        }
+    }
+    resp
+}
+
+#[utoipa::path(
+    post,
+    path = "/favorites/remove",
+    request_body(content = LocalListForm, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "Removed"),
+        (status = 400, description = "Invalid list"),
+        (status = 404, description = "Local favorites disabled")
+    )
+)]
+pub async fn remove_from_list(
+    req: HttpRequest,
+    form: web::Form<LocalListForm>,
+    data: web::Data<crate::AppState>,
+) -> impl Responder {
+    let config = &data.config;
+    if !enabled(config) {
+        return disabled();
+    }
+    let redirect = form.redirect.as_deref();
+    let list = match list_name(form.list.as_deref()) {
+        Some(l) => l,
+        None => {
+            return crate::routes::profiles::respond(
+                redirect,
+                false,
+                serde_json::json!({ "error": "list must be favorites or liked" }),
+            );
+        }
+    };
+    if let Some(owner) = owner(&req, config) {
+        let result = db::with_conn(|conn| {
+            conn.execute(
+                "DELETE FROM local_lists WHERE owner = ?1 AND list = ?2 AND video_id = ?3",
+                params![owner, list, form.video_id],
+            )
+        });
+        if let Err(e) = result {
+            return crate::routes::profiles::respond(redirect, false, serde_json::json!({ "error": e }));
+        }
+    }
+    crate::routes::profiles::respond(redirect, true, serde_json::json!({ "ok": true }))
+}
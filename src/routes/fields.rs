@@ -0,0 +1,77 @@
+//! `fields=` response filtering: lets slow / low-memory clients ask only for the keys they render.
+//! Syntax: comma-separated whitelist, dotted paths go into nested objects and arrays
+//! (e.g. `fields=title,views,comments.author`).
+
+use actix_web::HttpResponse;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+/// Parses the raw (possibly URL-encoded) `fields` query value. `None` means "no filtering".
+pub fn parse_fields(raw: Option<&str>) -> Option<Vec<String>> {
+    let raw = raw?;
+    let decoded = urlencoding::decode(raw)
+        .map(|s| s.into_owned())
+        .unwrap_or_else(|_| raw.to_string());
+    let fields: Vec<String> = decoded
+        .split(',')
+        .map(|f| f.trim().to_string())
+        .filter(|f| !f.is_empty())
+        .collect();
+    if fields.is_empty() {
+        None
+    } else {
+        Some(fields)
+    }
+}
+
+fn filter_value(value: Value, paths: &[Vec<&str>]) -> Value {
+    if paths.iter().any(|p| p.is_empty()) {
+        return value;
+    }
+    match value {
+        Value::Object(map) => {
+            let mut out = Map::new();
+            for (key, child) in map {
+                let sub: Vec<Vec<&str>> = paths
+                    .iter()
+                    .filter(|p| p[0] == key)
+                    .map(|p| p[1..].to_vec())
+                    .collect();
+                if !sub.is_empty() {
+                    out.insert(key, filter_value(child, &sub));
+                }
+            }
+            Value::Object(out)
+        }
+        Value::Array(items) => Value::Array(
+            items
+                .into_iter()
+                .map(|item| filter_value(item, paths))
+                .collect(),
+        ),
+        other => other,
+    }
+}
+
+/// Serializes `body` and keeps only the whitelisted keys.
+pub fn apply_fields<T: Serialize>(body: &T, fields: &[String]) -> Value {
+    let value = serde_json::to_value(body).unwrap_or(Value::Null);
+    let paths: Vec<Vec<&str>> = fields.iter().map(|f| f.split('.').collect()).collect();
+    filter_value(value, &paths)
+}
+
+/// Same as [`apply_fields`], but passes the body through untouched when no `fields=` was given.
+pub fn filter_fields<T: Serialize>(body: &T, fields: Option<&str>) -> Value {
+    match parse_fields(fields) {
+        Some(list) => apply_fields(body, &list),
+        None => serde_json::to_value(body).unwrap_or(Value::Null),
+    }
+}
+
+/// `HttpResponse::Ok().json(body)` honoring an optional `fields=` whitelist.
+pub fn json_with_fields<T: Serialize>(body: &T, fields: Option<&str>) -> HttpResponse {
+    match parse_fields(fields) {
+        Some(list) => HttpResponse::Ok().json(apply_fields(body, &list)),
+        None => HttpResponse::Ok().json(body),
+    }
+}
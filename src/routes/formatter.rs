@@ -0,0 +1,80 @@
+//! Plain description and comment text to HTML, the way YouTube links it: URLs, `1:23`
+//! timestamps (to `/watch?t=`), `#hashtags` (to search) and `@handles` (to the channel page).
+//! Shared by the frontend and the `description_html` fields of the JSON API.
+
+use html_escape::{encode_double_quoted_attribute, encode_text};
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+    static ref TOKEN_REGEX: Regex = Regex::new(
+        r"(?P<url>https?://[^\s<>]+)|(?P<time>\b(?:\d{1,2}:)?\d{1,2}:[0-5]\d\b)|(?P<tag>#[\p{L}\p{N}_]+)|(?P<mention>@[\p{L}\p{N}_.\-]+)"
+    )
+    .expect("valid regex");
+}
+
+/// `1:23` / `1:02:03` in seconds.
+fn timestamp_seconds(text: &str) -> u64 {
+    text.split(':')
+        .fold(0, |total, part| total * 60 + part.parse::<u64>().unwrap_or(0))
+}
+
+fn escape(text: &str) -> String {
+    encode_text(text).replace('\n', "<br>")
+}
+
+fn link(href: &str, label: &str) -> String {
+    format!(r#"<a href="{}">{}</a>"#, encode_double_quoted_attribute(href), encode_text(label))
+}
+
+/// `text` escaped, with newlines as `<br>` and the links above. `base` is prepended to the
+/// frontend paths; timestamps are only linked when `video_id` is known.
+pub fn to_html(text: &str, base: &str, video_id: Option<&str>) -> String {
+    let base = base.trim_end_matches('/');
+    let mut out = String::with_capacity(text.len() + text.len() / 4);
+    let mut last = 0;
+    for caps in TOKEN_REGEX.captures_iter(text) {
+        let token = caps.get(0).expect("whole match");
+        // "C#", "mail@example.com", "v1:23": glued to a word, not a tag, handle or time
+        let glued = text[..token.start()]
+            .chars()
+            .next_back()
+            .is_some_and(|c| c.is_alphanumeric() || c == '/' || c == ':');
+        let html = if let Some(url) = caps.name("url") {
+            let u = url.as_str();
+            Some(format!(
+                r#"<a href="{}" target="_blank" rel="noopener">{}</a>"#,
+                encode_double_quoted_attribute(u),
+                encode_text(u)
+            ))
+        } else if glued {
+            None
+        } else if let Some(time) = caps.name("time") {
+            video_id.map(|id| {
+                let href = format!(
+                    "{}/watch?v={}&t={}",
+                    base,
+                    urlencoding::encode(id),
+                    timestamp_seconds(time.as_str())
+                );
+                link(&href, time.as_str())
+            })
+        } else if let Some(tag) = caps.name("tag") {
+            let href = format!("{}/results?search_query={}", base, urlencoding::encode(tag.as_str()));
+            Some(link(&href, tag.as_str()))
+        } else {
+            caps.name("mention").map(|m| {
+                let handle = m.as_str().trim_end_matches(['.', '-']);
+                let href = format!("{}/channel?handle={}", base, urlencoding::encode(handle));
+                format!("{}{}", link(&href, handle), encode_text(&m.as_str()[handle.len()..]))
+            })
+        };
+        if let Some(html) = html {
+            out.push_str(&escape(&text[last..token.start()]));
+            out.push_str(&html);
+            last = token.end();
+        }
+    }
+    out.push_str(&escape(&text[last..]));
+    out
+}
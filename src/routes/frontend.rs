@@ -1,39 +1,167 @@
 //! Frontend: serves HTML pages with data substituted by Rust (yt2014-style).
-//! Templates are in assets/html/frontend/, assets at /assets.
+//! Templates are in assets/html/frontend/ (Tera syntax, one typed context per template),
+//! assets at /assets.
 
 use actix_web::{web, HttpRequest, HttpResponse, Responder};
 use html_escape::encode_text;
-use serde::Deserialize;
-use std::fs;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant, SystemTime};
+use tera::{Context, Tera};
 
 use crate::config::Config;
+use crate::routes::actions::{MyPlaylistsResponse, NotificationsResponse};
 use crate::routes::additional::{HistoryItem, RecommendationItem};
 use crate::routes::auth::{AuthConfig, TokenStore};
 use crate::routes::channel::{ChannelVideosResponse, ChannelVideo};
-use crate::routes::search::{SearchResult, TopVideo};
+use crate::routes::formatter;
+use crate::routes::search::{PlaylistResponse, SearchResult, TopVideo};
 use crate::routes::video::{RelatedVideo, VideoInfoResponse};
 
-fn base_url(req: &HttpRequest, config: &Config) -> String {
+pub(crate) fn base_url(req: &HttpRequest, config: &Config) -> String {
     if !config.server.main_url.is_empty() {
-        return config.server.main_url.trim_end_matches('/').to_string();
+        return config.public_base(config.server.main_url.trim_end_matches('/').to_string());
     }
     let info = req.connection_info();
     let scheme = info.scheme();
     let host = info.host();
-    format!("{}://{}", scheme, host.trim_end_matches('/'))
+    config.public_base(format!("{}://{}", scheme, host.trim_end_matches('/')))
 }
 
-fn load_template(name: &str) -> String {
-    let path = format!("assets/html/frontend/{}.html", name);
-    fs::read_to_string(&path).unwrap_or_else(|_| format!("<!-- template {} not found -->", name))
+struct CachedTemplate {
+    tera: Arc<Tera>,
+    modified: Option<SystemTime>,
+    checked: Instant,
 }
 
-fn load_root_index() -> String {
-    fs::read_to_string("assets/html/index.html")
-        .unwrap_or_else(|_| "<!-- assets/html/index.html not found -->".to_string())
+lazy_static! {
+    static ref TEMPLATES: RwLock<HashMap<String, CachedTemplate>> = RwLock::new(HashMap::new());
+    /// Unread notification count per session token, for the navbar badge.
+    static ref UNREAD_NOTIFICATIONS: RwLock<HashMap<String, (Instant, u32)>> = RwLock::new(HashMap::new());
 }
 
-async fn fetch_json<T: for<'de> Deserialize<'de>>(
+/// The navbar asks InnerTube for the unread count at most this often per session.
+const NOTIFICATIONS_CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// How long a cached template is served before its mtime is looked at again.
+const TEMPLATE_RECHECK: Duration = Duration::from_secs(2);
+/// A slow disk (network mount, SD card) gets this long before the cached copy is served instead.
+const TEMPLATE_READ_TIMEOUT: Duration = Duration::from_secs(2);
+/// Anything bigger is not a template; refused rather than read into memory on every page.
+const TEMPLATE_MAX_BYTES: u64 = 1024 * 1024;
+
+fn template_path(name: &str) -> String {
+    format!("assets/html/{}.html", name)
+}
+
+/// Tera nests the useful part (line, column, what was expected) in the error's sources.
+fn tera_error(e: tera::Error) -> String {
+    let mut message = e.to_string();
+    let mut source = std::error::Error::source(&e);
+    while let Some(inner) = source {
+        message.push_str(": ");
+        message.push_str(&inner.to_string());
+        source = inner.source();
+    }
+    message
+}
+
+/// Reads and compiles a template through the cache. Edits on disk show up within
+/// `TEMPLATE_RECHECK` without a restart.
+async fn read_template(path: &str) -> Result<Arc<Tera>, String> {
+    let cached = TEMPLATES
+        .read()
+        .ok()
+        .and_then(|t| t.get(path).map(|c| (c.tera.clone(), c.modified, c.checked)));
+    if let Some((tera, _, checked)) = &cached {
+        if checked.elapsed() < TEMPLATE_RECHECK {
+            return Ok(tera.clone());
+        }
+    }
+    let load = async {
+        let meta = tokio::fs::metadata(path).await.map_err(|e| e.to_string())?;
+        let modified = meta.modified().ok();
+        if let Some((tera, cached_modified, _)) = &cached {
+            if modified.is_some() && *cached_modified == modified {
+                return Ok((tera.clone(), modified));
+            }
+        }
+        if meta.len() > TEMPLATE_MAX_BYTES {
+            return Err(format!("{} bytes, over the {} byte limit", meta.len(), TEMPLATE_MAX_BYTES));
+        }
+        let text = tokio::fs::read_to_string(path).await.map_err(|e| e.to_string())?;
+        // named after the file, so the .html suffix turns on auto-escaping
+        let mut tera = Tera::default();
+        tera.add_raw_template(path, &text).map_err(tera_error)?;
+        Ok((Arc::new(tera), modified))
+    };
+    match tokio::time::timeout(TEMPLATE_READ_TIMEOUT, load).await {
+        Ok(Ok((tera, modified))) => {
+            if let Ok(mut templates) = TEMPLATES.write() {
+                templates.insert(
+                    path.to_string(),
+                    CachedTemplate {
+                        tera: tera.clone(),
+                        modified,
+                        checked: Instant::now(),
+                    },
+                );
+            }
+            Ok(tera)
+        }
+        Ok(Err(e)) => {
+            if let Ok(mut templates) = TEMPLATES.write() {
+                templates.remove(path);
+            }
+            Err(format!("template {}: {}", path, e))
+        }
+        Err(_) => {
+            crate::log::info!("Template {}: read timed out, serving the cached copy", path);
+            cached
+                .map(|(tera, _, _)| tera)
+                .ok_or_else(|| format!("template {}: read timed out", path))
+        }
+    }
+}
+
+/// Renders `assets/html/{name}.html` with a typed context. Values are HTML-escaped unless the
+/// template marks them `| safe`; a variable the context lacks is an error, not literal
+/// `{{ ... }}` in the page.
+async fn render<C: Serialize>(name: &str, context: &C) -> Result<String, String> {
+    let path = template_path(name);
+    let tera = read_template(&path).await?;
+    let context = Context::from_serialize(context).map_err(tera_error)?;
+    tera.render(&path, &context).map_err(tera_error)
+}
+
+/// A rendered page, or a 500 with the reason in the log.
+fn html_page(rendered: Result<String, String>) -> HttpResponse {
+    match rendered {
+        Ok(html) => HttpResponse::Ok()
+            .content_type("text/html; charset=utf-8")
+            .body(html),
+        Err(e) => {
+            log::warn!("Frontend: {}", e);
+            HttpResponse::InternalServerError()
+                .content_type("text/html; charset=utf-8")
+                .body("<h1>Page failed to render</h1>")
+        }
+    }
+}
+
+/// Partials render into a page; a broken one leaves a gap instead of failing the page.
+async fn render_partial<C: Serialize>(name: &str, context: &C) -> String {
+    render(name, context).await.unwrap_or_else(|e| {
+        log::warn!("Frontend: {}", e);
+        String::new()
+    })
+}
+
+/// Calls our own API. The profile cookie is passed along so per-profile filtering applies.
+pub(crate) async fn fetch_json<T: for<'de> Deserialize<'de>>(
+    req: &HttpRequest,
     base: &str,
     path: &str,
 ) -> Result<T, String> {
@@ -42,11 +170,18 @@ async fn fetch_json<T: for<'de> Deserialize<'de>>(
         .timeout(std::time::Duration::from_secs(30))
         .build()
         .map_err(|e| e.to_string())?;
-    let resp = client
+    // Lets per-client limits (api.quota) charge the visitor, not this server. Only believed when
+    // the address this request arrives from is in server.trusted_proxies (loopback by default)
+    let mut request = client
         .get(&url)
-        .send()
-        .await
-        .map_err(|e| e.to_string())?;
+        .header("X-Forwarded-For", crate::routes::bandwidth::client_ip(req));
+    if let Some(cookie) = req.cookie(crate::routes::profiles::COOKIE) {
+        request = request.header(
+            "Cookie",
+            format!("{}={}", crate::routes::profiles::COOKIE, cookie.value()),
+        );
+    }
+    let resp = request.send().await.map_err(|e| e.to_string())?;
     if !resp.status().is_success() {
         return Err(format!("API returned {}", resp.status()));
     }
@@ -57,32 +192,153 @@ fn h(s: &str) -> String {
     encode_text(s).to_string()
 }
 
-fn make_clickable(text: &str) -> String {
-    // Simple: escape HTML and turn URLs into links, newlines to <br>
-    let escaped = h(text);
-    let with_br = escaped.replace("\n", "<br>");
-    // Very simple URL detection
-    let url_regex = regex::Regex::new(r"https?://[^\s<>]+").unwrap();
-    url_regex
-        .replace_all(&with_br, |caps: &regex::Captures| {
-            let u = &caps[0];
-            format!(r#"<a href="{}" target="_blank" rel="noopener">{}</a>"#, u, u)
-        })
-        .to_string()
+/// Escapes a value for a double-quoted attribute (`h` leaves quotes alone).
+fn attr(s: &str) -> String {
+    html_escape::encode_double_quoted_attribute(s).to_string()
 }
 
 // ---- Navbar (included in every page) ----
-fn render_navbar(main_url: &str, search_query: &str) -> String {
-    let t = load_template("partials/navbar");
-    t.replace("{{MAIN_URL}}", main_url)
-        .replace("{{SEARCH_QUERY}}", &h(search_query))
+/// Signed-in users get their avatar and name server-side (account info is cached in auth.rs), so
+/// the masthead is right on the first paint and without JS.
+async fn render_navbar(req: &HttpRequest, main_url: &str, search_query: &str) -> String {
+    let session = match (
+        req.app_data::<web::Data<TokenStore>>(),
+        req.app_data::<web::Data<AuthConfig>>(),
+    ) {
+        (Some(store), Some(auth)) => match store.session_token(req) {
+            Some(token) => crate::routes::auth::account_for_token(&token, auth, main_url.trim_end_matches('/'))
+                .await
+                .ok()
+                .map(|account| (account, token)),
+            None => None,
+        },
+        _ => None,
+    };
+    let user_area = match session.as_ref().and_then(|(a, token)| {
+        a.google_account
+            .name
+            .as_deref()
+            .or(a.google_account.given_name.as_deref())
+            .filter(|n| !n.is_empty())
+            .map(|name| (a, name, token))
+    }) {
+        Some((a, name, token)) => {
+            let unread = unread_notifications(req, main_url, token).await;
+            render_masthead_account(main_url, a, name, unread)
+        }
+        None => {
+            let notice = if crate::routes::token_health::take_notice(req) {
+                r#"<span class="signin-expired">Your sign-in has expired or was revoked.</span> "#
+            } else {
+                ""
+            };
+            format!(
+                r#"{}<button class="yt-uix-button yt-uix-button-size-default yt-uix-button-primary" type="button" onclick="window.location.href='{}/auth/login'"><span class="yt-uix-button-content">Sign in</span></button>"#,
+                notice, main_url
+            )
+        }
+    };
+    render_partial(
+        "frontend/partials/navbar",
+        &NavbarContext {
+            main_url,
+            search_query,
+            user_area: &user_area,
+        },
+    )
+    .await
+}
+
+#[derive(Serialize)]
+struct NavbarContext<'a> {
+    main_url: &'a str,
+    search_query: &'a str,
+    user_area: &'a str,
+}
+
+/// Unread count for the navbar badge, from /get_notifications.php at most once a minute per
+/// session; 0 while InnerTube is unreachable.
+async fn unread_notifications(req: &HttpRequest, main_url: &str, token: &str) -> u32 {
+    if let Some((at, count)) = UNREAD_NOTIFICATIONS.read().ok().and_then(|c| c.get(token).copied()) {
+        if at.elapsed() < NOTIFICATIONS_CACHE_TTL {
+            return count;
+        }
+    }
+    let count = fetch_json::<NotificationsResponse>(
+        req,
+        main_url,
+        &format!("/get_notifications.php?count=0&token={}", urlencoding::encode(token)),
+    )
+    .await
+    .map(|r| r.unread_count)
+    .unwrap_or_else(|e| {
+        crate::log::info!("Frontend navbar: failed to fetch notifications: {}", e);
+        0
+    });
+    if let Ok(mut cache) = UNREAD_NOTIFICATIONS.write() {
+        cache.retain(|_, (at, _)| at.elapsed() < NOTIFICATIONS_CACHE_TTL);
+        cache.insert(token.to_string(), (Instant::now(), count));
+    }
+    count
+}
+
+fn render_masthead_account(
+    main_url: &str,
+    account: &crate::routes::auth::AccountInfoResponse,
+    name: &str,
+    unread: u32,
+) -> String {
+    let avatar = account
+        .google_account
+        .picture
+        .clone()
+        .unwrap_or_else(|| format!("{}/assets/images/photo.jpg", main_url));
+    let handle = account
+        .youtube_channel
+        .as_ref()
+        .and_then(|c| c.custom_url.as_deref())
+        .unwrap_or("");
+    let (channel_url, handle_html) = if handle.is_empty() {
+        (format!("{}/channel", main_url), String::new())
+    } else {
+        let shown = if handle.starts_with('@') { handle.to_string() } else { format!("@{}", handle) };
+        (
+            format!("{}/channel?handle={}", main_url, urlencoding::encode(handle)),
+            format!(r#"<span class="masthead-menu-handle">{}</span>"#, h(&shown)),
+        )
+    };
+    let badge = match unread {
+        0 => String::new(),
+        1..=99 => format!(r#"<span class="masthead-notifications-badge">{}</span>"#, unread),
+        _ => r#"<span class="masthead-notifications-badge">99+</span>"#.to_string(),
+    };
+    format!(
+        r##"<span class="masthead-username">{name}</span><a href="{base}/notifications" class="masthead-notifications-icon" title="Notifications" aria-label="Notifications"><img src="{base}/assets/images/notifications_icon.png" alt="" width="24" height="24">{badge}</a><div class="masthead-account-wrap"><a href="#" class="masthead-avatar" onclick="window.toggleMastheadMenu(event);return false;"><img src="{avatar}" alt="" width="32" height="32" class="masthead-avatar-img"></a><div id="masthead-account-menu" class="masthead-account-menu" onclick="if(event.stopPropagation)event.stopPropagation();else event.cancelBubble=true;"><div class="masthead-menu-user"><img src="{avatar}" alt="" class="masthead-menu-avatar"><div class="masthead-menu-user-info"><span class="masthead-menu-name">{name}</span>{handle}<a href="{channel}" class="masthead-menu-channel-link">View your channel</a></div></div><div class="masthead-menu-sep"></div><a href="{base}/logout" class="masthead-menu-item">Sign out</a></div></div>"##,
+        name = h(name),
+        base = main_url,
+        badge = badge,
+        avatar = attr(&avatar),
+        handle = handle_html,
+        channel = attr(&channel_url)
+    )
 }
 
 // ---- Sidebar (guide) - separate partial; tech section only on root page
-fn render_sidebar(main_url: &str, tech_section: Option<&str>) -> String {
-    let t = load_template("partials/sidebar");
-    let t = t.replace("{{MAIN_URL}}", main_url);
-    t.replace("{{SIDEBAR_TECH_SECTION}}", tech_section.unwrap_or(""))
+async fn render_sidebar(main_url: &str, tech_section: Option<&str>) -> String {
+    render_partial(
+        "frontend/partials/sidebar",
+        &SidebarContext {
+            main_url,
+            sidebar_tech_section: tech_section.unwrap_or(""),
+        },
+    )
+    .await
+}
+
+#[derive(Serialize)]
+struct SidebarContext<'a> {
+    main_url: &'a str,
+    sidebar_tech_section: &'a str,
 }
 
 fn render_sidebar_tech_section(port: u16, instants: &[crate::config::InstantInstance], main_url: &str) -> String {
@@ -134,97 +390,238 @@ pub async fn page_root(
 ) -> impl Responder {
     let config = &data.config;
     let main_url = base_url(&req, config);
-    let main_url_trimmed = main_url.trim_end_matches('/');
     let port = config.server.port;
 
-    let videos: Vec<TopVideo> = match fetch_json::<Vec<TopVideo>>(
-        &main_url,
-        "/get_top_videos.php?count=24",
-    )
-    .await
-    {
-        Ok(v) => v,
-        Err(e) => {
-            crate::log::info!("Root index: failed to fetch top videos: {}", e);
-            Vec::new()
-        }
-    };
-
-    let refresh_token = req
-        .cookie("session_id")
-        .and_then(|c| token_store.get_token(c.value()))
-        .filter(|t| !t.is_empty() && !t.starts_with("Error"));
+    let refresh_token = token_store.session_token(&req);
 
-    let recommendations = match refresh_token {
-        Some(ref token) => crate::routes::additional::fetch_recommendations_for_token(
-            token,
-            &auth_config,
+    // A selected local profile has its own history instead of the shared Google one.
+    let profile = if config.profiles.enabled {
+        crate::routes::profiles::current_profile(&req)
+    } else {
+        None
+    };
+    let signed_in = refresh_token.is_some() || profile.is_some();
+
+    let mut main_content = String::new();
+    for shelf in &config.home.shelves {
+        let shown = match shelf.audience.as_str() {
+            "signed_in" => signed_in,
+            "signed_out" => !signed_in,
+            _ => true,
+        };
+        if !shown {
+            continue;
+        }
+        let html = render_home_shelf(
+            &req,
+            shelf,
             config,
-            main_url_trimmed,
-            24,
+            &auth_config,
+            &main_url,
+            refresh_token.as_deref(),
+            profile.as_ref().map(|p| p.id),
         )
-        .await
-        .unwrap_or_default(),
-        None => Vec::new(),
+        .await;
+        main_content.push_str(&html);
+    }
+
+    let navbar = render_navbar(&req, &main_url, "").await;
+    let sidebar_tech_section = render_sidebar_tech_section(port, &config.instants, &main_url);
+    let sidebar_html = render_sidebar(&main_url, Some(&sidebar_tech_section)).await;
+    let subscriptions_sidebar = if refresh_token.is_some() {
+        subscriptions_sidebar_loading_placeholder()
+    } else {
+        String::new()
+    };
+    let body_class = if main_content.trim().is_empty() {
+        main_content = logged_out_main_placeholder();
+        "home-logged-out"
+    } else {
+        ""
     };
 
-    let history = match refresh_token {
-        Some(ref token) => {
-            crate::routes::additional::fetch_history_for_token(
-                token,
-                &auth_config,
-                config,
-                main_url_trimmed,
-                24,
-            )
-            .await
+    html_page(
+        render(
+            "index",
+            &RootContext {
+                navbar: &navbar,
+                sidebar: &sidebar_html,
+                main_url: &main_url,
+                port,
+                main_content: &main_content,
+                subscriptions_sidebar: &subscriptions_sidebar,
+                body_class,
+            },
+        )
+        .await,
+    )
+}
+
+#[derive(Serialize)]
+struct RootContext<'a> {
+    navbar: &'a str,
+    sidebar: &'a str,
+    main_url: &'a str,
+    port: u16,
+    main_content: &'a str,
+    subscriptions_sidebar: &'a str,
+    body_class: &'a str,
+}
+
+/// One `home.shelves` entry; empty when it has nothing to show.
+async fn render_home_shelf(
+    req: &HttpRequest,
+    shelf: &crate::config::ShelfConfig,
+    config: &Config,
+    auth_config: &AuthConfig,
+    main_url: &str,
+    refresh_token: Option<&str>,
+    profile_id: Option<i64>,
+) -> String {
+    let count = shelf.count.max(1);
+    let main_url_trimmed = main_url.trim_end_matches('/');
+    let title = |default: &str| {
+        if shelf.title.is_empty() {
+            default.to_string()
+        } else {
+            shelf.title.clone()
         }
-        None => Vec::new(),
     };
-
-    let navbar = render_navbar(&main_url, "");
-    let sidebar_tech_section = render_sidebar_tech_section(port, &config.instants, &main_url);
-    let sidebar_html = render_sidebar(&main_url, Some(&sidebar_tech_section));
-    let (main_content, subscriptions_sidebar, body_class) = match refresh_token {
-        Some(_) => {
-            let videos_grid = render_video_grid(&videos, &main_url);
-            let recommendations_shelf = render_recommendations_shelf(&recommendations, &main_url);
-            let history_shelf = render_history_shelf(&history, &main_url);
-            // Logged in: recommendations → watch history → trends at the bottom
-            let content = format!(
+    match shelf.kind.as_str() {
+        "trending" | "category" => {
+            let mut path = if shelf.kind == "trending" {
+                format!("/get_top_videos.php?count={}", count)
+            } else {
+                format!(
+                    "/get-categories_videos.php?categoryId={}&count={}",
+                    urlencoding::encode(&shelf.category_id),
+                    count
+                )
+            };
+            // The profile cookie goes along with the request; the Google session doesn't
+            if shelf.hide_watched {
+                path.push_str("&hide_watched=true");
+                if let Some(token) = refresh_token {
+                    path.push_str(&format!("&token={}", urlencoding::encode(token)));
+                }
+            }
+            let videos = match fetch_json::<Vec<TopVideo>>(req, main_url, &path).await {
+                Ok(v) => v,
+                Err(e) => {
+                    crate::log::info!("Root index: failed to fetch {} shelf: {}", shelf.kind, e);
+                    return String::new();
+                }
+            };
+            if videos.is_empty() {
+                return String::new();
+            }
+            let heading = if shelf.title.is_empty() {
+                String::new()
+            } else {
+                format!(r#"<h2 class="branded-page-module-title">{}</h2>"#, h(&shelf.title))
+            };
+            format!(
                 r#"{}
-                    {}
                     <div class="compact-shelf-content-container">
                       <div class="yt-uix-shelfslider-body">
                         <ul class="yt-uix-shelfslider-list">{}</ul>
                       </div>
                     </div>"#,
-                recommendations_shelf,
-                history_shelf,
-                videos_grid
+                heading,
+                render_video_grid(&videos, main_url)
+            )
+        }
+        "playlist" => {
+            let path = format!(
+                "/playlist/{}?count={}",
+                urlencoding::encode(&shelf.playlist_id),
+                count
             );
-            (content, subscriptions_sidebar_loading_placeholder(), String::new())
+            let playlist = match fetch_json::<PlaylistResponse>(req, main_url, &path).await {
+                Ok(p) => p,
+                Err(e) => {
+                    crate::log::info!("Root index: failed to fetch playlist {}: {}", shelf.playlist_id, e);
+                    return String::new();
+                }
+            };
+            let items: Vec<HistoryItem> = playlist
+                .videos
+                .into_iter()
+                .map(|v| HistoryItem {
+                    video_id: v.video_id,
+                    title: v.title,
+                    author: v.author,
+                    views: v.views.unwrap_or_default(),
+                    duration: String::new(),
+                    watched_at: v.published_at.unwrap_or_default(),
+                    thumbnail: v.thumbnail,
+                    channel_thumbnail: v.channel_thumbnail,
+                })
+                .collect();
+            render_video_shelf(&title(&playlist.playlist_info.title), &items, main_url)
         }
-        None => (
-            logged_out_main_placeholder(),
-            String::new(),
-            "home-logged-out".to_string(),
-        ),
-    };
-
-    let t = load_root_index();
-    let html = t
-        .replace("{{NAVBAR}}", &navbar)
-        .replace("{{SIDEBAR}}", &sidebar_html)
-        .replace("{{MAIN_URL}}", &main_url)
-        .replace("{{PORT}}", &port.to_string())
-        .replace("{{MAIN_CONTENT}}", &main_content)
-        .replace("{{SUBSCRIPTIONS_SIDEBAR}}", &subscriptions_sidebar)
-        .replace("{{BODY_CLASS}}", &body_class);
-
-    HttpResponse::Ok()
-        .content_type("text/html; charset=utf-8")
-        .body(html)
+        "pinned_channels" => {
+            let pinned: Vec<HistoryItem> = crate::routes::pinned::latest(count as usize)
+                .into_iter()
+                .map(|v| HistoryItem {
+                    video_id: v.video_id,
+                    title: v.title,
+                    author: v.author,
+                    views: v.views,
+                    duration: String::new(),
+                    watched_at: v.published,
+                    thumbnail: String::new(),
+                    channel_thumbnail: String::new(),
+                })
+                .collect();
+            render_video_shelf(&title("From featured channels"), &pinned, main_url)
+        }
+        "recommendations" => {
+            let Some(token) = refresh_token else {
+                return String::new();
+            };
+            let items = crate::routes::additional::fetch_recommendations_for_token(
+                token,
+                auth_config,
+                config,
+                main_url_trimmed,
+                count as usize,
+            )
+            .await
+            .unwrap_or_default();
+            render_recommendations_shelf(&items, main_url)
+        }
+        "history" => {
+            let items = match (profile_id, refresh_token) {
+                (Some(id), _) => profile_history_items(id, count),
+                (None, Some(token)) => {
+                    crate::routes::additional::fetch_history_for_token(
+                        token,
+                        auth_config,
+                        config,
+                        main_url_trimmed,
+                        count as usize,
+                    )
+                    .await
+                }
+                (None, None) => Vec::new(),
+            };
+            render_video_shelf(&title("Watch history"), &items, main_url)
+        }
+        "favorites" => {
+            if !config.favorites.enabled {
+                return String::new();
+            }
+            let items = crate::routes::favorites::owner(req, config)
+                .map(|owner| local_list_items(&owner, "favorites", count))
+                .unwrap_or_default();
+            render_video_shelf(&title("Favorites"), &items, main_url)
+        }
+        other => {
+            crate::log::info!("Root index: unknown shelf type '{}'", other);
+            String::new()
+        }
+    }
 }
 
 fn thumb_url(v: &RecommendationItem, base: &str) -> String {
@@ -388,6 +785,40 @@ fn render_recommendations_shelf(items: &[RecommendationItem], main_url: &str) ->
     format!("{}{}", featured, rest_shelf)
 }
 
+fn profile_history_items(profile_id: i64, limit: u32) -> Vec<HistoryItem> {
+    crate::routes::profiles::history(profile_id, limit)
+        .into_iter()
+        .map(|item| HistoryItem {
+            video_id: item.video_id,
+            title: item.title,
+            author: item.author,
+            views: String::new(),
+            duration: String::new(),
+            watched_at: chrono::DateTime::from_timestamp(item.watched_at, 0)
+                .map(|t| t.to_rfc3339())
+                .unwrap_or_default(),
+            thumbnail: String::new(),
+            channel_thumbnail: String::new(),
+        })
+        .collect()
+}
+
+fn local_list_items(owner: &str, list: &str, limit: u32) -> Vec<HistoryItem> {
+    crate::routes::favorites::items(owner, list, limit)
+        .into_iter()
+        .map(|item| HistoryItem {
+            video_id: item.video_id,
+            title: item.title,
+            author: item.author,
+            views: String::new(),
+            duration: String::new(),
+            watched_at: String::new(),
+            thumbnail: String::new(),
+            channel_thumbnail: String::new(),
+        })
+        .collect()
+}
+
 fn history_thumb_url(v: &HistoryItem, base: &str) -> String {
     if v.thumbnail.is_empty() {
         format!("{}/thumbnail/{}", base.trim_end_matches('/'), v.video_id)
@@ -396,7 +827,8 @@ fn history_thumb_url(v: &HistoryItem, base: &str) -> String {
     }
 }
 
-fn render_history_shelf(items: &[HistoryItem], main_url: &str) -> String {
+/// Compact shelf of `HistoryItem`s (watch history, local favorites).
+fn render_video_shelf(title: &str, items: &[HistoryItem], main_url: &str) -> String {
     if items.is_empty() {
         return String::new();
     }
@@ -451,7 +883,7 @@ fn render_history_shelf(items: &[HistoryItem], main_url: &str) -> String {
         r#"<br>
 <div class="shelf-wrapper clearfix">
   <div class="compact-shelf shelf-item yt-uix-shelfslider clearfix">
-    <h2 class="branded-page-module-title">Watch history</h2>
+    <h2 class="branded-page-module-title">{}</h2>
     <div class="compact-shelf-content-container">
       <div class="yt-uix-shelfslider-body">
         <ul class="yt-uix-shelfslider-list">{}</ul>
@@ -459,6 +891,7 @@ fn render_history_shelf(items: &[HistoryItem], main_url: &str) -> String {
     </div>
   </div>
 </div>"#,
+        h(title),
         list
     )
 }
@@ -545,6 +978,7 @@ pub async fn page_index(
     let main_url = base.clone();
 
     let videos: Vec<TopVideo> = match fetch_json::<Vec<TopVideo>>(
+        &req,
         &base,
         "/get_top_videos.php?count=24",
     )
@@ -557,18 +991,27 @@ pub async fn page_index(
         }
     };
 
-    let navbar = render_navbar(&main_url, "");
+    let navbar = render_navbar(&req, &main_url, "").await;
     let videos_grid = render_video_grid(&videos, &main_url);
 
-    let t = load_template("index");
-    let html = t
-        .replace("{{NAVBAR}}", &navbar)
-        .replace("{{MAIN_URL}}", &main_url)
-        .replace("{{VIDEOS_GRID}}", &videos_grid);
+    html_page(
+        render(
+            "frontend/index",
+            &IndexContext {
+                navbar: &navbar,
+                main_url: &main_url,
+                videos_grid: &videos_grid,
+            },
+        )
+        .await,
+    )
+}
 
-    HttpResponse::Ok()
-        .content_type("text/html; charset=utf-8")
-        .body(html)
+#[derive(Serialize)]
+struct IndexContext<'a> {
+    navbar: &'a str,
+    main_url: &'a str,
+    videos_grid: &'a str,
 }
 
 // ---- Results: search ----
@@ -580,6 +1023,17 @@ fn render_search_results(videos: &[SearchResult], main_url: &str) -> String {
             continue;
         }
         let watch_url = format!("{}/watch?v={}", main_url, h(video_id));
+        let time_badge = match v.duration.as_deref().filter(|d| !d.is_empty()) {
+            Some(d) => format!(r#"<span class="video-time">{}</span>"#, h(d)),
+            None => String::new(),
+        };
+        let mut meta = format!("<li>{}</li>", h(&v.author));
+        for item in [v.published.as_deref(), v.short_views.as_deref().or(v.views.as_deref())]
+            .into_iter()
+            .flatten()
+        {
+            meta.push_str(&format!("<li>{}</li>", h(item)));
+        }
         out.push_str(&format!(
             r#"<li class="yt-lockup clearfix yt-lockup-video yt-lockup-tile result-item-padding">
     <div class="yt-lockup-thumbnail">
@@ -592,32 +1046,153 @@ fn render_search_results(videos: &[SearchResult], main_url: &str) -> String {
                     </span>
                 </span>
             </span>
-            <span class="video-time">{}</span>
+            {}
         </a>
     </div>
     <div class="yt-lockup-content">
         <h3 class="yt-lockup-title">
             <a class="yt-uix-tile-link spf-link yt-ui-ellipsis-2" href="{}" title="{}">{}</a>
         </h3>
-        <div class="yt-lockup-meta"><ul class="yt-lockup-meta-info"><li>{}</li></ul></div>
+        <div class="yt-lockup-meta"><ul class="yt-lockup-meta-info">{}</ul></div>
     </div>
 </li>"#,
             watch_url,
             h(&v.title),
             v.thumbnail,
-            v.duration.as_deref().unwrap_or(""),
+            time_badge,
             watch_url,
             h(&v.title),
             h(&v.title),
-            h(&v.author)
+            meta
         ));
     }
     out
 }
 
+/// Channel rows link by UC id and carry a subscribe form that posts back to this page.
+#[derive(Serialize)]
+struct ChannelRow {
+    url: String,
+    channel_id: String,
+    title: String,
+    thumbnail: String,
+    subscriber_count: String,
+    video_count: String,
+    description: String,
+}
+
+#[derive(Serialize)]
+struct ChannelResultsContext<'a> {
+    main_url: &'a str,
+    redirect: &'a str,
+    channels: Vec<ChannelRow>,
+}
+
+async fn render_channel_results(results: Vec<SearchResult>, main_url: &str, redirect: &str) -> String {
+    let channels = results
+        .into_iter()
+        .filter_map(|r| {
+            let channel_id = r.channel_id?;
+            Some(ChannelRow {
+                url: format!("{}/channel?handle={}", main_url, urlencoding::encode(&channel_id)),
+                channel_id,
+                title: r.title,
+                thumbnail: r.thumbnail,
+                subscriber_count: r.subscriber_count.unwrap_or_default(),
+                video_count: r.video_count.unwrap_or_default(),
+                description: r.description.unwrap_or_default(),
+            })
+        })
+        .collect();
+    render_partial(
+        "frontend/partials/channel_results",
+        &ChannelResultsContext {
+            main_url,
+            redirect,
+            channels,
+        },
+    )
+    .await
+}
+
+#[derive(Serialize)]
+struct PlaylistRow {
+    url: String,
+    title: String,
+    author: String,
+    thumbnail: String,
+    video_count: String,
+}
+
+#[derive(Serialize)]
+struct PlaylistResultsContext {
+    playlists: Vec<PlaylistRow>,
+}
+
+/// Playlist rows open the first video with `list=` set.
+async fn render_playlist_results(results: Vec<SearchResult>, main_url: &str) -> String {
+    let playlists = results
+        .into_iter()
+        .filter_map(|r| {
+            let playlist_id = r.playlist_id?;
+            let video_id = r.video_id?;
+            Some(PlaylistRow {
+                url: format!(
+                    "{}/watch?v={}&list={}",
+                    main_url,
+                    urlencoding::encode(&video_id),
+                    urlencoding::encode(&playlist_id)
+                ),
+                title: r.title,
+                author: r.author,
+                thumbnail: r.thumbnail,
+                video_count: r.video_count.unwrap_or_default(),
+            })
+        })
+        .collect();
+    render_partial(
+        "frontend/partials/playlist_results",
+        &PlaylistResultsContext { playlists },
+    )
+    .await
+}
+
+/// The Videos / Channels / Playlists tabs above the results.
+#[derive(Serialize)]
+struct SearchTab {
+    label: &'static str,
+    url: String,
+    selected: bool,
+}
+
+fn search_tabs(main_url: &str, search_query: &str, selected: &str) -> Vec<SearchTab> {
+    [("video", "Videos"), ("channel", "Channels"), ("playlist", "Playlists")]
+        .iter()
+        .map(|(kind, label)| SearchTab {
+            label,
+            url: if *kind == "video" {
+                format!("{}/results?search_query={}", main_url, urlencoding::encode(search_query))
+            } else {
+                format!(
+                    "{}/results?search_query={}&type={}",
+                    main_url,
+                    urlencoding::encode(search_query),
+                    kind
+                )
+            },
+            selected: *kind == selected,
+        })
+        .collect()
+}
+
 #[derive(serde::Deserialize)]
 pub struct ResultsQuery {
     search_query: Option<String>,
+    /// video (default), channel or playlist.
+    #[serde(rename = "type")]
+    kind: Option<String>,
+    subscribed: Option<String>,
+    error: Option<String>,
 }
 
 pub async fn page_results(
@@ -635,13 +1210,19 @@ pub async fn page_results(
         .trim()
         .to_string();
     let search_encoded = urlencoding::encode(&search_query);
+    let kind = match query.kind.as_deref() {
+        Some("channel") => "channel",
+        Some("playlist") => "playlist",
+        _ => "video",
+    };
 
     let videos: Vec<SearchResult> = if search_query.is_empty() {
         Vec::new()
     } else {
         match fetch_json::<Vec<SearchResult>>(
+            &req,
             &base,
-            &format!("/get_search_videos.php?query={}", search_encoded),
+            &format!("/get_search_videos.php?query={}&type={}", search_encoded, kind),
         )
         .await
         {
@@ -653,28 +1234,58 @@ pub async fn page_results(
         }
     };
 
-    let navbar = render_navbar(&main_url, &search_query);
-    let sidebar_html = render_sidebar(&main_url, None);
+    let navbar = render_navbar(&req, &main_url, &search_query).await;
+    let sidebar_html = render_sidebar(&main_url, None).await;
     let results_html = if videos.is_empty() && !search_query.is_empty() {
         format!(
             r#"<div class="yt-alert yt-alert-default"><div class="yt-alert-content">No results for "{}"</div></div>"#,
             h(&search_query)
         )
     } else {
-        render_search_results(&videos, &main_url)
+        match kind {
+            "channel" => {
+                let redirect = format!("/results?search_query={}&type=channel", search_encoded);
+                render_channel_results(videos, &main_url, &redirect).await
+            }
+            "playlist" => render_playlist_results(videos, &main_url).await,
+            _ => render_search_results(&videos, &main_url),
+        }
+    };
+    let status_banner = if query.subscribed.is_some() {
+        r#"<div class="yt-alert yt-alert-default yt-alert-success"><div class="yt-alert-content">Subscribed.</div></div>"#.to_string()
+    } else if let Some(e) = query.error.as_deref().filter(|e| !e.is_empty()) {
+        format!(
+            r#"<div class="yt-alert yt-alert-default yt-alert-error"><div class="yt-alert-content">{}</div></div>"#,
+            h(e)
+        )
+    } else {
+        String::new()
     };
 
-    let t = load_template("results");
-    let html = t
-        .replace("{{NAVBAR}}", &navbar)
-        .replace("{{SIDEBAR}}", &sidebar_html)
-        .replace("{{MAIN_URL}}", &main_url)
-        .replace("{{SEARCH_QUERY}}", &h(&search_query))
-        .replace("{{RESULTS}}", &results_html);
+    html_page(
+        render(
+            "frontend/results",
+            &ResultsContext {
+                navbar: &navbar,
+                sidebar: &sidebar_html,
+                main_url: &main_url,
+                tabs: search_tabs(&main_url, &search_query, kind),
+                status_banner: &status_banner,
+                results: &results_html,
+            },
+        )
+        .await,
+    )
+}
 
-    HttpResponse::Ok()
-        .content_type("text/html; charset=utf-8")
-        .body(html)
+#[derive(Serialize)]
+struct ResultsContext<'a> {
+    navbar: &'a str,
+    sidebar: &'a str,
+    main_url: &'a str,
+    tabs: Vec<SearchTab>,
+    status_banner: &'a str,
+    results: &'a str,
 }
 
 // ---- Watch: single video ----
@@ -713,7 +1324,7 @@ fn render_related_list(videos: &[RelatedVideo], main_url: &str) -> String {
     out
 }
 
-fn render_comments(comments: &[crate::routes::video::Comment], main_url: &str) -> String {
+fn render_comments(comments: &[crate::routes::video::Comment], main_url: &str, video_id: &str) -> String {
     let mut out = String::new();
     for c in comments.iter().take(20) {
         let author = c.author.as_str();
@@ -729,6 +1340,16 @@ fn render_comments(comments: &[crate::routes::video::Comment], main_url: &str) -
             main_url,
             urlencoding::encode(author)
         );
+        let mut actions = String::new();
+        if c.like_count != "0" {
+            actions.push_str(&format!(r#"<span class="comment-action">{} likes</span>"#, h(&c.like_count)));
+        }
+        if c.hearted {
+            actions.push_str(r#"<span class="comment-action comment-heart">&#9829; by uploader</span>"#);
+        }
+        if !actions.is_empty() {
+            actions = format!(r#"<div class="comment-actions">{}</div>"#, actions);
+        }
         out.push_str(&format!(
             r#"<div class="comment-item clearfix">
     <a href="{}" class="comment-author-thumb-link"><div class="comment-author-thumb">
@@ -740,6 +1361,7 @@ fn render_comments(comments: &[crate::routes::video::Comment], main_url: &str) -
             <span class="comment-time">{}</span>
         </div>
         <div class="comment-text">{}</div>
+        {}
     </div>
 </div>"#,
             channel_link,
@@ -748,7 +1370,8 @@ fn render_comments(comments: &[crate::routes::video::Comment], main_url: &str) -
             channel_link,
             h(author),
             h(published),
-            make_clickable(text)
+            formatter::to_html(text, main_url, Some(video_id)),
+            actions
         ));
     }
     out
@@ -757,12 +1380,124 @@ fn render_comments(comments: &[crate::routes::video::Comment], main_url: &str) -
 #[derive(serde::Deserialize)]
 pub struct WatchQuery {
     v: Option<String>,
+    /// Shared timestamp: `90`, `1m30s`, `1:30`.
+    t: Option<String>,
+    /// Playlist the video is played from; shows the playlist panel.
+    list: Option<String>,
+    /// 1-based position in `list`, for playlists that contain the video more than once.
+    index: Option<u32>,
+    /// Set by the "Add to playlist" form redirect.
+    playlist_added: Option<String>,
+    error: Option<String>,
+}
+
+/// Items fetched for the watch page playlist panel (4 playlistItems pages).
+const PLAYLIST_PANEL_ITEMS: u32 = 200;
+
+#[derive(Serialize)]
+struct PlaylistPanelItem {
+    url: String,
+    title: String,
+    author: String,
+    thumbnail: String,
+    position: u32,
+    current: bool,
+}
+
+#[derive(Serialize)]
+struct PlaylistPanelContext {
+    title: String,
+    author: String,
+    first_url: String,
+    current_index: u32,
+    total: u32,
+    prev_url: String,
+    next_url: String,
+    items: Vec<PlaylistPanelItem>,
+}
+
+fn playlist_watch_url(main_url: &str, video_id: &str, list: &str, position: u32) -> String {
+    format!(
+        "{}/watch?v={}&list={}&index={}",
+        main_url,
+        urlencoding::encode(video_id),
+        urlencoding::encode(list),
+        position
+    )
+}
+
+/// The playlist panel above the related videos: where `video_id` sits in `list` (by `index`
+/// when it points at this video, else its first occurrence), prev/next links and the item list.
+/// Empty when the playlist cannot be fetched.
+async fn render_playlist_panel(
+    req: &HttpRequest,
+    base: &str,
+    main_url: &str,
+    list: &str,
+    video_id: &str,
+    index: Option<u32>,
+) -> String {
+    let path = format!(
+        "/playlist/{}?count={}",
+        urlencoding::encode(list),
+        PLAYLIST_PANEL_ITEMS
+    );
+    let playlist = match fetch_json::<PlaylistResponse>(req, base, &path).await {
+        Ok(p) => p,
+        Err(e) => {
+            crate::log::info!("Frontend watch: failed to fetch playlist {}: {}", list, e);
+            return String::new();
+        }
+    };
+    let videos = &playlist.videos;
+    let current = index
+        .and_then(|i| videos.iter().position(|v| v.position == i && v.video_id == video_id))
+        .or_else(|| videos.iter().position(|v| v.video_id == video_id));
+    let link = |i: usize| {
+        videos
+            .get(i)
+            .map(|v| playlist_watch_url(main_url, &v.video_id, list, v.position))
+            .unwrap_or_default()
+    };
+    let (prev_url, next_url) = match current {
+        Some(i) => (i.checked_sub(1).map(link).unwrap_or_default(), link(i + 1)),
+        // not in the (first part of the) list: "next" starts the playlist
+        None => (String::new(), link(0)),
+    };
+    let total = videos
+        .first()
+        .map(|v| v.total_count)
+        .filter(|t| *t > 0)
+        .unwrap_or(playlist.playlist_info.video_count.max(0) as u32);
+    let context = PlaylistPanelContext {
+        title: playlist.playlist_info.title.clone(),
+        author: playlist.playlist_info.channel_title.clone(),
+        first_url: link(0),
+        current_index: current.map(|i| videos[i].position).unwrap_or(0),
+        total,
+        prev_url,
+        next_url,
+        items: videos
+            .iter()
+            .enumerate()
+            .map(|(i, v)| PlaylistPanelItem {
+                url: playlist_watch_url(main_url, &v.video_id, list, v.position),
+                title: v.title.clone(),
+                author: v.author.clone(),
+                thumbnail: v.thumbnail.clone(),
+                position: v.position,
+                current: Some(i) == current,
+            })
+            .collect(),
+    };
+    render_partial("frontend/partials/playlist_panel", &context).await
 }
 
 pub async fn page_watch(
     req: HttpRequest,
     data: web::Data<crate::AppState>,
     query: web::Query<WatchQuery>,
+    token_store: web::Data<TokenStore>,
 ) -> impl Responder {
     let video_id = match &query.v {
         Some(id) if !id.is_empty() => id.clone(),
@@ -779,6 +1514,7 @@ pub async fn page_watch(
     let base_trimmed = main_url.trim_end_matches('/');
 
     let info: VideoInfoResponse = match fetch_json(
+        &req,
         &base,
         &format!("/get-ytvideo-info.php?video_id={}", urlencoding::encode(&video_id)),
     )
@@ -794,6 +1530,7 @@ pub async fn page_watch(
     };
 
     let related: Vec<RelatedVideo> = fetch_json(
+        &req,
         &base,
         &format!("/get_related_videos.php?video_id={}", urlencoding::encode(&video_id)),
     )
@@ -819,12 +1556,14 @@ pub async fn page_watch(
     let views = info.views.as_deref().unwrap_or("0");
     let subscriber_count = info.subscriber_count.as_str();
     let likes = info.likes.as_deref().unwrap_or("0");
+    let (like_ratio, dislike_ratio) = sentiment_ratio(parse_views(likes), info.dislikes);
+    let dislikes = info.dislikes.map(|d| d.to_string()).unwrap_or_default();
     let published_at = info.published_at.as_str();
     let description = info.description.as_str();
     let comment_count = info.comment_count.as_deref().unwrap_or("0");
     let comments = &info.comments;
 
-    let video_src = if base_trimmed.is_empty() {
+    let mut video_src = if base_trimmed.is_empty() {
         format!("/direct_url?video_id={}", urlencoding::encode(&video_id))
     } else {
         format!(
@@ -833,13 +1572,98 @@ pub async fn page_watch(
             urlencoding::encode(&video_id)
         )
     };
+    video_src.push_str(&crate::routes::signed_urls::direct_url_params(&video_id));
+
+    if let Some(start) = query
+        .t
+        .as_deref()
+        .and_then(crate::routes::video::parse_timestamp)
+        .filter(|t| *t > 0)
+    {
+        video_src.push_str(&format!("#t={}", start));
+    }
+
+    let mut profile_script = String::new();
+    if config.profiles.enabled {
+        if let Some(profile) = crate::routes::profiles::current_profile(&req) {
+            crate::routes::profiles::record_history(
+                profile.id,
+                &video_id,
+                title,
+                author,
+                config.profiles.history_limit,
+            );
+            if let Some(pos) = crate::routes::profiles::resume_position(profile.id, &video_id) {
+                if pos > 0 && query.t.is_none() {
+                    video_src.push_str(&format!("#t={}", pos));
+                }
+            }
+            profile_script = render_profile_position_script(base_trimmed, &video_id);
+        }
+    }
     let poster = if base_trimmed.is_empty() {
         format!("/thumbnail/{}", urlencoding::encode(&video_id))
     } else {
         format!("{}/thumbnail/{}", base_trimmed, urlencoding::encode(&video_id))
     };
 
-    let navbar = render_navbar(&main_url, "");
+    let local_list_buttons = if config.favorites.enabled {
+        let owner = crate::routes::favorites::owner(&req, config);
+        let watch_path = format!("/watch?v={}", urlencoding::encode(&video_id));
+        [("favorites", "Favorite", "Unfavorite"), ("liked", "Like", "Unlike")]
+            .iter()
+            .map(|(list, add_label, remove_label)| {
+                let present = owner
+                    .as_deref()
+                    .map(|o| crate::routes::favorites::contains(o, list, &video_id))
+                    .unwrap_or(false);
+                render_local_list_button(
+                    &main_url,
+                    list,
+                    if present { remove_label } else { add_label },
+                    present,
+                    &video_id,
+                    title,
+                    author,
+                    &watch_path,
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    } else {
+        String::new()
+    };
+
+    let refresh_token = token_store.session_token(&req);
+    let addto_panel = match refresh_token {
+        Some(token) => {
+            let playlists = fetch_json::<MyPlaylistsResponse>(
+                &req,
+                &base,
+                &format!("/get_my_playlists.php?token={}", urlencoding::encode(&token)),
+            )
+            .await
+            .map(|r| r.playlists)
+            .unwrap_or_else(|e| {
+                crate::log::info!("Frontend watch: failed to fetch playlists: {}", e);
+                Vec::new()
+            });
+            render_addto_form(&main_url, &video_id, &playlists)
+        }
+        None => "<p>Sign in to add this video to a playlist.</p>".to_string(),
+    };
+    let status_banner = if query.playlist_added.is_some() {
+        r#"<div class="yt-alert yt-alert-default yt-alert-success"><div class="yt-alert-content">Video added to playlist.</div></div>"#.to_string()
+    } else if let Some(e) = query.error.as_deref().filter(|e| !e.is_empty()) {
+        format!(
+            r#"<div class="yt-alert yt-alert-default yt-alert-error"><div class="yt-alert-content">{}</div></div>"#,
+            h(e)
+        )
+    } else {
+        String::new()
+    };
+
+    let navbar = render_navbar(&req, &main_url, "").await;
     let related_html = if related.is_empty() {
         "<li style='padding:20px;color:#aaa'>No related videos</li>".to_string()
     } else {
@@ -848,39 +1672,235 @@ pub async fn page_watch(
     let comments_html = if comments.is_empty() {
         "<div class='comment-empty'><p>No comments yet.</p></div>".to_string()
     } else {
-        render_comments(comments, &main_url)
+        render_comments(comments, &main_url, &video_id)
+    };
+    let playlist_panel = match query.list.as_deref().filter(|l| !l.is_empty()) {
+        Some(list) => render_playlist_panel(&req, &base, &main_url, list, &video_id, query.index).await,
+        None => String::new(),
     };
 
-    let t = load_template("watch");
-    let html = t
-        .replace("{{NAVBAR}}", &navbar)
-        .replace("{{MAIN_URL}}", &main_url)
-        .replace("{{VIDEO_ID}}", &h(&video_id))
-        .replace("{{PAGE_TITLE}}", &format!("{} - YouTube", h(title)))
-        .replace("{{VIDEO_TITLE}}", &h(title))
-        .replace("{{CHANNEL_LINK}}", &channel_link)
-        .replace("{{CHANNEL_THUMB}}", channel_thumb)
-        .replace("{{AUTHOR}}", &h(author))
-        .replace("{{SUBSCRIBER_COUNT}}", subscriber_count)
-        .replace("{{VIEWS}}", views)
-        .replace("{{LIKE_RATIO}}", "50")
-        .replace("{{DISLIKE_RATIO}}", "50")
-        .replace("{{LIKES}}", likes)
-        .replace("{{PUBLISHED_AT}}", &h(published_at))
-        .replace("{{DESCRIPTION_HTML}}", &make_clickable(description))
-        .replace("{{COMMENT_COUNT}}", comment_count)
-        .replace("{{COMMENTS_HTML}}", &comments_html)
-        .replace("{{RELATED_VIDEOS}}", &related_html)
-        .replace("{{VIDEO_SRC}}", &h(&video_src))
-        .replace("{{POSTER}}", &h(&poster));
-
-    HttpResponse::Ok()
-        .content_type("text/html; charset=utf-8")
-        .body(html)
+    let context = WatchContext {
+        navbar: &navbar,
+        main_url: &main_url,
+        video_id: &video_id,
+        page_title: &format!("{} - YouTube", title),
+        social_meta: &render_social_meta(base_trimmed, &video_id, title, author, description),
+        video_title: title,
+        channel_link: &channel_link,
+        channel_thumb,
+        author,
+        subscriber_count,
+        views,
+        like_ratio,
+        dislike_ratio,
+        likes,
+        dislikes: &dislikes,
+        published_at,
+        description_html: &formatter::to_html(description, &main_url, Some(&video_id)),
+        comment_count,
+        comments_html: &comments_html,
+        related_videos: &related_html,
+        playlist_panel: &playlist_panel,
+        video_src: &video_src,
+        poster: &poster,
+        profile_script: &profile_script,
+        local_list_buttons: &local_list_buttons,
+        addto_panel: &addto_panel,
+        status_banner: &status_banner,
+    };
+    html_page(render("frontend/watch", &context).await)
+}
+
+#[derive(Serialize)]
+struct WatchContext<'a> {
+    navbar: &'a str,
+    main_url: &'a str,
+    video_id: &'a str,
+    page_title: &'a str,
+    social_meta: &'a str,
+    video_title: &'a str,
+    channel_link: &'a str,
+    channel_thumb: &'a str,
+    author: &'a str,
+    subscriber_count: &'a str,
+    views: &'a str,
+    like_ratio: u32,
+    dislike_ratio: u32,
+    likes: &'a str,
+    /// Empty without Return YouTube Dislike.
+    dislikes: &'a str,
+    published_at: &'a str,
+    description_html: &'a str,
+    comment_count: &'a str,
+    comments_html: &'a str,
+    related_videos: &'a str,
+    playlist_panel: &'a str,
+    video_src: &'a str,
+    poster: &'a str,
+    profile_script: &'a str,
+    local_list_buttons: &'a str,
+    addto_panel: &'a str,
+    status_banner: &'a str,
+}
+
+/// No-JS "Add to" panel: the account's playlists in a select, posted to /actions/playlist_add,
+/// which redirects back here with a status banner.
+fn render_addto_form(main_url: &str, video_id: &str, playlists: &[crate::routes::actions::MyPlaylist]) -> String {
+    if playlists.is_empty() {
+        return "<p>You have no playlists yet.</p>".to_string();
+    }
+    let options: String = playlists
+        .iter()
+        .map(|p| {
+            format!(
+                r#"<option value="{}">{} ({})</option>"#,
+                attr(&p.playlist_id),
+                h(&p.title),
+                p.video_count
+            )
+        })
+        .collect();
+    format!(
+        r#"<form method="POST" action="{}/actions/playlist_add"><input type="hidden" name="video_id" value="{}"><input type="hidden" name="redirect" value="/watch?v={}"><select name="playlist_id">{}</select> <button type="submit" class="yt-uix-button yt-uix-button-default yt-uix-button-size-default"><span class="yt-uix-button-content">Add</span></button></form>"#,
+        main_url,
+        attr(video_id),
+        urlencoding::encode(video_id),
+        options
+    )
+}
+
+/// No-JS toggle for a local list on the watch page: a form posting to /favorites/add or /remove.
+#[allow(clippy::too_many_arguments)]
+fn render_local_list_button(
+    main_url: &str,
+    list: &str,
+    label: &str,
+    present: bool,
+    video_id: &str,
+    title: &str,
+    author: &str,
+    redirect: &str,
+) -> String {
+    format!(
+        r#"<form method="POST" action="{}/favorites/{}" style="display:inline"><input type="hidden" name="list" value="{}"><input type="hidden" name="video_id" value="{}"><input type="hidden" name="title" value="{}"><input type="hidden" name="author" value="{}"><input type="hidden" name="redirect" value="{}"><button type="submit" class="yt-uix-button yt-uix-button-text yt-uix-button-size-default{}"><span class="yt-uix-button-content">{}</span></button></form>"#,
+        main_url,
+        if present { "remove" } else { "add" },
+        list,
+        attr(video_id),
+        attr(title),
+        attr(author),
+        attr(redirect),
+        if present { " yt-uix-button-toggled" } else { "" },
+        h(label)
+    )
+}
+
+/// Open Graph / Twitter card tags, so a watch link pasted into a chat app unfurls with the
+/// title, our /thumbnail and a player that points at /embed on this instance.
+fn render_social_meta(base: &str, video_id: &str, title: &str, author: &str, description: &str) -> String {
+    let id = urlencoding::encode(video_id);
+    let watch_url = format!("{}/watch?v={}", base, id);
+    let embed_url = format!("{}/embed/{}", base, id);
+    let image_url = format!("{}/thumbnail/{}?quality=high", base, id);
+    // unfurlers show one or two lines; a full description only bloats the page head
+    let flat = description.split_whitespace().collect::<Vec<_>>().join(" ");
+    let summary = if flat.chars().count() > 200 {
+        format!("{}...", flat.chars().take(197).collect::<String>().trim_end())
+    } else {
+        flat
+    };
+    let summary = if summary.is_empty() {
+        format!("Video by {}", author)
+    } else {
+        summary
+    };
+    let video_tags = if embed_url.starts_with("https://") {
+        format!(
+            r#"<meta property="og:video:url" content="{0}">
+    <meta property="og:video:secure_url" content="{0}">"#,
+            attr(&embed_url)
+        )
+    } else {
+        format!(r#"<meta property="og:video:url" content="{}">"#, attr(&embed_url))
+    };
+    format!(
+        r#"<meta name="title" content="{title}">
+    <meta name="description" content="{description}">
+    <meta property="og:site_name" content="YouTube">
+    <meta property="og:url" content="{watch_url}">
+    <meta property="og:title" content="{title}">
+    <meta property="og:description" content="{description}">
+    <meta property="og:image" content="{image_url}">
+    <meta property="og:image:width" content="480">
+    <meta property="og:image:height" content="360">
+    <meta property="og:type" content="video.other">
+    {video_tags}
+    <meta property="og:video:type" content="text/html">
+    <meta property="og:video:width" content="1280">
+    <meta property="og:video:height" content="720">
+    <meta name="twitter:card" content="player">
+    <meta name="twitter:url" content="{watch_url}">
+    <meta name="twitter:title" content="{title}">
+    <meta name="twitter:description" content="{description}">
+    <meta name="twitter:image" content="{image_url}">
+    <meta name="twitter:player" content="{embed_url}">
+    <meta name="twitter:player:width" content="1280">
+    <meta name="twitter:player:height" content="720">"#,
+        title = attr(title),
+        description = attr(&summary),
+        watch_url = attr(&watch_url),
+        image_url = attr(&image_url),
+        embed_url = attr(&embed_url),
+        video_tags = video_tags,
+    )
+}
+
+/// Saves the playback position of the selected profile every 15 seconds and when leaving the page.
+fn render_profile_position_script(base: &str, video_id: &str) -> String {
+    let url = format!("{}/profiles/position", base);
+    format!(
+        r#"<script type="text/javascript">
+(function(){{
+  var v = document.getElementsByTagName('video')[0];
+  if (!v) return;
+  var last = -1;
+  function save(finished) {{
+    var pos = finished === true ? 0 : Math.floor(v.currentTime || 0);
+    if (pos === last) return;
+    last = pos;
+    try {{
+      var x = new XMLHttpRequest();
+      x.open('POST', '{}', true);
+      x.setRequestHeader('Content-Type', 'application/x-www-form-urlencoded');
+      x.send('video_id={}&position=' + pos);
+    }} catch (e) {{}}
+  }}
+  setInterval(function(){{ if (!v.paused) save(); }}, 15000);
+  if (v.addEventListener) {{
+    v.addEventListener('pause', save, false);
+    v.addEventListener('ended', function(){{ save(true); }}, false);
+  }}
+  window.onbeforeunload = save;
+}})();
+</script>"#,
+        url,
+        urlencoding::encode(video_id)
+    )
 }
 
 // ---- Channel ----
 /// Parse views string (e.g. "1,234" or "1234") to number for comparison.
+/// Widths of the like/dislike sparkbar; an even split when dislikes are unknown or nobody voted.
+fn sentiment_ratio(likes: u64, dislikes: Option<u64>) -> (u32, u32) {
+    match dislikes {
+        Some(dislikes) if likes + dislikes > 0 => {
+            let like_ratio = (likes * 100 / (likes + dislikes)) as u32;
+            (like_ratio, 100 - like_ratio)
+        }
+        _ => (50, 50),
+    }
+}
+
 fn parse_views(views: &str) -> u64 {
     views
         .replace(',', "")
@@ -1053,6 +2073,7 @@ pub async fn page_channel(
     let main_url = base.clone();
 
     let channel_response: ChannelVideosResponse = match fetch_json(
+        &req,
         &base,
         &format!("/get_author_videos.php?author={}", urlencoding::encode(&handle)),
     )
@@ -1071,38 +2092,58 @@ pub async fn page_channel(
     let videos = &channel_response.videos;
 
     let channel_title = &channel_info.title;
-    let channel_description = &channel_info.description;
+    // The header avatar is larger than the 88px /channel_icon default
     let channel_thumbnail = if channel_info.thumbnail.is_empty() {
-        "/assets/images/photo.jpg"
+        "/assets/images/photo.jpg".to_string()
+    } else if channel_info.thumbnail.contains('?') {
+        channel_info.thumbnail.clone()
     } else {
-        &channel_info.thumbnail
+        format!("{}?quality=medium", channel_info.thumbnail)
     };
     let channel_banner = &channel_info.banner;
     let subscriber_count = &channel_info.subscriber_count;
     let channel_url = format!("{}/channel?handle={}", main_url, urlencoding::encode(&handle));
 
-    let navbar = render_navbar(&main_url, "");
-    let sidebar_html = render_sidebar(&main_url, None);
+    let navbar = render_navbar(&req, &main_url, "").await;
+    let sidebar_html = render_sidebar(&main_url, None).await;
     let spotlight_html = render_spotlight_html(videos, &main_url);
     let videos_html = render_channel_videos(videos, &main_url);
 
-    let t = load_template("channel");
-    let html = t
-        .replace("{{NAVBAR}}", &navbar)
-        .replace("{{SIDEBAR}}", &sidebar_html)
-        .replace("{{MAIN_URL}}", &main_url)
-        .replace("{{CHANNEL_TITLE}}", &h(channel_title))
-        .replace("{{CHANNEL_DESCRIPTION}}", &h(channel_description))
-        .replace("{{CHANNEL_THUMBNAIL}}", channel_thumbnail)
-        .replace("{{CHANNEL_BANNER}}", channel_banner)
-        .replace("{{SUBSCRIBER_COUNT}}", subscriber_count)
-        .replace("{{CHANNEL_URL}}", &channel_url)
-        .replace("{{SPOTLIGHT_HTML}}", &spotlight_html)
-        .replace("{{VIDEOS_HTML}}", &videos_html);
-
-    HttpResponse::Ok()
-        .content_type("text/html; charset=utf-8")
-        .body(html)
+    let context = ChannelContext {
+        navbar: &navbar,
+        sidebar: &sidebar_html,
+        main_url: &main_url,
+        channel_title,
+        channel_thumbnail: &channel_thumbnail,
+        channel_banner: &css_url(channel_banner),
+        subscriber_count,
+        channel_url: &channel_url,
+        spotlight_html: &spotlight_html,
+        videos_html: &videos_html,
+    };
+    html_page(render("frontend/channel", &context).await)
+}
+
+#[derive(Serialize)]
+struct ChannelContext<'a> {
+    navbar: &'a str,
+    sidebar: &'a str,
+    main_url: &'a str,
+    channel_title: &'a str,
+    channel_thumbnail: &'a str,
+    /// Goes into a `<style>` block, where HTML escaping does not apply; see `css_url`.
+    channel_banner: &'a str,
+    subscriber_count: &'a str,
+    channel_url: &'a str,
+    spotlight_html: &'a str,
+    videos_html: &'a str,
+}
+
+/// A URL for `url("...")` in CSS: drops what could close the string or the style block.
+fn css_url(url: &str) -> String {
+    url.chars()
+        .filter(|c| !matches!(c, '"' | '\\' | '<' | '>' | '\n' | '\r'))
+        .collect()
 }
 
 // ---- Login: sign-in page with navbar, sidebar, QR code auth (IE-compatible) ----
@@ -1112,16 +2153,27 @@ pub async fn page_login(
 ) -> impl Responder {
     let config = &data.config;
     let main_url = base_url(&req, config);
-    let navbar = render_navbar(&main_url, "");
-    let sidebar_html = render_sidebar(&main_url, None);
-    let t = load_template("login");
-    let html = t
-        .replace("{{NAVBAR}}", &navbar)
-        .replace("{{SIDEBAR}}", &sidebar_html)
-        .replace("{{MAIN_URL}}", &main_url);
-    HttpResponse::Ok()
-        .content_type("text/html; charset=utf-8")
-        .body(html)
+    let navbar = render_navbar(&req, &main_url, "").await;
+    let sidebar_html = render_sidebar(&main_url, None).await;
+    html_page(
+        render(
+            "frontend/login",
+            &PageContext {
+                navbar: &navbar,
+                sidebar: &sidebar_html,
+                main_url: &main_url,
+            },
+        )
+        .await,
+    )
+}
+
+/// Pages with nothing but the frame.
+#[derive(Serialize)]
+struct PageContext<'a> {
+    navbar: &'a str,
+    sidebar: &'a str,
+    main_url: &'a str,
 }
 
 // ---- Logout: clear session token, clear cookie, redirect to login ----
@@ -1138,18 +2190,271 @@ pub async fn page_logout(
     let login_url = format!("{}/auth/login", main_url);
     HttpResponse::Found()
         .insert_header(("Location", login_url))
-        .insert_header((
+        .append_header((
             "Set-Cookie",
             "session_id=; Path=/; Max-Age=0",
         ))
+        .append_header(("Set-Cookie", crate::routes::login_cookie::clear()))
         .finish()
 }
 
+// ---- Profiles: pick, create or leave a local profile (forms post to /profiles/*) ----
+#[derive(Deserialize)]
+pub struct ProfilesPageQuery {
+    pub error: Option<String>,
+}
+
+pub async fn page_profiles(
+    req: HttpRequest,
+    data: web::Data<crate::AppState>,
+    query: web::Query<ProfilesPageQuery>,
+) -> impl Responder {
+    let config = &data.config;
+    let main_url = base_url(&req, config);
+    if !config.profiles.enabled {
+        return HttpResponse::NotFound()
+            .content_type("text/html; charset=utf-8")
+            .body("<h1>Profiles are disabled</h1>");
+    }
+
+    let current = crate::routes::profiles::current_profile(&req);
+    let error_html = match query.error.as_deref().filter(|e| !e.is_empty()) {
+        Some(e) => format!(r#"<p class="auth-description" style="color:#c00">{}</p>"#, h(e)),
+        None => String::new(),
+    };
+    let current_html = match &current {
+        Some(p) => format!(
+            r#"<p class="auth-description">Watching as <b>{}</b>.</p>
+<form method="POST" action="{}/profiles/leave"><input type="hidden" name="redirect" value="/profiles"><button type="submit" class="yt-uix-button yt-uix-button-default yt-uix-button-size-default"><span class="yt-uix-button-content">Leave profile</span></button></form>"#,
+            h(&p.name),
+            main_url
+        ),
+        None => String::new(),
+    };
+
+    let mut list_html = String::new();
+    for p in crate::routes::profiles::list() {
+        let selected = current.as_ref().map(|c| c.id == p.id).unwrap_or(false);
+        list_html.push_str(&format!(
+            r#"<li style="margin:8px 0"><b>{name}</b>{mark}
+<form method="POST" action="{base}/profiles/select" style="display:inline"><input type="hidden" name="id" value="{id}"><input type="hidden" name="redirect" value="/profiles"><input class="yt-uix-form-input-text" type="password" name="pin" maxlength="8" size="8" placeholder="PIN"> <button type="submit" class="yt-uix-button yt-uix-button-primary yt-uix-button-size-default"><span class="yt-uix-button-content">Select</span></button></form>
+<form method="POST" action="{base}/profiles/delete" style="display:inline"><input type="hidden" name="id" value="{id}"><input type="hidden" name="redirect" value="/profiles"><input class="yt-uix-form-input-text" type="password" name="pin" maxlength="8" size="8" placeholder="PIN"> <button type="submit" class="yt-uix-button yt-uix-button-default yt-uix-button-size-default"><span class="yt-uix-button-content">Delete</span></button></form></li>"#,
+            name = h(&p.name),
+            mark = if selected { " (current)" } else { "" },
+            base = main_url,
+            id = p.id
+        ));
+    }
+    if !list_html.is_empty() {
+        list_html = format!("<ul>{}</ul>", list_html);
+    }
+
+    let navbar = render_navbar(&req, &main_url, "").await;
+    let sidebar_html = render_sidebar(&main_url, None).await;
+    html_page(
+        render(
+            "frontend/profiles",
+            &ProfilesContext {
+                navbar: &navbar,
+                sidebar: &sidebar_html,
+                main_url: &main_url,
+                error: &error_html,
+                current_profile: &current_html,
+                profile_list: &list_html,
+            },
+        )
+        .await,
+    )
+}
+
+#[derive(Serialize)]
+struct ProfilesContext<'a> {
+    navbar: &'a str,
+    sidebar: &'a str,
+    main_url: &'a str,
+    error: &'a str,
+    current_profile: &'a str,
+    profile_list: &'a str,
+}
+
+// ---- Favorites: local Favorites / Liked lists with remove buttons ----
+fn render_local_list(items: &[crate::routes::favorites::LocalListItem], list: &str, main_url: &str) -> String {
+    if items.is_empty() {
+        return "<p class=\"auth-description\">Nothing here yet.</p>".to_string();
+    }
+    let mut out = String::from("<ul>");
+    for item in items {
+        out.push_str(&format!(
+            r#"<li style="margin:8px 0"><a href="{base}/watch?v={id}"><img src="{base}/thumbnail/{id}" width="120" alt="" style="vertical-align:middle"></a> <a href="{base}/watch?v={id}">{title}</a> by {author}
+<form method="POST" action="{base}/favorites/remove" style="display:inline"><input type="hidden" name="list" value="{list}"><input type="hidden" name="video_id" value="{id}"><input type="hidden" name="redirect" value="/favorites"><button type="submit" class="yt-uix-button yt-uix-button-default yt-uix-button-size-default"><span class="yt-uix-button-content">Remove</span></button></form></li>"#,
+            base = main_url,
+            id = attr(&item.video_id),
+            title = h(&item.title),
+            author = h(&item.author),
+            list = list
+        ));
+    }
+    out.push_str("</ul>");
+    out
+}
+
+pub async fn page_favorites(req: HttpRequest, data: web::Data<crate::AppState>) -> impl Responder {
+    let config = &data.config;
+    let main_url = base_url(&req, config);
+    if !config.favorites.enabled {
+        return HttpResponse::NotFound()
+            .content_type("text/html; charset=utf-8")
+            .body("<h1>Local favorites are disabled</h1>");
+    }
+    let owner = crate::routes::favorites::owner(&req, config);
+    let list = |name: &str| {
+        owner
+            .as_deref()
+            .map(|o| crate::routes::favorites::items(o, name, config.favorites.max_items))
+            .unwrap_or_default()
+    };
+    let favorites_html = render_local_list(&list("favorites"), "favorites", &main_url);
+    let liked_html = render_local_list(&list("liked"), "liked", &main_url);
+
+    let navbar = render_navbar(&req, &main_url, "").await;
+    let sidebar_html = render_sidebar(&main_url, None).await;
+    html_page(
+        render(
+            "frontend/favorites",
+            &FavoritesContext {
+                navbar: &navbar,
+                sidebar: &sidebar_html,
+                main_url: &main_url,
+                favorites_list: &favorites_html,
+                liked_list: &liked_html,
+            },
+        )
+        .await,
+    )
+}
+
+#[derive(Serialize)]
+struct FavoritesContext<'a> {
+    navbar: &'a str,
+    sidebar: &'a str,
+    main_url: &'a str,
+    favorites_list: &'a str,
+    liked_list: &'a str,
+}
+
+#[derive(Serialize)]
+struct NotificationRow {
+    message: String,
+    sent: String,
+    thumbnail: String,
+    url: String,
+    read: bool,
+}
+
+#[derive(Serialize)]
+struct NotificationsContext<'a> {
+    navbar: &'a str,
+    sidebar: &'a str,
+    main_url: &'a str,
+    error: &'a str,
+    notifications: Vec<NotificationRow>,
+}
+
+pub async fn page_notifications(
+    req: HttpRequest,
+    data: web::Data<crate::AppState>,
+    token_store: web::Data<TokenStore>,
+) -> impl Responder {
+    let main_url = base_url(&req, &data.config);
+    let Some(token) = token_store.session_token(&req) else {
+        return HttpResponse::Found()
+            .insert_header(("Location", format!("{}/auth/login", main_url.trim_end_matches('/'))))
+            .finish();
+    };
+    let (notifications, error) = match fetch_json::<NotificationsResponse>(
+        &req,
+        &main_url,
+        &format!("/get_notifications.php?count=30&token={}", urlencoding::encode(&token)),
+    )
+    .await
+    {
+        Ok(r) => {
+            if let Ok(mut cache) = UNREAD_NOTIFICATIONS.write() {
+                cache.insert(token.clone(), (Instant::now(), r.unread_count));
+            }
+            (r.notifications, String::new())
+        }
+        Err(e) => {
+            crate::log::info!("Frontend notifications: {}", e);
+            (Vec::new(), "Notifications are unavailable right now.".to_string())
+        }
+    };
+    let rows = notifications
+        .into_iter()
+        .map(|n| NotificationRow {
+            url: n
+                .video_id
+                .as_deref()
+                .map(|id| format!("{}/watch?v={}", main_url, urlencoding::encode(id)))
+                .unwrap_or_default(),
+            message: n.message,
+            sent: n.sent,
+            thumbnail: n.thumbnail.unwrap_or_default(),
+            read: n.read,
+        })
+        .collect();
+
+    let navbar = render_navbar(&req, &main_url, "").await;
+    let sidebar_html = render_sidebar(&main_url, None).await;
+    html_page(
+        render(
+            "frontend/notifications",
+            &NotificationsContext {
+                navbar: &navbar,
+                sidebar: &sidebar_html,
+                main_url: &main_url,
+                error: &error,
+                notifications: rows,
+            },
+        )
+        .await,
+    )
+}
+
 // ---- Embed: iframe player for watch page (yt2014 embed with same styles) ----
+/// Query of /embed/{id}. Old embed URLs use the long names (`cc_load_policy`, `cc_lang_pref`/`hl`, `vq`).
+#[derive(Deserialize, Default)]
+pub struct EmbedQuery {
+    cc: Option<String>,
+    cc_load_policy: Option<String>,
+    lang: Option<String>,
+    cc_lang_pref: Option<String>,
+    hl: Option<String>,
+    quality: Option<String>,
+    vq: Option<String>,
+    autoplay: Option<String>,
+    start: Option<String>,
+}
+
+/// Old `vq=` names to heights understood by direct_url.
+fn embed_quality(q: &str) -> Option<String> {
+    let height = match q.trim().to_ascii_lowercase().as_str() {
+        "small" => "240",
+        "medium" => "360",
+        "large" => "480",
+        "hd720" => "720",
+        "hd1080" => "1080",
+        "auto" | "default" | "" => return None,
+        other => return Some(other.trim_end_matches('p').to_string()).filter(|h| h.chars().all(|c| c.is_ascii_digit())),
+    };
+    Some(height.to_string())
+}
+
 pub async fn page_embed(
     req: HttpRequest,
     data: web::Data<crate::AppState>,
     path: web::Path<String>,
+    query: web::Query<EmbedQuery>,
 ) -> impl Responder {
     let video_id = path.into_inner();
     if video_id.is_empty() {
@@ -1159,17 +2464,161 @@ pub async fn page_embed(
     }
     let config = &data.config;
     let base = base_url(&req, config);
-    let video_src = format!(
-        "{}/direct_url?video_id={}",
-        base.trim_end_matches('/'),
-        urlencoding::encode(&video_id)
+    let base = base.trim_end_matches('/');
+
+    let quality = query
+        .quality
+        .as_deref()
+        .or(query.vq.as_deref())
+        .and_then(embed_quality);
+    let start = query
+        .start
+        .as_deref()
+        .and_then(|s| s.trim().parse::<u64>().ok())
+        .filter(|s| *s > 0);
+    let autoplay = !matches!(query.autoplay.as_deref(), Some("0") | Some("false"));
+    let cc = matches!(
+        query.cc.as_deref().or(query.cc_load_policy.as_deref()),
+        Some("1") | Some("true")
+    );
+    let lang = query
+        .lang
+        .as_deref()
+        .or(query.cc_lang_pref.as_deref())
+        .or(query.hl.as_deref())
+        .map(|l| l.trim().to_string())
+        .filter(|l| !l.is_empty() && l.len() <= 16);
+
+    let mut video_src = format!(
+        "{}/direct_url?video_id={}{}",
+        base,
+        urlencoding::encode(&video_id),
+        crate::routes::signed_urls::direct_url_params(&video_id)
     );
-    let poster = format!("{}/thumbnail/{}", base.trim_end_matches('/'), urlencoding::encode(&video_id));
-    let t = load_template("embed");
-    let html = t
-        .replace("{{VIDEO_SRC}}", &h(&video_src))
-        .replace("{{POSTER}}", &h(&poster));
-    HttpResponse::Ok()
-        .content_type("text/html; charset=utf-8")
-        .body(html)
+    if let Some(q) = &quality {
+        video_src.push_str(&format!("&quality={}", q));
+    }
+    if let Some(start) = start {
+        video_src.push_str(&format!("#t={}", start));
+    }
+    let poster = format!("{}/thumbnail/{}", base, urlencoding::encode(&video_id));
+    let captions_track = if cc {
+        let lang = lang.as_deref().unwrap_or("en");
+        format!(
+            r#"<track kind="captions" default srclang="{}" label="{}" src="{}">"#,
+            attr(lang),
+            attr(lang),
+            attr(&format!(
+                "{}/embed/{}/captions.vtt?lang={}",
+                base,
+                urlencoding::encode(&video_id),
+                urlencoding::encode(lang)
+            ))
+        )
+    } else {
+        String::new()
+    };
+
+    html_page(
+        render(
+            "frontend/embed",
+            &EmbedContext {
+                video_src: &video_src,
+                poster: &poster,
+                autoplay: if autoplay { 1 } else { 0 },
+                captions_track: &captions_track,
+            },
+        )
+        .await,
+    )
+}
+
+#[derive(Serialize)]
+struct EmbedContext<'a> {
+    video_src: &'a str,
+    poster: &'a str,
+    autoplay: u8,
+    captions_track: &'a str,
+}
+
+/// WebVTT for the embed's `<track>`, served from our origin because `<track>` refuses
+/// cross-origin files without CORS.
+pub async fn page_embed_captions(
+    data: web::Data<crate::AppState>,
+    path: web::Path<String>,
+    query: web::Query<EmbedQuery>,
+) -> impl Responder {
+    let video_id = path.into_inner();
+    let lang = query.lang.as_deref().unwrap_or("en");
+    match crate::routes::video::fetch_caption_track(&video_id, lang, "vtt", &data.config).await {
+        Ok((_, text)) => HttpResponse::Ok()
+            .content_type("text/vtt; charset=utf-8")
+            .body(text),
+        Err(e) => {
+            crate::log::info!("Embed captions for {}: {}", video_id, e);
+            HttpResponse::NotFound().finish()
+        }
+    }
+}
+
+// ---- Legacy URL shapes: old share links and hardcoded client paths ----
+/// `/v/ID&hl=en&fs=1` (Flash embeds) carries parameters after `&` inside the path.
+fn split_legacy_id(raw: &str) -> (String, Vec<(String, String)>) {
+    let mut parts = raw.split(['&', '?']);
+    let id = parts.next().unwrap_or("").to_string();
+    let params = parts
+        .filter_map(|p| p.split_once('='))
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    (id, params)
+}
+
+/// 302 to `path` with the request's query string (minus `drop` keys) plus `extra`.
+fn legacy_redirect(req: &HttpRequest, path: &str, drop: &[&str], extra: &[(String, String)]) -> HttpResponse {
+    let mut pairs: Vec<String> = req
+        .query_string()
+        .split('&')
+        .filter(|p| !p.is_empty())
+        .filter(|p| !drop.contains(&p.split('=').next().unwrap_or("")))
+        .map(|p| p.to_string())
+        .collect();
+    for (k, v) in extra {
+        if !pairs.iter().any(|p| p.split('=').next() == Some(k.as_str())) {
+            pairs.push(format!("{}={}", k, v));
+        }
+    }
+    let sep = if path.contains('?') { '&' } else { '?' };
+    let location = if pairs.is_empty() {
+        path.to_string()
+    } else {
+        format!("{}{}{}", path, sep, pairs.join("&"))
+    };
+    HttpResponse::Found()
+        .insert_header(("Location", location))
+        .finish()
+}
+
+/// `/watch_popup?v=ID`: the bare popup player, served by the embed page.
+pub async fn page_watch_popup(req: HttpRequest, query: web::Query<WatchQuery>) -> impl Responder {
+    match query.v.as_deref().filter(|v| !v.is_empty()) {
+        Some(id) => legacy_redirect(
+            &req,
+            &format!("/embed/{}", urlencoding::encode(id)),
+            &["v"],
+            &[("autoplay".to_string(), "1".to_string())],
+        ),
+        None => HttpResponse::Found().insert_header(("Location", "/")).finish(),
+    }
+}
+
+/// `/v/{id}` and `/e/{id}`: old embed URLs.
+pub async fn page_legacy_embed(req: HttpRequest, path: web::Path<String>) -> impl Responder {
+    let (id, params) = split_legacy_id(&path.into_inner());
+    legacy_redirect(&req, &format!("/embed/{}", urlencoding::encode(&id)), &[], &params)
+}
+
+/// `/shorts/{id}`: played on the regular watch page.
+pub async fn page_shorts(req: HttpRequest, path: web::Path<String>) -> impl Responder {
+    let (id, params) = split_legacy_id(&path.into_inner());
+    legacy_redirect(&req, &format!("/watch?v={}", urlencoding::encode(&id)), &["v"], &params)
 }
@@ -0,0 +1,81 @@
+//! Hotlink protection for media endpoints (`proxy.hotlink`): /thumbnail, /channel_icon and
+//! /direct_url refuse requests whose `Referer` is another site, unless they carry a signed-in
+//! session. Pages of this instance, `allowed_referers` and (by default) requests without any
+//! `Referer`, as legacy apps send them, still go through.
+
+use actix_web::{web, HttpRequest, HttpResponse};
+
+use crate::config::Config;
+use crate::routes::auth::TokenStore;
+
+fn host_of(url: &str) -> Option<String> {
+    reqwest::Url::parse(url)
+        .ok()?
+        .host_str()
+        .map(|h| h.to_ascii_lowercase())
+}
+
+/// `example.com` also covers its subdomains.
+fn host_matches(host: &str, allowed: &str) -> bool {
+    let allowed = allowed.trim().trim_start_matches("*.").to_ascii_lowercase();
+    !allowed.is_empty() && (host == allowed || host.ends_with(&format!(".{}", allowed)))
+}
+
+fn has_session(req: &HttpRequest) -> bool {
+    req.app_data::<web::Data<TokenStore>>()
+        .and_then(|store| store.session_token(req))
+        .is_some()
+}
+
+/// 403 when hotlink protection is on and the request comes from a foreign page.
+pub fn check(req: &HttpRequest, config: &Config) -> Option<HttpResponse> {
+    let settings = &config.proxy.hotlink;
+    if !settings.enabled {
+        return None;
+    }
+    let referer = req
+        .headers()
+        .get(actix_web::http::header::REFERER)
+        .and_then(|r| r.to_str().ok())
+        .map(str::trim)
+        .filter(|r| !r.is_empty());
+    let Some(referer) = referer else {
+        return if settings.allow_empty_referer || has_session(req) {
+            None
+        } else {
+            Some(forbidden())
+        };
+    };
+
+    let own_host = req.connection_info().host().to_string();
+    let own_host = own_host
+        .rsplit_once(':')
+        .filter(|(_, port)| port.chars().all(|c| c.is_ascii_digit()))
+        .map(|(host, _)| host.to_string())
+        .unwrap_or(own_host)
+        .to_ascii_lowercase();
+    let allowed = match host_of(referer) {
+        Some(host) => {
+            host == own_host
+                || host_of(&config.server.main_url).is_some_and(|main| main == host)
+                || settings.allowed_referers.iter().any(|a| host_matches(&host, a))
+        }
+        None => false,
+    };
+    if allowed || has_session(req) {
+        return None;
+    }
+    let referer = match referer.split_once('?') {
+        Some((path, query)) => format!("{}?{}", path, crate::log::redact_query(query)),
+        None => referer.to_string(),
+    };
+    crate::log::info!("Hotlink: refused {} from {}", req.path(), referer);
+    Some(forbidden())
+}
+
+fn forbidden() -> HttpResponse {
+    HttpResponse::Forbidden().json(serde_json::json!({
+        "error": "Hotlinking is not allowed",
+        "details": "Open the media from this instance's pages or sign in"
+    }))
+}
@@ -0,0 +1,72 @@
+//! Conditional GET for JSON endpoints: ETag from a hash of the body, Cache-Control from `cache.*_max_age`.
+
+use actix_web::{http::header, HttpRequest, HttpResponse};
+use serde::Serialize;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+pub(crate) fn etag_for(body: &[u8]) -> String {
+    let mut hasher = DefaultHasher::new();
+    body.hash(&mut hasher);
+    format!("\"{:016x}-{:x}\"", hasher.finish(), body.len())
+}
+
+fn if_none_match_hits(req: &HttpRequest, etag: &str) -> bool {
+    req.headers()
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| etag_listed(v, etag))
+}
+
+/// Whether an `If-None-Match` value names `etag` (or is `*`).
+pub(crate) fn etag_listed(if_none_match: &str, etag: &str) -> bool {
+    if_none_match
+        .split(',')
+        .map(|t| t.trim().trim_start_matches("W/"))
+        .any(|t| t == "*" || t == etag)
+}
+
+fn cache_control_value(max_age: u32) -> String {
+    if max_age == 0 {
+        "no-cache".to_string()
+    } else {
+        format!("public, max-age={}", max_age)
+    }
+}
+
+/// Serializes `body` as JSON with `ETag` and `Cache-Control`; answers 304 when `If-None-Match` matches.
+pub fn cached_json<T: Serialize>(req: &HttpRequest, body: &T, max_age: u32) -> HttpResponse {
+    json_with_cache_control(req, body, cache_control_value(max_age))
+}
+
+/// [`cached_json`] for a body filtered for one viewer (profile blocklist, watch history):
+/// the browser may revalidate by ETag, shared caches must not store it.
+pub fn private_json<T: Serialize>(req: &HttpRequest, body: &T) -> HttpResponse {
+    json_with_cache_control(req, body, "private, no-store".to_string())
+}
+
+fn json_with_cache_control<T: Serialize>(req: &HttpRequest, body: &T, cache_control: String) -> HttpResponse {
+    let bytes = match serde_json::to_vec(body) {
+        Ok(b) => b,
+        Err(e) => {
+            log::info!("Failed to serialize response: {}", e);
+            return HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Failed to serialize response"
+            }));
+        }
+    };
+    let etag = etag_for(&bytes);
+
+    if if_none_match_hits(req, &etag) {
+        return HttpResponse::NotModified()
+            .insert_header((header::ETAG, etag))
+            .insert_header((header::CACHE_CONTROL, cache_control))
+            .finish();
+    }
+
+    HttpResponse::Ok()
+        .content_type("application/json")
+        .insert_header((header::ETAG, etag))
+        .insert_header((header::CACHE_CONTROL, cache_control))
+        .body(bytes)
+}
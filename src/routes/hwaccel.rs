@@ -0,0 +1,147 @@
+//! `video.hwaccel`: runs the decode and the H.264 encode of codec= conversions on VAAPI, NVENC,
+//! Quick Sync or the Raspberry Pi's V4L2 encoder instead of the CPU. mpeg4 and h263 have no
+//! hardware encoders, so those profiles only get the hardware decode.
+
+use std::sync::OnceLock;
+
+use crate::config::VideoConfig;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Backend {
+    Vaapi,
+    Nvenc,
+    Qsv,
+    V4l2m2m,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct HwAccel {
+    backend: Option<Backend>,
+    device: String,
+    /// `false` when ffmpeg lacks the backend's H.264 encoder; H.264 then falls back to libx264.
+    encoder_available: bool,
+}
+
+static HWACCEL: OnceLock<HwAccel> = OnceLock::new();
+
+impl Backend {
+    fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "vaapi" => Some(Self::Vaapi),
+            "nvenc" | "cuda" => Some(Self::Nvenc),
+            "qsv" => Some(Self::Qsv),
+            "v4l2m2m" | "v4l2" => Some(Self::V4l2m2m),
+            _ => None,
+        }
+    }
+
+    fn h264_encoder(self) -> &'static str {
+        match self {
+            Self::Vaapi => "h264_vaapi",
+            Self::Nvenc => "h264_nvenc",
+            Self::Qsv => "h264_qsv",
+            Self::V4l2m2m => "h264_v4l2m2m",
+        }
+    }
+}
+
+impl HwAccel {
+    /// Arguments that go before `-i`: the device and hardware decoding.
+    pub fn input_args(&self) -> Vec<String> {
+        let args: Vec<&str> = match self.backend {
+            Some(Backend::Vaapi) => vec![
+                "-init_hw_device",
+                "vaapi=va:DEVICE",
+                "-hwaccel",
+                "vaapi",
+                "-hwaccel_device",
+                "va",
+                "-filter_hw_device",
+                "va",
+            ],
+            Some(Backend::Nvenc) => vec!["-hwaccel", "cuda"],
+            Some(Backend::Qsv) => vec![
+                "-init_hw_device",
+                "qsv=qs:DEVICE",
+                "-hwaccel",
+                "qsv",
+                "-hwaccel_device",
+                "qs",
+            ],
+            // The Pi decodes fine on the CPU at 360p; its gain is the encoder
+            Some(Backend::V4l2m2m) | None => Vec::new(),
+        };
+        args.into_iter()
+            .map(|a| a.replace("DEVICE", &self.device))
+            .collect()
+    }
+
+    pub fn h264_encoder(&self) -> &'static str {
+        match self.backend {
+            Some(backend) if self.encoder_available => backend.h264_encoder(),
+            _ => "libx264",
+        }
+    }
+
+    /// Video arguments for an H.264 Baseline stream scaled to `height`.
+    pub fn h264_args(&self, height: u32, bitrate: &str) -> Vec<String> {
+        let scale = format!("scale=-2:{}", height);
+        let mut args: Vec<String> = vec!["-c:v".into(), self.h264_encoder().into()];
+        match self.backend.filter(|_| self.encoder_available) {
+            Some(Backend::Vaapi) => {
+                args.extend(["-vf".into(), format!("{},format=nv12,hwupload", scale)]);
+                args.extend(["-profile:v".into(), "constrained_baseline".into()]);
+            }
+            Some(Backend::Qsv) => {
+                args.extend(["-vf".into(), format!("{},format=nv12", scale)]);
+                args.extend(["-profile:v".into(), "baseline".into()]);
+            }
+            Some(Backend::Nvenc) => {
+                args.extend(["-vf".into(), scale]);
+                args.extend(["-profile:v".into(), "baseline".into(), "-preset".into(), "p4".into()]);
+            }
+            Some(Backend::V4l2m2m) => {
+                args.extend(["-vf".into(), format!("{},format=yuv420p", scale)]);
+            }
+            None => {
+                args.extend(["-vf".into(), scale, "-pix_fmt".into(), "yuv420p".into()]);
+                args.extend(["-profile:v".into(), "baseline".into(), "-preset".into(), "veryfast".into()]);
+            }
+        }
+        args.extend(["-b:v".into(), bitrate.into()]);
+        args
+    }
+}
+
+/// Resolves `video.hwaccel` against the detected ffmpeg encoders and logs the outcome.
+pub fn init(config: &VideoConfig) {
+    let name = config.hwaccel.trim();
+    let backend = Backend::parse(name);
+    if backend.is_none() && !name.is_empty() && !name.eq_ignore_ascii_case("none") {
+        log::warn!("Unknown video.hwaccel '{}'; conversions stay on the CPU.", name);
+    }
+    let encoder_available = backend
+        .map(|b| crate::check::ffmpeg_capabilities().has_encoder(b.h264_encoder()))
+        .unwrap_or(false);
+    match backend {
+        Some(b) if encoder_available => {
+            log::info!("Hardware transcoding: {:?} ({}).", b, b.h264_encoder())
+        }
+        Some(b) => log::warn!(
+            "video.hwaccel is {:?} but ffmpeg has no {} encoder; H.264 conversions use libx264.",
+            b,
+            b.h264_encoder()
+        ),
+        None => {}
+    }
+    let _ = HWACCEL.set(HwAccel {
+        backend,
+        device: config.hwaccel_device.clone(),
+        encoder_available,
+    });
+}
+
+/// The configured acceleration; software when `init` was not called.
+pub fn current() -> HwAccel {
+    HWACCEL.get().cloned().unwrap_or_default()
+}
@@ -0,0 +1,318 @@
+//! Offline library (`library.*`): videos saved to disk in a chosen quality together with their
+//! thumbnail and metadata, for deployments with unreliable internet. Layout per video:
+//! `<dir>/<video_id>/video.mp4`, `thumbnail.jpg`, `metadata.json` (written last, marks it complete).
+
+use actix_web::http::header::{HeaderValue, CONTENT_TYPE};
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use utoipa::ToSchema;
+
+use crate::config::{Config, LibraryConfig};
+use crate::routes::auth::TokenStore;
+use crate::routes::base_url;
+use crate::routes::video;
+
+lazy_static! {
+    static ref IN_PROGRESS: Mutex<HashSet<String>> = Mutex::new(HashSet::new());
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct LibraryItem {
+    pub video_id: String,
+    pub title: String,
+    pub author: String,
+    pub channel_id: String,
+    /// Seconds.
+    pub duration: u64,
+    pub quality: String,
+    pub size_bytes: u64,
+    pub downloaded_at: String,
+    #[serde(default)]
+    pub video_url: String,
+    #[serde(default)]
+    pub thumbnail_url: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct LibraryListResponse {
+    pub items: Vec<LibraryItem>,
+    /// Video ids still being downloaded.
+    pub downloading: Vec<String>,
+}
+
+fn is_valid_video_id(id: &str) -> bool {
+    id.len() == 11
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+fn video_dir(config: &LibraryConfig, video_id: &str) -> PathBuf {
+    Path::new(&config.dir).join(video_id)
+}
+
+fn read_item(dir: &Path) -> Option<LibraryItem> {
+    let text = fs::read_to_string(dir.join("metadata.json")).ok()?;
+    serde_json::from_str(&text).ok()
+}
+
+/// Path and duration of a completed library video, for /direct_url.
+pub(crate) fn find_video(config: &LibraryConfig, video_id: &str) -> Option<(PathBuf, u64)> {
+    if !config.enabled || !is_valid_video_id(video_id) {
+        return None;
+    }
+    let dir = video_dir(config, video_id);
+    let item = read_item(&dir)?;
+    let path = dir.join("video.mp4");
+    if path.is_file() {
+        Some((path, item.duration))
+    } else {
+        None
+    }
+}
+
+fn can_download(req: &HttpRequest, config: &Config, token_store: &TokenStore) -> bool {
+    if crate::routes::admin::is_admin(req, config) {
+        return true;
+    }
+    config.library.allow_signed_in
+        && token_store.session_token(req).is_some()
+}
+
+/// Copies the muxed temp file to `<dir>/video.mp4` (via `video.mp4.part`), returns its size.
+async fn store_video(temp_path: &Path, dir: &Path) -> Result<u64, String> {
+    tokio::fs::create_dir_all(dir)
+        .await
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let part = dir.join("video.mp4.part");
+    tokio::fs::copy(temp_path, &part)
+        .await
+        .map_err(|e| format!("Failed to copy video: {}", e))?;
+    let path = dir.join("video.mp4");
+    tokio::fs::rename(&part, &path).await.map_err(|e| e.to_string())?;
+    Ok(tokio::fs::metadata(&path).await.map(|m| m.len()).unwrap_or(0))
+}
+
+async fn save_to_library(video_id: String, height: u32, config: Config) -> Result<(), String> {
+    let player_response = video::fetch_player_response(&video_id, &config).await?;
+    let details = player_response
+        .get("videoDetails")
+        .cloned()
+        .unwrap_or(serde_json::Value::Null);
+    let text = |key: &str| {
+        details
+            .get(key)
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string()
+    };
+
+    let temp_path = video::download_mux_to_temp_file(video_id.clone(), height, &config).await?;
+
+    let dir = video_dir(&config.library, &video_id);
+    let size_bytes = match store_video(&temp_path, &dir).await {
+        Ok(size) => size,
+        Err(e) => {
+            // No half-written video.mp4.part left behind for list_library to trip over
+            let _ = tokio::fs::remove_dir_all(&dir).await;
+            return Err(e);
+        }
+    };
+
+    let thumb_host = crate::routes::circuit::order_hosts(&config.proxy.thumbnail_hosts)
+        .into_iter()
+        .next()
+        .unwrap_or("i.ytimg.com");
+    let thumb_url = format!("https://{}/vi/{}/hqdefault.jpg", thumb_host, video_id);
+    match crate::http_client::shared().get(&thumb_url).send().await {
+        Ok(resp) if resp.status().is_success() => {
+            if let Ok(bytes) = resp.bytes().await {
+                let _ = tokio::fs::write(dir.join("thumbnail.jpg"), &bytes).await;
+            }
+        }
+        Ok(resp) => log::warn!("Library: thumbnail for {} returned {}", video_id, resp.status()),
+        Err(e) => log::warn!("Library: thumbnail for {} failed: {}", video_id, e),
+    }
+
+    let item = LibraryItem {
+        video_id: video_id.clone(),
+        title: text("title"),
+        author: text("author"),
+        channel_id: text("channelId"),
+        duration: video::get_duration_from_player_response(&player_response),
+        quality: format!("{}p", height),
+        size_bytes,
+        downloaded_at: chrono::Utc::now().to_rfc3339(),
+        video_url: String::new(),
+        thumbnail_url: String::new(),
+    };
+    let json = serde_json::to_string_pretty(&item).map_err(|e| e.to_string())?;
+    if let Err(e) = tokio::fs::write(dir.join("metadata.json"), json).await {
+        let _ = tokio::fs::remove_dir_all(&dir).await;
+        return Err(e.to_string());
+    }
+    log::info!("Library: saved {} ({}p, {} bytes)", video_id, height, size_bytes);
+    Ok(())
+}
+
+fn with_urls(mut item: LibraryItem, base: &str) -> LibraryItem {
+    item.video_url = format!(
+        "{}/direct_url?video_id={}{}",
+        base,
+        item.video_id,
+        crate::routes::signed_urls::direct_url_params(&item.video_id)
+    );
+    item.thumbnail_url = format!("{}/library/thumbnail/{}", base, item.video_id);
+    item
+}
+
+#[utoipa::path(
+    post,
+    path = "/library/download",
+    params(
+        ("video_id" = String, Query, description = "YouTube video ID"),
+        ("quality" = Option<String>, Query, description = "Quality to keep (e.g. 360, 720); defaults to video.default_quality"),
+        ("admin_key" = Option<String>, Query, description = "Admin key (or X-Admin-Key header)")
+    ),
+    responses(
+        (status = 200, description = "Already in the library", body = LibraryItem),
+        (status = 202, description = "Download started or already running"),
+        (status = 401, description = "Admin key or signed-in session required"),
+        (status = 404, description = "Library disabled"),
+        (status = 501, description = "ffmpeg is not available")
+    )
+)]
+pub async fn download_to_library(
+    req: HttpRequest,
+    query: web::Query<HashMap<String, String>>,
+    data: web::Data<crate::AppState>,
+    token_store: web::Data<TokenStore>,
+) -> impl Responder {
+    let config = &data.config;
+    if !config.library.enabled {
+        return HttpResponse::NotFound().json(serde_json::json!({
+            "error": "Library is disabled"
+        }));
+    }
+    if !can_download(&req, config, &token_store) {
+        return HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Admin key or signed-in session required"
+        }));
+    }
+
+    let video_id = match query.get("video_id") {
+        Some(id) if is_valid_video_id(id) => id.clone(),
+        _ => {
+            return HttpResponse::BadRequest().json(serde_json::json!({
+                "error": "Valid video_id parameter is required"
+            }));
+        }
+    };
+    let height = query
+        .get("quality")
+        .and_then(|q| video::parse_quality_height(q))
+        .or_else(|| video::parse_quality_height(&config.video.default_quality))
+        .unwrap_or(360);
+
+    let base = base_url(&req, config);
+    if let Some(item) = read_item(&video_dir(&config.library, &video_id)) {
+        return HttpResponse::Ok().json(with_urls(item, base.trim_end_matches('/')));
+    }
+    if let Some(resp) = video::unsupported_profile(&[]) {
+        return resp;
+    }
+
+    let started = IN_PROGRESS
+        .lock()
+        .map(|mut set| set.insert(video_id.clone()))
+        .unwrap_or(false);
+    if started {
+        let config = config.clone();
+        let id = video_id.clone();
+        actix_web::rt::spawn(async move {
+            if let Err(e) = save_to_library(id.clone(), height, config).await {
+                log::error!("Library: failed to save {}: {}", id, e);
+            }
+            if let Ok(mut set) = IN_PROGRESS.lock() {
+                set.remove(&id);
+            }
+        });
+    }
+
+    HttpResponse::Accepted().json(serde_json::json!({
+        "status": if started { "queued" } else { "downloading" },
+        "video_id": video_id,
+        "quality": format!("{}p", height)
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/library/list",
+    responses(
+        (status = 200, description = "Videos in the offline library", body = LibraryListResponse),
+        (status = 404, description = "Library disabled")
+    )
+)]
+pub async fn list_library(req: HttpRequest, data: web::Data<crate::AppState>) -> impl Responder {
+    let config = &data.config;
+    if !config.library.enabled {
+        return HttpResponse::NotFound().json(serde_json::json!({
+            "error": "Library is disabled"
+        }));
+    }
+    let base = base_url(&req, config);
+    let base = base.trim_end_matches('/');
+
+    let mut items: Vec<LibraryItem> = fs::read_dir(&config.library.dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter(|e| e.path().join("video.mp4").is_file())
+                .filter_map(|e| read_item(&e.path()))
+                .map(|item| with_urls(item, base))
+                .collect()
+        })
+        .unwrap_or_default();
+    items.sort_by(|a, b| b.downloaded_at.cmp(&a.downloaded_at));
+
+    let mut downloading: Vec<String> = IN_PROGRESS
+        .lock()
+        .map(|set| set.iter().cloned().collect())
+        .unwrap_or_default();
+    downloading.sort();
+
+    HttpResponse::Ok().json(LibraryListResponse { items, downloading })
+}
+
+#[utoipa::path(
+    get,
+    path = "/library/thumbnail/{video_id}",
+    params(
+        ("video_id" = String, Path, description = "YouTube video ID")
+    ),
+    responses(
+        (status = 200, description = "Saved thumbnail", content_type = "image/jpeg"),
+        (status = 404, description = "Not in the library")
+    )
+)]
+pub async fn library_thumbnail(
+    path: web::Path<String>,
+    data: web::Data<crate::AppState>,
+) -> impl Responder {
+    let video_id = path.into_inner();
+    if !data.config.library.enabled || !is_valid_video_id(&video_id) {
+        return HttpResponse::NotFound().finish();
+    }
+    match fs::read(video_dir(&data.config.library, &video_id).join("thumbnail.jpg")) {
+        Ok(bytes) => HttpResponse::Ok()
+            .insert_header((CONTENT_TYPE, HeaderValue::from_static("image/jpeg")))
+            .body(bytes),
+        Err(_) => HttpResponse::NotFound().finish(),
+    }
+}
@@ -0,0 +1,74 @@
+//! `hl=` / `gl=` on metadata endpoints: the language titles, descriptions and categories come
+//! back in, and the region used for trending. Falls back to `api.hl` / `api.gl`.
+
+use actix_web::HttpRequest;
+use serde_json::Value;
+
+use crate::config::Config;
+
+pub struct Locale {
+    pub hl: String,
+    pub gl: String,
+}
+
+/// Language tags and region codes only: `en`, `pt-BR`, `zh_TW`, `US`.
+fn valid(value: &str) -> bool {
+    !value.is_empty()
+        && value.len() <= 10
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+impl Locale {
+    pub fn from_request(req: &HttpRequest, config: &Config) -> Self {
+        let mut hl = None;
+        let mut gl = None;
+        for pair in req.query_string().split('&') {
+            match pair.split_once('=') {
+                Some(("hl", v)) if valid(v) => hl = Some(v.to_string()),
+                Some(("gl", v)) if valid(v) => gl = Some(v.to_ascii_uppercase()),
+                _ => {}
+            }
+        }
+        Self {
+            hl: hl.unwrap_or_else(|| config.api.hl.clone()),
+            gl: gl.unwrap_or_else(|| config.api.gl.to_ascii_uppercase()),
+        }
+    }
+
+    /// Sets `hl` / `gl` on an InnerTube context (either `{"client": {..}}` or the client object).
+    pub fn apply_to_context(&self, ctx: &mut Value) {
+        let target = if ctx.get("client").is_some() {
+            ctx.get_mut("client")
+        } else {
+            Some(ctx)
+        };
+        if let Some(client) = target.and_then(|c| c.as_object_mut()) {
+            client.insert("hl".to_string(), Value::String(self.hl.clone()));
+            client.insert("gl".to_string(), Value::String(self.gl.clone()));
+        }
+    }
+
+    /// `&hl=..&regionCode=..` for Data API `videos` / `videoCategories` calls.
+    pub fn data_api_params(&self) -> String {
+        format!(
+            "&hl={}&regionCode={}",
+            urlencoding::encode(&self.hl),
+            urlencoding::encode(&self.gl)
+        )
+    }
+
+    /// `&hl=..&gl=..` for youtube.com pages.
+    pub fn page_params(&self) -> String {
+        format!(
+            "&hl={}&gl={}",
+            urlencoding::encode(&self.hl),
+            urlencoding::encode(&self.gl)
+        )
+    }
+
+    pub fn accept_language(&self) -> String {
+        format!("{},en;q=0.8", self.hl)
+    }
+}
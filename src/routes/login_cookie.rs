@@ -0,0 +1,76 @@
+//! Persistent frontend login (`server.persistent_login`). After OAuth the refresh token is also
+//! put in a long-lived cookie, encrypted and authenticated (AES-GCM private cookie) with a key
+//! derived from `server.secret_key`. When the in-memory session is gone (restart, other worker)
+//! `TokenStore::session_token` falls back to it, so the QR login doesn't have to be redone.
+
+use actix_web::cookie::{Cookie, CookieJar, Key, SameSite};
+use actix_web::HttpRequest;
+use sha2::{Digest, Sha512};
+use std::sync::OnceLock;
+
+use crate::config::Config;
+
+pub const COOKIE: &str = "yt_login";
+
+struct State {
+    key: Key,
+    max_age_days: u32,
+}
+
+static STATE: OnceLock<Option<State>> = OnceLock::new();
+
+pub fn init(config: &Config) {
+    let settings = &config.server.persistent_login;
+    let state = if !settings.enabled {
+        None
+    } else if config.server.secretkey.trim().is_empty() {
+        log::warn!("server.persistent_login needs server.secret_key; persistent logins are disabled");
+        None
+    } else {
+        // Key::from wants 64 bytes; the label keeps this key distinct from other uses of the secret
+        let digest = Sha512::new()
+            .chain_update(b"yt-api-legacy persistent_login\0")
+            .chain_update(config.server.secretkey.as_bytes())
+            .finalize();
+        Some(State {
+            key: Key::from(digest.as_slice()),
+            max_age_days: settings.max_age_days.max(1),
+        })
+    };
+    let _ = STATE.set(state);
+}
+
+fn state() -> Option<&'static State> {
+    STATE.get().and_then(|s| s.as_ref())
+}
+
+/// `Set-Cookie` value carrying `refresh_token`; `None` when persistent logins are off.
+pub fn issue(refresh_token: &str) -> Option<String> {
+    let state = state()?;
+    let cookie = Cookie::build(COOKIE, refresh_token.to_string())
+        .path("/")
+        .same_site(SameSite::Lax)
+        .http_only(true)
+        .max_age(actix_web::cookie::time::Duration::days(state.max_age_days as i64))
+        .finish();
+    let mut jar = CookieJar::new();
+    jar.private_mut(&state.key).add(cookie);
+    jar.get(COOKIE).map(|c| c.to_string())
+}
+
+/// Refresh token from the cookie; `None` if absent, tampered with or encrypted with another key.
+pub fn read(req: &HttpRequest) -> Option<String> {
+    let state = state()?;
+    let cookie = req.cookie(COOKIE)?;
+    let mut jar = CookieJar::new();
+    jar.add_original(cookie.into_owned());
+    jar.private(&state.key)
+        .get(COOKIE)
+        .map(|c| c.value().to_string())
+        .filter(|t| !t.is_empty())
+}
+
+/// Expires the cookie (logout).
+pub fn clear() -> String {
+    format!("{}=; Path=/; Max-Age=0", COOKIE)
+}
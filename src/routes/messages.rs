@@ -0,0 +1,83 @@
+//! Catalog of the `error` strings handlers return, in the language set by
+//! `server.error_language` (`en` or `ru`). English is the default and the fallback for anything
+//! not translated. Only the `error` field is localized; `details` carries upstream text as is.
+
+use std::sync::OnceLock;
+
+static LANGUAGE: OnceLock<String> = OnceLock::new();
+
+#[derive(Clone, Copy, Debug)]
+pub enum Msg {
+    VideoIdRequired,
+    IdRequired,
+    InvalidVideoId,
+    MissingInnertubeKey,
+    FailedToFetchVideoPage,
+    FailedToParseResponse,
+    InvalidRefreshToken,
+    FailedToGetAccountInfo,
+    AdminKeyRequired,
+    ChannelNotFound,
+    PlaylistNotFound,
+    VideoNotAvailable,
+    QuotaExceeded,
+    NotPermitted,
+    DataCapReached,
+}
+
+pub fn init(language: &str) {
+    let language = language.trim().to_ascii_lowercase();
+    if !matches!(language.as_str(), "en" | "ru") {
+        log::warn!("server.error_language '{}' is not supported, using en", language);
+    }
+    let _ = LANGUAGE.set(language);
+}
+
+impl Msg {
+    pub fn text(self) -> &'static str {
+        match LANGUAGE.get().map(String::as_str) {
+            Some("ru") => self.ru(),
+            _ => self.en(),
+        }
+    }
+
+    fn en(self) -> &'static str {
+        match self {
+            Msg::VideoIdRequired => "video_id parameter is required",
+            Msg::IdRequired => "id parameter is required",
+            Msg::InvalidVideoId => "Invalid video_id",
+            Msg::MissingInnertubeKey => "Missing innertube_key in config.yml",
+            Msg::FailedToFetchVideoPage => "Failed to fetch video page",
+            Msg::FailedToParseResponse => "Failed to parse response",
+            Msg::InvalidRefreshToken => "Invalid refresh token",
+            Msg::FailedToGetAccountInfo => "Failed to get account information",
+            Msg::AdminKeyRequired => "Admin key required",
+            Msg::ChannelNotFound => "Channel not found",
+            Msg::PlaylistNotFound => "Playlist not found",
+            Msg::VideoNotAvailable => "This video is not available on this instance",
+            Msg::QuotaExceeded => "Daily API quota exceeded",
+            Msg::NotPermitted => "Not permitted for this session",
+            Msg::DataCapReached => "Daily data cap reached",
+        }
+    }
+
+    fn ru(self) -> &'static str {
+        match self {
+            Msg::VideoIdRequired => "ID видео не был передан.",
+            Msg::IdRequired => "ID параметр обязателен",
+            Msg::InvalidVideoId => "Неверный video_id",
+            Msg::MissingInnertubeKey => "В config.yml не указан innertube_key",
+            Msg::FailedToFetchVideoPage => "Не удалось загрузить страницу видео",
+            Msg::FailedToParseResponse => "Не удалось разобрать ответ",
+            Msg::InvalidRefreshToken => "Неверный refresh token",
+            Msg::FailedToGetAccountInfo => "Не удалось получить данные аккаунта",
+            Msg::AdminKeyRequired => "Требуется ключ администратора",
+            Msg::ChannelNotFound => "Канал не найден",
+            Msg::PlaylistNotFound => "Плейлист не найден",
+            Msg::VideoNotAvailable => "Это видео недоступно на этом сервере",
+            Msg::QuotaExceeded => "Дневная квота API исчерпана",
+            Msg::NotPermitted => "Недоступно для этой сессии",
+            Msg::DataCapReached => "Дневной лимит трафика исчерпан",
+        }
+    }
+}
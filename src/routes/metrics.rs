@@ -0,0 +1,176 @@
+//! In-process counters exposed at /metrics (reset on restart).
+
+use actix_web::body::{BodySize, MessageBody};
+use actix_web::dev::ServiceResponse;
+use actix_web::http::header::CONTENT_LENGTH;
+use actix_web::{HttpResponse, Responder};
+use lazy_static::lazy_static;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::Instant;
+use utoipa::ToSchema;
+
+lazy_static! {
+    static ref STARTED_AT: Instant = Instant::now();
+    /// (source, upstream status, reason) -> count
+    static ref UPSTREAM_ERRORS: Mutex<HashMap<(String, u16, String), u64>> =
+        Mutex::new(HashMap::new());
+    /// route pattern -> body sizes of its recent requests/responses
+    static ref PAYLOADS: Mutex<HashMap<String, RoutePayloads>> = Mutex::new(HashMap::new());
+}
+
+/// Sizes kept per route for the percentiles; older ones are dropped.
+const PAYLOAD_SAMPLES: usize = 512;
+
+#[derive(Default)]
+struct RoutePayloads {
+    requests: u64,
+    request_sizes: VecDeque<u64>,
+    response_sizes: VecDeque<u64>,
+    request_max: u64,
+    response_max: u64,
+}
+
+fn push_sample(samples: &mut VecDeque<u64>, size: u64) {
+    if samples.len() == PAYLOAD_SAMPLES {
+        samples.pop_front();
+    }
+    samples.push_back(size);
+}
+
+pub fn record_upstream_error(source: &str, status: u16, reason: &str) {
+    if let Ok(mut map) = UPSTREAM_ERRORS.lock() {
+        *map.entry((source.to_string(), status, reason.to_string()))
+            .or_insert(0) += 1;
+    }
+}
+
+/// Records the body sizes of a finished request under its route pattern (`/hls/{video_id}/...`,
+/// not the concrete path). Requests without Content-Length and streamed responses of unknown
+/// length count towards `requests` only.
+pub fn record_payload<B: MessageBody>(res: &ServiceResponse<B>) {
+    let route = res.request().match_pattern().unwrap_or_else(|| "(unmatched)".to_string());
+    let request_size = res
+        .request()
+        .headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    let response_size = match res.response().body().size() {
+        BodySize::Sized(n) => Some(n),
+        BodySize::None | BodySize::Stream => None,
+    };
+    if let Ok(mut map) = PAYLOADS.lock() {
+        let entry = map.entry(route).or_default();
+        entry.requests += 1;
+        if let Some(size) = request_size {
+            push_sample(&mut entry.request_sizes, size);
+            entry.request_max = entry.request_max.max(size);
+        }
+        if let Some(size) = response_size {
+            push_sample(&mut entry.response_sizes, size);
+            entry.response_max = entry.response_max.max(size);
+        }
+    }
+}
+
+/// Nearest-rank percentile of `samples`; 0 when there are none.
+fn percentile(samples: &VecDeque<u64>, pct: usize) -> u64 {
+    if samples.is_empty() {
+        return 0;
+    }
+    let mut sorted: Vec<u64> = samples.iter().copied().collect();
+    sorted.sort_unstable();
+    let rank = (pct * sorted.len()).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+/// Body sizes in bytes for one route. Percentiles cover its last 512 requests; the maxima
+/// cover everything since startup.
+#[derive(Serialize, ToSchema)]
+pub struct PayloadSizes {
+    pub route: String,
+    pub requests: u64,
+    pub request_p50: u64,
+    pub request_p95: u64,
+    pub request_max: u64,
+    pub response_p50: u64,
+    pub response_p95: u64,
+    pub response_max: u64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct UpstreamErrorCount {
+    pub source: String,
+    pub status: u16,
+    pub reason: String,
+    pub count: u64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct MetricsResponse {
+    pub uptime_seconds: u64,
+    pub upstream_errors: Vec<UpstreamErrorCount>,
+    /// Upstream calls answered by joining an identical call already in flight.
+    pub coalesced_requests: u64,
+    pub hosts: Vec<crate::routes::circuit::HostHealth>,
+    /// Per route, largest p95 response first.
+    pub payload_sizes: Vec<PayloadSizes>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    responses(
+        (status = 200, description = "Server counters", body = MetricsResponse)
+    )
+)]
+pub async fn get_metrics() -> impl Responder {
+    let mut upstream_errors: Vec<UpstreamErrorCount> = UPSTREAM_ERRORS
+        .lock()
+        .map(|map| {
+            map.iter()
+                .map(|((source, status, reason), count)| UpstreamErrorCount {
+                    source: source.clone(),
+                    status: *status,
+                    reason: reason.clone(),
+                    count: *count,
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    upstream_errors.sort_by_key(|e| std::cmp::Reverse(e.count));
+
+    let mut payload_sizes: Vec<PayloadSizes> = PAYLOADS
+        .lock()
+        .map(|map| {
+            map.iter()
+                .map(|(route, p)| PayloadSizes {
+                    route: route.clone(),
+                    requests: p.requests,
+                    request_p50: percentile(&p.request_sizes, 50),
+                    request_p95: percentile(&p.request_sizes, 95),
+                    request_max: p.request_max,
+                    response_p50: percentile(&p.response_sizes, 50),
+                    response_p95: percentile(&p.response_sizes, 95),
+                    response_max: p.response_max,
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    payload_sizes.sort_by_key(|p| std::cmp::Reverse(p.response_p95));
+
+    HttpResponse::Ok().json(MetricsResponse {
+        uptime_seconds: STARTED_AT.elapsed().as_secs(),
+        upstream_errors,
+        coalesced_requests: crate::http_client::coalesced(),
+        hosts: crate::routes::circuit::snapshot(),
+        payload_sizes,
+    })
+}
+
+/// Forces the uptime clock to start at boot rather than on the first /metrics hit.
+pub fn init() {
+    lazy_static::initialize(&STARTED_AT);
+}
@@ -1,9 +1,72 @@
 pub mod actions;
+pub mod admin;
 pub mod additional;
+pub mod api_keys;
+pub mod audit;
 pub mod auth;
 pub mod auth_routes;
+pub mod bandwidth;
+pub mod blocklist;
 pub mod channel;
+pub mod circuit;
+pub mod cookies;
+pub mod crawlers;
+pub mod data_api;
+pub mod device_profile;
+pub mod envelope;
+pub mod extractor;
+pub mod favorites;
+pub mod fields;
+pub mod formatter;
 pub mod frontend;
+pub mod http_cache;
+pub mod hotlink;
+pub mod hwaccel;
+pub mod library;
+pub mod locale;
+pub mod login_cookie;
+pub mod messages;
+pub mod metrics;
 pub mod oauth;
+pub mod pinned;
+pub mod playback_errors;
+pub mod podcast;
+pub mod po_token;
+pub mod profiles;
+pub mod quota;
+pub mod ryd;
 pub mod search;
+pub mod segment_cache;
+pub mod signed_urls;
+pub mod token_health;
+pub mod token_scopes;
+pub mod transcode;
+pub mod transcode_cache;
+pub mod upload;
+pub mod upstream;
+pub mod usage;
 pub mod video;
+
+use actix_web::HttpRequest;
+
+use crate::config::Config;
+
+/// Public base URL with a trailing slash: `server.main_url` when set, else scheme and host of
+/// the request.
+pub(crate) fn base_url(req: &HttpRequest, config: &Config) -> String {
+    if !config.server.main_url.is_empty() {
+        return config.public_base(config.server.main_url.clone());
+    }
+    let info = req.connection_info();
+    let scheme = info.scheme();
+    let host = info.host();
+    config.public_base(format!("{}://{}/", scheme, host.trim_end_matches('/')))
+}
+
+/// Unix time in seconds; [`crate::db::now_secs`] is the same as the `i64` SQLite stores.
+pub(crate) fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
@@ -1,4 +1,3 @@
-use reqwest::Client;
 use serde_json::Value;
 
 use crate::routes::auth::AuthConfig;
@@ -7,20 +6,70 @@ pub async fn refresh_access_token(
     refresh_token: &str,
     auth_config: &AuthConfig,
 ) -> Result<String, String> {
-    let client = Client::new();
+    if crate::routes::token_health::is_revoked(refresh_token) {
+        return Err("Refresh token was revoked; sign in again".to_string());
+    }
+    let result = request_access_token(refresh_token, auth_config).await;
+    if let Err(e) = &result {
+        use crate::routes::audit;
+        audit::record(None, audit::TOKEN_REFRESH_FAILED, &audit::token_session(refresh_token), false, e);
+    }
+    result
+}
+
+/// Outcome of trying a refresh token without using the access token it yields.
+pub enum TokenCheck {
+    Valid,
+    /// Google answered `invalid_grant`: revoked by the user, expired or replaced.
+    Revoked,
+    /// Network trouble, 5xx or a client-side problem (e.g. bad client credentials); says
+    /// nothing about the token itself.
+    Unknown(String),
+}
+
+pub async fn check_refresh_token(refresh_token: &str, auth_config: &AuthConfig) -> TokenCheck {
+    let res = match token_request(refresh_token, auth_config).await {
+        Ok(r) => r,
+        Err(e) => return TokenCheck::Unknown(e),
+    };
+    let status = res.status();
+    if status.is_success() {
+        return TokenCheck::Valid;
+    }
+    let json: Value = res.json().await.unwrap_or(Value::Null);
+    match json.get("error").and_then(|e| e.as_str()) {
+        Some("invalid_grant") => TokenCheck::Revoked,
+        Some(error) => TokenCheck::Unknown(format!("{}: {}", status, error)),
+        None => TokenCheck::Unknown(status.to_string()),
+    }
+}
+
+async fn token_request(
+    refresh_token: &str,
+    auth_config: &AuthConfig,
+) -> Result<reqwest::Response, String> {
+    let client = crate::http_client::shared();
+    let (client_id, client_secret) = auth_config.credentials();
     let params = [
-        ("client_id", auth_config.client_id.as_str()),
-        ("client_secret", auth_config.client_secret.as_str()),
+        ("client_id", client_id.as_str()),
+        ("client_secret", client_secret.as_str()),
         ("refresh_token", refresh_token),
         ("grant_type", "refresh_token"),
     ];
 
-    let res = client
-        .post("https://oauth2.googleapis.com/token")
+    client
+        .post(crate::endpoints::oauth_token())
         .form(&params)
         .send()
         .await
-        .map_err(|e| e.to_string())?;
+        .map_err(|e| e.to_string())
+}
+
+async fn request_access_token(
+    refresh_token: &str,
+    auth_config: &AuthConfig,
+) -> Result<String, String> {
+    let res = token_request(refresh_token, auth_config).await?;
 
     if !res.status().is_success() {
         return Err(format!("Token refresh failed: {}", res.status()));
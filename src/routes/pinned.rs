@@ -0,0 +1,116 @@
+//! Pinned channels (`home.pinned_channels`): their latest uploads are pulled from the public
+//! RSS feeds on a timer and shown on the logged-out home page.
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::sync::RwLock;
+use std::time::Duration;
+
+use crate::config::HomeConfig;
+
+#[derive(Clone)]
+pub struct PinnedVideo {
+    pub video_id: String,
+    pub title: String,
+    pub author: String,
+    pub views: String,
+    /// RFC 3339, as in the feed.
+    pub published: String,
+}
+
+lazy_static! {
+    static ref LATEST: RwLock<Vec<PinnedVideo>> = RwLock::new(Vec::new());
+    static ref ENTRY_RE: Regex = Regex::new(r"(?s)<entry>(.*?)</entry>").unwrap();
+    static ref VIDEO_ID_RE: Regex = Regex::new(r"<yt:videoId>([^<]+)</yt:videoId>").unwrap();
+    static ref TITLE_RE: Regex = Regex::new(r"<title>([^<]*)</title>").unwrap();
+    static ref AUTHOR_RE: Regex = Regex::new(r"(?s)<author>\s*<name>([^<]*)</name>").unwrap();
+    static ref PUBLISHED_RE: Regex = Regex::new(r"<published>([^<]+)</published>").unwrap();
+    static ref VIEWS_RE: Regex = Regex::new(r#"<media:statistics views="(\d+)""#).unwrap();
+}
+
+/// Newest uploads across all pinned channels.
+pub fn latest(limit: usize) -> Vec<PinnedVideo> {
+    LATEST
+        .read()
+        .map(|items| items.iter().take(limit).cloned().collect())
+        .unwrap_or_default()
+}
+
+fn parse_feed(xml: &str, limit: usize) -> Vec<PinnedVideo> {
+    let capture = |re: &Regex, text: &str| {
+        re.captures(text)
+            .and_then(|c| c.get(1))
+            .map(|m| html_escape::decode_html_entities(m.as_str()).to_string())
+            .unwrap_or_default()
+    };
+    ENTRY_RE
+        .captures_iter(xml)
+        .filter_map(|c| c.get(1))
+        .map(|entry| entry.as_str())
+        .filter_map(|entry| {
+            let video_id = capture(&VIDEO_ID_RE, entry);
+            if video_id.is_empty() {
+                return None;
+            }
+            Some(PinnedVideo {
+                video_id,
+                title: capture(&TITLE_RE, entry),
+                author: capture(&AUTHOR_RE, entry),
+                views: capture(&VIEWS_RE, entry),
+                published: capture(&PUBLISHED_RE, entry),
+            })
+        })
+        .take(limit)
+        .collect()
+}
+
+pub(crate) async fn fetch_channel(channel_id: &str, limit: usize) -> Result<Vec<PinnedVideo>, String> {
+    let url = format!(
+        "https://www.youtube.com/feeds/videos.xml?channel_id={}",
+        urlencoding::encode(channel_id)
+    );
+    let resp = crate::http_client::shared()
+        .get(&url)
+        .timeout(Duration::from_secs(20))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !resp.status().is_success() {
+        return Err(format!("feed returned {}", resp.status()));
+    }
+    let xml = resp.text().await.map_err(|e| e.to_string())?;
+    Ok(parse_feed(&xml, limit))
+}
+
+async fn refresh(config: &HomeConfig) {
+    let mut videos = Vec::new();
+    for channel_id in &config.pinned_channels {
+        match fetch_channel(channel_id, config.pinned_per_channel as usize).await {
+            Ok(items) => videos.extend(items),
+            Err(e) => log::warn!("Pinned channel {}: {}", channel_id, e),
+        }
+    }
+    // A failed round keeps the previous shelf instead of blanking it
+    if videos.is_empty() {
+        return;
+    }
+    videos.sort_by(|a, b| b.published.cmp(&a.published));
+    if let Ok(mut latest) = LATEST.write() {
+        *latest = videos;
+    }
+}
+
+/// Fetches the feeds now and then every `pinned_refresh_minutes`.
+pub fn spawn_refresh(config: &HomeConfig) {
+    if config.pinned_channels.is_empty() {
+        return;
+    }
+    let config = config.clone();
+    let period = Duration::from_secs(u64::from(config.pinned_refresh_minutes.max(1)) * 60);
+    tokio::spawn(async move {
+        loop {
+            refresh(&config).await;
+            tokio::time::sleep(period).await;
+        }
+    });
+}
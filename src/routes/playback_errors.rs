@@ -0,0 +1,228 @@
+//! `/report_playback_error` (`playback_errors.*`): players report failed videos so the operator
+//! can tell from /admin/stats which videos and devices fail, and whether the resolver, the
+//! transcoder or the network is to blame.
+
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::config::Config;
+use crate::db;
+
+const STAGES: [&str; 5] = ["resolver", "transcoder", "network", "client", "other"];
+
+#[derive(Deserialize, ToSchema)]
+pub struct PlaybackErrorReport {
+    pub video_id: String,
+    /// Player error code or name, e.g. `resolver_timeout`, `MEDIA_ERR_DECODE`, `403`.
+    #[serde(alias = "error", alias = "code")]
+    pub error_code: String,
+    /// Seconds into the video when playback failed.
+    pub position: Option<f64>,
+    /// Overrides the request's `User-Agent` (players behind a proxy).
+    pub user_agent: Option<String>,
+    /// resolver, transcoder, network or client; guessed from the code when omitted.
+    pub stage: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CountEntry {
+    pub key: String,
+    pub count: i64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PlaybackErrorSummary {
+    pub enabled: bool,
+    /// Window the counts cover.
+    pub days: u32,
+    pub total: i64,
+    pub by_stage: Vec<CountEntry>,
+    pub top_videos: Vec<CountEntry>,
+    pub top_user_agents: Vec<CountEntry>,
+    pub top_codes: Vec<CountEntry>,
+}
+
+fn valid_video_id(id: &str) -> bool {
+    id.len() == 11
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Which part of the pipeline a code points at. Our own error codes (`resolver_timeout`,
+/// `Unsupported profile`, ...) are recognised, HTTP statuses count as network, and browser
+/// `MEDIA_ERR_*` codes mostly land on the client.
+fn stage_for(code: &str) -> &'static str {
+    let code = code.to_ascii_lowercase();
+    if code.contains("resolve") || code.contains("yt-dlp") || code.contains("ytdlp") {
+        "resolver"
+    } else if code.contains("transcod")
+        || code.contains("ffmpeg")
+        || code.contains("codec")
+        || code.contains("profile")
+        || code.contains("conversion")
+    {
+        "transcoder"
+    } else if code.contains("network")
+        || code.contains("timeout")
+        || (code.len() == 3 && code.chars().all(|c| c.is_ascii_digit()))
+    {
+        "network"
+    } else if code.contains("decode") || code.contains("src_not_supported") || code.contains("aborted") {
+        "client"
+    } else {
+        "other"
+    }
+}
+
+fn clip(value: &str, max: usize) -> String {
+    value.trim().chars().take(max).collect()
+}
+
+fn store(report: PlaybackErrorReport, req: &HttpRequest, config: &Config) -> HttpResponse {
+    if !config.playback_errors.enabled || !db::is_ready() {
+        return HttpResponse::NotFound().json(serde_json::json!({
+            "error": "Playback error reporting is disabled"
+        }));
+    }
+    let code = clip(&report.error_code, 64);
+    if !valid_video_id(&report.video_id) || code.is_empty() {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": "video_id and error_code are required"
+        }));
+    }
+    let stage = report
+        .stage
+        .as_deref()
+        .map(|s| s.trim().to_ascii_lowercase())
+        .and_then(|s| STAGES.iter().copied().find(|known| *known == s))
+        .unwrap_or_else(|| stage_for(&code));
+    let user_agent = report
+        .user_agent
+        .or_else(|| {
+            req.headers()
+                .get("User-Agent")
+                .and_then(|v| v.to_str().ok())
+                .map(|s| s.to_string())
+        })
+        .map(|ua| clip(&ua, 256))
+        .unwrap_or_default();
+    let position = report
+        .position
+        .filter(|p| p.is_finite() && *p >= 0.0)
+        .map(|p| p as i64);
+    let now = db::now_secs();
+    let cutoff = now - i64::from(config.playback_errors.retention_days) * 86400;
+
+    let result = db::with_conn(|conn| {
+        conn.execute(
+            "INSERT INTO playback_errors (video_id, code, stage, position_secs, user_agent, reported_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![report.video_id, code, stage, position, user_agent, now],
+        )?;
+        conn.execute("DELETE FROM playback_errors WHERE reported_at < ?1", params![cutoff])
+    });
+    match result {
+        Ok(_) => HttpResponse::Ok().json(serde_json::json!({ "ok": true, "stage": stage })),
+        Err(e) => {
+            log::warn!("Failed to store playback error for {}: {}", report.video_id, e);
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Failed to store report",
+                "details": e
+            }))
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/report_playback_error",
+    params(
+        ("video_id" = String, Query, description = "YouTube video ID"),
+        ("error_code" = String, Query, description = "Player error code (alias error)"),
+        ("position" = Option<f64>, Query, description = "Playback position in seconds"),
+        ("user_agent" = Option<String>, Query, description = "Device user agent; defaults to the User-Agent header"),
+        ("stage" = Option<String>, Query, description = "resolver, transcoder, network or client; guessed when omitted")
+    ),
+    responses(
+        (status = 200, description = "Report stored"),
+        (status = 400, description = "Missing video_id or error_code"),
+        (status = 404, description = "Reporting disabled")
+    )
+)]
+pub async fn report_playback_error(
+    req: HttpRequest,
+    query: web::Query<PlaybackErrorReport>,
+    data: web::Data<crate::AppState>,
+) -> impl Responder {
+    store(query.into_inner(), &req, &data.config)
+}
+
+#[utoipa::path(
+    post,
+    path = "/report_playback_error",
+    request_body(content = PlaybackErrorReport, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "Report stored"),
+        (status = 400, description = "Missing video_id or error_code"),
+        (status = 404, description = "Reporting disabled")
+    )
+)]
+pub async fn report_playback_error_form(
+    req: HttpRequest,
+    form: web::Form<PlaybackErrorReport>,
+    data: web::Data<crate::AppState>,
+) -> impl Responder {
+    store(form.into_inner(), &req, &data.config)
+}
+
+fn counts(conn: &rusqlite::Connection, column: &str, since: i64, limit: u32) -> rusqlite::Result<Vec<CountEntry>> {
+    // `column` is one of our own column names, never user input
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {col}, COUNT(*) AS n FROM playback_errors WHERE reported_at >= ?1
+         GROUP BY {col} ORDER BY n DESC LIMIT ?2",
+        col = column
+    ))?;
+    let rows = stmt.query_map(params![since, limit], |row| {
+        Ok(CountEntry {
+            key: row.get(0)?,
+            count: row.get(1)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Counts over the last `days` days for /admin/stats.
+pub fn summary(config: &Config, days: u32) -> PlaybackErrorSummary {
+    let mut summary = PlaybackErrorSummary {
+        enabled: config.playback_errors.enabled,
+        days,
+        total: 0,
+        by_stage: Vec::new(),
+        top_videos: Vec::new(),
+        top_user_agents: Vec::new(),
+        top_codes: Vec::new(),
+    };
+    if !summary.enabled || !db::is_ready() {
+        return summary;
+    }
+    let since = db::now_secs() - i64::from(days) * 86400;
+    let result = db::with_conn(|conn| {
+        summary.total = conn.query_row(
+            "SELECT COUNT(*) FROM playback_errors WHERE reported_at >= ?1",
+            params![since],
+            |row| row.get(0),
+        )?;
+        summary.by_stage = counts(conn, "stage", since, STAGES.len() as u32)?;
+        summary.top_videos = counts(conn, "video_id", since, 20)?;
+        summary.top_user_agents = counts(conn, "user_agent", since, 20)?;
+        summary.top_codes = counts(conn, "code", since, 20)?;
+        Ok(())
+    });
+    if let Err(e) = result {
+        log::warn!("Failed to summarize playback errors: {}", e);
+    }
+    summary
+}
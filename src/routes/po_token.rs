@@ -0,0 +1,118 @@
+//! Proof-of-origin tokens (`video.po_token`). YouTube wants a PO token, bound to a visitor id,
+//! for more and more unauthenticated playback; without one, player responses come back with
+//! "Sign in to confirm you're not a bot" or stream URLs that 403 after a minute. The pair is
+//! either configured as-is or fetched from a generator service (bgutil-ytdlp-pot-provider's
+//! `/get_pot` or anything answering alike), then handed to yt-dlp and the InnerTube player calls.
+
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+use crate::config::PoTokenConfig;
+
+#[derive(Clone, Debug)]
+pub struct PoToken {
+    pub po_token: String,
+    /// Visitor data the token is bound to; empty when the generator doesn't say.
+    pub visitor_data: String,
+}
+
+/// What the generator answers; bgutil uses the camelCase names.
+#[derive(Deserialize)]
+struct GeneratorResponse {
+    #[serde(alias = "poToken")]
+    po_token: String,
+    #[serde(default, alias = "contentBinding", alias = "visitorData", alias = "content_binding")]
+    visitor_data: String,
+}
+
+/// Last generated pair and when it was fetched.
+static GENERATED: RwLock<Option<(PoToken, Instant)>> = RwLock::new(None);
+/// The pair `yt_dlp_command` passes on, as of the last `ensure`.
+static CURRENT: RwLock<Option<PoToken>> = RwLock::new(None);
+
+fn configured(config: &PoTokenConfig) -> Option<PoToken> {
+    let po_token = config.po_token.trim();
+    (!po_token.is_empty()).then(|| PoToken {
+        po_token: po_token.to_string(),
+        visitor_data: config.visitor_data.trim().to_string(),
+    })
+}
+
+async fn generate(config: &PoTokenConfig) -> Result<PoToken, String> {
+    let mut body = json!({});
+    if !config.visitor_data.trim().is_empty() {
+        body["content_binding"] = json!(config.visitor_data.trim());
+    }
+    let resp = crate::http_client::shared()
+        .post(config.generator_url.trim())
+        .json(&body)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !resp.status().is_success() {
+        return Err(format!("HTTP {}", resp.status()));
+    }
+    let generated: GeneratorResponse = resp.json().await.map_err(|e| e.to_string())?;
+    if generated.po_token.is_empty() {
+        return Err("empty po_token".to_string());
+    }
+    let visitor_data = if generated.visitor_data.is_empty() {
+        config.visitor_data.trim().to_string()
+    } else {
+        generated.visitor_data
+    };
+    Ok(PoToken {
+        po_token: generated.po_token,
+        visitor_data,
+    })
+}
+
+/// The pair to use now: the configured one, else a generated one, refetched every
+/// `cache_minutes`. A failed refetch keeps the previous token. `None` when neither is set up.
+pub async fn ensure(config: &crate::config::Config) -> Option<PoToken> {
+    let po = &config.video.po_token;
+    let token = if let Some(token) = configured(po) {
+        Some(token)
+    } else if po.generator_url.trim().is_empty() {
+        None
+    } else {
+        let ttl = Duration::from_secs(u64::from(po.cache_minutes.max(1)) * 60);
+        let cached = GENERATED.read().ok().and_then(|g| g.clone());
+        match cached {
+            Some((token, at)) if at.elapsed() < ttl => Some(token),
+            stale => match generate(po).await {
+                Ok(token) => {
+                    log::info!("Fetched a PO token from {}", po.generator_url);
+                    if let Ok(mut g) = GENERATED.write() {
+                        *g = Some((token.clone(), Instant::now()));
+                    }
+                    Some(token)
+                }
+                Err(e) => {
+                    log::warn!("PO token generator {} failed: {}", po.generator_url, e);
+                    stale.map(|(token, _)| token)
+                }
+            },
+        }
+    };
+    if let Ok(mut current) = CURRENT.write() {
+        *current = token.clone();
+    }
+    token
+}
+
+/// yt-dlp `--extractor-args` for the pair from the last `ensure`, for the web and mweb clients
+/// it uses when handed a token.
+pub fn yt_dlp_extractor_args() -> Option<String> {
+    let token = CURRENT.read().ok()?.clone()?;
+    let mut args = format!(
+        "youtube:po_token=web.gvs+{0},mweb.gvs+{0},web.player+{0}",
+        token.po_token
+    );
+    if !token.visitor_data.is_empty() {
+        args.push_str(&format!(";visitor_data={}", token.visitor_data));
+    }
+    Some(args)
+}
@@ -0,0 +1,166 @@
+//! /feeds/podcast/{channel}.xml: a channel's latest uploads as an RSS 2.0 podcast with iTunes
+//! tags, each enclosure an MP3 from /direct_audio_url. iPods synced through iTunes, Nokia
+//! podcatchers and the like only take audio enclosures with a real date, so the items come
+//! from YouTube's own channel feed (publish times) and the InnerTube channel page (durations,
+//! artwork, description).
+
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use html_escape::{encode_double_quoted_attribute, encode_text};
+use std::collections::HashMap;
+
+use crate::routes::frontend::base_url;
+use crate::routes::messages::Msg;
+
+/// Uploads per channel page InnerTube sends in one go; only used for durations.
+const DURATION_LOOKUP_COUNT: i32 = 30;
+
+/// `4:13` / `1:02:03` in seconds.
+fn duration_seconds(text: &str) -> Option<u64> {
+    text.split(':')
+        .try_fold(0u64, |total, part| Some(total * 60 + part.trim().parse::<u64>().ok()?))
+        .filter(|s| *s > 0)
+}
+
+/// RFC 3339 from the feed to the RFC 2822 RSS wants; unparsable dates are left out.
+fn pub_date(published: &str) -> Option<String> {
+    chrono::DateTime::parse_from_rfc3339(published)
+        .ok()
+        .map(|d| d.to_rfc2822())
+}
+
+#[utoipa::path(
+    get,
+    path = "/feeds/podcast/{channel}.xml",
+    params(
+        ("channel" = String, Path, description = "Channel UC id or handle")
+    ),
+    responses(
+        (status = 200, description = "RSS 2.0 podcast of the latest uploads, MP3 enclosures via /direct_audio_url"),
+        (status = 404, description = "Unknown channel"),
+        (status = 502, description = "YouTube's channel feed could not be fetched")
+    )
+)]
+pub async fn podcast_feed(
+    req: HttpRequest,
+    path: web::Path<String>,
+    data: web::Data<crate::AppState>,
+) -> impl Responder {
+    let config = &data.config;
+    let base = base_url(&req, config);
+    let channel = path.into_inner();
+    let innertube_key = config.get_innertube_key();
+
+    let channel_id = if channel.len() == 24 && channel.starts_with("UC") {
+        Some(channel.clone())
+    } else if let Some(key) = &innertube_key {
+        crate::routes::channel::resolve_handle_to_channel_id(&channel, &crate::http_client::shared(), key, &base).await
+    } else {
+        None
+    };
+    let Some(channel_id) = channel_id else {
+        return HttpResponse::NotFound().json(serde_json::json!({
+            "error": Msg::ChannelNotFound.text()
+        }));
+    };
+
+    let entries = match crate::routes::pinned::fetch_channel(&channel_id, usize::MAX).await {
+        Ok(entries) => entries,
+        Err(e) => {
+            crate::log::info!("Podcast feed: channel feed for {} failed: {}", channel_id, e);
+            return HttpResponse::BadGateway().json(serde_json::json!({
+                "error": "Failed to fetch the channel feed",
+                "details": e
+            }));
+        }
+    };
+
+    let locale = crate::routes::locale::Locale::from_request(&req, config);
+    let (page_videos, info) = match &innertube_key {
+        Some(key) => {
+            let (videos, info, _) = crate::routes::channel::fetch_channel_videos_inner_tube(
+                &channel_id,
+                DURATION_LOOKUP_COUNT,
+                key,
+                &base,
+                &locale,
+            )
+            .await;
+            (videos, Some(info))
+        }
+        None => (Vec::new(), None),
+    };
+    let durations: HashMap<String, u64> = page_videos
+        .iter()
+        .filter_map(|v| Some((v.video_id.clone(), duration_seconds(&v.duration)?)))
+        .collect();
+
+    let author = entries
+        .first()
+        .map(|e| e.author.clone())
+        .filter(|a| !a.is_empty())
+        .or_else(|| info.as_ref().map(|i| i.title.clone()))
+        .unwrap_or_else(|| channel_id.clone());
+    let description = info
+        .as_ref()
+        .map(|i| i.description.clone())
+        .filter(|d| !d.is_empty())
+        .unwrap_or_else(|| author.clone());
+    let channel_link = format!("{}/channel?handle={}", base, urlencoding::encode(&channel_id));
+    // MP3 at this bitrate is what the enclosures carry; the size can only be estimated
+    let bytes_per_second = u64::from(config.video.mp3_bitrate_kbps) * 1000 / 8;
+
+    let mut body = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<rss version=\"2.0\" xmlns:itunes=\"http://www.itunes.com/dtds/podcast-1.0.dtd\">\n<channel>\n",
+    );
+    body.push_str(&format!("  <title>{}</title>\n", encode_text(&author)));
+    body.push_str(&format!("  <link>{}</link>\n", encode_text(&channel_link)));
+    body.push_str(&format!("  <description>{}</description>\n", encode_text(&description)));
+    body.push_str(&format!("  <itunes:author>{}</itunes:author>\n", encode_text(&author)));
+    body.push_str(&format!("  <itunes:summary>{}</itunes:summary>\n", encode_text(&description)));
+    if let Some(image) = info.as_ref().map(|i| i.thumbnail.as_str()).filter(|t| !t.is_empty()) {
+        body.push_str(&format!("  <itunes:image href=\"{}\"/>\n", encode_double_quoted_attribute(image)));
+        body.push_str(&format!(
+            "  <image><url>{}</url><title>{}</title><link>{}</link></image>\n",
+            encode_text(image),
+            encode_text(&author),
+            encode_text(&channel_link)
+        ));
+    }
+    for entry in &entries {
+        let id = urlencoding::encode(&entry.video_id);
+        let audio = format!(
+            "{}/direct_audio_url?video_id={}&format=mp3{}",
+            base,
+            id,
+            crate::routes::signed_urls::direct_audio_url_params(&entry.video_id)
+        );
+        let duration = durations.get(&entry.video_id).copied();
+        body.push_str("  <item>\n");
+        body.push_str(&format!("    <title>{}</title>\n", encode_text(&entry.title)));
+        body.push_str(&format!("    <link>{}/watch?v={}</link>\n", encode_text(&base), id));
+        body.push_str(&format!("    <guid isPermaLink=\"false\">yt:video:{}</guid>\n", encode_text(&entry.video_id)));
+        if let Some(date) = pub_date(&entry.published) {
+            body.push_str(&format!("    <pubDate>{}</pubDate>\n", date));
+        }
+        body.push_str(&format!(
+            "    <enclosure url=\"{}\" length=\"{}\" type=\"audio/mpeg\"/>\n",
+            encode_double_quoted_attribute(&audio),
+            duration.map_or(0, |s| s * bytes_per_second)
+        ));
+        if let Some(seconds) = duration {
+            body.push_str(&format!(
+                "    <itunes:duration>{}:{:02}:{:02}</itunes:duration>\n",
+                seconds / 3600,
+                seconds / 60 % 60,
+                seconds % 60
+            ));
+        }
+        body.push_str("  </item>\n");
+    }
+    body.push_str("</channel>\n</rss>\n");
+
+    HttpResponse::Ok()
+        .content_type("application/rss+xml; charset=utf-8")
+        .insert_header(("Cache-Control", format!("public, max-age={}", config.cache.channel_max_age)))
+        .body(body)
+}
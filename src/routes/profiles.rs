@@ -0,0 +1,780 @@
+//! Local profiles (`profiles.*`): several people sharing one instance (and often one Google
+//! login) each pick a profile with a PIN and get their own watch history, resume positions and
+//! blocklist additions, stored in SQLite. The choice is a server-side session behind the
+//! `profile_session` cookie.
+//!
+//! POST endpoints take form fields so the no-JS frontend can use them directly; with a
+//! `redirect` field (one of our frontend pages) they answer 303 instead of JSON.
+
+use actix_web::http::header::LOCATION;
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use rusqlite::{params, OptionalExtension};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use utoipa::ToSchema;
+
+use crate::db;
+use crate::routes::blocklist::{BlocklistChange, BlocklistLists};
+use crate::routes::messages::Msg;
+
+pub(crate) const COOKIE: &str = "profile_session";
+/// How long picking a profile lasts; older sessions are ignored and pruned.
+const SESSION_TTL_SECS: i64 = 30 * 24 * 3600;
+
+/// Wrong PINs a profile takes before it is locked; each further one doubles the lock.
+const FREE_PIN_ATTEMPTS: u32 = 3;
+const FIRST_PIN_LOCK: Duration = Duration::from_secs(30);
+const MAX_PIN_LOCK: Duration = Duration::from_secs(3600);
+
+#[derive(Default)]
+struct PinFailures {
+    in_a_row: u32,
+    locked_until: Option<Instant>,
+}
+
+lazy_static! {
+    /// Profile id -> its wrong PINs since the last right one.
+    static ref PIN_FAILURES: Mutex<HashMap<i64, PinFailures>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Serialize, Clone, ToSchema)]
+pub struct ProfileInfo {
+    pub id: i64,
+    pub name: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ProfilesResponse {
+    pub enabled: bool,
+    pub current: Option<ProfileInfo>,
+    pub profiles: Vec<ProfileInfo>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ProfileHistoryItem {
+    pub video_id: String,
+    pub title: String,
+    pub author: String,
+    /// Unix seconds.
+    pub watched_at: i64,
+    pub position: Option<u64>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ResumePosition {
+    pub video_id: String,
+    pub position: u64,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ProfileForm {
+    pub id: Option<i64>,
+    pub name: Option<String>,
+    pub pin: Option<String>,
+    /// Local path to redirect to (frontend forms).
+    pub redirect: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct HistoryForm {
+    pub video_id: String,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub author: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct PositionForm {
+    pub video_id: String,
+    pub position: u64,
+}
+
+fn enabled(config: &crate::config::Config) -> bool {
+    config.profiles.enabled && db::is_ready()
+}
+
+fn hash_pin(salt: &str, pin: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(salt.as_bytes());
+    hasher.update(b":");
+    hasher.update(pin.as_bytes());
+    hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn verify_pin(stored: &str, pin: &str) -> bool {
+    match stored.split_once('$') {
+        Some((salt, hash)) => {
+            crate::routes::admin::constant_time_eq(hash_pin(salt, pin).as_bytes(), hash.as_bytes())
+        }
+        None => false,
+    }
+}
+
+fn valid_pin(pin: &str) -> bool {
+    (4..=8).contains(&pin.len()) && pin.chars().all(|c| c.is_ascii_digit())
+}
+
+fn valid_video_id(id: &str) -> bool {
+    id.len() == 11
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Profile selected by this request's cookie.
+pub(crate) fn current_profile(req: &HttpRequest) -> Option<ProfileInfo> {
+    let token = req.cookie(COOKIE)?.value().to_string();
+    if !db::is_ready() {
+        return None;
+    }
+    db::with_conn(|conn| {
+        conn.query_row(
+            "SELECT p.id, p.name FROM profile_sessions s JOIN profiles p ON p.id = s.profile_id
+             WHERE s.token = ?1 AND s.created_at > ?2",
+            params![token, db::now_secs() - SESSION_TTL_SECS],
+            |row| {
+                Ok(ProfileInfo {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                })
+            },
+        )
+        .optional()
+    })
+    .ok()
+    .flatten()
+}
+
+fn load_blocklist(profile_id: i64) -> BlocklistLists {
+    let rows: Vec<(String, String)> = db::with_conn(|conn| {
+        let mut stmt =
+            conn.prepare("SELECT kind, value FROM profile_blocklist WHERE profile_id = ?1")?;
+        let rows = stmt
+            .query_map(params![profile_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(rows)
+    })
+    .unwrap_or_default();
+    let mut lists = BlocklistLists::default();
+    for (kind, value) in rows {
+        match kind.as_str() {
+            "channel" => lists.channels.push(value),
+            "keyword" => lists.keywords.push(value),
+            "category" => lists.categories.push(value),
+            _ => {}
+        }
+    }
+    lists
+}
+
+/// Blocklist additions of the request's profile, if one is selected and has any.
+pub(crate) fn blocklist_for(req: &HttpRequest) -> Option<BlocklistLists> {
+    let profile = current_profile(req)?;
+    let lists = load_blocklist(profile.id);
+    if lists.channels.is_empty() && lists.keywords.is_empty() && lists.categories.is_empty() {
+        None
+    } else {
+        Some(lists)
+    }
+}
+
+/// Adds or refreshes a history entry and trims the profile's history to `limit`.
+pub(crate) fn record_history(profile_id: i64, video_id: &str, title: &str, author: &str, limit: u32) {
+    let result = db::with_conn(|conn| {
+        conn.execute(
+            "INSERT INTO profile_history (profile_id, video_id, title, author, watched_at) VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT(profile_id, video_id) DO UPDATE SET title = excluded.title, author = excluded.author, watched_at = excluded.watched_at",
+            params![profile_id, video_id, title, author, db::now_secs()],
+        )?;
+        conn.execute(
+            "DELETE FROM profile_history WHERE profile_id = ?1 AND video_id NOT IN
+             (SELECT video_id FROM profile_history WHERE profile_id = ?1 ORDER BY watched_at DESC LIMIT ?2)",
+            params![profile_id, limit],
+        )?;
+        Ok(())
+    });
+    if let Err(e) = result {
+        log::warn!("Failed to record profile history: {}", e);
+    }
+}
+
+pub(crate) fn history(profile_id: i64, limit: u32) -> Vec<ProfileHistoryItem> {
+    db::with_conn(|conn| {
+        let mut stmt = conn.prepare(
+            "SELECT h.video_id, h.title, h.author, h.watched_at, p.position_secs FROM profile_history h
+             LEFT JOIN profile_positions p ON p.profile_id = h.profile_id AND p.video_id = h.video_id
+             WHERE h.profile_id = ?1 ORDER BY h.watched_at DESC LIMIT ?2",
+        )?;
+        let items = stmt
+            .query_map(params![profile_id, limit], |row| {
+                Ok(ProfileHistoryItem {
+                    video_id: row.get(0)?,
+                    title: row.get(1)?,
+                    author: row.get(2)?,
+                    watched_at: row.get(3)?,
+                    position: row.get::<_, Option<i64>>(4)?.map(|p| p.max(0) as u64),
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(items)
+    })
+    .unwrap_or_default()
+}
+
+pub(crate) fn resume_position(profile_id: i64, video_id: &str) -> Option<u64> {
+    db::with_conn(|conn| {
+        conn.query_row(
+            "SELECT position_secs FROM profile_positions WHERE profile_id = ?1 AND video_id = ?2",
+            params![profile_id, video_id],
+            |row| row.get::<_, i64>(0),
+        )
+        .optional()
+    })
+    .ok()
+    .flatten()
+    .map(|p| p.max(0) as u64)
+}
+
+/// Frontend pages a form may send the browser back to.
+const REDIRECT_PAGES: &[&str] = &[
+    "/",
+    "/home",
+    "/results",
+    "/watch",
+    "/channel",
+    "/profiles",
+    "/favorites",
+    "/notifications",
+];
+
+/// `redirect` when it is one of [`REDIRECT_PAGES`] (query allowed). Backslashes and control
+/// characters are refused outright: browsers read `/\host` as `//host`.
+fn local_redirect(redirect: &str) -> Option<&str> {
+    if redirect.chars().any(|c| c == '\\' || c.is_control() || c.is_whitespace()) {
+        return None;
+    }
+    let path = redirect.split(['?', '#']).next().unwrap_or_default();
+    REDIRECT_PAGES.contains(&path).then_some(redirect)
+}
+
+/// JSON, or a 303 to `redirect` (with `?error=` on failure) for frontend forms.
+pub(crate) fn respond(redirect: Option<&str>, ok: bool, body: serde_json::Value) -> HttpResponse {
+    if let Some(path) = redirect.and_then(local_redirect) {
+        let location = if ok {
+            path.to_string()
+        } else {
+            let message = body.get("error").and_then(|e| e.as_str()).unwrap_or("Error");
+            let sep = if path.contains('?') { '&' } else { '?' };
+            format!("{}{}error={}", path, sep, urlencoding::encode(message))
+        };
+        return HttpResponse::SeeOther()
+            .insert_header((LOCATION, location))
+            .finish();
+    }
+    if ok {
+        HttpResponse::Ok().json(body)
+    } else {
+        HttpResponse::BadRequest().json(body)
+    }
+}
+
+fn disabled() -> HttpResponse {
+    HttpResponse::NotFound().json(serde_json::json!({
+        "error": "Profiles are disabled"
+    }))
+}
+
+fn no_profile() -> HttpResponse {
+    HttpResponse::Unauthorized().json(serde_json::json!({
+        "error": "No profile selected"
+    }))
+}
+
+fn session_cookie(token: &str) -> String {
+    format!(
+        "{}={}; Path=/; Max-Age={}; HttpOnly; SameSite=Lax",
+        COOKIE, token, SESSION_TTL_SECS
+    )
+}
+
+fn start_session(profile_id: i64) -> Result<String, String> {
+    let token = uuid::Uuid::new_v4().simple().to_string();
+    let now = db::now_secs();
+    db::with_conn(|conn| {
+        conn.execute(
+            "DELETE FROM profile_sessions WHERE created_at <= ?1",
+            params![now - SESSION_TTL_SECS],
+        )?;
+        conn.execute(
+            "INSERT INTO profile_sessions (token, profile_id, created_at) VALUES (?1, ?2, ?3)",
+            params![token, profile_id, now],
+        )
+    })?;
+    Ok(token)
+}
+
+/// Counts a wrong PIN for `id`; past `FREE_PIN_ATTEMPTS` the profile is locked, twice as
+/// long each time, so a 4-digit PIN cannot be walked through.
+fn pin_failed(id: i64) {
+    let Ok(mut failures) = PIN_FAILURES.lock() else {
+        return;
+    };
+    let entry = failures.entry(id).or_default();
+    entry.in_a_row += 1;
+    if entry.in_a_row >= FREE_PIN_ATTEMPTS {
+        let lock = FIRST_PIN_LOCK
+            .saturating_mul(1 << (entry.in_a_row - FREE_PIN_ATTEMPTS).min(16))
+            .min(MAX_PIN_LOCK);
+        entry.locked_until = Some(Instant::now() + lock);
+        log::warn!("Profile {}: {} wrong PINs, locked for {}s", id, entry.in_a_row, lock.as_secs());
+    }
+}
+
+/// Seconds until profile `id` takes PINs again, if it is locked.
+fn pin_locked(id: i64) -> Option<u64> {
+    let failures = PIN_FAILURES.lock().ok()?;
+    let until = failures.get(&id)?.locked_until?;
+    let left = until.checked_duration_since(Instant::now())?;
+    Some(left.as_secs().max(1))
+}
+
+/// Checks `pin` against profile `id`; returns the profile name.
+fn check_pin(id: i64, pin: &str) -> Result<String, String> {
+    if let Some(secs) = pin_locked(id) {
+        return Err(format!("Too many wrong PINs; try again in {} seconds", secs));
+    }
+    let row: Option<(String, String)> = db::with_conn(|conn| {
+        conn.query_row(
+            "SELECT name, pin_hash FROM profiles WHERE id = ?1",
+            params![id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+    })?;
+    match row {
+        Some((name, stored)) if verify_pin(&stored, pin) => {
+            if let Ok(mut failures) = PIN_FAILURES.lock() {
+                failures.remove(&id);
+            }
+            Ok(name)
+        }
+        Some(_) => {
+            pin_failed(id);
+            Err("Wrong PIN".to_string())
+        }
+        None => Err("Profile not found".to_string()),
+    }
+}
+
+pub(crate) fn list() -> Vec<ProfileInfo> {
+    db::with_conn(|conn| {
+        let mut stmt = conn.prepare("SELECT id, name FROM profiles ORDER BY name COLLATE NOCASE")?;
+        let items = stmt
+            .query_map([], |row| {
+                Ok(ProfileInfo {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(items)
+    })
+    .unwrap_or_default()
+}
+
+#[utoipa::path(
+    get,
+    path = "/profiles/list",
+    responses(
+        (status = 200, description = "Profiles and the one selected by this browser", body = ProfilesResponse)
+    )
+)]
+pub async fn list_profiles(req: HttpRequest, data: web::Data<crate::AppState>) -> impl Responder {
+    if !enabled(&data.config) {
+        return HttpResponse::Ok().json(ProfilesResponse {
+            enabled: false,
+            current: None,
+            profiles: Vec::new(),
+        });
+    }
+    HttpResponse::Ok().json(ProfilesResponse {
+        enabled: true,
+        current: current_profile(&req),
+        profiles: list(),
+    })
+}
+
+#[utoipa::path(
+    post,
+    path = "/profiles/create",
+    request_body(content = ProfileForm, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "Profile created and selected", body = ProfileInfo),
+        (status = 400, description = "Invalid name/PIN or name taken")
+    )
+)]
+pub async fn create_profile(
+    form: web::Form<ProfileForm>,
+    data: web::Data<crate::AppState>,
+) -> impl Responder {
+    if !enabled(&data.config) {
+        return disabled();
+    }
+    let redirect = form.redirect.as_deref();
+    let name = form.name.as_deref().unwrap_or("").trim().to_string();
+    let pin = form.pin.as_deref().unwrap_or("").trim().to_string();
+    if name.is_empty() || name.chars().count() > 32 {
+        return respond(redirect, false, serde_json::json!({ "error": "Name must be 1-32 characters" }));
+    }
+    if !valid_pin(&pin) {
+        return respond(redirect, false, serde_json::json!({ "error": "PIN must be 4-8 digits" }));
+    }
+
+    let salt = uuid::Uuid::new_v4().simple().to_string();
+    let pin_hash = format!("{}${}", salt, hash_pin(&salt, &pin));
+    let created = db::with_conn(|conn| {
+        conn.execute(
+            "INSERT INTO profiles (name, pin_hash, created_at) VALUES (?1, ?2, ?3)",
+            params![name, pin_hash, db::now_secs()],
+        )?;
+        Ok(conn.last_insert_rowid())
+    });
+    let id = match created {
+        Ok(id) => id,
+        Err(e) => {
+            let error = if e.contains("UNIQUE") {
+                "A profile with this name already exists".to_string()
+            } else {
+                e
+            };
+            return respond(redirect, false, serde_json::json!({ "error": error }));
+        }
+    };
+
+    let mut resp = respond(redirect, true, serde_json::json!({ "id": id, "name": name }));
+    if let Ok(token) = start_session(id) {
+        if let Ok(value) = session_cookie(&token).parse() {
+            resp.headers_mut().append(actix_web::http::header::SET_COOKIE, value);
+        }
+    }
+    resp
+}
+
+#[utoipa::path(
+    post,
+    path = "/profiles/select",
+    request_body(content = ProfileForm, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "Profile selected (sets the profile_session cookie)", body = ProfileInfo),
+        (status = 400, description = "Wrong PIN, unknown profile, or locked after repeated wrong PINs")
+    )
+)]
+pub async fn select_profile(
+    form: web::Form<ProfileForm>,
+    data: web::Data<crate::AppState>,
+) -> impl Responder {
+    if !enabled(&data.config) {
+        return disabled();
+    }
+    let redirect = form.redirect.as_deref();
+    let id = form.id.unwrap_or(0);
+    let pin = form.pin.as_deref().unwrap_or("").trim();
+    let name = match check_pin(id, pin) {
+        Ok(name) => name,
+        Err(e) => return respond(redirect, false, serde_json::json!({ "error": e })),
+    };
+    let token = match start_session(id) {
+        Ok(t) => t,
+        Err(e) => return respond(redirect, false, serde_json::json!({ "error": e })),
+    };
+    let mut resp = respond(redirect, true, serde_json::json!({ "id": id, "name": name }));
+    if let Ok(value) = session_cookie(&token).parse() {
+        resp.headers_mut().append(actix_web::http::header::SET_COOKIE, value);
+    }
+    resp
+}
+
+#[utoipa::path(
+    post,
+    path = "/profiles/leave",
+    request_body(content = ProfileForm, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "Profile deselected")
+    )
+)]
+pub async fn leave_profile(req: HttpRequest, form: web::Form<ProfileForm>) -> impl Responder {
+    if let Some(cookie) = req.cookie(COOKIE) {
+        let token = cookie.value().to_string();
+        let _ = db::with_conn(|conn| {
+            conn.execute("DELETE FROM profile_sessions WHERE token = ?1", params![token])
+        });
+    }
+    let mut resp = respond(form.redirect.as_deref(), true, serde_json::json!({ "ok": true }));
+    if let Ok(value) = format!("{}=; Path=/; Max-Age=0", COOKIE).parse() {
+        resp.headers_mut().append(actix_web::http::header::SET_COOKIE, value);
+    }
+    resp
+}
+
+#[utoipa::path(
+    post,
+    path = "/profiles/delete",
+    request_body(content = ProfileForm, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "Profile and its data deleted"),
+        (status = 400, description = "Wrong PIN, unknown profile, or locked after repeated wrong PINs")
+    )
+)]
+pub async fn delete_profile(
+    form: web::Form<ProfileForm>,
+    data: web::Data<crate::AppState>,
+) -> impl Responder {
+    if !enabled(&data.config) {
+        return disabled();
+    }
+    let redirect = form.redirect.as_deref();
+    let id = form.id.unwrap_or(0);
+    if let Err(e) = check_pin(id, form.pin.as_deref().unwrap_or("").trim()) {
+        return respond(redirect, false, serde_json::json!({ "error": e }));
+    }
+    match db::with_conn(|conn| conn.execute("DELETE FROM profiles WHERE id = ?1", params![id])) {
+        Ok(_) => {
+            crate::routes::favorites::remove_owner(&format!("profile:{}", id));
+            respond(redirect, true, serde_json::json!({ "ok": true }))
+        }
+        Err(e) => respond(redirect, false, serde_json::json!({ "error": e })),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/profiles/history",
+    responses(
+        (status = 200, description = "Watch history of the selected profile", body = [ProfileHistoryItem]),
+        (status = 401, description = "No profile selected")
+    )
+)]
+pub async fn get_history(req: HttpRequest, data: web::Data<crate::AppState>) -> impl Responder {
+    if !enabled(&data.config) {
+        return disabled();
+    }
+    match current_profile(&req) {
+        Some(profile) => {
+            HttpResponse::Ok().json(history(profile.id, data.config.profiles.history_limit))
+        }
+        None => no_profile(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/profiles/history",
+    request_body(content = HistoryForm, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "Recorded"),
+        (status = 401, description = "No profile selected")
+    )
+)]
+pub async fn add_history(
+    req: HttpRequest,
+    form: web::Form<HistoryForm>,
+    data: web::Data<crate::AppState>,
+) -> impl Responder {
+    if !enabled(&data.config) {
+        return disabled();
+    }
+    let profile = match current_profile(&req) {
+        Some(p) => p,
+        None => return no_profile(),
+    };
+    if !valid_video_id(&form.video_id) {
+        return HttpResponse::BadRequest().json(serde_json::json!({ "error": Msg::InvalidVideoId.text() }));
+    }
+    record_history(
+        profile.id,
+        &form.video_id,
+        &form.title,
+        &form.author,
+        data.config.profiles.history_limit,
+    );
+    HttpResponse::Ok().json(serde_json::json!({ "ok": true }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/profiles/position",
+    params(
+        ("video_id" = String, Query, description = "YouTube video ID")
+    ),
+    responses(
+        (status = 200, description = "Saved resume position (0 if none)", body = ResumePosition),
+        (status = 401, description = "No profile selected")
+    )
+)]
+pub async fn get_position(
+    req: HttpRequest,
+    query: web::Query<HashMap<String, String>>,
+    data: web::Data<crate::AppState>,
+) -> impl Responder {
+    if !enabled(&data.config) {
+        return disabled();
+    }
+    let profile = match current_profile(&req) {
+        Some(p) => p,
+        None => return no_profile(),
+    };
+    let video_id = query.get("video_id").cloned().unwrap_or_default();
+    let position = resume_position(profile.id, &video_id).unwrap_or(0);
+    HttpResponse::Ok().json(ResumePosition { video_id, position })
+}
+
+#[utoipa::path(
+    post,
+    path = "/profiles/position",
+    request_body(content = PositionForm, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "Saved"),
+        (status = 401, description = "No profile selected")
+    )
+)]
+pub async fn save_position(
+    req: HttpRequest,
+    form: web::Form<PositionForm>,
+    data: web::Data<crate::AppState>,
+) -> impl Responder {
+    if !enabled(&data.config) {
+        return disabled();
+    }
+    let profile = match current_profile(&req) {
+        Some(p) => p,
+        None => return no_profile(),
+    };
+    if !valid_video_id(&form.video_id) {
+        return HttpResponse::BadRequest().json(serde_json::json!({ "error": Msg::InvalidVideoId.text() }));
+    }
+    let result = db::with_conn(|conn| {
+        conn.execute(
+            "INSERT INTO profile_positions (profile_id, video_id, position_secs, updated_at) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(profile_id, video_id) DO UPDATE SET position_secs = excluded.position_secs, updated_at = excluded.updated_at",
+            params![profile.id, form.video_id, form.position as i64, db::now_secs()],
+        )
+    });
+    match result {
+        Ok(_) => HttpResponse::Ok().json(serde_json::json!({ "ok": true })),
+        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({ "error": e })),
+    }
+}
+
+fn blocklist_target(
+    req: &HttpRequest,
+    query: &HashMap<String, String>,
+    config: &crate::config::Config,
+) -> Option<i64> {
+    if crate::routes::admin::is_admin(req, config) {
+        return query.get("profile_id").and_then(|id| id.parse().ok());
+    }
+    None
+}
+
+#[utoipa::path(
+    get,
+    path = "/profiles/blocklist",
+    params(
+        ("profile_id" = Option<i64>, Query, description = "Profile to inspect (admin key required); defaults to the selected profile")
+    ),
+    responses(
+        (status = 200, description = "Profile blocklist additions", body = BlocklistLists),
+        (status = 401, description = "No profile selected")
+    )
+)]
+pub async fn get_profile_blocklist(
+    req: HttpRequest,
+    query: web::Query<HashMap<String, String>>,
+    data: web::Data<crate::AppState>,
+) -> impl Responder {
+    if !enabled(&data.config) {
+        return disabled();
+    }
+    let profile_id = match blocklist_target(&req, &query, &data.config)
+        .or_else(|| current_profile(&req).map(|p| p.id))
+    {
+        Some(id) => id,
+        None => return no_profile(),
+    };
+    HttpResponse::Ok().json(load_blocklist(profile_id))
+}
+
+#[utoipa::path(
+    post,
+    path = "/profiles/blocklist",
+    request_body = BlocklistChange,
+    params(
+        ("profile_id" = i64, Query, description = "Profile to change"),
+        ("admin_key" = Option<String>, Query, description = "Admin key (or X-Admin-Key header)")
+    ),
+    responses(
+        (status = 200, description = "Updated additions", body = BlocklistLists),
+        (status = 401, description = "Admin key required")
+    )
+)]
+pub async fn update_profile_blocklist(
+    req: HttpRequest,
+    query: web::Query<HashMap<String, String>>,
+    body: web::Json<BlocklistChange>,
+    data: web::Data<crate::AppState>,
+) -> impl Responder {
+    if !enabled(&data.config) {
+        return disabled();
+    }
+    // Only the admin edits profile blocklists. They still only filter while the profile is
+    // selected: leaving it or creating another needs no PIN, so `parental` is the hard block.
+    let profile_id = match blocklist_target(&req, &query, &data.config) {
+        Some(id) => id,
+        None => {
+            return HttpResponse::Unauthorized().json(serde_json::json!({
+                "error": "Admin key and profile_id required"
+            }));
+        }
+    };
+    let change = body.into_inner();
+    let mut add = change.add;
+    let mut remove = change.remove;
+    crate::routes::blocklist::normalize(&mut add);
+    crate::routes::blocklist::normalize(&mut remove);
+
+    let result = db::with_conn(|conn| {
+        let groups = [
+            ("channel", &add.channels, &remove.channels),
+            ("keyword", &add.keywords, &remove.keywords),
+            ("category", &add.categories, &remove.categories),
+        ];
+        for (kind, adds, removes) in groups {
+            for value in adds {
+                conn.execute(
+                    "INSERT OR IGNORE INTO profile_blocklist (profile_id, kind, value) VALUES (?1, ?2, ?3)",
+                    params![profile_id, kind, value],
+                )?;
+            }
+            for value in removes {
+                conn.execute(
+                    "DELETE FROM profile_blocklist WHERE profile_id = ?1 AND kind = ?2 AND value = ?3",
+                    params![profile_id, kind, value],
+                )?;
+            }
+        }
+        Ok(())
+    });
+    match result {
+        Ok(()) => HttpResponse::Ok().json(load_blocklist(profile_id)),
+        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({ "error": e })),
+    }
+}
@@ -0,0 +1,91 @@
+//! Per-client Data API budgets (`api.quota`). Every upstream call a client triggers is charged
+//! its unit cost from YouTube's table (reads 1, writes 50) against a daily allowance, so one
+//! greedy client cannot burn the instance's keys. Clients are told apart by OAuth token when the
+//! request carries one, otherwise by IP (the connecting address; forwarded headers only count
+//! from `server.trusted_proxies`). Budgets reset at 00:00 UTC.
+
+use actix_web::http::header::RETRY_AFTER;
+use actix_web::{HttpRequest, HttpResponse};
+use lazy_static::lazy_static;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::config::Config;
+use crate::routes::messages::Msg;
+use crate::routes::now_secs;
+
+/// `videos.list`, `playlistItems.list`, `subscriptions.list`, `videos.getRating`, ...
+pub const READ_COST: u32 = 1;
+/// `subscriptions.insert` / `delete`, `videos.rate`, `videos.update`, `thumbnails.set`.
+pub const WRITE_COST: u32 = 50;
+/// `videos.insert` (a resumable upload session).
+pub const UPLOAD_COST: u32 = 1600;
+
+struct Usage {
+    day: u64,
+    units: HashMap<String, u32>,
+}
+
+lazy_static! {
+    static ref USAGE: Mutex<Usage> = Mutex::new(Usage {
+        day: 0,
+        units: HashMap::new(),
+    });
+}
+
+fn client_key(req: &HttpRequest, token: Option<&str>) -> String {
+    match token.map(str::trim).filter(|t| !t.is_empty()) {
+        Some(token) => {
+            let digest = Sha256::digest(token.as_bytes());
+            let hex: String = digest.iter().take(8).map(|b| format!("{:02x}", b)).collect();
+            format!("token:{}", hex)
+        }
+        None => format!("ip:{}", crate::routes::bandwidth::client_ip(req)),
+    }
+}
+
+/// Charges `units` to the client. `Some(429)` (with `Retry-After` until the reset) when that
+/// would go over `api.quota.daily_units`; nothing is charged then.
+pub fn charge(req: &HttpRequest, config: &Config, token: Option<&str>, units: u32) -> Option<HttpResponse> {
+    let quota = &config.api.quota;
+    if !quota.enabled || units == 0 || crate::routes::admin::is_admin(req, config) {
+        return None;
+    }
+    let ip = crate::routes::bandwidth::client_ip(req);
+    if quota.exempt_ips.iter().any(|e| e.trim() == ip) {
+        return None;
+    }
+    let key = client_key(req, token);
+    let now = now_secs();
+    let today = now / 86400;
+
+    let used = {
+        let mut usage = USAGE.lock().ok()?;
+        if usage.day != today {
+            usage.day = today;
+            usage.units.clear();
+        }
+        let used = usage.units.entry(key.clone()).or_insert(0);
+        if used.saturating_add(units) <= quota.daily_units {
+            *used += units;
+            return None;
+        }
+        *used
+    };
+
+    let retry_after = (today + 1) * 86400 - now;
+    crate::log::info!("Quota: {} over budget ({} of {} units)", key, used, quota.daily_units);
+    Some(
+        HttpResponse::TooManyRequests()
+            .insert_header((RETRY_AFTER, retry_after.to_string()))
+            .json(serde_json::json!({
+                "error": Msg::QuotaExceeded.text(),
+                "details": format!(
+                    "{} of {} units used today; this request needs {}",
+                    used, quota.daily_units, units
+                ),
+                "retry_after": retry_after
+            })),
+    )
+}
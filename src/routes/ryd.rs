@@ -0,0 +1,102 @@
+//! Return YouTube Dislike votes (`api.ryd`) and the 5-star rating pre-2010 clients expect. GData
+//! v2 gave every video a `rating` of 1-5 and a `ratingCount`; with dislikes hidden by YouTube
+//! they can only be rebuilt from RYD's estimates.
+
+use actix_web::{web, HttpRequest};
+use futures_util::future::join_all;
+use lazy_static::lazy_static;
+use lru::LruCache;
+use serde::Deserialize;
+use std::collections::HashMap;
+use tokio::sync::Mutex;
+
+use crate::routes::now_secs;
+
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub struct Votes {
+    pub likes: u64,
+    pub dislikes: u64,
+}
+
+impl Votes {
+    /// GData's scale: all dislikes is 1.0, all likes 5.0. `None` without any votes.
+    pub fn star_rating(&self) -> Option<f64> {
+        let total = self.likes + self.dislikes;
+        if total == 0 {
+            return None;
+        }
+        let rating = 1.0 + 4.0 * self.likes as f64 / total as f64;
+        Some((rating * 100.0).round() / 100.0)
+    }
+
+    pub fn rating_count(&self) -> u64 {
+        self.likes + self.dislikes
+    }
+}
+
+lazy_static! {
+    static ref VOTES_CACHE: Mutex<LruCache<String, (Votes, u64)>> =
+        Mutex::new(LruCache::new(std::num::NonZeroUsize::new(2000).unwrap()));
+}
+
+/// Whether a response should carry `rating`/`rating_count`: asked for with `ratings=true`, or
+/// implied by the GData emulation (`alt=jsonc`).
+pub fn ratings_requested(req: &HttpRequest) -> bool {
+    web::Query::<HashMap<String, String>>::from_query(req.query_string())
+        .ok()
+        .map(|q| {
+            q.get("ratings").map(|v| v == "true" || v == "1").unwrap_or(false)
+                || q.get("alt").map(|a| a == "jsonc").unwrap_or(false)
+        })
+        .unwrap_or(false)
+}
+
+/// Votes for `video_id`, cached for `api.ryd.cache_minutes`. `None` when RYD is off or failed.
+pub async fn votes(video_id: &str, config: &crate::config::Config) -> Option<Votes> {
+    if !config.api.ryd.enabled {
+        return None;
+    }
+    let ttl = u64::from(config.api.ryd.cache_minutes) * 60;
+    if let Some((votes, at)) = VOTES_CACHE.lock().await.get(video_id) {
+        if now_secs().saturating_sub(*at) < ttl {
+            return Some(*votes);
+        }
+    }
+    let url = crate::endpoints::ryd(&format!("votes?videoId={}", urlencoding::encode(video_id)));
+    let resp = match crate::http_client::shared().get(&url).send().await {
+        Ok(resp) if resp.status().is_success() => resp,
+        Ok(resp) => {
+            log::info!("RYD returned {} for {}", resp.status(), video_id);
+            return None;
+        }
+        Err(e) => {
+            log::info!("RYD request failed for {}: {}", video_id, e);
+            return None;
+        }
+    };
+    let votes: Votes = match resp.json().await {
+        Ok(votes) => votes,
+        Err(e) => {
+            log::info!("RYD answered {} with unexpected JSON: {}", video_id, e);
+            return None;
+        }
+    };
+    VOTES_CACHE
+        .lock()
+        .await
+        .put(video_id.to_string(), (votes, now_secs()));
+    Some(votes)
+}
+
+/// `votes` for several videos at once; ones without votes are left out.
+pub async fn votes_for(ids: &[String], config: &crate::config::Config) -> HashMap<String, Votes> {
+    if !config.api.ryd.enabled {
+        return HashMap::new();
+    }
+    let found = join_all(ids.iter().map(|id| votes(id, config))).await;
+    ids.iter()
+        .cloned()
+        .zip(found)
+        .filter_map(|(id, votes)| Some((id, votes?)))
+        .collect()
+}
@@ -1,21 +1,21 @@
 use actix_web::{web, HttpRequest, HttpResponse, Responder};
 use html_escape::decode_html_entities;
-use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_json;
-use std::collections::HashMap;
+use lru::LruCache;
+use std::collections::{HashMap, HashSet};
+use std::num::NonZeroUsize;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
 use urlencoding;
 use utoipa::ToSchema;
 
-fn base_url(req: &HttpRequest, config: &crate::config::Config) -> String {
-    if !config.server.main_url.is_empty() {
-        return config.server.main_url.clone();
-    }
-    let info = req.connection_info();
-    let scheme = info.scheme();
-    let host = info.host();
-    format!("{}://{}/", scheme, host.trim_end_matches('/'))
-}
+use crate::http_client::FetchError;
+use crate::routes::auth::{AuthConfig, TokenStore};
+use crate::routes::base_url;
+use crate::routes::data_api::{self, ApiList, Playlist, PlaylistItem, Video, VideoCategory, VideoSnippet};
+use crate::routes::envelope::ListResponse;
+use crate::routes::messages::Msg;
 
 fn simplify_text(node: &serde_json::Value) -> String {
     if node.is_null() {
@@ -37,18 +37,19 @@ fn simplify_text(node: &serde_json::Value) -> String {
     String::new()
 }
 
-fn find_video_renderers(obj: &serde_json::Value, out: &mut Vec<serde_json::Value>) {
+/// Collects every `<key>` object (`videoRenderer`, `channelRenderer`) in a search response.
+fn find_renderers(obj: &serde_json::Value, key: &str, out: &mut Vec<serde_json::Value>) {
     if let Some(obj_map) = obj.as_object() {
-        if obj_map.contains_key("videoRenderer") {
-            out.push(obj_map["videoRenderer"].clone());
+        if let Some(renderer) = obj_map.get(key) {
+            out.push(renderer.clone());
         } else {
             for value in obj_map.values() {
-                find_video_renderers(value, out);
+                find_renderers(value, key, out);
             }
         }
     } else if let Some(arr) = obj.as_array() {
         for item in arr {
-            find_video_renderers(item, out);
+            find_renderers(item, key, out);
         }
     }
 }
@@ -85,8 +86,20 @@ fn parse_video_renderer(vr: &serde_json::Value, base_trimmed: &str) -> Option<Se
     
     let title = simplify_text(&vr.get("title").unwrap_or(&serde_json::Value::Null));
     let description = simplify_text(&vr.get("descriptionSnippet").unwrap_or(&serde_json::Value::Null));
-    let duration = simplify_text(&vr.get("lengthText").unwrap_or(&serde_json::Value::Null));
+    let mut duration = simplify_text(&vr.get("lengthText").unwrap_or(&serde_json::Value::Null));
+    let mut overlays = Vec::new();
+    find_renderers(vr.get("thumbnailOverlays").unwrap_or(&serde_json::Value::Null), "thumbnailOverlayTimeStatusRenderer", &mut overlays);
+    let is_live = overlays
+        .iter()
+        .any(|o| o.get("style").and_then(|s| s.as_str()) == Some("LIVE"));
+    if duration.is_empty() && !is_live {
+        // some result rows only carry the length as the thumbnail's time overlay
+        if let Some(text) = overlays.first().and_then(|o| o.get("text")) {
+            duration = simplify_text(text);
+        }
+    }
     let views = simplify_text(&vr.get("viewCountText").unwrap_or(&serde_json::Value::Null));
+    let view_count = parse_view_count(&views);
     let published = simplify_text(&vr.get("publishedTimeText").unwrap_or(&serde_json::Value::Null));
     let author = simplify_text(&vr.get("ownerText").unwrap_or(&serde_json::Value::Null));
 
@@ -109,10 +122,305 @@ fn parse_video_renderer(vr: &serde_json::Value, base_trimmed: &str) -> Option<Se
         duration: if !duration.is_empty() { Some(duration) } else { None },
         description: if !description.is_empty() { Some(decode_label(&description)) } else { None },
         views: if !views.is_empty() { Some(decode_label(&views)) } else { None },
+        view_count,
+        short_views: view_count.map(|n| short_view_count(n, is_live)),
         published: if !published.is_empty() { Some(decode_label(&published)) } else { None },
+        subscriber_count: None,
+        video_count: None,
+    })
+}
+
+/// The number in "1,234,567 views" / "1 234 567 просмотров"; `None` for "No views".
+fn parse_view_count(text: &str) -> Option<u64> {
+    let digits: String = text.chars().filter(|c| c.is_ascii_digit()).collect();
+    digits.parse().ok()
+}
+
+/// "1.2M views" / "35K watching", as result rows labelled them: one decimal below ten
+/// of a unit, truncated rather than rounded so 999,999 never reads as "1000K".
+fn short_view_count(count: u64, is_live: bool) -> String {
+    let suffix = if is_live { "watching" } else if count == 1 { "view" } else { "views" };
+    let units = [(1_000_000_000, "B"), (1_000_000, "M"), (1_000, "K")];
+    let Some((unit, letter)) = units.iter().find(|(unit, _)| count >= *unit) else {
+        return format!("{} {}", count, suffix);
+    };
+    let tenths = count / (unit / 10);
+    let number = if tenths < 100 && !tenths.is_multiple_of(10) {
+        format!("{}.{}", tenths / 10, tenths % 10)
+    } else {
+        (tenths / 10).to_string()
+    };
+    format!("{}{} {}", number, letter, suffix)
+}
+
+type RendererParser = fn(&serde_json::Value, &str) -> Option<SearchResult>;
+
+/// Digits of "25 videos" / "1,024 videos".
+fn count_digits(text: &str) -> Option<String> {
+    let digits: String = text
+        .split_whitespace()
+        .next()?
+        .chars()
+        .filter(|c| c.is_ascii_digit())
+        .collect();
+    (!digits.is_empty()).then_some(digits)
+}
+
+fn playlist_result(
+    playlist_id: String,
+    title: String,
+    author: String,
+    channel_id: String,
+    first_video: String,
+    video_count: Option<String>,
+    base_trimmed: &str,
+) -> SearchResult {
+    let thumbnail = if first_video.is_empty() {
+        format!("{}/assets/images/hqdefault.jpg", base_trimmed)
+    } else {
+        format!("{}/thumbnail/{}", base_trimmed, first_video)
+    };
+    let channel_thumbnail = if channel_id.is_empty() {
+        thumbnail.clone()
+    } else {
+        format!("{}/channel_icon/{}", base_trimmed, channel_id)
+    };
+    SearchResult {
+        title: decode_label(&title),
+        author: decode_label(&author),
+        video_id: if first_video.is_empty() { None } else { Some(first_video) },
+        channel_id: if channel_id.is_empty() { None } else { Some(channel_id) },
+        playlist_id: Some(playlist_id),
+        thumbnail,
+        channel_thumbnail,
+        duration: None,
+        description: None,
+        views: None,
+        view_count: None,
+        short_views: None,
+        published: None,
+        subscriber_count: None,
+        video_count,
+    }
+}
+
+fn parse_playlist_renderer(pr: &serde_json::Value, base_trimmed: &str) -> Option<SearchResult> {
+    let playlist_id = pr.get("playlistId").and_then(|v| v.as_str())?.to_string();
+    let str_at = |pointer: &str| {
+        pr.pointer(pointer)
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string()
+    };
+    let byline = pr
+        .get("shortBylineText")
+        .or_else(|| pr.get("longBylineText"))
+        .unwrap_or(&serde_json::Value::Null);
+    let video_count = pr
+        .get("videoCount")
+        .and_then(|v| v.as_str())
+        .and_then(count_digits)
+        .or_else(|| count_digits(&simplify_text(pr.get("videoCountText").unwrap_or(&serde_json::Value::Null))));
+    Some(playlist_result(
+        playlist_id,
+        simplify_text(pr.get("title").unwrap_or(&serde_json::Value::Null)),
+        simplify_text(byline),
+        byline
+            .pointer("/runs/0/navigationEndpoint/browseEndpoint/browseId")
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string(),
+        str_at("/navigationEndpoint/watchEndpoint/videoId"),
+        video_count,
+        base_trimmed,
+    ))
+}
+
+/// Newer search responses describe playlists as `lockupViewModel`s instead of `playlistRenderer`s.
+fn parse_playlist_lockup(lockup: &serde_json::Value, base_trimmed: &str) -> Option<SearchResult> {
+    if lockup.get("contentType").and_then(|v| v.as_str()) != Some("LOCKUP_CONTENT_TYPE_PLAYLIST") {
+        return None;
+    }
+    let playlist_id = lockup.get("contentId").and_then(|v| v.as_str())?.to_string();
+    let str_at = |pointer: &str| {
+        lockup
+            .pointer(pointer)
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string()
+    };
+    let metadata = "/metadata/lockupMetadataViewModel";
+    let mut author = String::new();
+    let mut channel_id = String::new();
+    if let Some(rows) = lockup
+        .pointer(&format!("{}/metadata/contentMetadataViewModel/metadataRows", metadata))
+        .and_then(|r| r.as_array())
+    {
+        if let Some(part) = rows
+            .iter()
+            .filter_map(|row| row.pointer("/metadataParts/0/text"))
+            .next()
+        {
+            author = part.get("content").and_then(|v| v.as_str()).unwrap_or("").to_string();
+            channel_id = part
+                .pointer("/commandRuns/0/onTap/innertubeCommand/browseEndpoint/browseId")
+                .and_then(|v| v.as_str())
+                .unwrap_or("")
+                .to_string();
+        }
+    }
+    let mut badges = Vec::new();
+    find_renderers(lockup, "thumbnailBadgeViewModel", &mut badges);
+    let video_count = badges
+        .iter()
+        .filter_map(|b| b.get("text").and_then(|t| t.as_str()))
+        .find_map(count_digits);
+    Some(playlist_result(
+        playlist_id,
+        str_at(&format!("{}/title/content", metadata)),
+        author,
+        channel_id,
+        str_at("/rendererContext/commandContext/onTap/innertubeCommand/watchEndpoint/videoId"),
+        video_count,
+        base_trimmed,
+    ))
+}
+
+fn parse_channel_renderer(cr: &serde_json::Value, base_trimmed: &str) -> Option<SearchResult> {
+    let channel_id = cr.get("channelId").and_then(|v| v.as_str())?.to_string();
+    let title = simplify_text(cr.get("title").unwrap_or(&serde_json::Value::Null));
+    let description = simplify_text(cr.get("descriptionSnippet").unwrap_or(&serde_json::Value::Null));
+    let icon = format!("{}/channel_icon/{}", base_trimmed, channel_id);
+
+    Some(SearchResult {
+        author: decode_label(&title),
+        title: decode_label(&title),
+        video_id: None,
+        channel_id: Some(channel_id),
+        playlist_id: None,
+        thumbnail: icon.clone(),
+        channel_thumbnail: icon,
+        duration: None,
+        description: if !description.is_empty() { Some(decode_label(&description)) } else { None },
+        views: None,
+        view_count: None,
+        short_views: None,
+        published: None,
+        // InnerTube's own counts are localized text (and often just the @handle); filled from
+        // channels.list by `fill_channel_counts`
+        subscriber_count: None,
+        video_count: None,
     })
 }
 
+/// Adds `subscriber_count` / `video_count` to channel rows with one channels.list call (up to 50
+/// ids, 1 quota unit). Rows keep `None` when the Data API is unavailable or over quota.
+async fn fill_channel_counts(results: &mut [SearchResult], req: &HttpRequest, config: &crate::config::Config) {
+    let mut ids: Vec<&str> = Vec::new();
+    for id in results.iter().filter_map(|r| r.channel_id.as_deref()) {
+        if !ids.contains(&id) && ids.len() < 50 {
+            ids.push(id);
+        }
+    }
+    if ids.is_empty() || !config.has_api_key() {
+        return;
+    }
+    if crate::routes::quota::charge(req, config, None, crate::routes::quota::READ_COST).is_some() {
+        return;
+    }
+    let url = crate::endpoints::data_api(&format!(
+        "channels?part=statistics&id={}&key={}",
+        ids.join(","),
+        config.get_api_key_rotated()
+    ));
+    let body = match crate::http_client::get_json(&url).await {
+        Ok(body) => body,
+        Err(e) => {
+            crate::log::info!("Error fetching channel statistics: {}", e);
+            return;
+        }
+    };
+    if crate::routes::upstream::report("YouTube Data API", &body).is_some() {
+        return;
+    }
+    let Ok(list) = data_api::parse::<ApiList<data_api::Channel>>("channels.list", body) else {
+        return;
+    };
+    let stats: HashMap<String, data_api::ChannelStatistics> = list
+        .items
+        .into_iter()
+        .filter_map(|c| Some((c.id, c.statistics?)))
+        .collect();
+    for result in results.iter_mut() {
+        let Some(s) = result.channel_id.as_ref().and_then(|id| stats.get(id)) else {
+            continue;
+        };
+        if !s.hidden_subscriber_count {
+            result.subscriber_count = s.subscriber_count.map(|n| n.to_string());
+        }
+        result.video_count = s.video_count.map(|n| n.to_string());
+    }
+}
+
+/// Parental blocklist check for a Data API `videos` snippet.
+fn snippet_is_blocked(
+    snippet: &VideoSnippet,
+    title: &str,
+    author: &str,
+    extra: Option<&crate::routes::blocklist::BlocklistLists>,
+) -> bool {
+    let mut texts = vec![title, author, snippet.description.as_str()];
+    texts.extend(snippet.tags.iter().map(|t| t.as_str()));
+    crate::routes::blocklist::is_blocked(
+        extra,
+        snippet.channel_id.as_deref(),
+        &texts,
+        snippet.category_id.as_deref(),
+    )
+}
+
+/// Rows for a videos.list chart (trending, category videos), minus blocked ones.
+fn top_videos_from(
+    list: ApiList<Video>,
+    base: &str,
+    profile_blocks: Option<&crate::routes::blocklist::BlocklistLists>,
+) -> Vec<TopVideo> {
+    let base = base.trim_end_matches('/');
+    let mut top_videos = Vec::new();
+    for video in list.items {
+        let Some(snippet) = video.snippet.as_ref() else {
+            continue;
+        };
+        // With hl= the Data API puts the translated title under `localized`
+        let title = decode_label(snippet.display_title());
+        let author = snippet
+            .channel_title
+            .clone()
+            .unwrap_or_else(|| "Unknown Author".to_string());
+        if snippet_is_blocked(snippet, &title, &author, profile_blocks) {
+            continue;
+        }
+        let channel_id = snippet.channel_id.as_deref().unwrap_or(&video.id);
+        let duration = video
+            .content_details
+            .as_ref()
+            .and_then(|c| c.duration.as_deref())
+            .map(parse_iso_duration)
+            .unwrap_or_else(|| "0:00".to_string());
+
+        top_videos.push(TopVideo {
+            thumbnail: format!("{}/thumbnail/{}", base, video.id),
+            channel_thumbnail: format!("{}/channel_icon/{}", base, channel_id),
+            statistics: ItemStatistics::from_video(&video),
+            title,
+            author,
+            video_id: video.id,
+            duration,
+        });
+    }
+    top_videos
+}
+
 fn parse_iso_duration(iso: &str) -> String {
     let mut hours = 0;
     let mut minutes = 0;
@@ -160,12 +468,127 @@ pub struct TopVideo {
     pub thumbnail: String,
     pub channel_thumbnail: String,
     pub duration: String,
+    /// Only with `stats=true`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub statistics: Option<ItemStatistics>,
+}
+
+/// Counts for list rows (`stats=true`), for UIs that draw like bars or star ratings in lists.
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct ItemStatistics {
+    pub views: u64,
+    /// `None` when the uploader hides likes.
+    pub likes: Option<u64>,
+    /// ISO 8601.
+    pub published_at: Option<String>,
+    /// Whole days since `published_at`.
+    pub age_days: Option<i64>,
+    /// 1-5 stars from Return YouTube Dislike votes (`ratings=true` and `api.ryd.enabled`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rating: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rating_count: Option<u64>,
+}
+
+impl ItemStatistics {
+    /// From a videos.list item fetched with `part=snippet,statistics`.
+    fn from_video(video: &Video) -> Option<Self> {
+        let stats = video.statistics.as_ref()?;
+        let published_at = video.snippet.as_ref().and_then(|s| s.published_at.clone());
+        let age_days = published_at
+            .as_deref()
+            .and_then(|p| chrono::DateTime::parse_from_rfc3339(p).ok())
+            .map(|p| (chrono::Utc::now() - p.with_timezone(&chrono::Utc)).num_days().max(0));
+        Some(Self {
+            views: stats.view_count.unwrap_or(0),
+            likes: stats.like_count,
+            published_at,
+            age_days,
+            rating: None,
+            rating_count: None,
+        })
+    }
+}
+
+/// Whether list rows should carry `statistics` (`stats=true` or `stats=1`). GData feeds always
+/// had view counts, so `alt=jsonc` implies it.
+pub(crate) fn stats_requested(req: &HttpRequest) -> bool {
+    web::Query::<HashMap<String, String>>::from_query(req.query_string())
+        .ok()
+        .map(|q| {
+            q.get("stats").map(|v| v == "true" || v == "1").unwrap_or(false)
+                || q.get("alt").map(|a| a == "jsonc").unwrap_or(false)
+        })
+        .unwrap_or(false)
+}
+
+/// Fills `rating`/`rating_count` of rows that have statistics, when the client asked for ratings.
+pub(crate) async fn add_ratings(
+    rows: Vec<(String, &mut ItemStatistics)>,
+    req: &HttpRequest,
+    config: &crate::config::Config,
+) {
+    if rows.is_empty() || !crate::routes::ryd::ratings_requested(req) {
+        return;
+    }
+    let ids: Vec<String> = rows.iter().map(|(id, _)| id.clone()).collect();
+    let votes = crate::routes::ryd::votes_for(&ids, config).await;
+    for (id, stats) in rows {
+        if let Some(votes) = votes.get(&id) {
+            stats.rating = votes.star_rating();
+            stats.rating_count = Some(votes.rating_count());
+        }
+    }
+}
+
+/// Statistics for up to 50 videos with one videos.list call (1 quota unit). Videos the Data API
+/// doesn't return, or every one when it is unavailable or over quota, are left out.
+pub(crate) async fn video_statistics(
+    ids: &[&str],
+    req: &HttpRequest,
+    config: &crate::config::Config,
+) -> HashMap<String, ItemStatistics> {
+    let mut unique: Vec<&str> = Vec::new();
+    for id in ids {
+        if !unique.contains(id) && unique.len() < 50 {
+            unique.push(id);
+        }
+    }
+    if unique.is_empty() || !config.has_api_key() {
+        return HashMap::new();
+    }
+    if crate::routes::quota::charge(req, config, None, crate::routes::quota::READ_COST).is_some() {
+        return HashMap::new();
+    }
+    let url = crate::endpoints::data_api(&format!(
+        "videos?part=snippet,statistics&id={}&key={}",
+        unique.join(","),
+        config.get_api_key_rotated()
+    ));
+    let body = match crate::http_client::get_json(&url).await {
+        Ok(body) => body,
+        Err(e) => {
+            crate::log::info!("Error fetching video statistics: {}", e);
+            return HashMap::new();
+        }
+    };
+    if crate::routes::upstream::report("YouTube Data API", &body).is_some() {
+        return HashMap::new();
+    }
+    let Ok(list) = data_api::parse::<ApiList<Video>>("videos.list", body) else {
+        return HashMap::new();
+    };
+    list.items
+        .iter()
+        .filter_map(|v| Some((v.id.clone(), ItemStatistics::from_video(v)?)))
+        .collect()
 }
 
 #[derive(Serialize, Deserialize, ToSchema)]
 pub struct SearchResult {
     pub title: String,
     pub author: String,
+    /// For playlist results, the first video.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub video_id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -180,17 +603,29 @@ pub struct SearchResult {
     pub description: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub views: Option<String>,
+    /// `views` as a number.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub view_count: Option<u64>,
+    /// `view_count` abbreviated for result rows: "1.2M views", or "35K watching" when live.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub short_views: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub published: Option<String>,
+    /// Channel results only; absent when the channel hides it or the Data API is unavailable.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subscriber_count: Option<String>,
+    /// Channel and playlist results.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub video_count: Option<String>,
 }
 
-#[derive(Serialize, ToSchema)]
+#[derive(Serialize, Clone, ToSchema)]
 pub struct CategoryItem {
     pub id: String,
     pub title: String,
 }
 
-#[derive(Serialize, ToSchema)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct PlaylistVideo {
     pub title: String,
     pub author: String,
@@ -199,9 +634,15 @@ pub struct PlaylistVideo {
     pub channel_thumbnail: String,
     pub views: Option<String>,
     pub published_at: Option<String>,
+    /// 1-based index in the playlist (unchanged by `skip_unavailable`).
+    #[serde(default)]
+    pub position: u32,
+    /// Items in the whole playlist, as reported by YouTube.
+    #[serde(default)]
+    pub total_count: u32,
 }
 
-#[derive(Serialize, ToSchema)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct PlaylistInfo {
     pub title: String,
     pub description: String,
@@ -211,26 +652,157 @@ pub struct PlaylistInfo {
     pub video_count: i32,
 }
 
-#[derive(Serialize, ToSchema)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct PlaylistResponse {
     pub playlist_info: PlaylistInfo,
     pub videos: Vec<PlaylistVideo>,
+    /// Deleted/private entries left out because of `skip_unavailable`.
+    #[serde(default)]
+    pub skipped_unavailable: u32,
+    /// Seed used for `order=shuffle`; pass it back as `seed=` to page through the same order.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shuffle_seed: Option<u64>,
+}
+
+/// Reordering needs the whole playlist in hand, so it is capped.
+const MAX_REORDERED_ITEMS: u32 = 1000;
+
+#[derive(Clone, Copy, PartialEq)]
+enum PlaylistOrder {
+    Default,
+    Reverse,
+    Shuffle(u64),
+}
+
+impl PlaylistOrder {
+    fn from_query(order: Option<&String>, seed: Option<&String>) -> Option<Self> {
+        match order.map(|o| o.to_lowercase()).as_deref() {
+            None | Some("") | Some("default") => Some(PlaylistOrder::Default),
+            Some("reverse") => Some(PlaylistOrder::Reverse),
+            Some("shuffle") => {
+                let seed = seed
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or_else(|| uuid::Uuid::new_v4().as_u128() as u64);
+                Some(PlaylistOrder::Shuffle(seed))
+            }
+            Some(_) => None,
+        }
+    }
+
+    fn apply<T>(self, items: &mut [T]) {
+        match self {
+            PlaylistOrder::Default => {}
+            PlaylistOrder::Reverse => items.reverse(),
+            PlaylistOrder::Shuffle(seed) => {
+                // Fisher-Yates over splitmix64: same seed and playlist, same order
+                let mut state = seed;
+                for i in (1..items.len()).rev() {
+                    state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+                    let mut z = state;
+                    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+                    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+                    z ^= z >> 31;
+                    items.swap(i, (z % (i as u64 + 1)) as usize);
+                }
+            }
+        }
+    }
+}
+
+/// Deleted and private entries stay in playlistItems.list as placeholders: a fixed title and no
+/// `videoOwnerChannelId`.
+fn is_unavailable_item(snippet: &data_api::PlaylistItemSnippet) -> bool {
+    let title = snippet.title.trim().trim_start_matches('[').trim_end_matches(']');
+    matches!(title, "Deleted video" | "Private video") || snippet.video_owner_channel_id.is_none()
+}
+
+/// playlistItems.list page token for an arbitrary 0-based offset. Tokens are not documented but
+/// have been a stable protobuf for years: `{2: 0, 3: "PT:" + base64url({1: offset})}`.
+fn playlist_page_token(offset: u32) -> String {
+    use base64::{engine::general_purpose, Engine as _};
+
+    fn varint(mut n: u32, out: &mut Vec<u8>) {
+        while n >= 0x80 {
+            out.push((n as u8 & 0x7f) | 0x80);
+            n >>= 7;
+        }
+        out.push(n as u8);
+    }
+
+    let mut inner = vec![0x08];
+    varint(offset, &mut inner);
+    let pt = format!("PT:{}", general_purpose::URL_SAFE_NO_PAD.encode(inner));
+    let mut outer = vec![0x10, 0x00, 0x1a];
+    varint(pt.len() as u32, &mut outer);
+    outer.extend_from_slice(pt.as_bytes());
+    general_purpose::URL_SAFE_NO_PAD.encode(outer)
+}
+
+/// `hide_watched=true`: ids of videos the viewer has seen lately, from their Google history
+/// (`token=`, else the browser session) and the selected local profile's. `None` when not asked.
+async fn watched_ids(req: &HttpRequest, config: &crate::config::Config) -> Option<HashSet<String>> {
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).ok()?;
+    if !query.get("hide_watched").is_some_and(|v| v == "true" || v == "1") {
+        return None;
+    }
+    let mut ids = HashSet::new();
+    let token = query.get("token").cloned().or_else(|| {
+        req.app_data::<web::Data<TokenStore>>()
+            .and_then(|store| store.session_token(req))
+    });
+    if let (Some(token), Some(auth)) = (token, req.app_data::<web::Data<AuthConfig>>()) {
+        let watched = crate::routes::additional::recently_watched(&token, auth, config).await;
+        ids.extend(watched.iter().cloned());
+    }
+    if config.profiles.enabled {
+        if let Some(profile) = crate::routes::profiles::current_profile(req) {
+            let history = crate::routes::profiles::history(profile.id, config.profiles.history_limit);
+            ids.extend(history.into_iter().map(|item| item.video_id));
+        }
+    }
+    Some(ids)
+}
+
+/// Chart size to ask for: with `hide_watched` the full page, so enough are left after filtering.
+fn chart_size(count: i32, watched: &Option<HashSet<String>>) -> i32 {
+    if watched.is_some() {
+        50
+    } else {
+        count
+    }
+}
+
+/// Drops watched videos and trims back to `count`.
+fn without_watched(videos: &mut Vec<TopVideo>, watched: &Option<HashSet<String>>, count: i32) {
+    if let Some(watched) = watched {
+        videos.retain(|v| !watched.contains(&v.video_id));
+        videos.truncate(count.max(0) as usize);
+    }
 }
 
 #[utoipa::path(
     get,
     path = "/get_top_videos.php",
     params(
-        ("count" = Option<i32>, Query, description = "Number of videos to return (default: 50)")
+        ("count" = Option<i32>, Query, description = "Number of videos to return (default: 50)"),
+        ("hl" = Option<String>, Query, description = "Metadata language (default: api.hl)"),
+        ("gl" = Option<String>, Query, description = "Region (default: api.gl)"),
+        ("stats" = Option<bool>, Query, description = "Add views, likes and age to each video"),
+        ("ratings" = Option<bool>, Query, description = "With stats, add a 1-5 star rating from Return YouTube Dislike (api.ryd)"),
+        ("hide_watched" = Option<bool>, Query, description = "Leave out videos in the viewer's recent history (token, session or local profile)"),
+        ("token" = Option<String>, Query, description = "Refresh token whose history hide_watched uses"),
+        ("envelope" = Option<bool>, Query, description = "Return a ListResponse (items, next_page_token, total, warnings) instead of the legacy shape")
     ),
     responses(
         (status = 200, description = "List of top videos", body = [TopVideo]),
-        (status = 500, description = "Internal server error")
+        (status = 500, description = "Internal server error"),
+        (status = 429, description = "Daily API quota exceeded")
     )
 )]
 pub async fn get_top_videos(req: HttpRequest, data: web::Data<crate::AppState>) -> impl Responder {
     let config = &data.config;
     let base = base_url(&req, config);
+    let profile_blocks = crate::routes::profiles::blocklist_for(&req);
 
     let count: i32 = req
         .query_string()
@@ -247,81 +819,62 @@ pub async fn get_top_videos(req: HttpRequest, data: web::Data<crate::AppState>)
 
     let count = count.min(50).max(1);
 
+    if let Some(resp) = crate::routes::quota::charge(&req, config, None, crate::routes::quota::READ_COST) {
+        return resp;
+    }
     let apikey = config.get_api_key_rotated();
-
-    let client = Client::new();
-
-    let url = format!(
-        "https://www.googleapis.com/youtube/v3/videos?part=snippet,contentDetails&chart=mostPopular&maxResults={}&key={}",
-        count,
-        apikey
-    );
-
-    match client.get(&url).send().await {
-        Ok(response) => match response.json::<serde_json::Value>().await {
-            Ok(json_data) => {
-                let mut top_videos: Vec<TopVideo> = Vec::new();
-
-                if let Some(items) = json_data.get("items").and_then(|i| i.as_array()) {
-                    for video in items {
-                        if let (Some(video_info), Some(video_id)) = (
-                            video.get("snippet"),
-                            video.get("id").and_then(|id| id.as_str()),
-                        ) {
-                            let channel_id = video_info
-                                .get("channelId")
-                                .and_then(|c| c.as_str())
-                                .unwrap_or(video_id);
-                            let title = video_info
-                                .get("title")
-                                .and_then(|t| t.as_str())
-                                .unwrap_or("Unknown Title");
-                            let title = decode_label(title);
-
-                            let author = video_info
-                                .get("channelTitle")
-                                .and_then(|a| a.as_str())
-                                .unwrap_or("Unknown Author")
-                                .to_string();
-
-                            let thumbnail =
-                                format!("{}/thumbnail/{}", base.trim_end_matches('/'), video_id);
-
-                            let channel_thumbnail = format!(
-                                "{}/channel_icon/{}",
-                                base.trim_end_matches('/'),
-                                channel_id
-                            );
-
-                            let duration = video
-                                .get("contentDetails")
-                                .and_then(|c| c.get("duration"))
-                                .and_then(|d| d.as_str())
-                                .map(parse_iso_duration)
-                                .unwrap_or_else(|| "0:00".to_string());
-
-                            top_videos.push(TopVideo {
-                                title,
-                                author,
-                                video_id: video_id.to_string(),
-                                thumbnail,
-                                channel_thumbnail,
-                                duration,
-                            });
-                        }
-                    }
+    let watched = watched_ids(&req, config).await;
+
+    let locale = crate::routes::locale::Locale::from_request(&req, config);
+    // The chart call carries the statistics itself, so stats=true costs nothing extra
+    let parts = if stats_requested(&req) { "snippet,contentDetails,statistics" } else { "snippet,contentDetails" };
+    let url = crate::endpoints::data_api(&format!(
+        "videos?part={}&chart=mostPopular&maxResults={}&key={}{}",
+        parts,
+        chart_size(count, &watched),
+        apikey,
+        locale.data_api_params()
+    ));
+
+    match crate::http_client::get_json(&url).await {
+        Ok(json_data) => {
+            if let Some(err) = crate::routes::upstream::error_response("YouTube Data API", &json_data) {
+                return err;
+            }
+            let list: ApiList<Video> = match data_api::parse("videos.list", json_data) {
+                Ok(list) => list,
+                Err(e) => return data_api::mismatch_response(e),
+            };
+            let mut top_videos = top_videos_from(list, &base, profile_blocks.as_ref());
+            without_watched(&mut top_videos, &watched, count);
+            let rows = top_videos
+                .iter_mut()
+                .filter_map(|v| Some((v.video_id.clone(), v.statistics.as_mut()?)))
+                .collect();
+            add_ratings(rows, &req, config).await;
+
+            // Filtered for one viewer: not for shared caches
+            let per_viewer = watched.is_some() || profile_blocks.is_some();
+            let max_age = config.cache.trending_max_age;
+            if crate::routes::envelope::requested(&req) {
+                let list = ListResponse::new(top_videos);
+                if per_viewer {
+                    return crate::routes::http_cache::private_json(&req, &list);
                 }
-
-                HttpResponse::Ok().json(top_videos)
+                return crate::routes::http_cache::cached_json(&req, &list, max_age);
             }
-            Err(e) => {
-                crate::log::info!("Error parsing YouTube API response: {}", e);
-                HttpResponse::InternalServerError().json(serde_json::json!({
-                    "error": "Failed to parse YouTube API response"
-                }))
+            if per_viewer {
+                return crate::routes::http_cache::private_json(&req, &top_videos);
             }
-        },
-        Err(e) => {
+            crate::routes::http_cache::cached_json(&req, &top_videos, max_age)
+        }
+        Err(FetchError::Parse(e)) => {
+            crate::log::info!("Error parsing YouTube API response: {}", e);
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Failed to parse YouTube API response"
+            }))
+        }
+        Err(FetchError::Request(e)) => {
             crate::log::info!("Error calling YouTube API: {}", e);
             HttpResponse::InternalServerError().json(serde_json::json!({
                 "error": "Failed to call YouTube API"
@@ -336,7 +889,10 @@ pub async fn get_top_videos(req: HttpRequest, data: web::Data<crate::AppState>)
     params(
         ("query" = String, Query, description = "Search query"),
         ("count" = Option<i32>, Query, description = "Number of results to return (default: 50)"),
-        ("type" = Option<String>, Query, description = "Type of search results (video, channel, playlist) (default: video)")
+        ("type" = Option<String>, Query, description = "Type of search results (video, channel, playlist) (default: video)"),
+        ("hl" = Option<String>, Query, description = "Metadata language (default: api.hl)"),
+        ("gl" = Option<String>, Query, description = "Region (default: api.gl)"),
+        ("envelope" = Option<bool>, Query, description = "Return a ListResponse (items, next_page_token, total, warnings) instead of the legacy shape")
     ),
     responses(
         (status = 200, description = "List of search results", body = [SearchResult]),
@@ -350,6 +906,7 @@ pub async fn get_search_videos(
 ) -> impl Responder {
     let config = &data.config;
     let base = base_url(&req, config);
+    let profile_blocks = crate::routes::profiles::blocklist_for(&req);
     let base_trimmed = base.trim_end_matches('/');
 
     let mut query_params: HashMap<String, String> = HashMap::new();
@@ -397,34 +954,43 @@ pub async fn get_search_videos(
         Some(key) => key,
         None => {
             return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": "Missing innertube_key in config.yml"
+                "error": Msg::MissingInnertubeKey.text()
             }));
         }
     };
 
-    let client = Client::new();
+    let client = crate::http_client::shared();
 
-    let payload = serde_json::json!({
+    let locale = crate::routes::locale::Locale::from_request(&req, config);
+    let mut payload = serde_json::json!({
         "context": {
             "client": {
                 "clientName": "WEB",
                 "clientVersion": "2.20250101",
-                "hl": "ru",
-                "gl": "RU"
+                "hl": locale.hl,
+                "gl": locale.gl
             }
         },
         "query": query
     });
+    if search_type == "channel" {
+        // Search filter "Type: Channel"
+        payload["params"] = serde_json::json!("EgIQAg==");
+    } else if search_type == "playlist" {
+        // Search filter "Type: Playlist"
+        payload["params"] = serde_json::json!("EgIQAw==");
+    }
 
-    let url = format!(
-        "https://www.youtube.com/youtubei/v1/search?key={}",
+    let url = crate::endpoints::innertube(&format!(
+        "search?key={}",
         innertube_key
-    );
+    ));
 
+    let accept_language = locale.accept_language();
     let headers = [
         ("Content-Type", "application/json"),
         ("User-Agent", "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/117.0.0.0 Safari/537.36"),
-        ("Accept-Language", "ru-RU,ru;q=0.9,en-US;q=0.8,en;q=0.7"),
+        ("Accept-Language", accept_language.as_str()),
         ("X-YouTube-Client-Name", "1"),
         ("X-YouTube-Client-Version", "2.20250101"),
     ];
@@ -438,16 +1004,57 @@ pub async fn get_search_videos(
     {
         Ok(response) => match response.json::<serde_json::Value>().await {
             Ok(json_data) => {
+                if let Some(err) = crate::routes::upstream::error_response("InnerTube search", &json_data) {
+                    return err;
+                }
                 let mut search_results: Vec<SearchResult> = Vec::new();
-                let mut video_renderers = Vec::new();
-                find_video_renderers(&json_data, &mut video_renderers);
-                for vr in video_renderers.iter().take(count) {
-                    if let Some(result) = parse_video_renderer(vr, base_trimmed) {
-                        search_results.push(result);
+                let parsers: &[(&str, RendererParser)] = match search_type {
+                    "channel" => &[("channelRenderer", parse_channel_renderer)],
+                    "playlist" => &[
+                        ("playlistRenderer", parse_playlist_renderer),
+                        ("lockupViewModel", parse_playlist_lockup),
+                    ],
+                    _ => &[("videoRenderer", parse_video_renderer)],
+                };
+                let mut parsed = Vec::new();
+                for (key, parse) in parsers {
+                    let mut renderers = Vec::new();
+                    find_renderers(&json_data, key, &mut renderers);
+                    parsed.extend(renderers.iter().filter_map(|r| parse(r, base_trimmed)));
+                }
+                for result in parsed.into_iter().take(count) {
+                    let texts = [
+                        result.title.as_str(),
+                        result.author.as_str(),
+                        result.description.as_deref().unwrap_or(""),
+                    ];
+                    if crate::routes::blocklist::is_blocked(
+                        profile_blocks.as_ref(),
+                        result.channel_id.as_deref(),
+                        &texts,
+                        None,
+                    ) {
+                        continue;
                     }
+                    search_results.push(result);
+                }
+                if search_type == "channel" {
+                    fill_channel_counts(&mut search_results, &req, config).await;
                 }
 
-                HttpResponse::Ok().json(search_results)
+                // Filtered by the viewer's profile blocklist: not for shared caches
+                let per_viewer = profile_blocks.is_some();
+                if crate::routes::envelope::requested(&req) {
+                    let list = ListResponse::new(search_results);
+                    if per_viewer {
+                        return crate::routes::http_cache::private_json(&req, &list);
+                    }
+                    return crate::routes::http_cache::cached_json(&req, &list, config.cache.search_max_age);
+                }
+                if per_viewer {
+                    return crate::routes::http_cache::private_json(&req, &search_results);
+                }
+                crate::routes::http_cache::cached_json(&req, &search_results, config.cache.search_max_age)
             }
             Err(e) => {
                 crate::log::info!("Error parsing InnerTube response: {}", e);
@@ -465,6 +1072,21 @@ pub async fn get_search_videos(
     }
 }
 
+/// Strips the JSONP / XSSI wrapper Google puts around suggestion responses.
+fn unwrap_suggestions_payload(text: &str) -> String {
+    let mut data = text.trim().to_string();
+    if data.starts_with("window.google.ac.h(") {
+        data = data.trim_start_matches("window.google.ac.h(").to_string();
+        if data.ends_with(')') {
+            data.pop();
+        }
+    }
+    if data.starts_with(")]}'") {
+        data = data.trim_start_matches(")]}'").to_string();
+    }
+    data
+}
+
 #[utoipa::path(
     get,
     path = "/get_search_suggestions.php",
@@ -498,30 +1120,22 @@ pub async fn get_search_suggestions(
         }
     };
 
-    let client = Client::builder()
+    let client = crate::http_client::builder()
         .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/91.0.4472.124 Safari/537.36")
         .build()
         .unwrap();
 
     let encoded_query = urlencoding::encode(query);
     let url = format!(
-        "https://clients1.google.com/complete/search?client=youtube&hl=en&ds=yt&q={}",
+        "{}?client=youtube&hl=en&ds=yt&q={}",
+        crate::endpoints::suggest(),
         encoded_query
     );
 
     match client.get(&url).send().await {
         Ok(response) => match response.text().await {
             Ok(text) => {
-                let mut data = text.clone();
-                if data.starts_with("window.google.ac.h(") {
-                    data = data.trim_start_matches("window.google.ac.h(").to_string();
-                    if data.ends_with(')') {
-                        data.pop();
-                    }
-                }
-                if data.starts_with(")]}'") {
-                    data = data.trim_start_matches(")]}'").to_string();
-                }
+                let data = unwrap_suggestions_payload(&text);
 
                 match serde_json::from_str::<serde_json::Value>(&data) {
                     Ok(json_data) => {
@@ -560,19 +1174,118 @@ pub async fn get_search_suggestions(
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/complete/search",
+    params(
+        ("q" = String, Query, description = "Partial query"),
+        ("client" = Option<String>, Query, description = "youtube (default) answers like Google's JSONP; firefox/chrome get a plain [query, [suggestions]] array"),
+        ("hl" = Option<String>, Query, description = "Suggestion language (default: en)"),
+        ("callback" = Option<String>, Query, description = "JSONP callback name")
+    ),
+    responses(
+        (status = 200, description = "Suggestions in Google's complete/search format"),
+        (status = 502, description = "Suggestions upstream failed")
+    )
+)]
+pub async fn complete_search(query: web::Query<HashMap<String, String>>) -> impl Responder {
+    let q = query.get("q").map(|s| s.as_str()).unwrap_or("");
+    let client_name = query.get("client").map(|s| s.as_str()).unwrap_or("youtube");
+    let hl = query
+        .get("hl")
+        .map(|s| s.as_str())
+        .filter(|s| !s.is_empty())
+        .unwrap_or("en");
+    let callback = query
+        .get("callback")
+        .or_else(|| query.get("jsonp"))
+        .filter(|c| {
+            !c.is_empty()
+                && c.chars()
+                    .all(|ch| ch.is_ascii_alphanumeric() || ch == '_' || ch == '.' || ch == '$')
+        });
+
+    let client = crate::http_client::builder()
+        .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/91.0.4472.124 Safari/537.36")
+        .build()
+        .unwrap();
+    let url = format!(
+        "{}?client=youtube&hl={}&ds=yt&q={}",
+        crate::endpoints::suggest(),
+        urlencoding::encode(hl),
+        urlencoding::encode(q)
+    );
+
+    let upstream = match client.get(&url).send().await {
+        Ok(response) => response.text().await.map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+    .and_then(|text| {
+        serde_json::from_str::<serde_json::Value>(&unwrap_suggestions_payload(&text))
+            .map_err(|e| e.to_string())
+    });
+    let json_data = match upstream {
+        Ok(v) => v,
+        Err(e) => {
+            crate::log::info!("Error calling complete/search upstream: {}", e);
+            return HttpResponse::BadGateway().json(serde_json::json!({
+                "error": "Failed to call suggestions API"
+            }));
+        }
+    };
+
+    // client=youtube keeps Google's [query, [[text, 0, [..]], ...], {..}] shape untouched;
+    // the browser clients only ever had [query, [text, ...]].
+    let (payload, default_wrapper) = if client_name == "youtube" {
+        (json_data, Some("window.google.ac.h"))
+    } else {
+        let suggestions: Vec<serde_json::Value> = json_data
+            .get(1)
+            .and_then(|v| v.as_array())
+            .map(|arr| {
+                arr.iter()
+                    .filter_map(|s| s.get(0).or(Some(s)).and_then(|t| t.as_str()))
+                    .map(|t| serde_json::Value::String(t.to_string()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        (serde_json::json!([q, suggestions]), None)
+    };
+
+    match callback.map(|c| c.as_str()).or(default_wrapper) {
+        Some(wrapper) => HttpResponse::Ok()
+            .content_type("text/javascript; charset=utf-8")
+            .body(format!("{}({})", wrapper, payload)),
+        None => HttpResponse::Ok()
+            .content_type("application/json; charset=utf-8")
+            .body(payload.to_string()),
+    }
+}
+
+/// Category lists by `region|hl`; they change a few times a year at most.
+type CategoryCache = Mutex<LruCache<String, (Instant, Vec<CategoryItem>)>>;
+
+fn category_cache() -> &'static CategoryCache {
+    static CACHE: OnceLock<CategoryCache> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(LruCache::new(NonZeroUsize::new(64).unwrap())))
+}
+
 #[utoipa::path(
     get,
     path = "/get-categories.php",
     params(
-        ("region" = Option<String>, Query, description = "Region code (default: US)")
+        ("region" = Option<String>, Query, description = "Region code (default: gl, then api.gl)"),
+        ("hl" = Option<String>, Query, description = "Language of the category titles (default: api.hl)")
     ),
     responses(
-        (status = 200, description = "List of categories", body = [CategoryItem]),
-        (status = 500, description = "Internal server error")
+        (status = 200, description = "Assignable categories, cached per region and hl (cache.categories_max_age)", body = [CategoryItem]),
+        (status = 500, description = "Internal server error"),
+        (status = 429, description = "Daily API quota exceeded")
     )
 )]
 pub async fn get_categories(req: HttpRequest, data: web::Data<crate::AppState>) -> impl Responder {
     let config = &data.config;
+    let locale = crate::routes::locale::Locale::from_request(&req, config);
     let region = req
         .query_string()
         .split('&')
@@ -584,48 +1297,64 @@ pub async fn get_categories(req: HttpRequest, data: web::Data<crate::AppState>)
                 None
             }
         })
-        .unwrap_or_else(|| "US".to_string());
+        .unwrap_or_else(|| locale.gl.clone());
+
+    let max_age = config.cache.categories_max_age;
+    let cache_key = format!("{}|{}", region.to_uppercase(), locale.hl);
+    let cached = category_cache().lock().ok().and_then(|mut cache| {
+        cache
+            .get(&cache_key)
+            .filter(|(at, _)| at.elapsed() < Duration::from_secs(max_age as u64))
+            .map(|(_, items)| items.clone())
+    });
+    if let Some(categories) = cached {
+        return crate::routes::http_cache::cached_json(&req, &categories, max_age);
+    }
 
+    if let Some(resp) = crate::routes::quota::charge(&req, config, None, crate::routes::quota::READ_COST) {
+        return resp;
+    }
     let apikey = config.get_api_key_rotated();
-    let url = format!(
-        "https://www.googleapis.com/youtube/v3/videoCategories?part=snippet&regionCode={}&key={}",
-        region, apikey
-    );
-
-    let client = Client::new();
-    match client.get(&url).send().await {
-        Ok(resp) => match resp.json::<serde_json::Value>().await {
-            Ok(json_data) => {
-                let mut categories = Vec::new();
-                if let Some(items) = json_data.get("items").and_then(|i| i.as_array()) {
-                    for item in items {
-                        if let (Some(id), Some(snippet)) =
-                            (item.get("id").and_then(|i| i.as_str()), item.get("snippet"))
-                        {
-                            let title = snippet
-                                .get("title")
-                                .and_then(|t| t.as_str())
-                                .unwrap_or("");
-                            let title = decode_label(title);
-
-                            categories.push(CategoryItem {
-                                id: id.to_string(),
-                                title,
-                            });
-                        }
-                    }
-                }
+    let url = crate::endpoints::data_api(&format!(
+        "videoCategories?part=snippet&regionCode={}&hl={}&key={}",
+        region,
+        urlencoding::encode(&locale.hl),
+        apikey
+    ));
 
-                HttpResponse::Ok().json(categories)
+    match crate::http_client::get_json(&url).await {
+        Ok(json_data) => {
+            if let Some(err) = crate::routes::upstream::error_response("YouTube Data API", &json_data) {
+                return err;
             }
-            Err(e) => {
-                crate::log::info!("Error parsing categories response: {}", e);
-                HttpResponse::InternalServerError().json(serde_json::json!({
-                    "error": "Failed to parse categories response"
-                }))
+            let list: ApiList<VideoCategory> = match data_api::parse("videoCategories.list", json_data) {
+                Ok(list) => list,
+                Err(e) => return data_api::mismatch_response(e),
+            };
+            // non-assignable categories (e.g. "Movies", "Trailers") have no mostPopular chart,
+            // so /get-categories_videos.php would fail for them
+            let categories: Vec<CategoryItem> = list
+                .items
+                .into_iter()
+                .filter(|item| item.snippet.assignable)
+                .map(|item| CategoryItem {
+                    title: decode_label(&item.snippet.title),
+                    id: item.id,
+                })
+                .collect();
+
+            if let Ok(mut cache) = category_cache().lock() {
+                cache.put(cache_key, (Instant::now(), categories.clone()));
             }
-        },
-        Err(e) => {
+            crate::routes::http_cache::cached_json(&req, &categories, max_age)
+        }
+        Err(FetchError::Parse(e)) => {
+            crate::log::info!("Error parsing categories response: {}", e);
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Failed to parse categories response"
+            }))
+        }
+        Err(FetchError::Request(e)) => {
             crate::log::info!("Error calling categories API: {}", e);
             HttpResponse::InternalServerError().json(serde_json::json!({
                 "error": "Failed to call categories API"
@@ -639,11 +1368,18 @@ pub async fn get_categories(req: HttpRequest, data: web::Data<crate::AppState>)
     path = "/get-categories_videos.php",
     params(
         ("count" = Option<i32>, Query, description = "Number of videos to return (default: 50)"),
-        ("categoryId" = Option<String>, Query, description = "YouTube category ID")
+        ("categoryId" = Option<String>, Query, description = "YouTube category ID"),
+        ("hl" = Option<String>, Query, description = "Metadata language (default: api.hl)"),
+        ("gl" = Option<String>, Query, description = "Region (default: api.gl)"),
+        ("stats" = Option<bool>, Query, description = "Add views, likes and age to each video"),
+        ("ratings" = Option<bool>, Query, description = "With stats, add a 1-5 star rating from Return YouTube Dislike (api.ryd)"),
+        ("hide_watched" = Option<bool>, Query, description = "Leave out videos in the viewer's recent history (token, session or local profile)"),
+        ("token" = Option<String>, Query, description = "Refresh token whose history hide_watched uses")
     ),
     responses(
         (status = 200, description = "Videos from a category", body = [TopVideo]),
-        (status = 500, description = "Internal server error")
+        (status = 500, description = "Internal server error"),
+        (status = 429, description = "Daily API quota exceeded")
     )
 )]
 pub async fn get_categories_videos(
@@ -652,6 +1388,7 @@ pub async fn get_categories_videos(
 ) -> impl Responder {
     let config = &data.config;
     let base = base_url(&req, config);
+    let profile_blocks = crate::routes::profiles::blocklist_for(&req);
     let mut query_params: HashMap<String, String> = HashMap::new();
     for pair in req.query_string().split('&') {
         let mut parts = pair.split('=');
@@ -666,88 +1403,55 @@ pub async fn get_categories_videos(
         .unwrap_or(config.video.default_count as i32);
 
     let category_id = query_params.get("categoryId").cloned();
+    if let Some(resp) = crate::routes::quota::charge(&req, config, None, crate::routes::quota::READ_COST) {
+        return resp;
+    }
     let apikey = config.get_api_key_rotated();
-
-    let mut url = format!(
-        "https://www.googleapis.com/youtube/v3/videos?part=snippet,contentDetails&chart=mostPopular&maxResults={}&key={}",
-        count,
-        apikey
-    );
+    let watched = watched_ids(&req, config).await;
+
+    let locale = crate::routes::locale::Locale::from_request(&req, config);
+    let parts = if stats_requested(&req) { "snippet,contentDetails,statistics" } else { "snippet,contentDetails" };
+    let mut url = crate::endpoints::data_api(&format!(
+        "videos?part={}&chart=mostPopular&maxResults={}&key={}{}",
+        parts,
+        chart_size(count, &watched),
+        apikey,
+        locale.data_api_params()
+    ));
 
     if let Some(cat) = category_id {
         url.push_str(&format!("&videoCategoryId={}", cat));
     }
 
-    let client = Client::new();
-    match client.get(&url).send().await {
-        Ok(response) => match response.json::<serde_json::Value>().await {
-            Ok(json_data) => {
-                let mut top_videos: Vec<TopVideo> = Vec::new();
-
-                if let Some(items) = json_data.get("items").and_then(|i| i.as_array()) {
-                    for video in items {
-                        if let (Some(video_info), Some(video_id)) = (
-                            video.get("snippet"),
-                            video.get("id").and_then(|id| id.as_str()),
-                        ) {
-                            let title = video_info
-                                .get("title")
-                                .and_then(|t| t.as_str())
-                                .unwrap_or("Unknown Title");
-                            let title = decode_label(title);
-
-                            let author = video_info
-                                .get("channelTitle")
-                                .and_then(|a| a.as_str())
-                                .unwrap_or("Unknown Author")
-                                .to_string();
-
-                            let thumbnail =
-                                format!("{}/thumbnail/{}", base.trim_end_matches('/'), video_id);
-
-                            let channel_thumbnail = video_info
-                                .get("channelId")
-                                .and_then(|c| c.as_str())
-                                .map(|c| {
-                                    format!("{}/channel_icon/{}", base.trim_end_matches('/'), c)
-                                })
-                                .unwrap_or_else(|| {
-                                    format!(
-                                        "{}/channel_icon/{}",
-                                        base.trim_end_matches('/'),
-                                        video_id
-                                    )
-                                });
-
-                            let duration = video
-                                .get("contentDetails")
-                                .and_then(|c| c.get("duration"))
-                                .and_then(|d| d.as_str())
-                                .map(parse_iso_duration)
-                                .unwrap_or_else(|| "0:00".to_string());
-
-                            top_videos.push(TopVideo {
-                                title,
-                                author,
-                                video_id: video_id.to_string(),
-                                thumbnail,
-                                channel_thumbnail,
-                                duration,
-                            });
-                        }
-                    }
-                }
-
-                HttpResponse::Ok().json(top_videos)
+    match crate::http_client::get_json(&url).await {
+        Ok(json_data) => {
+            if let Some(err) = crate::routes::upstream::error_response("YouTube Data API", &json_data) {
+                return err;
             }
-            Err(e) => {
-                crate::log::info!("Error parsing category videos response: {}", e);
-                HttpResponse::InternalServerError().json(serde_json::json!({
-                    "error": "Failed to parse response"
-                }))
+            let list: ApiList<Video> = match data_api::parse("videos.list", json_data) {
+                Ok(list) => list,
+                Err(e) => return data_api::mismatch_response(e),
+            };
+            let mut top_videos = top_videos_from(list, &base, profile_blocks.as_ref());
+            without_watched(&mut top_videos, &watched, count);
+            let rows = top_videos
+                .iter_mut()
+                .filter_map(|v| Some((v.video_id.clone(), v.statistics.as_mut()?)))
+                .collect();
+            add_ratings(rows, &req, config).await;
+
+            if watched.is_some() || profile_blocks.is_some() {
+                return crate::routes::http_cache::private_json(&req, &top_videos);
             }
-        },
-        Err(e) => {
+            HttpResponse::Ok().json(top_videos)
+        }
+        Err(FetchError::Parse(e)) => {
+            crate::log::info!("Error parsing category videos response: {}", e);
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": Msg::FailedToParseResponse.text()
+            }))
+        }
+        Err(FetchError::Request(e)) => {
             crate::log::info!("Error calling category videos API: {}", e);
             HttpResponse::InternalServerError().json(serde_json::json!({
                 "error": "Failed to call YouTube API"
@@ -774,11 +1478,18 @@ pub async fn playlist_root() -> impl Responder {
     path = "/playlist/{playlist_id}",
     params(
         ("playlist_id" = String, Path, description = "YouTube playlist ID"),
-        ("count" = Option<i32>, Query, description = "Number of items to return (default: 50)")
+        ("count" = Option<i32>, Query, description = "Number of items to return (default: 50)"),
+        ("start_index" = Option<u32>, Query, description = "1-based playlist position to start from (default: 1)"),
+        ("skip_unavailable" = Option<bool>, Query, description = "Leave out deleted and private videos (default: true)"),
+        ("order" = Option<String>, Query, description = "default, reverse or shuffle; start_index and count apply to the reordered list"),
+        ("seed" = Option<u64>, Query, description = "Seed for order=shuffle (default: random, returned as shuffle_seed)"),
+        ("fields" = Option<String>, Query, description = "Comma-separated whitelist of response fields (e.g. playlist_info.title,videos.video_id)"),
+        ("envelope" = Option<bool>, Query, description = "Return a ListResponse instead; next_page_token is the next start_index")
     ),
     responses(
         (status = 200, description = "Playlist metadata and videos", body = PlaylistResponse),
-        (status = 400, description = "Playlist ID missing"),
+        (status = 400, description = "Playlist ID missing or invalid order"),
+        (status = 429, description = "Daily API quota exceeded"),
         (status = 500, description = "Internal server error")
     )
 )]
@@ -807,28 +1518,46 @@ pub async fn get_playlist_videos(
         .get("count")
         .and_then(|c| c.parse().ok())
         .unwrap_or(config.video.default_count as i32);
+    let skip_unavailable = query_params
+        .get("skip_unavailable")
+        .map(|v| v.to_lowercase() != "false" && v != "0")
+        .unwrap_or(true);
+    // 0-based offset of the first item to return
+    let offset = query_params
+        .get("start_index")
+        .and_then(|v| v.parse::<u32>().ok())
+        .unwrap_or(1)
+        .max(1)
+        - 1;
+    let Some(order) = PlaylistOrder::from_query(query_params.get("order"), query_params.get("seed")) else {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": "order must be default, reverse or shuffle"
+        }));
+    };
+    let reorder = order != PlaylistOrder::Default;
 
+    // playlists.list + channels.list + one playlistItems.list per 50 videos
+    let pages = (count.max(1) as u32).div_ceil(50);
+    let units = 2 * crate::routes::quota::READ_COST + pages * crate::routes::quota::READ_COST;
+    if let Some(resp) = crate::routes::quota::charge(&req, config, None, units) {
+        return resp;
+    }
     let apikey = config.get_api_key_rotated();
-    let client = Client::new();
 
-    let playlist_url = format!(
-        "https://www.googleapis.com/youtube/v3/playlists?part=snippet,contentDetails&id={}&key={}",
+    let playlist_url = crate::endpoints::data_api(&format!(
+        "playlists?part=snippet,contentDetails&id={}&key={}",
         playlist_id, apikey
-    );
+    ));
 
-    let playlist_resp = match client.get(&playlist_url).send().await {
-        Ok(r) => r,
-        Err(e) => {
+    let playlist_data = match crate::http_client::get_json(&playlist_url).await {
+        Ok(d) => d,
+        Err(FetchError::Request(e)) => {
             crate::log::info!("Error fetching playlist info: {}", e);
             return HttpResponse::InternalServerError().json(serde_json::json!({
                 "error": "Failed to fetch playlist"
             }));
         }
-    };
-
-    let playlist_data: serde_json::Value = match playlist_resp.json().await {
-        Ok(d) => d,
-        Err(e) => {
+        Err(FetchError::Parse(e)) => {
             crate::log::info!("Error parsing playlist info: {}", e);
             return HttpResponse::InternalServerError().json(serde_json::json!({
                 "error": "Failed to parse playlist"
@@ -836,168 +1565,177 @@ pub async fn get_playlist_videos(
         }
     };
 
-    let playlist_info = match playlist_data
-        .get("items")
-        .and_then(|i| i.as_array())
-        .and_then(|arr| arr.get(0))
-    {
-        Some(info) => info,
-        None => {
-            return HttpResponse::BadRequest().json(serde_json::json!({
-                "error": "Playlist not found"
-            }));
-        }
+    if let Some(err) = crate::routes::upstream::error_response("YouTube Data API", &playlist_data) {
+        return err;
+    }
+
+    let playlists: ApiList<Playlist> = match data_api::parse("playlists.list", playlist_data) {
+        Ok(list) => list,
+        Err(e) => return data_api::mismatch_response(e),
+    };
+    let Some(playlist_info) = playlists.items.into_iter().next() else {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": Msg::PlaylistNotFound.text()
+        }));
     };
+    let playlist_snippet = playlist_info.snippet.as_ref();
 
-    let channel_id = playlist_info
-        .get("snippet")
-        .and_then(|s| s.get("channelId"))
-        .and_then(|c| c.as_str())
+    let channel_id = playlist_snippet
+        .and_then(|s| s.channel_id.as_deref())
         .unwrap_or("");
 
-    let channel_resp = client
-        .get(format!(
-            "https://www.googleapis.com/youtube/v3/channels?part=snippet,statistics&id={}&key={}",
-            channel_id, apikey
-        ))
-        .send()
-        .await;
-
-    let channel_data: serde_json::Value = match channel_resp {
-        Ok(r) => match r.json().await {
-            Ok(d) => d,
-            Err(_) => serde_json::json!({}),
-        },
-        Err(_) => serde_json::json!({}),
+    let channel_url = crate::endpoints::data_api(&format!(
+        "channels?part=snippet,statistics&id={}&key={}",
+        channel_id, apikey
+    ));
+    // Only decorates the rows (author, icon); the playlist is still served without it
+    let channel_info = match crate::http_client::get_json(&channel_url).await {
+        Ok(body) if crate::routes::upstream::report("YouTube Data API", &body).is_none() => {
+            data_api::parse::<ApiList<data_api::Channel>>("channels.list", body)
+                .ok()
+                .and_then(|list| list.items.into_iter().next())
+        }
+        _ => None,
+    };
+    let channel_snippet = channel_info.as_ref().and_then(|c| c.snippet.as_ref());
+    let channel_icon = channel_snippet
+        .and_then(|s| s.thumbnails.high_url())
+        .map(|u| crate::routes::video::image_proxy_url(&data.config, base.trim_end_matches('/'), u));
+
+    let total_count = playlist_info
+        .content_details
+        .as_ref()
+        .and_then(|c| c.item_count)
+        .unwrap_or(0) as u32;
+
+    // Reordering reads the whole playlist; charge for the pages beyond the ones paid above
+    let limit = if reorder {
+        let all_pages = total_count.min(MAX_REORDERED_ITEMS).div_ceil(50);
+        if all_pages > pages {
+            let extra = (all_pages - pages) * crate::routes::quota::READ_COST;
+            if let Some(resp) = crate::routes::quota::charge(&req, config, None, extra) {
+                return resp;
+            }
+        }
+        MAX_REORDERED_ITEMS as i32
+    } else {
+        count
     };
-
-    let channel_info = channel_data
-        .get("items")
-        .and_then(|i| i.as_array())
-        .and_then(|arr| arr.get(0));
 
     let mut videos: Vec<PlaylistVideo> = Vec::new();
-    let mut next_page_token: Option<String> = None;
+    // Jump straight to start_index; if YouTube rejects the synthesized token, page from the start
+    // and drop everything before the offset instead
+    let mut next_page_token = (offset > 0 && !reorder).then(|| playlist_page_token(offset));
+    let mut synthesized_token = next_page_token.is_some();
+    let mut seen = if synthesized_token { offset } else { 0 };
     let mut total = 0;
+    let mut skipped_unavailable = 0;
 
-    while total < count {
-        let mut playlist_items_url = format!(
-            "https://www.googleapis.com/youtube/v3/playlistItems?part=snippet,contentDetails&playlistId={}&maxResults=50&key={}",
+    while total < limit {
+        let mut playlist_items_url = crate::endpoints::data_api(&format!(
+            "playlistItems?part=snippet,contentDetails&playlistId={}&maxResults=50&key={}",
             playlist_id, apikey
-        );
+        ));
         if let Some(token) = &next_page_token {
             playlist_items_url.push_str(&format!("&pageToken={}", token));
         }
 
-        let items_resp = match client.get(&playlist_items_url).send().await {
-            Ok(r) => r,
-            Err(e) => {
+        let items_data = match crate::http_client::get_json(&playlist_items_url).await {
+            Ok(d) => d,
+            Err(FetchError::Request(e)) => {
                 crate::log::info!("Error fetching playlist items: {}", e);
                 break;
             }
-        };
-
-        let items_data: serde_json::Value = match items_resp.json().await {
-            Ok(d) => d,
-            Err(e) => {
+            Err(FetchError::Parse(e)) => {
                 crate::log::info!("Error parsing playlist items: {}", e);
                 break;
             }
         };
-
-        if let Some(items) = items_data.get("items").and_then(|i| i.as_array()) {
-            for item in items {
-                if total >= count {
-                    break;
-                }
-
-                if let (Some(snippet), Some(content_details)) =
-                    (item.get("snippet"), item.get("contentDetails"))
-                {
-                    if let Some(video_id) = content_details.get("videoId").and_then(|v| v.as_str())
-                    {
-                        let title = snippet
-                            .get("title")
-                            .and_then(|t| t.as_str())
-                            .unwrap_or("");
-                        let title = decode_label(title);
-
-                        let author = channel_info
-                            .and_then(|c| c.get("snippet"))
-                            .and_then(|s| s.get("title"))
-                            .and_then(|t| t.as_str())
-                            .unwrap_or_else(|| {
-                                snippet
-                                    .get("channelTitle")
-                                    .and_then(|t| t.as_str())
-                                    .unwrap_or("")
-                            })
-                            .to_string();
-
-                        let thumbnail =
-                            format!("{}/thumbnail/{}", base.trim_end_matches('/'), video_id);
-
-                        let channel_thumbnail = channel_info
-                            .and_then(|c| c.get("snippet"))
-                            .and_then(|s| s.get("thumbnails"))
-                            .and_then(|t| t.get("high"))
-                            .and_then(|h| h.get("url"))
-                            .and_then(|u| u.as_str())
-                            .map(|u| u.to_string())
-                            .unwrap_or_else(|| {
-                                format!(
-                                    "{}/channel_icon/{}",
-                                    base.trim_end_matches('/'),
-                                    channel_id
-                                )
-                            });
-
-                        videos.push(PlaylistVideo {
-                            title,
-                            author,
-                            video_id: video_id.to_string(),
-                            thumbnail,
-                            channel_thumbnail,
-                            views: None,
-                            published_at: snippet
-                                .get("publishedAt")
-                                .and_then(|p| p.as_str())
-                                .map(|s| s.to_string()),
-                        });
-                        total += 1;
-                    }
-                }
+        if crate::routes::upstream::report("YouTube Data API", &items_data).is_some() {
+            if synthesized_token {
+                crate::log::info!("Playlist {}: start_index token rejected, paging from the start", playlist_id);
+                synthesized_token = false;
+                next_page_token = None;
+                seen = 0;
+                continue;
             }
+            break;
         }
+        synthesized_token = false;
+        let page: ApiList<PlaylistItem> = match data_api::parse("playlistItems.list", items_data) {
+            Ok(page) => page,
+            Err(_) => break,
+        };
 
-        next_page_token = items_data
-            .get("nextPageToken")
-            .and_then(|t| t.as_str())
-            .map(|s| s.to_string());
+        for item in page.items {
+            if total >= limit {
+                break;
+            }
+            let (Some(snippet), Some(content_details)) = (item.snippet, item.content_details) else {
+                seen += 1;
+                continue;
+            };
+            let position = snippet.position.map(|p| p as u32).unwrap_or(seen);
+            seen += 1;
+            if position < offset && !reorder {
+                continue;
+            }
+            if skip_unavailable && is_unavailable_item(&snippet) {
+                skipped_unavailable += 1;
+                continue;
+            }
+            let video_id = content_details.video_id;
+
+            let author = channel_snippet
+                .map(|s| s.title.clone())
+                .or(snippet.channel_title)
+                .unwrap_or_default();
+
+            let thumbnail = format!("{}/thumbnail/{}", base.trim_end_matches('/'), video_id);
+            let channel_thumbnail = channel_icon.clone().unwrap_or_else(|| {
+                format!("{}/channel_icon/{}", base.trim_end_matches('/'), channel_id)
+            });
+
+            videos.push(PlaylistVideo {
+                title: decode_label(&snippet.title),
+                author,
+                video_id,
+                thumbnail,
+                channel_thumbnail,
+                views: None,
+                published_at: snippet.published_at,
+                position: position + 1,
+                total_count,
+            });
+            total += 1;
+        }
 
+        next_page_token = page.next_page_token;
         if next_page_token.is_none() {
             break;
         }
     }
 
+    let reordered_len = videos.len() as u32;
+    if reorder {
+        order.apply(&mut videos);
+        videos = videos
+            .into_iter()
+            .skip(offset as usize)
+            .take(count.max(0) as usize)
+            .collect();
+    }
+
     let first_video_id = videos
         .first()
         .map(|v| v.video_id.clone())
         .unwrap_or_default();
 
     let playlist_info_resp = PlaylistInfo {
-        title: playlist_info
-            .get("snippet")
-            .and_then(|s| s.get("title"))
-            .and_then(|t| t.as_str())
-            .unwrap_or("")
-            .to_string(),
-        description: playlist_info
-            .get("snippet")
-            .and_then(|s| s.get("description"))
-            .and_then(|d| d.as_str())
-            .unwrap_or("")
-            .to_string(),
+        title: playlist_snippet.map(|s| s.title.clone()).unwrap_or_default(),
+        description: playlist_snippet
+            .map(|s| s.description.clone())
+            .unwrap_or_default(),
         thumbnail: if !first_video_id.is_empty() {
             format!(
                 "{}/thumbnail/{}",
@@ -1007,31 +1745,51 @@ pub async fn get_playlist_videos(
         } else {
             "".to_string()
         },
-        channel_title: channel_info
-            .and_then(|c| c.get("snippet"))
-            .and_then(|s| s.get("title"))
-            .and_then(|t| t.as_str())
-            .unwrap_or("")
-            .to_string(),
-        channel_thumbnail: channel_info
-            .and_then(|c| c.get("snippet"))
-            .and_then(|s| s.get("thumbnails"))
-            .and_then(|t| t.get("high"))
-            .and_then(|h| h.get("url"))
-            .and_then(|u| u.as_str())
-            .unwrap_or("")
-            .to_string(),
-        video_count: playlist_info
-            .get("contentDetails")
-            .and_then(|c| c.get("itemCount"))
-            .and_then(|v| v.as_i64())
-            .unwrap_or(0) as i32,
+        channel_title: channel_snippet.map(|s| s.title.clone()).unwrap_or_default(),
+        channel_thumbnail: channel_icon.unwrap_or_default(),
+        video_count: total_count as i32,
     };
 
     let response = PlaylistResponse {
         playlist_info: playlist_info_resp,
         videos,
+        skipped_unavailable,
+        shuffle_seed: match order {
+            PlaylistOrder::Shuffle(seed) => Some(seed),
+            _ => None,
+        },
     };
 
-    HttpResponse::Ok().json(response)
+    if crate::routes::envelope::requested(&req) {
+        // next page as a start_index: a full page that did not reach the end of the list
+        let full_page = response.videos.len() as i32 >= count.max(1);
+        let next_start = if !full_page {
+            None
+        } else if reorder {
+            Some(offset + count as u32 + 1).filter(|next| *next <= reordered_len)
+        } else {
+            response
+                .videos
+                .last()
+                .map(|v| v.position + 1)
+                .filter(|next| *next <= total_count)
+        };
+        let mut warnings = Vec::new();
+        if response.skipped_unavailable > 0 {
+            warnings.push(format!(
+                "{} deleted or private videos left out",
+                response.skipped_unavailable
+            ));
+        }
+        let list = ListResponse {
+            items: response.videos,
+            next_page_token: next_start.map(|n| n.to_string()),
+            total: Some(total_count as u64),
+            warnings,
+        };
+        let body = crate::routes::fields::filter_fields(&list, query_params.get("fields").map(|s| s.as_str()));
+        return crate::routes::http_cache::cached_json(&req, &body, config.cache.playlist_max_age);
+    }
+    let body = crate::routes::fields::filter_fields(&response, query_params.get("fields").map(|s| s.as_str()));
+    crate::routes::http_cache::cached_json(&req, &body, config.cache.playlist_max_age)
 }
@@ -0,0 +1,460 @@
+//! Disk cache of fixed-size byte ranges for proxied googlevideo streams (`cache.segments`).
+//! A stream lives in `<dir>/<video_id>_<itag>/` as `<index>.seg` files plus a `meta` file with the
+//! total length and content type. Segments are fetched from upstream only the first time any client
+//! asks for them, so a class seeking around the same trending video mostly reads from disk.
+//! Whole stream directories are evicted least-recently-used first once `max_size_mb` is exceeded.
+//! Once every segment of a stream is on disk, replays are served without resolving a URL at all
+//! (`is_complete`), so a video the class keeps rewatching costs YouTube nothing.
+
+use actix_web::http::header::{HeaderValue, CONTENT_RANGE, CONTENT_TYPE};
+use actix_web::http::StatusCode;
+use actix_web::{HttpRequest, HttpResponse};
+use bytes::Bytes;
+use serde::Serialize;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, SystemTime};
+use utoipa::ToSchema;
+
+use crate::config::SegmentCacheConfig;
+
+const USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/91.0.4472.124 Safari/537.36";
+
+static USAGE_BYTES: AtomicU64 = AtomicU64::new(0);
+/// Unix seconds of the last sweep; 0 until the first request.
+static LAST_SWEEP_SECS: AtomicU64 = AtomicU64::new(0);
+static SWEEPING: AtomicBool = AtomicBool::new(false);
+static TMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Serialize, ToSchema)]
+pub struct SegmentCacheStats {
+    pub enabled: bool,
+    /// As of the last sweep plus segments written since.
+    pub size_bytes: u64,
+    pub max_size_bytes: u64,
+}
+
+struct Meta {
+    total: u64,
+    content_type: String,
+}
+
+fn root(config: &SegmentCacheConfig) -> PathBuf {
+    if config.dir.trim().is_empty() {
+        env::temp_dir().join("yt_api_segment_cache")
+    } else {
+        PathBuf::from(&config.dir)
+    }
+}
+
+fn segment_size(config: &SegmentCacheConfig) -> u64 {
+    u64::from(config.segment_size_kb.max(64)) * 1024
+}
+
+/// Cache key for a stream: video id plus the `itag` of the upstream URL, so qualities never mix.
+pub fn key_for(video_id: &str, url: &str, fallback_variant: &str) -> String {
+    let itag = reqwest::Url::parse(url).ok().and_then(|u| {
+        u.query_pairs()
+            .find(|(k, _)| k == "itag")
+            .map(|(_, v)| v.into_owned())
+    });
+    key(video_id, itag.as_deref().unwrap_or(fallback_variant))
+}
+
+/// Cache key for `itag` of `video_id`.
+pub fn key(video_id: &str, itag: &str) -> String {
+    format!("{}_{}", video_id, itag)
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+        .collect()
+}
+
+fn read_meta(dir: &Path) -> Option<Meta> {
+    let text = fs::read_to_string(dir.join("meta")).ok()?;
+    let mut lines = text.lines();
+    let total = lines.next()?.trim().parse().ok()?;
+    let content_type = lines
+        .next()
+        .map(|l| l.trim().to_string())
+        .filter(|l| !l.is_empty())
+        .unwrap_or_else(|| "application/octet-stream".to_string());
+    Some(Meta { total, content_type })
+}
+
+fn write_meta(dir: &Path, meta: &Meta) {
+    if let Err(e) = fs::create_dir_all(dir)
+        .and_then(|_| fs::write(dir.join("meta"), format!("{}\n{}\n", meta.total, meta.content_type)))
+    {
+        log::warn!("Segment cache: failed to write meta in {}: {}", dir.display(), e);
+    }
+}
+
+/// Bumps the stream's mtime so eviction sees it as recently used.
+fn touch(dir: &Path) {
+    if let Ok(f) = fs::File::options().write(true).open(dir.join("meta")) {
+        let _ = f.set_modified(SystemTime::now());
+    }
+}
+
+/// Parses a single-range `bytes=` header against `total`. `None` means unsatisfiable.
+fn parse_range(header: &str, total: u64) -> Option<(u64, u64)> {
+    let spec = header.trim().strip_prefix("bytes=")?;
+    let (s, e) = spec.split_once('-')?;
+    let (s, e) = (s.trim(), e.trim());
+    if total == 0 {
+        return None;
+    }
+    if s.is_empty() {
+        let suffix: u64 = e.parse().ok()?;
+        if suffix == 0 {
+            return None;
+        }
+        return Some((total.saturating_sub(suffix), total - 1));
+    }
+    let start: u64 = s.parse().ok()?;
+    let end = if e.is_empty() {
+        total - 1
+    } else {
+        e.parse::<u64>().ok()?.min(total - 1)
+    };
+    if start > end {
+        return None;
+    }
+    Some((start, end))
+}
+
+/// Fetches `[start, end]` from upstream. Returns the body, the total length from `Content-Range`
+/// and the content type.
+async fn fetch_range(
+    url: &str,
+    start: u64,
+    end: u64,
+) -> Result<(Bytes, Option<u64>, Option<String>), String> {
+    let resp = crate::http_client::googlevideo()
+        .get(url)
+        .header("User-Agent", USER_AGENT)
+        .header("Range", format!("bytes={}-{}", start, end))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if resp.status() != reqwest::StatusCode::PARTIAL_CONTENT {
+        return Err(format!("upstream answered {} to a range request", resp.status()));
+    }
+    let total = resp
+        .headers()
+        .get(reqwest::header::CONTENT_RANGE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.rsplit('/').next())
+        .and_then(|v| v.parse().ok());
+    let content_type = resp
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string());
+    let body = resp.bytes().await.map_err(|e| e.to_string())?;
+    Ok((body, total, content_type))
+}
+
+async fn store_segment(dir: &Path, index: u64, data: &Bytes, config: &SegmentCacheConfig) {
+    let dir = dir.to_path_buf();
+    let data = data.clone();
+    let written = tokio::task::spawn_blocking(move || {
+        fs::create_dir_all(&dir)?;
+        // Concurrent viewers may fetch the same segment; each writes its own temp file.
+        let tmp = dir.join(format!(
+            "{}.seg.tmp{}",
+            index,
+            TMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::write(&tmp, &data)?;
+        fs::rename(&tmp, dir.join(format!("{}.seg", index)))?;
+        Ok::<u64, std::io::Error>(data.len() as u64)
+    })
+    .await;
+    match written {
+        Ok(Ok(len)) => {
+            USAGE_BYTES.fetch_add(len, Ordering::Relaxed);
+            sweep_if_over_limit(config);
+        }
+        Ok(Err(e)) => log::warn!("Segment cache: failed to store segment {}: {}", index, e),
+        Err(_) => {}
+    }
+}
+
+/// Reads segment `index` from disk, fetching and storing it first if missing.
+async fn load_segment(
+    dir: &Path,
+    url: &str,
+    index: u64,
+    total: u64,
+    config: &SegmentCacheConfig,
+) -> Result<Bytes, String> {
+    let seg = segment_size(config);
+    let start = index * seg;
+    let end = (start + seg).min(total) - 1;
+    let expected = (end - start + 1) as usize;
+
+    if let Ok(data) = tokio::fs::read(dir.join(format!("{}.seg", index))).await {
+        if data.len() == expected {
+            return Ok(Bytes::from(data));
+        }
+    }
+
+    let (body, _, _) = fetch_range(url, start, end).await?;
+    if body.len() != expected {
+        return Err(format!(
+            "segment {} came back with {} bytes, expected {}",
+            index,
+            body.len(),
+            expected
+        ));
+    }
+    store_segment(dir, index, &body, config).await;
+    Ok(body)
+}
+
+struct StreamState {
+    dir: PathBuf,
+    url: String,
+    config: SegmentCacheConfig,
+    total: u64,
+    start: u64,
+    end: u64,
+    index: u64,
+    last: u64,
+}
+
+fn all_segments_on_disk(dir: &Path, seg: u64) -> bool {
+    let Some(meta) = read_meta(dir) else {
+        return false;
+    };
+    meta.total > 0
+        && (0..meta.total.div_ceil(seg)).all(|index| {
+            let expected = ((index + 1) * seg).min(meta.total) - index * seg;
+            fs::metadata(dir.join(format!("{}.seg", index)))
+                .map(|m| m.len() == expected)
+                .unwrap_or(false)
+        })
+}
+
+/// Whether every segment of the stream `key` is on disk. Such a stream can be passed to `serve`
+/// with any URL, since no segment will be fetched.
+pub async fn is_complete(key: &str, config: &SegmentCacheConfig) -> bool {
+    if !config.enabled {
+        return false;
+    }
+    let dir = root(config).join(key);
+    let seg = segment_size(config);
+    tokio::task::spawn_blocking(move || all_segments_on_disk(&dir, seg))
+        .await
+        .unwrap_or(false)
+}
+
+/// Serves a GET for `url` through the segment cache. `None` means the cache is off or the stream
+/// can't be cached (upstream ignored the range, multi-range request) and the caller should proxy as usual.
+pub async fn serve(
+    key: &str,
+    url: &str,
+    req: &HttpRequest,
+    default_content_type: &str,
+    config: &SegmentCacheConfig,
+) -> Option<HttpResponse> {
+    if !config.enabled {
+        return None;
+    }
+    let range_header = req.headers().get("Range").and_then(|v| v.to_str().ok());
+    if range_header.map(|h| h.contains(',')).unwrap_or(false) {
+        return None;
+    }
+    sweep_periodically(config);
+
+    let dir = root(config).join(key);
+    let meta = match read_meta(&dir) {
+        Some(meta) => meta,
+        None => {
+            // First viewer: learn the length from segment 0 and keep it.
+            let seg = segment_size(config);
+            let (body, total, content_type) = match fetch_range(url, 0, seg - 1).await {
+                Ok(r) => r,
+                Err(e) => {
+                    log::info!("Segment cache: not caching {}: {}", key, e);
+                    return None;
+                }
+            };
+            let total = total.filter(|t| *t > 0)?;
+            let meta = Meta {
+                total,
+                content_type: content_type.unwrap_or_else(|| default_content_type.to_string()),
+            };
+            write_meta(&dir, &meta);
+            if body.len() as u64 == seg.min(total) {
+                store_segment(&dir, 0, &body, config).await;
+            }
+            meta
+        }
+    };
+    touch(&dir);
+
+    let total = meta.total;
+    let (start, end, status) = match range_header {
+        Some(h) => match parse_range(h, total) {
+            Some((s, e)) => (s, e, StatusCode::PARTIAL_CONTENT),
+            None => {
+                return Some(
+                    HttpResponse::build(StatusCode::RANGE_NOT_SATISFIABLE)
+                        .insert_header((CONTENT_RANGE, format!("bytes */{}", total)))
+                        .finish(),
+                )
+            }
+        },
+        None => (0, total - 1, StatusCode::OK),
+    };
+
+    let seg = segment_size(config);
+    let state = StreamState {
+        dir,
+        url: url.to_string(),
+        config: config.clone(),
+        total,
+        start,
+        end,
+        index: start / seg,
+        last: end / seg,
+    };
+    let stream = futures_util::stream::unfold(state, |mut st| async move {
+        if st.index > st.last {
+            return None;
+        }
+        let seg_start = st.index * segment_size(&st.config);
+        let item = match load_segment(&st.dir, &st.url, st.index, st.total, &st.config).await {
+            Ok(data) => {
+                let from = (st.start.saturating_sub(seg_start) as usize).min(data.len());
+                let to = ((st.end - seg_start + 1) as usize).min(data.len());
+                st.index += 1;
+                Ok(data.slice(from..to))
+            }
+            Err(e) => {
+                log::warn!("Segment cache: {}", e);
+                // Ends the stream after this error.
+                st.index = st.last + 1;
+                Err(actix_web::error::ErrorBadGateway(e))
+            }
+        };
+        Some((item, st))
+    });
+
+    let mut builder = HttpResponse::build(status);
+    builder
+        .insert_header((
+            CONTENT_TYPE,
+            HeaderValue::from_str(&meta.content_type)
+                .unwrap_or_else(|_| HeaderValue::from_static("application/octet-stream")),
+        ))
+        .insert_header(("Accept-Ranges", "bytes"))
+        .no_chunking(end - start + 1);
+    if status == StatusCode::PARTIAL_CONTENT {
+        builder.insert_header((CONTENT_RANGE, format!("bytes {}-{}/{}", start, end, total)));
+    }
+    Some(builder.streaming(stream))
+}
+
+fn dir_size(path: &Path) -> u64 {
+    fs::read_dir(path)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter_map(|e| e.metadata().ok())
+                .filter(|m| m.is_file())
+                .map(|m| m.len())
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+/// Drops streams older than `max_age_hours`, then least-recently-used ones until usage is
+/// under 90% of `max_size_mb`. Also resets the usage counter from what's actually on disk.
+fn sweep(root: PathBuf, max_bytes: u64, max_age: Duration) {
+    let now = SystemTime::now();
+    let mut streams: Vec<(PathBuf, SystemTime, u64)> = Vec::new();
+    if let Ok(entries) = fs::read_dir(&root) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if !path.is_dir() {
+                continue;
+            }
+            let used = fs::metadata(path.join("meta"))
+                .and_then(|m| m.modified())
+                .unwrap_or(SystemTime::UNIX_EPOCH);
+            if now.duration_since(used).unwrap_or(Duration::ZERO) > max_age {
+                let _ = fs::remove_dir_all(&path);
+                log::debug!("Segment cache: expired {}", path.display());
+                continue;
+            }
+            let size = dir_size(&path);
+            streams.push((path, used, size));
+        }
+    }
+
+    let mut usage: u64 = streams.iter().map(|(_, _, size)| size).sum();
+    let target = max_bytes / 10 * 9;
+    if usage > max_bytes {
+        streams.sort_by_key(|(_, used, _)| *used);
+        for (path, _, size) in &streams {
+            if usage <= target {
+                break;
+            }
+            let _ = fs::remove_dir_all(path);
+            usage = usage.saturating_sub(*size);
+            log::debug!("Segment cache: evicted {}", path.display());
+        }
+    }
+    USAGE_BYTES.store(usage, Ordering::Relaxed);
+}
+
+fn spawn_sweep(config: &SegmentCacheConfig) {
+    if SWEEPING
+        .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+        .is_err()
+    {
+        return;
+    }
+    let root = root(config);
+    let max_bytes = u64::from(config.max_size_mb) * 1024 * 1024;
+    let max_age = Duration::from_secs(u64::from(config.max_age_hours) * 3600);
+    tokio::task::spawn_blocking(move || {
+        sweep(root, max_bytes, max_age);
+        SWEEPING.store(false, Ordering::SeqCst);
+    });
+}
+
+/// Sweeps on the first request and then at most hourly, so expired streams go away without a size trigger.
+fn sweep_periodically(config: &SegmentCacheConfig) {
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let last = LAST_SWEEP_SECS.load(Ordering::Relaxed);
+    if now.saturating_sub(last) >= 3600
+        && LAST_SWEEP_SECS
+            .compare_exchange(last, now, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok()
+    {
+        spawn_sweep(config);
+    }
+}
+
+fn sweep_if_over_limit(config: &SegmentCacheConfig) {
+    if USAGE_BYTES.load(Ordering::Relaxed) > u64::from(config.max_size_mb) * 1024 * 1024 {
+        spawn_sweep(config);
+    }
+}
+
+pub fn stats(config: &SegmentCacheConfig) -> SegmentCacheStats {
+    SegmentCacheStats {
+        enabled: config.enabled,
+        size_bytes: USAGE_BYTES.load(Ordering::Relaxed),
+        max_size_bytes: u64::from(config.max_size_mb) * 1024 * 1024,
+    }
+}
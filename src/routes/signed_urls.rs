@@ -0,0 +1,320 @@
+//! Signed, expiring stream URLs (`proxy.signed_urls`). With it on, /direct_url, /direct_audio_url
+//! and /video.proxy only serve URLs this instance handed out: the info endpoints, feeds and
+//! frontend pages append `expires=` and `sig=` (HMAC-SHA256 over the video id or proxied URL,
+//! keyed by `server.secret_key`), so a public instance is no longer an open relay. Clients connecting
+//! from `trusted_networks` skip the check.
+
+use actix_web::{HttpRequest, HttpResponse};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::OnceLock;
+
+use crate::config::Config;
+use crate::routes::now_secs;
+
+type HmacSha256 = Hmac<Sha256>;
+
+struct State {
+    secret: Vec<u8>,
+    ttl_secs: u64,
+    trusted: Vec<(IpAddr, u8)>,
+}
+
+static STATE: OnceLock<Option<State>> = OnceLock::new();
+
+pub fn init(config: &Config) {
+    let settings = &config.proxy.signed_urls;
+    let state = if !settings.enabled {
+        None
+    } else if config.server.secretkey.trim().is_empty() {
+        log::warn!("proxy.signed_urls needs server.secret_key; stream URLs are not signed");
+        None
+    } else {
+        let trusted = settings
+            .trusted_networks
+            .iter()
+            .filter_map(|net| {
+                let parsed = parse_network(net);
+                if parsed.is_none() {
+                    log::warn!("proxy.signed_urls: ignoring trusted network '{}'", net);
+                }
+                parsed
+            })
+            .collect();
+        Some(State {
+            secret: config.server.secretkey.as_bytes().to_vec(),
+            ttl_secs: settings.ttl_secs.max(60),
+            trusted,
+        })
+    };
+    let _ = STATE.set(state);
+}
+
+fn state() -> Option<&'static State> {
+    STATE.get().and_then(|s| s.as_ref())
+}
+
+/// `10.0.0.0/8`, `fd00::/8`, or a single address.
+pub(crate) fn parse_network(value: &str) -> Option<(IpAddr, u8)> {
+    let value = value.trim();
+    let (addr, prefix) = match value.split_once('/') {
+        Some((addr, prefix)) => (addr.parse::<IpAddr>().ok()?, Some(prefix.parse::<u8>().ok()?)),
+        None => (value.parse::<IpAddr>().ok()?, None),
+    };
+    let max = if addr.is_ipv4() { 32 } else { 128 };
+    let prefix = prefix.unwrap_or(max);
+    (prefix <= max).then_some((addr, prefix))
+}
+
+pub(crate) fn in_network(ip: IpAddr, (net, prefix): (IpAddr, u8)) -> bool {
+    let (ip, net, bits) = match (ip, net) {
+        (IpAddr::V4(ip), IpAddr::V4(net)) => (u32::from(ip) as u128, u32::from(net) as u128, 32),
+        (IpAddr::V6(ip), IpAddr::V6(net)) => (u128::from(ip), u128::from(net), 128),
+        _ => return false,
+    };
+    if prefix == 0 {
+        return true;
+    }
+    let shift = bits - prefix as u32;
+    ip >> shift == net >> shift
+}
+
+fn mac(state: &State, resource: &str, expires: u64) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(&state.secret).expect("HMAC takes keys of any length");
+    mac.update(resource.as_bytes());
+    mac.update(b"\0");
+    mac.update(expires.to_string().as_bytes());
+    mac
+}
+
+fn signature(state: &State, resource: &str, expires: u64) -> String {
+    URL_SAFE_NO_PAD.encode(mac(state, resource, expires).finalize().into_bytes())
+}
+
+/// `&expires=..&sig=..` for `resource`, or nothing when signing is off.
+fn params(resource: &str) -> String {
+    let Some(state) = state() else {
+        return String::new();
+    };
+    let expires = now_secs() + state.ttl_secs;
+    format!("&expires={}&sig={}", expires, signature(state, resource, expires))
+}
+
+/// Whether `expires` and `sig` from the query authorize `resource` at `now`; the error is the
+/// reason given to the client.
+fn check_signature(
+    state: &State,
+    resource: &str,
+    expires: Option<&str>,
+    sig: Option<&str>,
+    now: u64,
+) -> Result<(), &'static str> {
+    let (Some(expires), Some(sig)) = (expires, sig) else {
+        return Err("This instance only serves signed stream URLs");
+    };
+    let Ok(expires) = expires.parse::<u64>() else {
+        return Err("Malformed expires");
+    };
+    if expires < now {
+        return Err("The URL has expired; request the video info again");
+    }
+    let valid = URL_SAFE_NO_PAD
+        .decode(sig)
+        .map(|sig| mac(state, resource, expires).verify_slice(&sig).is_ok())
+        .unwrap_or(false);
+    if valid {
+        Ok(())
+    } else {
+        Err("Bad signature")
+    }
+}
+
+/// Appended to a `/direct_url?video_id=...` URL.
+pub fn direct_url_params(video_id: &str) -> String {
+    params(&format!("direct_url:{}", video_id))
+}
+
+/// Appended to a `/direct_audio_url?video_id=...` URL.
+pub fn direct_audio_url_params(video_id: &str) -> String {
+    params(&format!("direct_audio_url:{}", video_id))
+}
+
+/// Appended to a `/video.proxy?url=...` URL; `url` is the unencoded target.
+pub fn proxy_params(url: &str) -> String {
+    params(&format!("video.proxy:{}", url))
+}
+
+fn verify(req: &HttpRequest, query: &HashMap<String, String>, resource: &str) -> Option<HttpResponse> {
+    let state = state()?;
+    // the socket address, not X-Forwarded-For: a forged header must not count as the LAN
+    if let Some(peer) = req.peer_addr() {
+        if state.trusted.iter().any(|net| in_network(peer.ip(), *net)) {
+            return None;
+        }
+    }
+    let expires = query.get("expires").map(String::as_str);
+    let sig = query.get("sig").map(String::as_str);
+    check_signature(state, resource, expires, sig, now_secs())
+        .err()
+        .map(|details| {
+            HttpResponse::Forbidden().json(serde_json::json!({
+                "error": "Invalid or expired stream URL",
+                "details": details
+            }))
+        })
+}
+
+/// 403 unless /direct_url was called with a valid signature for `video_id`.
+pub fn check_direct_url(req: &HttpRequest, query: &HashMap<String, String>, video_id: &str) -> Option<HttpResponse> {
+    verify(req, query, &format!("direct_url:{}", video_id))
+}
+
+/// 403 unless /direct_audio_url was called with a valid signature for `video_id`.
+pub fn check_direct_audio_url(req: &HttpRequest, query: &HashMap<String, String>, video_id: &str) -> Option<HttpResponse> {
+    verify(req, query, &format!("direct_audio_url:{}", video_id))
+}
+
+/// 403 unless /video.proxy was called with a valid signature for `url`.
+pub fn check_proxy(req: &HttpRequest, query: &HashMap<String, String>, url: &str) -> Option<HttpResponse> {
+    verify(req, query, &format!("video.proxy:{}", url))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn net(value: &str) -> (IpAddr, u8) {
+        parse_network(value).unwrap()
+    }
+
+    fn ip(value: &str) -> IpAddr {
+        value.parse().unwrap()
+    }
+
+    fn state() -> State {
+        State {
+            secret: b"test-secret".to_vec(),
+            ttl_secs: 3600,
+            trusted: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn parse_network_prefixes() {
+        assert_eq!(parse_network("10.0.0.0/8"), Some((ip("10.0.0.0"), 8)));
+        assert_eq!(parse_network(" 192.168.1.7 "), Some((ip("192.168.1.7"), 32)));
+        assert_eq!(parse_network("::1"), Some((ip("::1"), 128)));
+        assert_eq!(parse_network("0.0.0.0/0"), Some((ip("0.0.0.0"), 0)));
+        assert_eq!(parse_network("fd00::/128"), Some((ip("fd00::"), 128)));
+        assert_eq!(parse_network("10.0.0.0/33"), None);
+        assert_eq!(parse_network("fd00::/129"), None);
+        assert_eq!(parse_network("10.0.0.0/"), None);
+        assert_eq!(parse_network("10.0.0.0/-1"), None);
+        assert_eq!(parse_network("example.com"), None);
+    }
+
+    #[test]
+    fn in_network_zero_prefix_matches_whole_family() {
+        assert!(in_network(ip("203.0.113.9"), net("0.0.0.0/0")));
+        assert!(in_network(ip("2001:db8::1"), net("::/0")));
+        assert!(!in_network(ip("203.0.113.9"), net("::/0")));
+        assert!(!in_network(ip("2001:db8::1"), net("0.0.0.0/0")));
+    }
+
+    #[test]
+    fn in_network_full_prefix_is_one_address() {
+        assert!(in_network(ip("192.168.1.7"), net("192.168.1.7/32")));
+        assert!(!in_network(ip("192.168.1.8"), net("192.168.1.7/32")));
+        assert!(in_network(ip("2001:db8::7"), net("2001:db8::7/128")));
+        assert!(!in_network(ip("2001:db8::8"), net("2001:db8::7/128")));
+    }
+
+    #[test]
+    fn in_network_partial_prefix() {
+        assert!(in_network(ip("10.200.3.4"), net("10.0.0.0/8")));
+        assert!(!in_network(ip("11.0.0.1"), net("10.0.0.0/8")));
+        assert!(in_network(ip("172.31.255.255"), net("172.16.0.0/12")));
+        assert!(!in_network(ip("172.32.0.0"), net("172.16.0.0/12")));
+        assert!(in_network(ip("fdab::1"), net("fd00::/8")));
+        assert!(!in_network(ip("fe80::1"), net("fd00::/8")));
+    }
+
+    #[test]
+    fn in_network_never_mixes_families() {
+        assert!(!in_network(ip("::ffff:10.0.0.1"), net("10.0.0.0/8")));
+        assert!(!in_network(ip("10.0.0.1"), net("::ffff:10.0.0.0/104")));
+        assert!(!in_network(ip("127.0.0.1"), net("::1")));
+    }
+
+    #[test]
+    fn signature_round_trip() {
+        let state = state();
+        let expires = 2_000;
+        let sig = signature(&state, "direct_url:dQw4w9WgXcQ", expires);
+        let result = check_signature(&state, "direct_url:dQw4w9WgXcQ", Some("2000"), Some(&sig), 1_000);
+        assert_eq!(result, Ok(()));
+        // still valid in the last second
+        let result = check_signature(&state, "direct_url:dQw4w9WgXcQ", Some("2000"), Some(&sig), 2_000);
+        assert_eq!(result, Ok(()));
+    }
+
+    #[test]
+    fn signature_expires() {
+        let state = state();
+        let sig = signature(&state, "direct_url:dQw4w9WgXcQ", 2_000);
+        let result = check_signature(&state, "direct_url:dQw4w9WgXcQ", Some("2000"), Some(&sig), 2_001);
+        assert_eq!(result, Err("The URL has expired; request the video info again"));
+    }
+
+    #[test]
+    fn signature_rejects_tampering() {
+        let state = state();
+        let sig = signature(&state, "direct_url:dQw4w9WgXcQ", 2_000);
+        // another video, another endpoint, a pushed-out expiry, another key
+        assert_eq!(
+            check_signature(&state, "direct_url:aaaaaaaaaaa", Some("2000"), Some(&sig), 1_000),
+            Err("Bad signature")
+        );
+        assert_eq!(
+            check_signature(&state, "video.proxy:dQw4w9WgXcQ", Some("2000"), Some(&sig), 1_000),
+            Err("Bad signature")
+        );
+        assert_eq!(
+            check_signature(&state, "direct_url:dQw4w9WgXcQ", Some("9000"), Some(&sig), 1_000),
+            Err("Bad signature")
+        );
+        let other = State {
+            secret: b"other-secret".to_vec(),
+            ..state
+        };
+        assert_eq!(
+            check_signature(&other, "direct_url:dQw4w9WgXcQ", Some("2000"), Some(&sig), 1_000),
+            Err("Bad signature")
+        );
+    }
+
+    #[test]
+    fn signature_requires_well_formed_params() {
+        let state = state();
+        let sig = signature(&state, "direct_url:dQw4w9WgXcQ", 2_000);
+        assert_eq!(
+            check_signature(&state, "direct_url:dQw4w9WgXcQ", None, Some(&sig), 1_000),
+            Err("This instance only serves signed stream URLs")
+        );
+        assert_eq!(
+            check_signature(&state, "direct_url:dQw4w9WgXcQ", Some("2000"), None, 1_000),
+            Err("This instance only serves signed stream URLs")
+        );
+        assert_eq!(
+            check_signature(&state, "direct_url:dQw4w9WgXcQ", Some("soon"), Some(&sig), 1_000),
+            Err("Malformed expires")
+        );
+        assert_eq!(
+            check_signature(&state, "direct_url:dQw4w9WgXcQ", Some("2000"), Some("not base64!"), 1_000),
+            Err("Bad signature")
+        );
+    }
+}
@@ -0,0 +1,120 @@
+//! Background validation of stored refresh tokens (`api.oauth.token_validation`). Browser
+//! sessions in the TokenStore and linked devices in assets/tokens.json are tried against Google's
+//! token endpoint on a timer; a token Google rejects with `invalid_grant` is dropped from both,
+//! and the browser session that held it gets a "sign in again" notice on its next page instead
+//! of a masthead that looks signed in while every action fails.
+
+use actix_web::{web, HttpRequest};
+use lazy_static::lazy_static;
+use std::collections::HashSet;
+use std::sync::{Mutex, RwLock};
+use std::time::Duration;
+
+use crate::config::TokenValidationConfig;
+use crate::routes::audit;
+use crate::routes::auth::{AuthConfig, TokenStore};
+use crate::routes::oauth::{check_refresh_token, TokenCheck};
+
+/// Pause between two token checks, so a large store doesn't burst the token endpoint.
+const CHECK_SPACING: Duration = Duration::from_millis(500);
+
+lazy_static! {
+    /// Fingerprints (`audit::token_session`) of tokens found revoked; they are refused without
+    /// asking Google again, including when a persistent login cookie brings one back.
+    static ref REVOKED: RwLock<HashSet<String>> = RwLock::new(HashSet::new());
+    /// `session_id` cookies that lost their token and have not been told yet.
+    static ref PENDING_NOTICES: Mutex<HashSet<String>> = Mutex::new(HashSet::new());
+}
+
+pub fn is_revoked(refresh_token: &str) -> bool {
+    REVOKED
+        .read()
+        .map(|set| set.contains(&audit::token_session(refresh_token)))
+        .unwrap_or(false)
+}
+
+/// True once for a browser session whose token was revoked since its last request.
+pub fn take_notice(req: &HttpRequest) -> bool {
+    let Some(session) = req.cookie("session_id") else {
+        return false;
+    };
+    PENDING_NOTICES
+        .lock()
+        .map(|mut pending| pending.remove(session.value()))
+        .unwrap_or(false)
+}
+
+async fn validate(store: &TokenStore, auth: &AuthConfig) {
+    let sessions = store.sessions();
+    let mut tokens: Vec<String> = sessions.iter().map(|(_, t)| t.clone()).collect();
+    tokens.extend(crate::routes::auth_routes::linked_refresh_tokens());
+    tokens.retain(|t| !t.is_empty() && !t.starts_with("Error"));
+    tokens.sort();
+    tokens.dedup();
+
+    let mut revoked = HashSet::new();
+    let mut unknown = 0;
+    for token in &tokens {
+        if is_revoked(token) {
+            revoked.insert(token.clone());
+            continue;
+        }
+        match check_refresh_token(token, auth).await {
+            TokenCheck::Valid => {}
+            TokenCheck::Revoked => {
+                revoked.insert(token.clone());
+            }
+            TokenCheck::Unknown(e) => {
+                unknown += 1;
+                log::debug!("Token validation inconclusive: {}", e);
+            }
+        }
+        tokio::time::sleep(CHECK_SPACING).await;
+    }
+
+    if let Ok(mut set) = REVOKED.write() {
+        set.extend(revoked.iter().map(|t| audit::token_session(t)));
+    }
+    for (session_id, _) in sessions.iter().filter(|(_, t)| revoked.contains(t)) {
+        store.remove_token(session_id);
+        if let Ok(mut pending) = PENDING_NOTICES.lock() {
+            pending.insert(session_id.clone());
+        }
+        audit::record(None, audit::TOKEN_REVOKED, &format!("session:{}", session_id), true, "");
+    }
+    let unlinked = match crate::routes::auth_routes::unlink_revoked(|t| revoked.contains(t)) {
+        Ok(n) => n,
+        Err(e) => {
+            log::warn!("Could not unlink revoked devices in tokens.json: {}", e);
+            0
+        }
+    };
+    if unlinked > 0 {
+        let detail = format!("{} linked devices unlinked", unlinked);
+        audit::record(None, audit::TOKEN_REVOKED, "tokens.json", true, &detail);
+    }
+
+    if !revoked.is_empty() || unknown > 0 {
+        log::info!(
+            "Token validation: {} checked, {} revoked ({} devices unlinked), {} inconclusive",
+            tokens.len(),
+            revoked.len(),
+            unlinked,
+            unknown
+        );
+    }
+}
+
+/// Validates the stored tokens every `interval_minutes`, the first round one interval after boot.
+pub fn spawn(config: &TokenValidationConfig, store: web::Data<TokenStore>, auth: web::Data<AuthConfig>) {
+    if !config.enabled {
+        return;
+    }
+    let period = Duration::from_secs(u64::from(config.interval_minutes.max(1)) * 60);
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(period).await;
+            validate(&store, &auth).await;
+        }
+    });
+}
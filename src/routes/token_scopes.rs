@@ -0,0 +1,313 @@
+//! Permission tiers for stored OAuth tokens. A token can be limited to browsing: `/actions/*`
+//! writes (subscribe, rate, playlist_add) and watch-history writes (`mark_video_watched.php`) are
+//! allowed separately, so a shared family account can be handed to a kid's TV read-only.
+//! Tokens are unrestricted unless scoped, either at login (`/auth?scope=read_only`, also on
+//! /auth/start) or by an admin through /admin/token_scopes. Scopes are keyed by a SHA-256 of
+//! the refresh token and kept in the SQLite store (`profiles.db_path`); without it a token cannot
+//! be scoped, and asking for a scope fails instead of silently leaving the token unrestricted.
+
+use actix_web::{http::StatusCode, web, HttpRequest, HttpResponse, Responder};
+use lazy_static::lazy_static;
+use rusqlite::{params, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use utoipa::ToSchema;
+
+use crate::db;
+use crate::routes::auth::TokenStore;
+use crate::routes::messages::Msg;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Scope {
+    pub allow_actions: bool,
+    pub allow_history_write: bool,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Permission {
+    Actions,
+    HistoryWrite,
+}
+
+impl Scope {
+    pub const FULL: Scope = Scope {
+        allow_actions: true,
+        allow_history_write: true,
+    };
+    pub const READ_ONLY: Scope = Scope {
+        allow_actions: false,
+        allow_history_write: false,
+    };
+
+    /// `full`, `read_only`, or a comma list of what is allowed on top of reading:
+    /// `actions`, `history` (`actions,history` is the same as `full`).
+    pub fn parse(value: &str) -> Option<Scope> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "full" => return Some(Scope::FULL),
+            "read_only" | "readonly" | "read" => return Some(Scope::READ_ONLY),
+            _ => {}
+        }
+        let mut scope = Scope::READ_ONLY;
+        for part in value.split(',').map(|p| p.trim().to_ascii_lowercase()) {
+            match part.as_str() {
+                "actions" | "allow_actions" => scope.allow_actions = true,
+                "history" | "allow_history_write" => scope.allow_history_write = true,
+                _ => return None,
+            }
+        }
+        Some(scope)
+    }
+
+    fn allows(self, permission: Permission) -> bool {
+        match permission {
+            Permission::Actions => self.allow_actions,
+            Permission::HistoryWrite => self.allow_history_write,
+        }
+    }
+}
+
+const NO_STORE: &str = "Token scopes need the database (profiles.db_path), which is not open";
+
+lazy_static! {
+    /// Scopes asked for when a login started, by `session_id`, until the token arrives.
+    static ref PENDING: Mutex<HashMap<String, Scope>> = Mutex::new(HashMap::new());
+}
+
+fn token_key(refresh_token: &str) -> String {
+    format!("{:x}", Sha256::digest(refresh_token.trim().as_bytes()))
+}
+
+pub fn get(refresh_token: &str) -> Scope {
+    if !db::is_ready() {
+        // nothing can have been scoped
+        return Scope::FULL;
+    }
+    let key = token_key(refresh_token);
+    let row = db::with_conn(|conn| {
+        conn.query_row(
+            "SELECT allow_actions, allow_history_write FROM token_scopes WHERE token_hash = ?1",
+            params![key],
+            |row| {
+                Ok(Scope {
+                    allow_actions: row.get(0)?,
+                    allow_history_write: row.get(1)?,
+                })
+            },
+        )
+        .optional()
+    });
+    match row {
+        Ok(scope) => scope.unwrap_or(Scope::FULL),
+        Err(e) => {
+            // failing closed would lock every session out over a database hiccup
+            log::warn!("Failed to read token scope: {}", e);
+            Scope::FULL
+        }
+    }
+}
+
+pub fn set(refresh_token: &str, scope: Scope) -> Result<(), String> {
+    if !db::is_ready() {
+        // a full token is what `get` reports anyway; anything narrower would be lost on restart
+        return if scope == Scope::FULL { Ok(()) } else { Err(NO_STORE.to_string()) };
+    }
+    let key = token_key(refresh_token);
+    db::with_conn(|conn| {
+        if scope == Scope::FULL {
+            conn.execute("DELETE FROM token_scopes WHERE token_hash = ?1", params![key])?;
+        } else {
+            conn.execute(
+                "INSERT INTO token_scopes (token_hash, allow_actions, allow_history_write, updated_at)
+                 VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT(token_hash) DO UPDATE SET
+                    allow_actions = excluded.allow_actions,
+                    allow_history_write = excluded.allow_history_write,
+                    updated_at = excluded.updated_at",
+                params![key, scope.allow_actions, scope.allow_history_write, db::now_secs()],
+            )?;
+        }
+        Ok(())
+    })
+}
+
+/// Remembers the `scope=` of a login that is starting; applied by [`claim`] once it completes.
+/// Fails when a narrowing scope is asked for but there is no store to keep it in.
+pub fn set_pending(session_id: &str, scope: Option<&String>) -> Result<(), &'static str> {
+    let Some(scope) = scope.and_then(|s| Scope::parse(s)) else {
+        return Ok(());
+    };
+    if scope != Scope::FULL && !db::is_ready() {
+        return Err(NO_STORE);
+    }
+    if let Ok(mut pending) = PENDING.lock() {
+        pending.insert(session_id.to_string(), scope);
+    }
+    Ok(())
+}
+
+/// Applies the scope requested at login to the token it produced. Only ever narrows: a
+/// `scope=full` login does not lift a restriction an admin put on the same token.
+pub fn claim(session_id: &str, refresh_token: &str) {
+    let scope = PENDING.lock().ok().and_then(|mut p| p.remove(session_id));
+    let Some(scope) = scope.filter(|s| *s != Scope::FULL) else {
+        return;
+    };
+    let current = get(refresh_token);
+    let narrowed = Scope {
+        allow_actions: current.allow_actions && scope.allow_actions,
+        allow_history_write: current.allow_history_write && scope.allow_history_write,
+    };
+    if let Err(e) = set(refresh_token, narrowed) {
+        log::warn!("Failed to store the login scope: {}", e);
+    }
+}
+
+/// 403 when `refresh_token` is not allowed `permission`, `None` otherwise.
+pub fn deny(refresh_token: &str, permission: Permission) -> Option<HttpResponse> {
+    if get(refresh_token).allows(permission) {
+        return None;
+    }
+    let details = match permission {
+        Permission::Actions => "Subscribing, rating and editing playlists are disabled for this session",
+        Permission::HistoryWrite => "Writing watch history is disabled for this session",
+    };
+    Some(HttpResponse::Forbidden().json(serde_json::json!({
+        "error": Msg::NotPermitted.text(),
+        "details": details
+    })))
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ScopeResponse {
+    /// `token:` fingerprint, as in /admin/audit.
+    pub token: String,
+    pub allow_actions: bool,
+    pub allow_history_write: bool,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ScopeChange {
+    /// Refresh token to scope; or give `session`.
+    pub token: Option<String>,
+    /// `session_id` of a signed-in browser session.
+    pub session: Option<String>,
+    /// `full`, `read_only`, or a comma list of `actions`, `history`.
+    pub scope: String,
+}
+
+fn unauthorized() -> HttpResponse {
+    HttpResponse::Unauthorized().json(serde_json::json!({
+        "error": Msg::AdminKeyRequired.text()
+    }))
+}
+
+fn resolve_token(
+    token: Option<&String>,
+    session: Option<&String>,
+    token_store: &TokenStore,
+) -> Result<String, (StatusCode, &'static str)> {
+    if let Some(token) = token.filter(|t| !t.trim().is_empty()) {
+        return Ok(token.trim().to_string());
+    }
+    match session.filter(|s| !s.trim().is_empty()) {
+        Some(session) => token_store
+            .get_token(session.trim())
+            .filter(|t| !t.starts_with("Error"))
+            .ok_or((StatusCode::NOT_FOUND, "No token for this session")),
+        None => Err((StatusCode::BAD_REQUEST, "token or session is required")),
+    }
+}
+
+fn error_json(status: StatusCode, message: &str) -> HttpResponse {
+    HttpResponse::build(status).json(serde_json::json!({ "error": message }))
+}
+
+fn scope_response(refresh_token: &str) -> HttpResponse {
+    let scope = get(refresh_token);
+    HttpResponse::Ok().json(ScopeResponse {
+        token: crate::routes::audit::token_session(refresh_token),
+        allow_actions: scope.allow_actions,
+        allow_history_write: scope.allow_history_write,
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/token_scopes",
+    params(
+        ("token" = Option<String>, Query, description = "Refresh token"),
+        ("session" = Option<String>, Query, description = "session_id instead of the token"),
+        ("admin_key" = Option<String>, Query, description = "Admin key (or X-Admin-Key header)")
+    ),
+    responses(
+        (status = 200, description = "Permissions of the token", body = ScopeResponse),
+        (status = 400, description = "Neither token nor session given"),
+        (status = 401, description = "Admin key required"),
+        (status = 404, description = "Unknown session")
+    )
+)]
+pub async fn get_scope(
+    req: HttpRequest,
+    query: web::Query<HashMap<String, String>>,
+    token_store: web::Data<TokenStore>,
+    data: web::Data<crate::AppState>,
+) -> impl Responder {
+    if !crate::routes::admin::is_admin(&req, &data.config) {
+        return unauthorized();
+    }
+    match resolve_token(query.get("token"), query.get("session"), &token_store) {
+        Ok(token) => scope_response(&token),
+        Err((status, message)) => error_json(status, message),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/token_scopes",
+    request_body = ScopeChange,
+    params(
+        ("admin_key" = Option<String>, Query, description = "Admin key (or X-Admin-Key header)")
+    ),
+    responses(
+        (status = 200, description = "Updated permissions", body = ScopeResponse),
+        (status = 400, description = "Unknown scope, or neither token nor session given"),
+        (status = 401, description = "Admin key required"),
+        (status = 404, description = "Unknown session"),
+        (status = 503, description = "No database open to keep the scope in")
+    )
+)]
+pub async fn update_scope(
+    req: HttpRequest,
+    body: web::Json<ScopeChange>,
+    token_store: web::Data<TokenStore>,
+    data: web::Data<crate::AppState>,
+) -> impl Responder {
+    if !crate::routes::admin::is_admin(&req, &data.config) {
+        return unauthorized();
+    }
+    let change = body.into_inner();
+    let Some(scope) = Scope::parse(&change.scope) else {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": "Unknown scope",
+            "details": "Use full, read_only, or a comma list of actions, history"
+        }));
+    };
+    let token = match resolve_token(change.token.as_ref(), change.session.as_ref(), &token_store) {
+        Ok(token) => token,
+        Err((status, message)) => return error_json(status, message),
+    };
+    if let Err(e) = set(&token, scope) {
+        let status = if db::is_ready() {
+            StatusCode::INTERNAL_SERVER_ERROR
+        } else {
+            StatusCode::SERVICE_UNAVAILABLE
+        };
+        return HttpResponse::build(status).json(serde_json::json!({
+            "error": "Failed to save the scope",
+            "details": e
+        }));
+    }
+    scope_response(&token)
+}
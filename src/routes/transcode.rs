@@ -0,0 +1,224 @@
+//! Live transcodes for clients that cannot decode anything YouTube serves (`/direct_url?format=`,
+//! `/direct_audio_url?format=`). Unlike codec= conversions, which finish the whole file before
+//! the first byte goes out, ffmpeg writes to stdout (fragmented, for MP4-family containers) and
+//! the client starts playing as soon as the first frames arrive.
+//! The source is downloaded with reqwest and piped into ffmpeg's stdin (ffmpeg's own HTTP client
+//! chokes on googlevideo URLs); ffmpeg is killed as soon as the client hangs up.
+
+use actix_web::http::header::{HeaderValue, CONTENT_TYPE};
+use actix_web::HttpResponse;
+use bytes::Bytes;
+use futures_util::StreamExt;
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+
+use crate::config::{LoudnormConfig, VideoConfig};
+
+/// An output format a `format=` value maps to.
+pub struct LiveProfile {
+    pub name: &'static str,
+    pub content_type: &'static str,
+    /// ffmpeg encoders the profile needs, for `unsupported_profile`.
+    pub encoders: &'static [&'static str],
+    /// Drops the video; the only kind `/direct_audio_url` offers.
+    pub audio_only: bool,
+    args: fn(&VideoConfig) -> Vec<String>,
+}
+
+fn owned(args: &[&str]) -> Vec<String> {
+    args.iter().map(|a| a.to_string()).collect()
+}
+
+/// H.263 QCIF at 15 fps with AMR-NB mono: what J2ME's MMAPI and S60 RealPlayer decode. H.263 only
+/// allows a few frame sizes, so the picture is letterboxed into 176x144 rather than stretched.
+const THREE_GP: LiveProfile = LiveProfile {
+    name: "3gp",
+    content_type: "video/3gpp",
+    encoders: &["h263", "libopencore_amrnb"],
+    audio_only: false,
+    args: |_| {
+        owned(&[
+            "-c:v", "h263",
+            "-vf", "scale=176:144:force_original_aspect_ratio=decrease,pad=176:144:(ow-iw)/2:(oh-ih)/2,setsar=1",
+            "-r", "15", "-g", "30", "-b:v", "128k",
+            "-c:a", "libopencore_amrnb", "-ar", "8000", "-ac", "1", "-b:a", "12.2k",
+            "-movflags", "frag_keyframe+empty_moov",
+            "-f", "3gp",
+        ])
+    },
+};
+
+/// Constant-bitrate MP3 (`video.mp3_bitrate_kbps`) at 44.1 kHz for MP3-only players; CBR
+/// because some of them misreport the length of VBR files that lack a Xing header.
+const MP3: LiveProfile = LiveProfile {
+    name: "mp3",
+    content_type: "audio/mpeg",
+    encoders: &["libmp3lame"],
+    audio_only: true,
+    args: |config| {
+        let bitrate = format!("{}k", config.mp3_bitrate_kbps.clamp(32, 320));
+        let mut args = owned(&["-vn", "-c:a", "libmp3lame", "-ar", "44100", "-b:a"]);
+        args.push(bitrate);
+        args.extend(owned(&["-f", "mp3"]));
+        args
+    },
+};
+
+const PROFILES: &[&LiveProfile] = &[&THREE_GP, &MP3];
+
+/// The profile for a `format=` value; `audio_only` limits it to the ones without video.
+pub fn profile(format: &str, audio_only: bool) -> Option<&'static LiveProfile> {
+    PROFILES
+        .iter()
+        .copied()
+        .filter(|p| p.audio_only || !audio_only)
+        .find(|p| p.name.eq_ignore_ascii_case(format))
+}
+
+pub fn profile_names(audio_only: bool) -> Vec<&'static str> {
+    PROFILES
+        .iter()
+        .filter(|p| p.audio_only || !audio_only)
+        .map(|p| p.name)
+        .collect()
+}
+
+/// Whether a transcode gets `loudnorm`: `normalize=true/false` when given, else
+/// `video.loudnorm.enabled` for the audio-only profiles.
+pub fn normalize_requested(normalize: Option<&String>, profile: &LiveProfile, config: &VideoConfig) -> bool {
+    match normalize.map(|n| n.to_ascii_lowercase()) {
+        Some(n) if n == "true" || n == "1" => true,
+        Some(n) if n == "false" || n == "0" => false,
+        _ => config.loudnorm.enabled && profile.audio_only,
+    }
+}
+
+/// Single-pass EBU R128 leveling. The two-pass mode would need the whole track before the
+/// first byte, which a live transcode doesn't have; one pass follows the track as it plays.
+fn loudnorm_filter(config: &LoudnormConfig) -> String {
+    format!(
+        "loudnorm=I={}:TP={}:LRA={}",
+        config.target_lufs.clamp(-70.0, -5.0),
+        config.true_peak.clamp(-9.0, 0.0),
+        config.lra.clamp(1.0, 50.0)
+    )
+}
+
+/// Feeds `source_url` into ffmpeg's stdin until the download ends or ffmpeg stops reading.
+fn pump_download(source_url: String, user_agent: String, mut stdin: std::process::ChildStdin) {
+    let client = crate::http_client::blocking_builder().build().unwrap_or_default();
+    let mut response = match client
+        .get(&source_url)
+        .header("User-Agent", &user_agent)
+        .header("Referer", "https://www.youtube.com")
+        .header("Origin", "https://www.youtube.com")
+        .send()
+    {
+        Ok(r) => r,
+        Err(e) => {
+            log::warn!("Live transcode download failed: {}", e);
+            return;
+        }
+    };
+    let mut buffer = [0u8; 65536];
+    loop {
+        match response.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(n) => {
+                // ffmpeg was killed (client gone) or gave up on the input
+                if stdin.write_all(&buffer[..n]).is_err() {
+                    break;
+                }
+            }
+            Err(e) => {
+                log::warn!("Live transcode download interrupted: {}", e);
+                break;
+            }
+        }
+    }
+}
+
+/// Transcodes `source` (a URL, or a file from the offline library) to `profile` and streams
+/// ffmpeg's output as it is produced. `input_args` go before `-i` (a clip's `-ss`/`-t`);
+/// `normalize` levels the audio with `video.loudnorm`.
+pub fn stream(
+    source: &str,
+    user_agent: &str,
+    profile: &'static LiveProfile,
+    input_args: Vec<String>,
+    normalize: bool,
+    config: &VideoConfig,
+    permit: Option<tokio::sync::OwnedSemaphorePermit>,
+) -> HttpResponse {
+    let is_url = source.starts_with("http://") || source.starts_with("https://");
+    let mut cmd = Command::new(crate::routes::video::ffmpeg_binary());
+    cmd.args(["-hide_banner", "-loglevel", "error"]);
+    cmd.args(&input_args);
+    cmd.args(["-i", if is_url { "pipe:0" } else { source }]);
+    if normalize {
+        cmd.arg("-af").arg(loudnorm_filter(&config.loudnorm));
+    }
+    cmd.args((profile.args)(config));
+    cmd.arg("pipe:1");
+    cmd.stdin(if is_url { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    let mut child = match cmd.spawn() {
+        Ok(c) => c,
+        Err(e) => {
+            return HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "FFmpeg failed to start",
+                "details": e.to_string()
+            }));
+        }
+    };
+
+    if let Some(stdin) = child.stdin.take() {
+        let source_url = source.to_string();
+        let ua = user_agent.to_string();
+        std::thread::spawn(move || pump_download(source_url, ua, stdin));
+    }
+
+    let (tx, rx) = mpsc::channel::<std::result::Result<Bytes, std::io::Error>>(8);
+    let profile_name = profile.name;
+    std::thread::spawn(move || {
+        let _permit = permit;
+        if let Some(mut stdout) = child.stdout.take() {
+            let mut buffer = [0u8; 65536];
+            loop {
+                match stdout.read(&mut buffer) {
+                    Ok(0) => break,
+                    Ok(n) => {
+                        if tx.blocking_send(Ok(Bytes::copy_from_slice(&buffer[..n]))).is_err() {
+                            // Client went away; this also ends the download via a broken stdin
+                            let _ = child.kill();
+                            break;
+                        }
+                    }
+                    Err(e) => {
+                        let _ = tx.blocking_send(Err(e));
+                        break;
+                    }
+                }
+            }
+        }
+        if let Ok(output) = child.wait_with_output() {
+            if !output.status.success() && !output.stderr.is_empty() {
+                log::warn!(
+                    "FFmpeg live {} transcode failed: {}",
+                    profile_name,
+                    String::from_utf8_lossy(&output.stderr)
+                );
+            }
+        }
+    });
+
+    let stream = ReceiverStream::new(rx).map(|r| r.map_err(actix_web::error::ErrorInternalServerError));
+    HttpResponse::Ok()
+        .insert_header((CONTENT_TYPE, HeaderValue::from_static(profile.content_type)))
+        .insert_header(("Cache-Control", "no-store"))
+        .streaming(stream)
+}
@@ -0,0 +1,168 @@
+//! Finished codec= conversions kept on disk (`cache.transcodes`), one file per video and profile
+//! (`<dir>/<video_id>_<codec>[_clip].<ext>`). The second phone asking for the same 3GP gets the
+//! file instead of a new ffmpeg run. Files are evicted least-recently-used first once
+//! `max_size_mb` is exceeded, or when untouched for `max_age_hours`.
+
+use serde::Serialize;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime};
+use utoipa::ToSchema;
+
+use crate::config::TranscodeCacheConfig;
+
+static HITS: AtomicU64 = AtomicU64::new(0);
+static MISSES: AtomicU64 = AtomicU64::new(0);
+static EVICTIONS: AtomicU64 = AtomicU64::new(0);
+static TMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Serialize, ToSchema)]
+pub struct TranscodeCacheStats {
+    pub enabled: bool,
+    pub entries: u64,
+    pub size_bytes: u64,
+    pub max_size_bytes: u64,
+    /// Since start.
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+}
+
+fn root(config: &TranscodeCacheConfig) -> PathBuf {
+    if config.dir.trim().is_empty() {
+        env::temp_dir().join("yt_api_transcode_cache")
+    } else {
+        PathBuf::from(&config.dir)
+    }
+}
+
+fn max_bytes(config: &TranscodeCacheConfig) -> u64 {
+    u64::from(config.max_size_mb) * 1024 * 1024
+}
+
+fn max_age(config: &TranscodeCacheConfig) -> Duration {
+    Duration::from_secs(u64::from(config.max_age_hours) * 3600)
+}
+
+/// File name for a (video, profile) pair; anything but `[A-Za-z0-9_-]` is dropped from both.
+pub fn key(video_id: &str, profile: &str) -> String {
+    format!("{}_{}", video_id, profile)
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+        .collect()
+}
+
+fn entry_path(config: &TranscodeCacheConfig, key: &str, ext: &str) -> PathBuf {
+    root(config).join(format!("{}.{}", key, ext))
+}
+
+/// Path of a cached conversion, counted as a hit (and marked recently used) or a miss.
+pub fn lookup(config: &TranscodeCacheConfig, key: &str, ext: &str) -> Option<PathBuf> {
+    if !config.enabled {
+        return None;
+    }
+    let path = entry_path(config, key, ext);
+    let fresh = fs::metadata(&path)
+        .ok()
+        .filter(|m| m.is_file() && m.len() > 0)
+        .and_then(|m| m.modified().ok())
+        .map(|modified| {
+            SystemTime::now().duration_since(modified).unwrap_or(Duration::ZERO) <= max_age(config)
+        })
+        .unwrap_or(false);
+    if !fresh {
+        MISSES.fetch_add(1, Ordering::Relaxed);
+        return None;
+    }
+    if let Ok(f) = fs::File::options().write(true).open(&path) {
+        let _ = f.set_modified(SystemTime::now());
+    }
+    HITS.fetch_add(1, Ordering::Relaxed);
+    Some(path)
+}
+
+/// Copies a finished conversion into the cache, then evicts down to the size limit.
+/// Blocking; called from the conversion thread.
+pub fn store(config: &TranscodeCacheConfig, key: &str, ext: &str, file: &Path) {
+    if !config.enabled {
+        return;
+    }
+    let dir = root(config);
+    let tmp = dir.join(format!(
+        "{}.{}.tmp{}",
+        key,
+        ext,
+        TMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let stored = fs::create_dir_all(&dir)
+        .and_then(|_| fs::copy(file, &tmp))
+        .and_then(|_| fs::rename(&tmp, entry_path(config, key, ext)));
+    if let Err(e) = stored {
+        let _ = fs::remove_file(&tmp);
+        log::warn!("Transcode cache: failed to store {}: {}", key, e);
+        return;
+    }
+    evict(config);
+}
+
+/// Cached files with their last use and size, skipping in-flight `.tmp` copies.
+fn entries(dir: &Path) -> Vec<(PathBuf, SystemTime, u64)> {
+    fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter(|e| !e.file_name().to_string_lossy().contains(".tmp"))
+                .filter_map(|e| {
+                    let meta = e.metadata().ok().filter(|m| m.is_file())?;
+                    let used = meta.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                    Some((e.path(), used, meta.len()))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn evict(config: &TranscodeCacheConfig) {
+    let now = SystemTime::now();
+    let max_age = max_age(config);
+    let mut files = entries(&root(config));
+    files.retain(|(path, used, _)| {
+        let expired = now.duration_since(*used).unwrap_or(Duration::ZERO) > max_age;
+        if expired && fs::remove_file(path).is_ok() {
+            EVICTIONS.fetch_add(1, Ordering::Relaxed);
+        }
+        !expired
+    });
+
+    let limit = max_bytes(config);
+    let mut usage: u64 = files.iter().map(|(_, _, size)| size).sum();
+    if usage <= limit {
+        return;
+    }
+    files.sort_by_key(|(_, used, _)| *used);
+    for (path, _, size) in &files {
+        if usage <= limit / 10 * 9 {
+            break;
+        }
+        if fs::remove_file(path).is_ok() {
+            usage = usage.saturating_sub(*size);
+            EVICTIONS.fetch_add(1, Ordering::Relaxed);
+            log::debug!("Transcode cache: evicted {}", path.display());
+        }
+    }
+}
+
+pub fn stats(config: &TranscodeCacheConfig) -> TranscodeCacheStats {
+    let files = entries(&root(config));
+    TranscodeCacheStats {
+        enabled: config.enabled,
+        entries: files.len() as u64,
+        size_bytes: files.iter().map(|(_, _, size)| size).sum(),
+        max_size_bytes: max_bytes(config),
+        hits: HITS.load(Ordering::Relaxed),
+        misses: MISSES.load(Ordering::Relaxed),
+        evictions: EVICTIONS.load(Ordering::Relaxed),
+    }
+}
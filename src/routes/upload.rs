@@ -0,0 +1,557 @@
+//! Video uploads (`upload.*`) through the Data API's resumable protocol, for devices that record
+//! locally and cannot talk to Google themselves. `POST /upload` opens a session, the file goes up
+//! in chunks to `/upload/{upload_id}` (PUT, or POST for clients like J2ME that only have that),
+//! and `/upload/{upload_id}/finalize` sets the title, description and privacy once it is in.
+//! Google's session URL never leaves the server; the random `upload_id` stands in for it.
+
+use actix_web::http::header::CONTENT_RANGE;
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use futures_util::StreamExt;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use utoipa::ToSchema;
+
+use crate::config::Config;
+use crate::routes::auth::{AuthConfig, TokenStore};
+use crate::routes::base_url;
+use crate::routes::messages::Msg;
+use crate::routes::oauth::refresh_access_token;
+use crate::routes::quota::{self, UPLOAD_COST, WRITE_COST};
+use crate::routes::token_scopes::{self, Permission};
+
+/// "People & Blogs", YouTube's own default for uploads.
+const DEFAULT_CATEGORY_ID: &str = "22";
+const PRIVACY_VALUES: [&str; 3] = ["private", "unlisted", "public"];
+
+struct UploadSession {
+    session_uri: String,
+    refresh_token: String,
+    size: u64,
+    received: u64,
+    video_id: Option<String>,
+    title: String,
+    description: String,
+    privacy: String,
+    created: Instant,
+}
+
+lazy_static! {
+    static ref SESSIONS: Mutex<HashMap<String, UploadSession>> = Mutex::new(HashMap::new());
+}
+
+fn disabled() -> HttpResponse {
+    HttpResponse::NotFound().json(json!({ "error": "Uploads are disabled on this instance" }))
+}
+
+fn unknown_upload() -> HttpResponse {
+    HttpResponse::NotFound().json(json!({ "error": "Unknown or expired upload_id" }))
+}
+
+fn prune_expired(sessions: &mut HashMap<String, UploadSession>, ttl: Duration) {
+    sessions.retain(|_, s| s.created.elapsed() < ttl);
+}
+
+fn valid_privacy(value: &str) -> Option<String> {
+    let value = value.trim().to_ascii_lowercase();
+    PRIVACY_VALUES.contains(&value.as_str()).then_some(value)
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct UploadStartForm {
+    /// OAuth refresh token; the frontend session is used when omitted.
+    pub token: Option<String>,
+    /// Total file size in bytes.
+    pub size: u64,
+    /// MIME type of the file, e.g. `video/3gpp`; default `video/*`.
+    pub content_type: Option<String>,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    /// private (default), unlisted or public.
+    pub privacy: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct UploadFinalizeForm {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub privacy: Option<String>,
+    pub category_id: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct UploadStatusResponse {
+    pub upload_id: String,
+    /// Where the chunks go (PUT or POST).
+    pub upload_url: String,
+    /// `incomplete` or `complete`.
+    pub status: String,
+    /// Bytes Google has stored so far; the next chunk starts here.
+    pub received: u64,
+    pub size: u64,
+    /// Set once the last chunk is in.
+    pub video_id: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct UploadFinalizeResponse {
+    pub video_id: String,
+    pub title: String,
+    pub privacy: String,
+    pub watch_url: String,
+}
+
+fn status_response(req: &HttpRequest, config: &Config, upload_id: &str, session: &UploadSession) -> UploadStatusResponse {
+    UploadStatusResponse {
+        upload_id: upload_id.to_string(),
+        upload_url: format!("{}/upload/{}", base_url(req, config).trim_end_matches('/'), upload_id),
+        status: if session.video_id.is_some() { "complete" } else { "incomplete" }.to_string(),
+        received: session.received,
+        size: session.size,
+        video_id: session.video_id.clone(),
+    }
+}
+
+/// Bytes stored according to a 308's `Range: bytes=0-N` header (none yet when it is missing).
+fn received_from_range(resp: &reqwest::Response) -> u64 {
+    resp.headers()
+        .get(reqwest::header::RANGE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.rsplit('-').next())
+        .and_then(|end| end.trim().parse::<u64>().ok())
+        .map(|end| end + 1)
+        .unwrap_or(0)
+}
+
+enum ChunkOutcome {
+    Incomplete(u64),
+    Complete(String),
+    Expired,
+}
+
+/// PUTs `body` to the session URL; an empty body with `bytes */size` only asks for the status.
+async fn relay_chunk(session_uri: &str, content_range: &str, body: web::Bytes) -> Result<ChunkOutcome, String> {
+    let resp = crate::http_client::shared()
+        .put(session_uri)
+        .header(reqwest::header::CONTENT_RANGE, content_range)
+        .header(reqwest::header::CONTENT_LENGTH, body.len())
+        .body(body)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    match resp.status().as_u16() {
+        308 => Ok(ChunkOutcome::Incomplete(received_from_range(&resp))),
+        200 | 201 => {
+            let video: serde_json::Value = resp.json().await.map_err(|e| e.to_string())?;
+            video
+                .get("id")
+                .and_then(|id| id.as_str())
+                .map(|id| ChunkOutcome::Complete(id.to_string()))
+                .ok_or_else(|| "Upload finished without a video id".to_string())
+        }
+        404 | 410 => Ok(ChunkOutcome::Expired),
+        status => {
+            let text = resp.text().await.unwrap_or_default();
+            Err(format!("Upload endpoint returned {}: {}", status, text))
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/upload",
+    request_body(content = UploadStartForm, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "Upload session opened", body = UploadStatusResponse),
+        (status = 400, description = "Missing size or invalid privacy"),
+        (status = 401, description = "Not signed in or token rejected"),
+        (status = 403, description = "Token is scoped without actions"),
+        (status = 404, description = "Uploads disabled"),
+        (status = 413, description = "File larger than upload.max_file_bytes"),
+        (status = 429, description = "Daily API quota exceeded"),
+        (status = 502, description = "Google refused to open the session")
+    )
+)]
+pub async fn start_upload(
+    req: HttpRequest,
+    form: web::Form<UploadStartForm>,
+    auth_config: web::Data<AuthConfig>,
+    token_store: web::Data<TokenStore>,
+    data: web::Data<crate::AppState>,
+) -> impl Responder {
+    let config = &data.config;
+    if !config.upload.enabled {
+        return disabled();
+    }
+    let form = form.into_inner();
+    let Some(token) = form
+        .token
+        .filter(|t| !t.trim().is_empty())
+        .or_else(|| token_store.session_token(&req))
+    else {
+        return HttpResponse::Unauthorized().json(json!({ "error": Msg::InvalidRefreshToken.text() }));
+    };
+    if form.size == 0 {
+        return HttpResponse::BadRequest().json(json!({ "error": "size must be the file size in bytes" }));
+    }
+    if config.upload.max_file_bytes > 0 && form.size > config.upload.max_file_bytes {
+        return HttpResponse::PayloadTooLarge().json(json!({
+            "error": "File too large",
+            "details": format!("Uploads are limited to {} bytes", config.upload.max_file_bytes)
+        }));
+    }
+    let privacy = match form.privacy.as_deref() {
+        None | Some("") => "private".to_string(),
+        Some(p) => match valid_privacy(p) {
+            Some(p) => p,
+            None => {
+                return HttpResponse::BadRequest().json(json!({
+                    "error": "privacy must be private, unlisted or public"
+                }));
+            }
+        },
+    };
+    if let Some(resp) = token_scopes::deny(&token, Permission::Actions) {
+        return resp;
+    }
+    if let Some(resp) = quota::charge(&req, config, Some(&token), UPLOAD_COST) {
+        return resp;
+    }
+    let access_token = match refresh_access_token(token.trim(), &auth_config).await {
+        Ok(t) => t,
+        Err(e) => return HttpResponse::Unauthorized().json(json!({ "error": e })),
+    };
+
+    let title = form.title.filter(|t| !t.trim().is_empty()).unwrap_or_else(|| "Untitled".to_string());
+    let description = form.description.unwrap_or_default();
+    let content_type = form
+        .content_type
+        .filter(|c| c.contains('/'))
+        .unwrap_or_else(|| "video/*".to_string());
+    let metadata = json!({
+        "snippet": {
+            "title": title,
+            "description": description,
+            "categoryId": DEFAULT_CATEGORY_ID
+        },
+        "status": { "privacyStatus": privacy }
+    });
+    let resp = match crate::http_client::shared()
+        .post(crate::endpoints::data_api_upload("videos"))
+        .query(&[("uploadType", "resumable"), ("part", "snippet,status")])
+        .header("Authorization", format!("Bearer {}", access_token))
+        .header("X-Upload-Content-Length", form.size.to_string())
+        .header("X-Upload-Content-Type", content_type.as_str())
+        .json(&metadata)
+        .send()
+        .await
+    {
+        Ok(r) => r,
+        Err(e) => {
+            return HttpResponse::BadGateway().json(json!({
+                "error": "Failed to open upload session",
+                "details": e.to_string()
+            }));
+        }
+    };
+    let session_uri = resp
+        .headers()
+        .get(reqwest::header::LOCATION)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let Some(session_uri) = session_uri.filter(|_| resp.status().is_success()) else {
+        let status = resp.status().as_u16();
+        let text = resp.text().await.unwrap_or_default();
+        return HttpResponse::BadGateway().json(json!({
+            "error": "Failed to open upload session",
+            "details": format!("Upload endpoint returned {}: {}", status, text)
+        }));
+    };
+
+    let upload_id = uuid::Uuid::new_v4().simple().to_string();
+    let session = UploadSession {
+        session_uri,
+        refresh_token: token.trim().to_string(),
+        size: form.size,
+        received: 0,
+        video_id: None,
+        title,
+        description,
+        privacy,
+        created: Instant::now(),
+    };
+    let body = status_response(&req, config, &upload_id, &session);
+    if let Ok(mut sessions) = SESSIONS.lock() {
+        prune_expired(&mut sessions, config.upload.session_ttl());
+        sessions.insert(upload_id, session);
+    }
+    HttpResponse::Ok().json(body)
+}
+
+#[utoipa::path(
+    put,
+    path = "/upload/{upload_id}",
+    params(
+        ("upload_id" = String, Path, description = "From POST /upload"),
+        ("Content-Range" = Option<String>, Header, description = "bytes first-last/size; defaults to the next bytes after `received`")
+    ),
+    request_body(content = Vec<u8>, content_type = "application/octet-stream"),
+    responses(
+        (status = 200, description = "Chunk stored; `complete` with video_id after the last one", body = UploadStatusResponse),
+        (status = 404, description = "Unknown or expired upload_id, or uploads disabled"),
+        (status = 410, description = "Google discarded the session; start over"),
+        (status = 413, description = "Chunk larger than upload.max_chunk_bytes"),
+        (status = 502, description = "Google rejected the chunk")
+    )
+)]
+pub async fn upload_chunk(
+    req: HttpRequest,
+    path: web::Path<String>,
+    mut payload: web::Payload,
+    data: web::Data<crate::AppState>,
+) -> impl Responder {
+    let config = &data.config;
+    if !config.upload.enabled {
+        return disabled();
+    }
+    let upload_id = path.into_inner();
+    let (session_uri, received, size) = match SESSIONS.lock().ok().and_then(|s| {
+        s.get(&upload_id)
+            .filter(|s| s.created.elapsed() < config.upload.session_ttl())
+            .map(|s| (s.session_uri.clone(), s.received, s.size))
+    }) {
+        Some(s) => s,
+        None => return unknown_upload(),
+    };
+
+    // The global body limit is meant for API calls, so chunks are read with their own
+    let limit = config.upload.max_chunk_bytes as usize;
+    let mut body = web::BytesMut::new();
+    while let Some(chunk) = payload.next().await {
+        let chunk = match chunk {
+            Ok(c) => c,
+            Err(e) => {
+                return HttpResponse::BadRequest().json(json!({
+                    "error": "Failed to read chunk",
+                    "details": e.to_string()
+                }));
+            }
+        };
+        if body.len() + chunk.len() > limit {
+            return HttpResponse::PayloadTooLarge().json(json!({
+                "error": "Chunk too large",
+                "details": format!("Send at most {} bytes per request", limit)
+            }));
+        }
+        body.extend_from_slice(&chunk);
+    }
+    if body.is_empty() {
+        return HttpResponse::BadRequest().json(json!({ "error": "Empty chunk" }));
+    }
+    let content_range = req
+        .headers()
+        .get(CONTENT_RANGE)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("bytes {}-{}/{}", received, received + body.len() as u64 - 1, size));
+
+    let outcome = relay_chunk(&session_uri, &content_range, body.freeze()).await;
+    finish_relay(&req, config, &upload_id, outcome)
+}
+
+#[utoipa::path(
+    get,
+    path = "/upload/{upload_id}",
+    params(("upload_id" = String, Path, description = "From POST /upload")),
+    responses(
+        (status = 200, description = "How much Google has stored, to resume from", body = UploadStatusResponse),
+        (status = 404, description = "Unknown or expired upload_id, or uploads disabled"),
+        (status = 410, description = "Google discarded the session; start over")
+    )
+)]
+pub async fn upload_status(
+    req: HttpRequest,
+    path: web::Path<String>,
+    data: web::Data<crate::AppState>,
+) -> impl Responder {
+    let config = &data.config;
+    if !config.upload.enabled {
+        return disabled();
+    }
+    let upload_id = path.into_inner();
+    let (session_uri, size, done) = match SESSIONS.lock().ok().and_then(|s| {
+        s.get(&upload_id)
+            .filter(|s| s.created.elapsed() < config.upload.session_ttl())
+            .map(|s| (s.session_uri.clone(), s.size, s.video_id.is_some()))
+    }) {
+        Some(s) => s,
+        None => return unknown_upload(),
+    };
+    if done {
+        return match SESSIONS.lock().ok().as_ref().and_then(|s| s.get(&upload_id)) {
+            Some(session) => HttpResponse::Ok().json(status_response(&req, config, &upload_id, session)),
+            None => unknown_upload(),
+        };
+    }
+    let outcome = relay_chunk(&session_uri, &format!("bytes */{}", size), web::Bytes::new()).await;
+    finish_relay(&req, config, &upload_id, outcome)
+}
+
+/// Stores what Google reported about the session and answers with its status.
+fn finish_relay(
+    req: &HttpRequest,
+    config: &Config,
+    upload_id: &str,
+    outcome: Result<ChunkOutcome, String>,
+) -> HttpResponse {
+    let Ok(mut sessions) = SESSIONS.lock() else {
+        return unknown_upload();
+    };
+    let outcome = match outcome {
+        Ok(ChunkOutcome::Expired) => {
+            sessions.remove(upload_id);
+            return HttpResponse::Gone().json(json!({
+                "error": "Upload session expired on YouTube's side; start a new upload"
+            }));
+        }
+        Ok(o) => o,
+        Err(e) => {
+            return HttpResponse::BadGateway().json(json!({
+                "error": "Failed to upload chunk",
+                "details": e
+            }));
+        }
+    };
+    let Some(session) = sessions.get_mut(upload_id) else {
+        return unknown_upload();
+    };
+    match outcome {
+        ChunkOutcome::Incomplete(received) => session.received = received.min(session.size),
+        ChunkOutcome::Complete(video_id) => {
+            session.received = session.size;
+            session.video_id = Some(video_id);
+        }
+        ChunkOutcome::Expired => {}
+    }
+    HttpResponse::Ok().json(status_response(req, config, upload_id, session))
+}
+
+#[utoipa::path(
+    post,
+    path = "/upload/{upload_id}/finalize",
+    params(("upload_id" = String, Path, description = "From POST /upload")),
+    request_body(content = UploadFinalizeForm, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "Metadata set", body = UploadFinalizeResponse),
+        (status = 400, description = "Invalid privacy"),
+        (status = 401, description = "Token rejected"),
+        (status = 404, description = "Unknown or expired upload_id, or uploads disabled"),
+        (status = 409, description = "Not all bytes are uploaded yet"),
+        (status = 502, description = "videos.update failed")
+    )
+)]
+pub async fn finalize_upload(
+    req: HttpRequest,
+    path: web::Path<String>,
+    form: web::Form<UploadFinalizeForm>,
+    auth_config: web::Data<AuthConfig>,
+    data: web::Data<crate::AppState>,
+) -> impl Responder {
+    let config = &data.config;
+    if !config.upload.enabled {
+        return disabled();
+    }
+    let upload_id = path.into_inner();
+    let form = form.into_inner();
+    let snapshot = SESSIONS.lock().ok().and_then(|s| {
+        s.get(&upload_id).map(|s| {
+            (
+                s.refresh_token.clone(),
+                s.video_id.clone(),
+                s.title.clone(),
+                s.description.clone(),
+                s.privacy.clone(),
+            )
+        })
+    });
+    let Some((token, video_id, title, description, privacy)) = snapshot else {
+        return unknown_upload();
+    };
+    let Some(video_id) = video_id else {
+        return HttpResponse::Conflict().json(json!({
+            "error": "Upload is not complete yet",
+            "details": "Send the remaining chunks, then finalize"
+        }));
+    };
+    let privacy = match form.privacy.as_deref().filter(|p| !p.is_empty()) {
+        None => privacy,
+        Some(p) => match valid_privacy(p) {
+            Some(p) => p,
+            None => {
+                return HttpResponse::BadRequest().json(json!({
+                    "error": "privacy must be private, unlisted or public"
+                }));
+            }
+        },
+    };
+    let title = form.title.filter(|t| !t.trim().is_empty()).unwrap_or(title);
+    let description = form.description.unwrap_or(description);
+    let category_id = form
+        .category_id
+        .filter(|c| !c.is_empty() && c.chars().all(|ch| ch.is_ascii_digit()))
+        .unwrap_or_else(|| DEFAULT_CATEGORY_ID.to_string());
+
+    if let Some(resp) = quota::charge(&req, config, Some(&token), WRITE_COST) {
+        return resp;
+    }
+    let access_token = match refresh_access_token(&token, &auth_config).await {
+        Ok(t) => t,
+        Err(e) => return HttpResponse::Unauthorized().json(json!({ "error": e })),
+    };
+    let body = json!({
+        "id": video_id,
+        "snippet": {
+            "title": title,
+            "description": description,
+            "categoryId": category_id
+        },
+        "status": { "privacyStatus": privacy }
+    });
+    let result = crate::http_client::shared()
+        .put(crate::endpoints::data_api("videos"))
+        .query(&[("part", "snippet,status")])
+        .header("Authorization", format!("Bearer {}", access_token))
+        .json(&body)
+        .send()
+        .await;
+    match result {
+        Ok(resp) if resp.status().is_success() => {}
+        Ok(resp) => {
+            let status = resp.status().as_u16();
+            let text = resp.text().await.unwrap_or_default();
+            return HttpResponse::BadGateway().json(json!({
+                "error": "Failed to update video",
+                "details": format!("videos.update returned {}: {}", status, text)
+            }));
+        }
+        Err(e) => {
+            return HttpResponse::BadGateway().json(json!({
+                "error": "Failed to update video",
+                "details": e.to_string()
+            }));
+        }
+    }
+
+    if let Ok(mut sessions) = SESSIONS.lock() {
+        sessions.remove(&upload_id);
+    }
+    HttpResponse::Ok().json(UploadFinalizeResponse {
+        watch_url: format!("https://www.youtube.com/watch?v={}", video_id),
+        video_id,
+        title,
+        privacy,
+    })
+}
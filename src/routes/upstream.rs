@@ -0,0 +1,76 @@
+//! Turning YouTube / Google API error bodies into something useful for logs and clients.
+//! Google returns `{"error": {"code", "message", "status", "errors": [{"reason"}]}}` for
+//! both the Data API (quotaExceeded, keyInvalid, ...) and InnerTube.
+
+use actix_web::HttpResponse;
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde_json::{json, Value};
+
+lazy_static! {
+    static ref KEY_PARAM_REGEX: Regex =
+        Regex::new(r"(?i)(key|token|access_token)=[^&\s]+").expect("valid regex");
+    static ref API_KEY_REGEX: Regex = Regex::new(r"AIza[0-9A-Za-z_-]{20,}").expect("valid regex");
+}
+
+/// Strips keys/tokens and control characters, and caps the length, so the reason can be returned to clients.
+pub fn sanitize_reason(reason: &str) -> String {
+    let cleaned = KEY_PARAM_REGEX.replace_all(reason, "$1=***");
+    let cleaned = API_KEY_REGEX.replace_all(&cleaned, "***");
+    cleaned
+        .chars()
+        .filter(|c| !c.is_control())
+        .take(200)
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+/// Extracts `(status code, reason)` from a Google error body, if it is one.
+pub fn upstream_error(body: &Value) -> Option<(u16, String)> {
+    let err = body.get("error")?;
+    if !err.is_object() {
+        return None;
+    }
+    let code = err.get("code").and_then(|c| c.as_u64()).unwrap_or(0) as u16;
+    let reason = err
+        .get("errors")
+        .and_then(|e| e.as_array())
+        .and_then(|arr| arr.first())
+        .and_then(|e| e.get("reason"))
+        .and_then(|r| r.as_str())
+        .or_else(|| err.get("status").and_then(|s| s.as_str()))
+        .or_else(|| err.get("message").and_then(|m| m.as_str()))
+        .unwrap_or("unknown");
+    Some((code, sanitize_reason(reason)))
+}
+
+/// Logs and counts an upstream error; returns the `(status, reason)` pair for the caller.
+pub fn report(source: &str, body: &Value) -> Option<(u16, String)> {
+    let (code, reason) = upstream_error(body)?;
+    let message = body
+        .get("error")
+        .and_then(|e| e.get("message"))
+        .and_then(|m| m.as_str())
+        .map(sanitize_reason)
+        .unwrap_or_default();
+    log::warn!(
+        "Upstream {} returned {}: {} {}",
+        source,
+        code,
+        reason,
+        message
+    );
+    crate::routes::metrics::record_upstream_error(source, code, &reason);
+    Some((code, reason))
+}
+
+/// 502 with `upstream_reason` when `body` is a Google error, `None` otherwise.
+pub fn error_response(source: &str, body: &Value) -> Option<HttpResponse> {
+    let (code, reason) = report(source, body)?;
+    Some(HttpResponse::BadGateway().json(json!({
+        "error": format!("{} returned an error", source),
+        "upstream_status": code,
+        "upstream_reason": reason,
+    })))
+}
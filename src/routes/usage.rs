@@ -0,0 +1,340 @@
+//! Data usage per client and day (`usage`). Every response body is metered as it goes out, so
+//! long streams count as they play rather than when they end. Clients are told apart by their
+//! signed-in session when they have one, else by IP (forwarded headers only count from
+//! `server.trusted_proxies`, so nobody can claim an exempt address or another client's
+//! allowance); days roll over at 00:00 UTC. With a cap a
+//! client over it gets 429 on new requests and its running streams end at the next chunk.
+//! `/usage` shows a client its own figures, and every client's to the admin (or to anyone with
+//! `usage.public`, for a household that wants to see which device is eating the data).
+
+use actix_web::{
+    body::{BodySize, EitherBody, MessageBody},
+    dev::{Service, ServiceRequest, ServiceResponse, Transform},
+    http::header::RETRY_AFTER,
+    web, Error, HttpRequest, HttpResponse, Responder,
+};
+use bytes::Bytes;
+use futures_util::future::LocalBoxFuture;
+use lazy_static::lazy_static;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::future::{ready, Ready};
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::task::{Context, Poll};
+use utoipa::ToSchema;
+
+use crate::config::UsageConfig;
+use crate::routes::auth::TokenStore;
+use crate::routes::messages::Msg;
+use crate::routes::now_secs;
+
+#[derive(Default)]
+struct ClientUsage {
+    bytes: u64,
+    requests: u64,
+    last_ip: String,
+    last_seen: u64,
+}
+
+lazy_static! {
+    /// Day number (days since the epoch) -> client key -> usage.
+    static ref DAYS: Mutex<BTreeMap<u64, HashMap<String, ClientUsage>>> = Mutex::new(BTreeMap::new());
+}
+
+fn date_of(day: u64) -> String {
+    chrono::DateTime::from_timestamp((day * 86400) as i64, 0)
+        .map(|d| d.format("%Y-%m-%d").to_string())
+        .unwrap_or_default()
+}
+
+/// `token:...` for a signed-in session (as in the audit log), else `ip:<address>`.
+fn client_key(req: &HttpRequest, ip: &str) -> String {
+    req.app_data::<web::Data<TokenStore>>()
+        .and_then(|store| store.session_token(req))
+        .map(|token| crate::routes::audit::token_session(&token))
+        .unwrap_or_else(|| format!("ip:{}", ip))
+}
+
+/// The client's cap in bytes: its entry in `client_caps_mb` (by key or bare IP), else
+/// `daily_cap_mb`. `None` without a cap.
+fn cap_bytes(config: &UsageConfig, key: &str, ip: &str) -> Option<u64> {
+    let mb = config
+        .client_caps_mb
+        .get(key)
+        .or_else(|| config.client_caps_mb.get(ip))
+        .copied()
+        .unwrap_or(config.daily_cap_mb);
+    (mb > 0).then(|| mb * 1024 * 1024)
+}
+
+fn bytes_today(key: &str) -> u64 {
+    let today = now_secs() / 86400;
+    DAYS.lock()
+        .ok()
+        .and_then(|days| days.get(&today).and_then(|d| d.get(key)).map(|u| u.bytes))
+        .unwrap_or(0)
+}
+
+/// Adds `bytes` (and a request, if `request`) to today's figures and returns today's total.
+fn add(key: &str, ip: &str, bytes: u64, request: bool, history_days: u32) -> u64 {
+    let now = now_secs();
+    let today = now / 86400;
+    let Ok(mut days) = DAYS.lock() else {
+        return 0;
+    };
+    if !days.contains_key(&today) {
+        let oldest = today.saturating_sub(u64::from(history_days.max(1)) - 1);
+        days.retain(|day, _| *day >= oldest);
+    }
+    let usage = days.entry(today).or_default().entry(key.to_string()).or_default();
+    usage.bytes += bytes;
+    if request {
+        usage.requests += 1;
+        usage.last_ip = ip.to_string();
+    }
+    usage.last_seen = now;
+    usage.bytes
+}
+
+/// Who a metered response is charged to.
+struct Meter {
+    key: String,
+    ip: String,
+    cap: Option<u64>,
+    history_days: u32,
+}
+
+/// A response body that charges its chunks to a client as they are sent, and ends early once
+/// the client is over its cap.
+pub struct MeteredBody<B> {
+    body: Pin<Box<B>>,
+    meter: Meter,
+    cut: bool,
+}
+
+impl<B: MessageBody> MessageBody for MeteredBody<B> {
+    type Error = B::Error;
+
+    fn size(&self) -> BodySize {
+        self.body.size()
+    }
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Bytes, Self::Error>>> {
+        let this = self.get_mut();
+        if this.cut {
+            return Poll::Ready(None);
+        }
+        let polled = this.body.as_mut().poll_next(cx);
+        if let Poll::Ready(Some(Ok(chunk))) = &polled {
+            let m = &this.meter;
+            let total = add(&m.key, &m.ip, chunk.len() as u64, false, m.history_days);
+            if m.cap.is_some_and(|cap| total >= cap) && matches!(this.body.size(), BodySize::Stream) {
+                crate::log::info!("Usage: {} reached its cap, ending the stream", m.key);
+                this.cut = true;
+            }
+        }
+        polled
+    }
+}
+
+/// Meters every response under `usage`; see the module docs.
+pub struct UsageMeter;
+
+impl<S, B> Transform<S, ServiceRequest> for UsageMeter
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<EitherBody<MeteredBody<B>, B>>>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = UsageMeterMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(UsageMeterMiddleware { service }))
+    }
+}
+
+pub struct UsageMeterMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for UsageMeterMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<EitherBody<MeteredBody<B>, B>>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let ip = crate::routes::bandwidth::client_ip(req.request());
+        let metered = req.app_data::<web::Data<crate::AppState>>().and_then(|data| {
+            let usage = &data.config.usage;
+            (usage.enabled && !usage.exempt_ips.iter().any(|e| e.trim() == ip)).then(|| {
+                let key = client_key(req.request(), &ip);
+                let cap = cap_bytes(usage, &key, &ip);
+                (key, cap, usage.history_days)
+            })
+        });
+        let Some((key, cap, history_days)) = metered else {
+            let fut = self.service.call(req);
+            return Box::pin(async move { fut.await.map(|res| res.map_into_right_body().map_into_left_body()) });
+        };
+
+        // /usage stays reachable so the client can see why it is cut off
+        if req.path() != "/usage" {
+            if let Some(cap) = cap {
+                let used = bytes_today(&key);
+                if used >= cap {
+                    let now = now_secs();
+                    let retry_after = (now / 86400 + 1) * 86400 - now;
+                    let res = HttpResponse::TooManyRequests()
+                        .insert_header((RETRY_AFTER, retry_after.to_string()))
+                        .json(serde_json::json!({
+                            "error": Msg::DataCapReached.text(),
+                            "details": format!("{} of {} MB used today", used / (1024 * 1024), cap / (1024 * 1024)),
+                            "retry_after": retry_after
+                        }));
+                    return Box::pin(ready(Ok(req.into_response(res).map_into_right_body())));
+                }
+            }
+        }
+
+        add(&key, &ip, 0, true, history_days);
+        let meter = Meter {
+            key,
+            ip,
+            cap,
+            history_days,
+        };
+        let fut = self.service.call(req);
+        Box::pin(async move {
+            let res = fut.await?;
+            Ok(res
+                .map_body(|_, body| MeteredBody {
+                    body: Box::pin(body),
+                    meter,
+                    cut: false,
+                })
+                .map_into_left_body()
+                .map_into_left_body())
+        })
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ClientUsageEntry {
+    /// `ip:<address>` or `token:<fingerprint>` for a signed-in session.
+    pub client: String,
+    /// Address of the client's latest request.
+    pub last_ip: String,
+    pub bytes: u64,
+    pub megabytes: f64,
+    pub requests: u64,
+    /// Unix time of the latest request.
+    pub last_seen: u64,
+    /// Daily cap in bytes, if any.
+    pub cap_bytes: Option<u64>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct UsageDay {
+    /// UTC date, `YYYY-MM-DD`.
+    pub date: String,
+    pub total_bytes: u64,
+    /// Heaviest first.
+    pub clients: Vec<ClientUsageEntry>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct UsageResponse {
+    /// The caller's own client key.
+    pub client: String,
+    /// Whether `days` lists every client or only the caller.
+    pub all_clients: bool,
+    /// Newest first.
+    pub days: Vec<UsageDay>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/usage",
+    params(
+        ("days" = Option<u32>, Query, description = "Days to show, newest first (default 1, at most usage.history_days)"),
+        ("admin_key" = Option<String>, Query, description = "Admin key (or X-Admin-Key header): every client instead of only the caller")
+    ),
+    responses(
+        (status = 200, description = "Bytes sent per client and day", body = UsageResponse),
+        (status = 404, description = "usage is disabled")
+    )
+)]
+pub async fn get_usage(
+    req: HttpRequest,
+    query: web::Query<HashMap<String, String>>,
+    data: web::Data<crate::AppState>,
+) -> impl Responder {
+    let config = &data.config;
+    let usage = &config.usage;
+    if !usage.enabled {
+        return HttpResponse::NotFound().json(serde_json::json!({
+            "error": "Usage accounting is disabled"
+        }));
+    }
+    let ip = crate::routes::bandwidth::client_ip(&req);
+    let own_key = client_key(&req, &ip);
+    let all_clients = usage.public || crate::routes::admin::is_admin(&req, config);
+    let count = query
+        .get("days")
+        .and_then(|d| d.parse::<usize>().ok())
+        .unwrap_or(1)
+        .clamp(1, usage.history_days.max(1) as usize);
+
+    let days = DAYS
+        .lock()
+        .map(|days| {
+            days.iter()
+                .rev()
+                .take(count)
+                .map(|(day, clients)| {
+                    let mut entries: Vec<ClientUsageEntry> = clients
+                        .iter()
+                        .filter(|(key, _)| all_clients || **key == own_key)
+                        .map(|(key, u)| ClientUsageEntry {
+                            client: key.clone(),
+                            last_ip: u.last_ip.clone(),
+                            bytes: u.bytes,
+                            megabytes: (u.bytes as f64 / 10485.76).round() / 100.0,
+                            requests: u.requests,
+                            last_seen: u.last_seen,
+                            cap_bytes: cap_bytes(usage, key, &u.last_ip),
+                        })
+                        .collect();
+                    entries.sort_by_key(|e| std::cmp::Reverse(e.bytes));
+                    UsageDay {
+                        date: date_of(*day),
+                        total_bytes: entries.iter().map(|e| e.bytes).sum(),
+                        clients: entries,
+                    }
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    HttpResponse::Ok()
+        .insert_header(("Cache-Control", "no-store"))
+        .json(UsageResponse {
+            client: own_key,
+            all_clients,
+            days,
+        })
+}
@@ -18,7 +18,7 @@ use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Command;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tokio::sync::Mutex;
@@ -26,15 +26,11 @@ use tokio::task;
 use urlencoding;
 use utoipa::ToSchema;
 
-fn base_url(req: &HttpRequest, config: &crate::config::Config) -> String {
-    if !config.server.main_url.is_empty() {
-        return config.server.main_url.clone();
-    }
-    let info = req.connection_info();
-    let scheme = info.scheme();
-    let host = info.host();
-    format!("{}://{}/", scheme, host.trim_end_matches('/'))
-}
+use crate::config::TranscodeCacheConfig;
+use crate::routes::base_url;
+use crate::routes::device_profile::DeviceProfile;
+use crate::routes::messages::Msg;
+use crate::routes::transcode_cache;
 
 fn extract_ytcfg(html: &str) -> serde_json::Value {
     if let Some(cap) = regex::Regex::new(r"ytcfg\.set\((\{.*?\})\);")
@@ -67,10 +63,12 @@ fn extract_initial_player_response(html: &str) -> serde_json::Value {
     serde_json::Value::Object(serde_json::Map::new())
 }
 
-async fn download_mux_to_temp_file(
+pub(crate) async fn download_mux_to_temp_file(
     video_id: String,
     height: u32,
+    config: &crate::config::Config,
 ) -> Result<PathBuf, String> {
+    let timeout = Duration::from_secs(config.video.ytdlp_download_timeout_secs.max(1));
     let temp_dir = env::temp_dir();
     
     // 1. Имя файла теперь содержит качество: yt_api_video_ID_1080p.mp4
@@ -135,12 +133,8 @@ async fn download_mux_to_temp_file(
     let ffmpeg_path = Path::new(&ffmpeg);
     let ffmpeg_dir = ffmpeg_path.parent().unwrap_or(Path::new(".")).to_string_lossy().to_string();
 
-    let cookie_paths = collect_cookie_paths();
-    let cookie_arg = if let Some(path) = cookie_paths.first() {
-        Some(path.to_string_lossy().to_string())
-    } else {
-        None
-    };
+    let cookie_arg = crate::routes::cookies::rotation(&config.video).into_iter().next();
+    crate::routes::po_token::ensure(config).await;
 
     // Шаблон имени для yt-dlp (он сам подставит расширение)
     // Важно: имя шаблона должно совпадать с ожидаемым final_path, но без расширения .mp4,
@@ -148,8 +142,16 @@ async fn download_mux_to_temp_file(
     let output_template = temp_dir.join(format!("yt_api_video_{}_{}p.%(ext)s", video_id, height));
     let output_template_str = output_template.to_string_lossy().to_string();
 
+    let slot = match yt_dlp_slot(&config.video).await {
+        Ok(slot) => slot,
+        Err(e) => {
+            let _ = fs::remove_file(&lock_path);
+            return Err(e);
+        }
+    };
     let download_result = task::spawn_blocking(move || {
-        let mut cmd = Command::new(&yt_dlp);
+        let _slot = slot;
+        let mut cmd = yt_dlp_command(&yt_dlp);
         
         // --- ВАЖНОЕ ИЗМЕНЕНИЕ ДЛЯ WINDOWS 7 ---
         // 1. Приоритет: H.264 (avc) + AAC (mp4a) <= нужной высоты
@@ -171,7 +173,7 @@ async fn download_mux_to_temp_file(
         // Опционально: можно добавить --postprocessor-args для ffmpeg, чтобы убедиться в faststart
         // cmd.arg("--postprocessor-args").arg("Merger+ffmpeg:-movflags +faststart");
 
-        if let Some(c) = cookie_arg {
+        if let Some(c) = &cookie_arg {
             cmd.arg("--cookies").arg(c);
         }
 
@@ -179,15 +181,13 @@ async fn download_mux_to_temp_file(
 
         log::info!("Starting yt-dlp for {}p...", height);
         
-        let output = cmd
-            .stdin(Stdio::null())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .output()
-            .map_err(|e| format!("Failed to execute yt-dlp: {}", e))?;
+        let output = run_yt_dlp(&mut cmd, timeout)?;
 
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if let Some(c) = &cookie_arg {
+            crate::routes::cookies::report(c, if output.status.success() { Ok(()) } else { Err(&stderr) });
+        }
         if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
             log::error!("yt-dlp failed.\nSTDERR: {}", stderr);
             return Err(format!("yt-dlp error: {}", stderr));
         }
@@ -316,6 +316,40 @@ fn search_number_near(data: &serde_json::Value, words: &[&str]) -> String {
     String::new()
 }
 
+/// The Data API snippet, for the category id and the uploader's full tag list (the player's
+/// `keywords` are often cut short). `None` without API keys or quota.
+async fn video_snippet(
+    video_id: &str,
+    req: &HttpRequest,
+    config: &crate::config::Config,
+) -> Option<crate::routes::data_api::VideoSnippet> {
+    use crate::routes::data_api::{self, ApiList, Video};
+
+    if !config.has_api_key() {
+        return None;
+    }
+    if crate::routes::quota::charge(req, config, None, crate::routes::quota::READ_COST).is_some() {
+        return None;
+    }
+    let url = crate::endpoints::data_api(&format!(
+        "videos?part=snippet&id={}&key={}",
+        urlencoding::encode(video_id),
+        config.get_api_key_rotated()
+    ));
+    let body = match crate::http_client::get_json(&url).await {
+        Ok(body) => body,
+        Err(e) => {
+            log::info!("Error fetching snippet for {}: {}", video_id, e);
+            return None;
+        }
+    };
+    if crate::routes::upstream::report("YouTube Data API", &body).is_some() {
+        return None;
+    }
+    let list = data_api::parse::<ApiList<Video>>("videos.list", body).ok()?;
+    list.items.into_iter().next().and_then(|v| v.snippet)
+}
+
 fn find_likes(next_data: &serde_json::Value) -> String {
     if let Some(contents) = next_data
         .get("contents")
@@ -613,10 +647,30 @@ fn translate_russian_time(time_str: &str) -> String {
     result
 }
 
+/// `heartState` of every `engagementToolbarStateEntityPayload`, by the key comments refer to it
+/// with (`properties.toolbarStateKey`).
+fn comment_heart_states(data: &serde_json::Value) -> HashMap<String, bool> {
+    let mut states = HashMap::new();
+    let mutations = data
+        .pointer("/frameworkUpdates/entityBatchUpdate/mutations")
+        .and_then(|m| m.as_array());
+    for mutation in mutations.into_iter().flatten() {
+        let Some(state) = mutation.pointer("/payload/engagementToolbarStateEntityPayload") else {
+            continue;
+        };
+        if let Some(key) = state.get("key").and_then(|k| k.as_str()) {
+            let hearted = state.get("heartState").and_then(|h| h.as_str()) == Some("TOGGLE_STATE_ON");
+            states.insert(key.to_string(), hearted);
+        }
+    }
+    states
+}
+
 fn extract_comments(data: &serde_json::Value, base_url: &str) -> Vec<Comment> {
     let mut comments = Vec::new();
+    let hearts = comment_heart_states(data);
     
-    fn walk(obj: &serde_json::Value, comments: &mut Vec<Comment>, base_url: &str) {
+    fn walk(obj: &serde_json::Value, comments: &mut Vec<Comment>, base_url: &str, hearts: &HashMap<String, bool>) {
         if let Some(obj_map) = obj.as_object() {
             if obj_map.contains_key("commentEntityPayload") {
                 let p = &obj_map["commentEntityPayload"];
@@ -684,26 +738,45 @@ fn extract_comments(data: &serde_json::Value, base_url: &str) -> Vec<Comment> {
                         String::new()
                     };
                     
+                    let toolbar = p.get("toolbar").unwrap_or(&serde_json::Value::Null);
+                    let like_count = toolbar
+                        .get("likeCountNotliked")
+                        .and_then(|l| l.as_str())
+                        .map(|l| l.trim())
+                        .filter(|l| !l.is_empty())
+                        .unwrap_or("0")
+                        .to_string();
+                    // The toolbar always carries the heart's tooltip; whether it is lit is in a
+                    // separate state mutation
+                    let hearted = props
+                        .get("toolbarStateKey")
+                        .and_then(|k| k.as_str())
+                        .and_then(|k| hearts.get(k).copied())
+                        .unwrap_or(false);
+                    
                     comments.push(Comment {
                         author,
                         text: text.trim().to_string(),  // Only trim if necessary
                         published_at,
                         author_thumbnail,
                         author_channel_url: None,
+                        comment_id: props.get("commentId").and_then(|c| c.as_str()).map(String::from),
+                        like_count,
+                        hearted,
                     });
                 }
             }
             for value in obj_map.values() {
-                walk(value, comments, base_url);
+                walk(value, comments, base_url, hearts);
             }
         } else if let Some(arr) = obj.as_array() {
             for item in arr {
-                walk(item, comments, base_url);
+                walk(item, comments, base_url, hearts);
             }
         }
     }
     
-    walk(data, &mut comments, base_url);
+    walk(data, &mut comments, base_url, &hearts);
     comments
 }
 
@@ -712,11 +785,13 @@ lazy_static! {
         Mutex::new(LruCache::new(std::num::NonZeroUsize::new(1000).unwrap()))
     );
     static ref DIRECT_URL_CLEANUP_STARTED: AtomicBool = AtomicBool::new(false);
+    /// Size token in googleusercontent / ggpht avatar URLs (`=s900-c-k-...`).
+    static ref AVATAR_SIZE_REGEX: regex::Regex = regex::Regex::new(r"=s\d+(-|$)").unwrap();
 }
 
 const CACHE_DURATION: u64 = 3600;
 
-fn ffmpeg_binary() -> String {
+pub(crate) fn ffmpeg_binary() -> String {
     let exe_name = if cfg!(target_os = "windows") { "ffmpeg.exe" } else { "ffmpeg" };
 
     // 1. Ищем в текущей рабочей папке (откуда запущен cargo run)
@@ -748,7 +823,7 @@ fn ffmpeg_binary() -> String {
     exe_name.to_string()
 }
 
-fn get_duration_from_player_response(data: &serde_json::Value) -> u64 {
+pub(crate) fn get_duration_from_player_response(data: &serde_json::Value) -> u64 {
     // Пытаемся достать длительность из videoDetails
     if let Some(seconds_str) = data.get("videoDetails")
         .and_then(|vd| vd.get("lengthSeconds"))
@@ -771,7 +846,7 @@ fn get_duration_from_player_response(data: &serde_json::Value) -> u64 {
     0 // Если не нашли, считаем видео коротким/потоком
 }
 
-fn yt_dlp_binary() -> String {
+pub(crate) fn yt_dlp_binary() -> String {
     if cfg!(target_os = "windows") {
         if Path::new("assets/yt-dlp.exe").exists() {
             return "assets/yt-dlp.exe".to_string();
@@ -782,6 +857,174 @@ fn yt_dlp_binary() -> String {
     "yt-dlp".to_string()
 }
 
+/// A yt-dlp invocation, sent through `proxy.outbound_url` like the server's own requests.
+pub(crate) fn yt_dlp_command(binary: &str) -> Command {
+    let mut cmd = Command::new(binary);
+    if let Some(proxy) = crate::http_client::outbound_proxy() {
+        cmd.arg("--proxy").arg(proxy);
+    }
+    if let Some(args) = crate::routes::po_token::yt_dlp_extractor_args() {
+        cmd.arg("--extractor-args").arg(args);
+    }
+    cmd
+}
+
+/// Prefix of errors from a yt-dlp run that was killed for taking too long.
+pub(crate) const RESOLVER_TIMEOUT: &str = "resolver_timeout";
+/// Prefix of errors for a run turned away because every yt-dlp slot and the queue are taken.
+pub(crate) const RESOLVER_BUSY: &str = "resolver_busy";
+
+/// Bounds the yt-dlp processes alive at once (`video.ytdlp_max_concurrent`); each one costs
+/// 50-100 MB, which a burst of watch pages on a small board can't afford.
+struct YtDlpPool {
+    slots: Arc<tokio::sync::Semaphore>,
+    waiting: AtomicUsize,
+}
+
+/// Sized by the config seen on first use; changing `ytdlp_max_concurrent` needs a restart.
+static YT_DLP_POOL: std::sync::OnceLock<YtDlpPool> = std::sync::OnceLock::new();
+
+/// Leaves the queue even when the waiting request is dropped (client hung up).
+struct Queued<'a>(&'a AtomicUsize);
+
+impl Drop for Queued<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// A slot for one yt-dlp run, held until the permit is dropped. Waits for a free slot, or fails
+/// with `RESOLVER_BUSY` when `ytdlp_max_queue` runs are already waiting.
+pub(crate) async fn yt_dlp_slot(config: &crate::config::VideoConfig) -> Result<tokio::sync::OwnedSemaphorePermit, String> {
+    let pool = YT_DLP_POOL.get_or_init(|| YtDlpPool {
+        slots: Arc::new(tokio::sync::Semaphore::new(config.ytdlp_max_concurrent.max(1) as usize)),
+        waiting: AtomicUsize::new(0),
+    });
+    if let Ok(permit) = pool.slots.clone().try_acquire_owned() {
+        return Ok(permit);
+    }
+    let waiting = pool.waiting.fetch_add(1, Ordering::SeqCst);
+    let _queued = Queued(&pool.waiting);
+    if waiting >= config.ytdlp_max_queue as usize {
+        return Err(format!(
+            "{}: all yt-dlp slots are busy and {} requests are already waiting",
+            RESOLVER_BUSY, waiting
+        ));
+    }
+    pool.slots
+        .clone()
+        .acquire_owned()
+        .await
+        .map_err(|e| format!("yt-dlp pool closed: {}", e))
+}
+
+/// Reads a child pipe into a shared buffer as data arrives, so whatever was written is still
+/// available if the child has to be killed (a grandchild may keep the pipe open).
+fn drain_pipe<R: Read + Send + 'static>(pipe: Option<R>) -> Arc<std::sync::Mutex<Vec<u8>>> {
+    let buf = Arc::new(std::sync::Mutex::new(Vec::new()));
+    if let Some(mut pipe) = pipe {
+        let sink = buf.clone();
+        std::thread::spawn(move || {
+            let mut chunk = [0u8; 8192];
+            while let Ok(n) = pipe.read(&mut chunk) {
+                if n == 0 {
+                    break;
+                }
+                if let Ok(mut b) = sink.lock() {
+                    b.extend_from_slice(&chunk[..n]);
+                }
+            }
+        });
+    }
+    buf
+}
+
+pub(crate) fn stderr_tail(stderr: &[u8]) -> String {
+    let text = String::from_utf8_lossy(stderr);
+    let lines: Vec<&str> = text.lines().filter(|l| !l.trim().is_empty()).collect();
+    lines[lines.len().saturating_sub(10)..].join("\n")
+}
+
+/// Runs yt-dlp and kills it once `timeout` has passed. Blocking: call from `spawn_blocking`.
+pub(crate) fn run_yt_dlp(cmd: &mut Command, timeout: Duration) -> Result<std::process::Output, String> {
+    let mut child = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to execute yt-dlp: {}", e))?;
+    let stdout = drain_pipe(child.stdout.take());
+    let stderr = drain_pipe(child.stderr.take());
+    let take = |buf: &Arc<std::sync::Mutex<Vec<u8>>>| buf.lock().map(|b| b.clone()).unwrap_or_default();
+
+    let started = std::time::Instant::now();
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => {
+                // Let the reader threads catch the last bytes after exit
+                std::thread::sleep(Duration::from_millis(50));
+                return Ok(std::process::Output {
+                    status,
+                    stdout: take(&stdout),
+                    stderr: take(&stderr),
+                });
+            }
+            Ok(None) if started.elapsed() >= timeout => {
+                let _ = child.kill();
+                let _ = child.wait();
+                log::error!(
+                    "yt-dlp killed after {}s without finishing.\nSTDERR (tail): {}",
+                    timeout.as_secs(),
+                    stderr_tail(&take(&stderr))
+                );
+                return Err(format!(
+                    "{}: yt-dlp did not finish within {}s",
+                    RESOLVER_TIMEOUT,
+                    timeout.as_secs()
+                ));
+            }
+            Ok(None) => std::thread::sleep(Duration::from_millis(100)),
+            Err(e) => {
+                let _ = child.kill();
+                return Err(format!("Failed to wait for yt-dlp: {}", e));
+            }
+        }
+    }
+}
+
+/// Error response for a failed yt-dlp resolve: 504 with `code: resolver_timeout` when it hung.
+fn resolver_error(message: &str, details: String) -> HttpResponse {
+    if details.starts_with(RESOLVER_TIMEOUT) {
+        HttpResponse::GatewayTimeout().json(serde_json::json!({
+            "error": message,
+            "code": RESOLVER_TIMEOUT,
+            "details": details
+        }))
+    } else if details.starts_with(RESOLVER_BUSY) {
+        HttpResponse::TooManyRequests()
+            .insert_header(("Retry-After", "5"))
+            .json(serde_json::json!({
+                "error": message,
+                "code": RESOLVER_BUSY,
+                "details": details
+            }))
+    } else {
+        HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": message,
+            "details": details
+        }))
+    }
+}
+
+/// 501 when this instance's ffmpeg is missing or lacks one of `encoders`; `None` if it can run them.
+pub(crate) fn unsupported_profile(encoders: &[&str]) -> Option<HttpResponse> {
+    let details = crate::check::ffmpeg_capabilities().require(encoders).err()?;
+    Some(HttpResponse::NotImplemented().json(serde_json::json!({
+        "error": "Unsupported profile",
+        "details": details
+    })))
+}
+
 fn sanitize_text(input: &str) -> String {
     let decoded = urlencoding::decode(input)
         .unwrap_or_else(|_| input.into())
@@ -807,7 +1050,7 @@ fn sanitize_text(input: &str) -> String {
 }
 
 async fn dominant_color_from_url(url: &str) -> Option<String> {
-    let client = Client::new();
+    let client = crate::http_client::shared();
     let bytes = client.get(url).send().await.ok()?.bytes().await.ok()?;
     let vec = bytes.to_vec();
     task::spawn_blocking(move || {
@@ -836,31 +1079,7 @@ async fn dominant_color_from_url(url: &str) -> Option<String> {
     .flatten()
 }
 
-fn collect_cookie_paths() -> Vec<PathBuf> {
-    let mut paths = Vec::new();
-    if let Ok(entries) = fs::read_dir("cookies") {
-        for entry in entries.flatten() {
-            let p = entry.path();
-            if p.is_file() {
-                if let Some(ext) = p.extension() {
-                    if ext == "txt" {
-                        paths.push(p);
-                    }
-                }
-            }
-        }
-    }
-    let legacy = ["assets/cookies.txt", "cookies.txt"];
-    for p in legacy {
-        let pb = PathBuf::from(p);
-        if pb.exists() {
-            paths.push(pb);
-        }
-    }
-    paths
-}
-
-fn parse_quality_height(quality: &str) -> Option<u32> {
+pub(crate) fn parse_quality_height(quality: &str) -> Option<u32> {
     let s = quality.trim().to_lowercase();
     let digits: String = s.chars().filter(|c| c.is_ascii_digit()).collect();
     if !digits.is_empty() {
@@ -890,34 +1109,276 @@ fn parse_quality_height(quality: &str) -> Option<u32> {
     aliases.get(s.as_str()).copied()
 }
 
+/// `start`/`end` of /direct_url, in seconds.
+#[derive(Clone, Copy)]
+struct Clip {
+    start: u64,
+    end: Option<u64>,
+}
+
+impl Clip {
+    fn from_query(query: &HashMap<String, String>) -> Result<Option<Clip>, String> {
+        let param = |key: &str| {
+            query
+                .get(key)
+                .map(|v| urlencoding::decode(v).map(|d| d.into_owned()).unwrap_or_else(|_| v.clone()))
+        };
+        let start = param("start")
+            .or_else(|| param("t"))
+            .map(|v| parse_timestamp(&v).ok_or_else(|| format!("Invalid start '{}'", v)))
+            .transpose()?
+            .unwrap_or(0);
+        let end = param("end")
+            .map(|v| parse_timestamp(&v).ok_or_else(|| format!("Invalid end '{}'", v)))
+            .transpose()?;
+        match end {
+            Some(end) if end <= start => Err("end must be after start".to_string()),
+            None if start == 0 => Ok(None),
+            _ => Ok(Some(Clip { start, end })),
+        }
+    }
+
+    /// Length of the clip given the full video duration (0 if unknown).
+    fn length(&self, total: u64) -> u64 {
+        match self.end {
+            Some(end) if total > 0 => end.min(total).saturating_sub(self.start),
+            Some(end) => end - self.start,
+            None => total.saturating_sub(self.start),
+        }
+    }
+
+    /// Output-side trim for ffmpeg reading a pipe.
+    fn ffmpeg_args(&self) -> Vec<String> {
+        let mut args = vec!["-ss".to_string(), self.start.to_string()];
+        if let Some(end) = self.end {
+            args.push("-t".to_string());
+            args.push((end - self.start).to_string());
+        }
+        args
+    }
+}
+
+/// Parses `90`, `90s`, `1m30s`, `1h2m3s` or `1:30` into seconds (the forms `t=` takes on YouTube).
+pub(crate) fn parse_timestamp(value: &str) -> Option<u64> {
+    let value = value.trim();
+    if value.is_empty() {
+        return None;
+    }
+    if value.contains(':') {
+        return value
+            .split(':')
+            .try_fold(0u64, |acc, part| part.parse::<u64>().ok().map(|n| acc * 60 + n));
+    }
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(secs);
+    }
+    let mut total = 0u64;
+    let mut number = String::new();
+    for c in value.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let n: u64 = number.parse().ok()?;
+        number.clear();
+        total += match c {
+            'h' => n * 3600,
+            'm' => n * 60,
+            's' => n,
+            _ => return None,
+        };
+    }
+    if !number.is_empty() {
+        return None;
+    }
+    Some(total)
+}
+
+/// Streams `clip` of a seekable MP4 (URL or local file) without re-encoding. ffmpeg seeks the
+/// input itself (HTTP Range requests for URLs) and writes fragmented MP4 so it can go to a pipe.
+fn stream_clip(source: &str, user_agent: &str, clip: Clip, total_duration: u64) -> HttpResponse {
+    let mut cmd = Command::new(ffmpeg_binary());
+    cmd.args(["-hide_banner", "-loglevel", "error", "-nostdin"]);
+    if source.starts_with("http://") || source.starts_with("https://") {
+        cmd.args(["-user_agent", user_agent]);
+        cmd.args(crate::http_client::ffmpeg_proxy_args());
+    }
+    cmd.args(["-ss", &clip.start.to_string(), "-i", source]);
+    if let Some(end) = clip.end {
+        cmd.args(["-t", &(end - clip.start).to_string()]);
+    }
+    cmd.args([
+        "-c", "copy",
+        "-movflags", "frag_keyframe+empty_moov+default_base_moof",
+        "-f", "mp4", "pipe:1",
+    ]);
+    cmd.stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped());
+
+    let mut child = match cmd.spawn() {
+        Ok(c) => c,
+        Err(e) => {
+            return HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "FFmpeg failed to start",
+                "details": e.to_string()
+            }));
+        }
+    };
+
+    let (tx, rx) = mpsc::channel::<std::result::Result<Bytes, std::io::Error>>(8);
+    std::thread::spawn(move || {
+        if let Some(mut stdout) = child.stdout.take() {
+            let mut buffer = [0u8; 65536];
+            loop {
+                match stdout.read(&mut buffer) {
+                    Ok(0) => break,
+                    Ok(n) => {
+                        if tx.blocking_send(Ok(Bytes::copy_from_slice(&buffer[..n]))).is_err() {
+                            // Client went away
+                            let _ = child.kill();
+                            break;
+                        }
+                    }
+                    Err(e) => {
+                        let _ = tx.blocking_send(Err(e));
+                        break;
+                    }
+                }
+            }
+        }
+        if let Ok(output) = child.wait_with_output() {
+            if !output.status.success() && !output.stderr.is_empty() {
+                log::warn!("FFmpeg clip failed: {}", String::from_utf8_lossy(&output.stderr));
+            }
+        }
+    });
+
+    let stream = ReceiverStream::new(rx).map(|r| r.map_err(actix_web::error::ErrorInternalServerError));
+    let mut builder = HttpResponse::Ok();
+    builder.insert_header((CONTENT_TYPE, HeaderValue::from_static("video/mp4")));
+    let length = clip.length(total_duration);
+    if length > 0 {
+        builder.insert_header(("X-Content-Duration", length.to_string()));
+    }
+    builder.streaming(stream)
+}
+
+/// Remuxes a video-only and an audio-only stream into one fragmented MP4 on the fly (`-c copy`,
+/// so it costs no more CPU than proxying). A clip's `-ss`/`-t` is applied to both inputs.
+fn stream_adaptive_mux(
+    video_url: &str,
+    audio_url: &str,
+    user_agent: &str,
+    clip: Option<Clip>,
+    total_duration: u64,
+) -> HttpResponse {
+    let input_args = clip.map(|c| c.ffmpeg_args()).unwrap_or_default();
+    let mut cmd = Command::new(ffmpeg_binary());
+    cmd.args(["-hide_banner", "-loglevel", "error", "-nostdin"]);
+    for url in [video_url, audio_url] {
+        cmd.args(["-user_agent", user_agent]);
+        cmd.args(crate::http_client::ffmpeg_proxy_args());
+        cmd.args(&input_args);
+        cmd.args(["-i", url]);
+    }
+    cmd.args([
+        "-map", "0:v:0", "-map", "1:a:0",
+        "-c", "copy",
+        "-movflags", "frag_keyframe+empty_moov+default_base_moof",
+        "-f", "mp4", "pipe:1",
+    ]);
+    cmd.stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped());
+
+    let mut child = match cmd.spawn() {
+        Ok(c) => c,
+        Err(e) => {
+            return HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "FFmpeg failed to start",
+                "details": e.to_string()
+            }));
+        }
+    };
+
+    let (tx, rx) = mpsc::channel::<std::result::Result<Bytes, std::io::Error>>(8);
+    std::thread::spawn(move || {
+        if let Some(mut stdout) = child.stdout.take() {
+            let mut buffer = [0u8; 65536];
+            loop {
+                match stdout.read(&mut buffer) {
+                    Ok(0) => break,
+                    Ok(n) => {
+                        if tx.blocking_send(Ok(Bytes::copy_from_slice(&buffer[..n]))).is_err() {
+                            let _ = child.kill();
+                            break;
+                        }
+                    }
+                    Err(e) => {
+                        let _ = tx.blocking_send(Err(e));
+                        break;
+                    }
+                }
+            }
+        }
+        if let Ok(output) = child.wait_with_output() {
+            if !output.status.success() && !output.stderr.is_empty() {
+                log::warn!("FFmpeg remux failed: {}", String::from_utf8_lossy(&output.stderr));
+            }
+        }
+    });
+
+    let stream = ReceiverStream::new(rx).map(|r| r.map_err(actix_web::error::ErrorInternalServerError));
+    let mut builder = HttpResponse::Ok();
+    builder.insert_header((CONTENT_TYPE, HeaderValue::from_static("video/mp4")));
+    let length = clip.map(|c| c.length(total_duration)).unwrap_or(total_duration);
+    if length > 0 {
+        builder.insert_header(("X-Content-Duration", length.to_string()));
+    }
+    builder.streaming(stream)
+}
+
+fn converted_extension(codec: &str) -> &'static str {
+    if codec == "h263" {
+        "3gp"
+    } else {
+        "mp4"
+    }
+}
+
+/// Converts `source_url` to `codec`, streams the result and keeps it in the transcode cache
+/// under `cache_key`.
 fn stream_converted_video(
     source_url: &str,
     user_agent: &str,
-    _video_id: &str,
+    cache_key: &str,
+    cache: &TranscodeCacheConfig,
     codec: &str,
+    clip: Option<Clip>,
     _permit: Option<tokio::sync::OwnedSemaphorePermit>,
 ) -> HttpResponse {
     let source_url = source_url.to_string();
     let ua = user_agent.to_string();
     let codec_str = codec.to_string();
+    let cache_key = cache_key.to_string();
+    let cache = cache.clone();
     let (tx, rx) = mpsc::channel::<std::result::Result<Bytes, std::io::Error>>(8);
     
     let ffmpeg = ffmpeg_binary();
+    let hwaccel = crate::routes::hwaccel::current();
 
     std::thread::spawn(move || {
         let _permit = _permit; // Hold semaphore permit
         let temp_dir = env::temp_dir();
+        let ext = converted_extension(&codec_str);
         let temp_file_name = format!(
             "yt_api_video_{}_{}.{}",
             SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis(),
             std::process::id(),
-            if codec_str == "mpeg4" { "mp4" } else { "3gp" }
+            ext
         );
         let temp_file_path = temp_dir.join(temp_file_name);
 
         // 1. Download the stream using Rust (reqwest::blocking) instead of FFmpeg
         // We move the network logic that caused the crash out of FFmpeg
-        let client = reqwest::blocking::Client::new();
+        let client = crate::http_client::blocking_builder().build().unwrap_or_default();
         let download_result = client
             .get(&source_url)
             .header("User-Agent", &ua)
@@ -943,8 +1404,12 @@ fn stream_converted_video(
             "-hide_banner", "-loglevel", "error",
             // REMOVED: -nostdin (we need stdin!)
             // REMOVED: -reconnect, -user_agent, -headers, -i URL (network args)
-            "-i", "pipe:0", // Read from Stdin
         ]);
+        cmd.args(hwaccel.input_args());
+        cmd.args(["-i", "pipe:0"]); // Read from Stdin
+        if let Some(clip) = clip {
+            cmd.args(clip.ffmpeg_args());
+        }
 
         if codec_str == "mpeg4" {
             cmd.args([
@@ -952,6 +1417,12 @@ fn stream_converted_video(
                 "-brand", "isom", "-pix_fmt", "yuv420p",
                 "-c:a", "copy", "-f", "mp4",
             ]);
+        } else if codec_str == "h264" {
+            cmd.args(hwaccel.h264_args(360, "600k"));
+            cmd.args([
+                "-c:a", "aac", "-b:a", "96k", "-ac", "2",
+                "-movflags", "+faststart", "-f", "mp4",
+            ]);
         } else {
             cmd.args([
                 "-c:v", "h263", "-vf", "scale=352:288",
@@ -1025,6 +1496,7 @@ fn stream_converted_video(
             let _ = fs::remove_file(&temp_file_path);
             return;
         }
+        transcode_cache::store(&cache, &cache_key, ext, &temp_file_path);
 
         // 5. Stream the resulting file back (Same logic as before)
         match fs::File::open(&temp_file_path) {
@@ -1051,7 +1523,7 @@ fn stream_converted_video(
         let _ = fs::remove_file(&temp_file_path);
     });
 
-    let mime_type = if codec == "mpeg4" { "video/mp4" } else { "video/3gpp" };
+    let mime_type = if codec == "h263" { "video/3gpp" } else { "video/mp4" };
     let stream = ReceiverStream::new(rx).map(|r| r.map(web::Bytes::from).map_err(actix_web::error::ErrorInternalServerError));
     HttpResponse::Ok()
         .insert_header((CONTENT_TYPE, HeaderValue::from_str(mime_type).unwrap()))
@@ -1072,7 +1544,7 @@ fn clean_direct_url_temp_files() {
             let path = entry.path();
             if path.is_file() {
                 if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
-                    if name.starts_with("yt_api_video_") && (name.ends_with(".mp4") || name.ends_with(".3gp")) {
+                    if name.starts_with("yt_api_video_") && (name.ends_with(".mp4") || name.ends_with(".3gp") || name.ends_with(".mkv")) {
                         if let Ok(meta) = fs::metadata(&path) {
                             if let Ok(mtime) = meta.modified() {
                                 if now.duration_since(mtime).unwrap_or(Duration::MAX) > max_age_video {
@@ -1122,21 +1594,222 @@ fn spawn_direct_url_cleanup_if_needed() {
     }
 }
 
-async fn resolve_direct_stream_url(
+/// The parts of `yt-dlp --dump-single-json` the API uses.
+#[derive(Deserialize, Clone)]
+pub(crate) struct YtDlpInfo {
+    #[serde(default)]
+    pub duration: Option<f64>,
+    #[serde(default)]
+    pub is_live: Option<bool>,
+    #[serde(default)]
+    pub formats: Vec<YtDlpFormat>,
+    #[serde(default)]
+    pub chapters: Option<Vec<Chapter>>,
+    #[serde(default)]
+    pub subtitles: Option<HashMap<String, Value>>,
+    #[serde(default)]
+    pub automatic_captions: Option<HashMap<String, Value>>,
+}
+
+#[derive(Deserialize, Clone)]
+pub(crate) struct YtDlpFormat {
+    #[serde(default)]
+    pub format_id: Option<String>,
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub ext: Option<String>,
+    #[serde(default)]
+    pub width: Option<u32>,
+    #[serde(default)]
+    pub height: Option<u32>,
+    #[serde(default)]
+    pub fps: Option<f64>,
+    #[serde(default)]
+    pub asr: Option<u32>,
+    #[serde(default)]
+    pub filesize: Option<u64>,
+    #[serde(default)]
+    pub filesize_approx: Option<u64>,
+    #[serde(default)]
+    pub vcodec: Option<String>,
+    #[serde(default)]
+    pub acodec: Option<String>,
+    #[serde(default)]
+    pub tbr: Option<f64>,
+    #[serde(default)]
+    pub abr: Option<f64>,
+    #[serde(default)]
+    pub protocol: Option<String>,
+}
+
+impl YtDlpFormat {
+    fn has(codec: &Option<String>) -> bool {
+        codec.as_deref().map(|c| c != "none").unwrap_or(false)
+    }
+
+    fn has_video(&self) -> bool {
+        Self::has(&self.vcodec)
+    }
+
+    fn has_audio(&self) -> bool {
+        Self::has(&self.acodec)
+    }
+
+    /// A muxed HLS variant (`m3u8`/`m3u8_native`) with both audio and video.
+    fn is_hls_muxed(&self) -> bool {
+        self.url.is_some()
+            && self.format_id.is_some()
+            && self.protocol.as_deref().map(|p| p.starts_with("m3u8")).unwrap_or(false)
+            && self.has_video()
+            && self.has_audio()
+    }
+
+    /// A single-track (video-only or audio-only) progressive stream, as DASH representations are.
+    fn is_adaptive(&self) -> bool {
+        self.is_direct()
+            && self.format_id.as_deref().map(|id| !id.ends_with("-drc")).unwrap_or(false)
+            && self.has_video() != self.has_audio()
+    }
+
+    /// A plain HTTP URL (not an HLS/DASH manifest) a client can play directly.
+    fn is_direct(&self) -> bool {
+        self.url.is_some()
+            && self
+                .protocol
+                .as_deref()
+                .map(|p| p == "https" || p == "http")
+                .unwrap_or(true)
+    }
+}
+
+impl YtDlpInfo {
+    /// Same preference as the old `-f` selectors:
+    /// audio `bestaudio/best`, video `best[height<=H][ext=mp4]/best[ext=mp4]/best`.
+    /// `baseline` puts AAC audio and Baseline H.264 (`avc1.42...`) video first.
+    pub(crate) fn pick_url(&self, max_height: u32, audio_only: bool, baseline: bool) -> Option<String> {
+        let direct: Vec<&YtDlpFormat> = self.formats.iter().filter(|f| f.is_direct()).collect();
+        let bitrate = |f: &YtDlpFormat| (f.abr.or(f.tbr).unwrap_or(0.0) * 1000.0) as u64;
+        let progressive = || direct.iter().filter(|f| f.has_video() && f.has_audio());
+        let best_progressive = |filter: &dyn Fn(&YtDlpFormat) -> bool| {
+            progressive()
+                .filter(|f| filter(f))
+                .max_by_key(|f| (f.height.unwrap_or(0), bitrate(f)))
+                .copied()
+        };
+
+        let codec = |c: &Option<String>, prefix: &str| c.as_deref().is_some_and(|c| c.starts_with(prefix));
+
+        let chosen = if audio_only {
+            let best_audio = |filter: &dyn Fn(&YtDlpFormat) -> bool| {
+                direct
+                    .iter()
+                    .filter(|f| f.has_audio() && !f.has_video() && filter(f))
+                    .max_by_key(|f| bitrate(f))
+                    .copied()
+            };
+            (if baseline { best_audio(&|f| codec(&f.acodec, "mp4a")) } else { None })
+                .or_else(|| best_audio(&|_| true))
+                .or_else(|| best_progressive(&|_| true))
+        } else {
+            let mp4 = |f: &YtDlpFormat| f.ext.as_deref() == Some("mp4");
+            let fits = |f: &YtDlpFormat| mp4(f) && f.height.unwrap_or(0) <= max_height;
+            (if baseline { best_progressive(&|f| fits(f) && codec(&f.vcodec, "avc1.42")) } else { None })
+                .or_else(|| best_progressive(&fits))
+                .or_else(|| best_progressive(&mp4))
+                .or_else(|| best_progressive(&|_| true))
+        };
+        chosen.and_then(|f| f.url.clone())
+    }
+
+    /// Height of the tallest progressive (audio+video) format; YouTube stops these at 720p,
+    /// and for most videos at 360p.
+    pub(crate) fn progressive_height(&self) -> u32 {
+        self.formats
+            .iter()
+            .filter(|f| f.is_direct() && f.has_video() && f.has_audio())
+            .filter_map(|f| f.height)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Video-only MP4 up to `max_height` (H.264 over other codecs, then the tallest) and the best
+    /// AAC audio: the pair `stream_adaptive_mux` combines for qualities progressive formats lack.
+    pub(crate) fn pick_adaptive_pair(&self, max_height: u32) -> Option<(&YtDlpFormat, &YtDlpFormat)> {
+        let bitrate = |f: &YtDlpFormat| (f.abr.or(f.tbr).unwrap_or(0.0) * 1000.0) as u64;
+        let is_avc = |f: &YtDlpFormat| f.vcodec.as_deref().map(|c| c.starts_with("avc1")).unwrap_or(false);
+        let video = self
+            .formats
+            .iter()
+            .filter(|f| f.is_adaptive() && f.has_video() && f.ext.as_deref() == Some("mp4"))
+            .filter(|f| f.height.unwrap_or(0) <= max_height)
+            .max_by_key(|f| (is_avc(f), f.height.unwrap_or(0), bitrate(f)))?;
+        let audio = self
+            .formats
+            .iter()
+            .filter(|f| f.is_adaptive() && f.has_audio() && f.ext.as_deref() == Some("m4a"))
+            .max_by_key(|f| bitrate(f))?;
+        Some((video, audio))
+    }
+
+    pub(crate) fn caption_languages(&self) -> Vec<String> {
+        let mut langs: Vec<String> = self
+            .subtitles
+            .iter()
+            .chain(self.automatic_captions.iter())
+            .flat_map(|m| m.keys().cloned())
+            .collect();
+        langs.sort();
+        langs.dedup();
+        langs
+    }
+}
+
+/// Stream URLs stay valid for hours; half an hour keeps repeated plays on one yt-dlp run.
+const YT_DLP_INFO_TTL: u64 = 1800;
+
+type YtDlpInfoCache = LruCache<String, (Arc<YtDlpInfo>, u64)>;
+
+lazy_static! {
+    static ref YT_DLP_INFO_CACHE: Arc<Mutex<YtDlpInfoCache>> = Arc::new(
+        Mutex::new(LruCache::new(std::num::NonZeroUsize::new(200).unwrap()))
+    );
+}
+
+/// Info from an earlier resolve of `video_id`, if still fresh. Never starts yt-dlp.
+pub(crate) async fn cached_yt_dlp_info(video_id: &str) -> Option<Arc<YtDlpInfo>> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+    let mut cache = YT_DLP_INFO_CACHE.lock().await;
+    match cache.get(video_id) {
+        Some((info, at)) if now.saturating_sub(*at) < YT_DLP_INFO_TTL => Some(info.clone()),
+        _ => None,
+    }
+}
+
+/// Stream info for `video_id`, cached per video: from the native InnerTube extractor when it
+/// works, else one `yt-dlp --dump-single-json` run (per cookie file until one works).
+pub(crate) async fn yt_dlp_info(
     video_id: &str,
-    quality: Option<&str>,
-    audio_only: bool,
     config: &crate::config::Config,
-) -> Result<String, String> {
-    let video_id = video_id.to_string();
-    let quality = quality
-        .map(|q| q.to_string())
-        .unwrap_or_else(|| config.video.default_quality.clone());
-    let use_cookies = config.video.use_cookies;
+) -> Result<Arc<YtDlpInfo>, String> {
+    if let Some(info) = cached_yt_dlp_info(video_id).await {
+        return Ok(info);
+    }
+    if config.video.extractor.native {
+        match crate::routes::extractor::player_info(video_id, config).await {
+            Ok(info) => {
+                let info = Arc::new(info);
+                cache_yt_dlp_info(video_id, info.clone()).await;
+                return Ok(info);
+            }
+            Err(e) => log::info!("Native extractor failed for {}, using yt-dlp: {}", video_id, e),
+        }
+    }
+    let url = format!("https://www.youtube.com/watch?v={}", video_id);
+    let timeout = Duration::from_secs(config.video.ytdlp_resolve_timeout_secs.max(1));
     let yt_dlp = yt_dlp_binary();
-    let mut cookie_paths = Vec::new();
-    if use_cookies {
-        cookie_paths = collect_cookie_paths();
+    let cookie_paths = crate::routes::cookies::rotation(&config.video);
+    if config.video.use_cookies {
         let names: Vec<String> = cookie_paths
             .iter()
             .map(|p| p.display().to_string())
@@ -1152,66 +1825,64 @@ async fn resolve_direct_stream_url(
         }
     }
 
-    task::spawn_blocking(move || {
-        let url = format!("https://www.youtube.com/watch?v={}", video_id);
-        let format_selector = if audio_only {
-            "bestaudio/best".to_string()
-        } else {
-            // Превращаем "1080p" в число 1080, чтобы yt-dlp не выдал ошибку синтаксиса
-            let numeric_height = parse_quality_height(&quality).unwrap_or(360);
-            format!("best[height<={}][ext=mp4]/best[ext=mp4]/best", numeric_height)
-        };
-
-        let mut attempts: Vec<Option<PathBuf>> = Vec::new();
-        for p in cookie_paths {
-            attempts.push(Some(p));
-        }
+    crate::routes::po_token::ensure(config).await;
+    let slot = yt_dlp_slot(&config.video).await?;
+    // A request queued behind one for the same video can use its result
+    if let Some(info) = cached_yt_dlp_info(video_id).await {
+        return Ok(info);
+    }
+    let info = task::spawn_blocking(move || {
+        let _slot = slot;
+        let mut attempts: Vec<Option<PathBuf>> = cookie_paths.into_iter().map(Some).collect();
         attempts.push(None);
 
         let mut last_err = None;
         for cookie in attempts {
-            let mut cmd = Command::new(&yt_dlp);
-            cmd.arg("-f")
-                .arg(&format_selector)
-                .arg("--get-url")
+            let mut cmd = yt_dlp_command(&yt_dlp);
+            cmd.arg("--dump-single-json")
+                .arg("--no-playlist")
+                .arg("--no-warnings")
                 .arg(&url);
 
             if let Some(ref path) = cookie {
                 cmd.arg("--cookies").arg(path);
             }
 
-            match cmd.output() {
+            let with = cookie
+                .as_ref()
+                .map(|p| format!("with cookies {}", p.display()))
+                .unwrap_or_else(|| "without cookies".to_string());
+            let result = run_yt_dlp(&mut cmd, timeout);
+            if let (Some(path), Ok(output)) = (&cookie, &result) {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                let outcome = if output.status.success() { Ok(()) } else { Err(stderr.as_ref()) };
+                crate::routes::cookies::report(path, outcome);
+            }
+            match result {
                 Ok(output) if output.status.success() => {
-                    let stdout = String::from_utf8_lossy(&output.stdout);
-                    if let Some(line) = stdout.lines().find(|l| !l.trim().is_empty()) {
-                        return Ok(line.to_string());
+                    match serde_json::from_slice::<YtDlpInfo>(&output.stdout) {
+                        Ok(info) => return Ok(info),
+                        Err(e) => {
+                            let msg = format!("yt-dlp returned unreadable JSON {}: {}", with, e);
+                            log::info!("{}", msg);
+                            last_err = Some(msg);
+                        }
                     }
-                    last_err = Some("yt-dlp returned empty output".to_string());
                 }
                 Ok(output) => {
-                    let stderr = String::from_utf8_lossy(&output.stderr);
-                    let msg = if let Some(ref path) = cookie {
-                        format!(
-                            "yt-dlp failed with cookies {}: status {} stderr {}",
-                            path.display(),
-                            output.status,
-                            stderr
-                        )
-                    } else {
-                        format!(
-                            "yt-dlp failed without cookies: status {} stderr {}",
-                            output.status, stderr
-                        )
-                    };
+                    let msg = format!(
+                        "yt-dlp failed {}: status {} stderr {}",
+                        with,
+                        output.status,
+                        String::from_utf8_lossy(&output.stderr)
+                    );
                     log::info!("{}", msg);
                     last_err = Some(msg);
                 }
+                // A hung run would hang again with the next cookie file
+                Err(e) if e.starts_with(RESOLVER_TIMEOUT) => return Err(e),
                 Err(e) => {
-                    let msg = if let Some(ref path) = cookie {
-                        format!("yt-dlp exec error with cookies {}: {}", path.display(), e)
-                    } else {
-                        format!("yt-dlp exec error: {}", e)
-                    };
+                    let msg = format!("yt-dlp exec error {}: {}", with, e);
                     log::info!("{}", msg);
                     last_err = Some(msg);
                 }
@@ -1221,37 +1892,302 @@ async fn resolve_direct_stream_url(
         Err(last_err.unwrap_or_else(|| "yt-dlp failed for all attempts".to_string()))
     })
     .await
-    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())??;
+
+    let info = Arc::new(info);
+    cache_yt_dlp_info(video_id, info.clone()).await;
+    Ok(info)
 }
 
-async fn proxy_stream_response(
-    target_url: &str,
-    req: &HttpRequest,
-    default_content_type: &str,
-) -> HttpResponse {
-    let client = Client::builder()
-        .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/91.0.4472.124 Safari/537.36")
-        .build()
-        .unwrap();
+async fn cache_yt_dlp_info(video_id: &str, info: Arc<YtDlpInfo>) {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    YT_DLP_INFO_CACHE
+        .lock()
+        .await
+        .put(video_id.to_string(), (info, now));
+}
 
-    let mut request_builder = client.get(target_url);
-    if let Some(range_header) = req.headers().get("Range") {
-        request_builder = request_builder.header("Range", range_header.clone());
-    }
+/// A resolved URL is handed out again until this many seconds before its `expire=`, so a
+/// player that starts just before the cut-off still gets to the end of a typical video.
+const STREAM_URL_EXPIRY_MARGIN: u64 = 600;
 
-    match request_builder.send().await {
-        Ok(resp) => {
-            let status = resp.status();
-            let headers = resp.headers().clone();
+/// (video_id, max height, audio only, baseline) -> (googlevideo URL, unix time it expires)
+type StreamUrlCache = LruCache<(String, u32, bool, bool), (String, u64)>;
+
+lazy_static! {
+    static ref STREAM_URL_CACHE: Arc<Mutex<StreamUrlCache>> = Arc::new(Mutex::new(
+        LruCache::new(std::num::NonZeroUsize::new(1000).unwrap())
+    ));
+}
+
+/// The progressive 360p format, what /direct_url serves up to 360p.
+const PROGRESSIVE_360P_ITAG: &str = "18";
+
+/// A query parameter of a googlevideo URL.
+fn stream_url_param(url: &str, name: &str) -> Option<String> {
+    reqwest::Url::parse(url)
+        .ok()?
+        .query_pairs()
+        .find(|(k, _)| k == name)
+        .map(|(_, v)| v.into_owned())
+}
+
+/// Unix time from a googlevideo URL's `expire=` parameter.
+fn stream_url_expiry(url: &str) -> Option<u64> {
+    stream_url_param(url, "expire").and_then(|v| v.parse().ok())
+}
+
+/// Whether two URLs serve the same bytes: the same `itag`, and the same `clen` when both have
+/// one. Only then can a broken body carry on from the other URL.
+fn same_stream_format(a: &str, b: &str) -> bool {
+    let itag_a = stream_url_param(a, "itag");
+    if itag_a.is_none() || itag_a != stream_url_param(b, "itag") {
+        return false;
+    }
+    match (stream_url_param(a, "clen"), stream_url_param(b, "clen")) {
+        (Some(clen_a), Some(clen_b)) => clen_a == clen_b,
+        _ => true,
+    }
+}
+
+/// `STREAM_URL_CACHE` key of a resolve. `device` only applies without an explicit `quality`:
+/// it caps `default_quality` and, for baseline devices, narrows the codecs.
+fn stream_url_key(
+    video_id: &str,
+    quality: Option<&str>,
+    audio_only: bool,
+    device: Option<&DeviceProfile>,
+    config: &crate::config::Config,
+) -> (String, u32, bool, bool) {
+    let device = device.filter(|_| quality.is_none());
+    let quality = quality.unwrap_or(&config.video.default_quality);
+    let mut max_height = parse_quality_height(quality).unwrap_or(360);
+    if let Some(device) = device {
+        max_height = max_height.min(device.max_height);
+        log::debug!("{}: {} profile, up to {}p", video_id, device.name, max_height);
+    }
+    let baseline = device.is_some_and(|d| d.baseline);
+    (video_id.to_string(), max_height, audio_only, baseline)
+}
+
+/// itag of the format the last resolve for `key` picked, whether or not its URL still works.
+async fn last_picked_itag(key: &(String, u32, bool, bool)) -> Option<String> {
+    let cache = STREAM_URL_CACHE.lock().await;
+    cache.peek(key).and_then(|(url, _)| stream_url_param(url, "itag"))
+}
+
+/// See `stream_url_key` for how `quality` and `device` combine.
+async fn resolve_direct_stream_url(
+    video_id: &str,
+    quality: Option<&str>,
+    audio_only: bool,
+    device: Option<&DeviceProfile>,
+    config: &crate::config::Config,
+) -> Result<String, String> {
+    let key = stream_url_key(video_id, quality, audio_only, device, config);
+    let (max_height, baseline) = (key.1, key.3);
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    if let Some((url, expires)) = STREAM_URL_CACHE.lock().await.get(&key) {
+        if now + STREAM_URL_EXPIRY_MARGIN < *expires {
+            return Ok(url.clone());
+        }
+    }
+
+    let info = yt_dlp_info(video_id, config).await?;
+    let url = info
+        .pick_url(max_height, audio_only, baseline)
+        .ok_or_else(|| "yt-dlp listed no directly playable format".to_string())?;
+    // Without expire= (not a googlevideo URL) fall back to the yt-dlp info lifetime
+    let expires = stream_url_expiry(&url).unwrap_or(now + YT_DLP_INFO_TTL + STREAM_URL_EXPIRY_MARGIN);
+    STREAM_URL_CACHE.lock().await.put(key, (url.clone(), expires));
+    Ok(url)
+}
+
+/// Drops every cached URL and the cached info for `video_id`, so the next resolve is fresh.
+async fn forget_stream_urls(video_id: &str) {
+    YT_DLP_INFO_CACHE.lock().await.pop(video_id);
+    let mut cache = STREAM_URL_CACHE.lock().await;
+    let stale: Vec<(String, u32, bool, bool)> = cache
+        .iter()
+        .filter(|(key, _)| key.0 == video_id)
+        .map(|(key, _)| key.clone())
+        .collect();
+    for key in stale {
+        cache.pop(&key);
+    }
+}
+
+/// What `proxy_stream_response` resolves again when googlevideo refuses a URL (403 once it
+/// has expired, or when the IP it was issued to changed).
+struct StreamSource {
+    video_id: String,
+    quality: Option<String>,
+    audio_only: bool,
+    device: Option<DeviceProfile>,
+    data: web::Data<crate::AppState>,
+}
+
+/// A fresh URL in place of `stale_url`. Takes `source`, so a request re-resolves at most once.
+/// With `same_format` the fresh URL must serve the same file (`same_stream_format`), for
+/// continuing a body part of which came from the stale one.
+async fn reresolve_stream(source: &mut Option<StreamSource>, stale_url: &str, same_format: bool) -> Option<String> {
+    let source = source.take()?;
+    forget_stream_urls(&source.video_id).await;
+    match resolve_direct_stream_url(
+        &source.video_id,
+        source.quality.as_deref(),
+        source.audio_only,
+        source.device.as_ref(),
+        &source.data.config,
+    )
+    .await
+    {
+        Ok(url) if same_format && !same_stream_format(stale_url, &url) => {
+            log::info!(
+                "Re-resolved {} to another format; not continuing the stream from it",
+                source.video_id
+            );
+            None
+        }
+        Ok(url) if url != stale_url => {
+            log::info!("googlevideo refused the URL for {}; re-resolved it", source.video_id);
+            Some(url)
+        }
+        Ok(_) => None,
+        Err(e) => {
+            log::warn!("Re-resolving {} after a 403 failed: {}", source.video_id, e);
+            None
+        }
+    }
+}
+
+/// `bytes=START-[END]` -> (START, END), and (0, None) without a Range. Suffix and multi-part
+/// ranges give `None`: a broken download of those is not resumed.
+fn parse_byte_range(range: Option<&str>) -> Option<(u64, Option<u64>)> {
+    let Some(range) = range else {
+        return Some((0, None));
+    };
+    let (start, end) = range.trim().strip_prefix("bytes=")?.split_once('-')?;
+    if end.contains(',') {
+        return None;
+    }
+    let end = if end.is_empty() { None } else { Some(end.parse().ok()?) };
+    Some((start.parse().ok()?, end))
+}
+
+async fn send_stream_request(
+    client: &Client,
+    url: &str,
+    range: Option<&str>,
+) -> reqwest::Result<reqwest::Response> {
+    let mut request_builder = client.get(url).header(
+        "User-Agent",
+        "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/91.0.4472.124 Safari/537.36",
+    );
+    if let Some(range) = range {
+        request_builder = request_builder.header("Range", range);
+    }
+    request_builder.send().await
+}
+
+/// Relays a googlevideo URL with the client's Range. With a `source`, a 403 (the URL expired or
+/// was cached too long) re-resolves the video once and retries. A body that breaks off is picked
+/// up once from the byte it stopped at, also re-resolving if that request is refused.
+async fn proxy_stream_response(
+    target_url: &str,
+    req: &HttpRequest,
+    default_content_type: &str,
+    source: Option<StreamSource>,
+) -> HttpResponse {
+    let client = crate::http_client::googlevideo();
+    let range = req
+        .headers()
+        .get("Range")
+        .and_then(|r| r.to_str().ok())
+        .map(|r| r.to_string());
+    let mut source = source;
+    let mut url = target_url.to_string();
+
+    let mut result = send_stream_request(&client, &url, range.as_deref()).await;
+    if matches!(&result, Ok(resp) if resp.status() == reqwest::StatusCode::FORBIDDEN) {
+        // a Range past the start continues bytes the client got from the stale URL
+        let continues = parse_byte_range(range.as_deref()).is_none_or(|(start, _)| start > 0);
+        if let Some(fresh) = reresolve_stream(&mut source, &url, continues).await {
+            url = fresh;
+            result = send_stream_request(&client, &url, range.as_deref()).await;
+        }
+    }
+
+    match result {
+        Ok(resp) => {
+            let status = resp.status();
+            let headers = resp.headers().clone();
             let content_type = headers
                 .get(CONTENT_TYPE)
                 .and_then(|ct| ct.to_str().ok())
                 .unwrap_or(default_content_type)
                 .to_string();
 
-            let stream = resp
-                .bytes_stream()
-                .map(|item| item.map_err(|e| actix_web::error::ErrorBadGateway(e)));
+            let resume_from = parse_byte_range(range.as_deref()).filter(|_| status.is_success());
+            let (tx, rx) = mpsc::channel::<Result<Bytes, std::io::Error>>(8);
+            tokio::spawn(async move {
+                let mut body = resp.bytes_stream().boxed();
+                let mut sent: u64 = 0;
+                let mut resumed = false;
+                loop {
+                    match body.next().await {
+                        None => return,
+                        Some(Ok(chunk)) => {
+                            sent += chunk.len() as u64;
+                            if tx.send(Ok(chunk)).await.is_err() {
+                                return;
+                            }
+                        }
+                        Some(Err(e)) => {
+                            let Some((start, end)) = resume_from.filter(|_| !resumed) else {
+                                let _ = tx.send(Err(std::io::Error::other(e))).await;
+                                return;
+                            };
+                            resumed = true;
+                            log::info!("googlevideo stream broke after {} bytes ({}); resuming", sent, e);
+                            let rest = match end {
+                                Some(end) => format!("bytes={}-{}", start + sent, end),
+                                None => format!("bytes={}-", start + sent),
+                            };
+                            let mut next = send_stream_request(&client, &url, Some(&rest)).await;
+                            if matches!(&next, Ok(r) if r.status() == reqwest::StatusCode::FORBIDDEN) {
+                                // None also when the fresh URL is another format: its bytes
+                                // cannot follow the ones already sent, so the body ends here
+                                if let Some(fresh) = reresolve_stream(&mut source, &url, true).await {
+                                    url = fresh;
+                                    next = send_stream_request(&client, &url, Some(&rest)).await;
+                                }
+                            }
+                            match next {
+                                Ok(r) if r.status() == reqwest::StatusCode::PARTIAL_CONTENT => {
+                                    body = r.bytes_stream().boxed();
+                                }
+                                Ok(r) => {
+                                    let err = format!("Resuming the stream returned {}", r.status());
+                                    let _ = tx.send(Err(std::io::Error::other(err))).await;
+                                    return;
+                                }
+                                Err(e) => {
+                                    let _ = tx.send(Err(std::io::Error::other(e))).await;
+                                    return;
+                                }
+                            }
+                        }
+                    }
+                }
+            });
+            let stream = ReceiverStream::new(rx).map(|r| r.map_err(actix_web::error::ErrorBadGateway));
 
             let mut builder = HttpResponse::build(status);
             for (key, value) in headers.iter() {
@@ -1284,6 +2220,9 @@ pub struct VideoInfoResponse {
     pub subscriber_count: String,
     pub channel_custom_url: Option<String>,
     pub description: String,
+    /// `description` as HTML: URLs, timestamps, #hashtags and @handles linked to this server.
+    #[serde(default)]
+    pub description_html: String,
     pub video_id: String,
     pub embed_url: String,
     pub duration: String,
@@ -1295,6 +2234,45 @@ pub struct VideoInfoResponse {
     pub channel_thumbnail: String,
     pub thumbnail: String,
     pub video_url: String,
+    #[serde(default)]
+    pub is_live: bool,
+    /// Only known once the video has been resolved through yt-dlp (cached for a while).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chapters: Option<Vec<Chapter>>,
+    /// Caption languages, from the same yt-dlp data as `chapters`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub caption_languages: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category_id: Option<String>,
+    /// In the requested language, as the watch page names the category.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category_title: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Return YouTube Dislike's estimate; only with `api.ryd.enabled`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dislikes: Option<u64>,
+    /// 1-5 stars from Return YouTube Dislike votes, as GData reported them (`ratings=true` or
+    /// `alt=jsonc`, and `api.ryd.enabled`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rating: Option<f64>,
+    /// Likes plus dislikes behind `rating`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rating_count: Option<u64>,
+    /// True when a section failed upstream, as opposed to being empty; see `warnings`.
+    #[serde(default)]
+    pub partial: bool,
+    /// `section: reason` for each section that could not be fetched (details, statistics,
+    /// channel, comments).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct Chapter {
+    pub start_time: f64,
+    pub end_time: f64,
+    pub title: String,
 }
 
 #[derive(Serialize, Deserialize, ToSchema)]
@@ -1304,6 +2282,13 @@ pub struct Comment {
     pub published_at: String,
     pub author_thumbnail: String,
     pub author_channel_url: Option<String>,
+    /// Id for `/actions/rate_comment`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment_id: Option<String>,
+    /// As YouTube abbreviates it ("12", "1.2K"); "0" without likes.
+    pub like_count: String,
+    /// Hearted by the channel owner.
+    pub hearted: bool,
 }
 
 #[derive(Serialize, Deserialize, ToSchema)]
@@ -1318,6 +2303,25 @@ pub struct RelatedVideo {
     pub url: String,
     pub source: String,
     pub color: Option<String>,
+    /// Only with `stats=true`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub statistics: Option<crate::routes::search::ItemStatistics>,
+}
+
+/// Answer of /get_up_next.php: the one video to autoplay after the current one.
+#[derive(Serialize, ToSchema)]
+pub struct UpNextResponse {
+    pub video_id: String,
+    pub title: String,
+    pub author: String,
+    /// Seconds, 0 if unknown.
+    pub duration: u64,
+    pub thumbnail: String,
+    pub url: String,
+    /// `queue`, `autoplay` (YouTube's own pick) or `related` (first related video).
+    pub source: String,
+    /// Seconds the client should count down before switching.
+    pub countdown_secs: u32,
 }
 
 #[derive(Serialize, ToSchema)]
@@ -1325,6 +2329,34 @@ pub struct DirectUrlResponse {
     pub video_url: String,
 }
 
+#[derive(Serialize, ToSchema)]
+pub struct VideoFormat {
+    /// yt-dlp format id; the YouTube itag for regular streams.
+    pub itag: String,
+    pub ext: String,
+    /// `video+audio`, `video` or `audio`.
+    pub kind: String,
+    /// `1280x720`, or `audio only`.
+    pub resolution: String,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub fps: Option<f64>,
+    pub vcodec: Option<String>,
+    pub acodec: Option<String>,
+    pub bitrate_kbps: Option<f64>,
+    /// Exact when YouTube reports it, otherwise yt-dlp's estimate from bitrate and duration.
+    pub filesize: Option<u64>,
+    /// Through /video.proxy (signed when proxy.signed_urls is on).
+    pub url: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct VideoFormatsResponse {
+    pub video_id: String,
+    pub duration: Option<f64>,
+    pub formats: Vec<VideoFormat>,
+}
+
 #[derive(Serialize, ToSchema)]
 pub struct HlsManifestUrlResponse {
     pub hls_manifest_url: String,
@@ -1341,11 +2373,19 @@ pub struct HlsManifestUrlResponse {
     ),
     responses(
         (status = 200, description = "Thumbnail image", content_type = "image/jpeg"),
+        (status = 403, description = "Hotlinked from another site (proxy.hotlink)"),
         (status = 404, description = "Thumbnail not found")
     )
 )]
-pub async fn thumbnail_proxy(path: web::Path<String>, req: HttpRequest) -> impl Responder {
+pub async fn thumbnail_proxy(
+    path: web::Path<String>,
+    req: HttpRequest,
+    data: web::Data<crate::AppState>,
+) -> impl Responder {
     let video_id = path.into_inner();
+    if let Some(resp) = crate::routes::hotlink::check(&req, &data.config) {
+        return resp;
+    }
 
     let mut query_params: HashMap<String, String> = HashMap::new();
     for pair in req.query_string().split('&') {
@@ -1392,107 +2432,154 @@ pub async fn thumbnail_proxy(path: web::Path<String>, req: HttpRequest) -> impl
         }
     }
 
-    let url = format!("https://i.ytimg.com/vi/{}/{}", video_id, thumbnail_type);
+    let proxy_config = &data.config.proxy;
+    let cooldown = Duration::from_secs(proxy_config.host_cooldown_secs);
+    let client = crate::http_client::shared();
 
-    let client = Client::new();
+    for host in crate::routes::circuit::order_hosts(&proxy_config.thumbnail_hosts) {
+        let mut result = fetch_thumbnail(&client, host, &video_id, thumbnail_type).await;
+        if matches!(result, Ok(None)) && thumbnail_type != "mqdefault.jpg" {
+            result = fetch_thumbnail(&client, host, &video_id, "mqdefault.jpg").await;
+        }
 
-    match client.get(&url).send().await {
-        Ok(resp) => {
-            let status = resp.status().as_u16();
-            let headers = resp.headers().clone();
-            if status == 404 && thumbnail_type != "mqdefault.jpg" {
-                let fallback_url = format!("https://i.ytimg.com/vi/{}/mqdefault.jpg", video_id);
-                match client.get(&fallback_url).send().await {
-                    Ok(fallback_resp) => {
-                        let fallback_headers = fallback_resp.headers().clone();
-                        let content_type = fallback_headers
-                            .get("content-type")
-                            .and_then(|ct| ct.to_str().ok())
-                            .unwrap_or("image/jpeg")
-                            .to_string();
-
-                        match fallback_resp.bytes().await {
-                            Ok(bytes) => {
-                                let current_time = SystemTime::now()
-                                    .duration_since(UNIX_EPOCH)
-                                    .unwrap()
-                                    .as_secs();
-
-                                let mut cache = THUMBNAIL_CACHE.lock().await;
-                                cache.put(
-                                    cache_key,
-                                    (bytes.to_vec(), content_type.clone(), current_time),
-                                );
-
-                                HttpResponse::Ok()
-                                    .content_type(content_type.as_str())
-                                    .body(bytes)
-                            }
-                            Err(_) => HttpResponse::NotFound().finish(),
+        match result {
+            Ok(Some((mut bytes, mut content_type))) => {
+                crate::routes::circuit::record_success(host);
+                if proxy_config.thumbnails.webp_to_jpeg && is_webp(&bytes) {
+                    let source = bytes.clone();
+                    match task::spawn_blocking(move || webp_to_jpeg(&source)).await {
+                        Ok(Ok(jpeg)) => {
+                            bytes = Bytes::from(jpeg);
+                            content_type = "image/jpeg".to_string();
                         }
+                        Ok(Err(e)) => log::info!("Thumbnail {} is WebP and could not be converted: {}", cache_key, e),
+                        Err(e) => log::info!("Thumbnail {} conversion panicked: {}", cache_key, e),
                     }
-                    Err(_) => HttpResponse::NotFound().finish(),
                 }
-            } else {
-                let content_type = headers
-                    .get("content-type")
-                    .and_then(|ct| ct.to_str().ok())
-                    .unwrap_or("image/jpeg")
-                    .to_string();
+                let current_time = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs();
 
-                match resp.bytes().await {
-                    Ok(bytes) => {
-                        let current_time = SystemTime::now()
-                            .duration_since(UNIX_EPOCH)
-                            .unwrap()
-                            .as_secs();
-
-                        let mut cache = THUMBNAIL_CACHE.lock().await;
-                        cache.put(
-                            cache_key,
-                            (bytes.to_vec(), content_type.clone(), current_time),
-                        );
+                let mut cache = THUMBNAIL_CACHE.lock().await;
+                cache.put(
+                    cache_key,
+                    (bytes.to_vec(), content_type.clone(), current_time),
+                );
 
-                        HttpResponse::Ok()
-                            .content_type(content_type.as_str())
-                            .body(bytes)
-                    }
-                    Err(_) => HttpResponse::NotFound().finish(),
-                }
+                return HttpResponse::Ok()
+                    .content_type(content_type.as_str())
+                    .body(bytes);
+            }
+            // The host answered, the thumbnail just doesn't exist: other hosts mirror the same data.
+            Ok(None) => {
+                crate::routes::circuit::record_success(host);
+                return HttpResponse::NotFound().finish();
+            }
+            Err(e) => {
+                log::info!("Thumbnail host {} failed: {}", host, e);
+                crate::routes::circuit::record_failure(
+                    host,
+                    proxy_config.host_failure_threshold,
+                    cooldown,
+                );
             }
         }
-        Err(_) => HttpResponse::NotFound().finish(),
     }
+
+    HttpResponse::NotFound().finish()
+}
+
+/// Sniffed from the `RIFF....WEBP` header; the hosts' Content-Type isn't always right.
+fn is_webp(bytes: &[u8]) -> bool {
+    bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP"
+}
+
+fn webp_to_jpeg(bytes: &[u8]) -> Result<Vec<u8>, String> {
+    let img = image::load_from_memory_with_format(bytes, image::ImageFormat::WebP).map_err(|e| e.to_string())?;
+    let mut out = std::io::Cursor::new(Vec::new());
+    img.to_rgb8()
+        .write_to(&mut out, image::ImageOutputFormat::Jpeg(90))
+        .map_err(|e| e.to_string())?;
+    Ok(out.into_inner())
+}
+
+/// `Ok(None)` on 404, `Err` when the host itself misbehaves (network error, 5xx, blocked).
+async fn fetch_thumbnail(
+    client: &Client,
+    host: &str,
+    video_id: &str,
+    file: &str,
+) -> Result<Option<(Bytes, String)>, String> {
+    let url = format!("https://{}/vi/{}/{}", host, video_id, file);
+    let resp = client
+        .get(&url)
+        .timeout(Duration::from_secs(10))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    let status = resp.status();
+    if status.as_u16() == 404 {
+        return Ok(None);
+    }
+    if !status.is_success() {
+        return Err(format!("HTTP {}", status.as_u16()));
+    }
+    let content_type = resp
+        .headers()
+        .get("content-type")
+        .and_then(|ct| ct.to_str().ok())
+        .unwrap_or("image/jpeg")
+        .to_string();
+    if !content_type.starts_with("image/") {
+        return Err(format!("unexpected content type {}", content_type));
+    }
+    let bytes = resp.bytes().await.map_err(|e| e.to_string())?;
+    Ok(Some((bytes, content_type)))
 }
 
 #[utoipa::path(
     get,
     path = "/channel_icon/{path_video_id}",
     params(
-        ("path_video_id" = String, Path, description = "Channel ID (UC...), @handle, video ID or direct image URL")
+        ("path_video_id" = String, Path, description = "Channel ID (UC...), @handle, video ID or direct image URL"),
+        ("quality" = Option<String>, Query, description = "default (88px), medium (240px) or high (800px); default: default"),
+        ("size" = Option<u32>, Query, description = "Edge length in pixels (16-900), overrides quality")
     ),
     responses(
         (status = 200, description = "Channel icon image", content_type = "image/jpeg, image/png, image/webp"),
+        (status = 403, description = "Hotlinked from another site (proxy.hotlink)"),
         (status = 404, description = "Channel icon not found"),
         (status = 400, description = "Bad request")
     )
 )]
 pub async fn channel_icon(
+    req: HttpRequest,
     path: web::Path<String>,
+    query: web::Query<HashMap<String, String>>,
     data: web::Data<crate::AppState>,
 ) -> impl Responder {
     let input = path.into_inner();
     let config = &data.config;
+    if let Some(resp) = crate::routes::hotlink::check(&req, config) {
+        return resp;
+    }
+    let size = IconSize::from_query(&query);
 
     let decoded = urlencoding::decode(&input)
         .unwrap_or_else(|_| std::borrow::Cow::Owned(input.clone()))
         .to_string();
     
     if decoded.starts_with("http://") || decoded.starts_with("https://") {
-        return proxy_image(&decoded).await;
+        if !image_host_allowed(&config.proxy, &decoded) {
+            return HttpResponse::BadRequest().json(serde_json::json!({
+                "error": "Image host is not allowed"
+            }));
+        }
+        return proxy_image(&decoded, size).await;
     }
 
-    let client = Client::builder()
+    let client = crate::http_client::builder()
         .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/122.0.0.0 Safari/537.36")
         .build()
         .unwrap();
@@ -1501,7 +2588,7 @@ pub async fn channel_icon(
         Some(key) => key,
         None => {
             return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": "Missing innertube_key in config.yml"
+                "error": Msg::MissingInnertubeKey.text()
             }));
         }
     };
@@ -1557,7 +2644,7 @@ pub async fn channel_icon(
             .json(serde_json::json!({"error": "Channel avatar not found"}));
     }
 
-    proxy_image(&avatar_url).await
+    proxy_image(&avatar_url, size).await
 }
 
 #[utoipa::path(
@@ -1566,7 +2653,14 @@ pub async fn channel_icon(
     params(
         ("video_id" = String, Query, description = "YouTube video ID"),
         ("quality" = Option<String>, Query, description = "Video quality"),
-        ("proxy" = Option<String>, Query, description = "Use video proxy (true/false)")
+        ("proxy" = Option<String>, Query, description = "Use video proxy (true/false)"),
+        ("comments_count" = Option<u32>, Query, description = "Maximum number of comments to return (default: video.comments_count)"),
+        ("include_comments" = Option<bool>, Query, description = "Fetch and return comments (default: video.include_comments)"),
+        ("include_statistics" = Option<bool>, Query, description = "Return likes, views and comment count (default: video.include_statistics)"),
+        ("ratings" = Option<bool>, Query, description = "Add a 1-5 star rating from Return YouTube Dislike votes (api.ryd; implied by alt=jsonc)"),
+        ("fields" = Option<String>, Query, description = "Comma-separated whitelist of response fields (e.g. title,views,comments.author)"),
+        ("hl" = Option<String>, Query, description = "Metadata language (default: api.hl)"),
+        ("gl" = Option<String>, Query, description = "Region (default: api.gl)")
     ),
     responses(
         (status = 200, description = "Video information", body = VideoInfoResponse),
@@ -1581,6 +2675,7 @@ pub async fn get_ytvideo_info(
     let config = &data.config;
     let base = base_url(&req, config);
     let base_trimmed = base.trim_end_matches('/');
+    let profile_blocks = crate::routes::profiles::blocklist_for(&req);
 
     let mut query_params: HashMap<String, String> = HashMap::new();
     for pair in req.query_string().split('&') {
@@ -1594,7 +2689,7 @@ pub async fn get_ytvideo_info(
         Some(id) => id.clone(),
         None => {
             return HttpResponse::BadRequest().json(serde_json::json!({
-                "error": "ID видео не был передан."
+                "error": Msg::VideoIdRequired.text()
             }));
         }
     };
@@ -1609,40 +2704,70 @@ pub async fn get_ytvideo_info(
         .unwrap_or("true".to_string());
     let _use_video_proxy = proxy_param != "false";
 
+    let comments_count = query_params
+        .get("comments_count")
+        .and_then(|c| c.parse::<u32>().ok())
+        .unwrap_or(config.video.comments_count)
+        .min(100) as usize;
+    let include_comments = query_params
+        .get("include_comments")
+        .map(|v| v.to_lowercase() != "false" && v != "0")
+        .unwrap_or(config.video.include_comments)
+        && comments_count > 0;
+    let include_statistics = query_params
+        .get("include_statistics")
+        .map(|v| v.to_lowercase() != "false" && v != "0")
+        .unwrap_or(config.video.include_statistics);
+
     let innertube_key = match config.get_innertube_key() {
         Some(key) => key,
         None => {
             return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": "Missing innertube_key in config.yml"
+                "error": Msg::MissingInnertubeKey.text()
             }));
         }
     };
 
-    let client = Client::new();
+    let client = crate::http_client::shared();
     
-    let video_url = format!("https://www.youtube.com/watch?v={}", video_id);
+    let locale = crate::routes::locale::Locale::from_request(&req, config);
+    let video_url = format!("https://www.youtube.com/watch?v={}{}", video_id, locale.page_params());
     
-    let html = match client.get(&video_url).send().await {
+    let html = match client
+        .get(&video_url)
+        .header("Accept-Language", locale.accept_language())
+        .send()
+        .await
+    {
         Ok(resp) => match resp.text().await {
             Ok(text) => text,
             Err(e) => {
                 log::info!("Error fetching video page: {}", e);
                 return HttpResponse::InternalServerError().json(serde_json::json!({
-                    "error": "Failed to fetch video page"
+                    "error": Msg::FailedToFetchVideoPage.text()
                 }));
             }
         },
         Err(e) => {
             log::info!("Error fetching video page: {}", e);
             return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": "Failed to fetch video page"
+                "error": Msg::FailedToFetchVideoPage.text()
             }));
         }
     };
     
     let cfg = extract_ytcfg(&html);
     let pr = extract_initial_player_response(&html);
-    let api_key = cfg.get("INNERTUBE_API_KEY").and_then(|v| v.as_str()).unwrap_or(innertube_key);
+    let mut warnings: Vec<String> = Vec::new();
+    if pr.get("videoDetails").is_none() {
+        warnings.push("details: no player response in the watch page".to_string());
+    }
+    if crate::routes::blocklist::blocks_player_response(&pr, profile_blocks.as_ref()) {
+        return HttpResponse::Forbidden().json(serde_json::json!({
+            "error": Msg::VideoNotAvailable.text()
+        }));
+    }
+    let api_key = cfg.get("INNERTUBE_API_KEY").and_then(|v| v.as_str()).unwrap_or(&innertube_key);
     let mut ctx = cfg.get("INNERTUBE_CONTEXT").cloned().unwrap_or_else(|| {
         serde_json::json!({
             "client": {
@@ -1652,17 +2777,14 @@ pub async fn get_ytvideo_info(
         })
     });
     
-    if let Some(client) = ctx.get_mut("client").and_then(|c| c.as_object_mut()) {
-        client.insert("gl".to_string(), serde_json::Value::String("US".to_string())); // Set region to USA
-        client.insert("hl".to_string(), serde_json::Value::String("en-US".to_string())); // Set language to English (USA)
-    }
+    locale.apply_to_context(&mut ctx);
     
     let next_payload = serde_json::json!({
         "context": ctx,
         "videoId": video_id
     });
     
-    let next_url = format!("https://www.youtube.com/youtubei/v1/next?key={}", api_key);
+    let next_url = crate::endpoints::innertube(&format!("next?key={}", api_key));
     
     let next_data = match client
         .post(&next_url)
@@ -1684,7 +2806,25 @@ pub async fn get_ytvideo_info(
         }
     };
     
-    let comments_token = get_comments_token(&next_data);
+    let next_failed = match crate::routes::upstream::report("InnerTube next", &next_data) {
+        Some((code, reason)) => Some(format!("InnerTube next returned {} {}", code, reason)),
+        None if next_data.is_null() => Some("InnerTube next request failed".to_string()),
+        None => None,
+    };
+    if let Some(reason) = &next_failed {
+        // Likes, the subscriber count and the channel icon only come from `next`
+        warnings.push(format!("statistics: {}", reason));
+        warnings.push(format!("channel: {}", reason));
+        if include_comments {
+            warnings.push(format!("comments: {}", reason));
+        }
+    }
+
+    let comments_token = if include_comments {
+        get_comments_token(&next_data)
+    } else {
+        None
+    };
     let mut cont_resp = serde_json::Value::Null;
     
     if let Some(token) = comments_token {
@@ -1712,6 +2852,11 @@ pub async fn get_ytvideo_info(
                 serde_json::Value::Null
             }
         };
+        if cont_resp.is_null()
+            || crate::routes::upstream::report("InnerTube next", &cont_resp).is_some()
+        {
+            warnings.push("comments: comment continuation request failed".to_string());
+        }
     }
     
     let vd = pr.get("videoDetails").unwrap_or(&serde_json::Value::Null);
@@ -1720,11 +2865,14 @@ pub async fn get_ytvideo_info(
         .and_then(|m| m.get("playerMicroformatRenderer"))
         .unwrap_or(&serde_json::Value::Null);
     
-    let comments = if !cont_resp.is_null() {
+    let mut comments = if !include_comments {
+        Vec::new()
+    } else if !cont_resp.is_null() {
         extract_comments(&cont_resp, base_trimmed)
     } else {
         extract_comments(&next_data, base_trimmed)
     };
+    comments.truncate(comments_count);
     
     let likes = find_likes(&next_data);
     
@@ -1836,8 +2984,10 @@ pub async fn get_ytvideo_info(
     
     let final_video_url = if config.video.source == "direct" {
         format!(
-            "{}/direct_url?video_id={}",
-            base_trimmed, video_id
+            "{}/direct_url?video_id={}{}",
+            base_trimmed,
+            video_id,
+            crate::routes::signed_urls::direct_url_params(&video_id)
         )
     } else {
         "".to_string()
@@ -1845,18 +2995,50 @@ pub async fn get_ytvideo_info(
     
     let _final_video_url_with_proxy = if config.proxy.video_proxy && !final_video_url.is_empty() {
         format!(
-            "{}/video.proxy?url={}",
+            "{}/video.proxy?url={}{}",
             base_trimmed,
-            urlencoding::encode(&final_video_url)
+            urlencoding::encode(&final_video_url),
+            crate::routes::signed_urls::proxy_params(&final_video_url)
         )
     } else {
         final_video_url.clone()
     };
     
+    let resolved = cached_yt_dlp_info(&video_id).await;
+    let is_live = vd
+        .get("isLive")
+        .and_then(|v| v.as_bool())
+        .or_else(|| resolved.as_ref().and_then(|i| i.is_live))
+        .unwrap_or(false);
+    let duration = match resolved.as_ref().and_then(|i| i.duration) {
+        Some(secs) if duration.is_empty() => {
+            let secs = secs as u64;
+            format!("PT{}M{}S", secs / 60, secs % 60)
+        }
+        _ => duration,
+    };
+    let snippet = video_snippet(&video_id, &req, config).await;
+    let category_title = micro
+        .get("category")
+        .and_then(|c| c.as_str())
+        .filter(|c| !c.is_empty())
+        .map(String::from);
+    let tags = match snippet.as_ref() {
+        Some(s) if !s.tags.is_empty() => s.tags.clone(),
+        _ => vd
+            .get("keywords")
+            .and_then(|k| k.as_array())
+            .map(|k| k.iter().filter_map(|t| t.as_str()).map(String::from).collect())
+            .unwrap_or_default(),
+    };
+    let votes = crate::routes::ryd::votes(&video_id, config).await;
+    let rated = votes.filter(|_| crate::routes::ryd::ratings_requested(&req));
+
     let response = VideoInfoResponse {
         title: sanitize_text(&title),
         author,
         subscriber_count,
+        description_html: crate::routes::formatter::to_html(&description, base_trimmed, Some(&video_id)),
         description,
         video_id: video_id.clone(),
         channel_custom_url: micro
@@ -1868,9 +3050,11 @@ pub async fn get_ytvideo_info(
         embed_url: format!("https://www.youtube.com/embed/{}", video_id),
         duration,
         published_at,
-        likes: if !likes.is_empty() { Some(likes) } else { None },
-        views: if !views.is_empty() { Some(views) } else { None },
-        comment_count: if !comm_cnt.is_empty() { 
+        likes: if include_statistics && !likes.is_empty() { Some(likes) } else { None },
+        views: if include_statistics && !views.is_empty() { Some(views) } else { None },
+        comment_count: if !include_statistics {
+            None
+        } else if !comm_cnt.is_empty() { 
             Some(comm_cnt) 
         } else { 
             Some(comments.len().to_string()) 
@@ -1885,9 +3069,20 @@ pub async fn get_ytvideo_info(
         },
         thumbnail: format!("{}/thumbnail/{}", base_trimmed, video_id),
         video_url: final_video_url,
+        is_live,
+        chapters: resolved.as_ref().and_then(|i| i.chapters.clone()),
+        caption_languages: resolved.as_ref().map(|i| i.caption_languages()),
+        category_id: snippet.and_then(|s| s.category_id),
+        category_title,
+        tags,
+        dislikes: votes.map(|v| v.dislikes),
+        rating: rated.and_then(|v| v.star_rating()),
+        rating_count: rated.map(|v| v.rating_count()),
+        partial: !warnings.is_empty(),
+        warnings,
     };
     
-    HttpResponse::Ok().json(response)
+    crate::routes::fields::json_with_fields(&response, query_params.get("fields").map(|s| s.as_str()))
 }
 
 #[utoipa::path(
@@ -1899,7 +3094,9 @@ pub async fn get_ytvideo_info(
         ("offset" = Option<i32>, Query, description = "Offset for pagination (default: 0)"),
         ("limit" = Option<i32>, Query, description = "Limit for pagination (default: 50)"),
         ("order" = Option<String>, Query, description = "Order of results (relevance, date, rating, viewCount, title) (default: relevance)"),
-        ("token" = Option<String>, Query, description = "Refresh token for InnerTube recommendations")
+        ("token" = Option<String>, Query, description = "Refresh token for InnerTube recommendations"),
+        ("stats" = Option<bool>, Query, description = "Add exact views, likes and age to each video (one Data API call)"),
+        ("ratings" = Option<bool>, Query, description = "With stats, add a 1-5 star rating from Return YouTube Dislike (api.ryd)")
     ),
     responses(
         (status = 200, description = "List of related videos", body = [RelatedVideo]),
@@ -1914,6 +3111,7 @@ pub async fn get_related_videos(
     let config = &data.config;
     let base = base_url(&req, config);
     let base_trimmed = base.trim_end_matches('/');
+    let profile_blocks = crate::routes::profiles::blocklist_for(&req);
 
     let mut query_params: HashMap<String, String> = HashMap::new();
     for pair in req.query_string().split('&') {
@@ -1927,7 +3125,7 @@ pub async fn get_related_videos(
         Some(id) => id.clone(),
         None => {
             return HttpResponse::BadRequest().json(serde_json::json!({
-                "error": "ID видео не был передан."
+                "error": Msg::VideoIdRequired.text()
             }));
         }
     };
@@ -1954,13 +3152,13 @@ pub async fn get_related_videos(
 
     let desired_count = limit.max(20).min(100); // Target more videos like in Python script
 
-    let client = Client::new();
+    let client = crate::http_client::shared();
     
     let innertube_key = match config.get_innertube_key() {
         Some(key) => key,
         None => {
             return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": "Missing innertube_key in config.yml"
+                "error": Msg::MissingInnertubeKey.text()
             }));
         }
     };
@@ -1992,16 +3190,16 @@ pub async fn get_related_videos(
         Err(e) => {
             log::info!("Error fetching watch page: {}", e);
             return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": "Failed to fetch video page"
+                "error": Msg::FailedToFetchVideoPage.text()
             }));
         }
     };
 
     let ytcfg = extract_ytcfg(&html_response);
-    let api_key_from_cfg = ytcfg.get("INNERTUBE_API_KEY").and_then(|v| v.as_str()).unwrap_or(innertube_key);
+    let api_key_from_cfg = ytcfg.get("INNERTUBE_API_KEY").and_then(|v| v.as_str()).unwrap_or(&innertube_key);
     let context_from_cfg = ytcfg.get("INNERTUBE_CONTEXT").cloned().unwrap_or(context);
 
-    let next_url = format!("https://www.youtube.com/youtubei/v1/next?key={}", api_key_from_cfg);
+    let next_url = crate::endpoints::innertube(&format!("next?key={}", api_key_from_cfg));
     let body = serde_json::json!({
         "context": context_from_cfg,
         "videoId": video_id
@@ -2020,7 +3218,7 @@ pub async fn get_related_videos(
             Err(e) => {
                 log::info!("Error parsing next response: {}", e);
                 return HttpResponse::InternalServerError().json(serde_json::json!({
-                    "error": "Failed to parse response"
+                    "error": Msg::FailedToParseResponse.text()
                 }));
             }
         },
@@ -2079,6 +3277,13 @@ pub async fn get_related_videos(
         .filter(|v| {
             if v.video_id == video_id || seen.contains(&v.video_id) {
                 false
+            } else if crate::routes::blocklist::is_blocked(
+                profile_blocks.as_ref(),
+                v.channel_id.as_deref(),
+                &[v.title.as_str(), v.channel.as_str()],
+                None,
+            ) {
+                false
             } else {
                 seen.insert(v.video_id.clone());
                 true
@@ -2105,8 +3310,9 @@ pub async fn get_related_videos(
             base_trimmed, video.video_id, quality);
         
         let final_url = if config.proxy.video_proxy {
-            format!("{}/video.proxy?url={}", 
-                base_trimmed, urlencoding::encode(&video_url))
+            format!("{}/video.proxy?url={}{}", 
+                base_trimmed, urlencoding::encode(&video_url),
+                crate::routes::signed_urls::proxy_params(&video_url))
         } else {
             video_url
         };
@@ -2122,102 +3328,550 @@ pub async fn get_related_videos(
             url: final_url,
             source: "innertube".to_string(),
             color,
+            statistics: None,
         });
     }
 
+    if crate::routes::search::stats_requested(&req) {
+        let ids: Vec<&str> = result_videos.iter().map(|v| v.video_id.as_str()).collect();
+        let mut stats = crate::routes::search::video_statistics(&ids, &req, config).await;
+        for video in &mut result_videos {
+            video.statistics = stats.remove(&video.video_id);
+        }
+        let rows = result_videos
+            .iter_mut()
+            .filter_map(|v| Some((v.video_id.clone(), v.statistics.as_mut()?)))
+            .collect();
+        crate::routes::search::add_ratings(rows, &req, config).await;
+    }
+
     HttpResponse::Ok().json(result_videos)
 }
 
+const UP_NEXT_COUNTDOWN_SECS: u32 = 10;
+
+/// "4:13" / "1:02:03" -> seconds.
+fn clock_to_secs(text: &str) -> u64 {
+    text.trim()
+        .split(':')
+        .try_fold(0u64, |acc, part| part.trim().parse::<u64>().ok().map(|n| acc * 60 + n))
+        .unwrap_or(0)
+}
+
+/// Id of YouTube's own autoplay pick plus its countdown, from a `next` response.
+fn extract_autoplay(data: &Value) -> Option<(String, Option<u32>)> {
+    let video_id = data
+        .pointer("/contents/twoColumnWatchNextResults/autoplay/autoplay/sets/0/autoplayVideo/watchEndpoint/videoId")
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string())?;
+    let countdown = data
+        .pointer("/playerOverlays/playerOverlayRenderer/autoplay/playerOverlayAutoplayRenderer/countDownSecs")
+        .and_then(|v| v.as_u64())
+        .map(|n| n as u32);
+    Some((video_id, countdown))
+}
+
 #[utoipa::path(
     get,
-    path = "/get-direct-video-url.php",
+    path = "/get_up_next.php",
     params(
-        ("video_id" = String, Query, description = "YouTube video ID"),
-        ("quality" = Option<String>, Query, description = "Preferred quality")
+        ("video_id" = String, Query, description = "Video that is playing now"),
+        ("queue" = Option<String>, Query, description = "Comma-separated local queue; the entry after video_id wins over YouTube's pick"),
+        ("token" = Option<String>, Query, description = "Refresh token for a personalized pick")
     ),
     responses(
-        (status = 200, description = "Direct URL for the video", body = DirectUrlResponse),
-        (status = 400, description = "Missing video_id")
+        (status = 200, description = "Video to play next", body = UpNextResponse),
+        (status = 400, description = "Missing video ID"),
+        (status = 404, description = "Nothing to play next"),
+        (status = 500, description = "Internal server error")
     )
 )]
-pub async fn get_direct_video_url(
+pub async fn get_up_next(
     req: HttpRequest,
     data: web::Data<crate::AppState>,
+    auth_config: web::Data<crate::routes::auth::AuthConfig>,
 ) -> impl Responder {
+    let config = &data.config;
+    let base = base_url(&req, config);
+    let base_trimmed = base.trim_end_matches('/');
+    let profile_blocks = crate::routes::profiles::blocklist_for(&req);
+
     let mut query_params: HashMap<String, String> = HashMap::new();
     for pair in req.query_string().split('&') {
         let mut parts = pair.split('=');
         if let (Some(key), Some(value)) = (parts.next(), parts.next()) {
-            query_params.insert(key.to_string(), value.to_string());
+            let value = urlencoding::decode(value)
+                .map(|v| v.into_owned())
+                .unwrap_or_else(|_| value.to_string());
+            query_params.insert(key.to_string(), value);
         }
     }
 
     let video_id = match query_params.get("video_id") {
-        Some(id) => id.clone(),
-        None => {
+        Some(id) if !id.is_empty() => id.clone(),
+        _ => {
             return HttpResponse::BadRequest().json(serde_json::json!({
-                "error": "ID параметр обязателен"
+                "error": Msg::VideoIdRequired.text()
             }));
         }
     };
-
-    let quality = query_params.get("quality").map(|q| q.as_str());
-    match resolve_direct_stream_url(&video_id, quality, false, &data.config).await {
-        Ok(url) => HttpResponse::Ok().json(DirectUrlResponse { video_url: url }),
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
-            "error": "Failed to resolve direct url",
-            "details": e
-        })),
-    }
-}
-
-#[utoipa::path(
-    get,
-    path = "/direct_url",
-    params(
-        ("video_id" = String, Query, description = "YouTube video ID"),
-        ("quality" = Option<String>, Query, description = "Preferred quality"),
-        ("proxy" = Option<String>, Query, description = "Pass-through proxy (true/false)"),
-        ("codec" = Option<String>, Query, description = "Video codec for optional conversion: mpeg4 or h263. If passed, quality will be 360p")
-    ),
-    responses(
-        (status = 200, description = "Video stream"),
-        (status = 400, description = "Missing video_id or invalid codec")
-    )
-)]
-pub async fn direct_url(req: HttpRequest, data: web::Data<crate::AppState>) -> impl Responder {
-    spawn_direct_url_cleanup_if_needed();
-
-    let mut query_params: HashMap<String, String> = HashMap::new();
-    for pair in req.query_string().split('&') {
-        let mut parts = pair.split('=');
-        if let (Some(key), Some(value)) = (parts.next(), parts.next()) {
-            query_params.insert(key.to_string(), value.to_string());
+    let quality = query_params
+        .get("quality")
+        .cloned()
+        .unwrap_or_else(|| config.video.default_quality.clone());
+    let video_url = |id: &str| {
+        let url = format!(
+            "{}/get-ytvideo-info.php?video_id={}&quality={}",
+            base_trimmed, id, quality
+        );
+        if config.proxy.video_proxy {
+            format!(
+                "{}/video.proxy?url={}{}",
+                base_trimmed,
+                urlencoding::encode(&url),
+                crate::routes::signed_urls::proxy_params(&url)
+            )
+        } else {
+            url
         }
+    };
+
+    // The client's own queue comes first: the entry after the current video, or its head.
+    let queue: Vec<&str> = query_params
+        .get("queue")
+        .map(|q| q.split(',').map(|s| s.trim()).filter(|s| !s.is_empty()).collect())
+        .unwrap_or_default();
+    let queued = match queue.iter().position(|id| *id == video_id) {
+        Some(pos) => queue.get(pos + 1).copied(),
+        None => queue.first().copied(),
+    };
+    if let Some(next_id) = queued {
+        let (title, author, duration) = match fetch_player_response(next_id, config).await {
+            Ok(pr) => {
+                let text = |key: &str| {
+                    pr.pointer(&format!("/videoDetails/{}", key))
+                        .and_then(|v| v.as_str())
+                        .unwrap_or("")
+                        .to_string()
+                };
+                (text("title"), text("author"), get_duration_from_player_response(&pr))
+            }
+            Err(e) => {
+                log::info!("Up next: no details for queued {}: {}", next_id, e);
+                (String::new(), String::new(), 0)
+            }
+        };
+        return HttpResponse::Ok().json(UpNextResponse {
+            video_id: next_id.to_string(),
+            title,
+            author,
+            duration,
+            thumbnail: format!("{}/thumbnail/{}", base_trimmed, next_id),
+            url: video_url(next_id),
+            source: "queue".to_string(),
+            countdown_secs: UP_NEXT_COUNTDOWN_SECS,
+        });
     }
 
-    let video_id = match query_params.get("video_id") {
-        Some(id) => id.clone(),
+    let innertube_key = match config.get_innertube_key() {
+        Some(key) => key,
         None => {
-            return HttpResponse::BadRequest().json(serde_json::json!({
-                "error": "video_id parameter is required"
+            return HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": Msg::MissingInnertubeKey.text()
             }));
         }
     };
+    let access_token = match query_params.get("token").filter(|t| !t.is_empty()) {
+        Some(token) => crate::routes::oauth::refresh_access_token(token, &auth_config)
+            .await
+            .map_err(|e| log::info!("Up next: token refresh failed: {}", e))
+            .ok(),
+        None => None,
+    };
 
-    // 1. Старые кодеки (всегда конвертация на лету)
-    let codec = query_params.get("codec").map(|c| c.as_str());
-	if let Some(codec_str) = codec {
-		if codec_str != "mpeg4" && codec_str != "h263" {
-			return HttpResponse::BadRequest().json(serde_json::json!({
-				"error": "Unsupported codec",
-				"details": format!("Codec '{}' is not supported. Available: mpeg4, h263", codec_str),
-				"supported_codecs":["mpeg4", "h263"]
-			}));
-		}
-
-        // Get video duration and check if it's longer than 40 minutes
-        let player_response = match fetch_player_response(&video_id, &data.config).await {
+    let body = serde_json::json!({
+        "context": {
+            "client": {
+                "clientName": "WEB",
+                "clientVersion": "2.20260128.05.00"
+            }
+        },
+        "videoId": video_id
+    });
+    let mut request = crate::http_client::shared()
+        .post(crate::endpoints::innertube(&format!("next?key={}", innertube_key)))
+        .header(reqwest::header::USER_AGENT, "Mozilla/5.0 (Windows NT 10.0; Win64; x64) Chrome/121.0.0.0 Safari/537.36")
+        .header(reqwest::header::ACCEPT_LANGUAGE, "en-US,en;q=0.9")
+        .json(&body)
+        .timeout(std::time::Duration::from_secs(25));
+    if let Some(token) = &access_token {
+        request = request.header("Authorization", format!("Bearer {}", token));
+    }
+    let next_response: Value = match request.send().await {
+        Ok(resp) => match resp.json().await {
+            Ok(json) => json,
+            Err(e) => {
+                log::info!("Up next: error parsing next response: {}", e);
+                return HttpResponse::InternalServerError().json(serde_json::json!({
+                    "error": Msg::FailedToParseResponse.text()
+                }));
+            }
+        },
+        Err(e) => {
+            log::info!("Up next: error making next request: {}", e);
+            return HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Failed to fetch watch-next data"
+            }));
+        }
+    };
+
+    let related: Vec<RelatedVideoInfo> = extract_related_videos_from_response(&next_response)
+        .into_iter()
+        .filter(|v| {
+            v.video_id != video_id
+                && !crate::routes::blocklist::is_blocked(
+                    profile_blocks.as_ref(),
+                    v.channel_id.as_deref(),
+                    &[v.title.as_str(), v.channel.as_str()],
+                    None,
+                )
+        })
+        .collect();
+
+    let autoplay = extract_autoplay(&next_response);
+    let countdown_secs = autoplay
+        .as_ref()
+        .and_then(|(_, c)| *c)
+        .unwrap_or(UP_NEXT_COUNTDOWN_SECS);
+    // YouTube's pick is only used when it also shows up (unblocked) among the related videos.
+    let (pick, source) = match autoplay
+        .as_ref()
+        .and_then(|(id, _)| related.iter().find(|v| &v.video_id == id))
+    {
+        Some(v) => (Some(v), "autoplay"),
+        None => (related.first(), "related"),
+    };
+
+    match pick {
+        Some(v) => HttpResponse::Ok().json(UpNextResponse {
+            video_id: v.video_id.clone(),
+            title: v.title.clone(),
+            author: v.channel.clone(),
+            duration: clock_to_secs(&v.duration),
+            thumbnail: format!("{}/thumbnail/{}", base_trimmed, v.video_id),
+            url: video_url(&v.video_id),
+            source: source.to_string(),
+            countdown_secs,
+        }),
+        None => HttpResponse::NotFound().json(serde_json::json!({
+            "error": "No video to play next"
+        })),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/get-direct-video-url.php",
+    params(
+        ("video_id" = String, Query, description = "YouTube video ID"),
+        ("quality" = Option<String>, Query, description = "Preferred quality")
+    ),
+    responses(
+        (status = 200, description = "Direct URL for the video", body = DirectUrlResponse),
+        (status = 400, description = "Missing video_id")
+    )
+)]
+pub async fn get_direct_video_url(
+    req: HttpRequest,
+    data: web::Data<crate::AppState>,
+) -> impl Responder {
+    let mut query_params: HashMap<String, String> = HashMap::new();
+    for pair in req.query_string().split('&') {
+        let mut parts = pair.split('=');
+        if let (Some(key), Some(value)) = (parts.next(), parts.next()) {
+            query_params.insert(key.to_string(), value.to_string());
+        }
+    }
+
+    let video_id = match query_params.get("video_id") {
+        Some(id) => id.clone(),
+        None => {
+            return HttpResponse::BadRequest().json(serde_json::json!({
+                "error": Msg::IdRequired.text()
+            }));
+        }
+    };
+
+    let quality = query_params.get("quality").map(|q| q.as_str());
+    // Progressive formats end at 720p; a higher quality is only reachable through the remux
+    // in /direct_url, so hand that out instead of a lower-quality googlevideo link
+    if let Some(height) = quality.and_then(parse_quality_height).filter(|h| *h > 720) {
+        if let Ok(info) = yt_dlp_info(&video_id, &data.config).await {
+            if info.progressive_height() < height && info.pick_adaptive_pair(height).is_some() {
+                let base = base_url(&req, &data.config);
+                return HttpResponse::Ok().json(DirectUrlResponse {
+                    video_url: format!(
+                        "{}/direct_url?video_id={}&quality={}p{}",
+                        base.trim_end_matches('/'),
+                        urlencoding::encode(&video_id),
+                        height,
+                        crate::routes::signed_urls::direct_url_params(&video_id)
+                    ),
+                });
+            }
+        }
+    }
+    let device = crate::routes::device_profile::detect(&req, &data.config);
+    match resolve_direct_stream_url(&video_id, quality, false, device.as_ref(), &data.config).await {
+        Ok(url) if data.config.server.force_http_urls => {
+            let base = base_url(&req, &data.config);
+            HttpResponse::Ok().json(DirectUrlResponse {
+                video_url: format!(
+                    "{}/video.proxy?url={}{}",
+                    base.trim_end_matches('/'),
+                    urlencoding::encode(&url),
+                    crate::routes::signed_urls::proxy_params(&url)
+                ),
+            })
+        }
+        Ok(url) => HttpResponse::Ok().json(DirectUrlResponse { video_url: url }),
+        Err(e) => resolver_error("Failed to resolve direct url", e),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/get_video_formats.php",
+    params(
+        ("video_id" = String, Query, description = "YouTube video ID")
+    ),
+    responses(
+        (status = 200, description = "Directly playable formats, muxed first, best first", body = VideoFormatsResponse),
+        (status = 400, description = "Missing video_id"),
+        (status = 403, description = "Hotlinked from another site (proxy.hotlink)"),
+        (status = 500, description = "yt-dlp failed"),
+        (status = 504, description = "yt-dlp timed out")
+    )
+)]
+pub async fn get_video_formats(
+    req: HttpRequest,
+    query: web::Query<HashMap<String, String>>,
+    data: web::Data<crate::AppState>,
+) -> impl Responder {
+    let Some(video_id) = query.get("video_id").filter(|id| !id.is_empty()).cloned() else {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": Msg::VideoIdRequired.text()
+        }));
+    };
+    if let Some(resp) = crate::routes::hotlink::check(&req, &data.config) {
+        return resp;
+    }
+    let info = match yt_dlp_info(&video_id, &data.config).await {
+        Ok(info) => info,
+        Err(e) => return resolver_error("Failed to resolve video formats", e),
+    };
+
+    let base = base_url(&req, &data.config);
+    let base = base.trim_end_matches('/');
+    let mut formats: Vec<VideoFormat> = info
+        .formats
+        .iter()
+        .filter(|f| f.is_direct() && (f.has_video() || f.has_audio()))
+        .filter_map(|f| {
+            let url = f.url.as_deref()?;
+            let kind = match (f.has_video(), f.has_audio()) {
+                (true, true) => "video+audio",
+                (true, false) => "video",
+                _ => "audio",
+            };
+            let resolution = match (f.width, f.height) {
+                (Some(w), Some(h)) if f.has_video() => format!("{}x{}", w, h),
+                (None, Some(h)) if f.has_video() => format!("{}p", h),
+                _ => "audio only".to_string(),
+            };
+            Some(VideoFormat {
+                itag: f.format_id.clone().unwrap_or_default(),
+                ext: f.ext.clone().unwrap_or_default(),
+                kind: kind.to_string(),
+                resolution,
+                width: f.width,
+                height: f.height,
+                fps: f.fps.filter(|_| f.has_video()),
+                vcodec: f.vcodec.clone().filter(|_| f.has_video()),
+                acodec: f.acodec.clone().filter(|_| f.has_audio()),
+                bitrate_kbps: f.tbr.or(f.abr),
+                filesize: f.filesize.or(f.filesize_approx),
+                url: format!(
+                    "{}/video.proxy?url={}{}",
+                    base,
+                    urlencoding::encode(url),
+                    crate::routes::signed_urls::proxy_params(url)
+                ),
+            })
+        })
+        .collect();
+    let rank = |f: &VideoFormat| match f.kind.as_str() {
+        "video+audio" => 0,
+        "video" => 1,
+        _ => 2,
+    };
+    formats.sort_by(|a, b| {
+        rank(a)
+            .cmp(&rank(b))
+            .then(b.height.unwrap_or(0).cmp(&a.height.unwrap_or(0)))
+            .then(b.bitrate_kbps.unwrap_or(0.0).total_cmp(&a.bitrate_kbps.unwrap_or(0.0)))
+    });
+
+    HttpResponse::Ok()
+        .insert_header(("Cache-Control", "no-store"))
+        .json(VideoFormatsResponse {
+            video_id,
+            duration: info.duration,
+            formats,
+        })
+}
+
+#[utoipa::path(
+    get,
+    path = "/direct_url",
+    params(
+        ("video_id" = String, Query, description = "YouTube video ID"),
+        ("quality" = Option<String>, Query, description = "Preferred quality, or auto to pick 240p/360p/720p from the client's last /probe. Above 720p the separate video and audio streams are combined into one MP4"),
+        ("proxy" = Option<String>, Query, description = "Pass-through proxy (true/false)"),
+        ("codec" = Option<String>, Query, description = "Video codec for optional conversion: mpeg4, h263 or h264 (Baseline, on the GPU with video.hwaccel). If passed, quality will be 360p"),
+        ("format" = Option<String>, Query, description = "Live transcode streamed while it runs: 3gp (H.263 176x144 + AMR-NB, fragmented) for feature phones, or mp3"),
+        ("normalize" = Option<bool>, Query, description = "With format: level the loudness (video.loudnorm); off by default for video"),
+        ("start" = Option<String>, Query, description = "Start of a clip: seconds or 1m30s / 1:30 (alias t)"),
+        ("end" = Option<String>, Query, description = "End of a clip, same format as start"),
+        ("expires" = Option<u64>, Query, description = "Expiry of a signed URL (proxy.signed_urls)"),
+        ("sig" = Option<String>, Query, description = "Signature of a signed URL (proxy.signed_urls)")
+    ),
+    responses(
+        (status = 200, description = "Video stream"),
+        (status = 400, description = "Missing video_id, invalid codec or format, or invalid start/end"),
+        (status = 403, description = "Missing, expired or invalid signature (proxy.signed_urls), or hotlinked (proxy.hotlink)"),
+        (status = 501, description = "ffmpeg or the codec's encoder is not available")
+    )
+)]
+pub async fn direct_url(req: HttpRequest, data: web::Data<crate::AppState>) -> impl Responder {
+    spawn_direct_url_cleanup_if_needed();
+
+    let mut query_params: HashMap<String, String> = HashMap::new();
+    for pair in req.query_string().split('&') {
+        let mut parts = pair.split('=');
+        if let (Some(key), Some(value)) = (parts.next(), parts.next()) {
+            query_params.insert(key.to_string(), value.to_string());
+        }
+    }
+
+    let video_id = match query_params.get("video_id") {
+        Some(id) => id.clone(),
+        None => {
+            return HttpResponse::BadRequest().json(serde_json::json!({
+                "error": Msg::VideoIdRequired.text()
+            }));
+        }
+    };
+    if let Some(resp) = crate::routes::signed_urls::check_direct_url(&req, &query_params, &video_id) {
+        return resp;
+    }
+    if let Some(resp) = crate::routes::hotlink::check(&req, &data.config) {
+        return resp;
+    }
+
+    let clip = match Clip::from_query(&query_params) {
+        Ok(c) => c,
+        Err(e) => {
+            return HttpResponse::BadRequest().json(serde_json::json!({
+                "error": "Invalid clip",
+                "details": e
+            }));
+        }
+    };
+    if clip.is_some() {
+        if let Some(resp) = unsupported_profile(&[]) {
+            return resp;
+        }
+    }
+
+    // 0. Live transcodes: output goes to the client while ffmpeg is still running
+    if let Some(format) = query_params.get("format") {
+        let Some(profile) = crate::routes::transcode::profile(format, false) else {
+            let formats = crate::routes::transcode::profile_names(false);
+            return HttpResponse::BadRequest().json(serde_json::json!({
+                "error": "Unsupported format",
+                "details": format!("Format '{}' is not supported. Available: {}", format, formats.join(", ")),
+                "supported_formats": formats
+            }));
+        };
+        if let Some(resp) = unsupported_profile(profile.encoders) {
+            return resp;
+        }
+        let input_args = clip.map(|c| c.ffmpeg_args()).unwrap_or_default();
+        let user_agent = data.config.get_innertube_user_agent();
+        let source = match crate::routes::library::find_video(&data.config.library, &video_id) {
+            Some((path, _)) => path.to_string_lossy().to_string(),
+            None => match resolve_direct_stream_url(&video_id, Some("360"), false, None, &data.config).await {
+                Ok(url) => url,
+                Err(e) => return resolver_error("Failed to resolve video url for transcoding", e),
+            },
+        };
+        let normalize =
+            crate::routes::transcode::normalize_requested(query_params.get("normalize"), profile, &data.config.video);
+        let permit = data.codec_semaphore.clone().acquire_owned().await.ok();
+        return crate::routes::transcode::stream(
+            &source,
+            &user_agent,
+            profile,
+            input_args,
+            normalize,
+            &data.config.video,
+            permit,
+        );
+    }
+
+    // 1. Старые кодеки (всегда конвертация на лету)
+    let codec = query_params.get("codec").map(|c| c.as_str());
+	if let Some(codec_str) = codec {
+		if codec_str != "mpeg4" && codec_str != "h263" && codec_str != "h264" {
+			return HttpResponse::BadRequest().json(serde_json::json!({
+				"error": "Unsupported codec",
+				"details": format!("Codec '{}' is not supported. Available: mpeg4, h263, h264", codec_str),
+				"supported_codecs":["mpeg4", "h263", "h264"]
+			}));
+		}
+        let h264_encoder = crate::routes::hwaccel::current().h264_encoder();
+        let encoders: &[&str] = match codec_str {
+            "mpeg4" => &["mpeg4"],
+            "h264" => &[h264_encoder, "aac"],
+            _ => &["h263", "libopencore_amrnb"],
+        };
+        if let Some(resp) = unsupported_profile(encoders) {
+            return resp;
+        }
+
+        let profile = match clip {
+            Some(c) => format!(
+                "{}_{}-{}",
+                codec_str,
+                c.start,
+                c.end.map(|e| e.to_string()).unwrap_or_default()
+            ),
+            None => codec_str.to_string(),
+        };
+        let cache_key = transcode_cache::key(&video_id, &profile);
+        let ext = converted_extension(codec_str);
+        if let Some(path) = transcode_cache::lookup(&data.config.cache.transcodes, &cache_key, ext) {
+            let mut resp = serve_mp4_from_cache(&path, &req, None);
+            if ext == "3gp" {
+                resp.headers_mut()
+                    .insert(CONTENT_TYPE, HeaderValue::from_static("video/3gpp"));
+            }
+            return resp;
+        }
+
+        // Get video duration and check if it's longer than 40 minutes
+        let player_response = match fetch_player_response(&video_id, &data.config).await {
             Ok(data) => data,
             Err(e) => {
                 return HttpResponse::InternalServerError().json(serde_json::json!({
@@ -2234,18 +3888,21 @@ pub async fn direct_url(req: HttpRequest, data: web::Data<crate::AppState>) -> i
             }));
         }
 
-		let direct_url = match resolve_direct_stream_url(&video_id, Some("360"), false, &data.config).await {
+		let direct_url = match resolve_direct_stream_url(&video_id, Some("360"), false, None, &data.config).await {
 			Ok(url) => url,
-			Err(e) => {
-				return HttpResponse::InternalServerError().json(serde_json::json!({
-					"error": "Failed to resolve video url for conversion",
-					"details": e
-				}));
-			}
+			Err(e) => return resolver_error("Failed to resolve video url for conversion", e),
 		};
 		let user_agent = data.config.get_innertube_user_agent();
 		let permit = data.codec_semaphore.clone().acquire_owned().await.ok();
-		return stream_converted_video(&direct_url, &user_agent, &video_id, codec_str, permit);
+		return stream_converted_video(
+            &direct_url,
+            &user_agent,
+            &cache_key,
+            &data.config.cache.transcodes,
+            codec_str,
+            clip,
+            permit,
+        );
 	}
 
     // 2. HLS
@@ -2268,8 +3925,48 @@ pub async fn direct_url(req: HttpRequest, data: web::Data<crate::AppState>) -> i
         }
     } 
 
+    // Сохранённое в офлайн-библиотеке отдаём с диска, без обращения к YouTube
+    if let Some((path, duration)) = crate::routes::library::find_video(&data.config.library, &video_id) {
+        if let Some(clip) = clip {
+            let user_agent = data.config.get_innertube_user_agent();
+            return stream_clip(&path.to_string_lossy(), &user_agent, clip, duration);
+        }
+        return serve_mp4_from_cache(&path, &req, Some(duration));
+    }
+
     let proxy_param = query_params.get("proxy").map(|p| p.to_lowercase()).unwrap_or_else(|| "true".to_string());
-    let use_proxy = proxy_param != "false";
+    // a redirect to googlevideo would need the TLS force_http_urls clients lack
+    let use_proxy = proxy_param != "false" || data.config.server.force_http_urls;
+
+    let requested_quality = query_params.get("quality").map(|q| q.as_str());
+    
+    // without a height of its own the client gets no more than its device profile allows
+    let device_height = crate::routes::device_profile::detect(&req, &data.config).map_or(u32::MAX, |d| d.max_height);
+    let default_height = || parse_quality_height(&data.config.video.default_quality).unwrap_or(360).min(device_height);
+    let mut target_height = match requested_quality {
+        Some(q) if q.eq_ignore_ascii_case("auto") => {
+            let height = crate::routes::bandwidth::auto_height(&req)
+                .unwrap_or_else(|| default_height().min(360))
+                .min(device_height);
+            log::info!("quality=auto for {}: {}p", video_id, height);
+            height
+        }
+        _ => requested_quality
+            .and_then(parse_quality_height)
+            .unwrap_or_else(default_height),
+    };
+
+    // Up to 360p the progressive stream is served; if an earlier play left all of it in the
+    // segment cache, replay it from disk without asking YouTube for a fresh URL
+    if use_proxy && target_height <= 360 && clip.is_none() && req.method() == actix_web::http::Method::GET {
+        let segments = &data.config.cache.segments;
+        let key = crate::routes::segment_cache::key(&video_id, PROGRESSIVE_360P_ITAG);
+        if crate::routes::segment_cache::is_complete(&key, segments).await {
+            if let Some(resp) = crate::routes::segment_cache::serve(&key, "", &req, "video/mp4", segments).await {
+                return resp;
+            }
+        }
+    }
 
     // Получаем инфо о видео
     let player_response = match fetch_player_response(&video_id, &data.config).await {
@@ -2283,14 +3980,43 @@ pub async fn direct_url(req: HttpRequest, data: web::Data<crate::AppState>) -> i
     };
 
     let duration_seconds = get_duration_from_player_response(&player_response);
-    let requested_quality = query_params.get("quality").map(|q| q.as_str());
-    
-    let mut target_height = requested_quality
-        .and_then(|q| parse_quality_height(q))
-        .unwrap_or_else(|| parse_quality_height(&data.config.video.default_quality).unwrap_or(360));
 
     // --- ЛОГИКА КАЧЕСТВА ---
 
+    // 0. Above 720p there are only separate video and audio streams. Unless the short-video
+    // download below will mux them, remux them live rather than quietly falling back to 360p
+    let downloads_full_file = use_proxy && duration_seconds <= 1800;
+    if target_height > 720 && !downloads_full_file {
+        if let Some(resp) = unsupported_profile(&[]) {
+            return resp;
+        }
+        match yt_dlp_info(&video_id, &data.config).await {
+            Ok(info) => {
+                if let Some((video, audio)) = info.pick_adaptive_pair(target_height) {
+                    if let (Some(video_url), Some(audio_url)) = (&video.url, &audio.url) {
+                        log::info!(
+                            "Remuxing {} live: {}p video + audio (requested {}p)",
+                            video_id,
+                            video.height.unwrap_or(0),
+                            target_height
+                        );
+                        if req.method() == actix_web::http::Method::HEAD {
+                            let length = clip.map(|c| c.length(duration_seconds)).unwrap_or(duration_seconds);
+                            return HttpResponse::Ok()
+                                .insert_header((CONTENT_TYPE, HeaderValue::from_static("video/mp4")))
+                                .insert_header(("X-Content-Duration", length.to_string()))
+                                .finish();
+                        }
+                        let user_agent = data.config.get_innertube_user_agent();
+                        return stream_adaptive_mux(video_url, audio_url, &user_agent, clip, duration_seconds);
+                    }
+                }
+                log::warn!("No adaptive streams to remux for {}; falling back", video_id);
+            }
+            Err(e) => log::warn!("yt-dlp failed for {} remux: {}; falling back", video_id, e),
+        }
+    }
+
     // 1. Длинные видео (> 30 мин) и высокое качество -> Форсируем 360p
     if target_height > 360 && duration_seconds > 1800 {
         log::info!("Video > 30m ({}s). Forcing 360p for stability.", duration_seconds);
@@ -2301,12 +4027,19 @@ pub async fn direct_url(req: HttpRequest, data: web::Data<crate::AppState>) -> i
     // 2. Короткие видео (< 30 мин) и высокое качество -> Скачиваем целиком на сервер
     // 2. Короткие видео (< 30 мин) и высокое качество -> Скачиваем целиком на сервер
     if target_height > 360 && use_proxy {
+        if let Some(resp) = unsupported_profile(&[]) {
+            return resp;
+        }
         log::info!("Short video ({}s) in {}p. Downloading full file via yt-dlp...", duration_seconds, target_height);
         
         // Теперь здесь создастся файл вида yt_api_video_ID_1080p.mp4
-        match download_mux_to_temp_file(video_id.clone(), target_height).await {
+        match download_mux_to_temp_file(video_id.clone(), target_height, &data.config).await {
             Ok(path) => {
                 log::info!("Download complete: {}. Serving file.", path.display());
+                if let Some(clip) = clip {
+                    let user_agent = data.config.get_innertube_user_agent();
+                    return stream_clip(&path.to_string_lossy(), &user_agent, clip, duration_seconds);
+                }
                 return serve_mp4_from_cache(&path, &req, Some(duration_seconds));
             },
             Err(e) => {
@@ -2327,20 +4060,27 @@ pub async fn direct_url(req: HttpRequest, data: web::Data<crate::AppState>) -> i
         Some(u) => u,
         None => {
              log::warn!("Falling back to yt-dlp for direct URL");
-             match resolve_direct_stream_url(&video_id, Some("360"), false, &data.config).await {
+             match resolve_direct_stream_url(&video_id, Some("360"), false, None, &data.config).await {
                 Ok(url) => url,
-                Err(e) => {
-                    return HttpResponse::InternalServerError().json(serde_json::json!({
-                        "error": "Failed to resolve video url",
-                        "details": e
-                    }));
-                }
+                Err(e) => return resolver_error("Failed to resolve video url", e),
              }
         }
     };
 
+    // Клип всегда идёт через ffmpeg, даже при proxy=false
+    if let Some(clip) = clip {
+        if req.method() == actix_web::http::Method::HEAD {
+            return HttpResponse::Ok()
+                .insert_header((CONTENT_TYPE, HeaderValue::from_static("video/mp4")))
+                .insert_header(("X-Content-Duration", clip.length(duration_seconds).to_string()))
+                .finish();
+        }
+        let user_agent = data.config.get_innertube_user_agent();
+        return stream_clip(&final_url, &user_agent, clip, duration_seconds);
+    }
+
     if req.method() == actix_web::http::Method::HEAD {
-        let client = Client::new();
+        let client = crate::http_client::googlevideo();
         match client.head(&final_url).send().await {
             Ok(resp) => {
                 let mut builder = HttpResponse::build(resp.status());
@@ -2360,7 +4100,20 @@ pub async fn direct_url(req: HttpRequest, data: web::Data<crate::AppState>) -> i
             .insert_header((LOCATION, final_url))
             .finish()
     } else {
-        proxy_stream_response(&final_url, &req, "video/mp4").await
+        let key = crate::routes::segment_cache::key_for(&video_id, &final_url, "video");
+        if let Some(resp) =
+            crate::routes::segment_cache::serve(&key, &final_url, &req, "video/mp4", &data.config.cache.segments).await
+        {
+            return resp;
+        }
+        let source = StreamSource {
+            video_id: video_id.clone(),
+            quality: Some(target_height.min(360).to_string()),
+            audio_only: false,
+            device: None,
+            data: data.clone(),
+        };
+        proxy_stream_response(&final_url, &req, "video/mp4", Some(source)).await
     }
 }
 
@@ -2384,31 +4137,416 @@ pub async fn hls_manifest_url(req: HttpRequest, data: web::Data<crate::AppState>
             query_params.insert(key.to_string(), value.to_string());
         }
     }
-
-    let video_id = match query_params.get("video_id") {
-        Some(id) => id.clone(),
-        None => {
-            return HttpResponse::BadRequest().json(serde_json::json!({
-                "error": "video_id parameter is required"
-            }));
-        }
+
+    let video_id = match query_params.get("video_id") {
+        Some(id) => id.clone(),
+        None => {
+            return HttpResponse::BadRequest().json(serde_json::json!({
+                "error": Msg::VideoIdRequired.text()
+            }));
+        }
+    };
+
+    match get_hls_manifest_url(&video_id, &data.config).await {
+        Ok(manifest_url) => {
+            HttpResponse::Ok().json(HlsManifestUrlResponse {
+                hls_manifest_url: manifest_url,
+                video_id,
+                message: Some("HLS Master Manifest URL - use this for streams without quality selection".to_string()),
+            })
+        },
+        Err(e) => {
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Failed to get HLS manifest URL",
+                "details": e
+            }))
+        }
+    }
+}
+
+const HLS_CONTENT_TYPE: &str = "application/vnd.apple.mpegurl";
+
+/// The HLS variants to advertise: H.264 ones when there are any (old TVs and iOS decode
+/// nothing else), lowest bitrate first so players start on the cheapest rendition.
+fn hls_variants(info: &YtDlpInfo) -> Vec<&YtDlpFormat> {
+    let hls: Vec<&YtDlpFormat> = info.formats.iter().filter(|f| f.is_hls_muxed()).collect();
+    let is_avc = |f: &&YtDlpFormat| f.vcodec.as_deref().map(|c| c.starts_with("avc1")).unwrap_or(false);
+    let mut variants: Vec<&YtDlpFormat> = if hls.iter().any(is_avc) {
+        hls.into_iter().filter(is_avc).collect()
+    } else {
+        hls
+    };
+    variants.sort_by(|a, b| a.tbr.unwrap_or(0.0).total_cmp(&b.tbr.unwrap_or(0.0)));
+    variants
+}
+
+/// Points a playlist entry at `/video.proxy`, resolving it against the playlist's own URL.
+fn hls_proxy_uri(uri: &str, playlist_url: &reqwest::Url, base: &str) -> String {
+    let absolute = playlist_url
+        .join(uri)
+        .map(|u| u.to_string())
+        .unwrap_or_else(|_| uri.to_string());
+    format!(
+        "{}/video.proxy?url={}{}",
+        base,
+        urlencoding::encode(&absolute),
+        crate::routes::signed_urls::proxy_params(&absolute)
+    )
+}
+
+/// Rewrites every segment line and every `URI="..."` attribute (`#EXT-X-MAP`, `#EXT-X-KEY`)
+/// of a media playlist so the client fetches them through the server.
+fn rewrite_hls_media(body: &str, playlist_url: &reqwest::Url, base: &str) -> String {
+    let mut out = String::with_capacity(body.len() * 2);
+    for line in body.lines() {
+        let line = line.trim_end();
+        if line.is_empty() {
+            out.push('\n');
+            continue;
+        }
+        if !line.starts_with('#') {
+            out.push_str(&hls_proxy_uri(line, playlist_url, base));
+        } else if let Some(start) = line.find("URI=\"") {
+            let value_start = start + 5;
+            match line[value_start..].find('"') {
+                Some(len) => {
+                    out.push_str(&line[..value_start]);
+                    out.push_str(&hls_proxy_uri(&line[value_start..value_start + len], playlist_url, base));
+                    out.push_str(&line[value_start + len..]);
+                }
+                None => out.push_str(line),
+            }
+        } else {
+            out.push_str(line);
+        }
+        out.push('\n');
+    }
+    out
+}
+
+#[utoipa::path(
+    get,
+    path = "/hls/{video_id}/master.m3u8",
+    params(
+        ("video_id" = String, Path, description = "YouTube video ID")
+    ),
+    responses(
+        (status = 200, description = "HLS master playlist", content_type = "application/vnd.apple.mpegurl"),
+        (status = 403, description = "Hotlinked from another site (proxy.hotlink)"),
+        (status = 404, description = "No HLS formats for this video"),
+        (status = 500, description = "yt-dlp failed"),
+        (status = 504, description = "yt-dlp timed out")
+    )
+)]
+pub async fn hls_master(
+    path: web::Path<String>,
+    req: HttpRequest,
+    data: web::Data<crate::AppState>,
+) -> impl Responder {
+    let video_id = path.into_inner();
+    if let Some(resp) = crate::routes::hotlink::check(&req, &data.config) {
+        return resp;
+    }
+    let info = match yt_dlp_info(&video_id, &data.config).await {
+        Ok(info) => info,
+        Err(e) => return resolver_error("Failed to resolve video formats", e),
+    };
+    let variants = hls_variants(&info);
+    if variants.is_empty() {
+        return HttpResponse::NotFound().json(serde_json::json!({
+            "error": "No HLS formats available",
+            "details": video_id
+        }));
+    }
+
+    let base = base_url(&req, &data.config);
+    let base = base.trim_end_matches('/');
+    let mut playlist = String::from("#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-INDEPENDENT-SEGMENTS\n");
+    for format in variants {
+        let bandwidth = (format.tbr.unwrap_or(0.0) * 1000.0).round() as u64;
+        let mut inf = format!("#EXT-X-STREAM-INF:BANDWIDTH={}", bandwidth.max(1));
+        if let (Some(w), Some(h)) = (format.width, format.height) {
+            inf.push_str(&format!(",RESOLUTION={}x{}", w, h));
+        }
+        if let Some(fps) = format.fps.filter(|f| *f > 0.0) {
+            inf.push_str(&format!(",FRAME-RATE={:.3}", fps));
+        }
+        if let (Some(v), Some(a)) = (format.vcodec.as_deref(), format.acodec.as_deref()) {
+            inf.push_str(&format!(",CODECS=\"{},{}\"", v, a));
+        }
+        playlist.push_str(&format!(
+            "{}\n{}/hls/{}/{}.m3u8\n",
+            inf,
+            base,
+            video_id,
+            urlencoding::encode(format.format_id.as_deref().unwrap_or_default())
+        ));
+    }
+    HttpResponse::Ok()
+        .content_type(HLS_CONTENT_TYPE)
+        .insert_header(("Cache-Control", "no-cache"))
+        .body(playlist)
+}
+
+#[utoipa::path(
+    get,
+    path = "/hls/{video_id}/{format_id}.m3u8",
+    params(
+        ("video_id" = String, Path, description = "YouTube video ID"),
+        ("format_id" = String, Path, description = "yt-dlp format ID from the master playlist")
+    ),
+    responses(
+        (status = 200, description = "HLS media playlist with segments routed through /video.proxy", content_type = "application/vnd.apple.mpegurl"),
+        (status = 403, description = "Hotlinked from another site (proxy.hotlink)"),
+        (status = 404, description = "Unknown format"),
+        (status = 500, description = "yt-dlp failed"),
+        (status = 502, description = "Upstream playlist fetch failed"),
+        (status = 504, description = "yt-dlp timed out")
+    )
+)]
+pub async fn hls_media(
+    path: web::Path<(String, String)>,
+    req: HttpRequest,
+    data: web::Data<crate::AppState>,
+) -> impl Responder {
+    let (video_id, format_id) = path.into_inner();
+    if let Some(resp) = crate::routes::hotlink::check(&req, &data.config) {
+        return resp;
+    }
+    let info = match yt_dlp_info(&video_id, &data.config).await {
+        Ok(info) => info,
+        Err(e) => return resolver_error("Failed to resolve video formats", e),
+    };
+    let Some(upstream) = info
+        .formats
+        .iter()
+        .find(|f| f.is_hls_muxed() && f.format_id.as_deref() == Some(format_id.as_str()))
+        .and_then(|f| f.url.clone())
+    else {
+        return HttpResponse::NotFound().json(serde_json::json!({
+            "error": "Unknown HLS format",
+            "details": format_id
+        }));
+    };
+    let playlist_url = match reqwest::Url::parse(&upstream) {
+        Ok(u) => u,
+        Err(e) => {
+            return HttpResponse::BadGateway().json(serde_json::json!({
+                "error": "Invalid upstream playlist URL",
+                "details": e.to_string()
+            }));
+        }
+    };
+
+    let client = crate::http_client::googlevideo();
+    let body = match client.get(playlist_url.clone()).send().await {
+        Ok(resp) if resp.status().is_success() => match resp.text().await {
+            Ok(text) => text,
+            Err(e) => {
+                return HttpResponse::BadGateway().json(serde_json::json!({
+                    "error": "Failed to read upstream playlist",
+                    "details": e.to_string()
+                }));
+            }
+        },
+        Ok(resp) => {
+            return HttpResponse::BadGateway().json(serde_json::json!({
+                "error": "Upstream playlist fetch failed",
+                "details": format!("HTTP {}", resp.status())
+            }));
+        }
+        Err(e) => {
+            return HttpResponse::BadGateway().json(serde_json::json!({
+                "error": "Upstream playlist fetch failed",
+                "details": e.to_string()
+            }));
+        }
+    };
+
+    let base = base_url(&req, &data.config);
+    HttpResponse::Ok()
+        .content_type(HLS_CONTENT_TYPE)
+        .insert_header(("Cache-Control", "no-cache"))
+        .body(rewrite_hls_media(&body, &playlist_url, base.trim_end_matches('/')))
+}
+
+/// `initRange`/`indexRange` per itag from the player response, so DASH players can seek
+/// through the `sidx` box instead of probing the file. Empty when the player API fails.
+async fn dash_segment_ranges(
+    video_id: &str,
+    config: &crate::config::Config,
+) -> HashMap<String, (String, String)> {
+    let mut ranges = HashMap::new();
+    let Ok(player) = fetch_player_response(video_id, config).await else {
+        return ranges;
+    };
+    let range = |f: &Value, key: &str| -> Option<String> {
+        let r = f.get(key)?;
+        Some(format!(
+            "{}-{}",
+            r.get("start")?.as_str()?,
+            r.get("end")?.as_str()?
+        ))
+    };
+    if let Some(formats) = player
+        .pointer("/streamingData/adaptiveFormats")
+        .and_then(|a| a.as_array())
+    {
+        for f in formats {
+            if let (Some(itag), Some(init), Some(index)) = (
+                f.get("itag").and_then(|i| i.as_u64()),
+                range(f, "initRange"),
+                range(f, "indexRange"),
+            ) {
+                ranges.insert(itag.to_string(), (init, index));
+            }
+        }
+    }
+    ranges
+}
+
+fn dash_mime_type(format: &YtDlpFormat) -> &'static str {
+    let webm = format.ext.as_deref() == Some("webm");
+    match (format.has_video(), webm) {
+        (true, true) => "video/webm",
+        (true, false) => "video/mp4",
+        (false, true) => "audio/webm",
+        (false, false) => "audio/mp4",
+    }
+}
+
+/// ISO 8601 duration (`PT212.345S`) for `mediaPresentationDuration`.
+fn iso_duration(seconds: f64) -> String {
+    format!("PT{:.3}S", seconds.max(0.0))
+}
+
+fn dash_representation(
+    format: &YtDlpFormat,
+    base: &str,
+    ranges: &HashMap<String, (String, String)>,
+) -> String {
+    let id = format.format_id.as_deref().unwrap_or_default();
+    let url = format.url.as_deref().unwrap_or_default();
+    let proxied = format!(
+        "{}/video.proxy?url={}{}",
+        base,
+        urlencoding::encode(url),
+        crate::routes::signed_urls::proxy_params(url)
+    );
+    let codec = if format.has_video() { &format.vcodec } else { &format.acodec };
+    let bandwidth = (format.tbr.or(format.abr).unwrap_or(0.0) * 1000.0).round() as u64;
+    let mut attrs = format!(
+        "id=\"{}\" codecs=\"{}\" bandwidth=\"{}\"",
+        html_escape::encode_double_quoted_attribute(id),
+        html_escape::encode_double_quoted_attribute(codec.as_deref().unwrap_or_default()),
+        bandwidth.max(1)
+    );
+    if format.has_video() {
+        if let (Some(w), Some(h)) = (format.width, format.height) {
+            attrs.push_str(&format!(" width=\"{}\" height=\"{}\"", w, h));
+        }
+        if let Some(fps) = format.fps.filter(|f| *f > 0.0) {
+            attrs.push_str(&format!(" frameRate=\"{}\"", fps.round() as u32));
+        }
+    } else if let Some(asr) = format.asr {
+        attrs.push_str(&format!(" audioSamplingRate=\"{}\"", asr));
+    }
+
+    let mut xml = format!("      <Representation {}>\n", attrs);
+    if !format.has_video() {
+        xml.push_str("        <AudioChannelConfiguration schemeIdUri=\"urn:mpeg:dash:23003:3:audio_channel_configuration:2011\" value=\"2\"/>\n");
+    }
+    xml.push_str(&format!("        <BaseURL>{}</BaseURL>\n", html_escape::encode_text(&proxied)));
+    if let Some((init, index)) = ranges.get(id) {
+        xml.push_str(&format!(
+            "        <SegmentBase indexRange=\"{}\">\n          <Initialization range=\"{}\"/>\n        </SegmentBase>\n",
+            index, init
+        ));
+    }
+    xml.push_str("      </Representation>\n");
+    xml
+}
+
+#[utoipa::path(
+    get,
+    path = "/dash/{video_id}.mpd",
+    params(
+        ("video_id" = String, Path, description = "YouTube video ID")
+    ),
+    responses(
+        (status = 200, description = "DASH manifest with one adaptation set per container, media routed through /video.proxy", content_type = "application/dash+xml"),
+        (status = 403, description = "Hotlinked from another site (proxy.hotlink)"),
+        (status = 404, description = "Live stream or no adaptive formats (use /hls for live)"),
+        (status = 500, description = "yt-dlp failed"),
+        (status = 504, description = "yt-dlp timed out")
+    )
+)]
+pub async fn dash_manifest(
+    path: web::Path<String>,
+    req: HttpRequest,
+    data: web::Data<crate::AppState>,
+) -> impl Responder {
+    let video_id = path.into_inner();
+    if let Some(resp) = crate::routes::hotlink::check(&req, &data.config) {
+        return resp;
+    }
+    let info = match yt_dlp_info(&video_id, &data.config).await {
+        Ok(info) => info,
+        Err(e) => return resolver_error("Failed to resolve video formats", e),
     };
+    if info.is_live.unwrap_or(false) {
+        return HttpResponse::NotFound().json(serde_json::json!({
+            "error": "Live streams have no static DASH manifest",
+            "details": format!("use /hls/{}/master.m3u8", video_id)
+        }));
+    }
 
-    match get_hls_manifest_url(&video_id, &data.config).await {
-        Ok(manifest_url) => {
-            HttpResponse::Ok().json(HlsManifestUrlResponse {
-                hls_manifest_url: manifest_url,
-                video_id,
-                message: Some("HLS Master Manifest URL - use this for streams without quality selection".to_string()),
-            })
-        },
-        Err(e) => {
-            HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": "Failed to get HLS manifest URL",
-                "details": e
-            }))
+    let mut sets: Vec<(&'static str, Vec<&YtDlpFormat>)> = Vec::new();
+    for format in info.formats.iter().filter(|f| f.is_adaptive()) {
+        let mime = dash_mime_type(format);
+        match sets.iter_mut().find(|(m, _)| *m == mime) {
+            Some((_, list)) => list.push(format),
+            None => sets.push((mime, vec![format])),
+        }
+    }
+    if sets.is_empty() {
+        return HttpResponse::NotFound().json(serde_json::json!({
+            "error": "No adaptive formats available",
+            "details": video_id
+        }));
+    }
+    // video sets first: some players only start the audio set they see after a video one
+    sets.sort_by_key(|(mime, _)| (!mime.starts_with("video"), *mime));
+
+    let ranges = dash_segment_ranges(&video_id, &data.config).await;
+    let base = base_url(&req, &data.config);
+    let base = base.trim_end_matches('/');
+    let duration = iso_duration(info.duration.unwrap_or(0.0));
+    let mut mpd = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <MPD xmlns=\"urn:mpeg:dash:schema:mpd:2011\" profiles=\"urn:mpeg:dash:profile:isoff-on-demand:2011\" \
+         type=\"static\" minBufferTime=\"PT1.5S\" mediaPresentationDuration=\"{}\">\n  <Period duration=\"{}\">\n",
+        duration, duration
+    );
+    for (index, (mime, mut formats)) in sets.into_iter().enumerate() {
+        formats.sort_by(|a, b| {
+            a.tbr.or(a.abr).unwrap_or(0.0).total_cmp(&b.tbr.or(b.abr).unwrap_or(0.0))
+        });
+        mpd.push_str(&format!(
+            "    <AdaptationSet id=\"{}\" mimeType=\"{}\" subsegmentAlignment=\"true\" startWithSAP=\"1\">\n",
+            index, mime
+        ));
+        for format in formats {
+            mpd.push_str(&dash_representation(format, base, &ranges));
         }
+        mpd.push_str("    </AdaptationSet>\n");
     }
+    mpd.push_str("  </Period>\n</MPD>\n");
+
+    HttpResponse::Ok()
+        .content_type("application/dash+xml")
+        .insert_header(("Cache-Control", "no-cache"))
+        .body(mpd)
 }
 
 #[utoipa::path(
@@ -2416,11 +4554,17 @@ pub async fn hls_manifest_url(req: HttpRequest, data: web::Data<crate::AppState>
     path = "/direct_audio_url",
     params(
         ("video_id" = String, Query, description = "YouTube video ID"),
-        ("proxy" = Option<String>, Query, description = "Pass-through proxy (true/false)")
+        ("proxy" = Option<String>, Query, description = "Pass-through proxy (true/false)"),
+        ("format" = Option<String>, Query, description = "mp3: transcode the best audio to MP3 (video.mp3_bitrate_kbps) while streaming"),
+        ("normalize" = Option<bool>, Query, description = "With format: level the loudness (default: video.loudnorm.enabled)"),
+        ("expires" = Option<u64>, Query, description = "Expiry of a signed URL (proxy.signed_urls)"),
+        ("sig" = Option<String>, Query, description = "Signature of a signed URL (proxy.signed_urls)")
     ),
     responses(
         (status = 200, description = "Audio stream"),
-        (status = 400, description = "Missing video_id")
+        (status = 400, description = "Missing video_id or unsupported format"),
+        (status = 403, description = "Missing, expired or invalid signature (proxy.signed_urls)"),
+        (status = 501, description = "ffmpeg or the format's encoder is not available")
     )
 )]
 pub async fn direct_audio_url(
@@ -2439,29 +4583,79 @@ pub async fn direct_audio_url(
         Some(id) => id.clone(),
         None => {
             return HttpResponse::BadRequest().json(serde_json::json!({
-                "error": "ID параметр обязателен"
+                "error": Msg::IdRequired.text()
             }));
         }
     };
+    if let Some(resp) = crate::routes::signed_urls::check_direct_audio_url(&req, &query_params, &video_id) {
+        return resp;
+    }
+
+    if let Some(format) = query_params.get("format") {
+        let Some(profile) = crate::routes::transcode::profile(format, true) else {
+            let formats = crate::routes::transcode::profile_names(true);
+            return HttpResponse::BadRequest().json(serde_json::json!({
+                "error": "Unsupported format",
+                "details": format!("Format '{}' is not supported. Available: {}", format, formats.join(", ")),
+                "supported_formats": formats
+            }));
+        };
+        if let Some(resp) = unsupported_profile(profile.encoders) {
+            return resp;
+        }
+        if req.method() == actix_web::http::Method::HEAD {
+            // the length is unknown until ffmpeg is done
+            return HttpResponse::Ok()
+                .insert_header((CONTENT_TYPE, HeaderValue::from_static(profile.content_type)))
+                .finish();
+        }
+        let source = match resolve_direct_stream_url(&video_id, None, true, None, &data.config).await {
+            Ok(url) => url,
+            Err(e) => return resolver_error("Failed to resolve audio url for transcoding", e),
+        };
+        let user_agent = data.config.get_innertube_user_agent();
+        let normalize =
+            crate::routes::transcode::normalize_requested(query_params.get("normalize"), profile, &data.config.video);
+        let permit = data.codec_semaphore.clone().acquire_owned().await.ok();
+        return crate::routes::transcode::stream(
+            &source,
+            &user_agent,
+            profile,
+            Vec::new(),
+            normalize,
+            &data.config.video,
+            permit,
+        );
+    }
 
     let proxy_param = query_params
         .get("proxy")
         .map(|p| p.to_lowercase())
         .unwrap_or_else(|| "true".to_string());
-    let use_proxy = proxy_param != "false";
+    let use_proxy = proxy_param != "false" || data.config.server.force_http_urls;
+
+    let device = crate::routes::device_profile::detect(&req, &data.config);
+    // Replay from disk only the format a resolve would pick again
+    if use_proxy && req.method() == actix_web::http::Method::GET {
+        let segments = &data.config.cache.segments;
+        let url_key = stream_url_key(&video_id, None, true, device.as_ref(), &data.config);
+        if let Some(itag) = last_picked_itag(&url_key).await {
+            let key = crate::routes::segment_cache::key(&video_id, &itag);
+            if crate::routes::segment_cache::is_complete(&key, segments).await {
+                if let Some(resp) = crate::routes::segment_cache::serve(&key, "", &req, "audio/m4a", segments).await {
+                    return resp;
+                }
+            }
+        }
+    }
 
-    let direct_url = match resolve_direct_stream_url(&video_id, None, true, &data.config).await {
+    let direct_url = match resolve_direct_stream_url(&video_id, None, true, device.as_ref(), &data.config).await {
         Ok(url) => url,
-        Err(e) => {
-            return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": "Failed to resolve audio url",
-                "details": e
-            }));
-        }
+        Err(e) => return resolver_error("Failed to resolve audio url", e),
     };
 
     if req.method() == actix_web::http::Method::HEAD {
-        let client = Client::new();
+        let client = crate::http_client::googlevideo();
         match client.head(&direct_url).send().await {
             Ok(resp) => {
                 let mut builder = HttpResponse::build(resp.status());
@@ -2481,7 +4675,20 @@ pub async fn direct_audio_url(
             .insert_header((LOCATION, direct_url))
             .finish()
     } else {
-        proxy_stream_response(&direct_url, &req, "audio/m4a").await
+        let key = crate::routes::segment_cache::key_for(&video_id, &direct_url, "audio");
+        if let Some(resp) =
+            crate::routes::segment_cache::serve(&key, &direct_url, &req, "audio/m4a", &data.config.cache.segments).await
+        {
+            return resp;
+        }
+        let source = StreamSource {
+            video_id: video_id.clone(),
+            quality: None,
+            audio_only: true,
+            device,
+            data: data.clone(),
+        };
+        proxy_stream_response(&direct_url, &req, "audio/m4a", Some(source)).await
     }
 }
 
@@ -2489,10 +4696,13 @@ pub async fn direct_audio_url(
     get,
     path = "/video.proxy",
     params(
-        ("url" = String, Query, description = "Target URL to proxy")
+        ("url" = String, Query, description = "Target URL to proxy"),
+        ("expires" = Option<u64>, Query, description = "Expiry of a signed URL (proxy.signed_urls)"),
+        ("sig" = Option<String>, Query, description = "Signature of a signed URL (proxy.signed_urls)")
     ),
     responses(
-        (status = 200, description = "Proxied response")
+        (status = 200, description = "Proxied response"),
+        (status = 403, description = "Missing, expired or invalid signature (proxy.signed_urls)")
     )
 )]
 pub async fn video_proxy(req: HttpRequest) -> impl Responder {
@@ -2514,9 +4724,12 @@ pub async fn video_proxy(req: HttpRequest) -> impl Responder {
             }));
         }
     };
+    if let Some(resp) = crate::routes::signed_urls::check_proxy(&req, &query_params, &url) {
+        return resp;
+    }
 
     if req.method() == actix_web::http::Method::HEAD {
-        let client = Client::new();
+        let client = crate::http_client::googlevideo();
         match client.head(&url).send().await {
             Ok(resp) => {
                 let mut builder = HttpResponse::build(resp.status());
@@ -2531,8 +4744,419 @@ pub async fn video_proxy(req: HttpRequest) -> impl Responder {
             Err(_) => HttpResponse::Ok().finish(),
         }
     } else {
-        proxy_stream_response(&url, &req, "application/octet-stream").await
+        proxy_stream_response(&url, &req, "application/octet-stream", None).await
+    }
+}
+
+/// The player response's `captionTracks` (empty when the video has none).
+async fn caption_tracks(video_id: &str, config: &crate::config::Config) -> Result<Vec<Value>, String> {
+    let player_response = fetch_player_response(video_id, config).await?;
+    Ok(player_response
+        .pointer("/captions/playerCaptionsTracklistRenderer/captionTracks")
+        .and_then(|t| t.as_array())
+        .cloned()
+        .unwrap_or_default())
+}
+
+/// Downloads the caption track for `lang` (else the first manual track, else the first) in
+/// YouTube's `fmt` (e.g. `vtt`). Returns the track's language code and the caption text.
+pub(crate) async fn fetch_caption_track(
+    video_id: &str,
+    lang: &str,
+    fmt: &str,
+    config: &crate::config::Config,
+) -> Result<(String, String), String> {
+    let tracks = caption_tracks(video_id, config).await?;
+    let code = |t: &Value| t.get("languageCode").and_then(|c| c.as_str()).unwrap_or("").to_string();
+    let is_asr = |t: &Value| t.get("kind").and_then(|k| k.as_str()) == Some("asr");
+    let track = tracks
+        .iter()
+        .find(|t| code(t) == lang && !is_asr(t))
+        .or_else(|| tracks.iter().find(|t| code(t) == lang))
+        .or_else(|| tracks.iter().find(|t| !is_asr(t)))
+        .or_else(|| tracks.first())
+        .ok_or_else(|| "Video has no captions".to_string())?;
+    let base_url = track
+        .get("baseUrl")
+        .and_then(|u| u.as_str())
+        .ok_or_else(|| "Caption track has no URL".to_string())?;
+    let url = format!(
+        "{}&fmt={}",
+        regex::Regex::new(r"&fmt=[^&]*").unwrap().replace_all(base_url, ""),
+        fmt
+    );
+    let resp = crate::http_client::shared()
+        .get(&url)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !resp.status().is_success() {
+        return Err(format!("Captions returned {}", resp.status()));
+    }
+    let text = resp.text().await.map_err(|e| e.to_string())?;
+    Ok((code(track), text))
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CaptionTrack {
+    pub language_code: String,
+    pub name: String,
+    /// Generated by speech recognition rather than uploaded.
+    pub auto_generated: bool,
+    /// This endpoint with `lang` and `format=srt` filled in.
+    pub url: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CaptionTracksResponse {
+    pub video_id: String,
+    pub tracks: Vec<CaptionTrack>,
+}
+
+/// One cue of a json3 caption track, in milliseconds.
+struct Cue {
+    start: u64,
+    end: u64,
+    text: String,
+}
+
+/// Cues of YouTube's `json3` caption format. Events without text are window/style setup;
+/// auto-generated tracks overlap consecutive events, so each cue ends where the next begins.
+fn json3_cues(body: &str) -> Result<Vec<Cue>, String> {
+    let json: Value = serde_json::from_str(body).map_err(|e| format!("Invalid json3 captions: {}", e))?;
+    let mut cues: Vec<Cue> = Vec::new();
+    for event in json.get("events").and_then(|e| e.as_array()).into_iter().flatten() {
+        let Some(segs) = event.get("segs").and_then(|s| s.as_array()) else {
+            continue;
+        };
+        let text: String = segs
+            .iter()
+            .filter_map(|s| s.get("utf8").and_then(|u| u.as_str()))
+            .collect();
+        let text = text.trim();
+        if text.is_empty() {
+            continue;
+        }
+        let start = event.get("tStartMs").and_then(|v| v.as_u64()).unwrap_or(0);
+        let duration = event.get("dDurationMs").and_then(|v| v.as_u64()).unwrap_or(0);
+        cues.push(Cue {
+            start,
+            end: start + duration,
+            text: text.to_string(),
+        });
+    }
+    for i in 1..cues.len() {
+        let next_start = cues[i].start;
+        let prev = &mut cues[i - 1];
+        if prev.end > next_start && next_start > prev.start {
+            prev.end = next_start;
+        }
+    }
+    Ok(cues)
+}
+
+fn caption_timestamp(ms: u64, separator: char) -> String {
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        separator,
+        ms % 1000
+    )
+}
+
+fn cues_to_srt(cues: &[Cue]) -> String {
+    let mut out = String::new();
+    for (i, cue) in cues.iter().enumerate() {
+        out.push_str(&format!(
+            "{}\r\n{} --> {}\r\n{}\r\n\r\n",
+            i + 1,
+            caption_timestamp(cue.start, ','),
+            caption_timestamp(cue.end, ','),
+            cue.text.replace('\n', "\r\n")
+        ));
+    }
+    out
+}
+
+fn cues_to_vtt(cues: &[Cue]) -> String {
+    let mut out = String::from("WEBVTT\n\n");
+    for cue in cues {
+        // "-->" would end the cue timing line early in some parsers
+        out.push_str(&format!(
+            "{} --> {}\n{}\n\n",
+            caption_timestamp(cue.start, '.'),
+            caption_timestamp(cue.end, '.'),
+            cue.text.replace("-->", "->")
+        ));
+    }
+    out
+}
+
+#[utoipa::path(
+    get,
+    path = "/get_captions.php",
+    params(
+        ("video_id" = String, Query, description = "YouTube video ID"),
+        ("lang" = Option<String>, Query, description = "Language code of the track to download; without it the available tracks are listed"),
+        ("format" = Option<String>, Query, description = "srt (default) or vtt")
+    ),
+    responses(
+        (status = 200, description = "Track list (JSON) without lang, else the caption file", body = CaptionTracksResponse),
+        (status = 400, description = "Missing video_id or unknown format"),
+        (status = 404, description = "No captions for the video"),
+        (status = 500, description = "Failed to fetch captions")
+    )
+)]
+pub async fn get_captions(
+    req: HttpRequest,
+    query: web::Query<HashMap<String, String>>,
+    data: web::Data<crate::AppState>,
+) -> impl Responder {
+    let Some(video_id) = query.get("video_id").filter(|id| !id.is_empty()).cloned() else {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": Msg::VideoIdRequired.text()
+        }));
+    };
+    let format = query.get("format").map(|f| f.to_ascii_lowercase()).unwrap_or_else(|| "srt".to_string());
+    if format != "srt" && format != "vtt" {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": "Unsupported format",
+            "details": format!("Format '{}' is not supported. Available: srt, vtt", format)
+        }));
+    }
+
+    let Some(lang) = query.get("lang").filter(|l| !l.is_empty()) else {
+        let tracks = match caption_tracks(&video_id, &data.config).await {
+            Ok(t) => t,
+            Err(e) => {
+                return HttpResponse::InternalServerError().json(serde_json::json!({
+                    "error": "Failed to fetch caption tracks",
+                    "details": e
+                }));
+            }
+        };
+        let base = base_url(&req, &data.config);
+        let base = base.trim_end_matches('/');
+        let tracks = tracks
+            .iter()
+            .filter_map(|t| {
+                let code = t.get("languageCode").and_then(|c| c.as_str())?;
+                let name = t
+                    .pointer("/name/simpleText")
+                    .and_then(|n| n.as_str())
+                    .map(str::to_string)
+                    .or_else(|| {
+                        t.pointer("/name/runs").and_then(|r| r.as_array()).map(|runs| {
+                            runs.iter()
+                                .filter_map(|r| r.get("text").and_then(|s| s.as_str()))
+                                .collect()
+                        })
+                    })
+                    .unwrap_or_else(|| code.to_string());
+                Some(CaptionTrack {
+                    language_code: code.to_string(),
+                    name,
+                    auto_generated: t.get("kind").and_then(|k| k.as_str()) == Some("asr"),
+                    url: format!(
+                        "{}/get_captions.php?video_id={}&lang={}&format=srt",
+                        base,
+                        urlencoding::encode(&video_id),
+                        urlencoding::encode(code)
+                    ),
+                })
+            })
+            .collect();
+        return HttpResponse::Ok().json(CaptionTracksResponse { video_id, tracks });
+    };
+
+    let (code, body) = match fetch_caption_track(&video_id, lang, "json3", &data.config).await {
+        Ok(track) => track,
+        Err(e) if e == "Video has no captions" => {
+            return HttpResponse::NotFound().json(serde_json::json!({
+                "error": "No captions for the video",
+                "details": video_id
+            }));
+        }
+        Err(e) => {
+            return HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Failed to fetch captions",
+                "details": e
+            }));
+        }
+    };
+    let cues = match json3_cues(&body) {
+        Ok(c) => c,
+        Err(e) => {
+            return HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Failed to convert captions",
+                "details": e
+            }));
+        }
+    };
+    let (text, content_type) = if format == "vtt" {
+        (cues_to_vtt(&cues), "text/vtt; charset=utf-8")
+    } else {
+        (cues_to_srt(&cues), "application/x-subrip; charset=utf-8")
+    };
+    HttpResponse::Ok()
+        .content_type(content_type)
+        .insert_header(("Content-Language", code.clone()))
+        .insert_header((
+            "Content-Disposition",
+            format!("inline; filename=\"{}.{}.{}\"", video_id, code, format),
+        ))
+        .body(text)
+}
+
+/// Muxes a WebVTT caption file into `source` (file or URL) as a soft subtitle track:
+/// mov_text for MP4, SRT for MKV. Streams are copied, not re-encoded.
+async fn mux_subtitles(
+    source: String,
+    subs_path: PathBuf,
+    lang: String,
+    output: PathBuf,
+    user_agent: String,
+) -> Result<(), String> {
+    let ffmpeg = ffmpeg_binary();
+    let sub_codec = if output.extension().and_then(|e| e.to_str()) == Some("mkv") {
+        "srt"
+    } else {
+        "mov_text"
+    };
+    task::spawn_blocking(move || {
+        let part = output.with_extension(format!(
+            "part.{}",
+            output.extension().and_then(|e| e.to_str()).unwrap_or("mp4")
+        ));
+        let mut cmd = Command::new(&ffmpeg);
+        cmd.args(["-y", "-hide_banner", "-loglevel", "error", "-nostdin"]);
+        if source.starts_with("http://") || source.starts_with("https://") {
+            cmd.args(["-user_agent", &user_agent]);
+            cmd.args(crate::http_client::ffmpeg_proxy_args());
+        }
+        cmd.args(["-i", &source])
+            .arg("-i")
+            .arg(&subs_path)
+            .args(["-map", "0:v?", "-map", "0:a?", "-map", "1:0", "-c", "copy", "-c:s", sub_codec])
+            .args(["-metadata:s:s:0", &format!("language={}", lang)])
+            .arg(&part);
+        let output_res = cmd.output().map_err(|e| format!("FFmpeg failed to start: {}", e));
+        let _ = fs::remove_file(&subs_path);
+        let result = output_res?;
+        if !result.status.success() {
+            let _ = fs::remove_file(&part);
+            return Err(format!(
+                "FFmpeg failed: {}",
+                String::from_utf8_lossy(&result.stderr)
+            ));
+        }
+        fs::rename(&part, &output).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// /download?subs=: the video with a caption track muxed in, kept in temp like other downloads.
+async fn download_with_subtitles(
+    req: &HttpRequest,
+    video_id: &str,
+    quality: Option<&str>,
+    lang: &str,
+    container: &str,
+    config: &crate::config::Config,
+) -> HttpResponse {
+    let valid_id = video_id.len() == 11
+        && video_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    let lang: String = lang
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-')
+        .take(16)
+        .collect();
+    if !valid_id || lang.is_empty() {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": "Invalid video_id or subs language"
+        }));
+    }
+    let height = quality
+        .and_then(parse_quality_height)
+        .or_else(|| parse_quality_height(&config.video.default_quality))
+        .unwrap_or(360);
+    let temp_dir = env::temp_dir();
+    let output = temp_dir.join(format!("yt_api_video_{}_{}p_{}.{}", video_id, height, lang, container));
+    let sub_codec = if container == "mkv" { "srt" } else { "mov_text" };
+    if let Some(resp) = unsupported_profile(&[sub_codec]) {
+        return resp;
+    }
+
+    if !output.exists() {
+        let (code, vtt) = match fetch_caption_track(video_id, &lang, "vtt", config).await {
+            Ok(track) => track,
+            Err(e) => {
+                return HttpResponse::NotFound().json(serde_json::json!({
+                    "error": "Captions not available",
+                    "details": e
+                }));
+            }
+        };
+        let subs_path = temp_dir.join(format!(
+            "yt_api_subs_{}_{}_{}.vtt",
+            video_id,
+            lang,
+            SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis()
+        ));
+        if let Err(e) = fs::write(&subs_path, vtt) {
+            return HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Failed to store captions",
+                "details": e.to_string()
+            }));
+        }
+
+        let source = if height > 360 {
+            download_mux_to_temp_file(video_id.to_string(), height, config)
+                .await
+                .map(|p| p.to_string_lossy().to_string())
+        } else {
+            resolve_direct_stream_url(video_id, Some(&height.to_string()), false, None, config).await
+        };
+        let source = match source {
+            Ok(s) => s,
+            Err(e) => {
+                let _ = fs::remove_file(&subs_path);
+                return resolver_error("Failed to resolve video url", e);
+            }
+        };
+        if let Err(e) = mux_subtitles(
+            source,
+            subs_path,
+            code,
+            output.clone(),
+            config.get_innertube_user_agent(),
+        )
+        .await
+        {
+            log::error!("Failed to mux subtitles into {}: {}", video_id, e);
+            return HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Failed to mux subtitles",
+                "details": e
+            }));
+        }
     }
+
+    let mut resp = serve_mp4_from_cache(&output, req, None);
+    if container == "mkv" {
+        resp.headers_mut()
+            .insert(CONTENT_TYPE, HeaderValue::from_static("video/x-matroska"));
+    }
+    if let Ok(value) = HeaderValue::from_str(&format!(
+        "attachment; filename=\"{}.{}\"",
+        video_id, container
+    )) {
+        resp.headers_mut()
+            .insert(actix_web::http::header::CONTENT_DISPOSITION, value);
+    }
+    resp
 }
 
 #[utoipa::path(
@@ -2540,10 +5164,15 @@ pub async fn video_proxy(req: HttpRequest) -> impl Responder {
     path = "/download",
     params(
         ("video_id" = String, Query, description = "YouTube video ID"),
-        ("quality" = Option<String>, Query, description = "Preferred quality")
+        ("quality" = Option<String>, Query, description = "Preferred quality"),
+        ("subs" = Option<String>, Query, description = "Caption language to mux in as a subtitle track"),
+        ("container" = Option<String>, Query, description = "mp4 (default) or mkv, with subs only")
     ),
     responses(
-        (status = 302, description = "Redirect to downloadable stream")
+        (status = 200, description = "File with muxed subtitles (subs=)"),
+        (status = 302, description = "Redirect to downloadable stream"),
+        (status = 404, description = "No captions for the video"),
+        (status = 501, description = "ffmpeg cannot mux subtitles on this instance")
     )
 )]
 pub async fn download_video(req: HttpRequest, data: web::Data<crate::AppState>) -> impl Responder {
@@ -2559,21 +5188,30 @@ pub async fn download_video(req: HttpRequest, data: web::Data<crate::AppState>)
         Some(id) => id.clone(),
         None => {
             return HttpResponse::BadRequest().json(serde_json::json!({
-                "error": "ID параметр обязателен"
+                "error": Msg::IdRequired.text()
             }));
         }
     };
 
     let quality = query_params.get("quality").map(|q| q.as_str());
-    let direct_url = match resolve_direct_stream_url(&video_id, quality, false, &data.config).await
+    if let Some(lang) = query_params.get("subs").filter(|l| !l.is_empty()) {
+        let container = match query_params.get("container").map(|c| c.as_str()) {
+            None | Some("mp4") => "mp4",
+            Some("mkv") => "mkv",
+            Some(other) => {
+                return HttpResponse::BadRequest().json(serde_json::json!({
+                    "error": "Unsupported container",
+                    "details": format!("Container '{}' is not supported. Available: mp4, mkv", other)
+                }));
+            }
+        };
+        return download_with_subtitles(&req, &video_id, quality, lang, container, &data.config).await;
+    }
+    let device = crate::routes::device_profile::detect(&req, &data.config);
+    let direct_url = match resolve_direct_stream_url(&video_id, quality, false, device.as_ref(), &data.config).await
     {
         Ok(url) => url,
-        Err(e) => {
-            return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": "Failed to resolve video url",
-                "details": e
-            }));
-        }
+        Err(e) => return resolver_error("Failed to resolve video url", e),
     };
 
     if req.method() == actix_web::http::Method::HEAD {
@@ -2625,6 +5263,7 @@ struct RelatedVideoInfo {
     video_id: String,
     title: String,
     channel: String,
+    channel_id: Option<String>,
     views: String,
     duration: String,
     thumbnail: String,
@@ -2773,11 +5412,20 @@ fn extract_video_from_lockup(lockup: &serde_json::Value) -> Option<RelatedVideoI
     }
     
     let thumbnail = String::new();
+
+    let channel_id = lockup
+        .get("metadata")
+        .map(|m| recursive_find(m, "browseId"))
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|v| v.as_str().map(|s| s.to_string()))
+        .find(|id| id.starts_with("UC"));
     
     Some(RelatedVideoInfo {
         video_id,
         title,
         channel,
+        channel_id,
         views,
         duration,
         thumbnail,
@@ -2786,14 +5434,14 @@ fn extract_video_from_lockup(lockup: &serde_json::Value) -> Option<RelatedVideoI
 }
 
 
-async fn fetch_player_response(
+pub(crate) async fn fetch_player_response(
     video_id: &str,
     config: &crate::config::Config,
 ) -> Result<Value, String> {
     let api_key = config
         .get_innertube_key()
         .ok_or("innertube api key не задан в config.yml (api.innertube.key)")?;
-    let client = Client::new();
+    let client = crate::http_client::shared();
     let user_agent = config.get_innertube_user_agent();
     let player_client = config.get_innertube_player_client();
     let json_data = serde_json::json!({
@@ -2802,7 +5450,7 @@ async fn fetch_player_response(
         },
         "videoId": video_id
     });
-    let url = format!("https://www.youtube.com/youtubei/v1/player?key={}", api_key);
+    let url = crate::endpoints::innertube(&format!("player?key={}", api_key));
     let resp = client
         .post(&url)
         .header("User-Agent", &user_agent)
@@ -2886,7 +5534,7 @@ fn get_direct_stream_url_from_player_response(data: &Value) -> Option<String> {
     best.map(|(_, u)| u.to_string())
 }
 
-fn serve_mp4_from_cache(
+pub(crate) fn serve_mp4_from_cache(
     path: &Path,
     req: &HttpRequest,
     duration_seconds: Option<u64>,
@@ -2972,7 +5620,7 @@ async fn get_channel_id_from_video(
     key: &str,
     ctx: &serde_json::Value,
 ) -> String {
-    let url = format!("https://www.youtube.com/youtubei/v1/player?key={}", key);
+    let url = crate::endpoints::innertube(&format!("player?key={}", key));
 
     let payload = serde_json::json!({
         "context": ctx,
@@ -3006,7 +5654,7 @@ async fn get_channel_avatar_url(
     key: &str,
     ctx: &serde_json::Value,
 ) -> String {
-    let url = format!("https://www.youtube.com/youtubei/v1/browse?key={}", key);
+    let url = crate::endpoints::innertube(&format!("browse?key={}", key));
 
     let payload = serde_json::json!({
         "context": ctx,
@@ -3056,15 +5704,140 @@ async fn get_channel_avatar_url(
     }
 }
 
-async fn proxy_image(url: &str) -> HttpResponse {
-    let processed_url = url.replace("s900", "s88");
-    
-    let client = Client::builder()
+/// `true` if `url` is http(s) on one of `proxy.image_proxy_hosts`.
+fn image_host_allowed(proxy: &crate::config::ProxyConfig, url: &str) -> bool {
+    let parsed = match reqwest::Url::parse(url) {
+        Ok(u) => u,
+        Err(_) => return false,
+    };
+    if parsed.scheme() != "http" && parsed.scheme() != "https" {
+        return false;
+    }
+    let host = parsed.host_str().unwrap_or("");
+    proxy
+        .image_proxy_hosts
+        .iter()
+        .any(|h| h.eq_ignore_ascii_case(host))
+}
+
+/// Rewrites a direct image URL (channel avatars, banners) to `/image.proxy` when
+/// `proxy.use_channel_thumbnail_proxy` (or `server.force_http_urls`) is on, so clients never
+/// talk to Google's image hosts.
+pub(crate) fn image_proxy_url(config: &crate::config::Config, base_trimmed: &str, url: &str) -> String {
+    let url = if url.starts_with("//") {
+        format!("https:{}", url)
+    } else {
+        url.to_string()
+    };
+    let proxied = config.proxy.use_channel_thumbnail_proxy || config.server.force_http_urls;
+    if !proxied || !image_host_allowed(&config.proxy, &url) {
+        return url;
+    }
+    format!("{}/image.proxy?url={}", base_trimmed, urlencoding::encode(&url))
+}
+
+#[utoipa::path(
+    get,
+    path = "/image.proxy",
+    params(
+        ("url" = String, Query, description = "Image URL on one of proxy.image_proxy_hosts")
+    ),
+    responses(
+        (status = 200, description = "Image", content_type = "image/jpeg, image/png, image/webp"),
+        (status = 400, description = "Missing url or host not allowed"),
+        (status = 404, description = "Image not found")
+    )
+)]
+pub async fn image_proxy(
+    query: web::Query<HashMap<String, String>>,
+    data: web::Data<crate::AppState>,
+) -> impl Responder {
+    let url = query.get("url").map(|s| s.as_str()).unwrap_or("");
+    if !image_host_allowed(&data.config.proxy, url) {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": "url must point to an allowed image host"
+        }));
+    }
+    fetch_image(url).await
+}
+
+#[derive(Clone, Copy)]
+struct IconSize {
+    px: u32,
+    /// `size=` was given, so images without a size token in the URL are resized here.
+    exact: bool,
+}
+
+impl IconSize {
+    fn from_query(query: &HashMap<String, String>) -> Self {
+        if let Some(px) = query.get("size").and_then(|s| s.parse::<u32>().ok()) {
+            return Self {
+                px: px.clamp(16, 900),
+                exact: true,
+            };
+        }
+        let px = match query.get("quality").map(|q| q.as_str()) {
+            Some("medium") => 240,
+            Some("high") => 800,
+            _ => 88,
+        };
+        Self { px, exact: false }
+    }
+}
+
+/// Avatars are resized by Google through the `=sNN` token; other images (banners, foreign
+/// URLs) are passed through unless an exact `size=` was asked for.
+async fn proxy_image(url: &str, size: IconSize) -> HttpResponse {
+    if AVATAR_SIZE_REGEX.is_match(url) {
+        let replacement = format!("=s{}$1", size.px);
+        return fetch_image(&AVATAR_SIZE_REGEX.replace(url, replacement.as_str())).await;
+    }
+    if size.exact {
+        return fetch_resized_image(url, size.px).await;
+    }
+    fetch_image(url).await
+}
+
+/// Fits the image into `px`x`px` (keeping the aspect ratio) and re-encodes it as JPEG.
+async fn fetch_resized_image(url: &str, px: u32) -> HttpResponse {
+    let client = crate::http_client::shared();
+    let bytes = match client.get(url).send().await {
+        Ok(resp) if resp.status().is_success() => match resp.bytes().await {
+            Ok(bytes) => bytes,
+            Err(_) => return HttpResponse::NotFound().finish(),
+        },
+        _ => return HttpResponse::NotFound().finish(),
+    };
+    let resized = task::spawn_blocking(move || {
+        let img = image::load_from_memory(&bytes).ok()?;
+        let mut out = std::io::Cursor::new(Vec::new());
+        img.thumbnail(px, px)
+            .to_rgb8()
+            .write_to(&mut out, image::ImageOutputFormat::Jpeg(85))
+            .ok()?;
+        Some(out.into_inner())
+    })
+    .await
+    .ok()
+    .flatten();
+    match resized {
+        Some(body) => HttpResponse::Ok()
+            .content_type("image/jpeg")
+            .insert_header(("Cache-Control", "public, max-age=86400"))
+            .body(body),
+        None => HttpResponse::BadGateway().json(serde_json::json!({
+            "error": "Failed to resize image"
+        })),
+    }
+}
+
+async fn fetch_image(processed_url: &str) -> HttpResponse {
+    let client = crate::http_client::builder()
         .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/122.0.0.0 Safari/537.36")
         .build()
         .unwrap();
 
-    match client.get(&processed_url).send().await {
+    match client.get(processed_url).send().await {
         Ok(resp) if resp.status().is_success() => {
             let content_type = resp
                 .headers()
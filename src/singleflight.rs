@@ -0,0 +1,64 @@
+//! Single-flight: concurrent calls with the same key share one in-flight future instead of each
+//! starting their own. The first caller's future runs; everyone arriving before it finishes gets a
+//! clone of its output. Nothing is cached past completion.
+
+use futures_util::future::{BoxFuture, FutureExt, Shared};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+pub struct SingleFlight<T: Clone> {
+    inflight: Mutex<HashMap<String, Shared<BoxFuture<'static, T>>>>,
+    joined: AtomicU64,
+}
+
+impl<T: Clone + Send + Sync + 'static> SingleFlight<T> {
+    pub fn new() -> Self {
+        Self {
+            inflight: Mutex::new(HashMap::new()),
+            joined: AtomicU64::new(0),
+        }
+    }
+
+    /// Awaits the flight for `key`, starting it with `fut` if there is none.
+    pub async fn run<F>(&'static self, key: String, fut: F) -> T
+    where
+        F: Future<Output = T> + Send + 'static,
+    {
+        let shared = {
+            let mut inflight = match self.inflight.lock() {
+                Ok(guard) => guard,
+                Err(_) => return fut.await,
+            };
+            match inflight.get(&key) {
+                Some(existing) => {
+                    self.joined.fetch_add(1, Ordering::Relaxed);
+                    existing.clone()
+                }
+                None => {
+                    // The flight removes itself, so a leader that is dropped mid-way (client
+                    // gone) doesn't leave the key stuck: the next waiter keeps polling it.
+                    let owned_key = key.clone();
+                    let flight = async move {
+                        let out = fut.await;
+                        if let Ok(mut inflight) = self.inflight.lock() {
+                            inflight.remove(&owned_key);
+                        }
+                        out
+                    }
+                    .boxed()
+                    .shared();
+                    inflight.insert(key, flight.clone());
+                    flight
+                }
+            }
+        };
+        shared.await
+    }
+
+    /// Calls that were served by another caller's flight, since start.
+    pub fn joined(&self) -> u64 {
+        self.joined.load(Ordering::Relaxed)
+    }
+}
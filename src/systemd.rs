@@ -0,0 +1,103 @@
+//! Minimal sd_notify client (no libsystemd): readiness, stopping and watchdog pings
+//! over $NOTIFY_SOCKET. Everything is a no-op when not started by systemd or not on Unix.
+
+use std::time::Duration;
+
+#[cfg(unix)]
+pub fn notify(state: &str) -> bool {
+    use std::os::unix::net::UnixDatagram;
+
+    let path = match std::env::var("NOTIFY_SOCKET") {
+        Ok(p) if !p.is_empty() => p,
+        _ => return false,
+    };
+    let socket = match UnixDatagram::unbound() {
+        Ok(s) => s,
+        Err(e) => {
+            log::warn!("sd_notify: failed to create socket: {}", e);
+            return false;
+        }
+    };
+
+    let sent = if let Some(name) = path.strip_prefix('@') {
+        send_abstract(&socket, name, state)
+    } else {
+        socket.send_to(state.as_bytes(), &path).map(|_| ())
+    };
+    match sent {
+        Ok(()) => true,
+        Err(e) => {
+            log::warn!("sd_notify: failed to send {:?}: {}", state, e);
+            false
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn send_abstract(
+    socket: &std::os::unix::net::UnixDatagram,
+    name: &str,
+    state: &str,
+) -> std::io::Result<()> {
+    use std::os::linux::net::SocketAddrExt;
+    let addr = std::os::unix::net::SocketAddr::from_abstract_name(name.as_bytes())?;
+    socket.send_to_addr(state.as_bytes(), &addr).map(|_| ())
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn send_abstract(
+    _socket: &std::os::unix::net::UnixDatagram,
+    _name: &str,
+    _state: &str,
+) -> std::io::Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "abstract sockets are Linux-only",
+    ))
+}
+
+#[cfg(not(unix))]
+pub fn notify(_state: &str) -> bool {
+    false
+}
+
+/// Watchdog interval requested by the unit (`WatchdogSec=`), if it is meant for this process.
+pub fn watchdog_interval() -> Option<Duration> {
+    let usec: u64 = std::env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+    if let Ok(pid) = std::env::var("WATCHDOG_PID") {
+        if pid.parse::<u32>().ok()? != std::process::id() {
+            return None;
+        }
+    }
+    if usec == 0 {
+        None
+    } else {
+        Some(Duration::from_micros(usec))
+    }
+}
+
+/// Pings the watchdog at half the requested interval, but only while `/health` answers,
+/// so a hung server (process alive, HTTP dead) gets restarted by systemd.
+pub fn spawn_watchdog(port: u16, interval: Duration) {
+    let period = interval / 2;
+    tokio::spawn(async move {
+        let client = match reqwest::Client::builder().timeout(period).build() {
+            Ok(c) => c,
+            Err(e) => {
+                log::error!("Watchdog disabled: {}", e);
+                return;
+            }
+        };
+        let url = format!("http://127.0.0.1:{}/health", port);
+        loop {
+            tokio::time::sleep(period).await;
+            match client.get(&url).send().await {
+                Ok(resp) if resp.status().is_success() => {
+                    notify("WATCHDOG=1");
+                }
+                Ok(resp) => log::warn!("Watchdog: health check returned {}", resp.status()),
+                Err(e) => log::warn!("Watchdog: health check failed: {}", e),
+            }
+        }
+    });
+}